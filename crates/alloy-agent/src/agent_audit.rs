@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::sync::Notify;
+
+// Events recorded while the control tunnel is down (e.g. orphan cleanup at
+// boot) wait here; past this the oldest are dropped.
+const MAX_PENDING: usize = 1024;
+
+/// A destructive or lifecycle action the agent took on its own behalf,
+/// mirrored into the control plane's `audit_events`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct AgentAuditEvent {
+    pub action: &'static str,
+    pub process_id: String,
+    pub template_id: String,
+    pub detail: String,
+    pub at_unix_ms: u64,
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Queue of audit events waiting for the control tunnel to forward them.
+#[derive(Debug, Clone, Default)]
+pub struct AgentAudit {
+    pending: Arc<Mutex<VecDeque<AgentAuditEvent>>>,
+    notify: Arc<Notify>,
+}
+
+impl AgentAudit {
+    pub fn record(
+        &self,
+        action: &'static str,
+        process_id: &str,
+        template_id: &str,
+        detail: impl Into<String>,
+    ) {
+        let event = AgentAuditEvent {
+            action,
+            process_id: process_id.to_string(),
+            template_id: template_id.to_string(),
+            detail: detail.into(),
+            at_unix_ms: now_unix_ms(),
+        };
+        tracing::info!(
+            action,
+            process_id,
+            template_id,
+            detail = %event.detail,
+            "agent audit"
+        );
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if pending.len() >= MAX_PENDING {
+                pending.pop_front();
+            }
+            pending.push_back(event);
+        }
+        self.notify.notify_one();
+    }
+
+    /// Takes every queued event, oldest first.
+    pub fn drain(&self) -> Vec<AgentAuditEvent> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending.drain(..).collect()
+    }
+
+    /// Puts events that could not be forwarded back at the front of the queue.
+    pub fn requeue(&self, events: Vec<AgentAuditEvent>) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        for event in events.into_iter().rev() {
+            pending.push_front(event);
+        }
+        while pending.len() > MAX_PENDING {
+            pending.pop_front();
+        }
+    }
+
+    /// Resolves once an event has been recorded since the last wait.
+    pub async fn wait(&self) {
+        self.notify.notified().await;
+    }
+}
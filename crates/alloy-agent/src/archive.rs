@@ -0,0 +1,522 @@
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use tokio::sync::mpsc;
+
+use crate::process_manager_support::env_u64;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+/// Caps applied while expanding user-provided archives (modpacks, server
+/// packs, save imports) so a zip bomb cannot fill the node's disk.
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveLimits {
+    pub max_total_bytes: u64,
+    pub max_files: u64,
+    pub max_file_bytes: u64,
+}
+
+impl ArchiveLimits {
+    /// For archives the agent produced itself (backups, instance transfers).
+    pub fn unlimited() -> Self {
+        Self {
+            max_total_bytes: u64::MAX,
+            max_files: u64::MAX,
+            max_file_bytes: u64::MAX,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        Self {
+            max_total_bytes: env_u64("ALLOY_ARCHIVE_MAX_TOTAL_BYTES")
+                .map(|v| v.clamp(1024 * 1024, 1024 * GIB))
+                .unwrap_or(16 * GIB),
+            max_files: env_u64("ALLOY_ARCHIVE_MAX_FILES")
+                .map(|v| v.clamp(1, 10_000_000))
+                .unwrap_or(200_000),
+            max_file_bytes: env_u64("ALLOY_ARCHIVE_MAX_FILE_BYTES")
+                .map(|v| v.clamp(1024 * 1024, 1024 * GIB))
+                .unwrap_or(8 * GIB),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ArchiveLimitError(String);
+
+impl std::fmt::Display for ArchiveLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ArchiveLimitError {}
+
+/// If `err` came from an exceeded archive limit, re-encode it as `install_failed`.
+pub fn as_install_failed(err: &anyhow::Error) -> Option<anyhow::Error> {
+    let limit = err.downcast_ref::<ArchiveLimitError>()?;
+    Some(crate::error_payload::anyhow(
+        "install_failed",
+        limit.to_string(),
+        None,
+        Some(
+            "The archive expands beyond this node's limits (ALLOY_ARCHIVE_MAX_*); \
+             use a smaller pack or ask the operator to raise them."
+                .to_string(),
+        ),
+    ))
+}
+
+/// Running totals for a single archive extraction.
+#[derive(Debug)]
+pub struct ExtractBudget {
+    limits: ArchiveLimits,
+    total_bytes: u64,
+    files: u64,
+}
+
+impl ExtractBudget {
+    pub fn new(limits: ArchiveLimits) -> Self {
+        Self {
+            limits,
+            total_bytes: 0,
+            files: 0,
+        }
+    }
+
+    /// Counts a file entry and rejects it up front when its declared size
+    /// already exceeds a cap.
+    pub fn admit(&mut self, name: &str, declared_size: u64) -> Result<(), ArchiveLimitError> {
+        self.files += 1;
+        if self.files > self.limits.max_files {
+            return Err(ArchiveLimitError(format!(
+                "archive has more than {} files",
+                self.limits.max_files
+            )));
+        }
+        self.check(
+            name,
+            declared_size,
+            self.total_bytes.saturating_add(declared_size),
+        )
+    }
+
+    /// Copies one entry, enforcing the caps on the bytes actually produced
+    /// (declared sizes in the archive cannot be trusted).
+    pub fn copy(
+        &mut self,
+        name: &str,
+        reader: &mut impl Read,
+        writer: &mut impl Write,
+    ) -> anyhow::Result<u64> {
+        let file_room = self.limits.max_file_bytes;
+        let total_room = self.limits.max_total_bytes.saturating_sub(self.total_bytes);
+        let cap = file_room.min(total_room);
+        let written = std::io::copy(&mut reader.take(cap.saturating_add(1)), writer)?;
+        self.check(name, written, self.total_bytes.saturating_add(written))?;
+        self.total_bytes += written;
+        Ok(written)
+    }
+
+    fn check(
+        &self,
+        name: &str,
+        file_bytes: u64,
+        total_bytes: u64,
+    ) -> Result<(), ArchiveLimitError> {
+        if file_bytes > self.limits.max_file_bytes {
+            return Err(ArchiveLimitError(format!(
+                "archive entry {name:?} expands beyond {} bytes",
+                self.limits.max_file_bytes
+            )));
+        }
+        if total_bytes > self.limits.max_total_bytes {
+            return Err(ArchiveLimitError(format!(
+                "archive expands beyond {} bytes",
+                self.limits.max_total_bytes
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn entry_rel_path(name: &str) -> anyhow::Result<PathBuf> {
+    let p = Path::new(name);
+    let mut out = PathBuf::new();
+    for c in p.components() {
+        match c {
+            Component::CurDir => {}
+            Component::Normal(seg) => out.push(seg),
+            Component::ParentDir => anyhow::bail!("path traversal is not allowed"),
+            Component::Prefix(_) | Component::RootDir => {
+                anyhow::bail!("absolute paths are not allowed")
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Whether `path` starts with a zip signature, e.g. to catch an HTML error
+/// page served in place of a download.
+pub fn has_zip_magic(path: &Path) -> bool {
+    let mut header = [0u8; 4];
+    let read = fs::File::open(path).and_then(|mut f| f.read_exact(&mut header));
+    read.is_ok()
+        && matches!(
+            header,
+            [b'P', b'K', 0x03, 0x04]
+                | [b'P', b'K', 0x05, 0x06]
+                | [b'P', b'K', 0x07, 0x08]
+                | [b'P', b'K', 0x01, 0x02]
+        )
+}
+
+/// Reports coarse extraction progress (start, each quarter, done) as plain
+/// lines, e.g. to an instance's `[install]` console output.
+pub struct ExtractProgress {
+    output: Option<mpsc::UnboundedSender<String>>,
+    label: String,
+    total: usize,
+    quarter: usize,
+}
+
+impl ExtractProgress {
+    pub fn new(output: Option<mpsc::UnboundedSender<String>>, label: &str, total: usize) -> Self {
+        let progress = Self {
+            output,
+            label: label.to_string(),
+            total,
+            quarter: 0,
+        };
+        progress.send(format!("extracting {label} ({total} entries)"));
+        progress
+    }
+
+    /// Called as entry `index` (0-based) is reached.
+    pub fn entry(&mut self, index: usize) {
+        if self.total == 0 {
+            return;
+        }
+        let done = index + 1;
+        let quarter = done * 4 / self.total;
+        if quarter > self.quarter && quarter < 4 {
+            self.quarter = quarter;
+            self.send(format!(
+                "extracting {}: {}% ({done}/{} entries)",
+                self.label,
+                quarter * 25,
+                self.total
+            ));
+        }
+    }
+
+    pub fn finish(&self) {
+        self.send(format!("extracted {}", self.label));
+    }
+
+    fn send(&self, line: String) {
+        if let Some(output) = &self.output {
+            let _ = output.send(line);
+        }
+    }
+}
+
+fn archive_label(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "archive".to_string())
+}
+
+/// [`extract_zip`] with the env-configured limits, for user-provided archives.
+pub fn extract_zip_safely(zip_path: &Path, out_dir: &Path) -> anyhow::Result<()> {
+    extract_zip_safely_with_output(zip_path, out_dir, None)
+}
+
+/// [`extract_zip_safely`], reporting progress lines to `output`.
+pub fn extract_zip_safely_with_output(
+    zip_path: &Path,
+    out_dir: &Path,
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<()> {
+    extract_zip_with_output(zip_path, out_dir, ArchiveLimits::from_env(), output)
+}
+
+/// Extracts `zip_path` into `out_dir`, rejecting absolute and traversal
+/// entries and aborting once `limits` are exceeded. A directory created by
+/// this call is removed again on failure.
+pub fn extract_zip(zip_path: &Path, out_dir: &Path, limits: ArchiveLimits) -> anyhow::Result<()> {
+    extract_zip_with_output(zip_path, out_dir, limits, None)
+}
+
+/// [`extract_zip`], reporting progress lines to `output`.
+pub fn extract_zip_with_output(
+    zip_path: &Path,
+    out_dir: &Path,
+    limits: ArchiveLimits,
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<()> {
+    let created = !out_dir.exists();
+    let res = extract_zip_into(zip_path, out_dir, limits, output);
+    if res.is_err() && created {
+        let _ = fs::remove_dir_all(out_dir);
+    }
+    res
+}
+
+fn extract_zip_into(
+    zip_path: &Path,
+    out_dir: &Path,
+    limits: ArchiveLimits,
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(out_dir)?;
+    let f = fs::File::open(zip_path)?;
+    let mut archive = zip::ZipArchive::new(f)?;
+    let mut budget = ExtractBudget::new(limits);
+    let mut progress = ExtractProgress::new(output, &archive_label(zip_path), archive.len());
+
+    for i in 0..archive.len() {
+        progress.entry(i);
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+        let trimmed = name.trim_end_matches('/');
+        if trimmed.is_empty() {
+            continue;
+        }
+        let rel =
+            entry_rel_path(trimmed).with_context(|| format!("invalid zip path {trimmed:?}"))?;
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+        let out_path = out_dir.join(&rel);
+        if name.ends_with('/') {
+            fs::create_dir_all(&out_path)?;
+            continue;
+        }
+        budget.admit(trimmed, file.size())?;
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = out_path.with_extension("tmp");
+        let mut out = fs::File::create(&tmp_path)?;
+        if let Err(e) = budget.copy(trimmed, &mut file, &mut out) {
+            drop(out);
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+        out.sync_all().ok();
+        fs::rename(&tmp_path, &out_path)?;
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// Unpacks a (decompressed) tar stream into `out_dir` under the same path
+/// rules and limits as [`extract_zip`]. Only directories and regular files
+/// are materialized; links and special entries are skipped.
+pub fn extract_tar(reader: impl Read, out_dir: &Path, limits: ArchiveLimits) -> anyhow::Result<()> {
+    let created = !out_dir.exists();
+    let res = extract_tar_into(reader, out_dir, limits);
+    if res.is_err() && created {
+        let _ = fs::remove_dir_all(out_dir);
+    }
+    res
+}
+
+fn extract_tar_into(
+    reader: impl Read,
+    out_dir: &Path,
+    limits: ArchiveLimits,
+) -> anyhow::Result<()> {
+    fs::create_dir_all(out_dir)?;
+    let mut archive = tar::Archive::new(reader);
+    let mut budget = ExtractBudget::new(limits);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let trimmed = name.trim_end_matches('/');
+        if trimmed.is_empty() {
+            continue;
+        }
+        let rel =
+            entry_rel_path(trimmed).with_context(|| format!("invalid tar path {trimmed:?}"))?;
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+        let out_path = out_dir.join(&rel);
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            fs::create_dir_all(&out_path)?;
+            continue;
+        }
+        if !kind.is_file() {
+            continue;
+        }
+        budget.admit(trimmed, entry.size())?;
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = out_path.with_extension("tmp");
+        let mut out = fs::File::create(&tmp_path)?;
+        if let Err(e) = budget.copy(trimmed, &mut entry, &mut out) {
+            drop(out);
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+        out.sync_all().ok();
+        fs::rename(&tmp_path, &out_path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> ArchiveLimits {
+        ArchiveLimits {
+            max_total_bytes: 1000,
+            max_files: 3,
+            max_file_bytes: 600,
+        }
+    }
+
+    #[test]
+    fn copy_stops_at_actual_bytes_even_when_declared_size_lies() {
+        let mut budget = ExtractBudget::new(limits());
+        budget.admit("bomb.bin", 10).unwrap();
+        let data = vec![0u8; 10_000];
+        let mut out = Vec::new();
+        let err = budget
+            .copy("bomb.bin", &mut data.as_slice(), &mut out)
+            .unwrap_err();
+        assert!(err.downcast_ref::<ArchiveLimitError>().is_some());
+        assert!(out.len() <= 601);
+    }
+
+    #[test]
+    fn total_and_file_count_caps_apply_across_entries() {
+        let mut budget = ExtractBudget::new(limits());
+        for name in ["a", "b"] {
+            budget.admit(name, 500).unwrap();
+            let mut out = Vec::new();
+            budget
+                .copy(name, &mut vec![1u8; 500].as_slice(), &mut out)
+                .unwrap();
+        }
+        assert!(budget.admit("c", 0).is_ok());
+        assert!(budget.admit("d", 0).is_err());
+
+        let mut budget = ExtractBudget::new(limits());
+        budget.admit("a", 0).unwrap();
+        budget
+            .copy("a", &mut vec![1u8; 600].as_slice(), &mut Vec::new())
+            .unwrap();
+        budget.admit("b", 0).unwrap();
+        assert!(
+            budget
+                .copy("b", &mut vec![1u8; 500].as_slice(), &mut Vec::new())
+                .is_err()
+        );
+    }
+
+    fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+        let f = fs::File::create(path).unwrap();
+        let mut zip = zip::ZipWriter::new(f);
+        let opts = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        for (name, data) in entries {
+            zip.start_file(*name, opts).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn malicious_archives_are_rejected_without_escaping_or_leftovers() {
+        let root = std::env::temp_dir().join(format!(
+            "alloy-archive-test-{}",
+            alloy_process::ProcessId::new().0
+        ));
+        fs::create_dir_all(&root).unwrap();
+
+        // Highly compressible entry: tiny on disk, large once expanded.
+        let bomb = root.join("bomb.zip");
+        write_zip(
+            &bomb,
+            &[("ok.txt", b"hi"), ("big.bin", &vec![0u8; 4 * 1024 * 1024])],
+        );
+        let out = root.join("bomb-out");
+        let small = ArchiveLimits {
+            max_total_bytes: 1024 * 1024,
+            max_files: 100,
+            max_file_bytes: 1024 * 1024,
+        };
+        let err = extract_zip(&bomb, &out, small).unwrap_err();
+        assert!(err.downcast_ref::<ArchiveLimitError>().is_some());
+        assert!(as_install_failed(&err).is_some());
+        assert!(!out.exists());
+
+        for name in ["../escape.txt", "/abs.txt", "a/../../escape.txt"] {
+            let evil = root.join("evil.zip");
+            write_zip(&evil, &[(name, b"x")]);
+            let out = root.join("evil-out");
+            assert!(extract_zip(&evil, &out, ArchiveLimits::unlimited()).is_err());
+            assert!(!out.exists());
+        }
+        assert!(!root.parent().unwrap().join("escape.txt").exists());
+        assert!(!root.join("escape.txt").exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn extraction_progress_is_sent_to_the_output() {
+        let root = std::env::temp_dir().join(format!(
+            "alloy-archive-test-{}",
+            alloy_process::ProcessId::new().0
+        ));
+        fs::create_dir_all(&root).unwrap();
+        let zip = root.join("pack.zip");
+        write_zip(
+            &zip,
+            &[
+                ("a.txt", b"a"),
+                ("b.txt", b"b"),
+                ("c.txt", b"c"),
+                ("d.txt", b"d"),
+            ],
+        );
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        extract_zip_with_output(
+            &zip,
+            &root.join("out"),
+            ArchiveLimits::unlimited(),
+            Some(tx),
+        )
+        .unwrap();
+        let mut lines = Vec::new();
+        while let Ok(line) = rx.try_recv() {
+            lines.push(line);
+        }
+        assert_eq!(
+            lines,
+            [
+                "extracting pack.zip (4 entries)",
+                "extracting pack.zip: 25% (1/4 entries)",
+                "extracting pack.zip: 50% (2/4 entries)",
+                "extracting pack.zip: 75% (3/4 entries)",
+                "extracted pack.zip",
+            ]
+        );
+        assert!(root.join("out").join("d.txt").is_file());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
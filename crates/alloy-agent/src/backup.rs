@@ -0,0 +1,741 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sha2::Digest;
+use tonic::Status;
+
+use crate::archive::ArchiveLimits;
+
+pub(crate) const BACKUPS_DIR: &str = "backups";
+const RECORD_EXT: &str = "json";
+
+// Files a running (or crashed) server keeps locked; never archived or restored.
+const SKIPPED_FILES: &[&str] = &["session.lock"];
+
+fn is_skipped_file(name: &str) -> bool {
+    SKIPPED_FILES.contains(&name) || name.ends_with(".lock")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArchiveFormat {
+    TarZst,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    const ALL: [ArchiveFormat; 3] = [Self::TarZst, Self::TarGz, Self::Zip];
+
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Self::TarZst => "tar.zst",
+            Self::TarGz => "tar.gz",
+            Self::Zip => "zip",
+        }
+    }
+
+    /// Accepts the file extension (`tar.zst`, `tar.gz`, `zip`); empty means `tar.zst`.
+    pub(crate) fn parse(raw: &str) -> Result<Self, BackupError> {
+        let raw = raw.trim().trim_start_matches('.').to_ascii_lowercase();
+        if raw.is_empty() {
+            return Ok(Self::TarZst);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|f| f.extension() == raw)
+            .ok_or_else(|| BackupError::Invalid(format!("unsupported backup format: {raw:?}")))
+    }
+}
+
+/// Metadata kept next to each archive as `backups/<id>.json`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct BackupRecord {
+    pub backup_id: String,
+    pub format: String,
+    pub size_bytes: u64,
+    /// Hex SHA-256 of the archive file; empty for archives without a record.
+    pub sha256: String,
+    pub created_at_unix_ms: u64,
+}
+
+#[derive(Debug)]
+pub(crate) enum BackupError {
+    Invalid(String),
+    NotFound,
+    Precondition(String),
+    Unsupported,
+    Internal(String),
+}
+
+impl From<BackupError> for Status {
+    fn from(value: BackupError) -> Self {
+        match value {
+            BackupError::Invalid(msg) => Status::invalid_argument(msg),
+            BackupError::NotFound => Status::not_found("backup not found"),
+            BackupError::Precondition(msg) => Status::failed_precondition(msg),
+            BackupError::Unsupported => {
+                Status::unimplemented("backups are not supported for this template")
+            }
+            BackupError::Internal(msg) => Status::internal(msg),
+        }
+    }
+}
+
+pub(crate) fn backups_dir(instance_dir: &Path) -> PathBuf {
+    instance_dir.join(BACKUPS_DIR)
+}
+
+fn backup_path(instance_dir: &Path, backup_id: &str, format: ArchiveFormat) -> PathBuf {
+    backups_dir(instance_dir).join(format!("{backup_id}.{}", format.extension()))
+}
+
+fn record_path(instance_dir: &Path, backup_id: &str) -> PathBuf {
+    backups_dir(instance_dir).join(format!("{backup_id}.{RECORD_EXT}"))
+}
+
+fn find_backup(instance_dir: &Path, backup_id: &str) -> Option<(PathBuf, ArchiveFormat)> {
+    ArchiveFormat::ALL
+        .into_iter()
+        .map(|f| (backup_path(instance_dir, backup_id, f), f))
+        .find(|(p, _)| p.is_file())
+}
+
+pub(crate) fn normalize_backup_id(id: &str) -> Result<String, BackupError> {
+    let id = id.trim();
+    if id.is_empty() {
+        return Err(BackupError::Invalid(
+            "backup_id must be non-empty".to_string(),
+        ));
+    }
+    if id.starts_with('.')
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(BackupError::Invalid("invalid backup_id".to_string()));
+    }
+    Ok(id.to_string())
+}
+
+/// Whether a top-level entry of the instance dir holds world/save data.
+pub(crate) fn is_world_root(template_id: &str, name: &str) -> bool {
+    match template_id {
+        "minecraft:vanilla"
+        | "minecraft:paper"
+        | "minecraft:modrinth"
+        | "minecraft:import"
+        | "minecraft:curseforge" => name.starts_with("world"),
+        "terraria:vanilla" | "terraria:tmodloader" => name == "worlds",
+        "dst:vanilla" => name == "klei",
+        _ => false,
+    }
+}
+
+pub(crate) fn supports_backups(template_id: &str) -> bool {
+    matches!(
+        template_id,
+        "minecraft:vanilla"
+            | "minecraft:paper"
+            | "minecraft:modrinth"
+            | "minecraft:import"
+            | "minecraft:curseforge"
+            | "terraria:vanilla"
+            | "terraria:tmodloader"
+            | "dst:vanilla"
+    )
+}
+
+fn world_roots(template_id: &str, instance_dir: &Path) -> Vec<String> {
+    let mut out = Vec::new();
+    let Ok(rd) = std::fs::read_dir(instance_dir) else {
+        return out;
+    };
+    for e in rd.flatten() {
+        let Some(name) = e.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let Ok(meta) = std::fs::symlink_metadata(e.path()) else {
+            continue;
+        };
+        if meta.file_type().is_symlink() || !is_world_root(template_id, &name) {
+            continue;
+        }
+        out.push(name);
+    }
+    out.sort();
+    out
+}
+
+pub(crate) fn tree_size(path: &Path) -> u64 {
+    let meta = match std::fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(_) => return 0,
+    };
+    if meta.file_type().is_symlink() {
+        return 0;
+    }
+    if meta.is_file() {
+        return meta.len();
+    }
+    let mut sum = 0u64;
+    if let Ok(rd) = std::fs::read_dir(path) {
+        for e in rd.flatten() {
+            sum = sum.saturating_add(tree_size(&e.path()));
+        }
+    }
+    sum
+}
+
+fn new_backup_id(instance_dir: &Path, label: &str) -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let base = format!("{secs}-{label}");
+    let mut id = base.clone();
+    let mut n = 1;
+    while find_backup(instance_dir, &id).is_some() {
+        n += 1;
+        id = format!("{base}-{n}");
+    }
+    id
+}
+
+/// Visits `rel` under `root` depth-first in name order, skipping symlinks and
+/// lock files. The callback gets the archive name, the file path and whether
+/// it is a directory.
+fn walk_tree(
+    root: &Path,
+    rel: &str,
+    visit: &mut dyn FnMut(&str, &Path, bool) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let path = root.join(rel);
+    let meta = std::fs::symlink_metadata(&path)?;
+    if meta.file_type().is_symlink() {
+        return Ok(());
+    }
+    if meta.is_dir() {
+        visit(rel, &path, true)?;
+        let mut names = std::fs::read_dir(&path)?
+            .flatten()
+            .filter_map(|e| e.file_name().to_str().map(str::to_string))
+            .collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            walk_tree(root, &format!("{rel}/{name}"), visit)?;
+        }
+        return Ok(());
+    }
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    if !meta.is_file() || is_skipped_file(name) {
+        return Ok(());
+    }
+    visit(rel, &path, false)
+}
+
+fn write_zip_entries<W: Write + std::io::Seek>(
+    out: W,
+    root: &Path,
+    entries: &[String],
+) -> anyhow::Result<W> {
+    let mut zip = zip::ZipWriter::new(out);
+    let opts = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+    for entry in entries {
+        walk_tree(root, entry, &mut |rel, path, is_dir| {
+            if is_dir {
+                zip.add_directory(format!("{rel}/"), opts)?;
+            } else {
+                zip.start_file(rel, opts)?;
+                let mut f = std::fs::File::open(path)?;
+                std::io::copy(&mut f, &mut zip)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(zip.finish()?)
+}
+
+fn write_tar_entries<W: Write>(out: W, root: &Path, entries: &[String]) -> anyhow::Result<W> {
+    let mut tar = tar::Builder::new(out);
+    tar.follow_symlinks(false);
+    for entry in entries {
+        walk_tree(root, entry, &mut |rel, path, is_dir| {
+            if is_dir {
+                tar.append_dir(rel, path)?;
+            } else {
+                // Streams the file; nothing is buffered beyond the encoder.
+                tar.append_path_with_name(path, rel)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(tar.into_inner()?)
+}
+
+/// Archive the top-level `entries` of `root` into `out` (written via a temp file).
+pub(crate) fn write_archive(
+    root: &Path,
+    entries: &[String],
+    out: &Path,
+    format: ArchiveFormat,
+) -> anyhow::Result<()> {
+    let mut tmp = out.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let res = (|| -> anyhow::Result<()> {
+        let f = std::fs::File::create(&tmp)?;
+        let f = match format {
+            ArchiveFormat::Zip => write_zip_entries(f, root, entries)?,
+            ArchiveFormat::TarZst => {
+                write_tar_entries(zstd::Encoder::new(f, 0)?, root, entries)?.finish()?
+            }
+            ArchiveFormat::TarGz => write_tar_entries(
+                flate2::write::GzEncoder::new(f, flate2::Compression::default()),
+                root,
+                entries,
+            )?
+            .finish()?,
+        };
+        f.sync_all().ok();
+        std::fs::rename(&tmp, out)?;
+        Ok(())
+    })();
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    res
+}
+
+/// Zip the top-level `entries` of `root` into `out` (written via a temp file).
+pub(crate) fn write_zip(root: &Path, entries: &[String], out: &Path) -> anyhow::Result<()> {
+    write_archive(root, entries, out, ArchiveFormat::Zip)
+}
+
+pub(crate) fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut f = std::fs::File::open(path)?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Archive the instance's world data into `backups/<id>.<ext>` and record its
+/// size and checksum next to it.
+pub(crate) fn create_backup(
+    instance_dir: &Path,
+    template_id: &str,
+    label: &str,
+    format: ArchiveFormat,
+) -> anyhow::Result<BackupRecord> {
+    std::fs::create_dir_all(backups_dir(instance_dir))?;
+    let id = new_backup_id(instance_dir, label);
+    let roots = world_roots(template_id, instance_dir);
+    let path = backup_path(instance_dir, &id, format);
+    write_archive(instance_dir, &roots, &path, format)?;
+
+    let record = BackupRecord {
+        backup_id: id.clone(),
+        format: format.extension().to_string(),
+        size_bytes: std::fs::metadata(&path)?.len(),
+        sha256: sha256_file(&path)?,
+        created_at_unix_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+    };
+    std::fs::write(
+        record_path(instance_dir, &id),
+        serde_json::to_vec_pretty(&record)?,
+    )?;
+    Ok(record)
+}
+
+/// [`create_backup`] in the default format; returns the backup id.
+pub(crate) fn create_snapshot(
+    instance_dir: &Path,
+    template_id: &str,
+    label: &str,
+) -> anyhow::Result<String> {
+    create_backup(instance_dir, template_id, label, ArchiveFormat::TarZst).map(|r| r.backup_id)
+}
+
+fn read_record(instance_dir: &Path, backup_id: &str) -> Option<BackupRecord> {
+    let raw = std::fs::read(record_path(instance_dir, backup_id)).ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+/// All archives under `backups/`, newest first. Archives without a record
+/// (e.g. from older agents) are listed with an empty checksum.
+pub(crate) fn list_backups(instance_dir: &Path) -> Vec<BackupRecord> {
+    let Ok(rd) = std::fs::read_dir(backups_dir(instance_dir)) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    for e in rd.flatten() {
+        let Some(name) = e.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let Some((id, format)) = ArchiveFormat::ALL.into_iter().find_map(|f| {
+            name.strip_suffix(&format!(".{}", f.extension()))
+                .map(|id| (id.to_string(), f))
+        }) else {
+            continue;
+        };
+        if normalize_backup_id(&id).is_err() {
+            continue;
+        }
+        let Ok(meta) = e.metadata() else {
+            continue;
+        };
+        let record = read_record(instance_dir, &id).unwrap_or_else(|| BackupRecord {
+            backup_id: id.clone(),
+            format: format.extension().to_string(),
+            size_bytes: meta.len(),
+            sha256: String::new(),
+            created_at_unix_ms: meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+        });
+        out.push(record);
+    }
+    out.sort_by(|a, b| {
+        b.created_at_unix_ms
+            .cmp(&a.created_at_unix_ms)
+            .then_with(|| b.backup_id.cmp(&a.backup_id))
+    });
+    out
+}
+
+/// Decompressing reader over a `tar.*` backup.
+fn open_tar(archive_path: &Path, format: ArchiveFormat) -> std::io::Result<Box<dyn Read>> {
+    let f = std::fs::File::open(archive_path)?;
+    match format {
+        ArchiveFormat::TarZst => Ok(Box::new(zstd::Decoder::new(f)?)),
+        ArchiveFormat::TarGz => Ok(Box::new(flate2::read::GzDecoder::new(f))),
+        ArchiveFormat::Zip => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "zip backups are not tar archives",
+        )),
+    }
+}
+
+/// Tracks the entries seen while validating a backup archive.
+struct ArchiveCheck<'a> {
+    template_id: &'a str,
+    total: u64,
+    has_world: bool,
+}
+
+impl ArchiveCheck<'_> {
+    fn entry(&mut self, name: &str, rel: Option<&Path>, size: u64) -> Result<(), BackupError> {
+        let Some(rel) = rel else {
+            return Err(BackupError::Invalid(format!(
+                "backup contains an unsafe path: {name:?}"
+            )));
+        };
+        let top = rel
+            .components()
+            .next()
+            .and_then(|c| c.as_os_str().to_str())
+            .unwrap_or("");
+        if !is_world_root(self.template_id, top) {
+            return Err(BackupError::Invalid(format!(
+                "backup contains an unexpected path: {name:?}"
+            )));
+        }
+        self.has_world = true;
+        self.total = self.total.saturating_add(size);
+        Ok(())
+    }
+}
+
+/// Relative path of a tar entry, or None if it is absolute or escapes upwards.
+fn enclosed_tar_path(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            std::path::Component::Normal(seg) => out.push(seg),
+            std::path::Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!out.as_os_str().is_empty()).then_some(out)
+}
+
+/// Check that every entry is a safe relative path under a world root; returns
+/// the total uncompressed size.
+fn validate_archive(
+    archive_path: &Path,
+    format: ArchiveFormat,
+    template_id: &str,
+) -> Result<u64, BackupError> {
+    let corrupt =
+        |e: &dyn std::fmt::Display| BackupError::Invalid(format!("backup archive is corrupt: {e}"));
+    let mut check = ArchiveCheck {
+        template_id,
+        total: 0,
+        has_world: false,
+    };
+
+    if format == ArchiveFormat::Zip {
+        let f = std::fs::File::open(archive_path)
+            .map_err(|e| BackupError::Internal(format!("failed to open backup: {e}")))?;
+        let mut archive = zip::ZipArchive::new(f).map_err(|e| corrupt(&e))?;
+        for i in 0..archive.len() {
+            let file = archive.by_index(i).map_err(|e| corrupt(&e))?;
+            check.entry(file.name(), file.enclosed_name().as_deref(), file.size())?;
+        }
+    } else {
+        let reader = open_tar(archive_path, format)
+            .map_err(|e| BackupError::Internal(format!("failed to open backup: {e}")))?;
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries().map_err(|e| corrupt(&e))? {
+            let entry = entry.map_err(|e| corrupt(&e))?;
+            let path = entry.path().map_err(|e| corrupt(&e))?;
+            let name = path.to_string_lossy().to_string();
+            check.entry(&name, enclosed_tar_path(&path).as_deref(), entry.size())?;
+        }
+    }
+
+    if !check.has_world {
+        return Err(BackupError::Invalid(
+            "backup contains no world data".to_string(),
+        ));
+    }
+    Ok(check.total)
+}
+
+/// Compares the archive against its recorded checksum, if it has one.
+fn verify_checksum(
+    instance_dir: &Path,
+    backup_id: &str,
+    archive_path: &Path,
+) -> Result<(), BackupError> {
+    let Some(record) = read_record(instance_dir, backup_id) else {
+        return Ok(());
+    };
+    if record.sha256.is_empty() {
+        return Ok(());
+    }
+    let actual = sha256_file(archive_path)
+        .map_err(|e| BackupError::Internal(format!("failed to read backup: {e}")))?;
+    if !actual.eq_ignore_ascii_case(&record.sha256) {
+        return Err(BackupError::Precondition(format!(
+            "backup {backup_id} is damaged: sha256 {actual} does not match recorded {}",
+            record.sha256
+        )));
+    }
+    Ok(())
+}
+
+fn extract_backup(
+    archive_path: &Path,
+    format: ArchiveFormat,
+    out_dir: &Path,
+) -> anyhow::Result<()> {
+    match format {
+        ArchiveFormat::Zip => {
+            crate::archive::extract_zip(archive_path, out_dir, ArchiveLimits::unlimited())
+        }
+        ArchiveFormat::TarZst | ArchiveFormat::TarGz => crate::archive::extract_tar(
+            open_tar(archive_path, format)?,
+            out_dir,
+            ArchiveLimits::unlimited(),
+        ),
+    }
+}
+
+fn ensure_restore_space(instance_dir: &Path, needed: u64) -> Result<(), BackupError> {
+    let Some(free) = crate::process_manager::free_bytes(instance_dir) else {
+        return Ok(());
+    };
+    if free < needed {
+        return Err(BackupError::Precondition(format!(
+            "insufficient disk space for restore: free {free} bytes < required {needed} bytes"
+        )));
+    }
+    Ok(())
+}
+
+/// Replace the instance's world data with the contents of `backup_id`.
+///
+/// The current world is archived first; the returned id refers to that
+/// pre-restore snapshot so a bad restore can be undone. The caller must make
+/// sure the instance is stopped.
+pub(crate) fn restore_backup(
+    instance_dir: &Path,
+    template_id: &str,
+    backup_id: &str,
+) -> Result<String, BackupError> {
+    if !supports_backups(template_id) {
+        return Err(BackupError::Unsupported);
+    }
+    let backup_id = normalize_backup_id(backup_id)?;
+    let Some((archive_path, format)) = find_backup(instance_dir, &backup_id) else {
+        return Err(BackupError::NotFound);
+    };
+
+    verify_checksum(instance_dir, &backup_id, &archive_path)?;
+    let restored_bytes = validate_archive(&archive_path, format, template_id)?;
+    let current = world_roots(template_id, instance_dir);
+    let current_bytes = current
+        .iter()
+        .map(|r| tree_size(&instance_dir.join(r)))
+        .fold(0u64, u64::saturating_add);
+    // Room for the pre-restore snapshot (worst case: uncompressed) and the extracted backup.
+    ensure_restore_space(instance_dir, restored_bytes.saturating_add(current_bytes))?;
+
+    let snapshot_id = create_snapshot(instance_dir, template_id, "pre-restore")
+        .map_err(|e| BackupError::Internal(format!("failed to snapshot current world: {e}")))?;
+
+    let nonce = alloy_process::ProcessId::new().0;
+    let staging = instance_dir.join(format!(".restore-{nonce}"));
+    let displaced = instance_dir.join(format!(".restore-old-{nonce}"));
+
+    if let Err(e) = extract_backup(&archive_path, format, &staging) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(BackupError::Internal(format!(
+            "failed to extract backup: {e}"
+        )));
+    }
+
+    let swap = swap_in(instance_dir, &staging, &displaced, &current);
+    let _ = std::fs::remove_dir_all(&staging);
+    match swap {
+        Ok(()) => {
+            let _ = std::fs::remove_dir_all(&displaced);
+            Ok(snapshot_id)
+        }
+        Err(e) => Err(BackupError::Internal(format!(
+            "failed to install backup (previous world kept, snapshot {snapshot_id}): {e}"
+        ))),
+    }
+}
+
+/// Move the current world roots aside and the staged ones into place, rolling
+/// back on any failure.
+fn swap_in(
+    instance_dir: &Path,
+    staging: &Path,
+    displaced: &Path,
+    current: &[String],
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(displaced)?;
+    let mut moved_out = Vec::<&str>::new();
+    let mut moved_in = Vec::<String>::new();
+
+    let res = (|| -> std::io::Result<()> {
+        for name in current {
+            std::fs::rename(instance_dir.join(name), displaced.join(name))?;
+            moved_out.push(name);
+        }
+        for e in std::fs::read_dir(staging)?.flatten() {
+            let name = e.file_name().to_string_lossy().to_string();
+            std::fs::rename(e.path(), instance_dir.join(&name))?;
+            moved_in.push(name);
+        }
+        Ok(())
+    })();
+
+    if res.is_err() {
+        for name in &moved_in {
+            let _ = std::fs::rename(instance_dir.join(name), staging.join(name));
+        }
+        for name in &moved_out {
+            let _ = std::fs::rename(displaced.join(name), instance_dir.join(name));
+        }
+        let _ = std::fs::remove_dir_all(displaced);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ArchiveFormat, create_backup, create_snapshot, list_backups, restore_backup, sha256_file,
+    };
+
+    #[test]
+    fn restore_swaps_world_and_keeps_pre_restore_snapshot() {
+        let mut root = std::env::temp_dir();
+        root.push(format!("alloy-agent-backup-restore-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let world = root.join("worlds").join("world");
+        std::fs::create_dir_all(&world).unwrap();
+        std::fs::write(world.join("level.dat"), b"old").unwrap();
+        std::fs::write(world.join("session.lock"), b"").unwrap();
+
+        let first = create_snapshot(&root, "minecraft:vanilla", "manual").unwrap();
+        std::fs::write(world.join("level.dat"), b"new").unwrap();
+        std::fs::write(world.join("extra.dat"), b"x").unwrap();
+
+        let undo = restore_backup(&root, "minecraft:vanilla", &first).unwrap();
+        assert_ne!(undo, first);
+        assert_eq!(std::fs::read(world.join("level.dat")).unwrap(), b"old");
+        assert!(!world.join("extra.dat").exists());
+        assert!(!world.join("session.lock").exists());
+
+        restore_backup(&root, "minecraft:vanilla", &undo).unwrap();
+        assert_eq!(std::fs::read(world.join("level.dat")).unwrap(), b"new");
+        assert!(world.join("extra.dat").exists());
+
+        assert!(restore_backup(&root, "minecraft:vanilla", "../x").is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn every_format_round_trips_with_a_matching_checksum() {
+        for format in ArchiveFormat::ALL {
+            let mut root = std::env::temp_dir();
+            root.push(format!(
+                "alloy-agent-backup-{}-{}",
+                format.extension(),
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&root);
+            let saves = root.join("klei").join("Cluster_1").join("Master");
+            std::fs::create_dir_all(&saves).unwrap();
+            std::fs::write(saves.join("save.bin"), vec![7u8; 100_000]).unwrap();
+            std::fs::write(saves.join("world.lock"), b"").unwrap();
+            std::fs::write(root.join("dst.log"), b"not world data").unwrap();
+
+            let record = create_backup(&root, "dst:vanilla", "manual", format).unwrap();
+            let path =
+                root.join("backups")
+                    .join(format!("{}.{}", record.backup_id, format.extension()));
+            assert_eq!(record.format, format.extension());
+            assert_eq!(record.size_bytes, std::fs::metadata(&path).unwrap().len());
+            assert_eq!(record.sha256, sha256_file(&path).unwrap());
+            assert_eq!(list_backups(&root), vec![record.clone()]);
+
+            std::fs::remove_dir_all(root.join("klei")).unwrap();
+            restore_backup(&root, "dst:vanilla", &record.backup_id).unwrap();
+            assert_eq!(
+                std::fs::read(saves.join("save.bin")).unwrap(),
+                vec![7u8; 100_000]
+            );
+            assert!(!saves.join("world.lock").exists());
+
+            // A damaged archive is refused before anything is touched.
+            std::fs::write(&path, b"garbage").unwrap();
+            assert!(restore_backup(&root, "dst:vanilla", &record.backup_id).is_err());
+            assert!(saves.join("save.bin").is_file());
+
+            let _ = std::fs::remove_dir_all(&root);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashSet;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Shared download caches; every child of a category root is one artifact.
+pub(crate) const CATEGORIES: [&str; 7] = [
+    "minecraft:vanilla",
+    "minecraft:paper",
+    "minecraft:modrinth",
+    "minecraft:curseforge",
+    "terraria:vanilla",
+    "terraria:tmodloader",
+    "dst:vanilla",
+];
+
+#[derive(Debug, Clone)]
+pub(crate) struct CacheRoots {
+    cache: PathBuf,
+}
+
+impl CacheRoots {
+    pub(crate) fn new(data_root: &Path) -> Self {
+        Self {
+            cache: data_root.join("cache"),
+        }
+    }
+
+    pub(crate) fn root(&self, category: &str) -> PathBuf {
+        match category {
+            "minecraft:modrinth" => self.cache.join("minecraft").join("modrinth").join("packs"),
+            "minecraft:paper" => self.cache.join("minecraft").join("paper"),
+            "minecraft:curseforge" => self.cache.join("minecraft").join("curseforge"),
+            "terraria:vanilla" => self.cache.join("terraria").join("vanilla"),
+            "terraria:tmodloader" => self.cache.join("terraria").join("tmodloader"),
+            "dst:vanilla" => self.cache.join("dst").join("vanilla"),
+            _ => self.cache.join("minecraft").join("vanilla"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CacheItem {
+    pub key: String,
+    pub category: &'static str,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub last_used_unix_ms: u64,
+    // Run from, or hard-linked into, an active server; never deleted.
+    pub in_use: bool,
+}
+
+/// What the active servers depend on: paths they run from and the inodes of
+/// hard-linked files in their instance dirs.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InUse {
+    paths: Vec<PathBuf>,
+    inodes: HashSet<(u64, u64)>,
+}
+
+impl InUse {
+    /// Looks two levels into each instance dir, which is where cached
+    /// artifacts (e.g. `server.jar`) get linked.
+    pub(crate) fn collect(instance_dirs: &[PathBuf], paths: Vec<PathBuf>) -> Self {
+        fn walk(dir: &Path, depth: u32, inodes: &mut HashSet<(u64, u64)>) {
+            let Ok(rd) = std::fs::read_dir(dir) else {
+                return;
+            };
+            for entry in rd.flatten() {
+                let Ok(meta) = entry.metadata() else {
+                    continue;
+                };
+                if meta.is_file() && meta.nlink() > 1 {
+                    inodes.insert((meta.dev(), meta.ino()));
+                } else if meta.is_dir() && depth > 0 {
+                    walk(&entry.path(), depth - 1, inodes);
+                }
+            }
+        }
+
+        let mut inodes = HashSet::new();
+        for dir in instance_dirs {
+            walk(dir, 1, &mut inodes);
+        }
+        Self { paths, inodes }
+    }
+}
+
+struct Stats {
+    size: u64,
+    last_access_ms: u64,
+    linked: Vec<(u64, u64)>,
+}
+
+fn unix_ms(t: std::io::Result<SystemTime>) -> u64 {
+    t.ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Size, latest file atime/mtime and hard-linked files under `path`; symlinks
+// are not followed.
+fn stats(path: &Path) -> Stats {
+    fn walk(p: &Path, out: &mut Stats) {
+        let Ok(meta) = std::fs::symlink_metadata(p) else {
+            return;
+        };
+        if meta.is_dir() {
+            let Ok(rd) = std::fs::read_dir(p) else {
+                return;
+            };
+            for e in rd.flatten() {
+                walk(&e.path(), out);
+            }
+            return;
+        }
+        let touched = unix_ms(meta.accessed()).max(unix_ms(meta.modified()));
+        out.last_access_ms = out.last_access_ms.max(touched);
+        out.size = out.size.saturating_add(meta.len());
+        if meta.is_file() && meta.nlink() > 1 {
+            out.linked.push((meta.dev(), meta.ino()));
+        }
+    }
+
+    let mut out = Stats {
+        size: 0,
+        last_access_ms: 0,
+        linked: Vec::new(),
+    };
+    walk(path, &mut out);
+    out
+}
+
+// The `.last_used` sidecar the downloaders write on every cache hit.
+fn read_last_used_marker(dir: &Path) -> u64 {
+    std::fs::read_to_string(dir.join(".last_used"))
+        .ok()
+        .and_then(|raw| raw.trim().parse::<u64>().ok())
+        .unwrap_or(0)
+}
+
+// The version label from a jar's `meta.json`: `version_id` for vanilla,
+// `<mc_version>-<build>` for Paper.
+fn minecraft_version_id(entry_dir: &Path) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct Meta {
+        version_id: Option<String>,
+        mc_version: Option<String>,
+        build: Option<u32>,
+    }
+    let bytes = std::fs::read(entry_dir.join("meta.json")).ok()?;
+    let meta: Meta = serde_json::from_slice(&bytes).ok()?;
+    let version = match (meta.mc_version, meta.build) {
+        (Some(v), Some(build)) => Some(format!("{}-{build}", v.trim())),
+        _ => meta.version_id,
+    };
+    version
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+// The cache key of `name` under `category`'s root, or `None` if it is not an
+// artifact (temp files, markers, sidecars).
+fn item_key(category: &str, root: &Path, name: &str, is_dir: bool) -> Option<String> {
+    match category {
+        // Keyed by the jar's sha1 (vanilla) or sha256 (Paper).
+        "minecraft:vanilla" | "minecraft:paper" => {
+            let entry = root.join(name);
+            let hex_len = if category == "minecraft:paper" {
+                64
+            } else {
+                40
+            };
+            let hash_ok = name.len() == hex_len && name.chars().all(|c| c.is_ascii_hexdigit());
+            if !is_dir || !hash_ok || !entry.join("server.jar").is_file() {
+                return None;
+            }
+            let version = minecraft_version_id(&entry).unwrap_or_else(|| "unknown".to_string());
+            Some(format!("{category}@{version}#{name}"))
+        }
+        "minecraft:modrinth" => {
+            let stem = name.strip_suffix(".mrpack")?;
+            (!is_dir).then(|| format!("{category}@{stem}"))
+        }
+        "minecraft:curseforge" | "terraria:vanilla" => {
+            (is_dir && is_digits(name)).then(|| format!("{category}@{name}"))
+        }
+        _ => (is_dir && !name.starts_with('.')).then(|| format!("{category}@{name}")),
+    }
+}
+
+/// Every cached artifact of `category`, most recently used first.
+pub(crate) fn scan_category(
+    roots: &CacheRoots,
+    category: &'static str,
+    in_use: &InUse,
+) -> Vec<CacheItem> {
+    let root = roots.root(category);
+    // SteamCMD installs mark the category root rather than the install dir.
+    let root_marker = read_last_used_marker(&root);
+    let Ok(rd) = std::fs::read_dir(&root) else {
+        return Vec::new();
+    };
+
+    let mut items = Vec::new();
+    for entry in rd.flatten() {
+        let Ok(ft) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(key) = item_key(category, &root, &name, ft.is_dir()) else {
+            continue;
+        };
+        let path = entry.path();
+        let st = stats(&path);
+        let mut last_used = st.last_access_ms.max(read_last_used_marker(&path));
+        if category == "dst:vanilla" {
+            last_used = last_used.max(root_marker);
+        }
+        let in_use = in_use.paths.iter().any(|p| p.starts_with(&path))
+            || st.linked.iter().any(|i| in_use.inodes.contains(i));
+        items.push(CacheItem {
+            key,
+            category,
+            path,
+            size_bytes: st.size,
+            last_used_unix_ms: last_used,
+            in_use,
+        });
+    }
+    items.sort_by(|a, b| {
+        b.last_used_unix_ms
+            .cmp(&a.last_used_unix_ms)
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    items
+}
+
+pub(crate) fn scan(roots: &CacheRoots, in_use: &InUse) -> Vec<CacheItem> {
+    CATEGORIES
+        .iter()
+        .flat_map(|c| scan_category(roots, c, in_use))
+        .collect()
+}
+
+/// Whether `key` names a whole category or one of its artifacts.
+pub(crate) fn is_valid_key(key: &str) -> bool {
+    CATEGORIES.iter().any(|c| {
+        key == *c
+            || key
+                .strip_prefix(c)
+                .and_then(|rest| rest.strip_prefix('@'))
+                .is_some_and(|rest| !rest.is_empty())
+    })
+}
+
+pub(crate) fn matches_key(item: &CacheItem, key: &str) -> bool {
+    item.category == key || item.key == key
+}
+
+pub(crate) fn remove_item(item: &CacheItem) -> std::io::Result<()> {
+    if item.path.is_dir() {
+        return std::fs::remove_dir_all(&item.path);
+    }
+    std::fs::remove_file(&item.path)?;
+    let mut sidecar = item.path.clone().into_os_string();
+    sidecar.push(".sha256");
+    let _ = std::fs::remove_file(sidecar);
+    Ok(())
+}
+
+/// Indices of the artifacts to delete, least recently used first, until the
+/// total fits in `max_bytes`. In-use artifacts are never picked.
+pub(crate) fn select_evictions(items: &[CacheItem], max_bytes: u64) -> Vec<usize> {
+    let mut total: u64 = items.iter().map(|i| i.size_bytes).sum();
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|&a, &b| {
+        items[a]
+            .last_used_unix_ms
+            .cmp(&items[b].last_used_unix_ms)
+            .then_with(|| items[a].key.cmp(&items[b].key))
+    });
+
+    let mut out = Vec::new();
+    for idx in order {
+        if total <= max_bytes {
+            break;
+        }
+        if items[idx].in_use {
+            continue;
+        }
+        total = total.saturating_sub(items[idx].size_bytes);
+        out.push(idx);
+    }
+    out
+}
+
+/// Deletes least recently used artifacts while the caches exceed `max_bytes`.
+pub(crate) fn evict_to_limit(roots: &CacheRoots, in_use: &InUse, max_bytes: u64) -> Vec<CacheItem> {
+    let items = scan(roots, in_use);
+    let mut evicted = Vec::new();
+    for idx in select_evictions(&items, max_bytes) {
+        let item = &items[idx];
+        match remove_item(item) {
+            Ok(()) => {
+                tracing::info!(
+                    key = %item.key,
+                    size_bytes = item.size_bytes,
+                    max_bytes,
+                    "evicted cached artifact"
+                );
+                evicted.push(item.clone());
+            }
+            Err(err) => {
+                tracing::warn!(key = %item.key, %err, "failed to evict cached artifact");
+            }
+        }
+    }
+    evicted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheRoots, InUse, evict_to_limit, scan};
+    use std::path::{Path, PathBuf};
+
+    fn write(path: &Path, len: usize, last_used_ms: u64) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, vec![0u8; len]).unwrap();
+        let old = std::time::UNIX_EPOCH + std::time::Duration::from_millis(last_used_ms);
+        let f = std::fs::File::options().write(true).open(path).unwrap();
+        f.set_times(
+            std::fs::FileTimes::new()
+                .set_accessed(old)
+                .set_modified(old),
+        )
+        .unwrap();
+    }
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "alloy-agent-cache-gc-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        root
+    }
+
+    fn jar_dir(roots: &CacheRoots, n: char) -> PathBuf {
+        roots
+            .root("minecraft:vanilla")
+            .join(std::iter::repeat_n(n, 40).collect::<String>())
+    }
+
+    #[test]
+    fn eviction_removes_least_recently_used_first() {
+        let data = temp_root("lru");
+        let roots = CacheRoots::new(&data);
+        write(&jar_dir(&roots, 'a').join("server.jar"), 100, 3_000);
+        write(&jar_dir(&roots, 'b').join("server.jar"), 100, 1_000);
+        write(
+            &roots.root("terraria:vanilla").join("1453").join("t.zip"),
+            100,
+            2_000,
+        );
+        write(
+            &roots.root("minecraft:modrinth").join("p.mrpack"),
+            100,
+            4_000,
+        );
+        // Not artifacts: never counted or evicted.
+        write(&roots.root("minecraft:vanilla").join("tmp.part"), 5_000, 0);
+
+        let in_use = InUse::default();
+        assert_eq!(scan(&roots, &in_use).len(), 4);
+
+        let evicted = evict_to_limit(&roots, &in_use, 250);
+        let keys: Vec<String> = evicted.into_iter().map(|i| i.key).collect();
+        assert_eq!(
+            keys,
+            [
+                format!("minecraft:vanilla@unknown#{}", "b".repeat(40)),
+                "terraria:vanilla@1453".to_string()
+            ]
+        );
+        assert!(!jar_dir(&roots, 'b').exists());
+        assert!(jar_dir(&roots, 'a').exists());
+
+        // Under the limit nothing more goes.
+        assert!(evict_to_limit(&roots, &in_use, 250).is_empty());
+        let _ = std::fs::remove_dir_all(&data);
+    }
+
+    #[test]
+    fn artifacts_used_by_running_instances_are_kept() {
+        let data = temp_root("in-use");
+        let roots = CacheRoots::new(&data);
+        let linked = jar_dir(&roots, 'a');
+        let run_from = roots.root("terraria:vanilla").join("1453");
+        write(&linked.join("server.jar"), 100, 1_000);
+        write(
+            &run_from.join("linux-x64").join("TerrariaServer"),
+            100,
+            2_000,
+        );
+        write(&jar_dir(&roots, 'b').join("server.jar"), 100, 3_000);
+
+        let inst = data.join("instances").join("mc-1");
+        std::fs::create_dir_all(&inst).unwrap();
+        std::fs::hard_link(linked.join("server.jar"), inst.join("server.jar")).unwrap();
+
+        let in_use = InUse::collect(
+            &[inst],
+            vec![run_from.join("linux-x64").join("TerrariaServer")],
+        );
+        let items = scan(&roots, &in_use);
+        assert_eq!(items.iter().filter(|i| i.in_use).count(), 2);
+
+        let evicted = evict_to_limit(&roots, &in_use, 0);
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].path, jar_dir(&roots, 'b'));
+        assert!(linked.exists() && run_from.exists());
+        let _ = std::fs::remove_dir_all(&data);
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::process_manager_support::config_override_max_bytes;
+
+/// JSON map of instance-relative path to content. The files are written just
+/// before launch, after the template generated its own config, so they win.
+pub const PARAM: &str = "config_overrides";
+const MAX_FILES: usize = 32;
+
+fn invalid(message: String) -> anyhow::Error {
+    crate::error_payload::anyhow(
+        "invalid_param",
+        format!("invalid {PARAM}: {message}"),
+        Some(BTreeMap::from([(PARAM.to_string(), message)])),
+        Some(
+            "Use a JSON object mapping paths inside the instance dir to file contents.".to_string(),
+        ),
+    )
+}
+
+fn rel_path(raw: &str) -> Result<PathBuf, String> {
+    let mut out = PathBuf::new();
+    for c in Path::new(raw).components() {
+        match c {
+            Component::CurDir => {}
+            Component::Normal(seg) => out.push(seg),
+            Component::ParentDir => return Err("path traversal is not allowed".to_string()),
+            Component::Prefix(_) | Component::RootDir => {
+                return Err("absolute paths are not allowed".to_string());
+            }
+        }
+    }
+    if out.as_os_str().is_empty() {
+        return Err("path must name a file".to_string());
+    }
+    Ok(out)
+}
+
+/// The overrides as (instance-relative path, content); empty when unset.
+pub fn parse(params: &BTreeMap<String, String>) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let Some(raw) = params
+        .get(PARAM)
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+    else {
+        return Ok(Vec::new());
+    };
+    let files: BTreeMap<String, String> = serde_json::from_str(raw)
+        .map_err(|e| invalid(format!("expected a JSON object of strings ({e})")))?;
+    if files.len() > MAX_FILES {
+        return Err(invalid(format!("at most {MAX_FILES} files are allowed")));
+    }
+    let max_bytes = config_override_max_bytes();
+    files
+        .into_iter()
+        .map(|(path, content)| {
+            let rel = rel_path(&path).map_err(|e| invalid(format!("{path}: {e}")))?;
+            if content.len() as u64 > max_bytes {
+                return Err(invalid(format!("{path}: larger than {max_bytes} bytes")));
+            }
+            Ok((rel, content))
+        })
+        .collect()
+}
+
+/// Writes the overrides under `instance_dir` and returns how many there
+/// were. Symlinks are never followed, so writes stay inside the instance.
+pub fn apply(instance_dir: &Path, params: &BTreeMap<String, String>) -> anyhow::Result<usize> {
+    let overrides = parse(params)?;
+    for (rel, content) in &overrides {
+        let mut path = instance_dir.to_path_buf();
+        let mut parts = rel.components().peekable();
+        while let Some(part) = parts.next() {
+            path.push(part);
+            let meta = fs::symlink_metadata(&path).ok();
+            if meta.as_ref().is_some_and(|m| m.file_type().is_symlink()) {
+                return Err(invalid(format!(
+                    "{}: refusing to follow a symlink",
+                    rel.display()
+                )));
+            }
+            if parts.peek().is_none() {
+                if meta.is_some_and(|m| m.is_dir()) {
+                    return Err(invalid(format!("{}: is a directory", rel.display())));
+                }
+            } else if meta.is_none() {
+                fs::create_dir(&path)?;
+            } else if meta.is_some_and(|m| !m.is_dir()) {
+                return Err(invalid(format!(
+                    "{}: a parent is not a directory",
+                    rel.display()
+                )));
+            }
+        }
+        // The tmp name is predictable: drop whatever sits there (a symlink is
+        // removed, not followed) and only ever write a freshly created file.
+        let tmp = path.with_extension("alloy-override.tmp");
+        let _ = fs::remove_file(&tmp);
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)?;
+        file.write_all(content.as_bytes())?;
+        drop(file);
+        fs::rename(&tmp, &path)?;
+    }
+    Ok(overrides.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{PARAM, apply, parse};
+
+    fn overrides(json: &str) -> BTreeMap<String, String> {
+        BTreeMap::from([(PARAM.to_string(), json.to_string())])
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "alloy-agent-config-overrides-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn writes_outside_the_instance_are_refused() {
+        for path in ["../escape.txt", "/etc/passwd", "a/../../b", "", "./"] {
+            let json = serde_json::json!({ path: "x" }).to_string();
+            assert!(parse(&overrides(&json)).is_err(), "{path:?}");
+        }
+        assert!(parse(&overrides("[\"not a map\"]")).is_err());
+        assert!(parse(&BTreeMap::new()).unwrap().is_empty());
+
+        let root = temp_dir("symlink");
+        let outside = temp_dir("symlink-outside");
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        let err = apply(&root, &overrides(r#"{"link/x.txt": "x"}"#)).unwrap_err();
+        assert!(err.to_string().contains("symlink"), "{err}");
+        assert!(!outside.join("x.txt").exists());
+
+        let target = outside.join("target.txt");
+        std::fs::write(&target, "untouched").unwrap();
+        std::os::unix::fs::symlink(&target, root.join("x.alloy-override.tmp")).unwrap();
+        apply(&root, &overrides(r#"{"x.txt": "x"}"#)).unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "untouched");
+        assert_eq!(std::fs::read_to_string(root.join("x.txt")).unwrap(), "x");
+
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_dir_all(&outside);
+    }
+
+    #[test]
+    fn overrides_replace_generated_config() {
+        let root = temp_dir("precedence");
+        let params = crate::terraria::validate_vanilla_params(&BTreeMap::new()).unwrap();
+        crate::terraria::ensure_vanilla_instance_layout(&root, &params).unwrap();
+
+        let json = serde_json::json!({
+            "config/serverconfig.txt": "maxplayers=42\n",
+            "config/extra/notes.txt": "hi",
+        })
+        .to_string();
+        assert_eq!(apply(&root, &overrides(&json)).unwrap(), 2);
+        let cfg = std::fs::read_to_string(root.join("config/serverconfig.txt")).unwrap();
+        assert_eq!(cfg, "maxplayers=42\n");
+        assert_eq!(
+            std::fs::read_to_string(root.join("config/extra/notes.txt")).unwrap(),
+            "hi"
+        );
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
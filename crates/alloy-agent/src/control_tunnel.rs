@@ -8,19 +8,27 @@ use tokio_tungstenite::tungstenite::{Message as WsMessage, client::IntoClientReq
 use tracing::{Instrument, info_span};
 
 use alloy_proto::agent_v1::{
-    ClearCacheRequest, CreateInstanceRequest, DeleteInstancePreviewRequest, DeleteInstanceRequest,
-    GetCacheStatsRequest, GetCapabilitiesRequest, GetInstanceRequest, GetStatusRequest,
-    GetWarmTemplateProgressRequest, HealthCheckRequest, ImportSaveFromUrlRequest,
-    ListDirRequest, ListInstancesRequest, ListProcessesRequest, ListTemplatesRequest,
-    MkdirRequest, ReadFileRequest, RenameRequest, StartFromTemplateRequest,
-    StartInstanceRequest, StopInstanceRequest, StopProcessRequest, TailFileRequest,
-    TailLogsRequest, UpdateInstanceRequest, WarmTemplateCacheRequest,
-    WriteFileRequest, agent_health_service_server::AgentHealthService,
+    ApplyUpdateRequest, BulkApplyParamsRequest, CancelStagedUpgradeRequest,
+    CancelWarmTemplateRequest, ClearCacheRequest, CreateBackupRequest, CreateInstanceRequest,
+    DeleteExportRequest, DeleteInstancePreviewRequest, DeleteInstanceRequest,
+    ExportInstanceRequest, GetCacheStatsRequest, GetCapabilitiesRequest,
+    GetInstanceDiskUsageRequest, GetInstanceRequest, GetNodeResourcesRequest,
+    GetResourceHistoryRequest, GetStagedUpgradeRequest, GetStatusRequest,
+    GetVersionRequirementsRequest, GetWarmTemplateProgressRequest, HealthCheckRequest,
+    ImportInstanceRequest, ImportSaveFromUrlRequest, InspectInstanceRequest, ListBackupsRequest,
+    ListDirRequest, ListInstancesRequest, ListProcessesRequest, ListTemplatesRequest, MkdirRequest,
+    PrepareUpdateRequest, ReadExportChunkRequest, ReadFileRequest, ReloadInstanceRequest,
+    ReloadTemplatesRequest, RenameRequest, RestartInstanceRequest, RestoreBackupRequest,
+    SendConsoleRequest, StageUpgradeRequest, StartFromTemplateRequest, StartInstanceRequest,
+    StopInstanceRequest, StopProcessRequest, TailFileRequest, TailLogsRequest,
+    UpdateInstanceRequest, ValidateTemplateRequest, WarmTemplateCacheRequest, WriteFileRequest,
+    WriteImportChunkRequest, agent_health_service_server::AgentHealthService,
     filesystem_service_server::FilesystemService, instance_service_server::InstanceService,
     logs_service_server::LogsService, process_service_server::ProcessService,
 };
 use tonic::{Request, Status};
 
+use crate::agent_audit::{AgentAudit, AgentAuditEvent};
 use crate::process_manager::ProcessManager;
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -35,7 +43,11 @@ enum AgentToControlFrame {
         payload_b64: Option<String>,
         status_code: Option<i32>,
         status_message: Option<String>,
+        agent_version: &'static str,
+        protocol_version: u32,
     },
+    #[serde(rename = "audit")]
+    Audit { event: AgentAuditEvent },
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -63,8 +75,8 @@ struct AgentRpc {
 impl AgentRpc {
     fn new(manager: ProcessManager) -> Self {
         Self {
-            health: crate::health_service::HealthApi,
-            fs: crate::filesystem_service::FilesystemApi,
+            health: crate::health_service::HealthApi::new(manager.clone()),
+            fs: crate::filesystem_service::FilesystemApi::new(manager.clone()),
             logs: crate::logs_service::LogsApi,
             process: crate::process_service::ProcessApi::new(manager.clone()),
             instance: crate::instance_service::InstanceApi::new(manager),
@@ -82,6 +94,33 @@ impl AgentRpc {
                 let resp = self.health.check(Request::new(req)).await?.into_inner();
                 Ok(resp.encode_to_vec())
             }
+            "/alloy.agent.v1.AgentHealthService/PrepareUpdate" => {
+                let req: PrepareUpdateRequest = self.decode_req(payload)?;
+                let resp = self
+                    .health
+                    .prepare_update(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.AgentHealthService/ApplyUpdate" => {
+                let req: ApplyUpdateRequest = self.decode_req(payload)?;
+                let resp = self
+                    .health
+                    .apply_update(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.AgentHealthService/GetNodeResources" => {
+                let req: GetNodeResourcesRequest = self.decode_req(payload)?;
+                let resp = self
+                    .health
+                    .get_node_resources(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
 
             "/alloy.agent.v1.FilesystemService/GetCapabilities" => {
                 let req: GetCapabilitiesRequest = self.decode_req(payload)?;
@@ -138,6 +177,15 @@ impl AgentRpc {
                     .into_inner();
                 Ok(resp.encode_to_vec())
             }
+            "/alloy.agent.v1.ProcessService/ReloadTemplates" => {
+                let req: ReloadTemplatesRequest = self.decode_req(payload)?;
+                let resp = self
+                    .process
+                    .reload_templates(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
             "/alloy.agent.v1.ProcessService/StartFromTemplate" => {
                 let req: StartFromTemplateRequest = self.decode_req(payload)?;
                 let resp = self
@@ -165,6 +213,15 @@ impl AgentRpc {
                     .into_inner();
                 Ok(resp.encode_to_vec())
             }
+            "/alloy.agent.v1.ProcessService/CancelWarmTemplate" => {
+                let req: CancelWarmTemplateRequest = self.decode_req(payload)?;
+                let resp = self
+                    .process
+                    .cancel_warm_template(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
             "/alloy.agent.v1.ProcessService/GetCacheStats" => {
                 let req: GetCacheStatsRequest = self.decode_req(payload)?;
                 let resp = self
@@ -188,6 +245,15 @@ impl AgentRpc {
                 let resp = self.process.stop(Request::new(req)).await?.into_inner();
                 Ok(resp.encode_to_vec())
             }
+            "/alloy.agent.v1.ProcessService/SendConsole" => {
+                let req: SendConsoleRequest = self.decode_req(payload)?;
+                let resp = self
+                    .process
+                    .send_console(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
             "/alloy.agent.v1.ProcessService/ListProcesses" => {
                 let req: ListProcessesRequest = self.decode_req(payload)?;
                 let resp = self
@@ -206,6 +272,15 @@ impl AgentRpc {
                     .into_inner();
                 Ok(resp.encode_to_vec())
             }
+            "/alloy.agent.v1.ProcessService/GetResourceHistory" => {
+                let req: GetResourceHistoryRequest = self.decode_req(payload)?;
+                let resp = self
+                    .process
+                    .get_resource_history(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
             "/alloy.agent.v1.ProcessService/TailLogs" => {
                 let req: TailLogsRequest = self.decode_req(payload)?;
                 let resp = self
@@ -215,6 +290,24 @@ impl AgentRpc {
                     .into_inner();
                 Ok(resp.encode_to_vec())
             }
+            "/alloy.agent.v1.ProcessService/GetVersionRequirements" => {
+                let req: GetVersionRequirementsRequest = self.decode_req(payload)?;
+                let resp = self
+                    .process
+                    .get_version_requirements(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.ProcessService/ValidateTemplate" => {
+                let req: ValidateTemplateRequest = self.decode_req(payload)?;
+                let resp = self
+                    .process
+                    .validate_template(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
 
             "/alloy.agent.v1.InstanceService/Create" => {
                 let req: CreateInstanceRequest = self.decode_req(payload)?;
@@ -241,11 +334,35 @@ impl AgentRpc {
                 let resp = self.instance.stop(Request::new(req)).await?.into_inner();
                 Ok(resp.encode_to_vec())
             }
+            "/alloy.agent.v1.InstanceService/Inspect" => {
+                let req: InspectInstanceRequest = self.decode_req(payload)?;
+                let resp = self.instance.inspect(Request::new(req)).await?.into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/Reload" => {
+                let req: ReloadInstanceRequest = self.decode_req(payload)?;
+                let resp = self.instance.reload(Request::new(req)).await?.into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/Restart" => {
+                let req: RestartInstanceRequest = self.decode_req(payload)?;
+                let resp = self.instance.restart(Request::new(req)).await?.into_inner();
+                Ok(resp.encode_to_vec())
+            }
             "/alloy.agent.v1.InstanceService/Update" => {
                 let req: UpdateInstanceRequest = self.decode_req(payload)?;
                 let resp = self.instance.update(Request::new(req)).await?.into_inner();
                 Ok(resp.encode_to_vec())
             }
+            "/alloy.agent.v1.InstanceService/BulkApplyParams" => {
+                let req: BulkApplyParamsRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .bulk_apply_params(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
             "/alloy.agent.v1.InstanceService/ImportSaveFromUrl" => {
                 let req: ImportSaveFromUrlRequest = self.decode_req(payload)?;
                 let resp = self
@@ -255,6 +372,114 @@ impl AgentRpc {
                     .into_inner();
                 Ok(resp.encode_to_vec())
             }
+            "/alloy.agent.v1.InstanceService/CreateBackup" => {
+                let req: CreateBackupRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .create_backup(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/ListBackups" => {
+                let req: ListBackupsRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .list_backups(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/RestoreBackup" => {
+                let req: RestoreBackupRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .restore_backup(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/StageUpgrade" => {
+                let req: StageUpgradeRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .stage_upgrade(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/GetStagedUpgrade" => {
+                let req: GetStagedUpgradeRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .get_staged_upgrade(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/CancelStagedUpgrade" => {
+                let req: CancelStagedUpgradeRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .cancel_staged_upgrade(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/ExportInstance" => {
+                let req: ExportInstanceRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .export_instance(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/ReadExportChunk" => {
+                let req: ReadExportChunkRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .read_export_chunk(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/DeleteExport" => {
+                let req: DeleteExportRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .delete_export(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/WriteImportChunk" => {
+                let req: WriteImportChunkRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .write_import_chunk(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/ImportInstance" => {
+                let req: ImportInstanceRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .import_instance(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
+            "/alloy.agent.v1.InstanceService/GetDiskUsage" => {
+                let req: GetInstanceDiskUsageRequest = self.decode_req(payload)?;
+                let resp = self
+                    .instance
+                    .get_disk_usage(Request::new(req))
+                    .await?
+                    .into_inner();
+                Ok(resp.encode_to_vec())
+            }
             "/alloy.agent.v1.InstanceService/DeletePreview" => {
                 let req: DeleteInstancePreviewRequest = self.decode_req(payload)?;
                 let resp = self
@@ -322,6 +547,7 @@ pub fn spawn(manager: ProcessManager) {
 
     let node = node_name();
     let token = node_token();
+    let audit = manager.audit();
     let rpc = AgentRpc::new(manager);
 
     tokio::spawn(async move {
@@ -329,7 +555,7 @@ pub fn spawn(manager: ProcessManager) {
         async move {
             let mut backoff = Duration::from_millis(500);
             loop {
-                let res = run_once(&url, &node, token.as_deref(), &rpc).await;
+                let res = run_once(&url, &node, token.as_deref(), &rpc, &audit).await;
                 match res {
                     Ok(()) => {
                         // Clean close; reconnect with a small delay.
@@ -348,11 +574,33 @@ pub fn spawn(manager: ProcessManager) {
     });
 }
 
+/// Sends queued audit events to control; whatever cannot be sent stays queued
+/// for the next connection.
+async fn forward_audit(audit: AgentAudit, out_tx: mpsc::Sender<WsMessage>) {
+    loop {
+        let mut events = audit.drain().into_iter();
+        while let Some(event) = events.next() {
+            let frame = AgentToControlFrame::Audit {
+                event: event.clone(),
+            };
+            let Ok(text) = serde_json::to_string(&frame) else {
+                continue;
+            };
+            if out_tx.send(WsMessage::Text(text.into())).await.is_err() {
+                audit.requeue(std::iter::once(event).chain(events).collect());
+                return;
+            }
+        }
+        audit.wait().await;
+    }
+}
+
 async fn run_once(
     url: &str,
     node: &str,
     token: Option<&str>,
     rpc: &AgentRpc,
+    audit: &AgentAudit,
 ) -> anyhow::Result<()> {
     let mut req = url.into_client_request()?;
     if let Some(tok) = token {
@@ -382,6 +630,7 @@ async fn run_once(
             }
         }
     });
+    let audit_forwarder = tokio::spawn(forward_audit(audit.clone(), out_tx.clone()));
 
     while let Some(msg) = stream.next().await {
         let msg = msg?;
@@ -406,6 +655,8 @@ async fn run_once(
                                         Status::invalid_argument("invalid base64").code() as i32,
                                     ),
                                     status_message: Some("invalid base64 payload".to_string()),
+                                    agent_version: env!("CARGO_PKG_VERSION"),
+                                    protocol_version: alloy_proto::PROTOCOL_VERSION,
                                 };
                                 let _ = out_tx
                                     .send(WsMessage::Text(serde_json::to_string(&resp)?.into()))
@@ -428,6 +679,8 @@ async fn run_once(
                                         ),
                                         status_code: None,
                                         status_message: None,
+                                        agent_version: env!("CARGO_PKG_VERSION"),
+                                        protocol_version: alloy_proto::PROTOCOL_VERSION,
                                     },
                                     Err(status) => AgentToControlFrame::Resp {
                                         id,
@@ -435,6 +688,8 @@ async fn run_once(
                                         payload_b64: None,
                                         status_code: Some(status.code() as i32),
                                         status_message: Some(status.message().to_string()),
+                                        agent_version: env!("CARGO_PKG_VERSION"),
+                                        protocol_version: alloy_proto::PROTOCOL_VERSION,
                                     },
                                 };
 
@@ -463,6 +718,7 @@ async fn run_once(
     }
 
     drop(out_tx);
+    audit_forwarder.abort();
     writer.abort();
 
     Ok(())
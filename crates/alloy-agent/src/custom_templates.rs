@@ -0,0 +1,524 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{OnceLock, RwLock},
+};
+
+use alloy_proto::agent_v1::{ParamType, TemplateParam};
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::process_manager_support::custom_template_commands;
+use crate::templates::{GracefulStopSpec, ProcessTemplate};
+
+const MAX_FILE_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateFile {
+    template_id: String,
+    #[serde(default)]
+    display_name: String,
+    command: String,
+    /// Each arg may reference params as `{{key}}`.
+    #[serde(default)]
+    args: Vec<String>,
+    /// Written to stdin on stop, e.g. "stop".
+    graceful_stdin: Option<String>,
+    #[serde(default)]
+    ready_log_markers: Vec<String>,
+    /// The `port` param is allocated free on UDP as well as TCP.
+    #[serde(default)]
+    needs_udp_and_tcp: bool,
+    #[serde(default)]
+    params: Vec<ParamFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ParamKind {
+    String,
+    Int,
+    Bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ParamFile {
+    key: String,
+    #[serde(default)]
+    label: String,
+    #[serde(rename = "type")]
+    kind: ParamKind,
+    #[serde(default)]
+    required: bool,
+    default: Option<toml::Value>,
+    min: Option<i64>,
+    max: Option<i64>,
+    #[serde(default, rename = "enum")]
+    enum_values: Vec<String>,
+    #[serde(default)]
+    secret: bool,
+    #[serde(default)]
+    placeholder: String,
+    #[serde(default)]
+    help: String,
+    #[serde(default)]
+    advanced: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    pub loaded: Vec<String>,
+    /// One entry per rejected file, prefixed with its file name.
+    pub errors: Vec<String>,
+}
+
+fn registry() -> &'static RwLock<Vec<ProcessTemplate>> {
+    static REGISTRY: OnceLock<RwLock<Vec<ProcessTemplate>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+pub fn dir() -> PathBuf {
+    crate::minecraft::data_root().join("templates")
+}
+
+pub fn list() -> Vec<ProcessTemplate> {
+    registry().read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn is_custom(template_id: &str) -> bool {
+    registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .any(|t| t.template_id == template_id)
+}
+
+fn find(template_id: &str) -> Option<ProcessTemplate> {
+    registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|t| t.template_id == template_id)
+        .cloned()
+}
+
+/// Re-reads `<data root>/templates/*.toml` and replaces the loaded set.
+/// Invalid files are skipped and reported; the rest still load.
+pub fn reload() -> LoadReport {
+    let (templates, errors) = load_dir(&dir());
+    let report = LoadReport {
+        loaded: templates.iter().map(|t| t.template_id.clone()).collect(),
+        errors,
+    };
+    *registry().write().unwrap_or_else(|e| e.into_inner()) = templates;
+    report
+}
+
+fn load_dir(dir: &Path) -> (Vec<ProcessTemplate>, Vec<String>) {
+    let mut templates = Vec::new();
+    let mut errors = Vec::new();
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(rd) => rd
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "toml") && p.is_file())
+            .collect(),
+        Err(_) => return (templates, errors),
+    };
+    paths.sort();
+
+    let mut taken: HashSet<String> = crate::templates::builtin_template_ids()
+        .into_iter()
+        .collect();
+    for path in paths {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match read_file(&path).and_then(|raw| parse(&raw, &taken)) {
+            Ok(t) => {
+                taken.insert(t.template_id.clone());
+                templates.push(t);
+            }
+            Err(e) => errors.push(format!("{name}: {e:#}")),
+        }
+    }
+    (templates, errors)
+}
+
+fn read_file(path: &Path) -> anyhow::Result<String> {
+    let len = std::fs::metadata(path)?.len();
+    if len > MAX_FILE_BYTES {
+        anyhow::bail!("file is larger than {MAX_FILE_BYTES} bytes");
+    }
+    Ok(std::fs::read_to_string(path)?)
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id.contains(':')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '-' | '_' | '.'))
+}
+
+fn valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= 64
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Replaces each `{{key}}` in `arg` via `lookup`.
+fn substitute<'a>(arg: &'a str, mut lookup: impl FnMut(&'a str) -> String) -> String {
+    let mut out = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&lookup(rest[start + 2..start + 2 + len].trim()));
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn command_allowed(command: &str, allowed: &[String]) -> bool {
+    if command.starts_with('/') {
+        return !command.split('/').any(|seg| seg == "..");
+    }
+    !command.contains('/') && allowed.iter().any(|a| a == command)
+}
+
+fn default_string(v: &toml::Value) -> anyhow::Result<String> {
+    Ok(match v {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(i) => i.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        other => anyhow::bail!("default must be a string, integer or boolean, got {other}"),
+    })
+}
+
+fn build_param(p: ParamFile) -> anyhow::Result<TemplateParam> {
+    if !valid_key(&p.key) {
+        anyhow::bail!("invalid param key {:?} (use a-z, 0-9 and _)", p.key);
+    }
+    if p.min.is_some() != p.max.is_some() {
+        anyhow::bail!("param {}: set both min and max, or neither", p.key);
+    }
+    let default_value = p
+        .default
+        .as_ref()
+        .map(default_string)
+        .transpose()
+        .with_context(|| format!("param {}", p.key))?
+        .unwrap_or_default();
+    let r#type = match p.kind {
+        ParamKind::String => ParamType::String,
+        ParamKind::Int => ParamType::Int,
+        ParamKind::Bool => ParamType::Bool,
+    };
+    let param = TemplateParam {
+        label: if p.label.is_empty() {
+            p.key.clone()
+        } else {
+            p.label
+        },
+        key: p.key,
+        r#type: r#type as i32,
+        required: p.required,
+        default_value,
+        min_int: p.min.unwrap_or_default(),
+        max_int: p.max.unwrap_or_default(),
+        enum_values: p.enum_values,
+        secret: p.secret,
+        placeholder: p.placeholder,
+        help: p.help,
+        advanced: p.advanced,
+    };
+    if !param.default_value.is_empty()
+        && let Err(e) = check_value(&param, &param.default_value)
+    {
+        anyhow::bail!("param {}: invalid default: {e}", param.key);
+    }
+    if param.min_int > param.max_int {
+        anyhow::bail!("param {}: min is greater than max", param.key);
+    }
+    Ok(param)
+}
+
+/// Parses one template file. `taken` holds ids that may not be reused.
+fn parse(raw: &str, taken: &HashSet<String>) -> anyhow::Result<ProcessTemplate> {
+    let file: TemplateFile = toml::from_str(raw).context("parse template toml")?;
+    let template_id = file.template_id.trim().to_string();
+    if !valid_id(&template_id) {
+        anyhow::bail!(
+            "invalid template_id {template_id:?} (use <game>:<name> with a-z, 0-9, -, _ and .)"
+        );
+    }
+    if taken.contains(&template_id) {
+        anyhow::bail!("template_id {template_id} is already defined");
+    }
+    let command = file.command.trim().to_string();
+    let allowed = custom_template_commands();
+    if !command_allowed(&command, &allowed) {
+        anyhow::bail!(
+            "command {command:?} must be an absolute path or one of: {}",
+            allowed.join(", ")
+        );
+    }
+
+    let mut params = Vec::with_capacity(file.params.len());
+    let mut keys = HashSet::new();
+    for p in file.params {
+        let p = build_param(p)?;
+        if !keys.insert(p.key.clone()) {
+            anyhow::bail!("param {} is declared twice", p.key);
+        }
+        params.push(p);
+    }
+    for arg in &file.args {
+        let mut undeclared = None;
+        substitute(arg, |key| {
+            if !keys.contains(key) {
+                undeclared.get_or_insert(key);
+            }
+            String::new()
+        });
+        if let Some(key) = undeclared {
+            anyhow::bail!("arg {arg:?} references undeclared param {key:?}");
+        }
+    }
+
+    Ok(ProcessTemplate {
+        display_name: if file.display_name.trim().is_empty() {
+            template_id.clone()
+        } else {
+            file.display_name.trim().to_string()
+        },
+        template_id,
+        command,
+        args: file.args,
+        params,
+        graceful_stop: file
+            .graceful_stdin
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| GracefulStopSpec {
+                stdin_command: Some(format!("{}\n", s.trim_end_matches('\n'))),
+                ..Default::default()
+            }),
+        ready_log_markers: file.ready_log_markers,
+        needs_udp_and_tcp: file.needs_udp_and_tcp,
+    })
+}
+
+fn check_value(p: &TemplateParam, value: &str) -> Result<(), String> {
+    if p.r#type == ParamType::Int as i32 {
+        let v: i64 = value
+            .parse()
+            .map_err(|_| "Must be an integer.".to_string())?;
+        if (p.min_int != 0 || p.max_int != 0) && !(p.min_int..=p.max_int).contains(&v) {
+            return Err(format!("Must be between {} and {}.", p.min_int, p.max_int));
+        }
+    } else if p.r#type == ParamType::Bool as i32 {
+        if value != "true" && value != "false" {
+            return Err("Must be true or false.".to_string());
+        }
+    } else if !p.enum_values.is_empty() && !p.enum_values.iter().any(|e| e == value) {
+        return Err(format!("Must be one of: {}.", p.enum_values.join(", ")));
+    }
+    Ok(())
+}
+
+/// Validates the declared params and renders `{{key}}` in the args.
+fn render_args(
+    custom: &ProcessTemplate,
+    params: &BTreeMap<String, String>,
+) -> anyhow::Result<Vec<String>> {
+    let mut values = BTreeMap::new();
+    let mut field_errors = BTreeMap::new();
+    for p in &custom.params {
+        let value = params
+            .get(&p.key)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .unwrap_or(&p.default_value);
+        if value.is_empty() {
+            if p.required {
+                field_errors.insert(p.key.clone(), "Required.".to_string());
+            }
+        } else if let Err(e) = check_value(p, value) {
+            field_errors.insert(p.key.clone(), e);
+        }
+        values.insert(p.key.as_str(), value);
+    }
+    if !field_errors.is_empty() {
+        return Err(crate::error_payload::anyhow(
+            "invalid_param",
+            format!("invalid {} params", custom.template_id),
+            Some(field_errors),
+            None,
+        ));
+    }
+
+    Ok(custom
+        .args
+        .iter()
+        .map(|arg| {
+            substitute(arg, |key| {
+                values.get(key).copied().unwrap_or_default().to_string()
+            })
+        })
+        .collect())
+}
+
+/// Whether the template declares an int `port` param the agent should assign.
+pub fn declares_port(t: &ProcessTemplate) -> bool {
+    t.params
+        .iter()
+        .any(|p| p.key == "port" && p.r#type == ParamType::Int as i32)
+}
+
+/// Reserves the `port` param for `owner` (0 or blank picks a free one) and
+/// renders the args with it. Returns the args and the params as launched.
+pub fn prepare_launch(
+    owner: &str,
+    t: &ProcessTemplate,
+    params: &BTreeMap<String, String>,
+) -> anyhow::Result<(Vec<String>, BTreeMap<String, String>)> {
+    let mut params = params.clone();
+    if declares_port(t) {
+        let preferred = params
+            .get("port")
+            .and_then(|v| v.trim().parse::<u16>().ok())
+            .unwrap_or(0);
+        let reserved = if t.needs_udp_and_tcp {
+            crate::port_alloc::reserve_dual_port(owner, preferred)
+        } else {
+            crate::port_alloc::reserve_tcp_port(owner, preferred)
+        };
+        let port = reserved.map_err(|e| {
+            crate::error_payload::anyhow(
+                "invalid_param",
+                "invalid port",
+                Some(BTreeMap::from([("port".to_string(), e.to_string())])),
+                Some(
+                    "Pick another port, or leave it blank (0) to auto-assign a free port."
+                        .to_string(),
+                ),
+            )
+        })?;
+        params.insert("port".to_string(), port.to_string());
+    }
+    let args = apply_params(t.clone(), &params)?.args;
+    Ok((args, params))
+}
+
+pub fn apply_params(
+    mut t: ProcessTemplate,
+    params: &BTreeMap<String, String>,
+) -> anyhow::Result<ProcessTemplate> {
+    let Some(custom) = find(&t.template_id) else {
+        anyhow::bail!("unknown template_id: {}", t.template_id);
+    };
+    t.args = render_args(&custom, params)?;
+    Ok(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashSet};
+
+    use super::{load_dir, parse, render_args};
+
+    const VALID: &str = r#"
+template_id = "valheim:vanilla"
+display_name = "Valheim"
+command = "/opt/valheim/valheim_server.x86_64"
+args = ["-nographics", "-port", "{{port}}", "-name", "{{name}}"]
+graceful_stdin = "quit"
+needs_udp_and_tcp = true
+
+[[params]]
+key = "port"
+type = "int"
+default = 2456
+min = 1024
+max = 65535
+
+[[params]]
+key = "name"
+type = "string"
+required = true
+"#;
+
+    #[test]
+    fn valid_file_loads_and_renders_args() {
+        let t = parse(VALID, &HashSet::new()).unwrap();
+        assert_eq!(t.template_id, "valheim:vanilla");
+        assert_eq!(t.params.len(), 2);
+        assert!(t.needs_udp_and_tcp);
+        assert_eq!(t.params[0].default_value, "2456");
+        assert_eq!(
+            t.graceful_stop.as_ref().unwrap().stdin_command.as_deref(),
+            Some("quit\n")
+        );
+
+        let params = BTreeMap::from([("name".to_string(), "My World".to_string())]);
+        assert_eq!(
+            render_args(&t, &params).unwrap(),
+            ["-nographics", "-port", "2456", "-name", "My World"]
+        );
+
+        let bad = BTreeMap::from([("port".to_string(), "80".to_string())]);
+        let err = render_args(&t, &bad).unwrap_err();
+        let fields = crate::error_payload::decode(&err)
+            .and_then(|p| p.field_errors)
+            .unwrap();
+        assert!(fields.contains_key("port") && fields.contains_key("name"));
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let taken = HashSet::from(["minecraft:vanilla".to_string()]);
+        let cases = [
+            ("template_id = ", "parse template toml"),
+            (
+                &VALID.replace("valheim:vanilla", "minecraft:vanilla"),
+                "already defined",
+            ),
+            (
+                &VALID.replace("/opt/valheim/valheim_server.x86_64", "curl"),
+                "absolute path",
+            ),
+            (&VALID.replace("{{name}}", "{{world}}"), "undeclared param"),
+            (
+                &VALID.replace("default = 2456", "default = 80"),
+                "invalid default",
+            ),
+        ];
+        for (raw, want) in cases {
+            let err = format!("{:#}", parse(raw, &taken).unwrap_err());
+            assert!(err.contains(want), "{want}: {err}");
+        }
+
+        let dir = std::env::temp_dir().join(format!(
+            "alloy-agent-custom-templates-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.toml"), VALID).unwrap();
+        std::fs::write(dir.join("b.toml"), VALID).unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        let (loaded, errors) = load_dir(&dir);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("b.toml: "), "{errors:?}");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::{Path, PathBuf};
+
+// Bounds the walk so a tree with millions of tiny files cannot pin the agent.
+const MAX_ENTRIES: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct DiskUsage {
+    pub total_bytes: u64,
+    pub world_bytes: u64,
+    pub logs_bytes: u64,
+    pub backups_bytes: u64,
+    // Hard-linked with the shared download cache; deleting the instance keeps them.
+    pub cache_shared_bytes: u64,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Category {
+    World,
+    Logs,
+    Backups,
+    Other,
+}
+
+fn classify(template_id: &str, top_level_name: &str) -> Category {
+    if top_level_name == "logs" {
+        Category::Logs
+    } else if top_level_name == crate::backup::BACKUPS_DIR {
+        Category::Backups
+    } else if crate::backup::is_world_root(template_id, top_level_name) {
+        Category::World
+    } else {
+        Category::Other
+    }
+}
+
+#[cfg(unix)]
+fn is_cache_shared(meta: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn is_cache_shared(_meta: &std::fs::Metadata) -> bool {
+    false
+}
+
+impl DiskUsage {
+    fn add(&mut self, category: Category, meta: &std::fs::Metadata) {
+        let len = meta.len();
+        self.total_bytes = self.total_bytes.saturating_add(len);
+        match category {
+            Category::World => self.world_bytes = self.world_bytes.saturating_add(len),
+            Category::Logs => self.logs_bytes = self.logs_bytes.saturating_add(len),
+            Category::Backups => self.backups_bytes = self.backups_bytes.saturating_add(len),
+            Category::Other => {}
+        }
+        if is_cache_shared(meta) {
+            self.cache_shared_bytes = self.cache_shared_bytes.saturating_add(len);
+        }
+    }
+}
+
+/// Sums the regular files under `instance_dir` by top-level category.
+/// Symlinks are counted as nothing and never followed, so the walk stays
+/// inside the instance.
+pub(crate) async fn instance_disk_usage(
+    instance_dir: &Path,
+    template_id: &str,
+) -> std::io::Result<DiskUsage> {
+    walk(instance_dir, template_id, MAX_ENTRIES).await
+}
+
+async fn walk(root: &Path, template_id: &str, max_entries: u64) -> std::io::Result<DiskUsage> {
+    if !tokio::fs::symlink_metadata(root).await?.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotADirectory,
+            format!("{} is not a directory", root.display()),
+        ));
+    }
+
+    let mut usage = DiskUsage::default();
+    let mut visited: u64 = 0;
+    let mut stack: Vec<(PathBuf, Option<Category>)> = vec![(root.to_path_buf(), None)];
+    while let Some((dir, category)) = stack.pop() {
+        let Ok(mut rd) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = rd.next_entry().await {
+            visited += 1;
+            if visited > max_entries {
+                usage.truncated = true;
+                return Ok(usage);
+            }
+            let category = category
+                .unwrap_or_else(|| classify(template_id, &entry.file_name().to_string_lossy()));
+            // Does not traverse symlinks.
+            let Ok(meta) = entry.metadata().await else {
+                continue;
+            };
+            if meta.is_dir() {
+                stack.push((entry.path(), Some(category)));
+            } else if meta.is_file() {
+                usage.add(category, &meta);
+            }
+        }
+    }
+    Ok(usage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiskUsage, walk};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[tokio::test]
+    async fn usage_is_split_by_top_level_category() {
+        let root = std::env::temp_dir().join(format!(
+            "alloy-agent-disk-usage-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        ));
+        let inst = root.join("inst");
+        let write = |rel: &str, len: usize| {
+            let p = inst.join(rel);
+            std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+            std::fs::write(p, vec![0u8; len]).unwrap();
+        };
+        write("world/level.dat", 100);
+        write("world/region/r.0.0.mca", 1000);
+        write("world_nether/DIM-1/region/r.0.0.mca", 500);
+        write("logs/latest.log", 40);
+        write("logs/console.log", 60);
+        write("backups/a.tar.zst", 300);
+        write("config/server.properties", 7);
+
+        // server.jar shares its inode with the download cache.
+        let cached = root.join("cache").join("server.jar");
+        std::fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        std::fs::write(&cached, vec![0u8; 2000]).unwrap();
+        std::fs::hard_link(&cached, inst.join("server.jar")).unwrap();
+
+        // Symlinks are neither counted nor followed out of the instance.
+        std::os::unix::fs::symlink(root.join("cache"), inst.join("world").join("escape")).unwrap();
+        std::os::unix::fs::symlink("config/server.properties", inst.join("server.properties"))
+            .unwrap();
+
+        let usage = walk(&inst, "minecraft:vanilla", 1000).await.unwrap();
+        assert_eq!(
+            usage,
+            DiskUsage {
+                total_bytes: 100 + 1000 + 500 + 40 + 60 + 300 + 7 + 2000,
+                world_bytes: 1600,
+                logs_bytes: 100,
+                backups_bytes: 300,
+                cache_shared_bytes: 2000,
+                truncated: false,
+            }
+        );
+
+        let capped = walk(&inst, "minecraft:vanilla", 3).await.unwrap();
+        assert!(capped.truncated);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
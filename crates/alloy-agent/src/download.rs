@@ -0,0 +1,528 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use reqwest::{StatusCode, header};
+use tokio::io::AsyncWriteExt;
+
+const MAX_ATTEMPTS: u32 = 5;
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(8);
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        // No overall timeout: a stalled read fails the attempt and the next
+        // one resumes where it stopped.
+        reqwest::Client::builder()
+            .user_agent("alloy-agent")
+            .connect_timeout(Duration::from_secs(30))
+            .read_timeout(Duration::from_secs(60))
+            .build()
+            .expect("failed to build reqwest client")
+    })
+}
+
+fn download_chunk_threshold(total_bytes: u64) -> u64 {
+    if total_bytes >= 2 * 1024 * 1024 * 1024 {
+        8 * 1024 * 1024
+    } else if total_bytes >= 512 * 1024 * 1024 {
+        4 * 1024 * 1024
+    } else {
+        1024 * 1024
+    }
+}
+
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    dest.with_file_name(name)
+}
+
+/// Start offset of a `Content-Range: bytes <start>-<end>/<total>` header, and
+/// the total if known.
+fn parse_content_range(v: &str) -> Option<(u64, Option<u64>)> {
+    let (range, total) = v.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split_once('-')?.0.trim().parse().ok()?;
+    Some((start, total.trim().parse().ok()))
+}
+
+enum AttemptError {
+    // Worth retrying: network errors, 5xx, 408/429.
+    Transient(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
+struct Progress<F> {
+    on_progress: F,
+    started_at: Instant,
+    // Bytes fetched by this call, excluding resumed ones; used for speed.
+    fetched: u64,
+    last_emit_bytes: u64,
+    last_emit_at: Instant,
+}
+
+impl<F: FnMut(u64, u64, u64)> Progress<F> {
+    fn speed(&self) -> u64 {
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            (self.fetched as f64 / elapsed).round() as u64
+        } else {
+            0
+        }
+    }
+
+    fn report(&mut self, downloaded: u64, total: u64, force: bool) {
+        let now = Instant::now();
+        let threshold = download_chunk_threshold(total.max(1));
+        if force
+            || downloaded.saturating_sub(self.last_emit_bytes) >= threshold
+            || now.duration_since(self.last_emit_at) >= Duration::from_millis(300)
+        {
+            let speed = self.speed();
+            (self.on_progress)(downloaded, total.max(downloaded), speed);
+            self.last_emit_bytes = downloaded;
+            self.last_emit_at = now;
+        }
+    }
+}
+
+struct ResumeState {
+    // Bytes already in the partial file.
+    offset: u64,
+    // None until the first response says whether ranges are supported.
+    accept_ranges: Option<bool>,
+    // ETag or Last-Modified, sent as If-Range so a changed file restarts.
+    validator: Option<String>,
+    total: u64,
+}
+
+async fn attempt<F: FnMut(u64, u64, u64)>(
+    url: &str,
+    partial: &Path,
+    max_bytes: u64,
+    state: &mut ResumeState,
+    progress: &mut Progress<F>,
+) -> Result<(), AttemptError> {
+    let mut req = http_client().get(url);
+    let resuming = state.offset > 0 && state.accept_ranges == Some(true);
+    if resuming {
+        req = req.header(header::RANGE, format!("bytes={}-", state.offset));
+        if let Some(v) = &state.validator {
+            req = req.header(header::IF_RANGE, v);
+        }
+    }
+    let resp = req
+        .send()
+        .await
+        .with_context(|| format!("download {url}"))
+        .map_err(AttemptError::Transient)?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        let err = anyhow::anyhow!("download {url}: http status {status}");
+        return Err(
+            if status.is_server_error()
+                || status == StatusCode::REQUEST_TIMEOUT
+                || status == StatusCode::TOO_MANY_REQUESTS
+            {
+                AttemptError::Transient(err)
+            } else {
+                AttemptError::Fatal(err)
+            },
+        );
+    }
+
+    let headers = resp.headers();
+    let content_range = headers
+        .get(header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_content_range);
+    let append = match (status, content_range) {
+        (StatusCode::PARTIAL_CONTENT, Some((start, total)))
+            if resuming && start == state.offset =>
+        {
+            if let Some(total) = total {
+                state.total = total;
+            }
+            true
+        }
+        (StatusCode::PARTIAL_CONTENT, _) => {
+            // A range we did not ask for; start over without ranges.
+            state.offset = 0;
+            state.accept_ranges = Some(false);
+            return Err(AttemptError::Transient(anyhow::anyhow!(
+                "download {url}: unexpected partial content"
+            )));
+        }
+        _ => {
+            state.offset = 0;
+            state.accept_ranges = Some(
+                headers
+                    .get(header::ACCEPT_RANGES)
+                    .and_then(|v| v.to_str().ok())
+                    .is_some_and(|v| v.trim().eq_ignore_ascii_case("bytes")),
+            );
+            state.validator = headers
+                .get(header::ETAG)
+                .or_else(|| headers.get(header::LAST_MODIFIED))
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            state.total = resp.content_length().unwrap_or(0);
+            false
+        }
+    };
+    if state.total > max_bytes {
+        return Err(AttemptError::Fatal(anyhow::anyhow!("download too large")));
+    }
+
+    let mut f = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(partial)
+        .await
+        .with_context(|| format!("open {}", partial.display()))
+        .map_err(AttemptError::Fatal)?;
+
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk
+            .with_context(|| format!("download {url} (body)"))
+            .map_err(AttemptError::Transient)?;
+        state.offset = state.offset.saturating_add(chunk.len() as u64);
+        progress.fetched = progress.fetched.saturating_add(chunk.len() as u64);
+        if state.offset > max_bytes {
+            return Err(AttemptError::Fatal(anyhow::anyhow!("download too large")));
+        }
+        f.write_all(&chunk)
+            .await
+            .context("write download")
+            .map_err(AttemptError::Fatal)?;
+        progress.report(state.offset, state.total, false);
+    }
+    f.flush()
+        .await
+        .context("flush download")
+        .map_err(AttemptError::Fatal)?;
+    f.sync_all().await.ok();
+
+    if state.total > 0 && state.offset < state.total {
+        return Err(AttemptError::Transient(anyhow::anyhow!(
+            "download {url}: connection closed at {} of {} bytes",
+            state.offset,
+            state.total
+        )));
+    }
+    Ok(())
+}
+
+/// Removes the partial file if the download is abandoned, including when the
+/// download future is dropped because the caller cancelled it.
+struct PartialGuard<'a> {
+    path: &'a Path,
+    armed: bool,
+}
+
+impl Drop for PartialGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            let _ = std::fs::remove_file(self.path);
+        }
+    }
+}
+
+/// Downloads `url` to `dest` via `dest.partial`, renamed into place only once
+/// complete. Transient failures are retried with backoff, resuming with a
+/// `Range` request when the server advertised `Accept-Ranges: bytes`.
+/// `on_progress` gets `(downloaded, total, bytes_per_sec)`. Returns the size.
+pub(crate) async fn download_with_resume<F>(
+    url: &str,
+    dest: &Path,
+    max_bytes: u64,
+    on_progress: F,
+) -> anyhow::Result<u64>
+where
+    F: FnMut(u64, u64, u64) + Send,
+{
+    if let Some(parent) = dest.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let partial = partial_path(dest);
+    let mut guard = PartialGuard {
+        path: &partial,
+        armed: true,
+    };
+    let now = Instant::now();
+    let mut progress = Progress {
+        on_progress,
+        started_at: now,
+        fetched: 0,
+        last_emit_bytes: 0,
+        last_emit_at: now,
+    };
+    let mut state = ResumeState {
+        offset: 0,
+        accept_ranges: None,
+        validator: None,
+        total: 0,
+    };
+
+    let mut attempt_no = 1;
+    loop {
+        match attempt(url, &partial, max_bytes, &mut state, &mut progress).await {
+            Ok(()) => break,
+            Err(AttemptError::Transient(e)) if attempt_no < MAX_ATTEMPTS => {
+                let backoff = BACKOFF_BASE
+                    .saturating_mul(2_u32.pow(attempt_no - 1))
+                    .min(BACKOFF_MAX);
+                tracing::warn!(url, attempt = attempt_no, error = %format!("{e:#}"), "download failed; retrying");
+                tokio::time::sleep(backoff).await;
+                attempt_no += 1;
+            }
+            Err(AttemptError::Transient(e)) | Err(AttemptError::Fatal(e)) => return Err(e),
+        }
+    }
+
+    tokio::fs::rename(&partial, dest)
+        .await
+        .with_context(|| format!("rename {} into place", partial.display()))?;
+    guard.armed = false;
+    progress.report(state.offset, state.total, true);
+    Ok(state.offset)
+}
+
+/// Returns a progress callback that produces `"<label> (NN%)"` each time another
+/// `step` percent is reached. Downloads of unknown size report nothing.
+pub(crate) fn percent_messages(
+    label: impl Into<String>,
+    step: u64,
+    mut emit: impl FnMut(String) + Send,
+) -> impl FnMut(u64, u64, u64) + Send {
+    let label = label.into();
+    let step = step.clamp(1, 100);
+    let mut last: Option<u64> = None;
+    move |downloaded, total, _speed| {
+        if total == 0 {
+            return;
+        }
+        let pct = downloaded.min(total).saturating_mul(100) / total;
+        let bucket = pct / step * step;
+        if last.is_none_or(|l| bucket > l) {
+            last = Some(bucket);
+            emit(format!("{label} ({bucket}%)"));
+        }
+    }
+}
+
+/// Tiny HTTP/1.1 server for download tests. `handler` gets the request
+/// index and head and returns the raw response; request heads are recorded.
+#[cfg(test)]
+pub(crate) async fn serve(
+    handler: impl Fn(usize, &str) -> Vec<u8> + Send + Sync + 'static,
+) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(Mutex::new(Vec::<String>::new()));
+    let seen = requests.clone();
+    tokio::spawn(async move {
+        while let Ok((mut sock, _)) = listener.accept().await {
+            let mut buf = vec![0u8; 8192];
+            let n = sock.read(&mut buf).await.unwrap_or(0);
+            let head = String::from_utf8_lossy(&buf[..n]).to_string();
+            let idx = {
+                let mut seen = seen.lock().unwrap();
+                seen.push(head.clone());
+                seen.len() - 1
+            };
+            let _ = sock.write_all(&handler(idx, &head)).await;
+            let _ = sock.shutdown().await;
+        }
+    });
+    (format!("http://{addr}"), requests)
+}
+
+#[cfg(test)]
+pub(crate) fn http_response(status: &str, headers: &[(&str, String)], body: &[u8]) -> Vec<u8> {
+    let mut out = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+    for (k, v) in headers {
+        out.push_str(&format!("{k}: {v}\r\n"));
+    }
+    out.push_str("\r\n");
+    let mut out = out.into_bytes();
+    out.extend_from_slice(body);
+    out
+}
+
+/// Serves `body` to every request and counts the requests.
+#[cfg(test)]
+pub(crate) async fn serve_bytes(
+    body: Vec<u8>,
+) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+    serve(move |_, _| {
+        http_response(
+            "200 OK",
+            &[("Content-Length", body.len().to_string())],
+            &body,
+        )
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        download_with_resume, http_response, parse_content_range, partial_path, percent_messages,
+        serve,
+    };
+
+    fn temp_dest(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join(format!("alloy-agent-download-{}", std::process::id()))
+            .join(name)
+    }
+
+    fn body() -> Vec<u8> {
+        (0..64 * 1024).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn range_start(head: &str) -> Option<usize> {
+        head.lines()
+            .find_map(|l| {
+                l.to_ascii_lowercase()
+                    .strip_prefix("range: bytes=")
+                    .map(str::to_string)
+            })
+            .and_then(|r| r.trim_end_matches('-').parse().ok())
+    }
+
+    #[test]
+    fn content_range_is_parsed() {
+        assert_eq!(
+            parse_content_range("bytes 100-199/200"),
+            Some((100, Some(200)))
+        );
+        assert_eq!(parse_content_range("bytes 5-9/*"), Some((5, None)));
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+    }
+
+    #[tokio::test]
+    async fn dropped_connection_resumes_with_a_range_request() {
+        let body = body();
+        let full = body.clone();
+        let (base, requests) = serve(move |idx, head| match (idx, range_start(head)) {
+            // Advertises ranges, then drops halfway through.
+            (0, None) => http_response(
+                "200 OK",
+                &[
+                    ("Content-Length", full.len().to_string()),
+                    ("Accept-Ranges", "bytes".to_string()),
+                    ("ETag", "\"v1\"".to_string()),
+                ],
+                &full[..full.len() / 2],
+            ),
+            (_, Some(start)) => http_response(
+                "206 Partial Content",
+                &[
+                    ("Content-Length", (full.len() - start).to_string()),
+                    (
+                        "Content-Range",
+                        format!("bytes {start}-{}/{}", full.len() - 1, full.len()),
+                    ),
+                ],
+                &full[start..],
+            ),
+            _ => http_response("500 Internal Server Error", &[], b""),
+        })
+        .await;
+
+        let dest = temp_dest("resume.mrpack");
+        let mut seen = Vec::new();
+        let size = download_with_resume(&format!("{base}/pack"), &dest, u64::MAX, |d, t, _| {
+            seen.push((d, t))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert!(!partial_path(&dest).exists());
+        assert_eq!(size, body.len() as u64);
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(range_start(&requests[1]), Some(body.len() / 2));
+        assert!(
+            requests[1]
+                .to_ascii_lowercase()
+                .contains("if-range: \"v1\"")
+        );
+        assert_eq!(seen.last(), Some(&(body.len() as u64, body.len() as u64)));
+        let _ = std::fs::remove_file(&dest);
+    }
+
+    #[tokio::test]
+    async fn servers_without_ranges_restart_from_zero() {
+        let body = body();
+        let full = body.clone();
+        let (base, requests) = serve(move |idx, _| {
+            let sent = if idx == 0 { &full[..100] } else { &full[..] };
+            http_response(
+                "200 OK",
+                &[("Content-Length", full.len().to_string())],
+                sent,
+            )
+        })
+        .await;
+
+        let dest = temp_dest("no-ranges.zip");
+        download_with_resume(&format!("{base}/zip"), &dest, u64::MAX, |_, _, _| {})
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(range_start(&requests[1]), None);
+        let _ = std::fs::remove_file(&dest);
+    }
+
+    #[tokio::test]
+    async fn client_errors_fail_without_retrying() {
+        let (base, requests) = serve(|_, _| http_response("404 Not Found", &[], b"")).await;
+        let dest = temp_dest("missing.zip");
+        let err = download_with_resume(&format!("{base}/missing"), &dest, u64::MAX, |_, _, _| {})
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("404"), "{err}");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert!(!dest.exists() && !partial_path(&dest).exists());
+    }
+
+    #[test]
+    fn percent_messages_step() {
+        let mut out = Vec::new();
+        {
+            let mut cb = percent_messages("downloading pack", 25, |m| out.push(m));
+            for d in [0, 10, 30, 49, 50, 99, 100] {
+                cb(d, 100, 0);
+            }
+            cb(5, 0, 0);
+        }
+        assert_eq!(
+            out,
+            vec![
+                "downloading pack (0%)",
+                "downloading pack (25%)",
+                "downloading pack (50%)",
+                "downloading pack (75%)",
+                "downloading pack (100%)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use sha1::Digest;
+
+/// Expected digest of a cached artifact, as hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Checksum {
+    Sha1(String),
+    Sha256(String),
+}
+
+impl Checksum {
+    fn expected(&self) -> &str {
+        match self {
+            Checksum::Sha1(v) | Checksum::Sha256(v) => v,
+        }
+    }
+
+    fn compute(&self, path: &Path) -> std::io::Result<String> {
+        let mut f = std::fs::File::open(path)?;
+        Ok(match self {
+            Checksum::Sha1(_) => {
+                let mut h = sha1::Sha1::new();
+                std::io::copy(&mut f, &mut h)?;
+                hex::encode(h.finalize())
+            }
+            Checksum::Sha256(_) => {
+                let mut h = sha2::Sha256::new();
+                std::io::copy(&mut f, &mut h)?;
+                hex::encode(h.finalize())
+            }
+        })
+    }
+}
+
+fn discard(path: &Path, reason: &str) {
+    tracing::warn!(path = %path.display(), reason, "discarding corrupt cached download");
+    let _ = std::fs::remove_file(path);
+}
+
+/// Checks a cache hit against `expected`. A missing file is a miss; a file
+/// that fails the check is deleted so the caller downloads it again.
+pub(crate) async fn verify_cached(path: &Path, expected: &Checksum) -> bool {
+    let (p, sum) = (path.to_path_buf(), expected.clone());
+    let actual = tokio::task::spawn_blocking(move || sum.compute(&p))
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+    match actual {
+        Ok(actual) if actual.eq_ignore_ascii_case(expected.expected()) => true,
+        Ok(actual) => {
+            discard(
+                path,
+                &format!("expected {}, got {actual}", expected.expected()),
+            );
+            false
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+        Err(e) => {
+            discard(path, &e.to_string());
+            false
+        }
+    }
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".sha256");
+    path.with_file_name(name)
+}
+
+/// Records the sha256 of a fresh download that has no upstream checksum, for
+/// [`verify_cached_zip`] to check later cache hits against.
+pub(crate) async fn write_sha256_sidecar(path: &Path) -> anyhow::Result<()> {
+    let p = path.to_path_buf();
+    let digest = tokio::task::spawn_blocking(move || Checksum::Sha256(String::new()).compute(&p))
+        .await
+        .context("hash task failed")?
+        .with_context(|| format!("hash {}", path.display()))?;
+    tokio::fs::write(sidecar_path(path), format!("{digest}\n"))
+        .await
+        .context("write sha256 sidecar")?;
+    Ok(())
+}
+
+/// Like [`verify_cached`], against the `.sha256` sidecar. Zips cached before
+/// sidecars existed are kept (and get a sidecar) if they still open.
+pub(crate) async fn verify_cached_zip(path: &Path) -> bool {
+    let sidecar = sidecar_path(path);
+    if let Ok(raw) = tokio::fs::read_to_string(&sidecar).await {
+        let ok = verify_cached(path, &Checksum::Sha256(raw.trim().to_string())).await;
+        if !ok {
+            let _ = tokio::fs::remove_file(&sidecar).await;
+        }
+        return ok;
+    }
+    if !path.exists() {
+        return false;
+    }
+
+    let p = path.to_path_buf();
+    let opened = tokio::task::spawn_blocking(move || {
+        std::fs::File::open(&p)
+            .map_err(anyhow::Error::from)
+            .and_then(|f| zip::ZipArchive::new(f).map(|_| ()).map_err(Into::into))
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.into()));
+    match opened {
+        Ok(()) => write_sha256_sidecar(path).await.is_ok(),
+        Err(e) => {
+            discard(path, &format!("not a readable zip: {e}"));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Checksum, sidecar_path, verify_cached, verify_cached_zip, write_sha256_sidecar};
+    use sha1::Digest;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "alloy-agent-download-cache-{}-{name}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[tokio::test]
+    async fn corrupt_cache_hits_are_deleted() {
+        let path = temp_path("server.jar");
+        std::fs::write(&path, b"jar bytes").unwrap();
+        let sha1 = hex::encode(sha1::Sha1::digest(b"jar bytes"));
+        assert!(verify_cached(&path, &Checksum::Sha1(sha1.to_ascii_uppercase())).await);
+
+        std::fs::write(&path, b"jar by").unwrap();
+        assert!(!verify_cached(&path, &Checksum::Sha1(sha1.clone())).await);
+        assert!(!path.exists());
+        // A miss is not an error.
+        assert!(!verify_cached(&path, &Checksum::Sha1(sha1)).await);
+    }
+
+    #[tokio::test]
+    async fn zip_cache_uses_sidecar_and_adopts_readable_legacy_zips() {
+        let path = temp_path("pack.zip");
+        {
+            let mut w = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+            w.start_file("a.txt", zip::write::SimpleFileOptions::default())
+                .unwrap();
+            std::io::Write::write_all(&mut w, b"hello").unwrap();
+            w.finish().unwrap();
+        }
+        // No sidecar yet: a readable zip is adopted.
+        assert!(verify_cached_zip(&path).await);
+        assert!(sidecar_path(&path).exists());
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[0] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(!verify_cached_zip(&path).await);
+        assert!(!path.exists() && !sidecar_path(&path).exists());
+
+        // Truncated legacy download without a sidecar.
+        std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(!verify_cached_zip(&path).await);
+        assert!(!path.exists());
+
+        std::fs::write(&path, b"anything").unwrap();
+        write_sha256_sidecar(&path).await.unwrap();
+        assert!(verify_cached_zip(&path).await);
+    }
+}
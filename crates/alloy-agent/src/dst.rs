@@ -11,6 +11,21 @@ pub struct VanillaParams {
     // Steam ports (best-effort; required for discovery/auth).
     pub master_port: u16,
     pub auth_port: u16,
+    /// Steam Workshop ids from `dst_mods`.
+    pub mods: Vec<String>,
+    /// Set when `enable_caves` runs a Caves shard next to Master.
+    pub caves: Option<CavesParams>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CavesParams {
+    pub port: u16,
+    pub master_port: u16,
+    pub auth_port: u16,
+    /// Port Master listens on for the Caves shard, bound to 127.0.0.1.
+    pub shard_port: u16,
+    /// Shared secret both shards read from cluster.ini.
+    pub cluster_key: String,
 }
 
 pub fn validate_vanilla_params(params: &BTreeMap<String, String>) -> anyhow::Result<VanillaParams> {
@@ -79,6 +94,49 @@ pub fn validate_vanilla_params(params: &BTreeMap<String, String>) -> anyhow::Res
         "auth_port",
     );
 
+    let enable_caves = match params.get("enable_caves").map(|v| v.trim()) {
+        Some("true") => true,
+        None | Some("") | Some("false") => false,
+        Some(_) => {
+            field_errors.insert(
+                "enable_caves".to_string(),
+                "Must be true or false.".to_string(),
+            );
+            false
+        }
+    };
+    let caves = enable_caves.then(|| CavesParams {
+        port: parse_port(
+            params.get("caves_port"),
+            11000,
+            &mut field_errors,
+            "caves_port",
+        ),
+        master_port: parse_port(
+            params.get("caves_master_port"),
+            27017,
+            &mut field_errors,
+            "caves_master_port",
+        ),
+        auth_port: parse_port(
+            params.get("caves_auth_port"),
+            8767,
+            &mut field_errors,
+            "caves_auth_port",
+        ),
+        // Assigned at launch.
+        shard_port: 0,
+        cluster_key: String::new(),
+    });
+
+    let mods = match parse_mod_ids(params.get("dst_mods").map(String::as_str).unwrap_or("")) {
+        Ok(ids) => ids,
+        Err(msg) => {
+            field_errors.insert("dst_mods".to_string(), msg);
+            Vec::new()
+        }
+    };
+
     if !field_errors.is_empty() {
         return Err(crate::error_payload::anyhow(
             "invalid_param",
@@ -96,9 +154,57 @@ pub fn validate_vanilla_params(params: &BTreeMap<String, String>) -> anyhow::Res
         port,
         master_port,
         auth_port,
+        mods,
+        caves,
     })
 }
 
+/// Splits `dst_mods` on commas and whitespace into distinct workshop ids.
+/// A `workshop-` prefix, as used in modoverrides.lua, is accepted.
+fn parse_mod_ids(raw: &str) -> Result<Vec<String>, String> {
+    let mut ids = Vec::<String>::new();
+    for item in raw.split(|c: char| c == ',' || c.is_whitespace()) {
+        if item.is_empty() {
+            continue;
+        }
+        let id = item.strip_prefix("workshop-").unwrap_or(item);
+        if id.is_empty() || id.len() > 20 || !id.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("Not a workshop id: {item:?}. Use numeric ids."));
+        }
+        if !ids.iter().any(|i| i == id) {
+            ids.push(id.to_string());
+        }
+    }
+    Ok(ids)
+}
+
+/// `dedicated_server_mods_setup.lua`: one `ServerModSetup` per mod.
+pub fn mods_setup_lua(ids: &[String]) -> String {
+    let mut out = String::from("-- Generated by Alloy from the dst_mods param.\n");
+    for id in ids {
+        out.push_str(&format!("ServerModSetup(\"{id}\")\n"));
+    }
+    out
+}
+
+/// `modoverrides.lua` enabling every mod with its default configuration.
+pub fn modoverrides_lua(ids: &[String]) -> String {
+    let mut out = String::from("return {\n");
+    for id in ids {
+        out.push_str(&format!(
+            "  [\"workshop-{id}\"] = {{ enabled = true, configuration_options = {{}} }},\n"
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Where SteamCMD downloads this instance's workshop mods; its
+/// `steamapps/workshop` dir is what the server takes as `-ugc_directory`.
+pub fn ugc_install_dir(instance_dir: &Path) -> PathBuf {
+    instance_dir.join("klei").join("ugc")
+}
+
 fn parse_port(
     raw: Option<&String>,
     default_value: u16,
@@ -155,7 +261,41 @@ pub fn ensure_vanilla_instance_layout(
         format!("{}\n", params.cluster_token),
     )?;
 
-    // Minimal cluster.ini (single shard).
+    fs::write(cluster.join("cluster.ini"), cluster_ini(params))?;
+    fs::write(
+        master.join("server.ini"),
+        server_ini("Master", params.port, params.master_port, params.auth_port),
+    )?;
+
+    let mods_dir = root.join("mods");
+    fs::create_dir_all(&mods_dir)?;
+    fs::write(
+        mods_dir.join("dedicated_server_mods_setup.lua"),
+        mods_setup_lua(&params.mods),
+    )?;
+    fs::write(
+        master.join("modoverrides.lua"),
+        modoverrides_lua(&params.mods),
+    )?;
+
+    if let Some(caves) = &params.caves {
+        let dir = cluster.join("Caves");
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join("server.ini"),
+            server_ini("Caves", caves.port, caves.master_port, caves.auth_port),
+        )?;
+        fs::write(dir.join("worldgenoverride.lua"), CAVES_WORLDGEN)?;
+        fs::write(dir.join("modoverrides.lua"), modoverrides_lua(&params.mods))?;
+    }
+
+    Ok(())
+}
+
+const CAVES_WORLDGEN: &str = "return {\n  override_enabled = true,\n  preset = \"DST_CAVE\",\n}\n";
+
+/// cluster.ini; sharding is enabled when there is a Caves shard.
+pub fn cluster_ini(params: &VanillaParams) -> String {
     let mut cluster_ini = String::new();
     cluster_ini.push_str("[GAMEPLAY]\n");
     cluster_ini.push_str("game_mode = survival\n");
@@ -178,23 +318,106 @@ pub fn ensure_vanilla_instance_layout(
     cluster_ini.push_str("console_enabled = true\n\n");
 
     cluster_ini.push_str("[SHARD]\n");
-    cluster_ini.push_str("shard_enabled = false\n");
-    fs::write(cluster.join("cluster.ini"), cluster_ini.as_bytes())?;
+    match &params.caves {
+        None => cluster_ini.push_str("shard_enabled = false\n"),
+        Some(caves) => {
+            cluster_ini.push_str("shard_enabled = true\n");
+            cluster_ini.push_str("bind_ip = 127.0.0.1\n");
+            cluster_ini.push_str("master_ip = 127.0.0.1\n");
+            cluster_ini.push_str(&format!("master_port = {}\n", caves.shard_port));
+            cluster_ini.push_str(&format!("cluster_key = {}\n", caves.cluster_key));
+        }
+    }
+    cluster_ini
+}
 
-    // Master shard server.ini.
+fn server_ini(shard: &str, port: u16, master_port: u16, auth_port: u16) -> String {
     let mut server_ini = String::new();
     server_ini.push_str("[NETWORK]\n");
-    server_ini.push_str(&format!("server_port = {}\n\n", params.port));
+    server_ini.push_str(&format!("server_port = {port}\n\n"));
     server_ini.push_str("[SHARD]\n");
-    server_ini.push_str("is_master = true\n");
-    server_ini.push_str("name = Master\n");
-    server_ini.push_str("id = 1\n\n");
+    if shard == "Master" {
+        server_ini.push_str("is_master = true\n");
+        server_ini.push_str("name = Master\n");
+        server_ini.push_str("id = 1\n\n");
+    } else {
+        server_ini.push_str("is_master = false\n");
+        server_ini.push_str(&format!("name = {shard}\n"));
+        server_ini.push_str("id = 2\n\n");
+    }
     server_ini.push_str("[ACCOUNT]\n");
     server_ini.push_str("encode_user_path = true\n\n");
     server_ini.push_str("[STEAM]\n");
-    server_ini.push_str(&format!("master_server_port = {}\n", params.master_port));
-    server_ini.push_str(&format!("authentication_port = {}\n", params.auth_port));
-    fs::write(master.join("server.ini"), server_ini.as_bytes())?;
+    server_ini.push_str(&format!("master_server_port = {master_port}\n"));
+    server_ini.push_str(&format!("authentication_port = {auth_port}\n"));
+    server_ini
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{
+        CavesParams, cluster_ini, modoverrides_lua, mods_setup_lua, parse_mod_ids,
+        validate_vanilla_params,
+    };
+
+    #[test]
+    fn mod_ids_must_be_numeric() {
+        assert_eq!(
+            parse_mod_ids(" 378160973, workshop-458940297\n378160973 ").unwrap(),
+            ["378160973", "458940297"]
+        );
+        assert!(parse_mod_ids("").unwrap().is_empty());
+        assert!(parse_mod_ids("378160973,abc").is_err());
+        assert!(parse_mod_ids("workshop-").is_err());
+    }
+
+    #[test]
+    fn lua_files_list_every_mod() {
+        let ids = ["378160973".to_string(), "458940297".to_string()];
+        assert_eq!(
+            mods_setup_lua(&ids),
+            "-- Generated by Alloy from the dst_mods param.\n\
+             ServerModSetup(\"378160973\")\n\
+             ServerModSetup(\"458940297\")\n"
+        );
+        assert_eq!(
+            modoverrides_lua(&ids),
+            "return {\n  \
+             [\"workshop-378160973\"] = { enabled = true, configuration_options = {} },\n  \
+             [\"workshop-458940297\"] = { enabled = true, configuration_options = {} },\n\
+             }\n"
+        );
+        assert_eq!(modoverrides_lua(&[]), "return {\n}\n");
+    }
+
+    #[test]
+    fn cluster_ini_enables_sharding_only_with_caves() {
+        let mut params = BTreeMap::from([("cluster_token".to_string(), "tok".to_string())]);
+        let single = validate_vanilla_params(&params).unwrap();
+        assert!(single.caves.is_none());
+        assert!(cluster_ini(&single).ends_with("[SHARD]\nshard_enabled = false\n"));
+
+        params.insert("enable_caves".to_string(), "true".to_string());
+        params.insert("caves_port".to_string(), "11001".to_string());
+        let mut sharded = validate_vanilla_params(&params).unwrap();
+        let caves = sharded.caves.as_mut().unwrap();
+        assert_eq!(
+            (caves.port, caves.master_port, caves.auth_port),
+            (11001, 27017, 8767)
+        );
+        *caves = CavesParams {
+            shard_port: 10888,
+            cluster_key: "key".to_string(),
+            ..caves.clone()
+        };
+        assert!(cluster_ini(&sharded).ends_with(
+            "[SHARD]\nshard_enabled = true\nbind_ip = 127.0.0.1\nmaster_ip = 127.0.0.1\n\
+             master_port = 10888\ncluster_key = key\n"
+        ));
+
+        params.insert("enable_caves".to_string(), "yes".to_string());
+        assert!(validate_vanilla_params(&params).is_err());
+    }
 }
@@ -2,18 +2,19 @@
 
 use std::{
     collections::HashMap,
+    ffi::OsString,
     path::{Path, PathBuf},
     sync::{Arc, OnceLock},
     time::Duration,
 };
 
 use anyhow::Context;
-use futures_util::StreamExt;
 use tokio::io::AsyncReadExt;
 use tokio::process::Command;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, mpsc};
 
 use crate::minecraft;
+use crate::steamcmd_login::{self, SteamLogin, SteamcmdAuth};
 
 pub struct InstalledDstServer {
     pub server_root: PathBuf,
@@ -141,33 +142,7 @@ fn http_client() -> &'static reqwest::Client {
 }
 
 async fn download_to_path(url: &str, path: &Path) -> anyhow::Result<()> {
-    if let Some(parent) = path.parent() {
-        tokio::fs::create_dir_all(parent).await?;
-    }
-
-    let resp = http_client()
-        .get(url)
-        .send()
-        .await
-        .with_context(|| format!("download {url}"))?
-        .error_for_status()
-        .with_context(|| format!("download {url} (status)"))?;
-
-    let tmp = path.with_extension("tmp");
-    let mut f = tokio::fs::File::create(&tmp).await?;
-    let mut total: u64 = 0;
-    let mut stream = resp.bytes_stream();
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        total = total.saturating_add(chunk.len() as u64);
-        if total > 2 * 1024 * 1024 * 1024_u64 {
-            let _ = tokio::fs::remove_file(&tmp).await;
-            anyhow::bail!("download too large");
-        }
-        tokio::io::AsyncWriteExt::write_all(&mut f, &chunk).await?;
-    }
-    tokio::io::AsyncWriteExt::flush(&mut f).await.ok();
-    tokio::fs::rename(&tmp, path).await?;
+    crate::download::download_with_resume(url, path, 2 * 1024 * 1024 * 1024, |_, _, _| {}).await?;
     Ok(())
 }
 
@@ -276,23 +251,223 @@ impl TailBuffer {
     }
 }
 
+fn forward_line(line: &mut Vec<u8>, output: &mpsc::UnboundedSender<String>) {
+    let text = String::from_utf8_lossy(line);
+    let text = text.trim();
+    if !text.is_empty() {
+        let _ = output.send(text.to_string());
+    }
+    line.clear();
+}
+
 async fn read_tail<R: tokio::io::AsyncRead + Unpin>(
     mut reader: R,
     limit_bytes: usize,
+    output: Option<mpsc::UnboundedSender<String>>,
 ) -> anyhow::Result<Vec<u8>> {
     let mut tail = TailBuffer::new(limit_bytes);
     let mut buf = [0u8; 8192];
+    // SteamCMD redraws progress with '\r', so treat it as a line break too.
+    let mut line = Vec::<u8>::new();
     loop {
         let n = reader.read(&mut buf).await?;
         if n == 0 {
             break;
         }
         tail.push(&buf[..n]);
+        if let Some(output) = &output {
+            for &b in &buf[..n] {
+                if b == b'\n' || b == b'\r' {
+                    forward_line(&mut line, output);
+                } else if line.len() < 4096 {
+                    line.push(b);
+                }
+            }
+        }
+    }
+    if let Some(output) = &output {
+        forward_line(&mut line, output);
     }
     Ok(tail.to_vec())
 }
 
 pub async fn ensure_dst_server() -> anyhow::Result<InstalledDstServer> {
+    ensure_dst_server_with_output(None, &SteamcmdAuth::default()).await
+}
+
+struct SteamcmdRun {
+    status: std::process::ExitStatus,
+    stdout_tail: Vec<u8>,
+    stderr_tail: Vec<u8>,
+}
+
+impl SteamcmdRun {
+    fn text(&self) -> String {
+        format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&self.stdout_tail),
+            String::from_utf8_lossy(&self.stderr_tail)
+        )
+    }
+
+    fn failure(&self) -> anyhow::Error {
+        let stdout = String::from_utf8_lossy(&self.stdout_tail);
+        let stderr = String::from_utf8_lossy(&self.stderr_tail);
+        anyhow::anyhow!(
+            "steamcmd failed (exit {}):\nstdout:\n{}\nstderr:\n{}",
+            self.status,
+            stdout,
+            stderr
+        )
+    }
+}
+
+async fn run_steamcmd(
+    steamcmd_sh: &Path,
+    args: Vec<OsString>,
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<SteamcmdRun> {
+    let mut cmd = Command::new(steamcmd_sh);
+    cmd.current_dir(steamcmd_dir())
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+
+    let mut child = cmd.spawn().context("spawn steamcmd")?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    const TAIL_BYTES: usize = 64 * 1024;
+    let stdout_task = stdout.map(|s| tokio::spawn(read_tail(s, TAIL_BYTES, output.clone())));
+    let stderr_task = stderr.map(|s| tokio::spawn(read_tail(s, TAIL_BYTES, output.clone())));
+
+    let status = child.wait().await.context("wait steamcmd")?;
+    let stdout_tail = match stdout_task {
+        Some(h) => h.await.context("join steamcmd stdout")??,
+        None => Vec::new(),
+    };
+    let stderr_tail = match stderr_task {
+        Some(h) => h.await.context("join steamcmd stderr")??,
+        None => Vec::new(),
+    };
+    Ok(SteamcmdRun {
+        status,
+        stdout_tail,
+        stderr_tail,
+    })
+}
+
+fn login_args(
+    before: &[OsString],
+    login: Option<(&SteamLogin, Option<&str>)>,
+    after: &[OsString],
+) -> Vec<OsString> {
+    let mut args = before.to_vec();
+    args.push("+login".into());
+    match login {
+        Some((login, code)) => {
+            args.push(login.username.clone().into());
+            args.push(login.password.clone().into());
+            args.extend(code.map(OsString::from));
+        }
+        None => args.push("anonymous".into()),
+    }
+    args.extend(after.iter().cloned());
+    args
+}
+
+/// Runs SteamCMD as `before +login <account> after`. With Steam Guard
+/// candidates, a rejected code moves on to the next one; any other login
+/// failure aborts with a payload naming the field to fix.
+async fn run_steamcmd_logged_in(
+    steamcmd_sh: &Path,
+    login: &SteamLogin,
+    before: &[OsString],
+    after: &[OsString],
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<SteamcmdRun> {
+    let attempts = login.attempts();
+    for (i, code) in attempts.iter().enumerate() {
+        let args = login_args(before, Some((login, *code)), after);
+        let run = run_steamcmd(steamcmd_sh, args, output.clone()).await?;
+        if run.status.success() {
+            return Ok(run);
+        }
+
+        let failure = steamcmd_login::classify_output(&run.text());
+        let codes_left = i + 1 < attempts.len();
+        if steamcmd_login::should_retry(failure, codes_left) {
+            if let Some(output) = &output {
+                let _ = output.send(format!(
+                    "steam guard code rejected, trying candidate {}/{}",
+                    i + 2,
+                    attempts.len()
+                ));
+            }
+            continue;
+        }
+        return Err(match failure {
+            Some(failure) => steamcmd_login::login_error(failure),
+            None => run.failure(),
+        });
+    }
+    unreachable!("attempts is never empty")
+}
+
+/// Runs SteamCMD as `before +login ... after` with the logins `auth`
+/// selects, moving on to the account when Steam refuses anonymous login.
+async fn run_steamcmd_with_auth(
+    steamcmd_sh: &Path,
+    auth: &SteamcmdAuth,
+    before: &[OsString],
+    after: &[OsString],
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<SteamcmdRun> {
+    let logins = auth.logins()?;
+    for (i, login) in logins.iter().enumerate() {
+        let run = match login {
+            Some(login) => {
+                run_steamcmd_logged_in(steamcmd_sh, login, before, after, output.clone()).await?
+            }
+            None => {
+                let args = login_args(before, None, after);
+                run_steamcmd(steamcmd_sh, args, output.clone()).await?
+            }
+        };
+        if run.status.success() {
+            return Ok(run);
+        }
+        let has_fallback = i + 1 < logins.len();
+        if login.is_none() && has_fallback && steamcmd_login::anonymous_refused(&run.text()) {
+            if let Some(output) = &output {
+                let _ = output.send(
+                    "steam refused anonymous login, retrying with the steamcmd account".to_string(),
+                );
+            }
+            continue;
+        }
+        return Err(run.failure());
+    }
+    unreachable!("logins is never empty")
+}
+
+/// Logs in and quits, to check Steam credentials without downloading anything.
+pub async fn check_steam_login(
+    login: &SteamLogin,
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<()> {
+    let steamcmd_sh = ensure_steamcmd().await?;
+    run_steamcmd_logged_in(&steamcmd_sh, login, &[], &["+quit".into()], output).await?;
+    Ok(())
+}
+
+/// Like `ensure_dst_server`, but forwards SteamCMD stdout/stderr lines to
+/// `output` while the install runs (the tail is still kept for errors), and
+/// logs in as `auth` selects.
+pub async fn ensure_dst_server_with_output(
+    output: Option<mpsc::UnboundedSender<String>>,
+    auth: &SteamcmdAuth,
+) -> anyhow::Result<InstalledDstServer> {
     // SteamCMD + DST dedicated server is only available as x86 Linux binaries.
     #[cfg(not(target_arch = "x86_64"))]
     {
@@ -320,54 +495,29 @@ pub async fn ensure_dst_server() -> anyhow::Result<InstalledDstServer> {
         });
     }
 
+    if let Some(output) = &output {
+        let _ = output.send("preparing steamcmd".to_string());
+    }
     let steamcmd_sh = ensure_steamcmd().await?;
+    if let Some(output) = &output {
+        let _ = output.send(format!(
+            "running steamcmd app_update 343050 into {}",
+            install_dir.display()
+        ));
+    }
     tokio::fs::create_dir_all(&install_dir).await?;
 
-    let mut cmd = Command::new(&steamcmd_sh);
-    cmd.current_dir(steamcmd_dir())
-        .arg("+force_install_dir")
-        .arg(&install_dir)
-        .arg("+login")
-        .arg("anonymous")
-        .arg("+app_update")
-        .arg("343050")
-        .arg("validate")
-        .arg("+quit")
-        .stdin(std::process::Stdio::null())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped());
-
-    let mut child = cmd.spawn().context("spawn steamcmd")?;
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
-    const TAIL_BYTES: usize = 64 * 1024;
-    let stdout_task = stdout.map(|s| tokio::spawn(read_tail(s, TAIL_BYTES)));
-    let stderr_task = stderr.map(|s| tokio::spawn(read_tail(s, TAIL_BYTES)));
-
-    let status = child.wait().await.context("wait steamcmd")?;
-    let stdout_tail = match stdout_task {
-        Some(h) => h.await.context("join steamcmd stdout")??,
-        None => Vec::new(),
-    };
-    let stderr_tail = match stderr_task {
-        Some(h) => h.await.context("join steamcmd stderr")??,
-        None => Vec::new(),
-    };
-
-    if !status.success() {
-        let stdout = String::from_utf8_lossy(&stdout_tail);
-        let stderr = String::from_utf8_lossy(&stderr_tail);
-        anyhow::bail!(
-            "steamcmd failed (exit {}):\nstdout:\n{}\nstderr:\n{}",
-            status,
-            stdout,
-            stderr
-        );
-    }
+    // force_install_dir must come before +login.
+    let before = [
+        OsString::from("+force_install_dir"),
+        install_dir.clone().into_os_string(),
+    ];
+    let after = ["+app_update", "343050", "validate", "+quit"].map(OsString::from);
+    let run = run_steamcmd_with_auth(&steamcmd_sh, auth, &before, &after, output).await?;
 
     let bin = find_dst_server_bin(&install_dir).with_context(|| {
-        let stdout = String::from_utf8_lossy(&stdout_tail);
-        let stderr = String::from_utf8_lossy(&stderr_tail);
+        let stdout = String::from_utf8_lossy(&run.stdout_tail);
+        let stderr = String::from_utf8_lossy(&run.stderr_tail);
         format!(
             "dst server binary not found after install.\nsteamcmd stdout (tail):\n{stdout}\nsteamcmd stderr (tail):\n{stderr}",
         )
@@ -380,6 +530,82 @@ pub async fn ensure_dst_server() -> anyhow::Result<InstalledDstServer> {
     })
 }
 
+/// Workshop items belong to the DST game app, not the dedicated server app.
+const DST_WORKSHOP_APP_ID: &str = "322330";
+
+/// Downloads the workshop mods `ids` that aren't already under `ugc_root`
+/// and returns the dir to pass the server as `-ugc_directory`.
+pub async fn ensure_workshop_mods(
+    ugc_root: &Path,
+    ids: &[String],
+    auth: &SteamcmdAuth,
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<PathBuf> {
+    ensure_workshop_items(ugc_root, DST_WORKSHOP_APP_ID, ids, "dst_mods", auth, output).await
+}
+
+/// Downloads the workshop items `ids` of `app_id` that aren't already under
+/// `ugc_root` and returns its `steamapps/workshop` dir. Failures are
+/// reported against the param `field`.
+pub async fn ensure_workshop_items(
+    ugc_root: &Path,
+    app_id: &str,
+    ids: &[String],
+    field: &str,
+    auth: &SteamcmdAuth,
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<PathBuf> {
+    let ugc_dir = ugc_root.join("steamapps").join("workshop");
+    let content = ugc_dir.join("content").join(app_id);
+    let missing: Vec<&String> = ids
+        .iter()
+        .filter(|id| {
+            std::fs::read_dir(content.join(id.as_str()))
+                .map(|mut entries| entries.next().is_none())
+                .unwrap_or(true)
+        })
+        .collect();
+    if missing.is_empty() {
+        return Ok(ugc_dir);
+    }
+
+    let steamcmd_sh = ensure_steamcmd().await?;
+    if let Some(output) = &output {
+        let _ = output.send(format!("downloading {} workshop mod(s)", missing.len()));
+    }
+    tokio::fs::create_dir_all(ugc_root).await?;
+    let before = [
+        OsString::from("+force_install_dir"),
+        ugc_root.as_os_str().to_owned(),
+    ];
+    let mut after = Vec::<OsString>::new();
+    for id in &missing {
+        after.extend(["+workshop_download_item", app_id, id.as_str()].map(OsString::from));
+    }
+    after.push("+quit".into());
+    let run = run_steamcmd_with_auth(&steamcmd_sh, auth, &before, &after, output).await?;
+
+    // SteamCMD can exit 0 after a failed item download.
+    if let Some(id) = missing
+        .iter()
+        .find(|id| !content.join(id.as_str()).is_dir())
+    {
+        return Err(crate::error_payload::anyhow(
+            "download_failed",
+            format!("workshop mod {id} was not downloaded"),
+            Some(std::collections::BTreeMap::from([(
+                field.to_string(),
+                format!("{id} failed to download: {}", run.text().trim()),
+            )])),
+            Some(
+                "Check the workshop id, or set a SteamCMD account if the mod needs one."
+                    .to_string(),
+            ),
+        ));
+    }
+    Ok(ugc_dir)
+}
+
 #[cfg(test)]
 mod tests {
     use super::TailBuffer;
@@ -6,7 +6,7 @@ const MAX_MESSAGE_BYTES: usize = 32 * 1024;
 const MAX_HINT_BYTES: usize = 8 * 1024;
 const MAX_FIELD_ERROR_BYTES: usize = 4 * 1024;
 
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ErrorPayload {
     pub code: String,
     pub message: String,
@@ -71,6 +71,15 @@ pub fn anyhow(
     anyhow::anyhow!(encode(code, message, field_errors, hint))
 }
 
+/// Finds and parses the first encoded payload in an error chain.
+pub fn decode(err: &anyhow::Error) -> Option<ErrorPayload> {
+    err.chain().find_map(|cause| {
+        let s = cause.to_string();
+        let json = &s[s.find(PREFIX)? + PREFIX.len()..];
+        serde_json::from_str(json).ok()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
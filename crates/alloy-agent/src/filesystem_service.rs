@@ -1,31 +1,77 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::path::{Component, Path, PathBuf};
-use std::time::UNIX_EPOCH;
+use std::pin::Pin;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
+use alloy_process::ProcessState;
 use alloy_proto::agent_v1::filesystem_service_server::{
     FilesystemService, FilesystemServiceServer,
 };
 use alloy_proto::agent_v1::{
-    DirEntry, GetCapabilitiesRequest, GetCapabilitiesResponse, ListDirRequest, ListDirResponse,
-    MkdirRequest, MkdirResponse, ReadFileRequest, ReadFileResponse, RemoveRequest, RemoveResponse,
-    RenameRequest, RenameResponse, WriteFileRequest, WriteFileResponse,
+    DirEntry, DownloadTreeChunk, DownloadTreeRequest, GetCapabilitiesRequest,
+    GetCapabilitiesResponse, ListDirRequest, ListDirResponse, MkdirRequest, MkdirResponse,
+    ReadFileRequest, ReadFileResponse, RemoveRequest, RemoveResponse, RenameRequest,
+    RenameResponse, WriteFileRequest, WriteFileResponse,
 };
+use futures_util::Stream;
 use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tonic::{Request, Response, Status};
 
 use crate::minecraft;
+use crate::process_manager::ProcessManager;
 
 const DEFAULT_READ_LIMIT: u64 = 64 * 1024;
 const MAX_READ_LIMIT: u64 = 1024 * 1024;
 const MAX_WRITE_LIMIT: usize = 1024 * 1024;
+// Largest file a chunked WriteFile may assemble.
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+const DEFAULT_TREE_ENTRIES: usize = 1000;
+const MAX_TREE_ENTRIES: usize = 10_000;
+// A recursive page ends early (with a cursor) once the walk takes this long.
+const TREE_WALK_BUDGET: Duration = Duration::from_secs(2);
+// Largest directory (uncompressed) DownloadTree will zip.
+const MAX_DOWNLOAD_TREE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+const DOWNLOAD_CHUNK_BYTES: usize = 256 * 1024;
+
+// Top-level instance dirs holding world data (Minecraft, Terraria); a live
+// server keeps them open, so they are read-only until it stops.
+const WORLD_DIRS: &[&str] = &["world", "world_nether", "world_the_end", "worlds"];
+
+#[derive(Debug, Clone)]
+pub struct FilesystemApi {
+    manager: ProcessManager,
+}
 
-#[derive(Debug, Default, Clone)]
-pub struct FilesystemApi;
+impl FilesystemApi {
+    pub fn new(manager: ProcessManager) -> Self {
+        Self { manager }
+    }
+
+    /// Processes that are not stopped.
+    async fn live_process_ids(&self) -> HashSet<String> {
+        self.manager
+            .list_processes()
+            .await
+            .into_iter()
+            .filter(|s| {
+                matches!(
+                    s.state,
+                    ProcessState::Starting | ProcessState::Running | ProcessState::Stopping
+                )
+            })
+            .map(|s| s.id.0)
+            .collect()
+    }
+}
 
 #[derive(Debug)]
 enum FsPathError {
     Absolute,
     Traversal,
     EscapesRoot,
+    // World files of a process that is not stopped.
+    LiveWorld(String),
 }
 
 impl From<FsPathError> for Status {
@@ -34,6 +80,9 @@ impl From<FsPathError> for Status {
             FsPathError::Absolute => Status::invalid_argument("path must be relative"),
             FsPathError::Traversal => Status::invalid_argument("path traversal is not allowed"),
             FsPathError::EscapesRoot => Status::invalid_argument("path escapes data root"),
+            FsPathError::LiveWorld(id) => Status::failed_precondition(format!(
+                "{id} is running; stop it before changing its world files"
+            )),
         }
     }
 }
@@ -86,13 +135,12 @@ fn scoped_path(rel: &str) -> Result<PathBuf, FsPathError> {
     Ok(data_root().join(rel))
 }
 
-async fn enforce_scoped_existing_path(p: &Path) -> Result<PathBuf, Status> {
-    let root = data_root();
+async fn enforce_scoped_existing_path(root: &Path, p: &Path) -> Result<PathBuf, Status> {
     // canonicalize() resolves symlinks. This prevents escaping the data root via symlink chains.
     let canon = tokio::fs::canonicalize(p)
         .await
         .map_err(|e| status_from_io("failed to canonicalize path", e))?;
-    if !canon.starts_with(&root) {
+    if !canon.starts_with(root) {
         return Err(Status::from(FsPathError::EscapesRoot));
     }
     Ok(canon)
@@ -118,10 +166,10 @@ fn ensure_fs_write_enabled() -> Result<(), Status> {
     Ok(())
 }
 
-async fn ensure_scoped_parent_dir(rel_path: &str) -> Result<PathBuf, Status> {
+async fn ensure_scoped_parent_dir(root: &Path, rel_path: &str) -> Result<PathBuf, Status> {
     let rel = normalize_rel_path(rel_path).map_err(Status::from)?;
     let parent = rel.parent().unwrap_or(Path::new(""));
-    let parent_scoped = data_root().join(parent);
+    let parent_scoped = root.join(parent);
 
     let meta = tokio::fs::metadata(&parent_scoped)
         .await
@@ -130,9 +178,233 @@ async fn ensure_scoped_parent_dir(rel_path: &str) -> Result<PathBuf, Status> {
         return Err(Status::invalid_argument("parent is not a directory"));
     }
 
-    enforce_scoped_existing_path(&parent_scoped).await
+    enforce_scoped_existing_path(root, &parent_scoped).await
+}
+
+/// The process whose world data `rel` (relative to the data root) points into.
+fn world_owner(rel: &Path) -> Option<&str> {
+    let mut parts = rel.components().map(|c| c.as_os_str().to_str());
+    let (Some(Some(kind)), Some(Some(id)), Some(Some(top))) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    (matches!(kind, "instances" | "processes") && WORLD_DIRS.contains(&top)).then_some(id)
+}
+
+fn ensure_not_live_world(
+    root: &Path,
+    path: &Path,
+    live: &HashSet<String>,
+) -> Result<(), FsPathError> {
+    let rel = path.strip_prefix(root).unwrap_or(path);
+    match world_owner(rel) {
+        Some(id) if live.contains(id) => Err(FsPathError::LiveWorld(id.to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// Writes one chunk of `req` under `root` and returns the bytes received so
+/// far. Chunks go to a hidden part file next to the target, which replaces the
+/// target by rename after the last chunk, so readers never see a partial file.
+async fn write_file_in(
+    root: &Path,
+    req: &WriteFileRequest,
+    live: &HashSet<String>,
+) -> Result<u64, Status> {
+    if req.data.len() > MAX_WRITE_LIMIT {
+        return Err(Status::invalid_argument("chunk too large"));
+    }
+    let size = req.offset.saturating_add(req.data.len() as u64);
+    if size > MAX_FILE_BYTES {
+        return Err(Status::invalid_argument("file too large"));
+    }
+
+    let parent = ensure_scoped_parent_dir(root, &req.path).await?;
+    let rel = normalize_rel_path(&req.path).map_err(Status::from)?;
+    let file_name = rel
+        .file_name()
+        .ok_or_else(|| Status::invalid_argument("path must include filename"))?;
+    let path = parent.join(file_name);
+    ensure_not_live_world(root, &path, live).map_err(Status::from)?;
+
+    let meta = tokio::fs::symlink_metadata(&path).await.ok();
+    if let Some(m) = meta {
+        if m.file_type().is_symlink() {
+            return Err(Status::invalid_argument("refusing to write to symlink"));
+        }
+        if m.is_dir() {
+            return Err(Status::invalid_argument("path is a directory"));
+        }
+    }
+
+    let mut part_name = OsString::from(".");
+    part_name.push(file_name);
+    part_name.push(".alloy-part");
+    let part = parent.join(part_name);
+    let mut f = if req.offset == 0 {
+        tokio::fs::File::create(&part).await
+    } else {
+        tokio::fs::OpenOptions::new().append(true).open(&part).await
+    }
+    .map_err(|e| status_from_io("failed to open part file", e))?;
+    let have = f
+        .metadata()
+        .await
+        .map_err(|e| status_from_io("failed to stat part file", e))?
+        .len();
+    if have != req.offset {
+        return Err(Status::failed_precondition(format!(
+            "write offset mismatch: have {have} bytes, got offset {}",
+            req.offset
+        )));
+    }
+    f.write_all(&req.data)
+        .await
+        .map_err(|e| Status::internal(format!("failed to write: {e}")))?;
+    f.flush().await.ok();
+    if req.more {
+        return Ok(size);
+    }
+
+    f.sync_all()
+        .await
+        .map_err(|e| Status::internal(format!("failed to sync: {e}")))?;
+    drop(f);
+    tokio::fs::rename(&part, &path)
+        .await
+        .map_err(|e| status_from_io("failed to persist file", e))?;
+    Ok(size)
+}
+
+fn modified_unix_ms(m: &std::fs::Metadata) -> u64 {
+    m.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+        .unwrap_or(0)
+}
+
+/// One page of a sorted, depth-first listing of `base`.
+struct TreeWalk<'a> {
+    // Symlinks resolving outside it are left out.
+    root: &'a Path,
+    base: PathBuf,
+    recursive: bool,
+    // Last entry of the previous page, relative to `base`; empty on the first page.
+    cursor: PathBuf,
+    limit: usize,
+    deadline: Instant,
+    entries: Vec<DirEntry>,
+    more: bool,
+}
+
+impl TreeWalk<'_> {
+    /// Visits the children of `rel` (relative to `base`) in path order; false
+    /// once the page is full or the time budget ran out.
+    fn walk(&mut self, rel: &Path) -> std::io::Result<bool> {
+        let mut names: Vec<OsString> = std::fs::read_dir(self.base.join(rel))?
+            .flatten()
+            .map(|e| e.file_name())
+            .collect();
+        names.sort();
+
+        for name in names {
+            let child = rel.join(&name);
+            // Path ordering is component-wise, which matches the walk order, so
+            // everything up to the cursor except its ancestors can be skipped.
+            let seen = !self.cursor.as_os_str().is_empty() && child <= self.cursor;
+            if seen && !self.cursor.starts_with(&child) {
+                continue;
+            }
+            let path = self.base.join(&child);
+            let Ok(lmeta) = std::fs::symlink_metadata(&path) else {
+                continue;
+            };
+            let is_link = lmeta.file_type().is_symlink();
+            let meta = if is_link {
+                match std::fs::canonicalize(&path) {
+                    Ok(target) if target.starts_with(self.root) => match std::fs::metadata(&path) {
+                        Ok(m) => m,
+                        Err(_) => continue,
+                    },
+                    _ => continue,
+                }
+            } else {
+                lmeta
+            };
+
+            if !seen {
+                // Every page carries at least one entry, so paging always advances.
+                if !self.entries.is_empty()
+                    && (self.entries.len() >= self.limit || Instant::now() >= self.deadline)
+                {
+                    self.more = true;
+                    return Ok(false);
+                }
+                self.entries.push(DirEntry {
+                    name: name.to_string_lossy().to_string(),
+                    is_dir: meta.is_dir(),
+                    size_bytes: if meta.is_file() { meta.len() } else { 0 },
+                    modified_unix_ms: modified_unix_ms(&meta),
+                    path: child.to_string_lossy().to_string(),
+                });
+            }
+            // Unreadable subdirectories are listed but not descended.
+            if self.recursive && meta.is_dir() && !is_link && matches!(self.walk(&child), Ok(false))
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Lists `dir` (canonical, under `root`) starting after `cursor`; returns the
+/// entries and the cursor of the next page, if any.
+fn list_tree(
+    root: &Path,
+    dir: PathBuf,
+    recursive: bool,
+    cursor: PathBuf,
+    max_entries: u32,
+) -> std::io::Result<(Vec<DirEntry>, Option<String>)> {
+    let limit = match (max_entries as usize, recursive) {
+        (0, false) => usize::MAX,
+        (0, true) => DEFAULT_TREE_ENTRIES,
+        (n, _) => n.min(MAX_TREE_ENTRIES),
+    };
+    let mut walk = TreeWalk {
+        root,
+        base: dir,
+        recursive,
+        cursor,
+        limit,
+        deadline: Instant::now() + TREE_WALK_BUDGET,
+        entries: Vec::new(),
+        more: false,
+    };
+    walk.walk(Path::new(""))?;
+    let next = walk
+        .more
+        .then(|| walk.entries.last().map(|e| e.path.clone()))
+        .flatten();
+    Ok((walk.entries, next))
 }
 
+/// Zips the children of `dir` into `out`; entry names are relative to `dir`.
+/// Symlinks and lock files are left out, as in backups.
+fn zip_tree(dir: &Path, out: &Path) -> anyhow::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .flatten()
+        .filter_map(|e| e.file_name().to_str().map(str::to_string))
+        .collect::<Vec<_>>();
+    entries.sort();
+    crate::backup::write_zip(dir, &entries, out)
+}
+
+type DownloadTreeStream = Pin<Box<dyn Stream<Item = Result<DownloadTreeChunk, Status>> + Send>>;
+
 async fn mkdir_rel(rel: &str, recursive: bool) -> Result<(), Status> {
     let rel = normalize_rel_path(rel).map_err(Status::from)?;
     let root = data_root();
@@ -195,12 +467,14 @@ async fn mkdir_rel(rel: &str, recursive: bool) -> Result<(), Status> {
 
 #[tonic::async_trait]
 impl FilesystemService for FilesystemApi {
+    type DownloadTreeStream = DownloadTreeStream;
+
     async fn get_capabilities(
         &self,
         _request: Request<GetCapabilitiesRequest>,
     ) -> Result<Response<GetCapabilitiesResponse>, Status> {
         Ok(Response::new(GetCapabilitiesResponse {
-            write_enabled: fs_write_enabled(),
+            write_enabled: fs_write_enabled() && !crate::read_only::is_read_only(),
         }))
     }
 
@@ -218,45 +492,20 @@ impl FilesystemService for FilesystemApi {
             return Err(Status::invalid_argument("path is not a directory"));
         }
 
-        let dir = enforce_scoped_existing_path(&dir).await?;
+        let root = data_root();
+        let dir = enforce_scoped_existing_path(&root, &dir).await?;
+        let cursor = normalize_rel_path(&req.cursor).map_err(Status::from)?;
 
-        let mut entries = Vec::new();
-        let mut rd = tokio::fs::read_dir(&dir)
-            .await
-            .map_err(|e| status_from_io("failed to read dir", e))?;
-        while let Some(de) = rd
-            .next_entry()
-            .await
-            .map_err(|e| status_from_io("failed to read dir entry", e))?
-        {
-            let name = de.file_name().to_string_lossy().to_string();
-            let m = de
-                .metadata()
-                .await
-                .map_err(|e| status_from_io("failed to stat dir entry", e))?;
-            let modified_unix_ms = m
-                .modified()
-                .ok()
-                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
-                .map(|d| {
-                    let ms = d.as_millis();
-                    if ms > u64::MAX as u128 {
-                        u64::MAX
-                    } else {
-                        ms as u64
-                    }
-                })
-                .unwrap_or(0);
-            entries.push(DirEntry {
-                name,
-                is_dir: m.is_dir(),
-                size_bytes: if m.is_file() { m.len() } else { 0 },
-                modified_unix_ms,
-            });
-        }
-
-        entries.sort_by(|a, b| a.name.cmp(&b.name));
-        Ok(Response::new(ListDirResponse { entries }))
+        let (entries, next_cursor) = tokio::task::spawn_blocking(move || {
+            list_tree(&root, dir, req.recursive, cursor, req.max_entries)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("list dir task failed: {e}")))?
+        .map_err(|e| status_from_io("failed to read dir", e))?;
+        Ok(Response::new(ListDirResponse {
+            entries,
+            next_cursor: next_cursor.unwrap_or_default(),
+        }))
     }
 
     async fn read_file(
@@ -273,7 +522,7 @@ impl FilesystemService for FilesystemApi {
             return Err(Status::invalid_argument("path is not a file"));
         }
 
-        let path = enforce_scoped_existing_path(&path).await?;
+        let path = enforce_scoped_existing_path(&data_root(), &path).await?;
 
         let size = meta.len();
         let offset = req.offset;
@@ -312,6 +561,7 @@ impl FilesystemService for FilesystemApi {
         &self,
         request: Request<MkdirRequest>,
     ) -> Result<Response<MkdirResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         ensure_fs_write_enabled()?;
         let req = request.into_inner();
         mkdir_rel(&req.path, req.recursive).await?;
@@ -322,59 +572,37 @@ impl FilesystemService for FilesystemApi {
         &self,
         request: Request<WriteFileRequest>,
     ) -> Result<Response<WriteFileResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         ensure_fs_write_enabled()?;
         let req = request.into_inner();
-        if req.data.len() > MAX_WRITE_LIMIT {
-            return Err(Status::invalid_argument("file too large"));
-        }
-
-        let parent = ensure_scoped_parent_dir(&req.path).await?;
-        let rel = normalize_rel_path(&req.path).map_err(Status::from)?;
-        let file_name = rel
-            .file_name()
-            .ok_or_else(|| Status::invalid_argument("path must include filename"))?;
-        let path = parent.join(file_name);
-
-        let meta = tokio::fs::symlink_metadata(&path).await.ok();
-        if let Some(m) = meta {
-            if m.file_type().is_symlink() {
-                return Err(Status::invalid_argument("refusing to write to symlink"));
-            }
-            if m.is_dir() {
-                return Err(Status::invalid_argument("path is a directory"));
-            }
-        }
-
-        let tmp = path.with_extension("tmp");
-        let mut f = tokio::fs::File::create(&tmp)
-            .await
-            .map_err(|e| status_from_io("failed to create temp file", e))?;
-        f.write_all(&req.data)
-            .await
-            .map_err(|e| Status::internal(format!("failed to write: {e}")))?;
-        f.flush().await.ok();
-        tokio::fs::rename(&tmp, &path)
-            .await
-            .map_err(|e| status_from_io("failed to persist file", e))?;
-
-        Ok(Response::new(WriteFileResponse { ok: true }))
+        let live = self.live_process_ids().await;
+        let size_bytes = write_file_in(&data_root(), &req, &live).await?;
+        Ok(Response::new(WriteFileResponse {
+            ok: true,
+            size_bytes,
+        }))
     }
 
     async fn rename(
         &self,
         request: Request<RenameRequest>,
     ) -> Result<Response<RenameResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         ensure_fs_write_enabled()?;
         let req = request.into_inner();
+        let root = data_root();
+        let live = self.live_process_ids().await;
         let from = scoped_path(&req.from_path).map_err(Status::from)?;
-        let from = enforce_scoped_existing_path(&from).await?;
+        let from = enforce_scoped_existing_path(&root, &from).await?;
+        ensure_not_live_world(&root, &from, &live).map_err(Status::from)?;
 
-        let to_parent = ensure_scoped_parent_dir(&req.to_path).await?;
+        let to_parent = ensure_scoped_parent_dir(&root, &req.to_path).await?;
         let to_rel = normalize_rel_path(&req.to_path).map_err(Status::from)?;
         let to_name = to_rel
             .file_name()
             .ok_or_else(|| Status::invalid_argument("to_path must include filename"))?;
         let to = to_parent.join(to_name);
+        ensure_not_live_world(&root, &to, &live).map_err(Status::from)?;
 
         if tokio::fs::symlink_metadata(&to).await.is_ok() {
             return Err(Status::already_exists("target already exists"));
@@ -390,10 +618,14 @@ impl FilesystemService for FilesystemApi {
         &self,
         request: Request<RemoveRequest>,
     ) -> Result<Response<RemoveResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         ensure_fs_write_enabled()?;
         let req = request.into_inner();
+        let root = data_root();
         let path = scoped_path(&req.path).map_err(Status::from)?;
-        let path = enforce_scoped_existing_path(&path).await?;
+        let path = enforce_scoped_existing_path(&root, &path).await?;
+        ensure_not_live_world(&root, &path, &self.live_process_ids().await)
+            .map_err(Status::from)?;
 
         let meta = tokio::fs::symlink_metadata(&path)
             .await
@@ -420,8 +652,256 @@ impl FilesystemService for FilesystemApi {
 
         Ok(Response::new(RemoveResponse { ok: true }))
     }
+
+    async fn download_tree(
+        &self,
+        request: Request<DownloadTreeRequest>,
+    ) -> Result<Response<Self::DownloadTreeStream>, Status> {
+        let req = request.into_inner();
+        let dir = scoped_path(&req.path).map_err(Status::from)?;
+        let meta = tokio::fs::metadata(&dir)
+            .await
+            .map_err(|e| status_from_io("failed to stat path", e))?;
+        if !meta.is_dir() {
+            return Err(Status::invalid_argument("path is not a directory"));
+        }
+        let dir = enforce_scoped_existing_path(&data_root(), &dir).await?;
+
+        let size_dir = dir.clone();
+        let size = tokio::task::spawn_blocking(move || crate::backup::tree_size(&size_dir))
+            .await
+            .map_err(|e| Status::internal(format!("download task failed: {e}")))?;
+        if size > MAX_DOWNLOAD_TREE_BYTES {
+            return Err(Status::resource_exhausted(format!(
+                "directory is {size} bytes; downloads are limited to {MAX_DOWNLOAD_TREE_BYTES}"
+            )));
+        }
+
+        // The zip is spooled to a temp file (the writer needs to seek), which
+        // is unlinked once open so an abandoned download leaves nothing behind.
+        let out = std::env::temp_dir().join(format!(
+            "alloy-download-{}.zip",
+            alloy_process::ProcessId::new().0
+        ));
+        let file = tokio::task::spawn_blocking(move || -> anyhow::Result<std::fs::File> {
+            zip_tree(&dir, &out)?;
+            let file = std::fs::File::open(&out);
+            let _ = std::fs::remove_file(&out);
+            Ok(file?)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("download task failed: {e}")))?
+        .map_err(|e| Status::internal(format!("failed to zip directory: {e}")))?;
+
+        let stream = futures_util::stream::unfold(
+            Some(tokio::fs::File::from_std(file)),
+            |file| async move {
+                let mut file = file?;
+                let mut data = vec![0u8; DOWNLOAD_CHUNK_BYTES];
+                match file.read(&mut data).await {
+                    Ok(0) => None,
+                    Ok(n) => {
+                        data.truncate(n);
+                        Some((Ok(DownloadTreeChunk { data }), Some(file)))
+                    }
+                    Err(e) => Some((Err(status_from_io("failed to read zip", e)), None)),
+                }
+            },
+        );
+        Ok(Response::new(Box::pin(stream)))
+    }
 }
 
-pub fn server() -> FilesystemServiceServer<FilesystemApi> {
-    FilesystemServiceServer::new(FilesystemApi)
+pub fn server(manager: ProcessManager) -> FilesystemServiceServer<FilesystemApi> {
+    FilesystemServiceServer::new(FilesystemApi::new(manager))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FsPathError, list_tree, normalize_rel_path, write_file_in, zip_tree};
+    use alloy_proto::agent_v1::WriteFileRequest;
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("alloy-agent-fs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::canonicalize(&root).unwrap()
+    }
+
+    fn chunk(path: &str, offset: u64, data: &[u8], more: bool) -> WriteFileRequest {
+        WriteFileRequest {
+            path: path.to_string(),
+            data: data.to_vec(),
+            offset,
+            more,
+        }
+    }
+
+    fn page(root: &Path, cursor: &str, max_entries: u32) -> (Vec<String>, Option<String>) {
+        let (entries, next) =
+            list_tree(root, root.to_path_buf(), true, cursor.into(), max_entries).unwrap();
+        (entries.into_iter().map(|e| e.path).collect(), next)
+    }
+
+    #[test]
+    fn recursive_listing_pages_continue_after_the_cursor() {
+        let root = temp_root("tree-pages");
+        for file in ["a/x.txt", "a/b/y.txt", "a.txt", "c/z.txt"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "x").unwrap();
+        }
+
+        let (all, next) = page(&root, "", 0);
+        assert_eq!(
+            all,
+            ["a", "a/b", "a/b/y.txt", "a/x.txt", "a.txt", "c", "c/z.txt"]
+        );
+        assert!(next.is_none());
+
+        let mut paged = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let (entries, next) = page(&root, &cursor, 3);
+            assert!(entries.len() <= 3);
+            paged.extend(entries);
+            match next {
+                Some(next) => cursor = next,
+                None => break,
+            }
+        }
+        assert_eq!(paged, all);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn recursive_listing_skips_symlinks_out_of_the_root() {
+        let root = temp_root("tree-links");
+        let outside = temp_root("tree-links-outside");
+        std::fs::write(outside.join("secret.txt"), "x").unwrap();
+        std::fs::create_dir_all(root.join("mods")).unwrap();
+        std::fs::write(root.join("mods").join("a.jar"), "x").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+        std::os::unix::fs::symlink(root.join("mods"), root.join("mods-link")).unwrap();
+
+        // In-root links are listed but not descended.
+        let (entries, _) = page(&root, "", 0);
+        assert_eq!(entries, ["mods", "mods/a.jar", "mods-link"]);
+
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_dir_all(&outside);
+    }
+
+    #[test]
+    fn zipped_tree_entries_are_relative_to_the_requested_dir() {
+        let root = temp_root("zip-tree");
+        let dir = root.join("instances").join("a").join("config");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("server.yml"), "x").unwrap();
+        std::fs::write(dir.join("sub").join("y.toml"), "y").unwrap();
+        std::fs::write(dir.join("session.lock"), "").unwrap();
+
+        let out = root.join("out.zip");
+        zip_tree(&dir, &out).unwrap();
+        let zip = zip::ZipArchive::new(std::fs::File::open(&out).unwrap()).unwrap();
+        let mut names = zip.file_names().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["server.yml", "sub/", "sub/y.toml"]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn writes_cannot_leave_the_root() {
+        assert!(matches!(
+            normalize_rel_path("instances/../../etc/passwd"),
+            Err(FsPathError::Traversal)
+        ));
+        assert!(matches!(
+            normalize_rel_path("/etc/passwd"),
+            Err(FsPathError::Absolute)
+        ));
+
+        let root = temp_root("escape");
+        let outside = temp_root("escape-outside");
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        let err = write_file_in(&root, &chunk("link/x.txt", 0, b"x", false), &HashSet::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.message(), "path escapes data root");
+        assert!(!outside.join("x.txt").exists());
+
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_dir_all(&outside);
+    }
+
+    #[tokio::test]
+    async fn chunked_write_replaces_the_file_on_the_last_chunk() {
+        let root = temp_root("chunked");
+        let target = root.join("server.properties");
+        std::fs::write(&target, "motd=old\n").unwrap();
+        let live = HashSet::new();
+
+        let path = "server.properties";
+        assert_eq!(
+            write_file_in(&root, &chunk(path, 0, b"motd=", true), &live)
+                .await
+                .unwrap(),
+            5
+        );
+        // A chunk at the wrong offset is rejected and the upload can resume.
+        assert!(
+            write_file_in(&root, &chunk(path, 3, b"new", true), &live)
+                .await
+                .is_err()
+        );
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "motd=old\n");
+
+        write_file_in(&root, &chunk(path, 5, b"new\n", false), &live)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "motd=new\n");
+        let names: Vec<String> = std::fs::read_dir(&root)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, ["server.properties"]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn running_instance_world_is_read_only() {
+        let root = temp_root("live-world");
+        for dir in ["instances/mc-1/world", "instances/mc-2/world"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let live = HashSet::from(["mc-1".to_string()]);
+
+        let err = write_file_in(
+            &root,
+            &chunk("instances/mc-1/world/level.dat", 0, b"x", false),
+            &live,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        assert!(!root.join("instances/mc-1/world/level.dat").exists());
+
+        // Config files of a running instance and worlds of stopped ones are fine.
+        for path in [
+            "instances/mc-1/server.properties",
+            "instances/mc-2/world/level.dat",
+        ] {
+            write_file_in(&root, &chunk(path, 0, b"x", false), &live)
+                .await
+                .unwrap();
+        }
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
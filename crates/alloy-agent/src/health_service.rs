@@ -1,11 +1,43 @@
+use alloy_process::ProcessState;
 use alloy_proto::agent_v1::agent_health_service_server::{
     AgentHealthService, AgentHealthServiceServer,
 };
-use alloy_proto::agent_v1::{HealthCheckRequest, HealthCheckResponse, PortAvailability};
+use alloy_proto::agent_v1::{
+    ApplyUpdateRequest, ApplyUpdateResponse, GetNodeResourcesRequest, GetNodeResourcesResponse,
+    HealthCheckRequest, HealthCheckResponse, PortAvailability, PrepareUpdateRequest,
+    PrepareUpdateResponse,
+};
+use std::time::Duration;
 use tonic::{Request, Response, Status};
 
-#[derive(Debug, Default, Clone)]
-pub struct HealthApi;
+use crate::process_manager::ProcessManager;
+
+#[derive(Debug, Clone)]
+pub struct HealthApi {
+    manager: ProcessManager,
+}
+
+impl HealthApi {
+    pub fn new(manager: ProcessManager) -> Self {
+        Self { manager }
+    }
+
+    /// Running and busy (starting/stopping) process ids, sorted.
+    async fn drain_snapshot(&self) -> (Vec<String>, Vec<String>) {
+        let mut running = Vec::new();
+        let mut busy = Vec::new();
+        for p in self.manager.list_processes().await {
+            match p.state {
+                ProcessState::Running => running.push(p.id.0),
+                ProcessState::Starting | ProcessState::Stopping => busy.push(p.id.0),
+                ProcessState::Exited | ProcessState::Failed => {}
+            }
+        }
+        running.sort_unstable();
+        busy.sort_unstable();
+        (running, busy)
+    }
+}
 
 #[tonic::async_trait]
 impl AgentHealthService for HealthApi {
@@ -103,8 +135,129 @@ impl AgentHealthService for HealthApi {
         };
         Ok(Response::new(reply))
     }
+
+    async fn prepare_update(
+        &self,
+        request: Request<PrepareUpdateRequest>,
+    ) -> Result<Response<PrepareUpdateResponse>, Status> {
+        let req = request.into_inner();
+        if req.cancel {
+            if !crate::update_drain::cancel() {
+                return Err(Status::failed_precondition(
+                    "update already applied; drain cannot be cancelled",
+                ));
+            }
+            let (running, busy) = self.drain_snapshot().await;
+            return Ok(Response::new(PrepareUpdateResponse {
+                draining: false,
+                ready: false,
+                running_process_ids: running,
+                busy_process_ids: busy,
+            }));
+        }
+
+        crate::update_drain::begin();
+
+        if req.stop_instances {
+            let timeout = if req.timeout_ms == 0 {
+                Duration::from_secs(30)
+            } else {
+                Duration::from_millis(u64::from(req.timeout_ms))
+            };
+            let (running, _) = self.drain_snapshot().await;
+            let stops = running.iter().map(|id| self.manager.stop(id, timeout));
+            for (id, res) in running
+                .iter()
+                .zip(futures_util::future::join_all(stops).await)
+            {
+                if let Err(err) = res {
+                    tracing::warn!(process_id = %id, %err, "failed to stop instance for update");
+                }
+            }
+        }
+
+        let (running, busy) = self.drain_snapshot().await;
+        let ready = busy.is_empty() && (!req.stop_instances || running.is_empty());
+        Ok(Response::new(PrepareUpdateResponse {
+            draining: true,
+            ready,
+            running_process_ids: running,
+            busy_process_ids: busy,
+        }))
+    }
+
+    async fn apply_update(
+        &self,
+        _request: Request<ApplyUpdateRequest>,
+    ) -> Result<Response<ApplyUpdateResponse>, Status> {
+        if !crate::update_drain::is_draining() {
+            return Err(Status::failed_precondition(
+                "call PrepareUpdate before ApplyUpdate",
+            ));
+        }
+        let (running, busy) = self.drain_snapshot().await;
+        if !busy.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "processes still starting or stopping: {}",
+                busy.join(", ")
+            )));
+        }
+
+        let mut adoptable = Vec::new();
+        let mut native = Vec::new();
+        for id in running {
+            if crate::process_manager::is_container_backed(&id).await {
+                adoptable.push(id);
+            } else {
+                native.push(id);
+            }
+        }
+
+        crate::update_drain::write_handoff(&adoptable)
+            .map_err(|e| Status::internal(format!("failed to write update handoff: {e}")))?;
+        crate::update_drain::mark_applied();
+        tracing::info!(
+            adoptable = adoptable.len(),
+            native = native.len(),
+            "update applied; waiting for the updater to restart the agent"
+        );
+
+        Ok(Response::new(ApplyUpdateResponse {
+            ok: true,
+            adoptable_process_ids: adoptable,
+            native_process_ids: native,
+        }))
+    }
+
+    async fn get_node_resources(
+        &self,
+        _request: Request<GetNodeResourcesRequest>,
+    ) -> Result<Response<GetNodeResourcesResponse>, Status> {
+        let r = crate::node_resources::node_resources(&crate::minecraft::data_root()).await;
+        let running = self
+            .manager
+            .list_processes()
+            .await
+            .iter()
+            .filter(|p| !matches!(p.state, ProcessState::Exited | ProcessState::Failed))
+            .count();
+        let load = r.load_avg.unwrap_or_default();
+        Ok(Response::new(GetNodeResourcesResponse {
+            cpu_count: r.cpu_count,
+            total_mem_bytes: r.memory.map(|m| m.total_bytes).unwrap_or_default(),
+            available_mem_bytes: r.memory.map(|m| m.available_bytes).unwrap_or_default(),
+            has_memory: r.memory.is_some(),
+            load_avg_1m: load.one,
+            load_avg_5m: load.five,
+            load_avg_15m: load.fifteen,
+            has_load_avg: r.load_avg.is_some(),
+            data_root_free_bytes: r.data_root_free_bytes.unwrap_or_default(),
+            has_data_root_free_bytes: r.data_root_free_bytes.is_some(),
+            running_process_count: running as u32,
+        }))
+    }
 }
 
-pub fn server() -> AgentHealthServiceServer<HealthApi> {
-    AgentHealthServiceServer::new(HealthApi)
+pub fn server(manager: ProcessManager) -> AgentHealthServiceServer<HealthApi> {
+    AgentHealthServiceServer::new(HealthApi::new(manager))
 }
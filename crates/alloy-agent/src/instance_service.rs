@@ -1,27 +1,43 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashMap, HashSet},
     path::{Component, Path, PathBuf},
     time::Duration,
 };
 
 use alloy_proto::agent_v1::instance_service_server::{InstanceService, InstanceServiceServer};
 use alloy_proto::agent_v1::{
-    CreateInstanceRequest, CreateInstanceResponse, DeleteInstancePreviewRequest,
-    DeleteInstancePreviewResponse, DeleteInstanceRequest, DeleteInstanceResponse,
-    GetInstanceRequest, GetInstanceResponse, ImportSaveFromUrlRequest, ImportSaveFromUrlResponse,
-    InstanceConfig, InstanceInfo, ListInstancesRequest, ListInstancesResponse,
-    StartInstanceRequest, StartInstanceResponse, StopInstanceRequest, StopInstanceResponse,
-    UpdateInstanceRequest, UpdateInstanceResponse,
+    BackupRecord, BulkApplyParamsRequest, BulkApplyParamsResponse, BulkApplyParamsResult,
+    CancelStagedUpgradeRequest, CancelStagedUpgradeResponse, CreateBackupRequest,
+    CreateBackupResponse, CreateInstanceRequest, CreateInstanceResponse, DeleteExportRequest,
+    DeleteExportResponse, DeleteInstancePreviewRequest, DeleteInstancePreviewResponse,
+    DeleteInstanceRequest, DeleteInstanceResponse, ExportInstanceRequest, ExportInstanceResponse,
+    GetInstanceDiskUsageRequest, GetInstanceDiskUsageResponse, GetInstanceRequest,
+    GetInstanceResponse, GetStagedUpgradeRequest, GetStagedUpgradeResponse, ImportInstanceRequest,
+    ImportInstanceResponse, ImportSaveFromUrlRequest, ImportSaveFromUrlResponse,
+    InspectInstanceRequest, InspectInstanceResponse, InstanceConfig, InstanceInfo,
+    ListBackupsRequest, ListBackupsResponse, ListInstancesRequest, ListInstancesResponse,
+    ReadExportChunkRequest, ReadExportChunkResponse, ReloadInstanceRequest, ReloadInstanceResponse,
+    RestartInstanceRequest, RestartInstanceResponse, RestoreBackupRequest, RestoreBackupResponse,
+    RunInfo, StageUpgradeRequest, StageUpgradeResponse, StartInstanceRequest,
+    StartInstanceResponse, StopInstanceRequest, StopInstanceResponse, UpdateInstanceRequest,
+    UpdateInstanceResponse, WriteImportChunkRequest, WriteImportChunkResponse,
 };
 use futures_util::StreamExt;
 use reqwest::Url;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tonic::{Request, Response, Status};
 
+use crate::archive::{self, ArchiveLimits};
 use crate::port_alloc;
 use crate::process_manager::ProcessManager;
+use crate::staged_upgrade::{self, Phase, StagedUpgrade};
 
 const INSTANCES_DIR: &str = "instances";
+const TRANSFERS_DIR: &str = "transfers";
+const TRANSFER_CHUNK_MAX: u32 = 4 * 1024 * 1024;
+// Written into an imported instance dir so a retried ImportInstance is a no-op.
+const IMPORT_MARKER: &str = ".alloy-import-id";
+const BULK_APPLY_MAX_INSTANCES: usize = 200;
 
 #[derive(Debug)]
 enum IdError {
@@ -126,6 +142,37 @@ async fn save_instance(inst: &PersistedInstance) -> Result<(), Status> {
     Ok(())
 }
 
+/// Instance dir entries that belong in an export (runtime state, local backups
+/// and scratch dirs stay behind).
+fn is_exported_entry(name: &str) -> bool {
+    !(name.starts_with('.')
+        || matches!(
+            name,
+            "run.json" | "run.json.tmp" | "params.json" | "params.json.tmp" | "backups" | "imports"
+        ))
+}
+
+fn transfer_path(kind: &str, id: &str) -> Option<PathBuf> {
+    // Transfer ids share the instance id charset.
+    let id = normalize_instance_id(id).ok()?;
+    Some(
+        data_root()
+            .join(TRANSFERS_DIR)
+            .join(format!("{kind}s"))
+            .join(format!("{id}.zip")),
+    )
+}
+
+fn backup_record_to_proto(r: crate::backup::BackupRecord) -> BackupRecord {
+    BackupRecord {
+        backup_id: r.backup_id,
+        format: r.format,
+        size_bytes: r.size_bytes,
+        sha256: r.sha256,
+        created_at_unix_ms: r.created_at_unix_ms,
+    }
+}
+
 async fn ensure_instance_stopped(
     manager: &ProcessManager,
     instance_id: &str,
@@ -151,10 +198,12 @@ async fn ensure_persisted_ports(inst: &mut PersistedInstance) -> Result<(), Stat
     // This keeps connection info stable across restarts.
     match inst.template_id.as_str() {
         "minecraft:vanilla"
+        | "minecraft:paper"
         | "minecraft:modrinth"
         | "minecraft:import"
         | "minecraft:curseforge"
-        | "terraria:vanilla" => {
+        | "terraria:vanilla"
+        | "terraria:tmodloader" => {
             let current = inst.params.get("port").map(|s| s.trim()).unwrap_or("");
             if current.is_empty() || current == "0" {
                 let port = port_alloc::allocate_tcp_port(0)
@@ -212,6 +261,23 @@ async fn ensure_persisted_ports(inst: &mut PersistedInstance) -> Result<(), Stat
                 save_instance(inst).await?;
             }
         }
+        id if crate::custom_templates::is_custom(id) => {
+            let Some(t) = crate::templates::find_template(id) else {
+                return Ok(());
+            };
+            let current = inst.params.get("port").map(|s| s.trim()).unwrap_or("");
+            if crate::custom_templates::declares_port(&t) && (current.is_empty() || current == "0")
+            {
+                let port = if t.needs_udp_and_tcp {
+                    port_alloc::allocate_dual_port(0)
+                } else {
+                    port_alloc::allocate_tcp_port(0)
+                }
+                .map_err(|e| Status::internal(format!("failed to allocate port: {e}")))?;
+                inst.params.insert("port".to_string(), port.to_string());
+                save_instance(inst).await?;
+            }
+        }
         _ => {}
     }
 
@@ -275,67 +341,11 @@ fn minecraft_level_rel(instance_dir: &Path) -> PathBuf {
     PathBuf::from("worlds/world")
 }
 
-fn extract_zip_safely(zip_path: &Path, out_dir: &Path) -> anyhow::Result<()> {
-    std::fs::create_dir_all(out_dir)?;
-    let f = std::fs::File::open(zip_path)?;
-    let mut archive = zip::ZipArchive::new(f)?;
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let name = file.name().to_string();
-        let trimmed = name.trim_end_matches('/');
-        if trimmed.is_empty() {
-            continue;
-        }
-        let rel = normalize_rel_path(trimmed)
-            .map_err(|e| anyhow::anyhow!("invalid zip path {trimmed:?}: {e}"))?;
-        if rel.as_os_str().is_empty() {
-            continue;
-        }
-
-        let out_path = out_dir.join(&rel);
-        if name.ends_with('/') {
-            std::fs::create_dir_all(&out_path)?;
-            continue;
-        }
-
-        if let Some(parent) = out_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        let tmp_path = out_path.with_extension("tmp");
-        let mut out = std::fs::File::create(&tmp_path)?;
-        std::io::copy(&mut file, &mut out)?;
-        out.sync_all().ok();
-        std::fs::rename(&tmp_path, &out_path)?;
+fn extract_failed(e: anyhow::Error) -> Status {
+    match archive::as_install_failed(&e) {
+        Some(err) => Status::invalid_argument(err.to_string()),
+        None => Status::invalid_argument(format!("failed to extract zip: {e}")),
     }
-
-    Ok(())
-}
-
-fn file_magic_is_zip(path: &Path) -> bool {
-    use std::io::Read;
-
-    let mut f = match std::fs::File::open(path) {
-        Ok(v) => v,
-        Err(_) => return false,
-    };
-    let mut header = [0u8; 4];
-    let n = match f.read(&mut header) {
-        Ok(v) => v,
-        Err(_) => return false,
-    };
-    if n < header.len() {
-        return false;
-    }
-
-    matches!(
-        header,
-        [b'P', b'K', 0x03, 0x04]
-            | [b'P', b'K', 0x05, 0x06]
-            | [b'P', b'K', 0x07, 0x08]
-            | [b'P', b'K', 0x01, 0x02]
-    )
 }
 
 fn find_single_file_by_suffix(root: &Path, suffix: &str) -> anyhow::Result<PathBuf> {
@@ -480,6 +490,154 @@ impl InstanceApi {
     pub fn new(manager: ProcessManager) -> Self {
         Self { manager }
     }
+
+    /// Restarts instances one at a time so a bulk change never takes the whole fleet down.
+    fn spawn_rolling_restart(
+        &self,
+        instance_ids: Vec<String>,
+        stop_timeout_ms: u32,
+        node_default_params: HashMap<String, String>,
+    ) {
+        let api = self.clone();
+        tokio::spawn(async move {
+            for id in instance_ids {
+                let stop = StopInstanceRequest {
+                    instance_id: id.clone(),
+                    timeout_ms: stop_timeout_ms,
+                };
+                if let Err(status) = api.stop(Request::new(stop)).await {
+                    tracing::warn!(instance_id = %id, err = %status.message(), "bulk restart: stop failed");
+                    continue;
+                }
+                let start = StartInstanceRequest {
+                    instance_id: id.clone(),
+                    node_default_params: node_default_params.clone(),
+                };
+                if let Err(status) = api.start(Request::new(start)).await {
+                    tracing::warn!(instance_id = %id, err = %status.message(), "bulk restart: start failed");
+                }
+            }
+        });
+    }
+
+    /// Sleeps until the upgrade window, then runs the upgrade. Restaging or
+    /// cancelling rewrites the state file, which makes this task step aside.
+    fn spawn_staged_upgrade(&self, upgrade: StagedUpgrade) {
+        let api = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(upgrade.delay()).await;
+            let Ok(dir) = instance_dir(&upgrade.instance_id) else {
+                return;
+            };
+            let Some(mut current) = pending_upgrade(&dir, &upgrade.upgrade_id).await else {
+                return;
+            };
+            if let Err(msg) = api.run_staged_upgrade(&dir, &mut current).await {
+                record_phase(&dir, &mut current, Phase::Failed, msg).await;
+            }
+        });
+    }
+
+    async fn run_staged_upgrade(&self, dir: &Path, up: &mut StagedUpgrade) -> Result<(), String> {
+        let id = up.instance_id.clone();
+        let was_running = self.manager.get_status(&id).await.is_some_and(|st| {
+            matches!(
+                st.state,
+                alloy_process::ProcessState::Running | alloy_process::ProcessState::Starting
+            )
+        });
+
+        if was_running && up.warning_seconds > 0 {
+            let msg = format!("warning players; stopping in {}s", up.warning_seconds);
+            record_phase(dir, up, Phase::Warning, msg).await;
+            let cmd = staged_upgrade::warning_command(&up.target_version, up.warning_seconds);
+            if let Err(err) = self.manager.send_stdin(&id, cmd).await {
+                tracing::warn!(instance_id = %id, %err, "staged upgrade: failed to warn players");
+            }
+            tokio::time::sleep(Duration::from_secs(up.warning_seconds.into())).await;
+            // A cancel during the warning still wins.
+            if pending_upgrade(dir, &up.upgrade_id).await.is_none() {
+                return Ok(());
+            }
+        }
+
+        if was_running {
+            record_phase(dir, up, Phase::Stopping, "stopping server").await;
+            let stop = StopInstanceRequest {
+                instance_id: id.clone(),
+                timeout_ms: up.stop_timeout_ms,
+            };
+            self.stop(Request::new(stop))
+                .await
+                .map_err(|s| format!("stop failed: {}", s.message()))?;
+        }
+
+        if let Err(msg) = self.backup_and_swap(dir, up).await {
+            // Nothing was swapped; bring the old version back up.
+            if was_running && let Err(status) = self.start_after_upgrade(up).await {
+                return Err(format!("{msg}; restart failed: {}", status.message()));
+            }
+            return Err(msg);
+        }
+
+        if was_running {
+            record_phase(dir, up, Phase::Starting, "starting server").await;
+            self.start_after_upgrade(up)
+                .await
+                .map_err(|s| format!("upgraded, but start failed: {}", s.message()))?;
+        }
+
+        let msg = format!(
+            "upgraded from {} to {}",
+            up.from_version.as_str(),
+            up.target_version.as_str()
+        );
+        record_phase(dir, up, Phase::Done, msg).await;
+        Ok(())
+    }
+
+    async fn backup_and_swap(&self, dir: &Path, up: &mut StagedUpgrade) -> Result<(), String> {
+        let mut inst = load_instance(&up.instance_id)
+            .await
+            .map_err(|s| s.message().to_string())?;
+
+        if up.backup {
+            record_phase(dir, up, Phase::Backup, "taking pre-upgrade backup").await;
+            let backup_dir = dir.to_path_buf();
+            let template_id = inst.template_id.clone();
+            let backup_id = tokio::task::spawn_blocking(move || {
+                crate::backup::create_snapshot(&backup_dir, &template_id, "pre-upgrade")
+            })
+            .await
+            .map_err(|e| format!("backup task failed: {e}"))?
+            .map_err(|e| format!("backup failed: {e}"))?;
+            up.backup_id = Some(backup_id);
+        }
+
+        let msg = format!("switching to minecraft {}", up.target_version.as_str());
+        record_phase(dir, up, Phase::Swapping, msg).await;
+        // Normally a cache hit; re-downloads if the cache was cleared since staging.
+        let resolved = crate::minecraft_download::resolve_server_jar(&up.target_version)
+            .await
+            .map_err(|e| format!("failed to resolve minecraft {}: {e}", up.target_version))?;
+        crate::minecraft_download::ensure_server_jar(&resolved)
+            .await
+            .map_err(|e| format!("failed to download minecraft {}: {e}", up.target_version))?;
+
+        inst.params
+            .insert("version".to_string(), up.target_version.clone());
+        save_instance(&inst)
+            .await
+            .map_err(|s| s.message().to_string())
+    }
+
+    async fn start_after_upgrade(&self, up: &StagedUpgrade) -> Result<(), Status> {
+        let start = StartInstanceRequest {
+            instance_id: up.instance_id.clone(),
+            node_default_params: crate::node_defaults::load().await.into_iter().collect(),
+        };
+        self.start(Request::new(start)).await.map(|_| ())
+    }
 }
 
 #[tonic::async_trait]
@@ -488,6 +646,7 @@ impl InstanceService for InstanceApi {
         &self,
         request: Request<CreateInstanceRequest>,
     ) -> Result<Response<CreateInstanceResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         let req = request.into_inner();
         let instance_id = alloy_process::ProcessId::new().0;
 
@@ -550,6 +709,15 @@ impl InstanceService for InstanceApi {
             .await
             .map_err(|e| Status::internal(format!("failed to create instances dir: {e}")))?;
 
+        // Includes instances stopped before an agent restart, from their run.json.
+        let mut statuses: HashMap<String, alloy_process::ProcessStatus> = self
+            .manager
+            .list_instances()
+            .await
+            .into_iter()
+            .map(|s| (s.id.0.clone(), s))
+            .collect();
+
         let mut out = Vec::new();
         let mut rd = tokio::fs::read_dir(&base)
             .await
@@ -570,10 +738,8 @@ impl InstanceService for InstanceApi {
                 Err(_) => continue,
             };
 
-            let status = self
-                .manager
-                .get_status(&name)
-                .await
+            let status = statuses
+                .remove(&name)
                 .map(crate::process_service::map_status);
 
             out.push(InstanceInfo {
@@ -589,6 +755,8 @@ impl InstanceService for InstanceApi {
         &self,
         request: Request<StartInstanceRequest>,
     ) -> Result<Response<StartInstanceResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        crate::update_drain::ensure_accepting_starts()?;
         let req = request.into_inner();
         let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
         let mut inst = load_instance(&id).await?;
@@ -596,6 +764,12 @@ impl InstanceService for InstanceApi {
         // If ports were omitted/blank, assign once and persist.
         ensure_persisted_ports(&mut inst).await?;
 
+        // Node defaults are merged by the process manager on every start and never
+        // persisted into the instance directory.
+        crate::node_defaults::save(&req.node_default_params)
+            .await
+            .map_err(|e| Status::internal(format!("failed to store node defaults: {e}")))?;
+
         let status = self
             .manager
             .start_from_template_with_process_id(&id, &inst.template_id, inst.params)
@@ -611,6 +785,7 @@ impl InstanceService for InstanceApi {
         &self,
         request: Request<ImportSaveFromUrlRequest>,
     ) -> Result<Response<ImportSaveFromUrlResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         let req = request.into_inner();
         let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
         ensure_instance_stopped(&self.manager, &id).await?;
@@ -707,12 +882,13 @@ impl InstanceService for InstanceApi {
                 let nonce = alloy_process::ProcessId::new().0;
 
                 if template_id == "minecraft:vanilla"
+                    || template_id == "minecraft:paper"
                     || template_id == "minecraft:modrinth"
                     || template_id == "minecraft:import"
                     || template_id == "minecraft:curseforge"
                 {
                     // Minecraft expects a world directory (zip recommended).
-                    let is_zip = is_zip_hint || file_magic_is_zip(&download_path2);
+                    let is_zip = is_zip_hint || archive::has_zip_magic(&download_path2);
                     if !is_zip {
                         return Err(Status::invalid_argument(
                             "minecraft save import expects a .zip world",
@@ -720,9 +896,8 @@ impl InstanceService for InstanceApi {
                     }
 
                     let extracted_root = imports_dir2.join(format!("extracted-{nonce}"));
-                    extract_zip_safely(&download_path2, &extracted_root).map_err(|e| {
-                        Status::invalid_argument(format!("failed to extract zip: {e}"))
-                    })?;
+                    archive::extract_zip_safely(&download_path2, &extracted_root)
+                        .map_err(extract_failed)?;
                     let world_root = find_minecraft_world_root(&extracted_root).map_err(|e| {
                         Status::invalid_argument(format!("invalid minecraft world: {e}"))
                     })?;
@@ -757,7 +932,7 @@ impl InstanceService for InstanceApi {
                     return Ok(("minecraft world imported".to_string(), target, backup));
                 }
 
-                if template_id == "terraria:vanilla" {
+                if template_id == "terraria:vanilla" || template_id == "terraria:tmodloader" {
                     let world_name = params
                         .get("world_name")
                         .map(|s| s.trim())
@@ -769,12 +944,11 @@ impl InstanceService for InstanceApi {
                     })?;
                     let target = worlds_dir.join(format!("{world_name}.wld"));
 
-                    let is_zip = is_zip_hint || file_magic_is_zip(&download_path2);
+                    let is_zip = is_zip_hint || archive::has_zip_magic(&download_path2);
                     let source_wld = if is_zip {
                         let extracted_root = imports_dir2.join(format!("extracted-{nonce}"));
-                        extract_zip_safely(&download_path2, &extracted_root).map_err(|e| {
-                            Status::invalid_argument(format!("failed to extract zip: {e}"))
-                        })?;
+                        archive::extract_zip_safely(&download_path2, &extracted_root)
+                            .map_err(extract_failed)?;
                         let wld =
                             find_single_file_by_suffix(&extracted_root, ".wld").map_err(|e| {
                                 Status::invalid_argument(format!("invalid terraria save: {e}"))
@@ -808,7 +982,7 @@ impl InstanceService for InstanceApi {
                 }
 
                 if template_id == "dst:vanilla" {
-                    let is_zip = is_zip_hint || file_magic_is_zip(&download_path2);
+                    let is_zip = is_zip_hint || archive::has_zip_magic(&download_path2);
                     if !is_zip {
                         return Err(Status::invalid_argument(
                             "dst save import expects a .zip cluster (Cluster_1/)",
@@ -816,9 +990,8 @@ impl InstanceService for InstanceApi {
                     }
 
                     let extracted_root = imports_dir2.join(format!("extracted-{nonce}"));
-                    extract_zip_safely(&download_path2, &extracted_root).map_err(|e| {
-                        Status::invalid_argument(format!("failed to extract zip: {e}"))
-                    })?;
+                    archive::extract_zip_safely(&download_path2, &extracted_root)
+                        .map_err(extract_failed)?;
 
                     let cluster_root = find_dst_cluster_root(&extracted_root)
                         .map_err(|e| Status::invalid_argument(format!("invalid dst save: {e}")))?;
@@ -871,10 +1044,463 @@ impl InstanceService for InstanceApi {
         }))
     }
 
+    async fn create_backup(
+        &self,
+        request: Request<CreateBackupRequest>,
+    ) -> Result<Response<CreateBackupResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
+        let format = crate::backup::ArchiveFormat::parse(&req.format)?;
+
+        let inst = load_instance(&id).await?;
+        if !crate::backup::supports_backups(&inst.template_id) {
+            return Err(crate::backup::BackupError::Unsupported.into());
+        }
+        let instance_dir = instance_dir(&id).map_err(Status::from)?;
+
+        let record = tokio::task::spawn_blocking(move || {
+            crate::backup::create_backup(&instance_dir, &inst.template_id, "manual", format)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("backup task failed: {e}")))?
+        .map_err(|e| Status::internal(format!("backup failed: {e:#}")))?;
+
+        Ok(Response::new(CreateBackupResponse {
+            backup: Some(backup_record_to_proto(record)),
+        }))
+    }
+
+    async fn list_backups(
+        &self,
+        request: Request<ListBackupsRequest>,
+    ) -> Result<Response<ListBackupsResponse>, Status> {
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
+        let _ = load_instance(&id).await?;
+        let instance_dir = instance_dir(&id).map_err(Status::from)?;
+
+        let backups =
+            tokio::task::spawn_blocking(move || crate::backup::list_backups(&instance_dir))
+                .await
+                .map_err(|e| Status::internal(format!("list backups task failed: {e}")))?;
+
+        Ok(Response::new(ListBackupsResponse {
+            backups: backups.into_iter().map(backup_record_to_proto).collect(),
+        }))
+    }
+
+    async fn restore_backup(
+        &self,
+        request: Request<RestoreBackupRequest>,
+    ) -> Result<Response<RestoreBackupResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.process_id).map_err(Status::from)?;
+        ensure_instance_stopped(&self.manager, &id).await?;
+
+        let inst = load_instance(&id).await?;
+        let instance_dir = instance_dir(&id).map_err(Status::from)?;
+        let backup_id = req.backup_id;
+
+        let pre_restore_backup_id = tokio::task::spawn_blocking(move || {
+            crate::backup::restore_backup(&instance_dir, &inst.template_id, &backup_id)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("restore task failed: {e}")))??;
+
+        Ok(Response::new(RestoreBackupResponse {
+            ok: true,
+            pre_restore_backup_id,
+        }))
+    }
+
+    async fn stage_upgrade(
+        &self,
+        request: Request<StageUpgradeRequest>,
+    ) -> Result<Response<StageUpgradeResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
+        let inst = load_instance(&id).await?;
+        if inst.template_id != "minecraft:vanilla" {
+            return Err(Status::failed_precondition(crate::error_payload::encode(
+                "unsupported_template",
+                "staged upgrades are only supported for minecraft:vanilla instances",
+                None,
+                Some("Modpack instances pin their Minecraft version in the pack.".to_string()),
+            )));
+        }
+
+        let invalid = |field: &str, message: String| {
+            let mut fields = BTreeMap::new();
+            fields.insert(field.to_string(), message.clone());
+            Status::invalid_argument(crate::error_payload::encode(
+                "invalid_param",
+                message,
+                Some(fields),
+                None,
+            ))
+        };
+        let target = req.target_version.trim();
+        if target.is_empty() {
+            return Err(invalid("target_version", "Required.".to_string()));
+        }
+        if req.warning_seconds > staged_upgrade::MAX_WARNING_SECONDS {
+            return Err(invalid(
+                "warning_seconds",
+                format!("At most {} seconds.", staged_upgrade::MAX_WARNING_SECONDS),
+            ));
+        }
+        let now = staged_upgrade::now_unix_ms();
+        if req.run_at_unix_ms > now + staged_upgrade::MAX_LEAD_TIME.as_millis() as u64 {
+            return Err(invalid(
+                "run_at_unix_ms",
+                "Must be within 30 days.".to_string(),
+            ));
+        }
+
+        let dir = instance_dir(&id).map_err(Status::from)?;
+        if let Some(existing) = staged_upgrade::load(&dir).await
+            && !existing.phase.is_finished()
+            && !existing.phase.is_cancellable()
+        {
+            return Err(Status::failed_precondition(format!(
+                "an upgrade is already running ({})",
+                existing.phase.as_str()
+            )));
+        }
+
+        let resolved = crate::minecraft_download::resolve_server_jar(target)
+            .await
+            .map_err(|e| {
+                Status::invalid_argument(crate::error_payload::encode(
+                    "download_failed",
+                    format!("failed to resolve minecraft server jar: {e}"),
+                    None,
+                    Some("Check the version id and network connectivity.".to_string()),
+                ))
+            })?;
+        let java = crate::process_manager::resolve_java_path(&inst.params);
+        let probe = java.clone();
+        let have_java =
+            tokio::task::spawn_blocking(move || crate::process_manager::detect_java_major(&probe))
+                .await
+                .ok()
+                .and_then(Result::ok);
+        if let Some(have) = have_java
+            && have != resolved.java_major
+        {
+            return Err(Status::failed_precondition(crate::error_payload::encode(
+                "java_major_mismatch",
+                format!(
+                    "Need Java {} for Minecraft {}, but {} is Java {have}.",
+                    resolved.java_major,
+                    resolved.version_id,
+                    java.display()
+                ),
+                None,
+                Some(format!(
+                    "Install Java {} before scheduling this upgrade.",
+                    resolved.java_major
+                )),
+            )));
+        }
+        // Download now so the maintenance window only pays for stop, swap and start.
+        crate::minecraft_download::ensure_server_jar(&resolved)
+            .await
+            .map_err(|e| {
+                Status::internal(crate::error_payload::encode(
+                    "download_failed",
+                    format!("failed to download minecraft server jar: {e}"),
+                    None,
+                    Some("Try again; if it persists, clear cache and retry.".to_string()),
+                ))
+            })?;
+
+        // The upgrade may start after an agent restart; it reads the defaults back from here.
+        crate::node_defaults::save(&req.node_default_params)
+            .await
+            .map_err(|e| Status::internal(format!("failed to store node defaults: {e}")))?;
+
+        let mut upgrade = StagedUpgrade {
+            instance_id: id,
+            upgrade_id: alloy_process::ProcessId::new().0,
+            from_version: inst.params.get("version").cloned().unwrap_or_default(),
+            target_version: resolved.version_id,
+            run_at_unix_ms: req.run_at_unix_ms,
+            warning_seconds: req.warning_seconds,
+            backup: req.backup,
+            stop_timeout_ms: req.stop_timeout_ms,
+            phase: Phase::Staged,
+            message: String::new(),
+            backup_id: None,
+            updated_at_unix_ms: now,
+        };
+        let msg = format!(
+            "minecraft {} downloaded; upgrade scheduled",
+            upgrade.target_version.as_str()
+        );
+        upgrade.set_phase(Phase::Staged, msg);
+        staged_upgrade::save(&dir, &upgrade)
+            .await
+            .map_err(|e| Status::internal(format!("failed to persist staged upgrade: {e}")))?;
+        self.spawn_staged_upgrade(upgrade.clone());
+
+        Ok(Response::new(StageUpgradeResponse {
+            upgrade: Some(upgrade.to_proto()),
+        }))
+    }
+
+    async fn get_staged_upgrade(
+        &self,
+        request: Request<GetStagedUpgradeRequest>,
+    ) -> Result<Response<GetStagedUpgradeResponse>, Status> {
+        let req = request.into_inner();
+        let dir = instance_dir(&req.instance_id).map_err(Status::from)?;
+        let upgrade = staged_upgrade::load(&dir).await;
+        Ok(Response::new(GetStagedUpgradeResponse {
+            upgrade: upgrade.map(|up| up.to_proto()),
+        }))
+    }
+
+    async fn cancel_staged_upgrade(
+        &self,
+        request: Request<CancelStagedUpgradeRequest>,
+    ) -> Result<Response<CancelStagedUpgradeResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        let dir = instance_dir(&req.instance_id).map_err(Status::from)?;
+        let Some(mut upgrade) = staged_upgrade::load(&dir).await else {
+            return Err(Status::not_found("no staged upgrade"));
+        };
+        if !upgrade.phase.is_cancellable() {
+            return Err(Status::failed_precondition(format!(
+                "upgrade can no longer be cancelled ({})",
+                upgrade.phase.as_str()
+            )));
+        }
+        record_phase(&dir, &mut upgrade, Phase::Cancelled, "cancelled").await;
+        Ok(Response::new(CancelStagedUpgradeResponse {
+            upgrade: Some(upgrade.to_proto()),
+        }))
+    }
+
+    async fn export_instance(
+        &self,
+        request: Request<ExportInstanceRequest>,
+    ) -> Result<Response<ExportInstanceResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
+        ensure_instance_stopped(&self.manager, &id).await?;
+        let _ = load_instance(&id).await?;
+
+        let dir = instance_dir(&id).map_err(Status::from)?;
+        let export_id = format!("{id}-{}", alloy_process::ProcessId::new().0);
+        let out = transfer_path("export", &export_id)
+            .ok_or_else(|| Status::internal("invalid export_id"))?;
+
+        let size_bytes = tokio::task::spawn_blocking(move || -> anyhow::Result<u64> {
+            if let Some(parent) = out.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut entries = std::fs::read_dir(&dir)?
+                .flatten()
+                .filter_map(|e| e.file_name().to_str().map(str::to_string))
+                .filter(|name| is_exported_entry(name))
+                .collect::<Vec<_>>();
+            entries.sort();
+            crate::backup::write_zip(&dir, &entries, &out)?;
+            Ok(std::fs::metadata(&out)?.len())
+        })
+        .await
+        .map_err(|e| Status::internal(format!("export task failed: {e}")))?
+        .map_err(|e| Status::internal(format!("failed to export instance: {e}")))?;
+
+        Ok(Response::new(ExportInstanceResponse {
+            export_id,
+            size_bytes,
+        }))
+    }
+
+    async fn read_export_chunk(
+        &self,
+        request: Request<ReadExportChunkRequest>,
+    ) -> Result<Response<ReadExportChunkResponse>, Status> {
+        let req = request.into_inner();
+        let path = transfer_path("export", &req.export_id)
+            .ok_or_else(|| Status::invalid_argument("invalid export_id"))?;
+        let limit = if req.limit == 0 {
+            TRANSFER_CHUNK_MAX
+        } else {
+            req.limit.min(TRANSFER_CHUNK_MAX)
+        };
+
+        let mut f = tokio::fs::File::open(&path)
+            .await
+            .map_err(|_| Status::not_found("export not found"))?;
+        let len = f
+            .metadata()
+            .await
+            .map_err(|e| Status::internal(format!("failed to stat export: {e}")))?
+            .len();
+        if req.offset > len {
+            return Err(Status::invalid_argument(
+                "offset is past the end of the export",
+            ));
+        }
+        f.seek(std::io::SeekFrom::Start(req.offset))
+            .await
+            .map_err(|e| Status::internal(format!("failed to read export: {e}")))?;
+        let mut data = Vec::with_capacity(limit as usize);
+        f.take(limit as u64)
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| Status::internal(format!("failed to read export: {e}")))?;
+
+        let eof = req.offset + data.len() as u64 >= len;
+        Ok(Response::new(ReadExportChunkResponse { data, eof }))
+    }
+
+    async fn delete_export(
+        &self,
+        request: Request<DeleteExportRequest>,
+    ) -> Result<Response<DeleteExportResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        let path = transfer_path("export", &req.export_id)
+            .ok_or_else(|| Status::invalid_argument("invalid export_id"))?;
+        let _ = tokio::fs::remove_file(&path).await;
+        Ok(Response::new(DeleteExportResponse { ok: true }))
+    }
+
+    async fn write_import_chunk(
+        &self,
+        request: Request<WriteImportChunkRequest>,
+    ) -> Result<Response<WriteImportChunkResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        let path = transfer_path("import", &req.import_id)
+            .ok_or_else(|| Status::invalid_argument("invalid import_id"))?
+            .with_extension("zip.part");
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| Status::internal(format!("failed to create imports dir: {e}")))?;
+        }
+
+        let mut f = if req.offset == 0 {
+            tokio::fs::File::create(&path).await
+        } else {
+            tokio::fs::OpenOptions::new().append(true).open(&path).await
+        }
+        .map_err(|e| Status::internal(format!("failed to open import: {e}")))?;
+        let have = f
+            .metadata()
+            .await
+            .map_err(|e| Status::internal(format!("failed to stat import: {e}")))?
+            .len();
+        if have != req.offset {
+            return Err(Status::failed_precondition(format!(
+                "import offset mismatch: have {have} bytes, got offset {}",
+                req.offset
+            )));
+        }
+        f.write_all(&req.data)
+            .await
+            .map_err(|e| Status::internal(format!("failed to write import: {e}")))?;
+        f.flush()
+            .await
+            .map_err(|e| Status::internal(format!("failed to flush import: {e}")))?;
+
+        Ok(Response::new(WriteImportChunkResponse {
+            size_bytes: have + req.data.len() as u64,
+        }))
+    }
+
+    async fn import_instance(
+        &self,
+        request: Request<ImportInstanceRequest>,
+    ) -> Result<Response<ImportInstanceResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
+        let part = transfer_path("import", &req.import_id)
+            .ok_or_else(|| Status::invalid_argument("invalid import_id"))?
+            .with_extension("zip.part");
+        let dir = instance_dir(&id).map_err(Status::from)?;
+
+        if tokio::fs::metadata(&dir).await.is_ok() {
+            let marker = tokio::fs::read_to_string(dir.join(IMPORT_MARKER))
+                .await
+                .unwrap_or_default();
+            if marker.trim() == req.import_id.trim() {
+                let inst = load_instance(&id).await?;
+                return Ok(Response::new(ImportInstanceResponse {
+                    config: Some(inst.to_proto()),
+                }));
+            }
+            return Err(Status::already_exists(
+                "instance already exists on this node",
+            ));
+        }
+
+        let have = tokio::fs::metadata(&part)
+            .await
+            .map_err(|_| Status::not_found("import not found"))?
+            .len();
+        if have != req.size_bytes {
+            return Err(Status::failed_precondition(format!(
+                "import is incomplete: have {have} of {} bytes",
+                req.size_bytes
+            )));
+        }
+
+        let import_id = req.import_id.trim().to_string();
+        let id2 = id.clone();
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let staging =
+                part.with_extension(format!("staging-{}", alloy_process::ProcessId::new().0));
+            let res = (|| -> anyhow::Result<()> {
+                archive::extract_zip(&part, &staging, ArchiveLimits::unlimited())?;
+                let raw = std::fs::read(staging.join("instance.json"))
+                    .map_err(|_| anyhow::anyhow!("archive is missing instance.json"))?;
+                let inst = serde_json::from_slice::<PersistedInstance>(&raw)?;
+                if inst.instance_id != id2 {
+                    anyhow::bail!(
+                        "archive holds instance {:?}, expected {id2:?}",
+                        inst.instance_id
+                    );
+                }
+                std::fs::write(staging.join(IMPORT_MARKER), &import_id)?;
+                if let Some(parent) = dir.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::rename(&staging, &dir)?;
+                Ok(())
+            })();
+            let _ = std::fs::remove_dir_all(&staging);
+            if res.is_ok() {
+                let _ = std::fs::remove_file(&part);
+            }
+            res
+        })
+        .await
+        .map_err(|e| Status::internal(format!("import task failed: {e}")))?
+        .map_err(|e| Status::invalid_argument(format!("failed to import instance: {e}")))?;
+
+        let inst = load_instance(&id).await?;
+        Ok(Response::new(ImportInstanceResponse {
+            config: Some(inst.to_proto()),
+        }))
+    }
+
     async fn stop(
         &self,
         request: Request<StopInstanceRequest>,
     ) -> Result<Response<StopInstanceResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         let req = request.into_inner();
         let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
 
@@ -895,10 +1521,101 @@ impl InstanceService for InstanceApi {
         }))
     }
 
+    async fn restart(
+        &self,
+        request: Request<RestartInstanceRequest>,
+    ) -> Result<Response<RestartInstanceResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        crate::update_drain::ensure_accepting_starts()?;
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
+
+        let timeout = if req.timeout_ms == 0 {
+            std::time::Duration::from_secs(30)
+        } else {
+            std::time::Duration::from_millis(req.timeout_ms as u64)
+        };
+
+        let status = match self.manager.restart(&id, timeout).await {
+            Ok(status) => status,
+            Err(e) => {
+                let no_params = crate::error_payload::decode(&e)
+                    .is_some_and(|p| p.code == "params_unavailable");
+                return Err(if no_params {
+                    Status::failed_precondition(e.to_string())
+                } else {
+                    Status::invalid_argument(e.to_string())
+                });
+            }
+        };
+
+        Ok(Response::new(RestartInstanceResponse {
+            status: Some(crate::process_service::map_status(status)),
+        }))
+    }
+
+    async fn reload(
+        &self,
+        request: Request<ReloadInstanceRequest>,
+    ) -> Result<Response<ReloadInstanceResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
+
+        let status = match self.manager.reload(&id).await {
+            Ok(status) => status,
+            Err(e) => {
+                let not_found =
+                    crate::error_payload::decode(&e).is_some_and(|p| p.code == "not_found");
+                return Err(if not_found {
+                    Status::not_found(e.to_string())
+                } else {
+                    Status::failed_precondition(e.to_string())
+                });
+            }
+        };
+
+        Ok(Response::new(ReloadInstanceResponse {
+            status: Some(crate::process_service::map_status(status)),
+        }))
+    }
+
+    async fn inspect(
+        &self,
+        request: Request<InspectInstanceRequest>,
+    ) -> Result<Response<InspectInstanceResponse>, Status> {
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
+        let run = self
+            .manager
+            .get_run_info(&id)
+            .await
+            .ok_or_else(|| Status::not_found("instance has not been started on this node"))?;
+
+        Ok(Response::new(InspectInstanceResponse {
+            run: Some(RunInfo {
+                process_id: run.process_id,
+                template_id: run.template_id,
+                started_at_unix_ms: run.started_at_unix_ms,
+                agent_version: run.agent_version,
+                pid: run.pid.unwrap_or_default(),
+                has_pid: run.pid.is_some(),
+                container_name: run.container_name.unwrap_or_default(),
+                container_id: run.container_id.unwrap_or_default(),
+                exec: run.exec,
+                args: run.args,
+                cwd: run.cwd,
+                params: run.params.into_iter().collect(),
+                env: run.env.into_iter().collect(),
+            }),
+        }))
+    }
+
     async fn delete(
         &self,
         request: Request<DeleteInstanceRequest>,
     ) -> Result<Response<DeleteInstanceResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         let req = request.into_inner();
         let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
 
@@ -909,13 +1626,46 @@ impl InstanceService for InstanceApi {
             return Err(Status::not_found("instance not found"));
         }
 
-        tokio::fs::remove_dir_all(&dir)
+        self.manager
+            .delete_instance(&id, true)
             .await
-            .map_err(|e| Status::internal(format!("failed to delete instance: {e}")))?;
+            .map_err(|e| Status::internal(format!("failed to delete instance: {e:#}")))?;
 
         Ok(Response::new(DeleteInstanceResponse { ok: true }))
     }
 
+    async fn get_disk_usage(
+        &self,
+        request: Request<GetInstanceDiskUsageRequest>,
+    ) -> Result<Response<GetInstanceDiskUsageResponse>, Status> {
+        let req = request.into_inner();
+        let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
+        let inst = load_instance(&id).await?;
+        let instance_dir = instance_dir(&id).map_err(Status::from)?;
+
+        let usage = crate::disk_usage::instance_disk_usage(&instance_dir, &inst.template_id)
+            .await
+            .map_err(|e| Status::internal(format!("disk usage failed: {e}")))?;
+        let data_root = data_root();
+        let free_bytes =
+            tokio::task::spawn_blocking(move || crate::process_manager::free_bytes(&data_root))
+                .await
+                .ok()
+                .flatten();
+
+        Ok(Response::new(GetInstanceDiskUsageResponse {
+            instance_id: id,
+            total_bytes: usage.total_bytes,
+            world_bytes: usage.world_bytes,
+            logs_bytes: usage.logs_bytes,
+            backups_bytes: usage.backups_bytes,
+            cache_shared_bytes: usage.cache_shared_bytes,
+            truncated: usage.truncated,
+            free_bytes: free_bytes.unwrap_or(0),
+            min_free_bytes: crate::process_manager::min_free_space_bytes(),
+        }))
+    }
+
     async fn delete_preview(
         &self,
         request: Request<DeleteInstancePreviewRequest>,
@@ -926,49 +1676,17 @@ impl InstanceService for InstanceApi {
         // If running, refuse preview to avoid races and to force explicit stop first.
         ensure_instance_stopped(&self.manager, &id).await?;
 
-        let dir = instance_dir(&id).map_err(Status::from)?;
-        if tokio::fs::metadata(&dir).await.is_err() {
-            return Err(Status::not_found("instance not found"));
-        }
-
-        let size_bytes = tokio::task::spawn_blocking({
-            let dir = dir.clone();
-            move || -> u64 {
-                fn walk(path: &std::path::Path) -> u64 {
-                    let meta = match std::fs::symlink_metadata(path) {
-                        Ok(m) => m,
-                        Err(_) => return 0,
-                    };
-                    // Count symlink itself, but don't traverse outside.
-                    if meta.file_type().is_symlink() {
-                        return meta.len();
-                    }
-                    if meta.is_file() {
-                        return meta.len();
-                    }
-                    if !meta.is_dir() {
-                        return 0;
-                    }
-                    let mut sum = 0u64;
-                    let rd = match std::fs::read_dir(path) {
-                        Ok(v) => v,
-                        Err(_) => return 0,
-                    };
-                    for e in rd.flatten() {
-                        sum = sum.saturating_add(walk(&e.path()));
-                    }
-                    sum
-                }
-                walk(&dir)
-            }
-        })
-        .await
-        .unwrap_or(0);
+        let preview = self
+            .manager
+            .delete_instance_preview(&id)
+            .await
+            .map_err(|e| Status::failed_precondition(format!("{e:#}")))?
+            .ok_or_else(|| Status::not_found("instance not found"))?;
 
         Ok(Response::new(DeleteInstancePreviewResponse {
             instance_id: id,
-            path: dir.display().to_string(),
-            size_bytes,
+            path: preview.path.display().to_string(),
+            size_bytes: preview.size_bytes,
         }))
     }
 
@@ -976,6 +1694,7 @@ impl InstanceService for InstanceApi {
         &self,
         request: Request<UpdateInstanceRequest>,
     ) -> Result<Response<UpdateInstanceResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         let req = request.into_inner();
         let id = normalize_instance_id(&req.instance_id).map_err(Status::from)?;
 
@@ -1007,6 +1726,163 @@ impl InstanceService for InstanceApi {
             config: Some(inst.to_proto()),
         }))
     }
+
+    async fn bulk_apply_params(
+        &self,
+        request: Request<BulkApplyParamsRequest>,
+    ) -> Result<Response<BulkApplyParamsResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        if req.instance_ids.is_empty() {
+            return Err(Status::invalid_argument("instance_ids is empty"));
+        }
+        if req.instance_ids.len() > BULK_APPLY_MAX_INSTANCES {
+            return Err(Status::invalid_argument(format!(
+                "at most {BULK_APPLY_MAX_INSTANCES} instances per request"
+            )));
+        }
+        if req.params.is_empty() {
+            return Err(Status::invalid_argument("params is empty"));
+        }
+        let patch: BTreeMap<String, String> = req.params.into_iter().collect();
+
+        let mut seen = HashSet::new();
+        let mut results = Vec::new();
+        let mut to_restart = Vec::new();
+        for raw_id in req.instance_ids {
+            if !seen.insert(raw_id.trim().to_string()) {
+                continue;
+            }
+            let outcome = async {
+                let id = normalize_instance_id(&raw_id).map_err(Status::from)?;
+                let running = match self.manager.get_status(&id).await.map(|s| s.state) {
+                    Some(alloy_process::ProcessState::Running) => true,
+                    Some(
+                        alloy_process::ProcessState::Starting
+                        | alloy_process::ProcessState::Stopping,
+                    ) => {
+                        return Err(Status::failed_precondition(
+                            "instance is starting or stopping; retry once it settles",
+                        ));
+                    }
+                    _ => false,
+                };
+
+                let mut inst = load_instance(&id).await?;
+                let template = crate::templates::find_template(&inst.template_id)
+                    .ok_or_else(|| Status::invalid_argument("unknown template_id"))?;
+                if let Some(key) = patch
+                    .keys()
+                    .find(|k| !template.params.iter().any(|p| &p.key == *k))
+                {
+                    return Err(Status::invalid_argument(format!(
+                        "param {key} is not supported by template {}",
+                        inst.template_id
+                    )));
+                }
+                for (key, value) in &patch {
+                    if value.trim().is_empty() {
+                        inst.params.remove(key);
+                    } else {
+                        inst.params.insert(key.clone(), value.clone());
+                    }
+                }
+                crate::templates::apply_params(template, &inst.params)
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+                ensure_persisted_ports(&mut inst).await?;
+                save_instance(&inst).await?;
+                Ok((inst, running))
+            }
+            .await;
+
+            results.push(match outcome {
+                Ok((inst, running)) => {
+                    let restart = running && req.restart_running;
+                    if restart {
+                        to_restart.push(inst.instance_id.clone());
+                    }
+                    BulkApplyParamsResult {
+                        instance_id: inst.instance_id,
+                        ok: true,
+                        error: String::new(),
+                        params: crate::process_manager::redact_params(inst.params)
+                            .into_iter()
+                            .collect(),
+                        restart_pending: running && !restart,
+                        restart_scheduled: restart,
+                    }
+                }
+                Err(status) => BulkApplyParamsResult {
+                    instance_id: raw_id,
+                    ok: false,
+                    error: status.message().to_string(),
+                    ..Default::default()
+                },
+            });
+        }
+
+        if !to_restart.is_empty() {
+            self.spawn_rolling_restart(to_restart, req.stop_timeout_ms, req.node_default_params);
+        }
+
+        Ok(Response::new(BulkApplyParamsResponse { results }))
+    }
+}
+
+/// The staged upgrade with `upgrade_id`, if it is still waiting to run.
+async fn pending_upgrade(dir: &Path, upgrade_id: &str) -> Option<StagedUpgrade> {
+    staged_upgrade::load(dir)
+        .await
+        .filter(|up| up.upgrade_id == upgrade_id && up.phase.is_cancellable())
+}
+
+async fn record_phase(
+    dir: &Path,
+    up: &mut StagedUpgrade,
+    phase: Phase,
+    message: impl Into<String>,
+) {
+    up.set_phase(phase, message);
+    tracing::info!(
+        instance_id = %up.instance_id,
+        phase = phase.as_str(),
+        message = %up.message,
+        "staged upgrade"
+    );
+    if let Err(err) = staged_upgrade::save(dir, up).await {
+        tracing::warn!(instance_id = %up.instance_id, %err, "failed to persist staged upgrade");
+    }
+}
+
+/// Re-arms upgrades staged before the agent restarted; ones that were mid-way
+/// are marked failed since the server may need attention.
+pub fn resume_staged_upgrades(manager: ProcessManager) {
+    tokio::spawn(async move {
+        let api = InstanceApi::new(manager);
+        let Ok(mut rd) = tokio::fs::read_dir(data_root().join(INSTANCES_DIR)).await else {
+            return;
+        };
+        while let Ok(Some(entry)) = rd.next_entry().await {
+            let dir = entry.path();
+            let Some(mut up) = staged_upgrade::load(&dir).await else {
+                continue;
+            };
+            if up.phase.is_finished() {
+                continue;
+            }
+            if up.phase.is_cancellable() {
+                record_phase(&dir, &mut up, Phase::Staged, "re-armed after agent restart").await;
+                api.spawn_staged_upgrade(up);
+            } else {
+                let msg = format!(
+                    "interrupted by agent restart during {}; check the instance",
+                    up.phase.as_str()
+                );
+                record_phase(&dir, &mut up, Phase::Failed, msg).await;
+            }
+        }
+    });
 }
 
 pub fn server(manager: ProcessManager) -> InstanceServiceServer<InstanceApi> {
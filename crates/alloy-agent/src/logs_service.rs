@@ -1,7 +1,13 @@
+use std::io::{Read, Seek};
 use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::time::Duration;
 
 use alloy_proto::agent_v1::logs_service_server::{LogsService, LogsServiceServer};
-use alloy_proto::agent_v1::{TailFileRequest, TailFileResponse};
+use alloy_proto::agent_v1::{
+    FollowFileChunk, FollowFileRequest, TailFileRequest, TailFileResponse,
+};
+use futures_util::Stream;
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
 use tonic::{Request, Response, Status};
 
@@ -11,6 +17,13 @@ const DEFAULT_LIMIT_BYTES: u32 = 64 * 1024;
 const MAX_LIMIT_BYTES: u32 = 1024 * 1024;
 const DEFAULT_MAX_LINES: u32 = 200;
 const MAX_MAX_LINES: u32 = 2000;
+// Bytes a follower reads per poll; with the partial line this bounds what a
+// connection holds in memory.
+const FOLLOW_READ_BYTES: u64 = 64 * 1024;
+// A line longer than this is sent in pieces.
+const FOLLOW_MAX_LINE_BYTES: usize = 64 * 1024;
+// Longest a follower sleeps before checking the file again.
+const FOLLOW_WAIT: Duration = Duration::from_millis(500);
 
 #[derive(Debug)]
 enum PathError {
@@ -86,11 +99,227 @@ fn split_lines_from_tail(buf: &[u8], max_lines: usize) -> Vec<String> {
     out
 }
 
+/// Follow cursors are `<inode>:<offset>`; a bare offset (a TailFile cursor)
+/// refers to the current file.
+fn parse_follow_cursor(cursor: &str) -> Result<Option<(Option<u64>, u64)>, ()> {
+    let c = cursor.trim();
+    if c.is_empty() {
+        return Ok(None);
+    }
+    match c.split_once(':') {
+        Some((id, offset)) => Ok(Some((
+            Some(id.parse().map_err(|_| ())?),
+            offset.parse().map_err(|_| ())?,
+        ))),
+        None => Ok(Some((None, c.parse().map_err(|_| ())?))),
+    }
+}
+
+#[cfg(unix)]
+fn file_id(meta: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &std::fs::Metadata) -> u64 {
+    0
+}
+
+fn line_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// inotify watch on a log's directory, so renames and appends wake a follower.
+#[cfg(target_os = "linux")]
+struct DirWatch(std::os::fd::OwnedFd);
+
+#[cfg(target_os = "linux")]
+impl DirWatch {
+    fn new(path: &Path) -> Option<Self> {
+        use std::ffi::CString;
+        use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = CString::new(path.parent()?.as_os_str().as_bytes()).ok()?;
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mask = libc::IN_MODIFY | libc::IN_CREATE | libc::IN_MOVED_FROM | libc::IN_MOVED_TO;
+        if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), dir.as_ptr(), mask) } < 0 {
+            return None;
+        }
+        Some(Self(fd))
+    }
+
+    /// Blocks until the directory changes or `timeout` passes.
+    fn wait(&self, timeout: Duration) {
+        use std::os::fd::AsRawFd;
+
+        let fd = self.0.as_raw_fd();
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut pfd, 1, timeout.as_millis() as libc::c_int) };
+        let mut buf = [0u8; 4096];
+        while unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct DirWatch;
+
+#[cfg(not(target_os = "linux"))]
+impl DirWatch {
+    fn new(_path: &Path) -> Option<Self> {
+        None
+    }
+
+    fn wait(&self, timeout: Duration) {
+        std::thread::sleep(timeout);
+    }
+}
+
+/// `tail -f` over a file that `FileLogWriter` may rotate underneath it.
+struct Follower {
+    path: PathBuf,
+    file: std::fs::File,
+    id: u64,
+    offset: u64,
+    // Bytes after the last newline, held until the line completes.
+    partial: Vec<u8>,
+    watch: Option<DirWatch>,
+}
+
+impl Follower {
+    fn open(path: PathBuf, cursor: Option<(Option<u64>, u64)>) -> std::io::Result<Self> {
+        let watch = DirWatch::new(&path);
+        let mut file = std::fs::File::open(&path)?;
+        let meta = file.metadata()?;
+        let id = file_id(&meta);
+        let offset = match cursor {
+            None => meta.len(),
+            // Rotated or truncated since the cursor was taken: the new file
+            // is read from the start.
+            Some((Some(cursor_id), _)) if cursor_id != id => 0,
+            Some((_, offset)) if offset > meta.len() => 0,
+            Some((_, offset)) => offset,
+        };
+        file.seek(std::io::SeekFrom::Start(offset))?;
+        Ok(Self {
+            path,
+            file,
+            id,
+            offset,
+            partial: Vec::new(),
+            watch,
+        })
+    }
+
+    fn cursor(&self) -> String {
+        format!("{}:{}", self.id, self.offset - self.partial.len() as u64)
+    }
+
+    fn read_lines(&mut self, lines: &mut Vec<String>) -> std::io::Result<u64> {
+        let n = (&mut self.file)
+            .take(FOLLOW_READ_BYTES)
+            .read_to_end(&mut self.partial)? as u64;
+        self.offset += n;
+
+        let mut start = 0;
+        while let Some(pos) = self.partial[start..].iter().position(|b| *b == b'\n') {
+            lines.push(line_text(&self.partial[start..start + pos]));
+            start += pos + 1;
+        }
+        self.partial.drain(..start);
+        if self.partial.len() >= FOLLOW_MAX_LINE_BYTES {
+            lines.push(line_text(&self.partial));
+            self.partial.clear();
+        }
+        Ok(n)
+    }
+
+    /// Reads what was appended since the last poll, moving on to the new file
+    /// once the old one is drained and the path was rotated or truncated.
+    fn poll(&mut self) -> std::io::Result<Option<FollowFileChunk>> {
+        let mut lines = Vec::new();
+        let mut rotated = false;
+        if self.read_lines(&mut lines)? < FOLLOW_READ_BYTES {
+            // A missing path is a rotation in progress; check again later.
+            if let Ok(meta) = std::fs::metadata(&self.path)
+                && (file_id(&meta) != self.id || meta.len() < self.offset)
+            {
+                if !self.partial.is_empty() {
+                    lines.push(line_text(&std::mem::take(&mut self.partial)));
+                }
+                self.file = std::fs::File::open(&self.path)?;
+                self.id = file_id(&self.file.metadata()?);
+                self.offset = 0;
+                rotated = true;
+                self.read_lines(&mut lines)?;
+            }
+        }
+        if lines.is_empty() && !rotated {
+            return Ok(None);
+        }
+        Ok(Some(FollowFileChunk {
+            lines,
+            next_cursor: self.cursor(),
+            rotated,
+        }))
+    }
+
+    fn wait(&self) {
+        match &self.watch {
+            Some(watch) => watch.wait(FOLLOW_WAIT),
+            None => std::thread::sleep(FOLLOW_WAIT),
+        }
+    }
+}
+
+type FollowFileStream = Pin<Box<dyn Stream<Item = Result<FollowFileChunk, Status>> + Send>>;
+
+/// Streams chunks from `follower`. Each blocking step waits at most
+/// `FOLLOW_WAIT`, so a dropped stream releases its thread promptly.
+fn follow_stream(follower: Follower) -> FollowFileStream {
+    let stream = futures_util::stream::unfold(Some(follower), |follower| async move {
+        let mut follower = follower?;
+        loop {
+            let (next, polled) = tokio::task::spawn_blocking(move || {
+                let polled = follower.poll();
+                if matches!(polled, Ok(None)) {
+                    follower.wait();
+                }
+                (follower, polled)
+            })
+            .await
+            .ok()?;
+            follower = next;
+            match polled {
+                Ok(Some(chunk)) => return Some((Ok(chunk), Some(follower))),
+                Ok(None) => {}
+                Err(e) => {
+                    let status = Status::internal(format!("failed to read file: {e}"));
+                    return Some((Err(status), None));
+                }
+            }
+        }
+    });
+    Box::pin(stream)
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct LogsApi;
 
 #[tonic::async_trait]
 impl LogsService for LogsApi {
+    type FollowFileStream = FollowFileStream;
+
     async fn tail_file(
         &self,
         request: Request<TailFileRequest>,
@@ -145,8 +374,87 @@ impl LogsService for LogsApi {
             next_cursor: next_cursor.to_string(),
         }))
     }
+
+    async fn follow_file(
+        &self,
+        request: Request<FollowFileRequest>,
+    ) -> Result<Response<Self::FollowFileStream>, Status> {
+        let req = request.into_inner();
+        let path = scoped_path(&req.path).map_err(Status::from)?;
+        let cursor = parse_follow_cursor(&req.cursor)
+            .map_err(|_| Status::invalid_argument("invalid cursor"))?;
+
+        let meta = tokio::fs::metadata(&path)
+            .await
+            .map_err(|_| Status::not_found("path not found"))?;
+        if !meta.is_file() {
+            return Err(Status::invalid_argument("path is not a file"));
+        }
+
+        let follower = tokio::task::spawn_blocking(move || Follower::open(path, cursor))
+            .await
+            .map_err(|e| Status::internal(format!("follow task failed: {e}")))?
+            .map_err(|e| Status::internal(format!("failed to open file: {e}")))?;
+        Ok(Response::new(follow_stream(follower)))
+    }
 }
 
 pub fn server() -> LogsServiceServer<LogsApi> {
     LogsServiceServer::new(LogsApi)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Follower, follow_stream, parse_follow_cursor};
+    use futures_util::StreamExt;
+    use std::io::Write;
+
+    fn append(path: &std::path::Path, text: &str) {
+        let mut f = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        f.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn follow_streams_appended_lines_across_a_rotation() {
+        let dir = std::env::temp_dir().join(format!("alloy-agent-follow-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("console.log");
+        append(&path, "before\n");
+
+        let mut stream = follow_stream(Follower::open(path.clone(), None).unwrap());
+        append(&path, "a\nb\n");
+        let chunk = stream.next().await.unwrap().unwrap();
+        assert_eq!(chunk.lines, ["a", "b"]);
+        assert!(!chunk.rotated);
+
+        // Same shape as FileLogWriter::rotate: rename, then a fresh file.
+        append(&path, "c");
+        std::fs::rename(&path, dir.join("console.log.1")).unwrap();
+        append(&path, "d\n");
+        let mut lines = Vec::new();
+        let mut rotated = false;
+        let mut cursor = String::new();
+        while lines.len() < 2 {
+            let chunk = stream.next().await.unwrap().unwrap();
+            rotated |= chunk.rotated;
+            lines.extend(chunk.lines);
+            cursor = chunk.next_cursor;
+        }
+        assert_eq!(lines, ["c", "d"]);
+        assert!(rotated);
+
+        // A reconnect resumes from the last cursor.
+        drop(stream);
+        append(&path, "e\n");
+        let cursor = parse_follow_cursor(&cursor).unwrap();
+        let mut stream = follow_stream(Follower::open(path.clone(), cursor).unwrap());
+        assert_eq!(stream.next().await.unwrap().unwrap().lines, ["e"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
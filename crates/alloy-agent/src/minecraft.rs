@@ -7,24 +7,35 @@ use std::{
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct VanillaParams {
+    /// Writes `eula=true`; otherwise an `eula.txt` already accepting it is required.
+    pub accept_eula: bool,
     pub version: String,
     pub memory_mb: u32,
     pub port: u16,
 }
 
-pub fn validate_vanilla_params(params: &BTreeMap<String, String>) -> anyhow::Result<VanillaParams> {
-    let mut field_errors = BTreeMap::<String, String>::new();
-
-    // EULA must be accepted explicitly (legal + UX).
+/// The `accept_eula` param; blank counts as not accepted.
+pub fn parse_accept_eula(
+    params: &BTreeMap<String, String>,
+    field_errors: &mut BTreeMap<String, String>,
+) -> bool {
     match params.get("accept_eula").map(|v| v.trim()) {
-        Some("true") => {}
-        _ => {
+        Some("true") => true,
+        None | Some("") | Some("false") => false,
+        Some(_) => {
             field_errors.insert(
                 "accept_eula".to_string(),
-                "Required. You must accept the Minecraft EULA.".to_string(),
+                "Must be true or false.".to_string(),
             );
+            false
         }
     }
+}
+
+pub fn validate_vanilla_params(params: &BTreeMap<String, String>) -> anyhow::Result<VanillaParams> {
+    let mut field_errors = BTreeMap::<String, String>::new();
+
+    let accept_eula = parse_accept_eula(params, &mut field_errors);
 
     let version = params
         .get("version")
@@ -94,6 +105,7 @@ pub fn validate_vanilla_params(params: &BTreeMap<String, String>) -> anyhow::Res
     }
 
     Ok(VanillaParams {
+        accept_eula,
         version,
         memory_mb,
         port,
@@ -119,6 +131,14 @@ pub fn instance_dir(process_id: &str) -> PathBuf {
     data_root().join("instances").join(process_id)
 }
 
+// Whether an existing eula.txt says `eula=true`.
+fn eula_accepted(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|raw| {
+        raw.lines()
+            .any(|l| l.trim().eq_ignore_ascii_case("eula=true"))
+    })
+}
+
 pub fn ensure_vanilla_instance_layout(
     instance_dir: &Path,
     params: &VanillaParams,
@@ -145,9 +165,21 @@ pub fn ensure_vanilla_instance_layout(
     migrate_into_config_dir(instance_dir, &config_dir, "eula.txt");
     migrate_into_config_dir(instance_dir, &config_dir, "server.properties");
 
-    // EULA gate is handled by validate_vanilla_params(); writing eula=true is the
-    // explicit acceptance action.
-    fs::write(config_dir.join("eula.txt"), b"eula=true\n")?;
+    // Writing eula=true is the explicit acceptance action. Without it the server
+    // would exit right away, so fail here with a clearer error instead.
+    if params.accept_eula {
+        fs::write(config_dir.join("eula.txt"), b"eula=true\n")?;
+    } else if !eula_accepted(&config_dir.join("eula.txt")) {
+        return Err(crate::error_payload::anyhow(
+            "eula_required",
+            "the Minecraft EULA has not been accepted",
+            None,
+            Some(
+                "Set accept_eula=true to agree to the Minecraft EULA (https://aka.ms/MinecraftEULA)."
+                    .to_string(),
+            ),
+        ));
+    }
 
     // Ensure root-level config files exist for the Minecraft server by symlinking into config/.
     #[cfg(unix)]
@@ -209,3 +241,43 @@ pub fn ensure_vanilla_instance_layout(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{VanillaParams, ensure_vanilla_instance_layout};
+
+    fn params(accept_eula: bool) -> VanillaParams {
+        VanillaParams {
+            accept_eula,
+            version: "1.21".to_string(),
+            memory_mb: 2048,
+            port: 25565,
+        }
+    }
+
+    #[test]
+    fn accepted_eula_is_written_and_missing_acceptance_fails_fast() {
+        let dir = std::env::temp_dir().join(format!("alloy-agent-eula-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let err = ensure_vanilla_instance_layout(&dir, &params(false)).unwrap_err();
+        assert_eq!(
+            crate::error_payload::decode(&err).unwrap().code,
+            "eula_required"
+        );
+
+        ensure_vanilla_instance_layout(&dir, &params(true)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("config").join("eula.txt")).unwrap(),
+            "eula=true\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("eula.txt")).unwrap(),
+            "eula=true\n"
+        );
+
+        // A previously accepted eula.txt is enough on later starts.
+        ensure_vanilla_instance_layout(&dir, &params(false)).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
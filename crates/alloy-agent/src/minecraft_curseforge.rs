@@ -2,25 +2,28 @@ use std::{
     collections::{BTreeMap, HashMap},
     fs,
     io::Write,
-    path::{Component, Path, PathBuf},
+    path::{Path, PathBuf},
     sync::{Arc, OnceLock},
     time::Duration,
 };
 
 use anyhow::Context;
-use futures_util::StreamExt;
 use reqwest::Url;
 use serde::Deserialize;
 use tokio::sync::Mutex;
 
+use crate::download::download_with_resume;
+use crate::download_cache::{verify_cached_zip, write_sha256_sidecar};
 use crate::minecraft;
 
 const CF_API_BASE: &str = "https://api.curseforge.com/v1";
 const CF_GAME_ID_MINECRAFT: u32 = 432;
 const CF_CLASS_ID_MODPACKS: u32 = 4471;
+const MAX_DOWNLOAD_BYTES: u64 = 8 * 1024 * 1024 * 1024;
 
 #[derive(Debug, Clone)]
 pub struct CurseforgeParams {
+    pub accept_eula: bool,
     pub source: String,
     pub api_key: String,
     pub memory_mb: u32,
@@ -30,15 +33,7 @@ pub struct CurseforgeParams {
 pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<CurseforgeParams> {
     let mut field_errors = BTreeMap::<String, String>::new();
 
-    match params.get("accept_eula").map(|v| v.trim()) {
-        Some("true") => {}
-        _ => {
-            field_errors.insert(
-                "accept_eula".to_string(),
-                "Required. You must accept the Minecraft EULA.".to_string(),
-            );
-        }
-    }
+    let accept_eula = crate::minecraft::parse_accept_eula(params, &mut field_errors);
 
     let source = params
         .get("curseforge")
@@ -49,8 +44,10 @@ pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<Curs
     if source.is_empty() {
         field_errors.insert(
             "curseforge".to_string(),
-            "Required. Paste a CurseForge file URL, or modId:fileId.".to_string(),
+            "Required. Paste a CurseForge modpack URL, slug, or modId:fileId.".to_string(),
         );
+    } else if let Err(e) = parse_source(&source) {
+        field_errors.insert("curseforge".to_string(), format!("{e}."));
     }
 
     let api_key = params
@@ -127,6 +124,7 @@ pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<Curs
     }
 
     Ok(CurseforgeParams {
+        accept_eula,
         source,
         api_key,
         memory_mb,
@@ -202,60 +200,6 @@ fn http_client() -> &'static reqwest::Client {
     })
 }
 
-fn normalize_rel_path(rel: &str) -> anyhow::Result<PathBuf> {
-    if rel.trim().is_empty() {
-        return Ok(PathBuf::new());
-    }
-    let p = Path::new(rel);
-    if p.is_absolute() {
-        anyhow::bail!("path must be relative");
-    }
-    let mut out = PathBuf::new();
-    for c in p.components() {
-        match c {
-            Component::CurDir => {}
-            Component::Normal(seg) => out.push(seg),
-            Component::ParentDir => anyhow::bail!("path traversal is not allowed"),
-            Component::Prefix(_) | Component::RootDir => anyhow::bail!("path must be relative"),
-        }
-    }
-    Ok(out)
-}
-
-fn extract_zip_safely(zip_path: &Path, out_dir: &Path) -> anyhow::Result<()> {
-    fs::create_dir_all(out_dir)?;
-    let f = fs::File::open(zip_path)?;
-    let mut archive = zip::ZipArchive::new(f)?;
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let name = file.name().to_string();
-        let trimmed = name.trim_end_matches('/');
-        if trimmed.is_empty() {
-            continue;
-        }
-        let rel =
-            normalize_rel_path(trimmed).with_context(|| format!("invalid zip path {trimmed:?}"))?;
-        if rel.as_os_str().is_empty() {
-            continue;
-        }
-        let out_path = out_dir.join(&rel);
-        if name.ends_with('/') {
-            fs::create_dir_all(&out_path)?;
-            continue;
-        }
-        if let Some(parent) = out_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let tmp_path = out_path.with_extension("tmp");
-        let mut out = fs::File::create(&tmp_path)?;
-        std::io::copy(&mut file, &mut out)?;
-        out.sync_all().ok();
-        fs::rename(&tmp_path, &out_path)?;
-    }
-    Ok(())
-}
-
 fn find_flatten_root(extracted: &Path) -> PathBuf {
     let rd = match fs::read_dir(extracted) {
         Ok(v) => v,
@@ -344,10 +288,11 @@ fn is_http_url(s: &str) -> bool {
     lower.starts_with("http://") || lower.starts_with("https://")
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct ModFileRef {
     mod_id: Option<u32>,
-    file_id: u32,
+    // None installs the project's main file.
+    file_id: Option<u32>,
     slug: Option<String>,
 }
 
@@ -359,6 +304,16 @@ fn parse_digits(s: &str) -> Option<u32> {
     v.parse::<u32>().ok()
 }
 
+fn is_slug(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Accepts `www.curseforge.com/minecraft/modpacks/<slug>` and the legacy
+/// `minecraft.curseforge.com/projects/<slug|id>`, optionally followed by
+/// `/files/<fileId>` to pin a file; also `modId:fileId`, a bare mod id, or a
+/// bare slug.
 fn parse_source(source: &str) -> anyhow::Result<ModFileRef> {
     let s = source.trim();
     if s.is_empty() {
@@ -369,30 +324,40 @@ fn parse_source(source: &str) -> anyhow::Result<ModFileRef> {
         let url = Url::parse(s).context("invalid url")?;
         let host = url.host_str().unwrap_or_default();
         if !host.contains("curseforge.com") {
-            // Direct download URL (e.g. forgecdn). Treat as file_id-only.
+            // Direct download URL (e.g. forgecdn).
             return Ok(ModFileRef {
                 mod_id: None,
-                file_id: 0,
+                file_id: None,
                 slug: None,
             });
         }
 
         let segs: Vec<&str> = url.path().split('/').filter(|s| !s.is_empty()).collect();
-        let file_id = segs
+        let project = segs
             .iter()
-            .position(|x| *x == "files")
+            .position(|x| *x == "modpacks" || *x == "projects")
             .and_then(|i| segs.get(i + 1))
-            .and_then(|v| parse_digits(v))
-            .ok_or_else(|| anyhow::anyhow!("curseforge url must include /files/<file_id>"))?;
-        let slug = segs
+            .copied()
+            .filter(|v| is_slug(v))
+            .ok_or_else(|| {
+                anyhow::anyhow!("curseforge url must point at a modpack (.../modpacks/<slug>)")
+            })?;
+        let file_id = segs
             .iter()
-            .position(|x| *x == "modpacks")
+            .position(|x| *x == "files")
             .and_then(|i| segs.get(i + 1))
-            .map(|s| s.to_string());
-        return Ok(ModFileRef {
-            mod_id: None,
-            file_id,
-            slug,
+            .and_then(|v| parse_digits(v));
+        return Ok(match parse_digits(project) {
+            Some(mod_id) => ModFileRef {
+                mod_id: Some(mod_id),
+                file_id,
+                slug: None,
+            },
+            None => ModFileRef {
+                mod_id: None,
+                file_id,
+                slug: Some(project.to_ascii_lowercase()),
+            },
         });
     }
 
@@ -401,13 +366,28 @@ fn parse_source(source: &str) -> anyhow::Result<ModFileRef> {
         if let (Some(mod_id), Some(file_id)) = (parse_digits(a), parse_digits(b)) {
             return Ok(ModFileRef {
                 mod_id: Some(mod_id),
-                file_id,
+                file_id: Some(file_id),
                 slug: None,
             });
         }
     }
 
-    anyhow::bail!("unsupported curseforge source; paste a file URL or modId:fileId");
+    if let Some(mod_id) = parse_digits(s) {
+        return Ok(ModFileRef {
+            mod_id: Some(mod_id),
+            file_id: None,
+            slug: None,
+        });
+    }
+    if is_slug(s) {
+        return Ok(ModFileRef {
+            mod_id: None,
+            file_id: None,
+            slug: Some(s.to_ascii_lowercase()),
+        });
+    }
+
+    anyhow::bail!("unsupported curseforge source; paste a modpack URL, slug, or modId:fileId");
 }
 
 #[derive(Debug, Deserialize)]
@@ -422,7 +402,33 @@ struct SearchModHit {
     slug: Option<String>,
 }
 
+// Slugs never change owner, so resolutions are kept for the agent's lifetime.
+fn slug_cache() -> &'static std::sync::Mutex<HashMap<String, u32>> {
+    static CACHE: OnceLock<std::sync::Mutex<HashMap<String, u32>>> = OnceLock::new();
+    CACHE.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+}
+
+fn slug_not_found(slug: &str) -> anyhow::Error {
+    crate::error_payload::anyhow(
+        "invalid_param",
+        format!("curseforge modpack not found for slug {slug:?}"),
+        Some(BTreeMap::from([(
+            "curseforge".to_string(),
+            format!("No CurseForge modpack matches \"{slug}\"."),
+        )])),
+        Some("Copy the URL from the modpack's CurseForge page, or use modId:fileId.".to_string()),
+    )
+}
+
 async fn resolve_mod_id_by_slug(api_key: &str, slug: &str) -> anyhow::Result<u32> {
+    if let Some(id) = slug_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(slug)
+    {
+        return Ok(*id);
+    }
+
     let mut url = Url::parse(&format!("{CF_API_BASE}/mods/search"))
         .expect("CF_API_BASE should be a valid URL");
     url.query_pairs_mut()
@@ -444,11 +450,50 @@ async fn resolve_mod_id_by_slug(api_key: &str, slug: &str) -> anyhow::Result<u32
     let hit = resp
         .data
         .into_iter()
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("curseforge mod not found for slug {slug:?}"))?;
+        .find(|h| {
+            h.slug
+                .as_deref()
+                .is_none_or(|s| s.eq_ignore_ascii_case(slug))
+        })
+        .ok_or_else(|| slug_not_found(slug))?;
+    slug_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(slug.to_string(), hit.id);
     Ok(hit.id)
 }
 
+#[derive(Debug, Deserialize)]
+struct ModResponse {
+    data: ModInfo,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModInfo {
+    #[serde(default)]
+    main_file_id: u32,
+}
+
+async fn get_main_file_id(api_key: &str, mod_id: u32) -> anyhow::Result<u32> {
+    let url = format!("{CF_API_BASE}/mods/{mod_id}");
+    let resp = http_client()
+        .get(url)
+        .header("x-api-key", api_key)
+        .send()
+        .await
+        .context("curseforge get mod")?
+        .error_for_status()
+        .context("curseforge get mod (status)")?
+        .json::<ModResponse>()
+        .await
+        .context("parse curseforge mod json")?;
+    if resp.data.main_file_id == 0 {
+        anyhow::bail!("curseforge mod {mod_id} has no main file; pin one with .../files/<fileId>");
+    }
+    Ok(resp.data.main_file_id)
+}
+
 #[derive(Debug, Deserialize)]
 struct ModFileResponse {
     data: ModFile,
@@ -505,37 +550,12 @@ async fn get_download_url(api_key: &str, mod_id: u32, file_id: u32) -> anyhow::R
     Ok(out)
 }
 
-async fn download_to_path(url: &str, path: &Path) -> anyhow::Result<()> {
-    if let Some(parent) = path.parent() {
-        tokio::fs::create_dir_all(parent).await?;
-    }
-
-    let resp = http_client()
-        .get(url)
-        .send()
-        .await
-        .context("download")?
-        .error_for_status()
-        .context("download (status)")?;
-
-    let mut f = tokio::fs::File::create(path)
-        .await
-        .context("create download file")?;
-    let mut total: u64 = 0;
-    let mut stream = resp.bytes_stream();
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.context("download chunk")?;
-        total = total.saturating_add(chunk.len() as u64);
-        // Hard safety limit: 8GiB.
-        if total > 8 * 1024 * 1024 * 1024_u64 {
-            let _ = tokio::fs::remove_file(path).await;
-            anyhow::bail!("download too large");
-        }
-        tokio::io::AsyncWriteExt::write_all(&mut f, &chunk)
-            .await
-            .context("write download")?;
-    }
-    tokio::io::AsyncWriteExt::flush(&mut f).await.ok();
+async fn download_to_path(
+    url: &str,
+    path: &Path,
+    on_progress: impl FnMut(u64, u64, u64) + Send,
+) -> anyhow::Result<()> {
+    download_with_resume(url, path, MAX_DOWNLOAD_BYTES, on_progress).await?;
     Ok(())
 }
 
@@ -543,6 +563,7 @@ async fn ensure_server_pack_downloaded(
     api_key: &str,
     mod_id: u32,
     file_id: u32,
+    on_progress: impl FnMut(u64, u64, u64) + Send,
 ) -> anyhow::Result<(u32, String, PathBuf)> {
     // Resolve server pack file id + download URL.
     let file = get_mod_file(api_key, mod_id, file_id).await?;
@@ -567,17 +588,22 @@ async fn ensure_server_pack_downloaded(
     let lock = lock_for(&format!("cf:{server_pack_file_id}"));
     let _guard = lock.lock().await;
 
-    if !zip_path.exists() {
-        download_to_path(&url, &zip_path).await?;
+    if !verify_cached_zip(&zip_path).await {
+        download_to_path(&url, &zip_path, on_progress).await?;
+        write_sha256_sidecar(&zip_path).await?;
     }
 
     Ok((server_pack_file_id, url, zip_path))
 }
 
+/// `on_progress` follows the pack download as `(downloaded, total, bytes_per_sec)`;
+/// `output`, when set, receives extraction progress lines.
 pub async fn ensure_installed(
     instance_dir: &Path,
     source: &str,
     api_key: &str,
+    on_progress: impl FnMut(u64, u64, u64) + Send,
+    output: Option<tokio::sync::mpsc::UnboundedSender<String>>,
 ) -> anyhow::Result<InstalledMarker> {
     if let Some(m) = read_marker(instance_dir) {
         if m.source.trim() == source.trim() {
@@ -597,13 +623,15 @@ pub async fn ensure_installed(
             tokio::fs::create_dir_all(&imports).await.ok();
             let nonce = alloy_process::ProcessId::new().0;
             let zip_path = imports.join(format!("curseforge-{nonce}.zip"));
-            download_to_path(src, &zip_path).await?;
+            download_to_path(src, &zip_path, on_progress).await?;
 
             let extracted = imports.join(format!("extracted-{nonce}"));
             tokio::task::spawn_blocking({
                 let zip_path = zip_path.clone();
                 let extracted = extracted.clone();
-                move || extract_zip_safely(&zip_path, &extracted)
+                move || {
+                    crate::archive::extract_zip_safely_with_output(&zip_path, &extracted, output)
+                }
             })
             .await
             .context("extract task failed")??;
@@ -632,21 +660,23 @@ pub async fn ensure_installed(
         }
     }
 
-    let file_id = parsed.file_id;
     let mod_id = match parsed.mod_id {
         Some(id) => id,
         None => {
             let slug = parsed
                 .slug
                 .as_deref()
-                .filter(|s| !s.trim().is_empty())
                 .ok_or_else(|| anyhow::anyhow!("missing modpack slug in curseforge url"))?;
             resolve_mod_id_by_slug(api_key, slug).await?
         }
     };
+    let file_id = match parsed.file_id {
+        Some(id) => id,
+        None => get_main_file_id(api_key, mod_id).await?,
+    };
 
     let (server_pack_file_id, download_url, zip_path) =
-        ensure_server_pack_downloaded(api_key, mod_id, file_id).await?;
+        ensure_server_pack_downloaded(api_key, mod_id, file_id, on_progress).await?;
 
     let imports = instance_dir.join("imports");
     tokio::fs::create_dir_all(&imports).await.ok();
@@ -655,7 +685,7 @@ pub async fn ensure_installed(
     tokio::task::spawn_blocking({
         let zip_path = zip_path.clone();
         let extracted = extracted.clone();
-        move || extract_zip_safely(&zip_path, &extracted)
+        move || crate::archive::extract_zip_safely_with_output(&zip_path, &extracted, output)
     })
     .await
     .context("extract task failed")??;
@@ -681,3 +711,94 @@ pub async fn ensure_installed(
     write_marker(instance_dir, &marker)?;
     Ok(marker)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ModFileRef, parse_source};
+
+    fn slug_ref(slug: &str, file_id: Option<u32>) -> ModFileRef {
+        ModFileRef {
+            mod_id: None,
+            file_id,
+            slug: Some(slug.to_string()),
+        }
+    }
+
+    fn id_ref(mod_id: u32, file_id: Option<u32>) -> ModFileRef {
+        ModFileRef {
+            mod_id: Some(mod_id),
+            file_id,
+            slug: None,
+        }
+    }
+
+    #[test]
+    fn project_urls_resolve_to_slug_with_optional_file() {
+        let atm = "all-the-mods-10";
+        for (src, want) in [
+            (
+                "https://www.curseforge.com/minecraft/modpacks/all-the-mods-10",
+                slug_ref(atm, None),
+            ),
+            (
+                "https://www.curseforge.com/minecraft/modpacks/all-the-mods-10/",
+                slug_ref(atm, None),
+            ),
+            (
+                "https://curseforge.com/minecraft/modpacks/All-The-Mods-10?page=2",
+                slug_ref(atm, None),
+            ),
+            (
+                "https://www.curseforge.com/minecraft/modpacks/all-the-mods-10/files/6104325",
+                slug_ref(atm, Some(6104325)),
+            ),
+            (
+                "https://www.curseforge.com/minecraft/modpacks/all-the-mods-10/files/6104325/",
+                slug_ref(atm, Some(6104325)),
+            ),
+            (
+                "https://www.curseforge.com/minecraft/modpacks/all-the-mods-10/files/all",
+                slug_ref(atm, None),
+            ),
+            // Legacy project host.
+            (
+                "https://minecraft.curseforge.com/projects/all-the-mods-10/files/6104325",
+                slug_ref(atm, Some(6104325)),
+            ),
+            (
+                "https://minecraft.curseforge.com/projects/925200",
+                id_ref(925200, None),
+            ),
+        ] {
+            assert_eq!(parse_source(src).unwrap(), want, "{src}");
+        }
+    }
+
+    #[test]
+    fn bare_ids_and_slugs_are_accepted() {
+        assert_eq!(
+            parse_source("925200:6104325").unwrap(),
+            id_ref(925200, Some(6104325))
+        );
+        assert_eq!(
+            parse_source("925200/6104325").unwrap(),
+            id_ref(925200, Some(6104325))
+        );
+        assert_eq!(parse_source("925200").unwrap(), id_ref(925200, None));
+        assert_eq!(
+            parse_source(" all-the-mods-10 ").unwrap(),
+            slug_ref("all-the-mods-10", None)
+        );
+        assert_eq!(
+            parse_source("https://edge.forgecdn.net/files/6104/325/server.zip").unwrap(),
+            ModFileRef {
+                mod_id: None,
+                file_id: None,
+                slug: None,
+            }
+        );
+
+        assert!(parse_source("https://www.curseforge.com/minecraft").is_err());
+        assert!(parse_source("all the mods").is_err());
+    }
+}
@@ -3,7 +3,6 @@
 use std::{
     collections::HashMap,
     fs,
-    io::Write,
     path::Path,
     path::PathBuf,
     sync::{Arc, OnceLock},
@@ -11,98 +10,10 @@ use std::{
 };
 
 use anyhow::Context;
-use futures_util::StreamExt;
-use reqwest::Url;
-use sha1::Digest;
 use tokio::sync::Mutex;
 
-#[derive(Debug, Clone)]
-pub struct DownloadReport {
-    pub downloaded_bytes: u64,
-    pub total_bytes: u64,
-    pub speed_bytes_per_sec: u64,
-}
-
-fn download_chunk_threshold(total_bytes: u64) -> u64 {
-    if total_bytes >= 2 * 1024 * 1024 * 1024 {
-        8 * 1024 * 1024
-    } else if total_bytes >= 512 * 1024 * 1024 {
-        4 * 1024 * 1024
-    } else {
-        1024 * 1024
-    }
-}
-
-pub async fn download_bytes_with_progress<F>(
-    url: Url,
-    expected_size: Option<u64>,
-    mut on_progress: F,
-) -> anyhow::Result<(Vec<u8>, DownloadReport)>
-where
-    F: FnMut(u64, u64, u64) + Send,
-{
-    let resp = http_client()
-        .get(url.clone())
-        .send()
-        .await
-        .context("download server.jar")?
-        .error_for_status()
-        .context("download server.jar (status)")?;
-
-    let total_bytes = expected_size.or(resp.content_length()).unwrap_or(0);
-    let threshold = download_chunk_threshold(total_bytes.max(1));
-    let mut stream = resp.bytes_stream();
-    let mut out: Vec<u8> = Vec::new();
-    let reserve_cap = total_bytes.min(256 * 1024 * 1024) as usize;
-    if reserve_cap > 0 {
-        out.reserve(reserve_cap);
-    }
-
-    let started_at = std::time::Instant::now();
-    let mut last_emit_bytes = 0u64;
-    let mut last_emit_at = started_at;
-    let mut downloaded_bytes = 0u64;
-
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.context("read server.jar body chunk")?;
-        downloaded_bytes = downloaded_bytes.saturating_add(chunk.len() as u64);
-        out.extend_from_slice(&chunk);
-
-        let now = std::time::Instant::now();
-        let should_emit = downloaded_bytes.saturating_sub(last_emit_bytes) >= threshold
-            || now.duration_since(last_emit_at) >= Duration::from_millis(300);
-        if should_emit {
-            let elapsed = now.duration_since(started_at).as_secs_f64();
-            let speed = if elapsed > 0.0 {
-                (downloaded_bytes as f64 / elapsed).round() as u64
-            } else {
-                0
-            };
-            let total = total_bytes.max(downloaded_bytes);
-            on_progress(downloaded_bytes, total, speed);
-            last_emit_bytes = downloaded_bytes;
-            last_emit_at = now;
-        }
-    }
-
-    let elapsed = started_at.elapsed().as_secs_f64();
-    let speed = if elapsed > 0.0 {
-        (downloaded_bytes as f64 / elapsed).round() as u64
-    } else {
-        0
-    };
-    let total = total_bytes.max(downloaded_bytes);
-    on_progress(downloaded_bytes, total, speed);
-
-    Ok((
-        out,
-        DownloadReport {
-            downloaded_bytes,
-            total_bytes: total,
-            speed_bytes_per_sec: speed,
-        },
-    ))
-}
+use crate::download::download_with_resume;
+use crate::download_cache::{Checksum, verify_cached};
 
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct VersionManifestV2 {
@@ -276,22 +187,22 @@ fn lock_for(key: &str) -> Arc<Mutex<()>> {
         .clone()
 }
 
-fn http_client() -> &'static reqwest::Client {
-    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
-    CLIENT.get_or_init(|| {
-        reqwest::Client::builder()
-            .user_agent("alloy-agent")
-            .timeout(Duration::from_secs(15 * 60))
-            .build()
-            .expect("failed to build reqwest client")
-    })
-}
-
 pub async fn ensure_server_jar(resolved: &ResolvedServerJar) -> anyhow::Result<PathBuf> {
     ensure_server_jar_with_progress(resolved, None::<fn(u64, u64, u64)>).await
 }
 
 pub async fn ensure_server_jar_with_progress<F>(
+    resolved: &ResolvedServerJar,
+    on_progress: Option<F>,
+) -> anyhow::Result<PathBuf>
+where
+    F: FnMut(u64, u64, u64) + Send,
+{
+    ensure_server_jar_in(&cache_dir(), resolved, on_progress).await
+}
+
+pub(crate) async fn ensure_server_jar_in<F>(
+    cache_root: &Path,
     resolved: &ResolvedServerJar,
     mut on_progress: Option<F>,
 ) -> anyhow::Result<PathBuf>
@@ -299,8 +210,11 @@ where
     F: FnMut(u64, u64, u64) + Send,
 {
     let sha1_hex = &resolved.sha1;
-    let jar_path = cache_dir().join(sha1_hex).join("server.jar");
-    if jar_path.exists() {
+    let jar_path = cache_root.join(sha1_hex).join("server.jar");
+    // Hits are re-hashed against piston-meta's SHA1; a corrupt jar is deleted
+    // and downloaded again below.
+    let checksum = Checksum::Sha1(sha1_hex.clone());
+    if verify_cached(&jar_path, &checksum).await {
         if let Some(dir) = jar_path.parent() {
             mark_last_used(dir);
             write_meta_best_effort(dir, resolved);
@@ -314,7 +228,7 @@ where
     let lock_key = format!("minecraft:vanilla:{sha1_hex}");
     let lock = lock_for(&lock_key);
     let _guard = lock.lock().await;
-    if jar_path.exists() {
+    if verify_cached(&jar_path, &checksum).await {
         if let Some(dir) = jar_path.parent() {
             mark_last_used(dir);
             write_meta_best_effort(dir, resolved);
@@ -325,96 +239,80 @@ where
         return Ok(jar_path);
     }
 
-    fs::create_dir_all(jar_path.parent().unwrap())?;
-
-    let url = Url::parse(&resolved.jar_url)?;
-    let mut last_err: Option<anyhow::Error> = None;
-    let mut bytes: Option<Vec<u8>> = None;
-    let mut last_report = DownloadReport {
-        downloaded_bytes: 0,
-        total_bytes: resolved.size,
-        speed_bytes_per_sec: 0,
-    };
-    for attempt in 1..=3_u32 {
-        let res: anyhow::Result<Vec<u8>> = (async {
-            let (bytes, report) = download_bytes_with_progress(
-                url.clone(),
-                Some(resolved.size),
-                |downloaded, total, speed| {
-                    last_report = DownloadReport {
-                        downloaded_bytes: downloaded,
-                        total_bytes: total,
-                        speed_bytes_per_sec: speed,
-                    };
-                    if let Some(cb) = on_progress.as_mut() {
-                        cb(downloaded, total, speed);
-                    }
-                },
-            )
-            .await?;
-
-            last_report = report;
-            Ok(bytes)
-        })
-        .await;
-
-        match res {
-            Ok(b) => {
-                bytes = Some(b);
-                break;
+    let downloaded = download_with_resume(
+        &resolved.jar_url,
+        &jar_path,
+        resolved.size,
+        |downloaded, total, speed| {
+            if let Some(cb) = on_progress.as_mut() {
+                cb(downloaded, total, speed);
             }
-            Err(e) => {
-                last_err = Some(e);
-                if attempt < 3 {
-                    tokio::time::sleep(Duration::from_millis(
-                        200_u64.saturating_mul(2_u64.pow(attempt - 1)),
-                    ))
-                    .await;
-                }
-            }
-        }
-    }
-
-    let bytes =
-        bytes.ok_or_else(|| last_err.unwrap_or_else(|| anyhow::anyhow!("download failed")))?;
+        },
+    )
+    .await
+    .context("download server.jar")?;
 
-    if bytes.len() as u64 != resolved.size {
+    if downloaded != resolved.size {
+        let _ = fs::remove_file(&jar_path);
         anyhow::bail!(
             "minecraft server.jar size mismatch: expected {} bytes, got {} bytes (url={} cache_path={})",
             resolved.size,
-            bytes.len(),
+            downloaded,
             resolved.jar_url,
             jar_path.display()
         );
     }
-
-    let got = sha1::Sha1::digest(bytes.as_slice());
-    let got_hex = hex::encode(got);
-    if got_hex != *sha1_hex {
+    if !verify_cached(&jar_path, &checksum).await {
         anyhow::bail!(
-            "minecraft server.jar sha1 mismatch: expected {sha1_hex}, got {got_hex} (url={} cache_path={})",
+            "minecraft server.jar sha1 mismatch: expected {sha1_hex} (url={} cache_path={})",
             resolved.jar_url,
             jar_path.display()
         );
     }
 
-    let tmp_path = jar_path.with_extension("tmp");
-    let mut f = fs::File::create(&tmp_path)?;
-    f.write_all(&bytes)?;
-    f.sync_all()?;
-    fs::rename(tmp_path, &jar_path)?;
-
-    if let Some(cb) = on_progress.as_mut() {
-        cb(
-            resolved.size,
-            resolved.size,
-            last_report.speed_bytes_per_sec,
-        );
-    }
-
     if let Some(dir) = jar_path.parent() {
         mark_last_used(dir);
         write_meta_best_effort(dir, resolved);
     }
     Ok(jar_path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ResolvedServerJar, ensure_server_jar_in};
+    use sha1::Digest;
+
+    #[tokio::test]
+    async fn corrupt_cached_jar_is_downloaded_again() {
+        let body = b"not really a jar".to_vec();
+        let sha1 = hex::encode(sha1::Sha1::digest(&body));
+        let (base, requests) = crate::download::serve_bytes(body.clone()).await;
+        let root =
+            std::env::temp_dir().join(format!("alloy-agent-mc-jar-cache-{}", std::process::id()));
+        let jar = root.join(&sha1).join("server.jar");
+        std::fs::create_dir_all(jar.parent().unwrap()).unwrap();
+        std::fs::write(&jar, &body[..4]).unwrap();
+
+        let resolved = ResolvedServerJar {
+            version_id: "1.21".to_string(),
+            jar_url: format!("{base}/server.jar"),
+            sha1,
+            size: body.len() as u64,
+            java_major: 21,
+        };
+        let got = ensure_server_jar_in(&root, &resolved, None::<fn(u64, u64, u64)>)
+            .await
+            .unwrap();
+        assert_eq!(got, jar);
+        assert_eq!(std::fs::read(&jar).unwrap(), body);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        // An intact hit is served from the cache.
+        ensure_server_jar_in(&root, &resolved, None::<fn(u64, u64, u64)>)
+            .await
+            .unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -2,11 +2,9 @@ use std::{
     collections::BTreeMap,
     fs,
     path::{Component, Path, PathBuf},
-    time::Duration,
 };
 
 use anyhow::Context;
-use futures_util::StreamExt;
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 
@@ -14,6 +12,7 @@ use crate::minecraft;
 
 #[derive(Debug, Clone)]
 pub struct ImportParams {
+    pub accept_eula: bool,
     pub pack: String,
     pub memory_mb: u32,
     pub port: u16,
@@ -41,18 +40,30 @@ fn normalize_rel_path(rel: &str) -> anyhow::Result<PathBuf> {
     Ok(out)
 }
 
+/// Where a pack comes from: a zip URL, or a zip or directory under the data root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackSource {
+    Url(Url),
+    Local(PathBuf),
+}
+
+pub fn pack_source(raw: &str) -> Result<PackSource, String> {
+    let raw = raw.trim();
+    if is_http_url(raw) {
+        let url = Url::parse(raw).map_err(|e| format!("Invalid URL: {e}."))?;
+        return Ok(PackSource::Url(url));
+    }
+    let rel = normalize_rel_path(raw).map_err(|e| format!("{e}."))?;
+    if rel.as_os_str().is_empty() {
+        return Err("Provide a server pack zip URL, or a path under /data.".to_string());
+    }
+    Ok(PackSource::Local(rel))
+}
+
 pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<ImportParams> {
     let mut field_errors = BTreeMap::<String, String>::new();
 
-    match params.get("accept_eula").map(|v| v.trim()) {
-        Some("true") => {}
-        _ => {
-            field_errors.insert(
-                "accept_eula".to_string(),
-                "Required. You must accept the Minecraft EULA.".to_string(),
-            );
-        }
-    }
+    let accept_eula = crate::minecraft::parse_accept_eula(params, &mut field_errors);
 
     let pack = params
         .get("pack")
@@ -65,6 +76,8 @@ pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<Impo
             "pack".to_string(),
             "Required. Provide a server pack zip URL, or a path under /data.".to_string(),
         );
+    } else if let Err(msg) = pack_source(&pack) {
+        field_errors.insert("pack".to_string(), msg);
     }
 
     let memory_mb = match params
@@ -127,6 +140,7 @@ pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<Impo
     }
 
     Ok(ImportParams {
+        accept_eula,
         pack,
         memory_mb,
         port,
@@ -156,40 +170,6 @@ fn write_marker(instance_dir: &Path, marker: &ImportMarker) -> anyhow::Result<()
     Ok(())
 }
 
-fn extract_zip_safely(zip_path: &Path, out_dir: &Path) -> anyhow::Result<()> {
-    fs::create_dir_all(out_dir)?;
-    let f = fs::File::open(zip_path)?;
-    let mut archive = zip::ZipArchive::new(f)?;
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
-        let name = file.name().to_string();
-        let trimmed = name.trim_end_matches('/');
-        if trimmed.is_empty() {
-            continue;
-        }
-        let rel =
-            normalize_rel_path(trimmed).with_context(|| format!("invalid zip path {trimmed:?}"))?;
-        if rel.as_os_str().is_empty() {
-            continue;
-        }
-        let out_path = out_dir.join(&rel);
-        if name.ends_with('/') {
-            fs::create_dir_all(&out_path)?;
-            continue;
-        }
-        if let Some(parent) = out_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let tmp_path = out_path.with_extension("tmp");
-        let mut out = fs::File::create(&tmp_path)?;
-        std::io::copy(&mut file, &mut out)?;
-        out.sync_all().ok();
-        fs::rename(&tmp_path, &out_path)?;
-    }
-    Ok(())
-}
-
 fn find_flatten_root(extracted: &Path) -> PathBuf {
     let rd = match fs::read_dir(extracted) {
         Ok(v) => v,
@@ -278,42 +258,74 @@ fn is_http_url(s: &str) -> bool {
     lower.starts_with("http://") || lower.starts_with("https://")
 }
 
-async fn download_to_path(url: &str, path: &Path) -> anyhow::Result<()> {
-    if let Some(parent) = path.parent() {
-        tokio::fs::create_dir_all(parent).await?;
-    }
-
-    let client = reqwest::Client::builder()
-        .user_agent("alloy-agent")
-        .timeout(Duration::from_secs(30 * 60))
-        .build()
-        .context("build http client")?;
-    let resp = client
-        .get(url)
-        .send()
-        .await
-        .context("download")?
-        .error_for_status()
-        .context("download (status)")?;
+fn download_failed(message: String, hint: &str) -> anyhow::Error {
+    crate::error_payload::anyhow(
+        "download_failed",
+        message.clone(),
+        Some(BTreeMap::from([("pack".to_string(), message)])),
+        Some(hint.to_string()),
+    )
+}
 
-    let mut f = tokio::fs::File::create(path)
+/// Downloads the pack at `url` into `imports` and checks it is a zip.
+async fn download_pack(url: &Url, imports: &Path, nonce: &str) -> anyhow::Result<PathBuf> {
+    let zip_path = imports.join(format!("pack-{nonce}.zip"));
+    let max_bytes = crate::process_manager_support::import_max_bytes();
+    crate::download::download_with_resume(url.as_str(), &zip_path, max_bytes, |_, _, _| {})
         .await
-        .context("create download file")?;
-    let mut total: u64 = 0;
-    let mut stream = resp.bytes_stream();
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.context("download chunk")?;
-        total = total.saturating_add(chunk.len() as u64);
-        // Hard safety limit: 8GiB.
-        if total > 8 * 1024 * 1024 * 1024_u64 {
-            let _ = tokio::fs::remove_file(path).await;
-            anyhow::bail!("download too large");
-        }
-        tokio::io::AsyncWriteExt::write_all(&mut f, &chunk)
-            .await
-            .context("write download")?;
+        .map_err(|e| {
+            download_failed(
+                format!("failed to download server pack: {e:#}"),
+                "Check that the URL is reachable and points at the pack zip itself.",
+            )
+        })?;
+    if !crate::archive::has_zip_magic(&zip_path) {
+        let _ = tokio::fs::remove_file(&zip_path).await;
+        return Err(download_failed(
+            "the URL did not return a zip file".to_string(),
+            "Use a direct download link; the URL may point at a web page or an error page.",
+        ));
     }
-    tokio::io::AsyncWriteExt::flush(&mut f).await.ok();
+    Ok(zip_path)
+}
+
+/// Extracts `zip_path` and moves its (flattened) contents into `instance_dir`.
+async fn install_zip(
+    zip_path: &Path,
+    imports: &Path,
+    nonce: &str,
+    instance_dir: &Path,
+    output: Option<tokio::sync::mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<()> {
+    let extracted = imports.join(format!("extracted-{nonce}"));
+    tokio::task::spawn_blocking({
+        let zip_path = zip_path.to_path_buf();
+        let extracted = extracted.clone();
+        move || crate::archive::extract_zip_safely_with_output(&zip_path, &extracted, output)
+    })
+    .await
+    .context("extract task failed")?
+    .map_err(|e| {
+        crate::archive::as_install_failed(&e).unwrap_or_else(|| {
+            crate::error_payload::anyhow(
+                "install_failed",
+                format!("failed to extract server pack: {e:#}"),
+                None,
+                Some("Ensure the pack is a valid, server-ready zip.".to_string()),
+            )
+        })
+    })?;
+
+    let root = find_flatten_root(&extracted);
+    tokio::task::spawn_blocking({
+        let root = root.clone();
+        let instance_dir = instance_dir.to_path_buf();
+        move || move_flattened_into_instance(&root, &instance_dir)
+    })
+    .await
+    .context("install task failed")??;
+
+    let _ = tokio::fs::remove_dir_all(&extracted).await;
     Ok(())
 }
 
@@ -342,7 +354,12 @@ fn copy_dir(src: &Path, dst: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn ensure_imported(instance_dir: &Path, source: &str) -> anyhow::Result<()> {
+/// `output`, when set, receives extraction progress lines.
+pub async fn ensure_imported(
+    instance_dir: &Path,
+    source: &str,
+    output: Option<tokio::sync::mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<()> {
     if let Some(m) = read_marker(instance_dir) {
         if m.source.trim() == source.trim() {
             return Ok(());
@@ -359,53 +376,27 @@ pub async fn ensure_imported(instance_dir: &Path, source: &str) -> anyhow::Resul
         anyhow::bail!("missing pack source");
     }
 
-    // URL -> download zip; path -> local zip or directory under data root.
-    if is_http_url(src) {
-        let url = Url::parse(src).context("invalid pack url")?;
-        if url.scheme() != "http" && url.scheme() != "https" {
-            anyhow::bail!("pack url must be http(s)");
+    let imports = instance_dir.join("imports");
+    let nonce = alloy_process::ProcessId::new().0;
+    let rel = match pack_source(src).map_err(|e| anyhow::anyhow!("invalid pack: {e}"))? {
+        PackSource::Url(url) => {
+            tokio::fs::create_dir_all(&imports).await.ok();
+            let zip_path = download_pack(&url, &imports, &nonce).await?;
+            let installed = install_zip(&zip_path, &imports, &nonce, instance_dir, output).await;
+            let _ = tokio::fs::remove_file(&zip_path).await;
+            installed?;
+
+            write_marker(
+                instance_dir,
+                &ImportMarker {
+                    source: src.to_string(),
+                },
+            )?;
+            return Ok(());
         }
+        PackSource::Local(rel) => rel,
+    };
 
-        let imports = instance_dir.join("imports");
-        tokio::fs::create_dir_all(&imports).await.ok();
-        let nonce = alloy_process::ProcessId::new().0;
-        let zip_path = imports.join(format!("pack-{nonce}.zip"));
-        download_to_path(src, &zip_path).await?;
-
-        let extracted = imports.join(format!("extracted-{nonce}"));
-        tokio::task::spawn_blocking({
-            let zip_path = zip_path.clone();
-            let extracted = extracted.clone();
-            move || extract_zip_safely(&zip_path, &extracted)
-        })
-        .await
-        .context("extract task failed")??;
-
-        let root = find_flatten_root(&extracted);
-        tokio::task::spawn_blocking({
-            let root = root.clone();
-            let instance_dir = instance_dir.to_path_buf();
-            move || move_flattened_into_instance(&root, &instance_dir)
-        })
-        .await
-        .context("install task failed")??;
-
-        let _ = tokio::fs::remove_dir_all(&extracted).await;
-        let _ = tokio::fs::remove_file(&zip_path).await;
-
-        write_marker(
-            instance_dir,
-            &ImportMarker {
-                source: src.to_string(),
-            },
-        )?;
-        return Ok(());
-    }
-
-    let rel = normalize_rel_path(src)?;
-    if rel.as_os_str().is_empty() {
-        anyhow::bail!("pack path must be relative to ALLOY_DATA_ROOT");
-    }
     let path = minecraft::data_root().join(&rel);
     let meta =
         fs::metadata(&path).with_context(|| format!("pack not found: {}", path.display()))?;
@@ -439,28 +430,8 @@ pub async fn ensure_imported(instance_dir: &Path, source: &str) -> anyhow::Resul
         anyhow::bail!("pack path must be a .zip or a directory");
     }
 
-    let imports = instance_dir.join("imports");
     tokio::fs::create_dir_all(&imports).await.ok();
-    let nonce = alloy_process::ProcessId::new().0;
-    let extracted = imports.join(format!("extracted-{nonce}"));
-    tokio::task::spawn_blocking({
-        let zip_path = path.clone();
-        let extracted = extracted.clone();
-        move || extract_zip_safely(&zip_path, &extracted)
-    })
-    .await
-    .context("extract task failed")??;
-
-    let root = find_flatten_root(&extracted);
-    tokio::task::spawn_blocking({
-        let root = root.clone();
-        let instance_dir = instance_dir.to_path_buf();
-        move || move_flattened_into_instance(&root, &instance_dir)
-    })
-    .await
-    .context("install task failed")??;
-
-    let _ = tokio::fs::remove_dir_all(&extracted).await;
+    install_zip(&path, &imports, &nonce, instance_dir, output).await?;
 
     write_marker(
         instance_dir,
@@ -470,3 +441,72 @@ pub async fn ensure_imported(instance_dir: &Path, source: &str) -> anyhow::Resul
     )?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, path::PathBuf};
+
+    use super::{PackSource, ensure_imported, pack_source};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "alloy-agent-mc-import-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn pack_source_branches_on_url_or_data_path() {
+        assert!(matches!(
+            pack_source(" https://example.com/pack.zip "),
+            Ok(PackSource::Url(url)) if url.as_str() == "https://example.com/pack.zip"
+        ));
+        assert_eq!(
+            pack_source("packs/server.zip"),
+            Ok(PackSource::Local(PathBuf::from("packs/server.zip")))
+        );
+        assert!(pack_source("../outside.zip").is_err());
+        assert!(pack_source("/etc/server.zip").is_err());
+        assert!(pack_source("https://").is_err());
+    }
+
+    #[tokio::test]
+    async fn url_packs_must_be_zips() {
+        let html = b"<!doctype html><h1>404 Not Found</h1>".to_vec();
+        let (base, _) = crate::download::serve_bytes(html).await;
+        let dir = temp_dir("html");
+        let err = ensure_imported(&dir, &format!("{base}/pack.zip"), None)
+            .await
+            .unwrap_err();
+        let payload = crate::error_payload::decode(&err).unwrap();
+        assert_eq!(payload.code, "download_failed");
+        assert!(payload.message.contains("zip"), "{}", payload.message);
+        assert!(payload.field_errors.unwrap().contains_key("pack"));
+        assert!(!dir.join("import.json").exists());
+
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file(
+            "pack/server.properties",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+        zip.write_all(b"motd=imported\n").unwrap();
+        let body = zip.finish().unwrap().into_inner();
+        let (base, _) = crate::download::serve_bytes(body).await;
+        let dir2 = temp_dir("zip");
+        ensure_imported(&dir2, &format!("{base}/pack.zip"), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir2.join("server.properties")).unwrap(),
+            "motd=imported\n"
+        );
+        assert!(dir2.join("import.json").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_dir_all(&dir2);
+    }
+}
@@ -1,17 +1,287 @@
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+use crate::node_resources::MemInfo;
+
+/// How a server pack is started; shown in logs as `launch=<kind>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchKind {
+    /// `java -jar server.jar` (vanilla, Fabric, Quilt, legacy Forge).
+    Jar,
+    /// `@libraries/net/neoforged/.../unix_args.txt`.
+    NeoForge,
+    /// `@libraries/net/minecraftforge/forge/.../unix_args.txt` (1.17+ installers).
+    ModernForge,
+    /// Some other `unix_args.txt` under the instance.
+    ArgsFile,
+}
+
+impl std::fmt::Display for LaunchKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Jar => "jar",
+            Self::NeoForge => "neoforge",
+            Self::ModernForge => "forge",
+            Self::ArgsFile => "args-file",
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LaunchSpec {
     pub exec: String,
     pub args: Vec<String>,
-    pub kind: String,
+    pub kind: LaunchKind,
+}
+
+/// GC tuning selected by the `jvm_preset` param.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JvmPreset {
+    Default,
+    Aikar,
+    G1gc,
+}
+
+impl JvmPreset {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim() {
+            "" | "default" => Some(Self::Default),
+            "aikar" => Some(Self::Aikar),
+            "g1gc" => Some(Self::G1gc),
+            _ => None,
+        }
+    }
+
+    /// Flags placed after the heap size; see https://mcflags.emc.gs for `aikar`.
+    pub fn flags(self, memory_mb: u32) -> Vec<String> {
+        // Aikar's recommendation changes above a 12 GiB heap.
+        let large = memory_mb > 12 * 1024;
+        let pick = |small: &'static str, big: &'static str| if large { big } else { small };
+        let flags = match self {
+            Self::Default => Vec::new(),
+            Self::G1gc => vec![
+                "-XX:+UseG1GC",
+                "-XX:+ParallelRefProcEnabled",
+                "-XX:MaxGCPauseMillis=200",
+                "-XX:+DisableExplicitGC",
+            ],
+            Self::Aikar => vec![
+                "-XX:+UseG1GC",
+                "-XX:+ParallelRefProcEnabled",
+                "-XX:MaxGCPauseMillis=200",
+                "-XX:+UnlockExperimentalVMOptions",
+                "-XX:+DisableExplicitGC",
+                "-XX:+AlwaysPreTouch",
+                pick("-XX:G1NewSizePercent=30", "-XX:G1NewSizePercent=40"),
+                pick("-XX:G1MaxNewSizePercent=40", "-XX:G1MaxNewSizePercent=50"),
+                pick("-XX:G1HeapRegionSize=8M", "-XX:G1HeapRegionSize=16M"),
+                pick("-XX:G1ReservePercent=20", "-XX:G1ReservePercent=15"),
+                "-XX:G1HeapWastePercent=5",
+                "-XX:G1MixedGCCountTarget=4",
+                pick(
+                    "-XX:InitiatingHeapOccupancyPercent=15",
+                    "-XX:InitiatingHeapOccupancyPercent=20",
+                ),
+                "-XX:G1MixedGCLiveThresholdPercent=90",
+                "-XX:G1RSetUpdatingPauseTimePercent=5",
+                "-XX:SurvivorRatio=32",
+                "-XX:+PerfDisableSharedMem",
+                "-XX:MaxTenuringThreshold=1",
+                "-Dusing.aikars.flags=https://mcflags.emc.gs",
+                "-Daikars.new.flags=true",
+            ],
+        };
+        flags.into_iter().map(String::from).collect()
+    }
+}
+
+// Splits user `jvm_flags` on whitespace. Only JVM options are accepted: anything
+// that would replace the launch target (`-jar`, `-cp`, `@argfile`, a bare jar or
+// class name) or the heap size from the memory params is rejected.
+fn parse_jvm_flags(raw: &str) -> Result<Vec<String>, String> {
+    let mut out = Vec::new();
+    for flag in raw.split_whitespace() {
+        if matches!(
+            flag,
+            "-jar" | "-cp" | "-classpath" | "--class-path" | "-m" | "--module"
+        ) {
+            return Err(format!(
+                "{flag} is not allowed; Alloy sets the launch target."
+            ));
+        }
+        if flag.starts_with("-Xmx") || flag.starts_with("-Xms") {
+            return Err(format!(
+                "{flag} is not allowed; set memory_mb or min_memory_mb instead."
+            ));
+        }
+        if !flag.starts_with('-') {
+            return Err(format!("{flag} is not a JVM option (must start with '-')."));
+        }
+        out.push(flag.to_string());
+    }
+    Ok(out)
+}
+
+const MIB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HeapSize {
+    min_mb: u32,
+    max_mb: u32,
+}
+
+/// `pct` percent of `total_bytes`, in MiB.
+pub fn percent_of_mb(total_bytes: u64, pct: u32) -> u32 {
+    (total_bytes / MIB * u64::from(pct) / 100).min(u64::from(u32::MAX)) as u32
 }
 
-fn write_alloy_jvm_args(instance_dir: &Path, memory_mb: u32) -> anyhow::Result<PathBuf> {
+// Heap bounds: the max is `memory_mb`, or `memory_pct` of node RAM when set; the
+// min is `min_memory_mb`, defaulting to the max so the heap never has to grow.
+fn heap_size(
+    memory_mb: u32,
+    params: &BTreeMap<String, String>,
+    mem: Option<MemInfo>,
+    field_errors: &mut BTreeMap<String, String>,
+) -> HeapSize {
+    // Blank and 0 both mean unset.
+    let int_param = |key: &str| {
+        params
+            .get(key)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty() && *v != "0")
+            .map(|v| v.parse::<u32>())
+    };
+
+    let mut max_field = "memory_mb";
+    let mut max_mb = memory_mb;
+    match int_param("memory_pct") {
+        None => {}
+        Some(Ok(pct)) if (1..=90).contains(&pct) => {
+            max_field = "memory_pct";
+            match mem {
+                Some(mem) => max_mb = percent_of_mb(mem.total_bytes, pct),
+                None => {
+                    field_errors.insert(
+                        "memory_pct".to_string(),
+                        "Node RAM is unknown on this platform; set memory_mb instead.".to_string(),
+                    );
+                }
+            }
+            if max_mb < 512 {
+                field_errors.insert(
+                    "memory_pct".to_string(),
+                    format!("Gives a {max_mb} MiB heap; at least 512 MiB is needed."),
+                );
+            }
+        }
+        Some(_) => {
+            field_errors.insert(
+                "memory_pct".to_string(),
+                "Must be an integer percentage of node RAM (1..90).".to_string(),
+            );
+        }
+    }
+
+    let min_mb = match int_param("min_memory_mb") {
+        None => max_mb,
+        Some(Ok(v)) if v >= 256 => v,
+        Some(_) => {
+            field_errors.insert(
+                "min_memory_mb".to_string(),
+                "Must be an integer (MiB), at least 256.".to_string(),
+            );
+            max_mb
+        }
+    };
+    if min_mb > max_mb {
+        field_errors.insert(
+            "min_memory_mb".to_string(),
+            format!("Must not exceed the max heap ({max_mb} MiB)."),
+        );
+    }
+
+    if let Some(mem) = mem {
+        let available_mb = mem.available_bytes / MIB;
+        if u64::from(max_mb) > available_mb {
+            field_errors.insert(
+                max_field.to_string(),
+                format!(
+                    "A {max_mb} MiB heap exceeds the {available_mb} MiB available on this node."
+                ),
+            );
+        }
+    }
+
+    HeapSize { min_mb, max_mb }
+}
+
+/// JVM options for a Minecraft launch, in order: heap size, the `jvm_preset`
+/// flags, then the user's `jvm_flags`.
+pub fn jvm_args(memory_mb: u32, params: &BTreeMap<String, String>) -> anyhow::Result<Vec<String>> {
+    build_jvm_args(memory_mb, params, crate::node_resources::read_meminfo())
+}
+
+fn build_jvm_args(
+    memory_mb: u32,
+    params: &BTreeMap<String, String>,
+    mem: Option<MemInfo>,
+) -> anyhow::Result<Vec<String>> {
+    let mut field_errors = BTreeMap::<String, String>::new();
+    let heap = heap_size(memory_mb, params, mem, &mut field_errors);
+    let raw_preset = params.get("jvm_preset").map(String::as_str).unwrap_or("");
+    let preset = JvmPreset::parse(raw_preset).unwrap_or_else(|| {
+        field_errors.insert(
+            "jvm_preset".to_string(),
+            "Must be one of: default, aikar, g1gc.".to_string(),
+        );
+        JvmPreset::Default
+    });
+    let user = parse_jvm_flags(params.get("jvm_flags").map(String::as_str).unwrap_or(""))
+        .unwrap_or_else(|e| {
+            field_errors.insert("jvm_flags".to_string(), e);
+            Vec::new()
+        });
+    if !field_errors.is_empty() {
+        let heap_error = ["memory_mb", "memory_pct", "min_memory_mb"]
+            .iter()
+            .any(|k| field_errors.contains_key(*k));
+        let hint = if heap_error {
+            "Keep min_memory_mb at or below the max heap, and the max heap within the node's free memory."
+        } else {
+            "Fix the highlighted fields, then try again."
+        };
+        return Err(crate::error_payload::anyhow(
+            "invalid_param",
+            "invalid jvm params",
+            Some(field_errors),
+            Some(hint.to_string()),
+        ));
+    }
+
+    let mut out = vec![
+        format!("-Xms{}M", heap.min_mb),
+        format!("-Xmx{}M", heap.max_mb),
+    ];
+    out.extend(preset.flags(heap.max_mb));
+    out.extend(user);
+    Ok(out)
+}
+
+/// `java` args that run `server.jar` with `jvm_args`.
+pub fn jar_args(jvm_args: &[String]) -> Vec<String> {
+    let mut args = jvm_args.to_vec();
+    args.extend(["-jar", "server.jar", "nogui"].map(String::from));
+    args
+}
+
+fn write_alloy_jvm_args(instance_dir: &Path, jvm_args: &[String]) -> anyhow::Result<PathBuf> {
     let path = instance_dir.join("alloy_jvm_args.txt");
     let tmp = instance_dir.join("alloy_jvm_args.txt.tmp");
     let mut out = String::new();
-    out.push_str(&format!("-Xmx{}M\n", memory_mb.max(256)));
+    for arg in jvm_args {
+        out.push_str(arg);
+        out.push('\n');
+    }
     std::fs::write(&tmp, out.as_bytes())?;
     std::fs::rename(tmp, &path)?;
     Ok(path)
@@ -59,8 +329,55 @@ fn best_candidate(mut candidates: Vec<PathBuf>) -> Option<PathBuf> {
     candidates.into_iter().next()
 }
 
-fn find_unix_args(instance_dir: &Path) -> Option<PathBuf> {
-    // Forge/neoforge server packs typically place args under libraries/**/unix_args.txt.
+// Where loader installers put the versioned args file, most preferred first.
+// NeoForge for 1.20.1 still shipped under the `forge` artifact id.
+const LOADER_ARGS_DIRS: [(LaunchKind, &str); 3] = [
+    (LaunchKind::NeoForge, "libraries/net/neoforged/neoforge"),
+    (LaunchKind::NeoForge, "libraries/net/neoforged/forge"),
+    (
+        LaunchKind::ModernForge,
+        "libraries/net/minecraftforge/forge",
+    ),
+];
+
+// Numeric runs of a loader version, so `21.1.77` sorts above `21.1.9`.
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().unwrap_or(u64::MAX))
+        .collect()
+}
+
+fn find_loader_args(instance_dir: &Path) -> Option<(LaunchKind, PathBuf)> {
+    for (kind, rel) in LOADER_ARGS_DIRS {
+        let Ok(rd) = std::fs::read_dir(instance_dir.join(rel)) else {
+            continue;
+        };
+        let newest = rd
+            .flatten()
+            .filter_map(|e| {
+                let args = e.path().join("unix_args.txt");
+                let version = e.file_name().to_string_lossy().to_string();
+                args.is_file().then_some((version, args))
+            })
+            .max_by(|(va, _), (vb, _)| {
+                version_key(va)
+                    .cmp(&version_key(vb))
+                    .then_with(|| va.cmp(vb))
+            });
+        if let Some((_, path)) = newest {
+            return Some((kind, path));
+        }
+    }
+    None
+}
+
+fn find_unix_args(instance_dir: &Path) -> Option<(LaunchKind, PathBuf)> {
+    if let Some(found) = find_loader_args(instance_dir) {
+        return Some(found);
+    }
+    // Other packs still tend to keep it somewhere under libraries/.
     let mut out = Vec::<PathBuf>::new();
     let libs = instance_dir.join("libraries");
     if libs.is_dir() {
@@ -69,7 +386,7 @@ fn find_unix_args(instance_dir: &Path) -> Option<PathBuf> {
     if out.is_empty() {
         collect_named_files(instance_dir, "unix_args.txt", &mut out);
     }
-    best_candidate(out)
+    best_candidate(out).map(|path| (LaunchKind::ArgsFile, path))
 }
 
 fn to_rel_str(base: &Path, path: &Path) -> anyhow::Result<String> {
@@ -83,25 +400,26 @@ fn to_rel_str(base: &Path, path: &Path) -> anyhow::Result<String> {
     Ok(s)
 }
 
-pub fn resolve_launch_spec(instance_dir: &Path, memory_mb: u32) -> anyhow::Result<LaunchSpec> {
+pub fn resolve_launch_spec(
+    instance_dir: &Path,
+    jvm_args: &[String],
+    java: &Path,
+) -> anyhow::Result<LaunchSpec> {
     let server_jar = instance_dir.join("server.jar");
     if server_jar.is_file() {
         return Ok(LaunchSpec {
-            exec: "java".to_string(),
-            args: vec![
-                format!("-Xmx{}M", memory_mb),
-                "-jar".to_string(),
-                "server.jar".to_string(),
-                "nogui".to_string(),
-            ],
-            kind: "jar".to_string(),
+            exec: java.display().to_string(),
+            args: jar_args(jvm_args),
+            kind: LaunchKind::Jar,
         });
     }
 
-    if let Some(unix_args) = find_unix_args(instance_dir) {
+    if let Some((kind, unix_args)) = find_unix_args(instance_dir) {
         let user_jvm = instance_dir.join("user_jvm_args.txt");
-        let alloy_jvm = write_alloy_jvm_args(instance_dir, memory_mb)?;
+        let alloy_jvm = write_alloy_jvm_args(instance_dir, jvm_args)?;
 
+        // Same order as the installer's run.sh, with Alloy's heap and flags
+        // after the user's so they win.
         let mut args = Vec::<String>::new();
         if user_jvm.is_file() {
             args.push(format!("@{}", to_rel_str(instance_dir, &user_jvm)?));
@@ -111,9 +429,9 @@ pub fn resolve_launch_spec(instance_dir: &Path, memory_mb: u32) -> anyhow::Resul
         args.push("nogui".to_string());
 
         return Ok(LaunchSpec {
-            exec: "java".to_string(),
+            exec: java.display().to_string(),
             args,
-            kind: "args-file".to_string(),
+            kind,
         });
     }
 
@@ -121,3 +439,207 @@ pub fn resolve_launch_spec(instance_dir: &Path, memory_mb: u32) -> anyhow::Resul
         "could not determine how to launch this server pack (expected server.jar or libraries/**/unix_args.txt)"
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        JvmPreset, LaunchKind, build_jvm_args, jar_args, percent_of_mb, resolve_launch_spec,
+    };
+    use crate::node_resources::MemInfo;
+    use std::collections::BTreeMap;
+    use std::path::{Path, PathBuf};
+
+    fn params(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn jvm_args(memory_mb: u32, params: &BTreeMap<String, String>) -> anyhow::Result<Vec<String>> {
+        build_jvm_args(memory_mb, params, None)
+    }
+
+    fn field_error_keys(err: anyhow::Error) -> Vec<String> {
+        let payload = crate::error_payload::decode(&err).unwrap();
+        assert_eq!(payload.code, "invalid_param");
+        payload.field_errors.unwrap().into_keys().collect()
+    }
+
+    #[test]
+    fn aikar_preset_expands_before_jar() {
+        let jvm = jvm_args(4096, &params(&[("jvm_preset", "aikar")])).unwrap();
+        let args = jar_args(&jvm);
+        assert_eq!(&args[..2], ["-Xms4096M", "-Xmx4096M"]);
+        assert_eq!(&args[2..args.len() - 3], JvmPreset::Aikar.flags(4096));
+        assert_eq!(&args[args.len() - 3..], ["-jar", "server.jar", "nogui"]);
+        for flag in [
+            "-XX:+UseG1GC",
+            "-XX:G1NewSizePercent=30",
+            "-XX:G1HeapRegionSize=8M",
+            "-Dusing.aikars.flags=https://mcflags.emc.gs",
+        ] {
+            assert!(args.iter().any(|a| a == flag), "missing {flag}");
+        }
+        assert!(
+            JvmPreset::Aikar
+                .flags(16384)
+                .contains(&"-XX:G1HeapRegionSize=16M".to_string())
+        );
+
+        // Without min_memory_mb the heap starts at its max.
+        let jvm = jvm_args(2048, &BTreeMap::new()).unwrap();
+        assert_eq!(
+            jar_args(&jvm),
+            ["-Xms2048M", "-Xmx2048M", "-jar", "server.jar", "nogui"]
+        );
+    }
+
+    #[test]
+    fn flags_that_change_the_launch_target_are_rejected() {
+        for bad in ["-jar evil.jar", "-XX:+UseZGC -cp x", "@evil.txt", "-Xmx64G"] {
+            let err = jvm_args(2048, &params(&[("jvm_flags", bad)])).unwrap_err();
+            assert_eq!(field_error_keys(err), ["jvm_flags"], "{bad}");
+        }
+        assert!(jvm_args(2048, &params(&[("jvm_preset", "zgc")])).is_err());
+
+        let jvm = jvm_args(
+            2048,
+            &params(&[
+                ("jvm_preset", "g1gc"),
+                ("jvm_flags", " -XX:+UseZGC  -Dfoo=bar "),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(&jvm[jvm.len() - 2..], ["-XX:+UseZGC", "-Dfoo=bar"]);
+    }
+
+    #[test]
+    fn memory_pct_sizes_the_heap_from_node_ram() {
+        let gib = 1024 * 1024 * 1024;
+        assert_eq!(percent_of_mb(16 * gib, 50), 8192);
+        assert_eq!(percent_of_mb(16 * gib + 512, 25), 4096);
+
+        let mem = MemInfo {
+            total_bytes: 16 * gib,
+            available_bytes: 12 * gib,
+        };
+        let jvm = build_jvm_args(
+            2048,
+            &params(&[("memory_pct", "50"), ("min_memory_mb", "1024")]),
+            Some(mem),
+        )
+        .unwrap();
+        assert_eq!(&jvm[..2], ["-Xms1024M", "-Xmx8192M"]);
+
+        // 90% of 16 GiB is more than the 12 GiB available.
+        let err = build_jvm_args(2048, &params(&[("memory_pct", "90")]), Some(mem)).unwrap_err();
+        assert_eq!(field_error_keys(err), ["memory_pct"]);
+        let err = build_jvm_args(16384, &BTreeMap::new(), Some(mem)).unwrap_err();
+        assert_eq!(field_error_keys(err), ["memory_mb"]);
+    }
+
+    #[test]
+    fn min_heap_above_max_is_rejected() {
+        let err = jvm_args(2048, &params(&[("min_memory_mb", "4096")])).unwrap_err();
+        assert_eq!(field_error_keys(err), ["min_memory_mb"]);
+
+        let jvm = jvm_args(2048, &params(&[("min_memory_mb", "2048")])).unwrap();
+        assert_eq!(&jvm[..2], ["-Xms2048M", "-Xmx2048M"]);
+    }
+
+    fn pack_dir(name: &str, files: &[&str]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("alloy-agent-launch-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for rel in files {
+            let path = dir.join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        dir
+    }
+
+    fn launch(dir: &Path) -> (LaunchKind, Vec<String>) {
+        let spec = resolve_launch_spec(dir, &["-Xmx2048M".to_string()], Path::new("java")).unwrap();
+        (spec.kind, spec.args)
+    }
+
+    #[test]
+    fn neoforge_layout_launches_the_newest_args_file() {
+        let dir = pack_dir(
+            "neoforge",
+            &[
+                "libraries/net/neoforged/neoforge/21.1.9/unix_args.txt",
+                "libraries/net/neoforged/neoforge/21.1.77/unix_args.txt",
+                "libraries/net/neoforged/neoforge/21.1.80/win_args.txt",
+            ],
+        );
+        let (kind, args) = launch(&dir);
+        assert_eq!(kind, LaunchKind::NeoForge);
+        assert_eq!(
+            args,
+            [
+                "@alloy_jvm_args.txt",
+                "@libraries/net/neoforged/neoforge/21.1.77/unix_args.txt",
+                "nogui"
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("alloy_jvm_args.txt")).unwrap(),
+            "-Xmx2048M\n"
+        );
+
+        // NeoForge for 1.20.1 still used the forge artifact id.
+        let legacy = pack_dir(
+            "neoforge-1201",
+            &["libraries/net/neoforged/forge/1.20.1-47.1.106/unix_args.txt"],
+        );
+        assert_eq!(launch(&legacy).0, LaunchKind::NeoForge);
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_dir_all(&legacy);
+    }
+
+    #[test]
+    fn modern_forge_layout_prepends_user_jvm_args() {
+        let dir = pack_dir(
+            "forge",
+            &[
+                "user_jvm_args.txt",
+                "run.sh",
+                "libraries/net/minecraftforge/forge/1.20.1-47.2.0/unix_args.txt",
+                "libraries/net/minecraftforge/forge/1.20.1-47.10.1/unix_args.txt",
+            ],
+        );
+        let (kind, args) = launch(&dir);
+        assert_eq!(kind, LaunchKind::ModernForge);
+        assert_eq!(kind.to_string(), "forge");
+        assert_eq!(
+            args,
+            [
+                "@user_jvm_args.txt",
+                "@alloy_jvm_args.txt",
+                "@libraries/net/minecraftforge/forge/1.20.1-47.10.1/unix_args.txt",
+                "nogui"
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn jar_and_unknown_args_layouts_are_still_detected() {
+        let jar = pack_dir("jar", &["server.jar", "libraries/x/unix_args.txt"]);
+        assert_eq!(launch(&jar).0, LaunchKind::Jar);
+
+        let other = pack_dir("other", &["libraries/com/example/loader/1.0/unix_args.txt"]);
+        let (kind, args) = launch(&other);
+        assert_eq!(kind, LaunchKind::ArgsFile);
+        assert_eq!(args[1], "@libraries/com/example/loader/1.0/unix_args.txt");
+
+        let empty = pack_dir("empty", &["mods/a.jar"]);
+        assert!(resolve_launch_spec(&empty, &[], Path::new("java")).is_err());
+        for dir in [jar, other, empty] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
@@ -4,50 +4,156 @@ use std::{
     io::Write,
     path::{Component, Path, PathBuf},
     sync::{Arc, OnceLock},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::Context;
-use futures_util::StreamExt;
 use reqwest::Url;
 use serde::Deserialize;
 use sha1::Digest;
 use tokio::sync::Mutex;
 
+use crate::download::download_with_resume;
+use crate::download_cache::{Checksum, verify_cached, verify_cached_zip, write_sha256_sidecar};
 use crate::minecraft;
 
+const MODRINTH_API_BASE: &str = "https://api.modrinth.com/v2";
+const MAX_DOWNLOAD_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+// `latest` is re-resolved after this; pinned versions are immutable and never expire.
+const LATEST_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
 #[derive(Debug, Clone)]
 pub struct ModrinthParams {
-    pub mrpack: String,
+    pub accept_eula: bool,
+    pub source: PackSource,
     pub memory_mb: u32,
     pub port: u16,
 }
 
-pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<ModrinthParams> {
-    let mut field_errors = BTreeMap::<String, String>::new();
+/// Where a pack comes from, parsed from the `mrpack`, `version_id` and
+/// `loader` params.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackSource {
+    // Recorded in modrinth.json; an unchanged key skips reinstalling.
+    key: String,
+    target: PackTarget,
+}
 
-    match params.get("accept_eula").map(|v| v.trim()) {
-        Some("true") => {}
-        _ => {
-            field_errors.insert(
-                "accept_eula".to_string(),
-                "Required. You must accept the Minecraft EULA.".to_string(),
-            );
-        }
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PackTarget {
+    Url(String),
+    // Version id or version number of a project.
+    Version { slug: String, version: String },
+    Latest { slug: String, loader: String },
+}
 
-    let mrpack = params
-        .get("mrpack")
-        .map(|v| v.trim())
-        .filter(|v| !v.is_empty())
-        .unwrap_or("")
-        .to_string();
-    if mrpack.is_empty() {
-        field_errors.insert(
-            "mrpack".to_string(),
-            "Required. Paste a Modrinth version link or a direct .mrpack URL.".to_string(),
+fn is_slug(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn parse_source(mrpack: &str, version_id: &str, loader: &str) -> Result<PackSource, String> {
+    let raw = mrpack.trim();
+    let version_id = version_id.trim();
+    if raw.is_empty() {
+        return Err(
+            "Required. Paste a Modrinth modpack link, slug, or a direct .mrpack URL.".into(),
         );
     }
+    if raw.to_ascii_lowercase().ends_with(".mrpack") {
+        return Ok(PackSource {
+            key: raw.to_string(),
+            target: PackTarget::Url(raw.to_string()),
+        });
+    }
+
+    let slug = if raw.starts_with("http://") || raw.starts_with("https://") {
+        let url = Url::parse(raw).map_err(|e| format!("Invalid URL: {e}."))?;
+        if !url.host_str().unwrap_or_default().contains("modrinth.com") {
+            return Err("Unsupported URL; paste a Modrinth link or a direct .mrpack URL.".into());
+        }
+        // https://modrinth.com/modpack/<slug>[/version/<version>]
+        let segs: Vec<&str> = url.path().split('/').filter(|s| !s.is_empty()).collect();
+        let slug = segs
+            .iter()
+            .position(|s| *s == "modpack" || *s == "project")
+            .and_then(|i| segs.get(i + 1))
+            .filter(|s| is_slug(s))
+            .ok_or_else(|| "Modrinth link must point at a modpack.".to_string())?;
+        let linked_version = segs
+            .iter()
+            .position(|s| *s == "version")
+            .and_then(|i| segs.get(i + 1));
+        if let Some(version) = linked_version {
+            return Ok(PackSource {
+                key: raw.to_string(),
+                target: PackTarget::Version {
+                    slug: slug.to_string(),
+                    version: version.to_string(),
+                },
+            });
+        }
+        slug.to_string()
+    } else if is_slug(raw) {
+        raw.to_string()
+    } else {
+        return Err("Unsupported source; paste a Modrinth link, slug, or .mrpack URL.".into());
+    };
+
+    if version_id.is_empty() || version_id.eq_ignore_ascii_case("latest") {
+        let loader = match loader.trim().to_ascii_lowercase().as_str() {
+            "" | "fabric" => "fabric".to_string(),
+            other => return Err(format!("Unsupported loader {other:?} (expected fabric).")),
+        };
+        Ok(PackSource {
+            key: format!("{raw}@latest:{loader}"),
+            target: PackTarget::Latest { slug, loader },
+        })
+    } else {
+        Ok(PackSource {
+            key: format!("{raw}@{version_id}"),
+            target: PackTarget::Version {
+                slug,
+                version: version_id.to_string(),
+            },
+        })
+    }
+}
+
+fn param<'a>(params: &'a BTreeMap<String, String>, key: &str) -> &'a str {
+    params.get(key).map(|v| v.trim()).unwrap_or("")
+}
+
+/// Parses just the pack source, for callers that don't need the full params.
+pub fn pack_source(params: &BTreeMap<String, String>) -> anyhow::Result<PackSource> {
+    parse_source(
+        param(params, "mrpack"),
+        param(params, "version_id"),
+        param(params, "loader"),
+    )
+    .map_err(|msg| {
+        crate::error_payload::anyhow(
+            "invalid_param",
+            "invalid mrpack",
+            Some(BTreeMap::from([("mrpack".to_string(), msg)])),
+            None,
+        )
+    })
+}
+
+pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<ModrinthParams> {
+    let mut field_errors = BTreeMap::<String, String>::new();
+
+    let accept_eula = crate::minecraft::parse_accept_eula(params, &mut field_errors);
+
+    let source = parse_source(
+        param(params, "mrpack"),
+        param(params, "version_id"),
+        param(params, "loader"),
+    )
+    .map_err(|msg| field_errors.insert("mrpack".to_string(), msg))
+    .ok();
 
     let memory_mb = match params
         .get("memory_mb")
@@ -99,17 +205,18 @@ pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<Modr
         },
     };
 
-    if !field_errors.is_empty() {
+    let Some(source) = source.filter(|_| field_errors.is_empty()) else {
         return Err(crate::error_payload::anyhow(
             "invalid_param",
             "invalid minecraft modrinth params",
             Some(field_errors),
             Some("Fix the highlighted fields, then try again.".to_string()),
         ));
-    }
+    };
 
     Ok(ModrinthParams {
-        mrpack,
+        accept_eula,
+        source,
         memory_mb,
         port,
     })
@@ -191,7 +298,11 @@ fn normalize_rel_path(rel: &str) -> anyhow::Result<PathBuf> {
 }
 
 #[derive(Debug, Deserialize)]
-struct ModrinthVersionResp {
+struct ModrinthVersion {
+    #[serde(default)]
+    loaders: Vec<String>,
+    #[serde(default)]
+    date_published: String,
     files: Vec<ModrinthVersionFile>,
 }
 
@@ -200,94 +311,166 @@ struct ModrinthVersionFile {
     url: String,
     filename: String,
     primary: Option<bool>,
+    #[serde(default)]
+    hashes: HashMap<String, String>,
 }
 
-async fn resolve_mrpack_url(source: &str) -> anyhow::Result<String> {
-    let raw = source.trim();
-    if raw.is_empty() {
-        anyhow::bail!("missing mrpack source");
-    }
-    if raw.to_ascii_lowercase().ends_with(".mrpack") {
-        return Ok(raw.to_string());
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ResolvedPack {
+    url: String,
+    // From the Modrinth API; direct URLs have none to check against.
+    sha1: Option<String>,
+}
 
-    let url = Url::parse(raw).context("invalid mrpack url")?;
-    let host = url.host_str().unwrap_or_default();
-    if host.contains("modrinth.com") {
-        // Common format: https://modrinth.com/modpack/<slug>/version/<version_id>
-        let segs: Vec<&str> = url.path().split('/').filter(|s| !s.is_empty()).collect();
-        if let Some(i) = segs.iter().position(|s| *s == "version") {
-            if let Some(version_id) = segs.get(i + 1) {
-                let api = format!("https://api.modrinth.com/v2/version/{version_id}");
-                let resp = http_client()
-                    .get(api)
-                    .send()
-                    .await
-                    .context("fetch modrinth version")?
-                    .error_for_status()
-                    .context("fetch modrinth version (status)")?
-                    .json::<ModrinthVersionResp>()
-                    .await
-                    .context("parse modrinth version json")?;
-
-                let mut candidates: Vec<&ModrinthVersionFile> = resp
-                    .files
-                    .iter()
-                    .filter(|f| f.filename.to_ascii_lowercase().ends_with(".mrpack"))
-                    .collect();
-                candidates.sort_by_key(|f| !(f.primary.unwrap_or(false)));
-                let file = candidates
-                    .first()
-                    .ok_or_else(|| anyhow::anyhow!("no .mrpack file found for that version"))?;
-                return Ok(file.url.clone());
-            }
-        }
-    }
+fn mrpack_file(version: &ModrinthVersion) -> Option<ResolvedPack> {
+    let mut candidates: Vec<&ModrinthVersionFile> = version
+        .files
+        .iter()
+        .filter(|f| f.filename.to_ascii_lowercase().ends_with(".mrpack"))
+        .collect();
+    candidates.sort_by_key(|f| !(f.primary.unwrap_or(false)));
+    candidates.first().map(|f| ResolvedPack {
+        url: f.url.clone(),
+        sha1: f.hashes.get("sha1").map(|h| h.to_ascii_lowercase()),
+    })
+}
 
-    anyhow::bail!(
-        "unsupported mrpack source; paste a Modrinth version link or a direct .mrpack URL"
-    );
+/// Newest version for `loader` that ships an .mrpack.
+fn pick_latest<'a>(versions: &'a [ModrinthVersion], loader: &str) -> Option<&'a ModrinthVersion> {
+    versions
+        .iter()
+        .filter(|v| v.loaders.iter().any(|l| l.eq_ignore_ascii_case(loader)))
+        .filter(|v| mrpack_file(v).is_some())
+        // RFC 3339 timestamps in one format sort chronologically as strings.
+        .max_by(|a, b| a.date_published.cmp(&b.date_published))
 }
 
-async fn download_to_path(url: &str, path: &Path) -> anyhow::Result<()> {
-    if let Some(parent) = path.parent() {
-        tokio::fs::create_dir_all(parent).await?;
-    }
+fn resolve_cache() -> &'static std::sync::Mutex<HashMap<String, (Instant, ResolvedPack)>> {
+    static CACHE: OnceLock<std::sync::Mutex<HashMap<String, (Instant, ResolvedPack)>>> =
+        OnceLock::new();
+    CACHE.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+}
+
+fn not_found(field: &str, message: String) -> anyhow::Error {
+    crate::error_payload::anyhow(
+        "invalid_param",
+        message.clone(),
+        Some(BTreeMap::from([(field.to_string(), format!("{message}."))])),
+        Some("Check the modpack slug and version on modrinth.com.".to_string()),
+    )
+}
 
+async fn fetch_json<T: serde::de::DeserializeOwned>(
+    url: Url,
+    what: &str,
+) -> anyhow::Result<Option<T>> {
     let resp = http_client()
         .get(url)
         .send()
         .await
-        .with_context(|| format!("download {url}"))?
+        .with_context(|| format!("fetch {what}"))?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let v = resp
         .error_for_status()
-        .with_context(|| format!("download {url} (status)"))?;
-
-    let tmp = path.with_extension("tmp");
-    let mut f = tokio::fs::File::create(&tmp).await?;
-    let mut total: u64 = 0;
-    let mut stream = resp.bytes_stream();
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        total = total.saturating_add(chunk.len() as u64);
-        if total > 2 * 1024 * 1024 * 1024_u64 {
-            let _ = tokio::fs::remove_file(&tmp).await;
-            anyhow::bail!("download too large");
+        .with_context(|| format!("fetch {what} (status)"))?
+        .json::<T>()
+        .await
+        .with_context(|| format!("parse {what} json"))?;
+    Ok(Some(v))
+}
+
+async fn fetch_pack(target: &PackTarget) -> anyhow::Result<ResolvedPack> {
+    match target {
+        PackTarget::Url(url) => Ok(ResolvedPack {
+            url: url.clone(),
+            sha1: None,
+        }),
+        PackTarget::Version { slug, version } => {
+            let url = Url::parse(&format!(
+                "{MODRINTH_API_BASE}/project/{slug}/version/{version}"
+            ))
+            .context("modrinth version url")?;
+            let v = fetch_json::<ModrinthVersion>(url, "modrinth version")
+                .await?
+                .ok_or_else(|| {
+                    not_found(
+                        "mrpack",
+                        format!("Modrinth version {slug}/{version} not found"),
+                    )
+                })?;
+            mrpack_file(&v).ok_or_else(|| anyhow::anyhow!("no .mrpack file found for that version"))
+        }
+        PackTarget::Latest { slug, loader } => {
+            let mut url = Url::parse(&format!("{MODRINTH_API_BASE}/project/{slug}/version"))
+                .context("modrinth versions url")?;
+            url.query_pairs_mut()
+                .append_pair("loaders", &format!("[\"{loader}\"]"));
+            let versions = fetch_json::<Vec<ModrinthVersion>>(url, "modrinth versions")
+                .await?
+                .ok_or_else(|| not_found("mrpack", format!("Modrinth project {slug} not found")))?;
+            pick_latest(&versions, loader)
+                .and_then(mrpack_file)
+                .ok_or_else(|| {
+                    not_found(
+                        "loader",
+                        format!("No {loader} version of {slug} has an .mrpack"),
+                    )
+                })
         }
-        tokio::io::AsyncWriteExt::write_all(&mut f, &chunk).await?;
     }
-    tokio::io::AsyncWriteExt::flush(&mut f).await.ok();
-    let _ = tokio::fs::rename(&tmp, path).await;
+}
+
+async fn resolve_pack(source: &PackSource) -> anyhow::Result<ResolvedPack> {
+    let ttl = match &source.target {
+        PackTarget::Url(_) => return fetch_pack(&source.target).await,
+        PackTarget::Version { .. } => None,
+        PackTarget::Latest { .. } => Some(LATEST_CACHE_TTL),
+    };
+    let cached = resolve_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&source.key)
+        .filter(|(at, _)| ttl.is_none_or(|ttl| at.elapsed() < ttl))
+        .map(|(_, pack)| pack.clone());
+    if let Some(pack) = cached {
+        return Ok(pack);
+    }
+
+    let pack = fetch_pack(&source.target).await?;
+    resolve_cache()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(source.key.clone(), (Instant::now(), pack.clone()));
+    Ok(pack)
+}
+
+async fn download_to_path(url: &str, path: &Path) -> anyhow::Result<()> {
+    download_with_resume(url, path, MAX_DOWNLOAD_BYTES, |_, _, _| {}).await?;
     Ok(())
 }
 
-async fn ensure_mrpack_downloaded(resolved_url: &str) -> anyhow::Result<PathBuf> {
+/// Packs resolved through the API are checked against its SHA1; direct URLs
+/// against the sidecar written when they were downloaded.
+async fn pack_cached(path: &Path, pack: &ResolvedPack) -> bool {
+    match &pack.sha1 {
+        Some(sha1) => verify_cached(path, &Checksum::Sha1(sha1.clone())).await,
+        None => verify_cached_zip(path).await,
+    }
+}
+
+async fn ensure_mrpack_downloaded(
+    pack: &ResolvedPack,
+    on_progress: impl FnMut(u64, u64, u64) + Send,
+) -> anyhow::Result<PathBuf> {
     let url_hash = {
         let mut h = sha1::Sha1::new();
-        h.update(resolved_url.as_bytes());
+        h.update(pack.url.as_bytes());
         hex::encode(h.finalize())
     };
     let pack_path = cache_dir().join("packs").join(format!("{url_hash}.mrpack"));
-    if pack_path.exists() {
+    if pack_cached(&pack_path, pack).await {
         if let Some(dir) = pack_path.parent() {
             mark_last_used(dir);
         }
@@ -297,14 +480,22 @@ async fn ensure_mrpack_downloaded(resolved_url: &str) -> anyhow::Result<PathBuf>
     let lock_key = format!("modrinth:mrpack:{url_hash}");
     let lock = lock_for(&lock_key);
     let _guard = lock.lock().await;
-    if pack_path.exists() {
+    if pack_cached(&pack_path, pack).await {
         if let Some(dir) = pack_path.parent() {
             mark_last_used(dir);
         }
         return Ok(pack_path);
     }
 
-    download_to_path(resolved_url, &pack_path).await?;
+    download_with_resume(&pack.url, &pack_path, MAX_DOWNLOAD_BYTES, on_progress).await?;
+    match &pack.sha1 {
+        Some(sha1) => {
+            if !verify_cached(&pack_path, &Checksum::Sha1(sha1.clone())).await {
+                anyhow::bail!("mrpack sha1 mismatch: expected {sha1} (url={})", pack.url);
+            }
+        }
+        None => write_sha256_sidecar(&pack_path).await?,
+    }
     if let Some(dir) = pack_path.parent() {
         mark_last_used(dir);
     }
@@ -423,24 +614,8 @@ fn write_marker(instance_dir: &Path, marker: &InstalledMarker) -> anyhow::Result
     Ok(())
 }
 
-pub async fn ensure_installed(instance_dir: &Path, source: &str) -> anyhow::Result<InstalledPack> {
-    if let Some(m) = read_marker(instance_dir) {
-        if m.source.trim() == source.trim() {
-            return Ok(InstalledPack {
-                minecraft: m.minecraft,
-                loader: m.loader,
-                loader_version: m.loader_version,
-            });
-        }
-    }
-
-    let resolved_url = resolve_mrpack_url(source).await?;
-    let mrpack_path = ensure_mrpack_downloaded(&resolved_url).await?;
-
-    // Parse index + keep archive to read overrides.
-    let (index, mut archive) = load_mrpack_index(&mrpack_path)?;
-
-    let mc_version = index
+fn pack_dependencies(index: &MrpackIndex) -> anyhow::Result<InstalledPack> {
+    let minecraft = index
         .dependencies
         .get("minecraft")
         .map(|v| v.trim().to_string())
@@ -463,6 +638,52 @@ pub async fn ensure_installed(instance_dir: &Path, source: &str) -> anyhow::Resu
         anyhow::bail!("only fabric-loader modpacks are supported for now");
     }
 
+    Ok(InstalledPack {
+        minecraft,
+        loader,
+        loader_version,
+    })
+}
+
+/// Resolves the pack's Minecraft version and loader without installing it.
+/// The mrpack is downloaded into the shared cache, so a later start reuses it.
+pub async fn inspect_pack(source: &PackSource) -> anyhow::Result<InstalledPack> {
+    let resolved = resolve_pack(source).await?;
+    let mrpack_path = ensure_mrpack_downloaded(&resolved, |_, _, _| {}).await?;
+    let (index, _) = load_mrpack_index(&mrpack_path)?;
+    pack_dependencies(&index)
+}
+
+/// `on_progress` follows the .mrpack download as `(downloaded, total, bytes_per_sec)`;
+/// `output`, when set, receives override extraction progress lines.
+pub async fn ensure_installed(
+    instance_dir: &Path,
+    source: &PackSource,
+    on_progress: impl FnMut(u64, u64, u64) + Send,
+    output: Option<tokio::sync::mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<InstalledPack> {
+    if let Some(m) = read_marker(instance_dir)
+        && m.source.trim() == source.key
+    {
+        return Ok(InstalledPack {
+            minecraft: m.minecraft,
+            loader: m.loader,
+            loader_version: m.loader_version,
+        });
+    }
+
+    let resolved = resolve_pack(source).await?;
+    let mrpack_path = ensure_mrpack_downloaded(&resolved, on_progress).await?;
+
+    // Parse index + keep archive to read overrides.
+    let (index, mut archive) = load_mrpack_index(&mrpack_path)?;
+
+    let InstalledPack {
+        minecraft: mc_version,
+        loader,
+        loader_version,
+    } = pack_dependencies(&index)?;
+
     ensure_fabric_server_jar(instance_dir, &mc_version, &loader_version).await?;
 
     // Download listed server files.
@@ -502,7 +723,11 @@ pub async fn ensure_installed(instance_dir: &Path, source: &str) -> anyhow::Resu
     }
 
     // Extract overrides/ into instance root.
+    let mut budget = crate::archive::ExtractBudget::new(crate::archive::ArchiveLimits::from_env());
+    let mut progress =
+        crate::archive::ExtractProgress::new(output, "modpack overrides", archive.len());
     for i in 0..archive.len() {
+        progress.entry(i);
         let mut file = archive.by_index(i)?;
         let name = file.name().to_string();
         let trimmed = name.trim_end_matches('/');
@@ -519,21 +744,27 @@ pub async fn ensure_installed(instance_dir: &Path, source: &str) -> anyhow::Resu
             fs::create_dir_all(&out_path)?;
             continue;
         }
+        budget.admit(rest, file.size())?;
         if let Some(parent) = out_path.parent() {
             fs::create_dir_all(parent)?;
         }
         let tmp = out_path.with_extension("tmp");
         let mut out = fs::File::create(&tmp)?;
-        std::io::copy(&mut file, &mut out)?;
+        if let Err(e) = budget.copy(rest, &mut file, &mut out) {
+            drop(out);
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
         out.sync_all().ok();
         fs::rename(&tmp, &out_path)?;
     }
+    progress.finish();
 
     write_marker(
         instance_dir,
         &InstalledMarker {
-            source: source.trim().to_string(),
-            resolved_mrpack_url: resolved_url.clone(),
+            source: source.key.clone(),
+            resolved_mrpack_url: resolved.url,
             minecraft: mc_version.clone(),
             loader: loader.clone(),
             loader_version: loader_version.clone(),
@@ -546,3 +777,120 @@ pub async fn ensure_installed(instance_dir: &Path, source: &str) -> anyhow::Resu
         loader_version,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ModrinthVersion, PackTarget, ResolvedPack, mrpack_file, pack_cached, parse_source,
+        pick_latest,
+    };
+    use sha1::Digest;
+
+    // Trimmed shape of `GET /v2/project/{slug}/version`.
+    const VERSIONS: &str = r#"[
+        {
+            "id": "B2", "version_number": "2.0", "loaders": ["fabric"],
+            "date_published": "2026-05-02T10:00:00.000000Z",
+            "files": [
+                {"url": "https://cdn.modrinth.com/b2-extra.zip", "filename": "extra.zip",
+                 "primary": false, "hashes": {"sha1": "00"}},
+                {"url": "https://cdn.modrinth.com/b2.mrpack", "filename": "pack-2.0.mrpack",
+                 "primary": true, "hashes": {"sha1": "ABCDEF", "sha512": "ff"}}
+            ]
+        },
+        {
+            "id": "C3", "version_number": "3.0-forge", "loaders": ["forge"],
+            "date_published": "2026-06-01T10:00:00.000000Z",
+            "files": [{"url": "https://cdn.modrinth.com/c3.mrpack", "filename": "c3.mrpack",
+                       "primary": true, "hashes": {"sha1": "cc"}}]
+        },
+        {
+            "id": "A1", "version_number": "1.0", "loaders": ["fabric"],
+            "date_published": "2026-01-01T10:00:00.000000Z",
+            "files": [{"url": "https://cdn.modrinth.com/a1.mrpack", "filename": "a1.mrpack",
+                       "primary": true, "hashes": {"sha1": "aa"}}]
+        }
+    ]"#;
+
+    #[test]
+    fn slug_and_version_params_pick_the_right_target() {
+        let t = |m: &str, v: &str| parse_source(m, v, "").unwrap().target;
+        assert_eq!(
+            t("fabulously-optimized", ""),
+            PackTarget::Latest {
+                slug: "fabulously-optimized".to_string(),
+                loader: "fabric".to_string(),
+            }
+        );
+        assert_eq!(
+            t(
+                "https://modrinth.com/modpack/fabulously-optimized/",
+                "tLb7w1bS"
+            ),
+            PackTarget::Version {
+                slug: "fabulously-optimized".to_string(),
+                version: "tLb7w1bS".to_string(),
+            }
+        );
+        // A version in the link wins over the param.
+        assert_eq!(
+            t(
+                "https://modrinth.com/modpack/fabulously-optimized/version/6.0.0",
+                "latest"
+            ),
+            PackTarget::Version {
+                slug: "fabulously-optimized".to_string(),
+                version: "6.0.0".to_string(),
+            }
+        );
+        assert_eq!(
+            t("https://example.com/packs/fo.mrpack", "1.0"),
+            PackTarget::Url("https://example.com/packs/fo.mrpack".to_string())
+        );
+
+        assert!(parse_source("https://example.com/pack", "", "").is_err());
+        assert!(parse_source("fo", "", "forge").is_err());
+        assert!(parse_source("", "", "").is_err());
+        // Markers of packs installed from a version link stay valid.
+        let link = "https://modrinth.com/modpack/fo/version/abc";
+        assert_eq!(parse_source(link, "", "").unwrap().key, link);
+    }
+
+    #[test]
+    fn mocked_versions_resolve_to_the_newest_loader_mrpack() {
+        let versions: Vec<ModrinthVersion> = serde_json::from_str(VERSIONS).unwrap();
+        let latest = pick_latest(&versions, "fabric").unwrap();
+        assert_eq!(
+            mrpack_file(latest),
+            Some(ResolvedPack {
+                url: "https://cdn.modrinth.com/b2.mrpack".to_string(),
+                sha1: Some("abcdef".to_string()),
+            })
+        );
+        let forge = pick_latest(&versions, "forge")
+            .and_then(mrpack_file)
+            .unwrap();
+        assert_eq!(forge.url, "https://cdn.modrinth.com/c3.mrpack");
+        assert!(pick_latest(&versions, "quilt").is_none());
+    }
+
+    #[tokio::test]
+    async fn sha1_mismatch_is_rejected_and_deleted() {
+        let path = std::env::temp_dir().join(format!(
+            "alloy-agent-mrpack-sha1-{}.mrpack",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"pack bytes").unwrap();
+        let pack = |sha1: String| ResolvedPack {
+            url: "https://cdn.modrinth.com/p.mrpack".to_string(),
+            sha1: Some(sha1),
+        };
+        let good = hex::encode(sha1::Sha1::digest(b"pack bytes"));
+
+        assert!(pack_cached(&path, &pack(good.to_ascii_uppercase())).await);
+        assert!(path.exists());
+
+        assert!(!pack_cached(&path, &pack("0".repeat(40))).await);
+        assert!(!path.exists());
+    }
+}
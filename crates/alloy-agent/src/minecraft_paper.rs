@@ -0,0 +1,452 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use tokio::sync::Mutex;
+
+use crate::download::download_with_resume;
+use crate::download_cache::{Checksum, verify_cached};
+
+// Paper jars are ~50 MiB; anything far past that is not a server jar.
+const MAX_JAR_BYTES: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct PaperParams {
+    pub accept_eula: bool,
+    /// A Minecraft version id, or `latest` for the newest one Paper supports.
+    pub mc_version: String,
+    /// `None` picks the newest stable build.
+    pub build: Option<u32>,
+    pub memory_mb: u32,
+    pub port: u16,
+}
+
+fn is_valid_mc_version(v: &str) -> bool {
+    !v.is_empty()
+        && v.len() <= 32
+        && v.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<PaperParams> {
+    let mut field_errors = BTreeMap::<String, String>::new();
+    let param = |key: &str| params.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+
+    let mc_version = param("mc_version").unwrap_or("latest").to_string();
+    if !is_valid_mc_version(&mc_version) {
+        field_errors.insert(
+            "mc_version".to_string(),
+            "Must be a Minecraft version (e.g. 1.21.1) or latest.".to_string(),
+        );
+    }
+
+    let build = match param("build") {
+        None | Some("latest") => None,
+        Some(raw) => match raw.parse::<u32>() {
+            Ok(v) if v > 0 => Some(v),
+            _ => {
+                field_errors.insert(
+                    "build".to_string(),
+                    "Must be a Paper build number or latest.".to_string(),
+                );
+                None
+            }
+        },
+    };
+
+    // EULA, memory and port follow the vanilla rules.
+    let base = match crate::minecraft::validate_vanilla_params(params) {
+        Ok(base) => Some(base),
+        Err(e) => match crate::error_payload::decode(&e) {
+            Some(payload) => {
+                field_errors.extend(payload.field_errors.unwrap_or_default());
+                None
+            }
+            None => return Err(e),
+        },
+    };
+
+    let Some(base) = base.filter(|_| field_errors.is_empty()) else {
+        return Err(crate::error_payload::anyhow(
+            "invalid_param",
+            "invalid paper params",
+            Some(field_errors),
+            Some("Fix the highlighted fields, then try again.".to_string()),
+        ));
+    };
+
+    Ok(PaperParams {
+        accept_eula: base.accept_eula,
+        mc_version,
+        build,
+        memory_mb: base.memory_mb,
+        port: base.port,
+    })
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct ProjectResponse {
+    versions: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct BuildsResponse {
+    builds: Vec<Build>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct Build {
+    pub build: u32,
+    // `default` for stable builds, `experimental` otherwise.
+    #[serde(default)]
+    pub channel: String,
+    pub downloads: BuildDownloads,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct BuildDownloads {
+    pub application: BuildDownload,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub(crate) struct BuildDownload {
+    pub name: String,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedPaperJar {
+    pub mc_version: String,
+    pub build: u32,
+    pub jar_url: String,
+    pub sha256: String,
+}
+
+fn api_base() -> String {
+    std::env::var("ALLOY_PAPER_API_URL")
+        .ok()
+        .map(|v| v.trim().trim_end_matches('/').to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "https://api.papermc.io".to_string())
+}
+
+/// `requested`, or else the newest stable build.
+pub(crate) fn select_build(builds: &[Build], requested: Option<u32>) -> anyhow::Result<&Build> {
+    match requested {
+        Some(n) => builds
+            .iter()
+            .find(|b| b.build == n)
+            .ok_or_else(|| anyhow::anyhow!("paper build {n} does not exist")),
+        None => builds
+            .iter()
+            .filter(|b| b.channel == "default")
+            .max_by_key(|b| b.build)
+            .ok_or_else(|| anyhow::anyhow!("no stable paper build yet; pin a build number")),
+    }
+}
+
+fn api_client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent("alloy-agent")
+        .timeout(Duration::from_secs(60))
+        .build()
+}
+
+// `mc_version` itself unless it is `latest`.
+async fn concrete_mc_version(
+    client: &reqwest::Client,
+    project_url: &str,
+    mc_version: &str,
+) -> anyhow::Result<String> {
+    if mc_version != "latest" {
+        return Ok(mc_version.to_string());
+    }
+    let project: ProjectResponse = client
+        .get(project_url)
+        .send()
+        .await
+        .context("fetch paper project")?
+        .error_for_status()?
+        .json()
+        .await
+        .context("parse paper project")?;
+    // Listed oldest first.
+    project
+        .versions
+        .last()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("paper lists no minecraft versions"))
+}
+
+/// The Minecraft version `mc_version` stands for (`latest` is looked up).
+pub async fn resolve_mc_version(mc_version: &str) -> anyhow::Result<String> {
+    let project_url = format!("{}/v2/projects/paper", api_base());
+    concrete_mc_version(&api_client()?, &project_url, mc_version).await
+}
+
+pub async fn resolve_paper_jar(
+    mc_version: &str,
+    build: Option<u32>,
+) -> anyhow::Result<ResolvedPaperJar> {
+    resolve_paper_jar_from(&api_base(), mc_version, build).await
+}
+
+async fn resolve_paper_jar_from(
+    base: &str,
+    mc_version: &str,
+    build: Option<u32>,
+) -> anyhow::Result<ResolvedPaperJar> {
+    let client = api_client()?;
+    let project_url = format!("{base}/v2/projects/paper");
+    let mc_version = concrete_mc_version(&client, &project_url, mc_version).await?;
+
+    let resp = client
+        .get(format!("{project_url}/versions/{mc_version}/builds"))
+        .send()
+        .await
+        .context("fetch paper builds")?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        anyhow::bail!("paper does not support minecraft {mc_version}");
+    }
+    let builds: BuildsResponse = resp
+        .error_for_status()?
+        .json()
+        .await
+        .context("parse paper builds")?;
+
+    let picked =
+        select_build(&builds.builds, build).with_context(|| format!("minecraft {mc_version}"))?;
+    Ok(ResolvedPaperJar {
+        jar_url: format!(
+            "{project_url}/versions/{mc_version}/builds/{}/downloads/{}",
+            picked.build, picked.downloads.application.name
+        ),
+        build: picked.build,
+        sha256: picked.downloads.application.sha256.to_ascii_lowercase(),
+        mc_version,
+    })
+}
+
+pub fn cache_dir() -> PathBuf {
+    crate::minecraft::data_root()
+        .join("cache")
+        .join("minecraft")
+        .join("paper")
+}
+
+fn mark_last_used(entry_dir: &Path) {
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    // Best-effort.
+    let _ = fs::write(entry_dir.join(".last_used"), format!("{now_ms}\n"));
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct PaperJarMeta {
+    mc_version: String,
+    build: u32,
+    sha256: String,
+}
+
+fn write_meta_best_effort(entry_dir: &Path, resolved: &ResolvedPaperJar) {
+    let meta = PaperJarMeta {
+        mc_version: resolved.mc_version.clone(),
+        build: resolved.build,
+        sha256: resolved.sha256.clone(),
+    };
+    let Ok(json) = serde_json::to_vec_pretty(&meta) else {
+        return;
+    };
+    let tmp = entry_dir.join("meta.json.tmp");
+    if fs::write(&tmp, json).is_err() || fs::rename(&tmp, entry_dir.join("meta.json")).is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+}
+
+fn download_locks() -> &'static std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>> {
+    static LOCKS: OnceLock<std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>> = OnceLock::new();
+    LOCKS.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
+}
+
+fn lock_for(key: &str) -> Arc<Mutex<()>> {
+    let mut map = download_locks().lock().unwrap_or_else(|e| e.into_inner());
+    map.entry(key.to_string())
+        .or_insert_with(|| Arc::new(Mutex::new(())))
+        .clone()
+}
+
+pub async fn ensure_paper_jar_with_progress<F>(
+    resolved: &ResolvedPaperJar,
+    on_progress: Option<F>,
+) -> anyhow::Result<PathBuf>
+where
+    F: FnMut(u64, u64, u64) + Send,
+{
+    ensure_paper_jar_in(&cache_dir(), resolved, on_progress).await
+}
+
+/// Caches the jar under `<cache_root>/<sha256>/server.jar`, checked against
+/// the SHA256 the PaperMC API published for the build.
+pub(crate) async fn ensure_paper_jar_in<F>(
+    cache_root: &Path,
+    resolved: &ResolvedPaperJar,
+    mut on_progress: Option<F>,
+) -> anyhow::Result<PathBuf>
+where
+    F: FnMut(u64, u64, u64) + Send,
+{
+    let sha256 = &resolved.sha256;
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("paper api returned an invalid sha256: {sha256}");
+    }
+    let jar_path = cache_root.join(sha256).join("server.jar");
+    let checksum = Checksum::Sha256(sha256.clone());
+
+    let lock = lock_for(&format!("minecraft:paper:{sha256}"));
+    let _guard = lock.lock().await;
+    if !verify_cached(&jar_path, &checksum).await {
+        download_with_resume(
+            &resolved.jar_url,
+            &jar_path,
+            MAX_JAR_BYTES,
+            |downloaded, total, speed| {
+                if let Some(cb) = on_progress.as_mut() {
+                    cb(downloaded, total, speed);
+                }
+            },
+        )
+        .await
+        .context("download paper jar")?;
+        if !verify_cached(&jar_path, &checksum).await {
+            anyhow::bail!(
+                "paper jar sha256 mismatch: expected {sha256} (url={} cache_path={})",
+                resolved.jar_url,
+                jar_path.display()
+            );
+        }
+    } else if let Some(cb) = on_progress.as_mut() {
+        let size = fs::metadata(&jar_path).map(|m| m.len()).unwrap_or(0);
+        cb(size, size, 0);
+    }
+
+    if let Some(dir) = jar_path.parent() {
+        mark_last_used(dir);
+        write_meta_best_effort(dir, resolved);
+    }
+    Ok(jar_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Build, BuildsResponse, ensure_paper_jar_in, resolve_paper_jar_from, select_build};
+    use crate::download::{http_response, serve};
+    use sha2::Digest;
+
+    fn builds_json(sha256: &str) -> String {
+        let build = |n: u32, channel: &str| {
+            format!(
+                r#"{{"build":{n},"channel":"{channel}","downloads":{{"application":{{"name":"paper-1.21.1-{n}.jar","sha256":"{sha256}"}}}}}}"#
+            )
+        };
+        format!(
+            r#"{{"project_id":"paper","version":"1.21.1","builds":[{},{},{}]}}"#,
+            build(118, "default"),
+            build(119, "default"),
+            build(120, "experimental"),
+        )
+    }
+
+    #[test]
+    fn latest_build_is_the_newest_stable_one() {
+        let builds = serde_json::from_str::<BuildsResponse>(&builds_json("ab"))
+            .unwrap()
+            .builds;
+        assert_eq!(select_build(&builds, None).unwrap().build, 119);
+        assert_eq!(select_build(&builds, Some(120)).unwrap().build, 120);
+        assert!(select_build(&builds, Some(7)).is_err());
+
+        let experimental: Vec<Build> = builds
+            .into_iter()
+            .filter(|b| b.channel == "experimental")
+            .collect();
+        assert!(select_build(&experimental, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn builds_resolve_against_the_v2_api_and_verify_sha256() {
+        let jar = b"paper server jar".to_vec();
+        let sha256 = hex::encode(sha2::Sha256::digest(&jar));
+        let builds = builds_json(&sha256);
+        let body = jar.clone();
+        let (base, requests) = serve(move |_, head| {
+            let path = head.split_whitespace().nth(1).unwrap_or("").to_string();
+            let json = |body: &str| {
+                http_response(
+                    "200 OK",
+                    &[
+                        ("Content-Type", "application/json".to_string()),
+                        ("Content-Length", body.len().to_string()),
+                    ],
+                    body.as_bytes(),
+                )
+            };
+            match path.as_str() {
+                "/v2/projects/paper" => json(r#"{"versions":["1.20.6","1.21","1.21.1"]}"#),
+                "/v2/projects/paper/versions/1.21.1/builds" => json(&builds),
+                "/v2/projects/paper/versions/1.21.1/builds/119/downloads/paper-1.21.1-119.jar" => {
+                    http_response(
+                        "200 OK",
+                        &[("Content-Length", body.len().to_string())],
+                        &body,
+                    )
+                }
+                _ => http_response("404 Not Found", &[("Content-Length", "0".to_string())], b""),
+            }
+        })
+        .await;
+
+        let resolved = resolve_paper_jar_from(&base, "latest", None).await.unwrap();
+        assert_eq!(resolved.mc_version, "1.21.1");
+        assert_eq!(resolved.build, 119);
+        assert_eq!(resolved.sha256, sha256);
+        assert!(
+            resolved
+                .jar_url
+                .ends_with("/builds/119/downloads/paper-1.21.1-119.jar")
+        );
+
+        let err = resolve_paper_jar_from(&base, "1.8.8", None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("does not support"), "{err}");
+
+        let root =
+            std::env::temp_dir().join(format!("alloy-agent-paper-cache-{}", std::process::id()));
+        let path = ensure_paper_jar_in(&root, &resolved, None::<fn(u64, u64, u64)>)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), jar);
+        let downloads = |r: &std::sync::Mutex<Vec<String>>| {
+            let r = r.lock().unwrap();
+            r.iter().filter(|h| h.contains("/downloads/")).count()
+        };
+        assert_eq!(downloads(&requests), 1);
+
+        // A build whose published hash does not match is rejected.
+        let mut tampered = resolved.clone();
+        tampered.sha256 = "0".repeat(64);
+        assert!(
+            ensure_paper_jar_in(&root, &tampered, None::<fn(u64, u64, u64)>)
+                .await
+                .is_err()
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,247 @@
+use std::{fmt, io, time::Duration};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+// Status JSON carries a base64 favicon; anything far beyond that is not a Minecraft server.
+const MAX_PACKET_BYTES: usize = 1024 * 1024;
+// "Any version"; servers answer status pings regardless of the protocol number.
+const STATUS_PROTOCOL_VERSION: i32 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerStatus {
+    pub version: String,
+    pub players_online: u32,
+    pub max_players: u32,
+    pub motd: String,
+}
+
+#[derive(Debug)]
+pub enum PingError {
+    /// Connect/read failed or timed out; the server may just not be up yet.
+    Io(io::Error),
+    /// Something answered, but not with a Server List Ping response.
+    Protocol(String),
+}
+
+impl fmt::Display for PingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PingError::Io(e) => write!(f, "server list ping failed: {e}"),
+            PingError::Protocol(msg) => write!(f, "invalid server list ping response: {msg}"),
+        }
+    }
+}
+
+impl From<io::Error> for PingError {
+    fn from(e: io::Error) -> Self {
+        PingError::Io(e)
+    }
+}
+
+/// Sends a Server List Ping status request to `127.0.0.1:port`.
+pub async fn ping(port: u16, timeout: Duration) -> Result<ServerStatus, PingError> {
+    tokio::time::timeout(timeout, ping_inner(port))
+        .await
+        .map_err(|_| PingError::Io(io::Error::new(io::ErrorKind::TimedOut, "timed out")))?
+}
+
+async fn ping_inner(port: u16) -> Result<ServerStatus, PingError> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await?;
+
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, 0x00);
+    write_varint(&mut handshake, STATUS_PROTOCOL_VERSION);
+    write_string(&mut handshake, "127.0.0.1");
+    handshake.extend_from_slice(&port.to_be_bytes());
+    // Next state: status.
+    write_varint(&mut handshake, 1);
+
+    let mut out = Vec::new();
+    write_frame(&mut out, &handshake);
+    write_frame(&mut out, &[0x00]);
+    stream.write_all(&out).await?;
+    stream.flush().await?;
+
+    let packet = read_frame(&mut stream).await?;
+    let mut body = packet.as_slice();
+    let id = read_varint_slice(&mut body)?;
+    if id != 0x00 {
+        return Err(PingError::Protocol(format!("unexpected packet id {id:#x}")));
+    }
+    let len = usize::try_from(read_varint_slice(&mut body)?)
+        .map_err(|_| PingError::Protocol("negative string length".to_string()))?;
+    let json = body
+        .get(..len)
+        .ok_or_else(|| PingError::Protocol("truncated status json".to_string()))?;
+    parse_status_json(json)
+}
+
+fn parse_status_json(raw: &[u8]) -> Result<ServerStatus, PingError> {
+    let v: serde_json::Value = serde_json::from_slice(raw)
+        .map_err(|e| PingError::Protocol(format!("status json: {e}")))?;
+    let count = |key: &str| {
+        v.pointer(&format!("/players/{key}"))
+            .and_then(serde_json::Value::as_u64)
+            .map(|n| u32::try_from(n).unwrap_or(u32::MAX))
+    };
+    let (Some(players_online), Some(max_players)) = (count("online"), count("max")) else {
+        return Err(PingError::Protocol(
+            "status json has no player counts".to_string(),
+        ));
+    };
+    Ok(ServerStatus {
+        version: v
+            .pointer("/version/name")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        players_online,
+        max_players,
+        motd: v.get("description").map(chat_text).unwrap_or_default(),
+    })
+}
+
+/// Flattens a chat component (plain string, `{text, extra}` object or array) to text.
+fn chat_text(v: &serde_json::Value) -> String {
+    match v {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(parts) => parts.iter().map(chat_text).collect(),
+        serde_json::Value::Object(obj) => {
+            let mut out = obj
+                .get("text")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            if let Some(extra) = obj.get("extra") {
+                out.push_str(&chat_text(extra));
+            }
+            out
+        }
+        _ => String::new(),
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut v = value as u32;
+    loop {
+        if v & !0x7f == 0 {
+            buf.push(v as u8);
+            return;
+        }
+        buf.push((v & 0x7f) as u8 | 0x80);
+        v >>= 7;
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    write_varint(buf, s.len() as i32);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn write_frame(buf: &mut Vec<u8>, payload: &[u8]) {
+    write_varint(buf, payload.len() as i32);
+    buf.extend_from_slice(payload);
+}
+
+fn decode_varint(mut next: impl FnMut() -> Result<u8, PingError>) -> Result<i32, PingError> {
+    let mut value: u32 = 0;
+    for i in 0..5 {
+        let b = next()?;
+        value |= u32::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(PingError::Protocol("varint too long".to_string()))
+}
+
+fn read_varint_slice(buf: &mut &[u8]) -> Result<i32, PingError> {
+    decode_varint(|| {
+        let (&b, rest) = buf
+            .split_first()
+            .ok_or_else(|| PingError::Protocol("truncated varint".to_string()))?;
+        *buf = rest;
+        Ok(b)
+    })
+}
+
+// The peer accepted the connection and then hung up: something is listening,
+// it just does not speak SLP.
+fn read_error(e: io::Error) -> PingError {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        PingError::Protocol("connection closed before a status response".to_string())
+    } else {
+        PingError::Io(e)
+    }
+}
+
+async fn read_frame(r: &mut (impl AsyncRead + Unpin)) -> Result<Vec<u8>, PingError> {
+    // Read the length prefix byte by byte; at most 5 bytes.
+    let mut prefix = Vec::with_capacity(5);
+    loop {
+        let b = r.read_u8().await.map_err(read_error)?;
+        prefix.push(b);
+        if b & 0x80 == 0 || prefix.len() == 5 {
+            break;
+        }
+    }
+    let len = read_varint_slice(&mut prefix.as_slice())?;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|n| (1..=MAX_PACKET_BYTES).contains(n))
+        .ok_or_else(|| PingError::Protocol(format!("bad packet length {len}")))?;
+    let mut packet = vec![0u8; len];
+    r.read_exact(&mut packet).await.map_err(read_error)?;
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PingError, ping, read_frame, write_frame, write_string, write_varint};
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    async fn serve_once(reply: Vec<u8>) -> u16 {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let handshake = read_frame(&mut sock).await.unwrap();
+            // Packet id 0, then the "any version" protocol number.
+            assert_eq!(&handshake[..6], &[0x00, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+            assert_eq!(read_frame(&mut sock).await.unwrap(), [0x00]);
+            sock.write_all(&reply).await.unwrap();
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn parses_status_from_a_mock_server() {
+        let json = r#"{"version":{"name":"1.21.1","protocol":767},
+            "players":{"max":20,"online":3},
+            "description":{"text":"A ","extra":[{"text":"Minecraft"}," Server"]}}"#;
+        let mut packet = Vec::new();
+        write_varint(&mut packet, 0x00);
+        write_string(&mut packet, json);
+        let mut reply = Vec::new();
+        write_frame(&mut reply, &packet);
+
+        let port = serve_once(reply).await;
+        let status = ping(port, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(status.version, "1.21.1");
+        assert_eq!(status.players_online, 3);
+        assert_eq!(status.max_players, 20);
+        assert_eq!(status.motd, "A Minecraft Server");
+    }
+
+    #[tokio::test]
+    async fn non_slp_replies_are_protocol_errors() {
+        let port = serve_once(b"HTTP/1.1 400 Bad Request\r\n\r\n".to_vec()).await;
+        let err = ping(port, Duration::from_secs(5)).await.unwrap_err();
+        assert!(matches!(err, PingError::Protocol(_)), "{err}");
+    }
+}
@@ -0,0 +1,86 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Context;
+
+// Kept under the data root, outside every instance directory: the defaults may
+// carry admin secrets that instance owners must not read back.
+const STATE_FILE: &str = "node-default-params.json";
+
+fn state_path() -> PathBuf {
+    crate::minecraft::data_root().join(STATE_FILE)
+}
+
+/// Node-wide default params last sent by the control plane.
+pub(crate) async fn load() -> BTreeMap<String, String> {
+    let Ok(raw) = tokio::fs::read(state_path()).await else {
+        return BTreeMap::new();
+    };
+    serde_json::from_slice(&raw).unwrap_or_default()
+}
+
+/// Replaces the stored defaults; the control plane sends the full map with every start.
+pub(crate) async fn save<'a>(
+    defaults: impl IntoIterator<Item = (&'a String, &'a String)>,
+) -> anyhow::Result<()> {
+    let defaults: BTreeMap<&String, &String> = defaults.into_iter().collect();
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .context("create data root")?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    let data = serde_json::to_vec_pretty(&defaults).context("serialize node defaults")?;
+    tokio::fs::write(&tmp, &data)
+        .await
+        .context("write node defaults")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = tokio::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600)).await;
+    }
+    tokio::fs::rename(&tmp, &path)
+        .await
+        .context("persist node defaults")?;
+    Ok(())
+}
+
+/// Drops params that only carry a node default, so they are re-merged (with
+/// whatever the default is by then) instead of being persisted per instance.
+pub(crate) fn strip_inherited(
+    params: &BTreeMap<String, String>,
+    defaults: &BTreeMap<String, String>,
+) -> BTreeMap<String, String> {
+    params
+        .iter()
+        .filter(|(k, v)| defaults.get(*k) != Some(*v))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_inherited;
+    use std::collections::BTreeMap;
+
+    fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn strip_inherited_keeps_only_instance_values() {
+        let defaults = map(&[("frp_config", "token = admin"), ("memory_mb", "4096")]);
+        let params = map(&[
+            ("frp_config", "token = admin"),
+            ("memory_mb", "2048"),
+            ("version", "1.21"),
+        ]);
+        assert_eq!(
+            strip_inherited(&params, &defaults),
+            map(&[("memory_mb", "2048"), ("version", "1.21")])
+        );
+    }
+}
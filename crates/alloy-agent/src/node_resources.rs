@@ -0,0 +1,115 @@
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MemInfo {
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct LoadAvg {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+/// Host capacity and load; `None` where the platform does not expose it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NodeResources {
+    pub cpu_count: u32,
+    pub memory: Option<MemInfo>,
+    pub load_avg: Option<LoadAvg>,
+    pub data_root_free_bytes: Option<u64>,
+}
+
+/// `MemTotal` and `MemAvailable` from `/proc/meminfo`. Kernels older than 3.14
+/// lack `MemAvailable`; `MemFree` is the closest stand-in there.
+fn parse_meminfo(raw: &str) -> Option<MemInfo> {
+    let field = |name: &str| {
+        raw.lines().find_map(|line| {
+            let rest = line.strip_prefix(name)?.strip_prefix(':')?;
+            let kib: u64 = rest.trim().trim_end_matches("kB").trim().parse().ok()?;
+            Some(kib.saturating_mul(1024))
+        })
+    };
+    Some(MemInfo {
+        total_bytes: field("MemTotal")?,
+        available_bytes: field("MemAvailable").or_else(|| field("MemFree"))?,
+    })
+}
+
+/// The three load averages leading `/proc/loadavg`.
+fn parse_loadavg(raw: &str) -> Option<LoadAvg> {
+    let mut it = raw.split_whitespace().map(|v| v.parse::<f64>().ok());
+    Some(LoadAvg {
+        one: it.next()??,
+        five: it.next()??,
+        fifteen: it.next()??,
+    })
+}
+
+async fn read_proc<T>(path: &str, parse: fn(&str) -> Option<T>) -> Option<T> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    parse(&tokio::fs::read_to_string(path).await.ok()?)
+}
+
+/// Blocking read of `/proc/meminfo`, for checks made while preparing a launch.
+pub(crate) fn read_meminfo() -> Option<MemInfo> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    parse_meminfo(&std::fs::read_to_string("/proc/meminfo").ok()?)
+}
+
+pub(crate) async fn node_resources(data_root: &Path) -> NodeResources {
+    NodeResources {
+        cpu_count: std::thread::available_parallelism()
+            .map(|n| n.get() as u32)
+            .unwrap_or(1),
+        memory: read_proc("/proc/meminfo", parse_meminfo).await,
+        load_avg: read_proc("/proc/loadavg", parse_loadavg).await,
+        data_root_free_bytes: crate::process_manager::free_bytes(data_root),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoadAvg, MemInfo, parse_loadavg, parse_meminfo};
+
+    #[test]
+    fn meminfo_fixture_is_parsed() {
+        let raw = "MemTotal:       16314128 kB\n\
+                   MemFree:         1203344 kB\n\
+                   MemAvailable:    9876544 kB\n\
+                   Buffers:          412340 kB\n\
+                   Cached:          7340032 kB\n";
+        assert_eq!(
+            parse_meminfo(raw),
+            Some(MemInfo {
+                total_bytes: 16314128 * 1024,
+                available_bytes: 9876544 * 1024,
+            })
+        );
+
+        // Pre-3.14 kernels: no MemAvailable.
+        let old = "MemTotal: 2048 kB\nMemFree: 512 kB\n";
+        assert_eq!(parse_meminfo(old).unwrap().available_bytes, 512 * 1024);
+        assert_eq!(parse_meminfo("MemFree: 512 kB\n"), None);
+    }
+
+    #[test]
+    fn loadavg_fixture_is_parsed() {
+        assert_eq!(
+            parse_loadavg("0.52 1.07 2.50 3/1234 56789\n"),
+            Some(LoadAvg {
+                one: 0.52,
+                five: 1.07,
+                fifteen: 2.5,
+            })
+        );
+        assert_eq!(parse_loadavg("0.52 oops 2.50"), None);
+        assert_eq!(parse_loadavg(""), None);
+    }
+}
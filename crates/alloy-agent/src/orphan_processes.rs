@@ -0,0 +1,462 @@
+use std::path::{Path, PathBuf};
+
+use crate::agent_audit::AgentAudit;
+use crate::process_manager::ProcessManager;
+use crate::process_manager_support::reattach_enabled;
+
+#[derive(Debug, serde::Deserialize)]
+struct RunJsonForCleanup {
+    process_id: Option<String>,
+    pid: Option<u32>,
+    pgid: Option<i32>,
+    exec: Option<String>,
+    args: Option<Vec<String>>,
+    cwd: Option<String>,
+    template_id: Option<String>,
+    container_name: Option<String>,
+    container_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OrphanAction {
+    Reattach,
+    Kill,
+}
+
+fn canonicalize_best_effort(p: &Path) -> PathBuf {
+    std::fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf())
+}
+
+fn parse_cmdline(bytes: Vec<u8>) -> Vec<String> {
+    bytes
+        .split(|b| *b == 0)
+        .filter(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).to_string())
+        .collect()
+}
+
+fn cmdline_contains_all(cmdline: &[String], args: &[String]) -> bool {
+    args.iter().all(|a| cmdline.iter().any(|c| c == a))
+}
+
+fn docker_no_such_container(stderr: &str) -> bool {
+    let msg = stderr.to_ascii_lowercase();
+    msg.contains("no such container") || msg.contains("no such object")
+}
+
+/// Whether `<proc_root>/<pid>` is still the process `run` launched: same cwd,
+/// a cmdline containing every recorded arg and, for absolute execs, same exe.
+fn live_process_matches(proc_root: &Path, pid: u32, run: &RunJsonForCleanup) -> bool {
+    let proc_dir = proc_root.join(pid.to_string());
+    if !proc_dir.exists() {
+        return false;
+    }
+
+    let Some(cwd_str) = run.cwd.as_deref() else {
+        return false;
+    };
+    let run_cwd = canonicalize_best_effort(Path::new(cwd_str));
+    let Ok(proc_cwd) = std::fs::read_link(proc_dir.join("cwd")) else {
+        return false;
+    };
+    if canonicalize_best_effort(&proc_cwd) != run_cwd {
+        return false;
+    }
+
+    let cmdline = std::fs::read(proc_dir.join("cmdline"))
+        .ok()
+        .map(parse_cmdline)
+        .unwrap_or_default();
+    let args = run.args.as_deref().unwrap_or(&[]);
+    if !args.is_empty() && !cmdline_contains_all(&cmdline, args) {
+        return false;
+    }
+
+    if let Some(exec) = run.exec.as_deref()
+        && Path::new(exec).is_absolute()
+    {
+        let Ok(exe) = std::fs::read_link(proc_dir.join("exe")) else {
+            return false;
+        };
+        if canonicalize_best_effort(&exe) != canonicalize_best_effort(Path::new(exec)) {
+            return false;
+        }
+    }
+    true
+}
+
+/// The container recorded in run.json, preferring its id over its name.
+fn container_ref(run: &RunJsonForCleanup) -> Option<&str> {
+    run.container_id
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+        .or_else(|| {
+            run.container_name
+                .as_deref()
+                .filter(|s| !s.trim().is_empty())
+        })
+}
+
+/// The container to re-adopt when the previous agent handed `process_id` off.
+fn handoff_container<'a>(
+    run: &'a RunJsonForCleanup,
+    process_id: &str,
+    handoff: &[String],
+) -> Option<&'a str> {
+    if !handoff.iter().any(|id| id == process_id) {
+        return None;
+    }
+    container_ref(run)
+}
+
+/// What to do with the process recorded in `run_dir/run.json`; `None` when
+/// it is gone or the pid now belongs to something else. Only native servers
+/// of instances that still exist are kept; ad-hoc processes and servers whose
+/// instance config was removed are orphans.
+fn orphan_action(
+    proc_root: &Path,
+    run_dir: &Path,
+    run: &RunJsonForCleanup,
+    reattach: bool,
+) -> Option<OrphanAction> {
+    let pid = run.pid?;
+    if !live_process_matches(proc_root, pid, run) {
+        return None;
+    }
+    let sandboxed = run.container_name.is_some() || run.container_id.is_some();
+    let kept = run_dir.join("instance.json").is_file();
+    if reattach && !sandboxed && kept {
+        Some(OrphanAction::Reattach)
+    } else {
+        Some(OrphanAction::Kill)
+    }
+}
+
+async fn kill_orphan(audit: &AgentAudit, pid: u32, pgid: i32, process_id: &str, label: &str) {
+    let proc_dir = PathBuf::from("/proc").join(pid.to_string());
+    tracing::warn!(pid, pgid, process_id = %process_id, template_id = %label, "found orphaned child process; terminating");
+
+    unsafe {
+        libc::kill(-pgid, libc::SIGTERM);
+    }
+    audit.record(
+        "orphan_cleanup",
+        process_id,
+        label,
+        format!("sigterm pid {pid} pgid {pgid}"),
+    );
+
+    let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(2);
+    while tokio::time::Instant::now() < deadline {
+        if !proc_dir.exists() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+
+    if proc_dir.exists() {
+        tracing::warn!(pid, pgid, process_id = %process_id, template_id = %label, "orphan still alive; sending SIGKILL");
+        unsafe {
+            libc::kill(-pgid, libc::SIGKILL);
+        }
+        audit.record(
+            "orphan_cleanup",
+            process_id,
+            label,
+            format!("sigkill pid {pid} pgid {pgid}"),
+        );
+    }
+}
+
+/// Deals with servers left behind by a previous agent: reattaches them to
+/// `manager` when `ALLOY_REATTACH` is on, otherwise terminates them.
+pub(crate) async fn reconcile(manager: &ProcessManager) {
+    let docker_available = std::process::Command::new("docker")
+        .env_remove("DOCKER_API_VERSION")
+        .arg("version")
+        .arg("--format")
+        .arg("{{.Server.Version}}")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false);
+
+    // Containers handed off by a previous agent during an update are re-adopted below.
+    let handoff = crate::update_drain::take_handoff();
+    let audit = manager.audit();
+    let reattach = reattach_enabled();
+
+    let data_root = crate::minecraft::data_root();
+    let bases = [data_root.join("instances"), data_root.join("processes")];
+
+    for base in bases {
+        let mut rd = match tokio::fs::read_dir(&base).await {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        while let Ok(Some(de)) = rd.next_entry().await {
+            let path = de.path();
+            let run_path = path.join("run.json");
+            let raw = match tokio::fs::read(&run_path).await {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            let run = match serde_json::from_slice::<RunJsonForCleanup>(&raw) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            let run_process_id = run
+                .process_id
+                .clone()
+                .unwrap_or_else(|| de.file_name().to_string_lossy().to_string());
+            let label = run
+                .template_id
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string());
+
+            // Containers handed off during an update keep running under this agent.
+            if docker_available
+                && let Some(container_ref) = handoff_container(&run, &run_process_id, &handoff)
+            {
+                match manager
+                    .reattach_container(&run_process_id, &label, container_ref, &path)
+                    .await
+                {
+                    Ok(()) => {
+                        tracing::info!(
+                            process_id = %run_process_id,
+                            template_id = %label,
+                            container = %container_ref,
+                            "re-adopted sandbox container after update"
+                        );
+                        continue;
+                    }
+                    Err(err) => {
+                        tracing::warn!(
+                            process_id = %run_process_id,
+                            template_id = %label,
+                            container = %container_ref,
+                            err = %err,
+                            "failed to re-adopt handed-off container; cleaning it up"
+                        );
+                    }
+                }
+            }
+
+            if docker_available && let Some(container_ref) = container_ref(&run) {
+                match std::process::Command::new("docker")
+                    .env_remove("DOCKER_API_VERSION")
+                    .arg("rm")
+                    .arg("-f")
+                    .arg(container_ref)
+                    .output()
+                {
+                    Ok(output) if output.status.success() => {
+                        tracing::warn!(
+                            process_id = %run_process_id,
+                            template_id = %label,
+                            container = %container_ref,
+                            "removed orphaned sandbox container"
+                        );
+                        audit.record(
+                            "orphan_cleanup",
+                            &run_process_id,
+                            &label,
+                            format!("docker rm -f {container_ref}"),
+                        );
+                    }
+                    Ok(output) => {
+                        let stderr = String::from_utf8_lossy(&output.stderr);
+                        if !docker_no_such_container(&stderr) {
+                            tracing::warn!(
+                                process_id = %run_process_id,
+                                template_id = %label,
+                                container = %container_ref,
+                                err = %stderr.trim(),
+                                "failed to cleanup orphaned sandbox container"
+                            );
+                        }
+                    }
+                    Err(err) => {
+                        tracing::warn!(
+                            process_id = %run_process_id,
+                            template_id = %label,
+                            container = %container_ref,
+                            err = %err,
+                            "failed to execute docker container cleanup"
+                        );
+                    }
+                }
+            }
+
+            let Some(action) = orphan_action(Path::new("/proc"), &path, &run, reattach) else {
+                continue;
+            };
+            let Some(pid) = run.pid else { continue };
+            let pgid = run.pgid.unwrap_or(pid as i32);
+
+            if action == OrphanAction::Reattach {
+                match manager
+                    .reattach(&run_process_id, &label, pid, pgid, &path)
+                    .await
+                {
+                    Ok(()) => {
+                        tracing::info!(pid, pgid, process_id = %run_process_id, template_id = %label, "reattached to running process");
+                        continue;
+                    }
+                    Err(err) => {
+                        tracing::warn!(pid, pgid, process_id = %run_process_id, template_id = %label, err = %err, "reattach failed");
+                    }
+                }
+            }
+            kill_orphan(&audit, pid, pgid, &run_process_id, &label).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OrphanAction, RunJsonForCleanup, handoff_container, orphan_action};
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    struct Fixture {
+        root: PathBuf,
+        proc_root: PathBuf,
+        run_dir: PathBuf,
+        exec: PathBuf,
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root);
+        }
+    }
+
+    // A fake `/proc/4242` whose cwd, cmdline and exe match a server launched
+    // from `run_dir`.
+    fn fixture(test_name: &str) -> Fixture {
+        let root = std::env::temp_dir().join(format!(
+            "alloy-agent-{test_name}-{}-{}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        ));
+        let proc_root = root.join("proc");
+        let run_dir = root.join("instances").join("inst-1");
+        let exec = root.join("bin").join("java");
+        std::fs::create_dir_all(&run_dir).unwrap();
+        std::fs::create_dir_all(exec.parent().unwrap()).unwrap();
+        std::fs::write(&exec, b"").unwrap();
+        std::fs::write(run_dir.join("instance.json"), b"{}").unwrap();
+
+        let pid_dir = proc_root.join("4242");
+        std::fs::create_dir_all(&pid_dir).unwrap();
+        std::os::unix::fs::symlink(&run_dir, pid_dir.join("cwd")).unwrap();
+        std::os::unix::fs::symlink(&exec, pid_dir.join("exe")).unwrap();
+        std::fs::write(pid_dir.join("cmdline"), b"java\0-jar\0server.jar\0nogui\0").unwrap();
+
+        Fixture {
+            root,
+            proc_root,
+            run_dir,
+            exec,
+        }
+    }
+
+    fn run(f: &Fixture, args: &[&str]) -> RunJsonForCleanup {
+        RunJsonForCleanup {
+            process_id: Some("inst-1".to_string()),
+            pid: Some(4242),
+            pgid: Some(4242),
+            exec: Some(f.exec.display().to_string()),
+            args: Some(args.iter().map(|s| s.to_string()).collect()),
+            cwd: Some(f.run_dir.display().to_string()),
+            template_id: Some("minecraft:vanilla".to_string()),
+            container_name: None,
+            container_id: None,
+        }
+    }
+
+    #[test]
+    fn a_verified_live_instance_is_reattached_only_when_enabled() {
+        let f = fixture("orphan-reattach");
+        let r = run(&f, &["-jar", "server.jar"]);
+        assert_eq!(
+            orphan_action(&f.proc_root, &f.run_dir, &r, true),
+            Some(OrphanAction::Reattach)
+        );
+        assert_eq!(
+            orphan_action(&f.proc_root, &f.run_dir, &r, false),
+            Some(OrphanAction::Kill)
+        );
+
+        // The instance was deleted but its data kept: nothing to reattach to.
+        std::fs::remove_file(f.run_dir.join("instance.json")).unwrap();
+        assert_eq!(
+            orphan_action(&f.proc_root, &f.run_dir, &r, true),
+            Some(OrphanAction::Kill)
+        );
+    }
+
+    #[test]
+    fn pids_that_no_longer_match_the_run_are_left_alone() {
+        let f = fixture("orphan-mismatch");
+
+        let other_args = run(&f, &["-jar", "other.jar"]);
+        assert_eq!(
+            orphan_action(&f.proc_root, &f.run_dir, &other_args, true),
+            None
+        );
+
+        let mut other_cwd = run(&f, &["-jar", "server.jar"]);
+        other_cwd.cwd = Some(f.proc_root.display().to_string());
+        assert_eq!(
+            orphan_action(&f.proc_root, &f.run_dir, &other_cwd, true),
+            None
+        );
+
+        let mut other_exe = run(&f, &["-jar", "server.jar"]);
+        other_exe.exec = Some("/usr/bin/true".to_string());
+        assert_eq!(
+            orphan_action(&f.proc_root, &f.run_dir, &other_exe, true),
+            None
+        );
+
+        let mut gone = run(&f, &["-jar", "server.jar"]);
+        gone.pid = Some(4243);
+        assert_eq!(orphan_action(&f.proc_root, &f.run_dir, &gone, true), None);
+
+        let mut sandboxed = run(&f, &["-jar", "server.jar"]);
+        sandboxed.container_name = Some("alloy-inst-1".to_string());
+        assert_eq!(
+            orphan_action(&f.proc_root, &f.run_dir, &sandboxed, true),
+            Some(OrphanAction::Kill)
+        );
+    }
+
+    #[test]
+    fn handed_off_containers_are_picked_for_readoption() {
+        let f = fixture("orphan-handoff");
+        let mut r = run(&f, &["-jar", "server.jar"]);
+        let handoff = vec!["inst-1".to_string()];
+
+        // Native runs have no container to adopt.
+        assert_eq!(handoff_container(&r, "inst-1", &handoff), None);
+
+        r.container_id = Some(" ".to_string());
+        r.container_name = Some("alloy-inst-1".to_string());
+        assert_eq!(
+            handoff_container(&r, "inst-1", &handoff),
+            Some("alloy-inst-1")
+        );
+        r.container_id = Some("0123abcd".to_string());
+        assert_eq!(handoff_container(&r, "inst-1", &handoff), Some("0123abcd"));
+
+        assert_eq!(handoff_container(&r, "inst-1", &[]), None);
+    }
+}
@@ -1,50 +1,183 @@
 use std::{
+    collections::BTreeMap,
     io::ErrorKind,
     net::{TcpListener, UdpSocket},
+    sync::{Mutex, OnceLock},
 };
 
 use anyhow::Context;
 
-pub fn allocate_tcp_port(preferred: u16) -> anyhow::Result<u16> {
-    if preferred != 0 {
-        // Validate availability.
-        match TcpListener::bind(("0.0.0.0", preferred)) {
-            Ok(l) => {
-                l.set_nonblocking(true).ok();
-            }
+// Ephemeral picks retried when the OS hands back a reserved port.
+const AUTO_ATTEMPTS: usize = 32;
+
+/// Ports handed to starts whose server is not confirmed listening yet, keyed
+/// to the owning process id. Binding and releasing a probe socket leaves a
+/// window where a concurrent start could pick the same port; this closes it.
+fn reserved() -> &'static Mutex<BTreeMap<u16, String>> {
+    static RESERVED: OnceLock<Mutex<BTreeMap<u16, String>>> = OnceLock::new();
+    RESERVED.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+fn bind_tcp(port: u16) -> std::io::Result<u16> {
+    let l = TcpListener::bind(("0.0.0.0", port))?;
+    l.set_nonblocking(true).ok();
+    Ok(l.local_addr()?.port())
+}
+
+fn bind_udp(port: u16) -> std::io::Result<u16> {
+    let s = UdpSocket::bind(("0.0.0.0", port))?;
+    s.set_nonblocking(true).ok();
+    Ok(s.local_addr()?.port())
+}
+
+/// Binds TCP, then UDP on the same number while the TCP listener is held.
+fn bind_dual(port: u16) -> std::io::Result<u16> {
+    let tcp = TcpListener::bind(("0.0.0.0", port))?;
+    let port = tcp.local_addr()?.port();
+    UdpSocket::bind(("0.0.0.0", port))?;
+    Ok(port)
+}
+
+/// Picks `preferred` (or a free port when 0), skipping ports other owners
+/// reserved, and reserves the result for `owner` when given.
+fn allocate(
+    owner: Option<&str>,
+    preferred: u16,
+    bind: fn(u16) -> std::io::Result<u16>,
+) -> anyhow::Result<u16> {
+    let mut reserved = reserved().lock().unwrap_or_else(|e| e.into_inner());
+    let port = if preferred != 0 {
+        if reserved
+            .get(&preferred)
+            .is_some_and(|o| Some(o.as_str()) != owner)
+        {
+            anyhow::bail!("port already in use: {preferred}");
+        }
+        match bind(preferred) {
+            Ok(_) => preferred,
             Err(e) if e.kind() == ErrorKind::AddrInUse => {
                 anyhow::bail!("port already in use: {preferred}");
             }
-            Err(e) => {
-                return Err(e).context(format!("bind port {preferred}"));
+            Err(e) => return Err(e).context(format!("bind port {preferred}")),
+        }
+    } else {
+        // Ask OS for an ephemeral port.
+        let mut picked = None;
+        for _ in 0..AUTO_ATTEMPTS {
+            // The OS only picks a port free for the first protocol a dual bind tries.
+            let port = match bind(0) {
+                Ok(port) => port,
+                Err(e) if e.kind() == ErrorKind::AddrInUse => continue,
+                Err(e) => return Err(e.into()),
+            };
+            // Own reservations count too, so one start gets distinct ports.
+            if !reserved.contains_key(&port) {
+                picked = Some(port);
+                break;
             }
         }
-        return Ok(preferred);
-    }
+        picked.context("no free port outside the reserved set")?
+    };
 
-    // Ask OS for an ephemeral port.
-    let listener = TcpListener::bind(("0.0.0.0", 0))?;
-    let port = listener.local_addr()?.port();
+    if let Some(owner) = owner {
+        reserved.insert(port, owner.to_string());
+    }
     Ok(port)
 }
 
+pub fn allocate_tcp_port(preferred: u16) -> anyhow::Result<u16> {
+    allocate(None, preferred, bind_tcp)
+}
+
 pub fn allocate_udp_port(preferred: u16) -> anyhow::Result<u16> {
-    if preferred != 0 {
-        match UdpSocket::bind(("0.0.0.0", preferred)) {
-            Ok(s) => {
-                s.set_nonblocking(true).ok();
-            }
-            Err(e) if e.kind() == ErrorKind::AddrInUse => {
-                anyhow::bail!("port already in use: {preferred}");
-            }
-            Err(e) => {
-                return Err(e).context(format!("bind port {preferred}"));
-            }
+    allocate(None, preferred, bind_udp)
+}
+
+/// A port free on both TCP and UDP, for servers that listen on both (e.g. a
+/// UDP query protocol next to the TCP game port).
+pub fn allocate_dual_port(preferred: u16) -> anyhow::Result<u16> {
+    allocate(None, preferred, bind_dual)
+}
+
+/// Like [`allocate_tcp_port`], but keeps the port reserved for `owner` until
+/// [`release`] so concurrent starts cannot pick it.
+pub fn reserve_tcp_port(owner: &str, preferred: u16) -> anyhow::Result<u16> {
+    allocate(Some(owner), preferred, bind_tcp)
+}
+
+pub fn reserve_udp_port(owner: &str, preferred: u16) -> anyhow::Result<u16> {
+    allocate(Some(owner), preferred, bind_udp)
+}
+
+pub fn reserve_dual_port(owner: &str, preferred: u16) -> anyhow::Result<u16> {
+    allocate(Some(owner), preferred, bind_dual)
+}
+
+/// Drops every reservation held by `owner`; call once its server is
+/// listening or its start failed.
+pub fn release(owner: &str) {
+    reserved()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|_, o| o != owner);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use std::net::{TcpListener, UdpSocket};
+
+    use super::{
+        allocate_dual_port, allocate_tcp_port, release, reserve_tcp_port, reserve_udp_port,
+        reserved,
+    };
+
+    #[test]
+    fn concurrent_reservations_are_unique_until_released() {
+        let handles: Vec<_> = (0..64)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let owner = format!("port-alloc-test-{i}");
+                    if i % 2 == 0 {
+                        reserve_tcp_port(&owner, 0).unwrap()
+                    } else {
+                        reserve_udp_port(&owner, 0).unwrap()
+                    }
+                })
+            })
+            .collect();
+        let ports: Vec<u16> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        let unique: BTreeSet<u16> = ports.iter().copied().collect();
+        assert_eq!(unique.len(), ports.len(), "{ports:?}");
+
+        // Another start may not take a reserved port; its owner may.
+        let port = ports[0];
+        assert!(reserve_tcp_port("port-alloc-test-other", port).is_err());
+        assert!(allocate_tcp_port(port).is_err());
+        assert_eq!(reserve_tcp_port("port-alloc-test-0", port).unwrap(), port);
+
+        for i in 0..64 {
+            release(&format!("port-alloc-test-{i}"));
         }
-        return Ok(preferred);
+        let reserved = reserved().lock().unwrap();
+        assert!(ports.iter().all(|p| !reserved.contains_key(p)));
     }
 
-    let sock = UdpSocket::bind(("0.0.0.0", 0))?;
-    let port = sock.local_addr()?.port();
-    Ok(port)
+    #[test]
+    fn dual_port_is_free_on_both_protocols() {
+        for _ in 0..8 {
+            let port = allocate_dual_port(0).unwrap();
+            let tcp = TcpListener::bind(("0.0.0.0", port)).unwrap();
+            let udp = UdpSocket::bind(("0.0.0.0", port)).unwrap();
+
+            // Taken on either protocol means the port is unusable.
+            drop(tcp);
+            assert!(allocate_dual_port(port).is_err());
+            drop(udp);
+            let tcp = TcpListener::bind(("0.0.0.0", port)).unwrap();
+            assert!(allocate_dual_port(port).is_err());
+            drop(tcp);
+        }
+    }
 }
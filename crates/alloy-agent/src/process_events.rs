@@ -0,0 +1,87 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use alloy_process::{ProcessResources, ProcessState};
+use tokio::sync::broadcast;
+
+use crate::process_manager_support::env_usize;
+
+/// Coarse step of a start, from the request until the process is Running or Failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartPhase {
+    Preparing,
+    Downloading,
+    Installing,
+    Launching,
+    WaitingForReady,
+    Running,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+pub enum ProcessEventKind {
+    StateChanged(ProcessState),
+    ResourcesSampled(ProcessResources),
+    RestartScheduled {
+        attempt: u32,
+        delay_ms: u64,
+    },
+    Exited {
+        state: ProcessState,
+        exit_code: Option<i32>,
+    },
+    /// `percent` is only set while downloading.
+    StartProgress {
+        phase: StartPhase,
+        percent: Option<u32>,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessEvent {
+    pub process_id: String,
+    pub kind: ProcessEventKind,
+    pub at_unix_ms: u64,
+}
+
+pub fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn event_buffer() -> usize {
+    env_usize("ALLOY_PROCESS_EVENT_BUFFER")
+        .map(|v| v.clamp(16, 65_536))
+        .unwrap_or(1024)
+}
+
+/// Bounded fan-out of lifecycle events. Sending never waits: a receiver that
+/// falls more than the buffer behind gets `Lagged` and skips ahead.
+#[derive(Debug, Clone)]
+pub struct ProcessEvents {
+    tx: broadcast::Sender<ProcessEvent>,
+}
+
+impl Default for ProcessEvents {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(event_buffer());
+        Self { tx }
+    }
+}
+
+impl ProcessEvents {
+    pub fn emit(&self, process_id: &str, kind: ProcessEventKind) {
+        // Err only means nobody is subscribed right now.
+        let _ = self.tx.send(ProcessEvent {
+            process_id: process_id.to_string(),
+            kind,
+            at_unix_ms: now_unix_ms(),
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ProcessEvent> {
+        self.tx.subscribe()
+    }
+}
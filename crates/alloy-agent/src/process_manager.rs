@@ -1,20 +1,28 @@
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, OnceLock},
     time::Duration,
 };
 
-use alloy_process::{ProcessId, ProcessState, ProcessStatus, ProcessTemplateId};
+use alloy_process::{
+    FailCategory, FailureReason, PlayerCount, ProcessId, ProcessState, ProcessStatus,
+    ProcessTemplateId, ProgressInfo,
+};
 use anyhow::Context;
 use serde::Serialize;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
     process::{ChildStdin, Command},
     sync::Mutex,
+    sync::broadcast,
     sync::mpsc,
 };
 
+use crate::agent_audit::AgentAudit;
+use crate::cache_gc;
+use crate::config_overrides;
+use crate::custom_templates;
 use crate::dst;
 use crate::dst_download;
 use crate::minecraft;
@@ -23,25 +31,51 @@ use crate::minecraft_download;
 use crate::minecraft_import;
 use crate::minecraft_launch;
 use crate::minecraft_modrinth;
+use crate::minecraft_paper;
+use crate::minecraft_ping::{self, PingError, ServerStatus};
 use crate::port_alloc;
+use crate::process_events::{ProcessEvent, ProcessEventKind, ProcessEvents, StartPhase};
 use crate::sandbox;
-use crate::templates;
+use crate::steamcmd_login::SteamcmdAuth;
+use crate::templates::{self, GracefulStopSpec};
 use crate::terraria;
 use crate::terraria_download;
+use crate::terraria_tmodloader;
 use crate::process_manager_support::{
     RestartConfig,
     RestartPolicy,
+    aggregate_pgid_resources,
+    cache_max_bytes,
+    classify_failure,
     compute_backoff_ms,
+    corruption_hint,
+    detects_port_in_use,
     early_exit_threshold,
     env_u64,
+    fail_category,
     format_error_chain,
+    frpc_restart_config,
+    frpc_verify_enabled,
+    install_output_enabled,
+    LineAdmit,
+    LineRateLimiter,
+    LogCompression,
+    log_compression,
     log_file_limits,
     log_max_lines,
+    log_total_max_bytes,
+    log_rate_limit_lines_per_sec,
+    log_ready_timeout,
+    minecraft_status_poll_interval,
+    oneshot_forget_grace,
+    online_cpus,
     parse_restart_config,
+    pgid_members,
+    port_in_use_message,
     port_probe_timeout,
     read_proc_cpu_ticks,
     read_proc_rss_bytes,
-    resource_sample_interval,
+    SampleHistory, resource_history_samples, resource_sample_interval,
     ticks_per_sec,
 };
 
@@ -67,18 +101,13 @@ async fn read_proc_io_bytes(_pid: u32) -> Option<(u64, u64)> {
     None
 }
 
-fn cpu_percent_x100(
-    prev_ticks: u64,
-    prev_at: tokio::time::Instant,
-    ticks: u64,
-    now: tokio::time::Instant,
-) -> u32 {
-    let dt = now.duration_since(prev_at).as_secs_f64();
-    if dt <= 0.0 {
+/// CPU use over `dt_secs` in 1/100 of a percent of one core, so a JVM busy
+/// on 4 cores reports 40000.
+fn cpu_percent_x100(delta_ticks: u64, ticks_per_sec: u64, dt_secs: f64) -> u32 {
+    if dt_secs <= 0.0 || ticks_per_sec == 0 {
         return 0;
     }
-    let delta_ticks = ticks.saturating_sub(prev_ticks) as f64;
-    let cpu = (delta_ticks / ticks_per_sec() as f64) / dt * 100.0;
+    let cpu = (delta_ticks as f64 / ticks_per_sec as f64) / dt_secs * 100.0;
     // 1/100 of a percent.
     let x100 = (cpu * 100.0).round();
     if x100.is_finite() {
@@ -88,16 +117,22 @@ fn cpu_percent_x100(
     }
 }
 
+/// `raw_x100` as a share of the whole machine (0-10000). Capped because a
+/// reaped child's ticks all land in the sample where it exits.
+fn normalize_cpu_x100(raw_x100: u32, cores: u32) -> u32 {
+    (raw_x100 / cores.max(1)).min(10_000)
+}
+
 const DEFAULT_MIN_FREE_SPACE_BYTES: u64 = 1024 * 1024 * 1024; // 1 GiB
 
-fn min_free_space_bytes() -> u64 {
+pub(crate) fn min_free_space_bytes() -> u64 {
     env_u64("ALLOY_MIN_FREE_SPACE_BYTES")
         .map(|v| v.clamp(0, 1024_u64 * 1024 * 1024 * 1024))
         .unwrap_or(DEFAULT_MIN_FREE_SPACE_BYTES)
 }
 
 #[cfg(unix)]
-fn free_bytes(p: &Path) -> Option<u64> {
+pub(crate) fn free_bytes(p: &Path) -> Option<u64> {
     use std::ffi::CString;
     use std::os::unix::ffi::OsStrExt;
 
@@ -111,11 +146,11 @@ fn free_bytes(p: &Path) -> Option<u64> {
 }
 
 #[cfg(not(unix))]
-fn free_bytes(_p: &Path) -> Option<u64> {
+pub(crate) fn free_bytes(_p: &Path) -> Option<u64> {
     None
 }
 
-fn ensure_min_free_space(path: &Path) -> anyhow::Result<()> {
+pub(crate) fn ensure_min_free_space(path: &Path) -> anyhow::Result<()> {
     let min = min_free_space_bytes();
     if min == 0 {
         return Ok(());
@@ -152,6 +187,16 @@ fn check_ldd_missing(path: &Path) -> anyhow::Result<Vec<String>> {
     Ok(missing)
 }
 
+const MAX_CONSOLE_LINE_BYTES: usize = 4096;
+// Most output lines handed to the log sink in one batch.
+const OUTPUT_BATCH_MAX: usize = 512;
+
+// After a stop's SIGKILL, how long a restart waits for the exit to be recorded.
+const RESTART_EXIT_GRACE: Duration = Duration::from_secs(10);
+
+// A reattached server is not our child, so its exit is noticed by polling.
+const REATTACH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
 fn graceful_term_grace() -> Duration {
     Duration::from_secs(
         env_u64("ALLOY_GRACEFUL_TERM_GRACE_SEC")
@@ -201,17 +246,72 @@ fn parse_java_major_from_version_line(first_line: &str) -> anyhow::Result<u32> {
     Ok(major)
 }
 
-fn detect_java_major() -> anyhow::Result<u32> {
-    // Use the runtime `java` in PATH. We vendor Java 21 in the Docker image,
-    // but this also supports local dev installs.
-    let out = std::process::Command::new("java")
+// `java -version` can be preceded by JVM notices such as
+// "Picked up JAVA_TOOL_OPTIONS: ..."; skip to the version banner.
+fn java_version_line(stderr: &str) -> &str {
+    stderr
+        .lines()
+        .find(|l| l.contains(" version ") || l.starts_with("openjdk "))
+        .or_else(|| stderr.lines().next())
+        .unwrap_or_default()
+}
+
+/// The java binary an instance runs: its `java_path` param, else
+/// `$JAVA_HOME/bin/java`, else `java` from PATH (Java 21 in the Docker image).
+pub fn resolve_java_path(params: &BTreeMap<String, String>) -> PathBuf {
+    if let Some(path) = params
+        .get("java_path")
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+    {
+        return PathBuf::from(path);
+    }
+    if let Some(home) = std::env::var_os("JAVA_HOME").filter(|v| !v.is_empty()) {
+        let java = PathBuf::from(home).join("bin").join("java");
+        if java.is_file() {
+            return java;
+        }
+    }
+    PathBuf::from("java")
+}
+
+/// Runs `<java> -version` once per binary and caches the major version.
+pub fn detect_java_major(java: &Path) -> anyhow::Result<u32> {
+    static CACHE: OnceLock<std::sync::Mutex<HashMap<PathBuf, u32>>> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| std::sync::Mutex::new(HashMap::new()));
+    // Keyed by the canonical path so repointing a symlinked default is noticed.
+    let key = std::fs::canonicalize(java).unwrap_or_else(|_| java.to_path_buf());
+    if let Some(major) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return Ok(*major);
+    }
+
+    let out = std::process::Command::new(java)
         .arg("-version")
         .output()
-        .context("run `java -version`")?;
+        .with_context(|| format!("run `{} -version`", java.display()))?;
     let text = String::from_utf8_lossy(&out.stderr);
-    let first = text.lines().next().unwrap_or_default();
+    let major = parse_java_major_from_version_line(java_version_line(&text))
+        .with_context(|| format!("probe {}", java.display()))?;
+
+    cache
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, major);
+    Ok(major)
+}
 
-    parse_java_major_from_version_line(first)
+fn java_major_mismatch(java: &Path, have: u32, need: u32, version_id: &str) -> anyhow::Error {
+    crate::error_payload::anyhow(
+        "java_major_mismatch",
+        format!(
+            "Need Java {need} for Minecraft {version_id}, but {} is Java {have}.",
+            java.display()
+        ),
+        None,
+        Some(format!(
+            "Install Java {need} (Temurin recommended) and point java_path at it, or use the Alloy agent Docker image."
+        )),
+    )
 }
 
 fn materialize_minecraft_server_jar(instance_jar: &Path, cached_jar: &Path) -> anyhow::Result<()> {
@@ -258,13 +358,27 @@ fn materialize_minecraft_server_jar(instance_jar: &Path, cached_jar: &Path) -> a
 #[cfg(test)]
 mod tests {
     use super::{
-        materialize_minecraft_server_jar, parse_java_major_from_version_line, patch_frp_config,
+        Companion, FailCategory, FailureReason, FileLogWriter, FrpConfigFormat, Frpc, FrpcLaunch,
+        FrpcStart, GracefulStopSpec, LaunchContext, LineAdmit, LineRateLimiter, LogBuffer,
+        LogCompression, LogFilter, LogLine, LogReadyMatcher, LogSink, LogStream, PortProbe,
+        ProcessEntry, ProcessEventKind, ProcessManager, ProcessState, ProcessTemplateId,
+        ProgressInfo, RestartConfig, RestartPolicy, RunInfo, RunInfoView, SampleHistory,
+        StartPhase, classify_failure, cpu_percent_x100, download_reporter, fail_category,
+        java_version_line, materialize_minecraft_server_jar, normalize_cpu_x100,
+        parse_java_major_from_version_line, parse_restart_config, patch_frp_config,
+        port_in_use_message, preview_instance_delete, pump_output, read_run_json,
+        set_entry_message, spawn_frpc, start_frpc_sidecar, supervise_frpc,
+        wait_for_local_udp_ready, write_run_json,
     };
+    use crate::process_manager_support::{corruption_hint, world_corruption_hint};
     use std::{
-        path::PathBuf,
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        sync::Arc,
         sync::atomic::{AtomicU64, Ordering},
-        time::{SystemTime, UNIX_EPOCH},
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     };
+    use tokio::sync::Mutex;
 
     fn temp_dir_for(test_name: &str) -> PathBuf {
         static COUNTER: AtomicU64 = AtomicU64::new(1);
@@ -281,6 +395,135 @@ mod tests {
         dir
     }
 
+    #[test]
+    fn fail_category_follows_error_payload_code() {
+        let disk = crate::error_payload::anyhow("insufficient_disk", "disk full", None, None)
+            .context("prepare instance");
+        assert_eq!(fail_category(&disk), FailCategory::Disk);
+
+        let mut fields = std::collections::BTreeMap::new();
+        fields.insert("master_port".to_string(), "port already in use".to_string());
+        let port =
+            crate::error_payload::anyhow("invalid_param", "invalid port", Some(fields), None);
+        assert_eq!(fail_category(&port), FailCategory::Port);
+
+        let other = crate::error_payload::anyhow("invalid_param", "bad memory", None, None);
+        assert_eq!(fail_category(&other), FailCategory::Unknown);
+        assert_eq!(
+            fail_category(&anyhow::anyhow!("boom")),
+            FailCategory::Unknown
+        );
+    }
+
+    #[test]
+    fn line_rate_limiter_drops_and_reports_next_window() {
+        let mut l = LineRateLimiter::new(2);
+        let start = Instant::now();
+        assert_eq!(l.admit(start), LineAdmit::Pass);
+        assert_eq!(l.admit(start), LineAdmit::Pass);
+        assert_eq!(l.admit(start), LineAdmit::Drop);
+        assert_eq!(l.admit(start), LineAdmit::Drop);
+
+        let next = start + Duration::from_secs(1);
+        assert_eq!(l.admit(next), LineAdmit::PassAfterDrops(2));
+        assert_eq!(l.admit(next), LineAdmit::Pass);
+        assert_eq!(l.admit(next), LineAdmit::Drop);
+        assert_eq!(l.take_dropped(), 1);
+    }
+
+    #[tokio::test]
+    async fn flooding_output_is_batched_and_marked() {
+        let input = (0..10_000)
+            .map(|i| format!("spam {i}\n"))
+            .collect::<String>();
+        let buffer = Arc::new(Mutex::new(LogBuffer::default()));
+        let sink = LogSink {
+            buffer: buffer.clone(),
+            file_tx: None,
+            limiter: Some(Arc::new(std::sync::Mutex::new(LineRateLimiter::new(5000)))),
+        };
+        pump_output(input.as_bytes(), sink, "[stdout]").await;
+
+        let buf = buffer.lock().await;
+        assert!(buf.lines.len() <= buf.max_lines);
+        let seqs = buf.lines.iter().map(|l| l.seq).collect::<Vec<_>>();
+        assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+        assert!(
+            buf.lines
+                .iter()
+                .any(|l| l.text.starts_with("[alloy-agent] suppressed "))
+        );
+    }
+
+    #[test]
+    fn cpu_percent_is_per_core_and_normalized_to_the_machine() {
+        // 400 ticks at 100 ticks/s over 1s: four cores busy.
+        assert_eq!(cpu_percent_x100(400, 100, 1.0), 40_000);
+        assert_eq!(normalize_cpu_x100(40_000, 8), 5_000);
+        assert_eq!(normalize_cpu_x100(40_000, 4), 10_000);
+        // 50 ticks over 2s is a quarter of one core.
+        assert_eq!(cpu_percent_x100(50, 100, 2.0), 2_500);
+        assert_eq!(normalize_cpu_x100(2_500, 1), 2_500);
+        // A child's whole lifetime arriving in one sample stays within 100%.
+        assert_eq!(normalize_cpu_x100(90_000, 2), 10_000);
+        assert_eq!(normalize_cpu_x100(1_234, 0), 1_234);
+        assert_eq!(cpu_percent_x100(400, 100, 0.0), 0);
+    }
+
+    #[test]
+    fn stat_cpu_ticks_include_reaped_children() {
+        let stat = "4242 (java (main)) S 1 4242 4242 0 -1 4194560 5000 0 0 0 \
+                    700 300 40 60 20 0 35 0 123456 0 0";
+        assert_eq!(
+            crate::process_manager_support::parse_stat_cpu_ticks(stat),
+            Some(700 + 300 + 40 + 60)
+        );
+        assert_eq!(
+            crate::process_manager_support::parse_stat_cpu_ticks("1 (x) S 1"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn pgid_members_match_the_process_group_only() {
+        use crate::process_manager_support::{parse_stat_pgid, pgid_members};
+
+        let root = temp_dir_for("fake-proc");
+        let write = |dir: &str, comm: &str, pgid: i32| {
+            let d = root.join(dir);
+            std::fs::create_dir_all(&d).unwrap();
+            std::fs::write(
+                d.join("stat"),
+                format!("{dir} ({comm}) S 1 {pgid} {pgid} 0 -1 0 0 0 0 0 10 5 0 0 20 0 1"),
+            )
+            .unwrap();
+        };
+        write("100", "java", 100);
+        write("101", "sh -c (start) 1", 100);
+        write("102", "frpc", 100);
+        write("200", "java", 200);
+        // Not a pid, and a process that exited before its stat was read.
+        write("self", "java", 100);
+        std::fs::create_dir_all(root.join("103")).unwrap();
+
+        assert_eq!(pgid_members(&root, 100).await, vec![100, 101, 102]);
+        assert_eq!(pgid_members(&root, 200).await, vec![200]);
+        assert!(pgid_members(&root, 300).await.is_empty());
+        assert_eq!(parse_stat_pgid("7 (a) b) R 1 77 77"), Some(77));
+        assert_eq!(parse_stat_pgid("7 (a) R"), None);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn sample_history_drops_oldest_past_cap() {
+        let mut h = SampleHistory::new(3);
+        for i in 0..5 {
+            h.push(i);
+        }
+        assert_eq!(h.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+    }
+
     #[test]
     fn parse_java_major_modern_openjdk() {
         let line = "openjdk version \"21.0.2\" 2024-01-16";
@@ -305,6 +548,31 @@ mod tests {
         assert_eq!(parse_java_major_from_version_line(line).unwrap(), 17);
     }
 
+    #[test]
+    fn parse_java_major_graalvm_banner() {
+        // Oracle GraalVM for JDK 21, then GraalVM CE 22.3 on Java 11.
+        let line = "java version \"21.0.1\" 2023-10-17 LTS";
+        assert_eq!(parse_java_major_from_version_line(line).unwrap(), 21);
+        let line = "openjdk version \"11.0.17\" 2022-10-18";
+        assert_eq!(parse_java_major_from_version_line(line).unwrap(), 11);
+    }
+
+    #[test]
+    fn parse_java_major_zulu_banner() {
+        let line = "openjdk version \"1.8.0_392\"";
+        assert_eq!(parse_java_major_from_version_line(line).unwrap(), 8);
+        let line = "openjdk version \"17.0.9\" 2023-10-17 LTS";
+        assert_eq!(parse_java_major_from_version_line(line).unwrap(), 17);
+    }
+
+    #[test]
+    fn java_version_line_skips_jvm_notices() {
+        let stderr = "Picked up JAVA_TOOL_OPTIONS: -Xss1m\n\
+                      openjdk version \"1.8.0_392\"\n\
+                      OpenJDK Runtime Environment (Zulu 8.74.0.17-CA-linux64)\n";
+        assert_eq!(java_version_line(stderr), "openjdk version \"1.8.0_392\"");
+    }
+
     #[test]
     fn parse_java_major_rejects_garbage() {
         let err = parse_java_major_from_version_line("not java").unwrap_err();
@@ -331,7 +599,7 @@ local_ip = 0.0.0.0
 local_port = 25565
 remote_port = 0
 "#;
-        let patched = patch_frp_config(raw, 25577);
+        let (patched, _) = patch_frp_config(raw, 25577);
         assert!(patched.contains("local_ip = 127.0.0.1"));
         assert!(patched.contains("local_port = 25577"));
         assert!(patched.contains("remote_port = 25577"));
@@ -349,7 +617,7 @@ type = tcp
 local_port = 25565
 remote_port = 0
 "#;
-        let patched = patch_frp_config(raw, 25577);
+        let (patched, _) = patch_frp_config(raw, 25577);
         assert!(patched.contains("remote_port = 30012"));
     }
 
@@ -366,7 +634,7 @@ remote_port = 0
     "remote_port": 0
   }
 }"#;
-        let patched = patch_frp_config(raw, 26666);
+        let (patched, _) = patch_frp_config(raw, 26666);
         assert!(patched.contains("[common]"));
         assert!(patched.contains("server_addr = frp.example.com"));
         assert!(patched.contains("[game]"));
@@ -386,12 +654,473 @@ proxies:
     local_port: 25565
     remote_port: 0
 "#;
-        let patched = patch_frp_config(raw, 27777);
+        let (patched, _) = patch_frp_config(raw, 27777);
         assert!(patched.contains("[game]"));
         assert!(patched.contains("local_port = 27777"));
         assert!(patched.contains("remote_port = 27777"));
     }
 
+    #[test]
+    fn patch_frp_ini_only_touches_the_primary_proxy() {
+        let raw = r#"[common]
+server_addr = frp.example.com
+server_port = 7000
+
+[rcon]
+type = tcp
+local_port = 25575
+remote_port = 35575
+
+[game]
+type = tcp
+local_port = 25565
+remote_port = 35565
+custom_domains = mc.example.com
+use_encryption = true
+"#;
+        let (patched, _) = patch_frp_config(raw, 25577);
+        assert!(patched.contains("[rcon]\ntype = tcp\nlocal_port = 25575\nremote_port = 35575\n"));
+        assert!(patched.contains(
+            "[game]\nlocal_ip = 127.0.0.1\ntype = tcp\nlocal_port = 25577\nremote_port = 35565\n\
+             custom_domains = mc.example.com\nuse_encryption = true\n"
+        ));
+        assert_eq!(patched.matches("local_ip").count(), 1);
+    }
+
+    #[test]
+    fn patch_frp_yaml_keeps_secondary_proxies_and_extra_fields() {
+        let raw = r#"
+common:
+  server_addr: frp.example.com
+  server_port: 7000
+proxies:
+  - name: query
+    type: udp
+    local_port: 25565
+    remote_port: 35566
+  - name: mc
+    local_port: 25565
+    remote_port: 35565
+  - name: web
+    type: http
+    local_port: 8123
+    custom_domains: [map.example.com, map2.example.com]
+    transport:
+      use_encryption: true
+"#;
+        let (patched, format) = patch_frp_config(raw, 25577);
+        assert_eq!(format, FrpConfigFormat::Ini);
+        assert!(patched.contains("server_addr = frp.example.com"));
+        assert!(patched.contains("[query]\nlocal_port = 25565\nremote_port = 35566\ntype = udp\n"));
+        // No proxy is named `game`, so the first tcp one (frp's default type) is patched.
+        assert!(patched.contains(
+            "[mc]\nlocal_ip = 127.0.0.1\nlocal_port = 25577\nremote_port = 35565\ntype = tcp\n"
+        ));
+        assert!(patched.contains("custom_domains = map.example.com,map2.example.com"));
+        assert!(patched.contains("use_encryption = true"));
+        assert!(patched.contains("local_port = 8123"));
+    }
+
+    #[test]
+    fn patch_frp_v2_toml_stays_toml_and_patches_the_game_proxy() {
+        let raw = r#"
+serverAddr = "frp.example.com"
+serverPort = 7000
+auth.token = "secret"
+
+[[proxies]]
+name = "rcon"
+type = "tcp"
+localPort = 25575
+remotePort = 35575
+
+[[proxies]]
+name = "game"
+type = "tcp"
+localIP = "0.0.0.0"
+localPort = 25565
+remotePort = 35565
+transport.useEncryption = true
+"#;
+        let (patched, format) = patch_frp_config(raw, 25577);
+        assert_eq!(format, FrpConfigFormat::Toml);
+
+        let v: toml::Value = patched.parse().unwrap();
+        assert_eq!(v["serverAddr"].as_str(), Some("frp.example.com"));
+        assert_eq!(v["auth"]["token"].as_str(), Some("secret"));
+        let proxies = v["proxies"].as_array().unwrap();
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(proxies[0]["localPort"].as_integer(), Some(25575));
+        assert_eq!(proxies[0]["remotePort"].as_integer(), Some(35575));
+        assert_eq!(proxies[1]["localIP"].as_str(), Some("127.0.0.1"));
+        assert_eq!(proxies[1]["localPort"].as_integer(), Some(25577));
+        assert_eq!(proxies[1]["remotePort"].as_integer(), Some(35565));
+        assert_eq!(
+            proxies[1]["transport"]["useEncryption"].as_bool(),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn delete_refuses_instance_dirs_that_escape_the_data_root() {
+        let base = temp_dir_for("delete-escape");
+        let root = base.join("data");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(root.join("instances")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("keep.txt"), b"keep").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("instances").join("evil")).unwrap();
+
+        let err = preview_instance_delete(&root, "evil").unwrap_err();
+        assert!(err.to_string().contains("outside the data root"), "{err}");
+        assert!(preview_instance_delete(&root, "..").is_err());
+        assert!(preview_instance_delete(&root, "missing").unwrap().is_none());
+        assert!(outside.join("keep.txt").is_file());
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn delete_preview_counts_bytes_and_files_without_following_links() {
+        let base = temp_dir_for("delete-preview");
+        let root = base.join("data");
+        let dir = root.join("processes").join("p1");
+        std::fs::create_dir_all(dir.join("world")).unwrap();
+        std::fs::write(dir.join("server.properties"), b"hello").unwrap();
+        std::fs::write(dir.join("world").join("level.dat"), b"1234567").unwrap();
+        std::fs::write(base.join("big.bin"), vec![0u8; 4096]).unwrap();
+        let link = dir.join("big-link");
+        std::os::unix::fs::symlink(base.join("big.bin"), &link).unwrap();
+        let link_len = std::fs::symlink_metadata(&link).unwrap().len();
+
+        let preview = preview_instance_delete(&root, "p1").unwrap().unwrap();
+        assert_eq!(preview.path, std::fs::canonicalize(&dir).unwrap());
+        assert_eq!(preview.file_count, 2);
+        assert_eq!(preview.size_bytes, 5 + 7 + link_len);
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    fn fake_frpc_that_exits(dir: &std::path::Path) -> FrpcLaunch {
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::create_dir_all(dir).unwrap();
+        let fake = dir.join("fake-frpc");
+        std::fs::write(&fake, "#!/bin/sh\necho run >> \"$(dirname \"$0\")/runs\"\n").unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+        FrpcLaunch {
+            exec: fake.display().to_string(),
+            cfg_path: dir.join("frpc.ini"),
+            instance_dir: dir.to_path_buf(),
+            owner_pgid: 0,
+        }
+    }
+
+    fn spawn_owner() -> tokio::process::Child {
+        tokio::process::Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap()
+    }
+
+    fn quick_restart(max_retries: u32) -> RestartConfig {
+        RestartConfig {
+            policy: RestartPolicy::Always,
+            max_retries,
+            backoff_ms: 10,
+            backoff_max_ms: 25,
+        }
+    }
+
+    #[tokio::test]
+    async fn frpc_is_restarted_with_backoff_while_the_owner_lives() {
+        let dir = temp_dir_for("frpc-restart");
+        let mut launch = fake_frpc_that_exits(&dir);
+        let mut owner = spawn_owner();
+        launch.owner_pgid = owner.id().unwrap() as i32;
+        let sink = LogSink {
+            buffer: Arc::new(Mutex::new(LogBuffer::default())),
+            file_tx: None,
+            limiter: None,
+        };
+
+        let child = spawn_frpc(&launch, &sink).unwrap();
+        let restarts = supervise_frpc(child, launch, sink.clone(), quick_restart(3)).await;
+        assert_eq!(restarts, 3);
+        let runs = std::fs::read_to_string(dir.join("runs")).unwrap();
+        assert_eq!(runs.lines().count(), 4);
+
+        let logs = sink.recent_lines(50).await;
+        for expected in [
+            "restarting frpc in 10ms (attempt 1/3)",
+            "restarting frpc in 20ms (attempt 2/3)",
+            "restarting frpc in 25ms (attempt 3/3)",
+            "giving up after 3 restarts",
+        ] {
+            assert!(
+                logs.iter().any(|l| l.contains(expected)),
+                "{expected}: {logs:?}"
+            );
+        }
+
+        owner.kill().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn frpc_is_not_restarted_once_the_owner_exits() {
+        let dir = temp_dir_for("frpc-owner-gone");
+        let mut launch = fake_frpc_that_exits(&dir);
+        let mut owner = spawn_owner();
+        launch.owner_pgid = owner.id().unwrap() as i32;
+        let sink = LogSink {
+            buffer: Arc::new(Mutex::new(LogBuffer::default())),
+            file_tx: None,
+            limiter: None,
+        };
+
+        let child = spawn_frpc(&launch, &sink).unwrap();
+        owner.kill().await.unwrap();
+        let restarts = supervise_frpc(child, launch, sink.clone(), quick_restart(3)).await;
+        assert_eq!(restarts, 0);
+        let runs = std::fs::read_to_string(dir.join("runs")).unwrap();
+        assert_eq!(runs.lines().count(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn reattached_process_runs_until_its_pid_exits() {
+        let dir = temp_dir_for("reattach");
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+        std::fs::write(
+            dir.join("logs").join("console.log"),
+            "[stdout] Done (3.2s)!\n",
+        )
+        .unwrap();
+        let mut owner = spawn_owner();
+        let pid = owner.id().unwrap();
+
+        let manager = ProcessManager::default();
+        manager
+            .reattach("reattach-me", "demo:sleep", pid, pid as i32, &dir)
+            .await
+            .unwrap();
+        let status = manager.get_status("reattach-me").await.unwrap();
+        assert_eq!(status.state, ProcessState::Running);
+        assert_eq!(status.pid, Some(pid));
+        let (lines, _) = manager
+            .tail_logs("reattach-me", 0, 10, &LogFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(lines[0].text, "[stdout] Done (3.2s)!");
+
+        owner.kill().await.unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let status = manager.get_status("reattach-me").await.unwrap();
+            if status.state == ProcessState::Exited {
+                break;
+            }
+            assert!(Instant::now() < deadline, "reattached exit not noticed");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn frpc_is_not_spawned_when_verify_rejects_the_config() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir_for("frpc-verify");
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake = dir.join("fake-frpc");
+        std::fs::write(
+            &fake,
+            "#!/bin/sh\n\
+             if [ \"$1\" = verify ]; then echo 'proxy [game]: invalid remote_port' >&2; exit 1; fi\n\
+             touch \"$(dirname \"$0\")/started\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let sink = LogSink {
+            buffer: Arc::new(Mutex::new(LogBuffer::default())),
+            file_tx: None,
+            limiter: None,
+        };
+        let frpc = Frpc {
+            exec: fake.display().to_string(),
+            verify: true,
+            restart: quick_restart(0),
+        };
+        let cfg = "[common]\nserver_addr = 1.2.3.4\n\n[game]\ntype = tcp\nremote_port = 0\n";
+        let started =
+            start_frpc_sidecar(sink.clone(), dir.clone(), 0, 25565, cfg.to_string(), &frpc)
+                .await
+                .unwrap();
+
+        assert!(
+            matches!(&started, FrpcStart::Rejected(msg) if msg.contains("invalid remote_port")),
+            "{started:?}"
+        );
+        assert!(dir.join("config/frpc.ini").is_file());
+        assert!(!dir.join("started").exists());
+        let logs = sink.recent_lines(10).await;
+        assert!(
+            logs.iter().any(|l| l.contains("frpc config rejected")),
+            "{logs:?}"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn world_corruption_hint_matches_game_specific_patterns() {
+        let mc = vec![
+            "[stdout] [Server thread/INFO]: Preparing level \"world\"".to_string(),
+            "[stdout] [Server thread/ERROR]: Failed to load level.dat".to_string(),
+            "[stdout] Exception reading ./world/level.dat".to_string(),
+        ];
+        assert!(world_corruption_hint("minecraft:vanilla", &mc).is_some());
+        assert!(world_corruption_hint("dst:vanilla", &mc).is_none());
+
+        let tr = vec!["[stdout] Load failed!".to_string()];
+        assert!(world_corruption_hint("terraria:vanilla", &tr).is_some());
+
+        let ok = vec!["[stdout] Done (3.2s)! For help, type \"help\"".to_string()];
+        assert!(world_corruption_hint("minecraft:vanilla", &ok).is_none());
+    }
+
+    #[test]
+    fn corruption_hint_requires_a_backup() {
+        let root = temp_dir_for("corruption-hint-backup");
+        let lines = vec!["[stdout] Load failed!".to_string()];
+        assert!(corruption_hint("terraria:vanilla", &lines, &root).is_none());
+
+        let backups = root.join(crate::backup::BACKUPS_DIR);
+        std::fs::create_dir_all(&backups).unwrap();
+        std::fs::write(backups.join("before-update.tar.zst"), b"").unwrap();
+        assert!(corruption_hint("terraria:vanilla", &lines, &root).is_some());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn classify_failure_maps_log_signatures_to_reasons() {
+        let reason = |lines: &[&str], exit_code| {
+            let lines: Vec<String> = lines.iter().map(|l| format!("[stdout] {l}")).collect();
+            classify_failure(&lines, exit_code).map(|(r, _)| r)
+        };
+        assert_eq!(
+            reason(
+                &[
+                    "Exception in thread \"Server thread\" java.lang.OutOfMemoryError: Java heap space"
+                ],
+                Some(1)
+            ),
+            Some(FailureReason::OutOfMemory)
+        );
+        assert_eq!(
+            reason(
+                &[
+                    "org.spongepowered.asm.mixin.transformer.throwables.MixinTransformerError: An unexpected critical error was encountered",
+                    "Caused by: org.spongepowered.asm.mixin.injection.throwables.InjectionError: Mixin apply failed create.mixins.json:ContraptionMixin"
+                ],
+                Some(1)
+            ),
+            Some(FailureReason::MixinFailure)
+        );
+        assert_eq!(
+            reason(
+                &[
+                    "[main/ERROR] [net.neoforged.fml.loading.ModSorter/LOADING]: Missing or unsupported mandatory dependencies:"
+                ],
+                Some(1)
+            ),
+            Some(FailureReason::MissingDependency)
+        );
+        assert_eq!(
+            reason(
+                &[
+                    "Unhandled exception. System.NullReferenceException: Object reference not set to an instance of an object.",
+                    "   at Terraria.WorldGen.SaveAndQuitCallBack(Object threadContext)"
+                ],
+                Some(134)
+            ),
+            Some(FailureReason::Crash)
+        );
+        assert_eq!(
+            reason(&["Loading world"], Some(137)),
+            Some(FailureReason::OutOfMemory)
+        );
+        assert_eq!(reason(&["Stopping server"], Some(1)), None);
+
+        let (_, hint) =
+            classify_failure(&["java.lang.OutOfMemoryError".to_string()], None).unwrap();
+        assert_eq!(hint, "Server ran out of memory; increase memory_mb");
+    }
+
+    #[test]
+    fn port_in_use_message_matches_bind_failures() {
+        let mc = vec![
+            "[stdout] [Server thread/WARN]: **** FAILED TO BIND TO PORT!".to_string(),
+            "[stdout] [Server thread/WARN]: The exception was: java.net.BindException: Address already in use".to_string(),
+        ];
+        assert_eq!(
+            port_in_use_message("minecraft:paper", Some(25565), &mc).as_deref(),
+            Some("port 25565 already in use; choose another port or 0 for auto")
+        );
+        assert!(port_in_use_message("dst:vanilla", Some(25565), &mc).is_none());
+
+        let tr = vec!["[stdout] Tried to run two servers on the same PC".to_string()];
+        assert_eq!(
+            port_in_use_message("terraria:vanilla", None, &tr).as_deref(),
+            Some("port already in use; choose another port or 0 for auto")
+        );
+
+        let ok = vec!["[stdout] Starting Minecraft server on *:25565".to_string()];
+        assert!(port_in_use_message("minecraft:vanilla", Some(25565), &ok).is_none());
+    }
+
+    #[tokio::test]
+    async fn bind_failure_fails_the_probe_as_port_in_use() {
+        let pm = ProcessManager::default();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let probe = PortProbe::Tcp {
+            port,
+            timeout: Duration::from_secs(30),
+        };
+        let mut ctx = sleep_context("bind-failure", "", Some(probe), native_params(&[]));
+        ctx.template_id = "minecraft:vanilla".to_string();
+        ctx.exec = "sh".to_string();
+        ctx.args = vec![
+            "-c".to_string(),
+            "echo '[Server thread/WARN]: **** FAILED TO BIND TO PORT!'; sleep 10".to_string(),
+        ];
+        let dir = ctx.instance_dir.clone();
+
+        pm.spawn_supervised(ctx).await.unwrap();
+        assert!(wait_for_state(&pm, "bind-failure", ProcessState::Failed).await);
+        let map = pm.inner.lock().await;
+        let e = map.get("bind-failure").unwrap();
+        assert_eq!(
+            e.message.as_deref(),
+            Some(format!("port {port} already in use; choose another port or 0 for auto").as_str())
+        );
+        assert_eq!(e.fail_category, Some(FailCategory::Port));
+        drop(map);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn materialize_server_jar_replaces_existing_file() {
         let root = temp_dir_for("materialize-server-jar-file");
@@ -434,983 +1163,3777 @@ proxies:
 
         let _ = std::fs::remove_dir_all(&root);
     }
-}
-
-#[derive(Debug)]
-struct LogBuffer {
-    next_seq: u64,
-    max_lines: usize,
-    lines: VecDeque<(u64, String)>,
-}
 
-impl Default for LogBuffer {
-    fn default() -> Self {
-        Self {
-            next_seq: 1,
-            max_lines: log_max_lines(),
-            lines: VecDeque::new(),
+    fn sleep_context(
+        test_name: &str,
+        seconds: &str,
+        port_probe: Option<PortProbe>,
+        params: BTreeMap<String, String>,
+    ) -> LaunchContext {
+        let dir = temp_dir_for(test_name);
+        std::fs::create_dir_all(&dir).unwrap();
+        LaunchContext {
+            process_id: test_name.to_string(),
+            template_id: "test:sleep".to_string(),
+            kind: "test",
+            exec_detail: String::new(),
+            exec: "sleep".to_string(),
+            args: vec![seconds.to_string()],
+            cwd: dir.clone(),
+            instance_dir: dir,
+            extra_rw_paths: Vec::new(),
+            env: Vec::new(),
+            spawn_hint: "",
+            port_probe,
+            frp_config: None,
+            restart: parse_restart_config(&params),
+            restart_attempts: 0,
+            graceful_stop: None,
+            sink: LogSink {
+                buffer: Arc::new(Mutex::new(LogBuffer::default())),
+                file_tx: None,
+                limiter: None,
+            },
+            params,
+            companions: Vec::new(),
         }
     }
-}
 
-impl LogBuffer {
-    fn push_line(&mut self, line: String) {
-        let seq = self.next_seq;
-        self.next_seq = self.next_seq.saturating_add(1);
-        self.lines.push_back((seq, line));
-        while self.lines.len() > self.max_lines {
-            self.lines.pop_front();
+    fn native_params(extra: &[(&str, &str)]) -> BTreeMap<String, String> {
+        let mut params = BTreeMap::from([("sandbox_mode".to_string(), "native".to_string())]);
+        for (k, v) in extra {
+            params.insert(k.to_string(), v.to_string());
         }
+        params
     }
 
-    fn tail_after(&self, cursor: u64, limit: usize) -> (Vec<String>, u64) {
-        // Convenience for UI polling: if cursor is 0, return the most recent lines.
-        if cursor == 0 {
-            let start = self.lines.len().saturating_sub(limit);
-            let mut out = Vec::new();
-            let mut last = 0;
-            for (seq, line) in self.lines.iter().skip(start) {
-                out.push(line.clone());
-                last = *seq;
-            }
-            return (out, last);
-        }
-
-        let mut out = Vec::new();
-        let mut last = cursor;
-        for (seq, line) in self.lines.iter() {
-            if *seq > cursor {
-                out.push(line.clone());
-                last = *seq;
-                if out.len() >= limit {
-                    break;
-                }
+    async fn wait_for_state(pm: &ProcessManager, id: &str, state: ProcessState) -> bool {
+        for _ in 0..100 {
+            if pm
+                .inner
+                .lock()
+                .await
+                .get(id)
+                .is_some_and(|e| e.state == state)
+            {
+                return true;
             }
+            tokio::time::sleep(Duration::from_millis(50)).await;
         }
-        (out, last)
+        false
     }
-}
 
-#[derive(Clone)]
-struct LogSink {
-    buffer: Arc<Mutex<LogBuffer>>,
-    file_tx: Option<mpsc::UnboundedSender<String>>,
-}
+    #[tokio::test]
+    async fn udp_probe_waits_for_the_bind() {
+        let sock = std::net::UdpSocket::bind(("127.0.0.1", 0)).unwrap();
+        let port = sock.local_addr().unwrap().port();
+        drop(sock);
+        assert!(!wait_for_local_udp_ready(port, Duration::from_millis(300)).await);
 
-impl LogSink {
-    async fn emit(&self, line: impl Into<String>) {
-        let line = line.into();
-        self.buffer.lock().await.push_line(line.clone());
-        if let Some(tx) = &self.file_tx {
-            let _ = tx.send(line);
-        }
+        // Answering servers are ready without waiting out the silence grace.
+        let server = tokio::net::UdpSocket::bind(("0.0.0.0", port))
+            .await
+            .unwrap();
+        let echo = tokio::spawn(async move {
+            let mut buf = [0u8; 16];
+            let (n, from) = server.recv_from(&mut buf).await.unwrap();
+            server.send_to(&buf[..n], from).await.unwrap();
+            server
+        });
+        assert!(wait_for_local_udp_ready(port, Duration::from_secs(5)).await);
+        drop(echo.await.unwrap());
     }
-}
 
-struct FileLogWriter {
-    path: PathBuf,
-    max_bytes: u64,
-    max_files: usize,
-    bytes: u64,
-    file: tokio::fs::File,
-}
+    #[tokio::test]
+    async fn spawn_supervised_moves_through_starting_running_and_exit() {
+        let pm = ProcessManager::default();
+        // Stands in for the server's game port; it never answers the probe.
+        let game_port = std::net::UdpSocket::bind(("0.0.0.0", 0)).unwrap();
+        let ctx = sleep_context(
+            "supervised-states",
+            "1",
+            Some(PortProbe::Udp {
+                port: game_port.local_addr().unwrap().port(),
+                timeout: Duration::from_secs(5),
+            }),
+            native_params(&[]),
+        );
+        let dir = ctx.instance_dir.clone();
+
+        let mut events = pm.subscribe();
+        let st = pm.spawn_supervised(ctx).await.unwrap();
+        assert_eq!(st.state, ProcessState::Starting);
+        assert!(st.pid.is_some());
+        assert_eq!(
+            pm.start_phase("supervised-states").await,
+            Some(StartPhase::WaitingForReady)
+        );
+        let phases: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|e| match e.kind {
+                ProcessEventKind::StartProgress { phase, .. } => Some(phase),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(phases, [StartPhase::Launching, StartPhase::WaitingForReady]);
+
+        assert!(wait_for_state(&pm, "supervised-states", ProcessState::Running).await);
+        // One second is well under the early-exit threshold, so a clean exit still fails.
+        assert!(wait_for_state(&pm, "supervised-states", ProcessState::Failed).await);
+        let map = pm.inner.lock().await;
+        let e = map.get("supervised-states").unwrap();
+        assert_eq!(e.exit_code, Some(0));
+        assert!(
+            e.message
+                .as_deref()
+                .unwrap()
+                .starts_with("exited too quickly")
+        );
+        assert!(e.stdin.is_none());
+        drop(map);
 
-impl FileLogWriter {
-    async fn open(path: PathBuf, max_bytes: u64, max_files: usize) -> std::io::Result<Self> {
-        if let Some(parent) = path.parent() {
-            tokio::fs::create_dir_all(parent).await?;
-        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 
-        let bytes = tokio::fs::metadata(&path)
+    #[tokio::test]
+    async fn log_marker_flips_starting_to_running() {
+        let pm = ProcessManager::default();
+        let matcher =
+            LogReadyMatcher::new(&["Sim paused".to_string()], Duration::from_secs(10)).unwrap();
+        let mut ctx = sleep_context(
+            "log-ready",
+            "",
+            Some(PortProbe::Log(matcher)),
+            native_params(&[]),
+        );
+        ctx.exec = "sh".to_string();
+        ctx.args = vec![
+            "-c".to_string(),
+            "echo 'Loading world'; sleep 0.5; echo '[00:00:12]: Sim paused'; sleep 5".to_string(),
+        ];
+        let dir = ctx.instance_dir.clone();
+
+        let st = pm.spawn_supervised(ctx).await.unwrap();
+        assert_eq!(st.state, ProcessState::Starting);
+        assert!(wait_for_state(&pm, "log-ready", ProcessState::Running).await);
+
+        let st = pm.stop("log-ready", Duration::from_secs(5)).await.unwrap();
+        assert_eq!(st.state, ProcessState::Exited);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn stop_escalation_is_audited_in_order() {
+        let pm = ProcessManager::default();
+        let mut ctx = sleep_context("audit-escalation", "", None, native_params(&[]));
+        ctx.exec = "sh".to_string();
+        ctx.args = vec!["-c".to_string(), "trap '' TERM; sleep 30".to_string()];
+        let dir = ctx.instance_dir.clone();
+        pm.spawn_supervised(ctx).await.unwrap();
+
+        pm.stop("audit-escalation", Duration::from_secs(1))
             .await
-            .map(|m| m.len())
-            .unwrap_or(0);
-        let file = tokio::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&path)
-            .await?;
+            .unwrap();
+        let events = pm.audit().drain();
+        let actions: Vec<&str> = events.iter().map(|e| e.action).collect();
+        assert_eq!(actions, ["start", "stop", "sigterm", "sigkill"]);
+        assert!(
+            events
+                .iter()
+                .all(|e| e.process_id == "audit-escalation" && e.template_id == "test:sleep")
+        );
+        assert!(
+            events
+                .windows(2)
+                .all(|w| w[0].at_unix_ms <= w[1].at_unix_ms)
+        );
 
-        Ok(Self {
-            path,
-            max_bytes,
-            max_files,
-            bytes,
-            file,
-        })
+        let _ = std::fs::remove_dir_all(&dir);
     }
 
-    async fn rotate(&mut self) -> std::io::Result<()> {
-        let _ = self.file.flush().await;
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn companion_processes_stop_with_their_instance_and_vice_versa() {
+        async fn gone(pid: u32) -> bool {
+            for _ in 0..100 {
+                if unsafe { libc::kill(pid as i32, 0) } != 0 {
+                    return true;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            false
+        }
+        fn paired(test_name: &str) -> LaunchContext {
+            let mut ctx = sleep_context(test_name, "30", None, native_params(&[]));
+            ctx.companions = vec![Companion {
+                name: "caves",
+                args: vec!["30".to_string()],
+                stdout_prefix: "[caves stdout]",
+                stderr_prefix: "[caves stderr]",
+            }];
+            ctx
+        }
+        fn companion_pid(dir: &Path) -> u32 {
+            let run: serde_json::Value =
+                serde_json::from_slice(&std::fs::read(dir.join("run.json")).unwrap()).unwrap();
+            assert_eq!(run["companions"][0]["name"], "caves");
+            assert_eq!(run["companions"][0]["pgid"], run["pgid"]);
+            run["companions"][0]["pid"].as_u64().unwrap() as u32
+        }
 
-        // Shift old rotations: .(n-1) -> .n
-        for i in (1..self.max_files).rev() {
-            let from = PathBuf::from(format!("{}.{}", self.path.display(), i));
-            let to = PathBuf::from(format!("{}.{}", self.path.display(), i + 1));
-            if tokio::fs::metadata(&from).await.is_ok() {
-                let _ = tokio::fs::rename(from, to).await;
+        let pm = ProcessManager::default();
+
+        // Stopping the instance stops the companion.
+        let ctx = paired("paired-stop");
+        let dir = ctx.instance_dir.clone();
+        let main_pid = pm.spawn_supervised(ctx).await.unwrap().pid.unwrap();
+        let caves_pid = companion_pid(&dir);
+        let st = pm
+            .stop("paired-stop", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(st.state, ProcessState::Exited);
+        assert!(gone(main_pid).await && gone(caves_pid).await);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // The companion exiting stops the instance.
+        let ctx = paired("paired-companion-exit");
+        let dir = ctx.instance_dir.clone();
+        let main_pid = pm.spawn_supervised(ctx).await.unwrap().pid.unwrap();
+        unsafe {
+            libc::kill(companion_pid(&dir) as i32, libc::SIGKILL);
+        }
+        assert!(wait_for_state(&pm, "paired-companion-exit", ProcessState::Failed).await);
+        assert!(gone(main_pid).await);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stop_all_waits_for_running_processes_to_exit() {
+        let pm = ProcessManager::default();
+        let mut ctx = sleep_context("shutdown-stop", "", None, native_params(&[]));
+        ctx.exec = "sh".to_string();
+        // Takes a moment to "save" after SIGTERM, like a game server would.
+        ctx.args = vec![
+            "-c".to_string(),
+            "trap 'sleep 0.3; exit 0' TERM; sleep 30 & wait".to_string(),
+        ];
+        let dir = ctx.instance_dir.clone();
+        pm.spawn_supervised(ctx).await.unwrap();
+
+        let stopped = pm
+            .stop_all(Duration::from_secs(5), Duration::from_secs(10))
+            .await;
+        assert_eq!(stopped, 1);
+        let st = pm.get_status("shutdown-stop").await.unwrap();
+        assert_eq!(st.state, ProcessState::Exited);
+        let actions: Vec<&str> = pm.audit().drain().iter().map(|e| e.action).collect();
+        assert_eq!(actions, ["start", "stop", "sigterm"]);
+
+        // Nothing left to stop.
+        assert_eq!(
+            pm.stop_all(Duration::from_secs(5), Duration::from_secs(10))
+                .await,
+            0
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn list_instances_merges_live_entries_with_run_json_on_disk() {
+        let pm = ProcessManager::default();
+        let ctx = sleep_context("list-live", "30", None, native_params(&[]));
+        let live_dir = ctx.instance_dir.clone();
+        pm.spawn_supervised(ctx).await.unwrap();
+
+        let data_root = temp_dir_for("list-instances-root");
+        for (dir, id, template) in [
+            ("instances", "list-live", "test:sleep"),
+            ("instances", "mc-stopped", "minecraft:vanilla"),
+            ("processes", "demo-stopped", "demo:sleep"),
+        ] {
+            let path = data_root.join(dir).join(id);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(
+                path.join("run.json"),
+                format!(r#"{{"process_id":"{id}","template_id":"{template}"}}"#),
+            )
+            .unwrap();
+        }
+        // Dirs without run.json were never launched here.
+        std::fs::create_dir_all(data_root.join("instances").join("never-started")).unwrap();
+
+        let listed = pm.list_instances_in(&data_root).await;
+        let summary: Vec<(&str, &str, ProcessState)> = listed
+            .iter()
+            .map(|s| (s.id.0.as_str(), s.template_id.0.as_str(), s.state))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("demo-stopped", "demo:sleep", ProcessState::Exited),
+                ("list-live", "test:sleep", ProcessState::Running),
+                ("mc-stopped", "minecraft:vanilla", ProcessState::Exited),
+            ]
+        );
+        assert!(listed[1].pid.is_some());
+        assert_eq!(listed[2].message.as_deref(), Some("not running"));
+
+        pm.stop("list-live", Duration::from_secs(5)).await.unwrap();
+        let _ = std::fs::remove_dir_all(&data_root);
+        let _ = std::fs::remove_dir_all(&live_dir);
+    }
+
+    #[tokio::test]
+    async fn download_progress_is_stored_on_the_entry_and_throttled() {
+        let pm = ProcessManager::default();
+        let sink = LogSink {
+            buffer: Arc::new(Mutex::new(LogBuffer::default())),
+            file_tx: None,
+            limiter: None,
+        };
+        pm.inner.lock().await.insert(
+            "progress".to_string(),
+            ProcessEntry {
+                template_id: ProcessTemplateId("minecraft:vanilla".to_string()),
+                state: ProcessState::Starting,
+                pid: None,
+                resources: None,
+                exit_code: None,
+                message: None,
+                restart: parse_restart_config(&BTreeMap::new()),
+                restart_attempts: 0,
+                stdin: None,
+                graceful_stop: None,
+                pgid: None,
+                logs: sink.buffer.clone(),
+                log_file_tx: None,
+                fail_category: None,
+                failure_reason: None,
+                failure_hint: None,
+                players: None,
+                progress: None,
+                start_phase: None,
+            },
+        );
+        let progress = |done| ProgressInfo {
+            bytes_done: done,
+            bytes_total: 1000,
+            phase: "downloading server.jar".to_string(),
+        };
+
+        let mut events = pm.subscribe();
+        let mut report = download_reporter(
+            &pm.inner,
+            &pm.events,
+            "progress",
+            &sink,
+            "downloading server.jar",
+        );
+        report(420, 1000, 0);
+        let st = pm.get_status("progress").await.unwrap();
+        assert_eq!(st.message.as_deref(), Some("downloading server.jar (42%)"));
+        assert_eq!(st.progress, Some(progress(420)));
+
+        // Within the interval only the final update gets through.
+        report(500, 1000, 0);
+        let st = pm.get_status("progress").await.unwrap();
+        assert_eq!(st.progress, Some(progress(420)));
+        report(1000, 1000, 0);
+        let st = pm.get_status("progress").await.unwrap();
+        assert_eq!(st.message.as_deref(), Some("downloading server.jar (100%)"));
+        assert_eq!(st.progress, Some(progress(1000)));
+
+        // The same throttled updates are broadcast as start progress.
+        let mut percents = Vec::new();
+        while let Ok(ev) = events.try_recv() {
+            if let ProcessEventKind::StartProgress { phase, percent, .. } = ev.kind {
+                assert_eq!(phase, StartPhase::Downloading);
+                percents.push(percent);
             }
         }
+        assert_eq!(percents, vec![Some(42), Some(100)]);
 
-        // Current -> .1
-        let rotated = PathBuf::from(format!("{}.1", self.path.display()));
-        if tokio::fs::metadata(&self.path).await.is_ok() {
-            let _ = tokio::fs::rename(&self.path, &rotated).await;
+        set_entry_message(&pm.inner, "progress", Some("installing".to_string())).await;
+        assert_eq!(pm.get_status("progress").await.unwrap().progress, None);
+    }
+
+    #[tokio::test]
+    async fn resource_history_is_windowed_and_capped() {
+        let pm = ProcessManager::default();
+        let sample = |cpu| alloy_process::ProcessResources {
+            cpu_percent_x100: cpu,
+            cpu_percent_normalized_x100: cpu,
+            rss_bytes: 1024,
+            read_bytes: 0,
+            write_bytes: 0,
+        };
+        {
+            let mut history = SampleHistory::new(4);
+            for i in 1..=6u32 {
+                history.push((u64::from(i) * 1000, sample(i)));
+            }
+            pm.resource_history
+                .lock()
+                .await
+                .insert("charted".to_string(), history);
         }
 
-        self.file = tokio::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.path)
-            .await?;
-        self.bytes = 0;
-        Ok(())
+        let cpus = |samples: Vec<(u64, alloy_process::ProcessResources)>| {
+            samples
+                .iter()
+                .map(|(_, r)| r.cpu_percent_x100)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            cpus(pm.resource_history("charted", 0).await),
+            vec![3, 4, 5, 6]
+        );
+        assert_eq!(cpus(pm.resource_history("charted", 4000).await), vec![5, 6]);
+        assert!(pm.resource_history("charted", 6000).await.is_empty());
+        assert!(pm.resource_history("unknown", 0).await.is_empty());
     }
 
-    async fn write_line(&mut self, line: &str) -> std::io::Result<()> {
-        let mut line = line.to_string();
-        if !line.ends_with('\n') {
-            line.push('\n');
+    #[tokio::test]
+    async fn run_info_round_trips_through_run_json() {
+        let root = temp_dir_for("run-info");
+        let info = RunInfo {
+            process_id: "inspect-me".to_string(),
+            template_id: "minecraft:vanilla".to_string(),
+            started_at_unix_ms: 1_700_000_000_000,
+            agent_version: "1.2.3".to_string(),
+            pid: Some(4242),
+            pgid: Some(4242),
+            container_name: Some("alloy-inspect-me".to_string()),
+            container_id: None,
+            exec: "java".to_string(),
+            args: vec!["-Xmx2048M".to_string(), "-jar".to_string()],
+            cwd: "/data/instances/inspect-me".to_string(),
+            params: BTreeMap::from([("rcon_password".to_string(), "<redacted>".to_string())]),
+            env: BTreeMap::from([("PATH".to_string(), "/usr/bin".to_string())]),
+            companions: Vec::new(),
+        };
+        write_run_json(&root.join("instances").join("inspect-me"), &info)
+            .await
+            .unwrap();
+
+        let view: RunInfoView = read_run_json(&root, "inspect-me").await.unwrap();
+        assert_eq!(view.template_id, "minecraft:vanilla");
+        assert_eq!(view.started_at_unix_ms, 1_700_000_000_000);
+        assert_eq!(view.pid, Some(4242));
+        assert_eq!(view.container_name.as_deref(), Some("alloy-inspect-me"));
+        assert_eq!(view.container_id, None);
+        assert_eq!(view.args, info.args);
+        assert_eq!(view.cwd, info.cwd);
+        assert_eq!(view.params, info.params);
+
+        assert!(
+            read_run_json::<RunInfoView>(&root, "never-started")
+                .await
+                .is_none()
+        );
+        assert!(
+            read_run_json::<RunInfoView>(&root, "../inspect-me")
+                .await
+                .is_none()
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn filtered_tail_keeps_sequence_numbers() {
+        let mut buf = LogBuffer::default();
+        for line in [
+            "[stdout] Done (3.2s)!",
+            "[stderr] WARN slow tick",
+            "[alloy-agent] process exited",
+            "[stdout] player joined",
+            "[stderr] ERROR chunk failed",
+        ] {
+            buf.push_line(line.to_string());
         }
+        let seqs = |lines: &[LogLine]| lines.iter().map(|l| l.seq).collect::<Vec<_>>();
 
-        let write_len = line.len() as u64;
-        if self.max_bytes > 0 && self.bytes.saturating_add(write_len) > self.max_bytes {
-            self.rotate().await.ok();
+        let errors = LogFilter {
+            streams: vec![LogStream::Stderr],
+            contains: None,
+        };
+        let (lines, next) = buf.tail_filtered(0, 10, &errors);
+        assert_eq!(seqs(&lines), [2, 5]);
+        assert_eq!(next, 5);
+        // Hitting the limit leaves the cursor on the last returned line.
+        let (lines, next) = buf.tail_filtered(1, 1, &errors);
+        assert_eq!(seqs(&lines), [2]);
+        assert_eq!(next, 2);
+        let (lines, next) = buf.tail_filtered(next, 10, &errors);
+        assert_eq!(seqs(&lines), [5]);
+        assert_eq!(next, 5);
+
+        // Non-matching lines still move the cursor forward.
+        buf.push_line("[stdout] saved the game".to_string());
+        let (lines, next) = buf.tail_filtered(5, 10, &errors);
+        assert!(lines.is_empty());
+        assert_eq!(next, 6);
+
+        let search = LogFilter {
+            streams: Vec::new(),
+            contains: Some("error".to_string()),
+        };
+        let (lines, next) = buf.tail_filtered(0, 10, &search);
+        assert_eq!(seqs(&lines), [5]);
+        assert_eq!(lines[0].stream, LogStream::Stderr);
+        assert_eq!(next, 6);
+
+        assert_eq!(
+            buf.tail_after(4, 10),
+            (
+                vec![
+                    "[stderr] ERROR chunk failed".to_string(),
+                    "[stdout] saved the game".to_string()
+                ],
+                6
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribers_see_state_changes_then_exit() {
+        let pm = ProcessManager::default();
+        let mut rx = pm.subscribe();
+        let ctx = sleep_context("supervised-events", "0", None, native_params(&[]));
+        let dir = ctx.instance_dir.clone();
+
+        pm.spawn_supervised(ctx).await.unwrap();
+
+        let mut kinds = Vec::new();
+        while !matches!(kinds.last(), Some(ProcessEventKind::Exited { .. })) {
+            let ev = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("event before timeout")
+                .unwrap();
+            assert_eq!(ev.process_id, "supervised-events");
+            if !matches!(ev.kind, ProcessEventKind::ResourcesSampled(_)) {
+                kinds.push(ev.kind);
+            }
         }
+        assert!(matches!(
+            kinds.as_slice(),
+            [
+                ProcessEventKind::StartProgress {
+                    phase: StartPhase::Launching,
+                    ..
+                },
+                ProcessEventKind::StateChanged(ProcessState::Running),
+                ProcessEventKind::Exited {
+                    state: ProcessState::Failed,
+                    exit_code: Some(0),
+                },
+            ]
+        ));
 
-        self.file.write_all(line.as_bytes()).await?;
-        self.bytes = self.bytes.saturating_add(write_len);
-        Ok(())
+        let _ = std::fs::remove_dir_all(&dir);
     }
-}
 
-#[derive(Debug, Clone, Serialize)]
-struct RunInfo {
-    process_id: String,
-    template_id: String,
-    started_at_unix_ms: u64,
-    agent_version: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pid: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pgid: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    container_name: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    container_id: Option<String>,
-    exec: String,
-    args: Vec<String>,
-    cwd: String,
-    // Params are redacted for known secret keys.
-    params: BTreeMap<String, String>,
-    env: BTreeMap<String, String>,
-}
+    #[tokio::test]
+    async fn spawn_supervised_schedules_restart_after_failure() {
+        let pm = ProcessManager::default();
+        let ctx = sleep_context(
+            "supervised-restart",
+            "0",
+            None,
+            native_params(&[
+                ("restart_policy", "on-failure"),
+                ("restart_max_retries", "2"),
+                ("restart_backoff_ms", "100"),
+            ]),
+        );
+        let dir = ctx.instance_dir.clone();
+        let logs = ctx.sink.clone();
 
-#[derive(Debug, Clone, serde::Deserialize)]
-struct RunContainerMeta {
-    container_name: Option<String>,
-    container_id: Option<String>,
-}
+        let st = pm.spawn_supervised(ctx).await.unwrap();
+        assert_eq!(st.state, ProcessState::Running);
 
-fn redact_params(mut params: BTreeMap<String, String>) -> BTreeMap<String, String> {
-    for (k, v) in params.iter_mut() {
-        let key = k.to_ascii_lowercase();
-        let is_secret = key.contains("password")
-            || key.contains("token")
-            || key.contains("secret")
-            || key.contains("api_key")
-            || key.contains("apikey")
-            || (key.contains("frp") && key.contains("config"));
-        if is_secret && !v.is_empty() {
-            *v = "<redacted>".to_string();
+        let mut lines = Vec::new();
+        for _ in 0..100 {
+            lines = logs.recent_lines(50).await;
+            if lines.iter().any(|l| l.contains("auto-restart failed")) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
         }
+        assert!(
+            lines
+                .iter()
+                .any(|l| l == "[alloy-agent] auto-restart scheduled in 100ms (attempt 1)")
+        );
+        // The restart goes back through the template path, which rejects the test template.
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.contains("auto-restart failed") && l.contains("test:sleep"))
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
-    params
-}
 
-async fn write_run_json(dir: &Path, info: &RunInfo) -> anyhow::Result<()> {
-    tokio::fs::create_dir_all(dir)
-        .await
-        .context("create instance dir")?;
-    let path = dir.join("run.json");
-    let tmp = dir.join("run.json.tmp");
-    let data = serde_json::to_vec_pretty(info).context("serialize run.json")?;
-    let mut f = tokio::fs::File::create(&tmp)
-        .await
-        .context("create run.json.tmp")?;
-    f.write_all(&data).await.context("write run.json.tmp")?;
-    f.flush().await.ok();
-    tokio::fs::rename(&tmp, &path)
-        .await
-        .context("persist run.json")?;
-    Ok(())
-}
+    #[tokio::test]
+    async fn send_stdin_reaches_the_process_console() {
+        let pm = ProcessManager::default();
+        let mut ctx = sleep_context("console-cat", "", None, native_params(&[]));
+        ctx.exec = "cat".to_string();
+        ctx.args.clear();
+        let dir = ctx.instance_dir.clone();
+        let logs = ctx.sink.clone();
+
+        pm.spawn_supervised(ctx).await.unwrap();
+        pm.send_stdin("console-cat", "say hello".to_string())
+            .await
+            .unwrap();
 
-fn collect_safe_env() -> BTreeMap<String, String> {
-    let mut out = BTreeMap::new();
-    for key in ["ALLOY_DATA_ROOT", "JAVA_HOME", "LD_LIBRARY_PATH", "PATH"] {
-        if let Ok(v) = std::env::var(key) {
-            let val = if key == "PATH" && v.len() > 512 {
-                format!("{}…(truncated)", &v[..512])
-            } else {
-                v
-            };
-            out.insert(key.to_string(), val);
+        let mut echoed = false;
+        for _ in 0..100 {
+            echoed = logs
+                .recent_lines(20)
+                .await
+                .iter()
+                .any(|l| l == "[stdout] say hello");
+            if echoed {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
         }
+        assert!(echoed);
+
+        // The console stays usable for further commands.
+        pm.send_stdin("console-cat", "x".repeat(5000))
+            .await
+            .unwrap();
+        assert!(pm.send_stdin("missing", "list".to_string()).await.is_err());
+
+        let _ = pm.stop("console-cat", Duration::from_secs(5)).await;
+        let _ = std::fs::remove_dir_all(&dir);
     }
-    out
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum FrpConfigFormat {
-    Ini,
-    Json,
-    Toml,
-    Yaml,
-}
+    #[tokio::test]
+    async fn stop_matches_template_save_keywords() {
+        let pm = ProcessManager::default();
+        let mut ctx = sleep_context("stop-save", "", None, native_params(&[]));
+        ctx.exec = "sh".to_string();
+        ctx.args = vec![
+            "-c".to_string(),
+            "read cmd; echo \"[Server] World Saved! ($cmd)\"; sleep 2".to_string(),
+        ];
+        ctx.graceful_stop = Some(GracefulStopSpec {
+            stdin_command: Some("save-and-quit\n".to_string()),
+            save_keywords: vec!["World Saved!".to_string()],
+            grace_secs: Some(1),
+        });
+        let dir = ctx.instance_dir.clone();
+        let logs = ctx.sink.clone();
+        pm.spawn_supervised(ctx).await.unwrap();
+
+        let stopper = pm.clone();
+        let stop = tokio::spawn(async move {
+            stopper
+                .stop("stop-save", Duration::from_secs(10))
+                .await
+                .unwrap()
+        });
 
-fn detect_frp_config_format(raw: &str) -> FrpConfigFormat {
-    let s = raw.trim();
-    if s.is_empty() {
-        return FrpConfigFormat::Ini;
+        let mut saved = false;
+        for _ in 0..100 {
+            saved = pm
+                .inner
+                .lock()
+                .await
+                .get("stop-save")
+                .and_then(|e| e.message.clone())
+                .is_some_and(|m| m == "stopping (world saved)");
+            if saved {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(saved);
+
+        let st = stop.await.unwrap();
+        assert_eq!(st.state, ProcessState::Exited);
+        let lines = logs.recent_lines(20).await;
+        assert!(lines.contains(&"[stdout] [Server] World Saved! (save-and-quit)".to_string()));
+        assert!(
+            lines.contains(&"[alloy-agent] stop: world save confirmed (world saved!)".to_string())
+        );
+        let _ = std::fs::remove_dir_all(&dir);
     }
-    if serde_json::from_str::<serde_json::Value>(s).is_ok() {
-        return FrpConfigFormat::Json;
+
+    #[tokio::test]
+    async fn reload_is_minecraft_only() {
+        let pm = ProcessManager::default();
+        let mut ctx = sleep_context("reload-mc", "", None, native_params(&[]));
+        ctx.template_id = "minecraft:vanilla".to_string();
+        ctx.exec = "cat".to_string();
+        ctx.args.clear();
+        let dir = ctx.instance_dir.clone();
+        let logs = ctx.sink.clone();
+        pm.spawn_supervised(ctx).await.unwrap();
+
+        let st = pm.reload("reload-mc").await.unwrap();
+        assert_eq!(st.state, ProcessState::Running);
+        let mut echoed = false;
+        for _ in 0..100 {
+            let lines = logs.recent_lines(20).await;
+            echoed = lines.iter().any(|l| l == "[stdout] reload");
+            if echoed {
+                assert!(lines.iter().any(|l| l == "[alloy-agent] reload requested"));
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(echoed);
+        let _ = pm.stop("reload-mc", Duration::from_secs(5)).await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let ctx = sleep_context("reload-other", "5", None, native_params(&[]));
+        let dir = ctx.instance_dir.clone();
+        pm.spawn_supervised(ctx).await.unwrap();
+        let err = pm.reload("reload-other").await.unwrap_err();
+        assert_eq!(
+            crate::error_payload::decode(&err).unwrap().code,
+            "unsupported"
+        );
+        let _ = pm.stop("reload-other", Duration::from_secs(5)).await;
+        let _ = std::fs::remove_dir_all(&dir);
     }
-    if s.parse::<toml::Value>().is_ok() {
-        return FrpConfigFormat::Toml;
+
+    #[tokio::test]
+    async fn rotations_past_the_total_budget_are_evicted_oldest_first() {
+        let dir = temp_dir_for("log-budget");
+        let path = dir.join("console.log");
+        let mut writer = FileLogWriter::open(path.clone(), 256 * 1024, 5)
+            .await
+            .unwrap();
+        writer.total_max_bytes = Some(600 * 1024);
+        let line = "x".repeat(100 * 1024 - 1);
+
+        // Ten 100 KiB lines: two per file, so four full rotations.
+        for _ in 0..10 {
+            writer.write_line(&line).await.unwrap();
+        }
+        writer.pending.take().unwrap().await.unwrap();
+
+        let rotated = |i: usize| PathBuf::from(format!("{}.{i}", path.display()));
+        assert!(rotated(1).exists());
+        assert!(rotated(2).exists());
+        assert!(rotated(3).exists());
+        assert!(!rotated(4).exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
-    if serde_yaml::from_str::<serde_yaml::Value>(s).is_ok() {
-        return FrpConfigFormat::Yaml;
+
+    #[tokio::test]
+    async fn rotation_compresses_segments_older_than_the_first() {
+        let dir = temp_dir_for("log-compress");
+        let path = dir.join("console.log");
+        let mut writer = FileLogWriter::open(path.clone(), 0, 3).await.unwrap();
+        writer.compression = Some(LogCompression::Zstd);
+        let rotated = |suffix: &str| PathBuf::from(format!("{}.{suffix}", path.display()));
+        let unzstd = |p: PathBuf| {
+            String::from_utf8(zstd::decode_all(std::fs::File::open(p).unwrap()).unwrap()).unwrap()
+        };
+
+        for line in ["one", "two", "three"] {
+            writer.write_line(line).await.unwrap();
+            writer.rotate().await.unwrap();
+        }
+        writer.pending.take().unwrap().await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(rotated("1")).unwrap(), "three\n");
+        assert!(!rotated("2").exists());
+        assert_eq!(unzstd(rotated("2.zst")), "two\n");
+        assert_eq!(unzstd(rotated("3.zst")), "one\n");
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
-    FrpConfigFormat::Ini
 }
 
-fn parse_port_scalar(raw: &str) -> Option<u16> {
-    let s = raw.trim().trim_matches('"').trim_matches('\'');
-    let p = s.parse::<u16>().ok()?;
-    if p == 0 {
-        return None;
+/// Where a console line came from; derived from the prefix the sink writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// Agent notices, install output and rate-limit markers.
+    Agent,
+}
+
+impl LogStream {
+    fn classify(line: &str) -> Self {
+        if line.starts_with("[stdout] ") {
+            LogStream::Stdout
+        } else if line.starts_with("[stderr] ") {
+            LogStream::Stderr
+        } else {
+            LogStream::Agent
+        }
     }
-    Some(p)
 }
 
-fn parse_allocatable_ports_spec(raw: &str) -> Vec<u16> {
-    let mut out = BTreeSet::<u16>::new();
-    for seg in raw.split(',') {
-        let token = seg.trim();
-        if token.is_empty() {
-            continue;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    pub seq: u64,
+    pub stream: LogStream,
+    pub text: String,
+}
+
+/// Empty `streams` matches every stream; `contains` is case-insensitive.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub streams: Vec<LogStream>,
+    pub contains: Option<String>,
+}
+
+impl LogFilter {
+    fn matcher(&self) -> impl Fn(&LogLine) -> bool + '_ {
+        let needle = self
+            .contains
+            .as_deref()
+            .filter(|s| !s.is_empty())
+            .map(str::to_lowercase);
+        move |line| {
+            (self.streams.is_empty() || self.streams.contains(&line.stream))
+                && needle
+                    .as_deref()
+                    .is_none_or(|n| line.text.to_lowercase().contains(n))
         }
-        if let Some((a_raw, b_raw)) = token.split_once('-') {
-            let Some(a) = parse_port_scalar(a_raw) else {
-                continue;
-            };
-            let Some(b) = parse_port_scalar(b_raw) else {
-                continue;
-            };
-            let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
-            if hi.saturating_sub(lo) > 4000 {
-                continue;
-            }
-            for p in lo..=hi {
-                out.insert(p);
-                if out.len() > 4000 {
-                    break;
-                }
-            }
-        } else if let Some(port) = parse_port_scalar(token) {
-            out.insert(port);
-        }
-        if out.len() > 4000 {
-            break;
+    }
+}
+
+#[derive(Debug)]
+struct LogBuffer {
+    next_seq: u64,
+    max_lines: usize,
+    lines: VecDeque<LogLine>,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self {
+            next_seq: 1,
+            max_lines: log_max_lines(),
+            lines: VecDeque::new(),
         }
     }
-    out.into_iter().collect()
 }
 
-fn parse_allocatable_ports_hint(raw: &str) -> Vec<u16> {
-    for line in raw.lines() {
-        let s = line.trim();
-        if s.is_empty() {
-            continue;
+impl LogBuffer {
+    fn push_line(&mut self, line: String) {
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.saturating_add(1);
+        self.lines.push_back(LogLine {
+            seq,
+            stream: LogStream::classify(&line),
+            text: line,
+        });
+        while self.lines.len() > self.max_lines {
+            self.lines.pop_front();
         }
-        let body = s
-            .strip_prefix('#')
-            .or_else(|| s.strip_prefix(';'))
-            .or_else(|| s.strip_prefix("//"))
-            .map(str::trim)
-            .unwrap_or(s);
-        if let Some((k, v)) = body.split_once('=') {
-            let key = k.trim().to_ascii_lowercase();
-            if key == "alloy_alloc_ports" || key == "allocatable_ports" {
-                return parse_allocatable_ports_spec(v);
-            }
+    }
+
+    fn last_seq(&self) -> u64 {
+        self.next_seq - 1
+    }
+
+    fn tail_after(&self, cursor: u64, limit: usize) -> (Vec<String>, u64) {
+        let (lines, next) = self.tail_filtered(cursor, limit, &LogFilter::default());
+        (lines.into_iter().map(|l| l.text).collect(), next)
+    }
+
+    /// Like `tail_after`, but only returns lines matching `filter`. The cursor
+    /// still advances past skipped lines so the next poll resumes after them.
+    fn tail_filtered(&self, cursor: u64, limit: usize, filter: &LogFilter) -> (Vec<LogLine>, u64) {
+        let matches = filter.matcher();
+        // Convenience for UI polling: if cursor is 0, return the most recent lines.
+        if cursor == 0 {
+            let mut out: Vec<LogLine> = self
+                .lines
+                .iter()
+                .rev()
+                .filter(|l| matches(l))
+                .take(limit)
+                .cloned()
+                .collect();
+            out.reverse();
+            // Lines newer than the last match were skipped by the filter.
+            return (out, self.lines.back().map_or(0, |l| l.seq));
         }
-        if let Some((k, v)) = body.split_once(':') {
-            let key = k.trim().to_ascii_lowercase();
-            if key == "alloy_alloc_ports" || key == "allocatable_ports" {
-                return parse_allocatable_ports_spec(v);
+
+        let mut out = Vec::new();
+        let mut last = cursor;
+        for line in self.lines.iter().filter(|l| l.seq > cursor) {
+            last = line.seq;
+            if matches(line) {
+                out.push(line.clone());
+                if out.len() >= limit {
+                    break;
+                }
             }
         }
+        (out, last)
     }
-    Vec::new()
 }
 
-fn choose_remote_port(explicit: Option<u16>, alloc_ports: &[u16], local_port: u16) -> u16 {
-    if let Some(v) = explicit {
-        return v;
-    }
-    if alloc_ports.is_empty() {
-        return local_port;
-    }
-    let idx = usize::from(local_port) % alloc_ports.len();
-    alloc_ports[idx]
+#[derive(Clone)]
+struct LogSink {
+    buffer: Arc<Mutex<LogBuffer>>,
+    file_tx: Option<mpsc::UnboundedSender<String>>,
+    // Shared by the stdout/stderr pumps so one noisy process cannot flood the agent.
+    limiter: Option<Arc<std::sync::Mutex<LineRateLimiter>>>,
 }
 
-fn normalize_ini_scalar_value(raw: &str) -> String {
-    raw.trim()
-        .split(['#', ';'])
-        .next()
-        .unwrap_or("")
-        .trim()
-        .trim_matches('"')
-        .trim_matches('\'')
-        .to_string()
+fn rate_limited_marker(dropped: u64) -> String {
+    format!("[alloy-agent] suppressed {dropped} duplicate/flooding lines")
 }
 
-fn patch_frpc_ini(raw: &str, local_port: u16, alloc_ports_hint: &[u16]) -> String {
-    let mut explicit_remote_port: Option<u16> = None;
-    for line in raw.lines() {
-        let trimmed = line.trim_start();
-        if trimmed.starts_with('#') || trimmed.starts_with(';') {
-            continue;
+impl LogSink {
+    async fn emit(&self, line: impl Into<String>) {
+        let line = line.into();
+        self.buffer.lock().await.push_line(line.clone());
+        if let Some(tx) = &self.file_tx {
+            let _ = tx.send(line);
         }
-        let lower = trimmed.to_ascii_lowercase();
-        if lower.starts_with("remote_port") {
-            let rest = trimmed
-                .get("remote_port".len()..)
-                .unwrap_or("")
-                .trim_start();
-            if rest.is_empty() || rest.starts_with('=') || rest.starts_with(':') {
-                if let Some((_, v_raw)) = trimmed.split_once('=') {
-                    explicit_remote_port = parse_port_scalar(&normalize_ini_scalar_value(v_raw));
-                } else if let Some((_, v_raw)) = trimmed.split_once(':') {
-                    explicit_remote_port = parse_port_scalar(&normalize_ini_scalar_value(v_raw));
+    }
+
+    /// Emits a batch of process output, subject to the per-process rate limit.
+    /// The batch takes the buffer lock once and goes to the log file as a
+    /// single write, so sequence numbers stay in order.
+    async fn emit_output_batch(&self, lines: Vec<String>) {
+        let mut out = Vec::with_capacity(lines.len());
+        match &self.limiter {
+            Some(l) => {
+                let mut limiter = l.lock().unwrap_or_else(|e| e.into_inner());
+                let now = std::time::Instant::now();
+                for line in lines {
+                    match limiter.admit(now) {
+                        LineAdmit::Drop => continue,
+                        LineAdmit::PassAfterDrops(n) => out.push(rate_limited_marker(n)),
+                        LineAdmit::Pass => {}
+                    }
+                    out.push(line);
                 }
             }
+            None => out = lines,
+        }
+        if out.is_empty() {
+            return;
         }
-    }
-
-    let remote_port = choose_remote_port(explicit_remote_port, alloc_ports_hint, local_port);
-
-    let mut out = String::with_capacity(raw.len().saturating_add(64));
-    let port = local_port.to_string();
-    let remote_port_str = remote_port.to_string();
 
-    for line in raw.lines() {
-        let trimmed = line.trim_start();
+        {
+            let mut buf = self.buffer.lock().await;
+            for line in &out {
+                buf.push_line(line.clone());
+            }
+        }
+        if let Some(tx) = &self.file_tx {
+            let _ = tx.send(out.join("\n"));
+        }
+    }
 
-        if trimmed.starts_with('#') || trimmed.starts_with(';') {
-            out.push_str(line);
-            out.push('\n');
-            continue;
+    /// Reports drops still pending when an output stream ends.
+    async fn flush_output(&self) {
+        let dropped = self.limiter.as_ref().map_or(0, |l| {
+            l.lock().unwrap_or_else(|e| e.into_inner()).take_dropped()
+        });
+        if dropped > 0 {
+            self.emit(rate_limited_marker(dropped)).await;
         }
+    }
 
-        let lower = trimmed.to_ascii_lowercase();
-        let indent_len = line.len().saturating_sub(trimmed.len());
-        let indent = &line[..indent_len];
+    async fn recent_lines(&self, limit: usize) -> Vec<String> {
+        let buf = self.buffer.lock().await;
+        let skip = buf.lines.len().saturating_sub(limit);
+        buf.lines
+            .iter()
+            .skip(skip)
+            .map(|l| l.text.clone())
+            .collect()
+    }
+}
 
-        if lower.starts_with("local_port") {
-            let rest = trimmed.get("local_port".len()..).unwrap_or("").trim_start();
-            if rest.is_empty() || rest.starts_with('=') || rest.starts_with(':') {
-                out.push_str(indent);
-                out.push_str("local_port = ");
-                out.push_str(&port);
-                out.push('\n');
-                continue;
+/// Pipes a child's output stream into `sink` with `prefix`. Lines already
+/// buffered behind the one just read are taken along, so a flooding process
+/// costs one lock and one file write per batch rather than per line.
+async fn pump_output(reader: impl AsyncRead + Unpin, sink: LogSink, prefix: &'static str) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let mut batch = vec![format!("{prefix} {line}")];
+        while batch.len() < OUTPUT_BATCH_MAX && lines.get_ref().buffer().contains(&b'\n') {
+            match lines.next_line().await {
+                Ok(Some(line)) => batch.push(format!("{prefix} {line}")),
+                _ => break,
             }
         }
+        sink.emit_output_batch(batch).await;
+    }
+    sink.flush_output().await;
+}
 
-        if lower.starts_with("local_ip") {
-            let rest = trimmed.get("local_ip".len()..).unwrap_or("").trim_start();
-            if rest.is_empty() || rest.starts_with('=') || rest.starts_with(':') {
-                out.push_str(indent);
-                out.push_str("local_ip = 127.0.0.1\n");
-                continue;
-            }
+/// Returns a sender whose lines are written to the console as `[install] ...`,
+/// or `None` when install output is disabled.
+fn install_output_sender(sink: &LogSink) -> Option<mpsc::UnboundedSender<String>> {
+    if !install_output_enabled() {
+        return None;
+    }
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let sink = sink.clone();
+    tokio::spawn(async move {
+        while let Some(line) = rx.recv().await {
+            sink.emit(format!("[install] {line}")).await;
         }
+    });
+    Some(tx)
+}
 
-        if lower.starts_with("remote_port") {
-            let rest = trimmed
-                .get("remote_port".len()..)
-                .unwrap_or("")
-                .trim_start();
-            if rest.is_empty() || rest.starts_with('=') || rest.starts_with(':') {
-                out.push_str(indent);
-                out.push_str("remote_port = ");
-                out.push_str(&remote_port_str);
-                out.push('\n');
-                continue;
+/// Compresses a rotated log segment to `<path>.<ext>` and removes the original.
+fn compress_log_segment(path: &Path, compression: LogCompression) -> std::io::Result<()> {
+    let out = PathBuf::from(format!("{}.{}", path.display(), compression.extension()));
+    let tmp = PathBuf::from(format!("{}.tmp", out.display()));
+    let res = (|| -> std::io::Result<()> {
+        let mut src = std::fs::File::open(path)?;
+        let dst = std::fs::File::create(&tmp)?;
+        match compression {
+            LogCompression::Gzip => {
+                let mut enc = flate2::write::GzEncoder::new(dst, flate2::Compression::default());
+                std::io::copy(&mut src, &mut enc)?;
+                enc.finish()?;
+            }
+            LogCompression::Zstd => {
+                let mut enc = zstd::Encoder::new(dst, 0)?;
+                std::io::copy(&mut src, &mut enc)?;
+                enc.finish()?;
             }
         }
-
-        out.push_str(line);
-        out.push('\n');
+        std::fs::rename(&tmp, &out)?;
+        std::fs::remove_file(path)
+    })();
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
     }
+    res
+}
 
+/// Rotation `i` of `base`, plain or compressed with any codec.
+fn log_segment_paths(base: &Path, i: usize) -> Vec<PathBuf> {
+    let plain = format!("{}.{}", base.display(), i);
+    let mut out = vec![PathBuf::from(&plain)];
+    for c in LogCompression::ALL {
+        out.push(PathBuf::from(format!("{plain}.{}", c.extension())));
+    }
     out
 }
 
-fn json_scalar_to_string(v: &serde_json::Value) -> Option<String> {
-    match v {
-        serde_json::Value::String(s) => Some(s.clone()),
-        serde_json::Value::Number(n) => Some(n.to_string()),
-        serde_json::Value::Bool(b) => Some(if *b { "true" } else { "false" }.to_string()),
-        _ => None,
+/// Deletes the oldest rotations of `base` until the rest fit in `budget` bytes.
+fn trim_log_rotations(base: &Path, max_files: usize, budget: u64) {
+    let mut segments = (1..=max_files)
+        .flat_map(|i| log_segment_paths(base, i))
+        .filter_map(|p| std::fs::metadata(&p).ok().map(|m| (p, m.len())))
+        .collect::<Vec<_>>();
+    let mut total = segments.iter().map(|(_, len)| len).sum::<u64>();
+    while total > budget {
+        let Some((oldest, len)) = segments.pop() else {
+            break;
+        };
+        if std::fs::remove_file(&oldest).is_ok() {
+            total -= len;
+        }
     }
 }
 
-fn patch_structured_frp_to_ini(
-    root: serde_json::Value,
-    local_port: u16,
-    alloc_ports_hint: &[u16],
-) -> Option<String> {
-    let obj = root.as_object()?;
+struct FileLogWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    bytes: u64,
+    file: tokio::fs::File,
+    compression: Option<LogCompression>,
+    total_max_bytes: Option<u64>,
+    // Compression and trimming after the previous rotation; finished before
+    // the next shift.
+    pending: Option<tokio::task::JoinHandle<()>>,
+}
 
-    let mut common = BTreeMap::<String, String>::new();
-    if let Some(common_obj) = obj.get("common").and_then(|v| v.as_object()) {
-        for (k, v) in common_obj {
-            if let Some(s) = json_scalar_to_string(v) {
-                common.insert(k.clone(), s);
-            }
+impl FileLogWriter {
+    async fn open(path: PathBuf, max_bytes: u64, max_files: usize) -> std::io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
         }
-    }
 
-    let mut alloc_ports = common
-        .get("alloy_alloc_ports")
-        .map(|s| parse_allocatable_ports_spec(s))
-        .filter(|v| !v.is_empty())
-        .or_else(|| {
-            common
-                .get("allocatable_ports")
-                .map(|s| parse_allocatable_ports_spec(s))
-                .filter(|v| !v.is_empty())
+        let bytes = tokio::fs::metadata(&path)
+            .await
+            .map(|m| m.len())
+            .unwrap_or(0);
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+
+        Ok(Self {
+            path,
+            max_bytes,
+            max_files,
+            bytes,
+            file,
+            compression: log_compression(),
+            total_max_bytes: log_total_max_bytes(),
+            pending: None,
         })
-        .unwrap_or_else(|| alloc_ports_hint.to_vec());
-    if alloc_ports.is_empty() {
-        alloc_ports = alloc_ports_hint.to_vec();
     }
 
-    let mut proxies: Vec<(String, BTreeMap<String, String>)> = Vec::new();
+    fn segment_paths(&self, i: usize) -> Vec<PathBuf> {
+        log_segment_paths(&self.path, i)
+    }
 
-    for (k, v) in obj {
-        if k == "common" || k == "proxies" {
-            continue;
-        }
-        let Some(m) = v.as_object() else {
-            continue;
-        };
-        let mut vals = BTreeMap::<String, String>::new();
-        for (kk, vv) in m {
-            if let Some(s) = json_scalar_to_string(vv) {
-                vals.insert(kk.clone(), s);
-            }
+    async fn rotate(&mut self) -> std::io::Result<()> {
+        let _ = self.file.flush().await;
+        if let Some(task) = self.pending.take() {
+            let _ = task.await;
         }
-        proxies.push((k.clone(), vals));
-    }
 
-    if let Some(arr) = obj.get("proxies").and_then(|v| v.as_array()) {
-        for (idx, item) in arr.iter().enumerate() {
-            let Some(m) = item.as_object() else {
-                continue;
-            };
-            let mut vals = BTreeMap::<String, String>::new();
-            for (kk, vv) in m {
-                if let Some(s) = json_scalar_to_string(vv) {
-                    vals.insert(kk.clone(), s);
+        // Drop the oldest rotation, then shift: .(n-1)[.ext] -> .n[.ext]
+        for oldest in self.segment_paths(self.max_files) {
+            let _ = tokio::fs::remove_file(oldest).await;
+        }
+        for i in (1..self.max_files).rev() {
+            for (from, to) in self
+                .segment_paths(i)
+                .into_iter()
+                .zip(self.segment_paths(i + 1))
+            {
+                if tokio::fs::metadata(&from).await.is_ok() {
+                    let _ = tokio::fs::rename(from, to).await;
                 }
             }
-            let name = vals
-                .get("name")
-                .cloned()
-                .filter(|s| !s.trim().is_empty())
-                .unwrap_or_else(|| format!("proxy{}", idx + 1));
-            proxies.push((name, vals));
         }
-    }
 
-    if proxies.is_empty() {
-        proxies.push(("alloy".to_string(), BTreeMap::new()));
-    }
+        // Current -> .1
+        let rotated = PathBuf::from(format!("{}.1", self.path.display()));
+        if tokio::fs::metadata(&self.path).await.is_ok() {
+            let _ = tokio::fs::rename(&self.path, &rotated).await;
+        }
 
-    for (_, vals) in proxies.iter_mut() {
-        let explicit_remote = vals
-            .get("remote_port")
-            .and_then(|v| parse_port_scalar(v))
-            .or_else(|| vals.get("remotePort").and_then(|v| parse_port_scalar(v)));
-        let remote = choose_remote_port(explicit_remote, &alloc_ports, local_port);
+        // .1 stays plain for tailing; the segment shifted past it is compressed,
+        // then the total budget applied, off the write path.
+        let second = PathBuf::from(format!("{}.2", self.path.display()));
+        let compression = match self.compression {
+            Some(c) if tokio::fs::metadata(&second).await.is_ok() => Some(c),
+            _ => None,
+        };
+        if compression.is_some() || self.total_max_bytes.is_some() {
+            let (base, max_files, budget) =
+                (self.path.clone(), self.max_files, self.total_max_bytes);
+            self.pending = Some(tokio::task::spawn_blocking(move || {
+                if let Some(compression) = compression {
+                    let _ = compress_log_segment(&second, compression);
+                }
+                if let Some(budget) = budget {
+                    trim_log_rotations(&base, max_files, budget);
+                }
+            }));
+        }
 
-        vals.remove("localIP");
-        vals.remove("localPort");
-        vals.remove("remotePort");
-        vals.insert("local_ip".to_string(), "127.0.0.1".to_string());
-        vals.insert("local_port".to_string(), local_port.to_string());
-        vals.insert("remote_port".to_string(), remote.to_string());
-        vals.entry("type".to_string())
-            .or_insert_with(|| "tcp".to_string());
+        self.file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        self.bytes = 0;
+        Ok(())
     }
 
-    common.remove("alloy_alloc_ports");
-    common.remove("allocatable_ports");
+    async fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let mut line = line.to_string();
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
 
-    let mut out = String::new();
-    out.push_str("[common]\n");
-    for (k, v) in common {
-        out.push_str(&format!("{k} = {v}\n"));
-    }
-    if !alloc_ports.is_empty() {
-        let spec = alloc_ports
-            .iter()
-            .map(|p| p.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
-        out.push_str(&format!("# alloy_alloc_ports = {spec}\n"));
+        let write_len = line.len() as u64;
+        if self.max_bytes > 0 && self.bytes.saturating_add(write_len) > self.max_bytes {
+            self.rotate().await.ok();
+        }
+
+        self.file.write_all(line.as_bytes()).await?;
+        self.bytes = self.bytes.saturating_add(write_len);
+        Ok(())
     }
+}
 
-    for (name, vals) in proxies {
-        out.push('\n');
-        out.push_str(&format!("[{name}]\n"));
-        for (k, v) in vals {
-            out.push_str(&format!("{k} = {v}\n"));
+/// Appends lines sent on the returned channel to `path`, rotating per the log file limits.
+fn spawn_console_log_writer(path: PathBuf) -> mpsc::UnboundedSender<String> {
+    let (max_bytes, max_files) = log_file_limits();
+    let (log_tx, mut log_rx) = mpsc::unbounded_channel::<String>();
+    tokio::spawn(async move {
+        let Ok(mut writer) = FileLogWriter::open(path, max_bytes, max_files).await else {
+            return;
+        };
+        while let Some(line) = log_rx.recv().await {
+            let _ = writer.write_line(&line).await;
         }
-    }
+    });
+    log_tx
+}
 
-    Some(out)
+/// Last `max_lines` lines of a console log, oldest first.
+async fn read_log_tail(path: &Path, max_lines: usize) -> Vec<String> {
+    let Ok(raw) = tokio::fs::read(path).await else {
+        return Vec::new();
+    };
+    let text = String::from_utf8_lossy(&raw);
+    let mut lines: Vec<String> = text
+        .lines()
+        .rev()
+        .take(max_lines)
+        .map(str::to_string)
+        .collect();
+    lines.reverse();
+    lines
 }
 
-fn patch_frp_config(raw: &str, local_port: u16) -> String {
-    let format = detect_frp_config_format(raw);
-    let alloc_ports_hint = parse_allocatable_ports_hint(raw);
+#[derive(Debug, Clone, Serialize)]
+struct RunInfo {
+    process_id: String,
+    template_id: String,
+    started_at_unix_ms: u64,
+    agent_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pgid: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    container_id: Option<String>,
+    exec: String,
+    args: Vec<String>,
+    cwd: String,
+    // Params are redacted for known secret keys.
+    params: BTreeMap<String, String>,
+    env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    companions: Vec<CompanionRun>,
+}
 
-    match format {
-        FrpConfigFormat::Ini => patch_frpc_ini(raw, local_port, &alloc_ports_hint),
-        FrpConfigFormat::Json => serde_json::from_str::<serde_json::Value>(raw)
-            .ok()
-            .and_then(|root| patch_structured_frp_to_ini(root, local_port, &alloc_ports_hint))
-            .unwrap_or_else(|| patch_frpc_ini(raw, local_port, &alloc_ports_hint)),
-        FrpConfigFormat::Toml => raw
-            .parse::<toml::Value>()
-            .ok()
-            .and_then(|v| serde_json::to_value(v).ok())
-            .and_then(|root| patch_structured_frp_to_ini(root, local_port, &alloc_ports_hint))
-            .unwrap_or_else(|| patch_frpc_ini(raw, local_port, &alloc_ports_hint)),
-        FrpConfigFormat::Yaml => serde_yaml::from_str::<serde_yaml::Value>(raw)
-            .ok()
-            .and_then(|v| serde_json::to_value(v).ok())
-            .and_then(|root| patch_structured_frp_to_ini(root, local_port, &alloc_ports_hint))
-            .unwrap_or_else(|| patch_frpc_ini(raw, local_port, &alloc_ports_hint)),
-    }
+#[derive(Debug, Clone, Serialize)]
+struct CompanionRun {
+    name: String,
+    pid: Option<u32>,
+    pgid: Option<i32>,
 }
 
-async fn start_frpc_sidecar(
-    sink: LogSink,
-    instance_dir: PathBuf,
-    owner_pgid: i32,
-    local_port: u16,
-    config_raw: String,
-) -> anyhow::Result<()> {
-    let cfg_dir = instance_dir.join("config");
-    let cfg_path = cfg_dir.join("frpc.ini");
-    let detected = detect_frp_config_format(&config_raw);
-    let patched = patch_frp_config(&config_raw, local_port);
+/// run.json as written by the last launch, for the Inspect view.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct RunInfoView {
+    pub process_id: String,
+    pub template_id: String,
+    pub started_at_unix_ms: u64,
+    #[serde(default)]
+    pub agent_version: String,
+    pub pid: Option<u32>,
+    pub container_name: Option<String>,
+    pub container_id: Option<String>,
+    pub exec: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub cwd: String,
+    // Already redacted on disk.
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
 
-    tokio::fs::create_dir_all(&cfg_dir)
-        .await
-        .context("create frpc config dir")?;
+#[derive(Debug, Clone, serde::Deserialize)]
+struct RunContainerMeta {
+    container_name: Option<String>,
+    container_id: Option<String>,
+}
 
-    let tmp = cfg_path.with_extension("ini.tmp");
-    tokio::fs::write(&tmp, patched.as_bytes())
+pub fn redact_params(mut params: BTreeMap<String, String>) -> BTreeMap<String, String> {
+    for (k, v) in params.iter_mut() {
+        if alloy_proto::is_secret_param_key(k) && !v.is_empty() {
+            *v = "<redacted>".to_string();
+        }
+    }
+    params
+}
+
+async fn write_run_json(dir: &Path, info: &RunInfo) -> anyhow::Result<()> {
+    tokio::fs::create_dir_all(dir)
         .await
-        .context("write frpc config tmp")?;
-    tokio::fs::rename(&tmp, &cfg_path)
+        .context("create instance dir")?;
+    let path = dir.join("run.json");
+    let tmp = dir.join("run.json.tmp");
+    let data = serde_json::to_vec_pretty(info).context("serialize run.json")?;
+    let mut f = tokio::fs::File::create(&tmp)
         .await
-        .context("persist frpc config")?;
-
-    let exec = std::env::var("ALLOY_FRPC_PATH").unwrap_or_else(|_| "frpc".to_string());
-
-    sink.emit(format!(
-        "[alloy-agent] starting frpc tunnel (local_port={local_port}, source={detected:?})"
-    ))
-    .await;
-
-    let mut cmd = Command::new(&exec);
-    cmd.current_dir(&instance_dir)
-        .arg("-c")
-        .arg(&cfg_path)
-        .stdin(std::process::Stdio::null())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped());
+        .context("create run.json.tmp")?;
+    f.write_all(&data).await.context("write run.json.tmp")?;
+    f.flush().await.ok();
+    tokio::fs::rename(&tmp, &path)
+        .await
+        .context("persist run.json")?;
+    Ok(())
+}
 
+/// Un-redacted launch params, kept next to run.json so a restart can reuse them.
+/// Node defaults are left out; the next start merges them again.
+async fn write_params_json(dir: &Path, params: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    let path = dir.join("params.json");
+    let tmp = dir.join("params.json.tmp");
+    let params = crate::node_defaults::strip_inherited(params, &crate::node_defaults::load().await);
+    let data = serde_json::to_vec_pretty(&params).context("serialize params.json")?;
+    tokio::fs::write(&tmp, &data)
+        .await
+        .context("write params.json.tmp")?;
+    // Params may carry secrets (rcon/frp tokens).
     #[cfg(unix)]
     {
-        unsafe {
-            cmd.pre_exec(move || {
-                set_parent_death_signal()?;
-                if libc::setpgid(0, owner_pgid) == -1 {
-                    return Err(std::io::Error::last_os_error());
-                }
-                Ok(())
-            });
+        use std::os::unix::fs::PermissionsExt;
+        let _ = tokio::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600)).await;
+    }
+    tokio::fs::rename(&tmp, &path)
+        .await
+        .context("persist params.json")?;
+    Ok(())
+}
+
+fn collect_safe_env() -> BTreeMap<String, String> {
+    let mut out = BTreeMap::new();
+    for key in ["ALLOY_DATA_ROOT", "JAVA_HOME", "LD_LIBRARY_PATH", "PATH"] {
+        if let Ok(v) = std::env::var(key) {
+            let val = if key == "PATH" && v.len() > 512 {
+                format!("{}…(truncated)", &v[..512])
+            } else {
+                v
+            };
+            out.insert(key.to_string(), val);
         }
     }
+    out
+}
 
-    let mut child = cmd
-        .spawn()
-        .with_context(|| format!("spawn frpc: exec={exec} (cfg {})", cfg_path.display()))?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrpConfigFormat {
+    Ini,
+    Json,
+    Toml,
+    Yaml,
+}
 
-    let stdout = child.stdout.take();
-    let stderr = child.stderr.take();
+impl FrpConfigFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FrpConfigFormat::Ini => "ini",
+            FrpConfigFormat::Json => "json",
+            FrpConfigFormat::Toml => "toml",
+            FrpConfigFormat::Yaml => "yaml",
+        }
+    }
+}
 
-    if let Some(out) = stdout {
-        let sink = sink.clone();
-        tokio::spawn(async move {
-            let mut lines = BufReader::new(out).lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                sink.emit(format!("[frpc stdout] {line}")).await;
-            }
-        });
+fn detect_frp_config_format(raw: &str) -> FrpConfigFormat {
+    let s = raw.trim();
+    if s.is_empty() {
+        return FrpConfigFormat::Ini;
     }
-    if let Some(err) = stderr {
-        let sink = sink.clone();
-        tokio::spawn(async move {
-            let mut lines = BufReader::new(err).lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                sink.emit(format!("[frpc stderr] {line}")).await;
-            }
-        });
+    if serde_json::from_str::<serde_json::Value>(s).is_ok() {
+        return FrpConfigFormat::Json;
+    }
+    if s.parse::<toml::Value>().is_ok() {
+        return FrpConfigFormat::Toml;
     }
+    if serde_yaml::from_str::<serde_yaml::Value>(s).is_ok() {
+        return FrpConfigFormat::Yaml;
+    }
+    FrpConfigFormat::Ini
+}
 
-    let wait_sink = sink.clone();
-    tokio::spawn(async move {
-        let res = child.wait().await;
-        match res {
-            Ok(st) => {
-                wait_sink
-                    .emit(format!("[alloy-agent] frpc exited: {st}"))
-                    .await
+fn parse_port_scalar(raw: &str) -> Option<u16> {
+    let s = raw.trim().trim_matches('"').trim_matches('\'');
+    let p = s.parse::<u16>().ok()?;
+    if p == 0 {
+        return None;
+    }
+    Some(p)
+}
+
+fn parse_allocatable_ports_spec(raw: &str) -> Vec<u16> {
+    let mut out = BTreeSet::<u16>::new();
+    for seg in raw.split(',') {
+        let token = seg.trim();
+        if token.is_empty() {
+            continue;
+        }
+        if let Some((a_raw, b_raw)) = token.split_once('-') {
+            let Some(a) = parse_port_scalar(a_raw) else {
+                continue;
+            };
+            let Some(b) = parse_port_scalar(b_raw) else {
+                continue;
+            };
+            let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+            if hi.saturating_sub(lo) > 4000 {
+                continue;
             }
-            Err(e) => {
-                wait_sink
-                    .emit(format!("[alloy-agent] frpc wait failed: {e}"))
-                    .await
+            for p in lo..=hi {
+                out.insert(p);
+                if out.len() > 4000 {
+                    break;
+                }
             }
+        } else if let Some(port) = parse_port_scalar(token) {
+            out.insert(port);
+        }
+        if out.len() > 4000 {
+            break;
         }
-    });
-
-    Ok(())
-}
-
-#[cfg(target_os = "linux")]
-unsafe fn set_parent_death_signal() -> std::io::Result<()> {
-    // If the agent process dies (crash/kill), ensure the child is terminated.
-    // NOTE: `unsafe fn` bodies are not implicitly unsafe in Rust 2024.
-    let rc = unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) };
-    if rc == -1 {
-        return Err(std::io::Error::last_os_error());
     }
-    Ok(())
+    out.into_iter().collect()
 }
 
-#[cfg(not(target_os = "linux"))]
-unsafe fn set_parent_death_signal() -> std::io::Result<()> {
-    Ok(())
-}
-
-fn prepare_instance_command(
-    process_id: &str,
-    template_id: &str,
-    params: &BTreeMap<String, String>,
-    instance_dir: &Path,
-    cwd: &Path,
-    exec: &str,
-    args: &[String],
-    extra_rw_paths: &[PathBuf],
-) -> anyhow::Result<(Command, sandbox::SandboxLaunch)> {
-    let launch = sandbox::prepare_launch(
-        process_id,
-        template_id,
-        params,
-        instance_dir,
-        cwd,
-        exec,
-        args,
-        extra_rw_paths,
-    )?;
-
-    let mut cmd = Command::new(&launch.exec);
-    cmd.current_dir(&launch.cwd)
-        .args(&launch.args)
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped());
-
-    #[cfg(unix)]
-    {
-        let limits = launch.limits.clone();
-        let apply_host_limits = launch.should_apply_host_limits();
-        unsafe {
-            cmd.pre_exec(move || {
-                set_parent_death_signal()?;
-                if libc::setpgid(0, 0) == -1 {
-                    return Err(std::io::Error::last_os_error());
-                }
-                if apply_host_limits {
-                    limits.apply_pre_exec()?;
-                }
-                Ok(())
-            });
+fn parse_allocatable_ports_hint(raw: &str) -> Vec<u16> {
+    for line in raw.lines() {
+        let s = line.trim();
+        if s.is_empty() {
+            continue;
+        }
+        let body = s
+            .strip_prefix('#')
+            .or_else(|| s.strip_prefix(';'))
+            .or_else(|| s.strip_prefix("//"))
+            .map(str::trim)
+            .unwrap_or(s);
+        if let Some((k, v)) = body.split_once('=') {
+            let key = k.trim().to_ascii_lowercase();
+            if key == "alloy_alloc_ports" || key == "allocatable_ports" {
+                return parse_allocatable_ports_spec(v);
+            }
+        }
+        if let Some((k, v)) = body.split_once(':') {
+            let key = k.trim().to_ascii_lowercase();
+            if key == "alloy_alloc_ports" || key == "allocatable_ports" {
+                return parse_allocatable_ports_spec(v);
+            }
         }
     }
-
-    Ok((cmd, launch))
+    Vec::new()
 }
 
-fn docker_no_such_container(stderr: &str) -> bool {
-    let msg = stderr.to_ascii_lowercase();
-    msg.contains("no such container") || msg.contains("is not running")
+fn choose_remote_port(explicit: Option<u16>, alloc_ports: &[u16], local_port: u16) -> u16 {
+    if let Some(v) = explicit {
+        return v;
+    }
+    if alloc_ports.is_empty() {
+        return local_port;
+    }
+    let idx = usize::from(local_port) % alloc_ports.len();
+    alloc_ports[idx]
 }
 
-fn first_non_empty_line(stdout: &[u8]) -> Option<String> {
-    String::from_utf8_lossy(stdout)
-        .lines()
-        .map(str::trim)
-        .find(|line| !line.is_empty())
-        .map(str::to_string)
+fn normalize_ini_scalar_value(raw: &str) -> String {
+    raw.trim()
+        .split(['#', ';'])
+        .next()
+        .unwrap_or("")
+        .trim()
+        .trim_matches('"')
+        .trim_matches('\'')
+        .to_string()
 }
 
-async fn read_run_container_meta(process_id: &str) -> Option<RunContainerMeta> {
-    let data_root = crate::minecraft::data_root();
-    for dir in ["instances", "processes"] {
-        let path = data_root.join(dir).join(process_id).join("run.json");
-        let raw = match tokio::fs::read(&path).await {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-        if let Ok(meta) = serde_json::from_slice::<RunContainerMeta>(&raw) {
-            return Some(meta);
-        }
-    }
-    None
+/// Picks the proxy that fronts the game port: one named `game`, else the first
+/// tcp proxy (frp's default type), else the first proxy.
+fn primary_proxy<'a>(
+    proxies: impl IntoIterator<Item = (&'a str, Option<&'a str>)>,
+) -> Option<usize> {
+    let proxies: Vec<_> = proxies.into_iter().collect();
+    proxies
+        .iter()
+        .position(|(name, _)| name.eq_ignore_ascii_case("game"))
+        .or_else(|| {
+            proxies
+                .iter()
+                .position(|(_, ty)| ty.is_none_or(|t| t.eq_ignore_ascii_case("tcp")))
+        })
+        .or_else(|| (!proxies.is_empty()).then_some(0))
 }
 
-async fn docker_find_container_by_name(container_name: &str) -> Option<String> {
-    let name_filter = format!("name=^/{container_name}$");
-    let output = Command::new("docker")
-        .env_remove("DOCKER_API_VERSION")
-        .arg("ps")
-        .arg("-aq")
-        .arg("--no-trunc")
-        .arg("--filter")
-        .arg(name_filter)
-        .output()
-        .await
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
-    }
-
-    first_non_empty_line(&output.stdout)
+fn ini_section_name(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix('[')?
+        .strip_suffix(']')
+        .map(str::trim)
 }
 
-async fn docker_find_container_by_process(process_id: &str) -> Option<String> {
-    let filter = format!("label=alloy.process_id={process_id}");
-    let output = Command::new("docker")
-        .env_remove("DOCKER_API_VERSION")
-        .arg("ps")
-        .arg("-q")
-        .arg("--no-trunc")
-        .arg("--filter")
-        .arg(filter)
-        .output()
-        .await
-        .ok()?;
-
-    if !output.status.success() {
+/// `key = value` (or `key: value`) with the key lowercased; None for
+/// comments, headers and blank lines.
+fn ini_entry(line: &str) -> Option<(String, &str)> {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with(['#', ';', '[']) {
         return None;
     }
-
-    first_non_empty_line(&output.stdout)
+    let idx = trimmed.find(['=', ':'])?;
+    Some((
+        trimmed[..idx].trim().to_ascii_lowercase(),
+        &trimmed[idx + 1..],
+    ))
 }
 
-async fn find_container_for_process(process_id: &str) -> Option<String> {
-    if let Some(meta) = read_run_container_meta(process_id).await {
-        if let Some(container_id) = meta.container_id.filter(|v| !v.trim().is_empty()) {
-            return Some(container_id);
-        }
-        if let Some(container_name) = meta.container_name.filter(|v| !v.trim().is_empty())
-            && let Some(container_id) = docker_find_container_by_name(&container_name).await
+/// Rewrites `local_ip`/`local_port`/`remote_port` of the primary proxy only;
+/// every other line, including other proxies, is kept verbatim.
+fn patch_frpc_ini(raw: &str, local_port: u16, alloc_ports_hint: &[u16]) -> String {
+    let lines: Vec<&str> = raw.lines().collect();
+
+    // (name, header line index, type) for every proxy section.
+    let mut sections: Vec<(&str, usize, Option<String>)> = Vec::new();
+    let mut in_proxy = false;
+    for (idx, line) in lines.iter().enumerate() {
+        if let Some(name) = ini_section_name(line) {
+            in_proxy = !name.eq_ignore_ascii_case("common");
+            if in_proxy {
+                sections.push((name, idx, None));
+            }
+        } else if in_proxy
+            && let Some((key, value)) = ini_entry(line)
+            && key == "type"
+            && let Some(section) = sections.last_mut()
         {
-            return Some(container_id);
+            section.2 = Some(normalize_ini_scalar_value(value));
         }
     }
+    let primary = primary_proxy(sections.iter().map(|(n, _, t)| (*n, t.as_deref())));
+    let Some(header) = primary.map(|i| sections[i].1) else {
+        return raw.to_string();
+    };
+    let end = (header + 1..lines.len())
+        .find(|&idx| ini_section_name(lines[idx]).is_some())
+        .unwrap_or(lines.len());
+
+    let explicit_remote_port = lines[header + 1..end]
+        .iter()
+        .filter_map(|l| ini_entry(l))
+        .find(|(k, _)| k == "remote_port")
+        .and_then(|(_, v)| parse_port_scalar(&normalize_ini_scalar_value(v)));
+    let remote_port = choose_remote_port(explicit_remote_port, alloc_ports_hint, local_port);
+    let overrides = [
+        ("local_ip", "127.0.0.1".to_string()),
+        ("local_port", local_port.to_string()),
+        ("remote_port", remote_port.to_string()),
+    ];
 
-    docker_find_container_by_process(process_id).await
-}
+    let mut out = String::with_capacity(raw.len().saturating_add(64));
+    for (idx, line) in lines.iter().enumerate() {
+        let key = if header < idx && idx < end {
+            ini_entry(line).map(|(k, _)| k)
+        } else {
+            None
+        };
+        if let Some((name, value)) = key.and_then(|k| overrides.iter().find(|(n, _)| *n == k)) {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            out.push_str(&format!("{indent}{name} = {value}\n"));
+            continue;
+        }
+        out.push_str(line);
+        out.push('\n');
 
-async fn refresh_docker_container_metadata(process_id: &str, run: &mut RunInfo) {
-    if run.container_name.is_none() {
-        return;
-    }
-    if run.container_id.is_some() {
-        return;
+        if idx == header {
+            // Keys the proxy leaves out still have to point at the game port.
+            for (name, value) in &overrides {
+                let present = lines[header + 1..end]
+                    .iter()
+                    .any(|l| ini_entry(l).is_some_and(|(k, _)| k == *name));
+                if !present {
+                    out.push_str(&format!("{name} = {value}\n"));
+                }
+            }
+        }
     }
 
-    if let Some(container_id) = docker_find_container_by_process(process_id).await {
-        run.container_id = Some(container_id);
-    }
+    out
 }
 
-async fn docker_stop_container(container_id: &str, stop_timeout_secs: u64) -> anyhow::Result<()> {
-    let timeout_secs = stop_timeout_secs.max(1).to_string();
-    let output = Command::new("docker")
-        .env_remove("DOCKER_API_VERSION")
-        .arg("stop")
-        .arg("--time")
-        .arg(&timeout_secs)
-        .arg(container_id)
-        .output()
-        .await
-        .with_context(|| format!("run `docker stop` for container {container_id}"))?;
+fn json_scalar_to_string(v: &serde_json::Value) -> Option<String> {
+    match v {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(if *b { "true" } else { "false" }.to_string()),
+        _ => None,
+    }
+}
 
-    if output.status.success() {
-        return Ok(());
+/// frp's newer TOML/JSON/YAML keys are camelCase; the INI sidecar config is snake_case.
+fn ini_key(key: &str) -> String {
+    let mut out = String::with_capacity(key.len() + 4);
+    let mut prev_lower = false;
+    for c in key.chars() {
+        if c.is_ascii_uppercase() && prev_lower {
+            out.push('_');
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        out.push(c.to_ascii_lowercase());
     }
+    out
+}
 
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    if docker_no_such_container(&stderr) {
-        return Ok(());
+/// Copies scalar fields, joins scalar lists (`customDomains`) with commas and
+/// lifts `transport.*` (`useEncryption`, ...) to the top, as INI expects.
+fn collect_ini_fields(
+    m: &serde_json::Map<String, serde_json::Value>,
+    out: &mut BTreeMap<String, String>,
+) {
+    for (k, v) in m {
+        match v {
+            serde_json::Value::Object(inner) if k == "transport" => collect_ini_fields(inner, out),
+            serde_json::Value::Array(items) => {
+                let items: Vec<String> = items.iter().filter_map(json_scalar_to_string).collect();
+                if !items.is_empty() {
+                    out.insert(ini_key(k), items.join(","));
+                }
+            }
+            _ => {
+                if let Some(s) = json_scalar_to_string(v) {
+                    out.insert(ini_key(k), s);
+                }
+            }
+        }
     }
+}
 
-    anyhow::bail!(
-        "docker stop failed for {container_id}: {}",
-        stderr.trim().to_string()
-    );
+fn looks_like_proxy(m: &serde_json::Map<String, serde_json::Value>) -> bool {
+    m.keys().any(|k| {
+        matches!(
+            ini_key(k).as_str(),
+            "type" | "local_port" | "remote_port" | "custom_domains" | "subdomain"
+        )
+    })
 }
 
-async fn docker_kill_container(container_id: &str) -> anyhow::Result<()> {
-    let output = Command::new("docker")
-        .env_remove("DOCKER_API_VERSION")
-        .arg("kill")
-        .arg(container_id)
-        .output()
-        .await
-        .with_context(|| format!("run `docker kill` for container {container_id}"))?;
+fn patch_structured_frp_to_ini(
+    root: serde_json::Value,
+    local_port: u16,
+    alloc_ports_hint: &[u16],
+) -> Option<String> {
+    let obj = root.as_object()?;
 
-    if output.status.success() {
-        return Ok(());
+    let mut common = BTreeMap::<String, String>::new();
+    if let Some(common_obj) = obj.get("common").and_then(|v| v.as_object()) {
+        for (k, v) in common_obj {
+            if let Some(s) = json_scalar_to_string(v) {
+                common.insert(k.clone(), s);
+            }
+        }
     }
-
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    if docker_no_such_container(&stderr) {
-        return Ok(());
+    // Newer configs keep client settings (serverAddr, ...) at the top level.
+    for (k, v) in obj {
+        if let Some(s) = json_scalar_to_string(v) {
+            common.entry(ini_key(k)).or_insert(s);
+        }
     }
 
-    anyhow::bail!(
-        "docker kill failed for {container_id}: {}",
-        stderr.trim().to_string()
-    );
-}
+    let mut alloc_ports = common
+        .get("alloy_alloc_ports")
+        .map(|s| parse_allocatable_ports_spec(s))
+        .filter(|v| !v.is_empty())
+        .or_else(|| {
+            common
+                .get("allocatable_ports")
+                .map(|s| parse_allocatable_ports_spec(s))
+                .filter(|v| !v.is_empty())
+        })
+        .unwrap_or_else(|| alloc_ports_hint.to_vec());
+    if alloc_ports.is_empty() {
+        alloc_ports = alloc_ports_hint.to_vec();
+    }
+
+    let mut proxies: Vec<(String, BTreeMap<String, String>)> = Vec::new();
+
+    for (k, v) in obj {
+        if k == "common" || k == "proxies" {
+            continue;
+        }
+        let Some(m) = v.as_object().filter(|m| looks_like_proxy(m)) else {
+            continue;
+        };
+        let mut vals = BTreeMap::<String, String>::new();
+        collect_ini_fields(m, &mut vals);
+        proxies.push((k.clone(), vals));
+    }
+
+    if let Some(arr) = obj.get("proxies").and_then(|v| v.as_array()) {
+        for (idx, item) in arr.iter().enumerate() {
+            let Some(m) = item.as_object() else {
+                continue;
+            };
+            let mut vals = BTreeMap::<String, String>::new();
+            collect_ini_fields(m, &mut vals);
+            let name = vals
+                .remove("name")
+                .filter(|s| !s.trim().is_empty())
+                .unwrap_or_else(|| format!("proxy{}", idx + 1));
+            proxies.push((name, vals));
+        }
+    }
+
+    if proxies.is_empty() {
+        proxies.push(("alloy".to_string(), BTreeMap::new()));
+    }
+
+    let primary = primary_proxy(
+        proxies
+            .iter()
+            .map(|(name, vals)| (name.as_str(), vals.get("type").map(String::as_str))),
+    )
+    .unwrap_or_default();
+    for (idx, (_, vals)) in proxies.iter_mut().enumerate() {
+        vals.entry("type".to_string())
+            .or_insert_with(|| "tcp".to_string());
+        if idx != primary {
+            continue;
+        }
+        let explicit_remote = vals.get("remote_port").and_then(|v| parse_port_scalar(v));
+        let remote = choose_remote_port(explicit_remote, &alloc_ports, local_port);
+        vals.insert("local_ip".to_string(), "127.0.0.1".to_string());
+        vals.insert("local_port".to_string(), local_port.to_string());
+        vals.insert("remote_port".to_string(), remote.to_string());
+    }
+
+    common.remove("alloy_alloc_ports");
+    common.remove("allocatable_ports");
+
+    let mut out = String::new();
+    out.push_str("[common]\n");
+    for (k, v) in common {
+        out.push_str(&format!("{k} = {v}\n"));
+    }
+    if !alloc_ports.is_empty() {
+        let spec = alloc_ports
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        out.push_str(&format!("# alloy_alloc_ports = {spec}\n"));
+    }
+
+    for (name, vals) in proxies {
+        out.push('\n');
+        out.push_str(&format!("[{name}]\n"));
+        for (k, v) in vals {
+            out.push_str(&format!("{k} = {v}\n"));
+        }
+    }
+
+    Some(out)
+}
+
+/// frp v0.52+ layout: client settings at the top level (`serverAddr`) and
+/// camelCase proxies in a `proxies` list.
+fn is_frp_v2(root: &serde_json::Value) -> bool {
+    root.as_object().is_some_and(|obj| {
+        !obj.contains_key("common")
+            && (obj.get("proxies").is_some_and(serde_json::Value::is_array)
+                || obj.contains_key("serverAddr")
+                || obj.contains_key("serverPort"))
+    })
+}
+
+/// Points the primary v2 proxy at the game port in place; other proxies and
+/// fields are left as they are.
+fn patch_frp_v2(root: &mut serde_json::Value, local_port: u16, alloc_ports_hint: &[u16]) {
+    let Some(obj) = root.as_object_mut() else {
+        return;
+    };
+    let proxies = obj
+        .entry("proxies")
+        .or_insert_with(|| serde_json::Value::Array(Vec::new()));
+    let Some(proxies) = proxies.as_array_mut() else {
+        return;
+    };
+    if proxies.is_empty() {
+        proxies.push(serde_json::json!({ "name": "alloy", "type": "tcp" }));
+    }
+
+    let str_field = |p: &serde_json::Value, key: &str| -> Option<String> {
+        p.get(key)
+            .and_then(serde_json::Value::as_str)
+            .map(str::to_string)
+    };
+    let fields: Vec<(String, Option<String>)> = proxies
+        .iter()
+        .map(|p| {
+            (
+                str_field(p, "name").unwrap_or_default(),
+                str_field(p, "type"),
+            )
+        })
+        .collect();
+    let primary = primary_proxy(fields.iter().map(|(n, t)| (n.as_str(), t.as_deref())));
+    let Some(proxy) = primary
+        .and_then(|i| proxies.get_mut(i))
+        .and_then(serde_json::Value::as_object_mut)
+    else {
+        return;
+    };
+
+    let explicit_remote = proxy
+        .get("remotePort")
+        .and_then(json_scalar_to_string)
+        .and_then(|v| parse_port_scalar(&v));
+    let remote = choose_remote_port(explicit_remote, alloc_ports_hint, local_port);
+    proxy.insert("localIP".to_string(), "127.0.0.1".into());
+    proxy.insert("localPort".to_string(), local_port.into());
+    proxy.insert("remotePort".to_string(), remote.into());
+}
+
+/// Returns the patched config and the format it is written in. v2 configs keep
+/// their format because frpc v0.52+ no longer reads INI; legacy structured
+/// configs are converted to INI for older frpc builds.
+fn patch_frp_config(raw: &str, local_port: u16) -> (String, FrpConfigFormat) {
+    let format = detect_frp_config_format(raw);
+    let alloc_ports_hint = parse_allocatable_ports_hint(raw);
+
+    let root = match format {
+        FrpConfigFormat::Ini => None,
+        FrpConfigFormat::Json => serde_json::from_str::<serde_json::Value>(raw).ok(),
+        FrpConfigFormat::Toml => raw
+            .parse::<toml::Value>()
+            .ok()
+            .and_then(|v| serde_json::to_value(v).ok()),
+        FrpConfigFormat::Yaml => serde_yaml::from_str::<serde_yaml::Value>(raw)
+            .ok()
+            .and_then(|v| serde_json::to_value(v).ok()),
+    };
+    let Some(mut root) = root else {
+        return (
+            patch_frpc_ini(raw, local_port, &alloc_ports_hint),
+            FrpConfigFormat::Ini,
+        );
+    };
+
+    if is_frp_v2(&root) {
+        patch_frp_v2(&mut root, local_port, &alloc_ports_hint);
+        let text = match format {
+            FrpConfigFormat::Json => serde_json::to_string_pretty(&root).ok(),
+            FrpConfigFormat::Yaml => serde_yaml::to_string(&root).ok(),
+            _ => toml::to_string(&root).ok(),
+        };
+        if let Some(text) = text {
+            return (text, format);
+        }
+    }
+
+    let ini = patch_structured_frp_to_ini(root, local_port, &alloc_ports_hint)
+        .unwrap_or_else(|| patch_frpc_ini(raw, local_port, &alloc_ports_hint));
+    (ini, FrpConfigFormat::Ini)
+}
+
+/// The frpc binary and whether configs are pre-flighted with `frpc verify`.
+struct Frpc {
+    exec: String,
+    verify: bool,
+    restart: RestartConfig,
+}
+
+impl Frpc {
+    fn from_env() -> Self {
+        Self {
+            exec: std::env::var("ALLOY_FRPC_PATH").unwrap_or_else(|_| "frpc".to_string()),
+            verify: frpc_verify_enabled(),
+            restart: frpc_restart_config(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum FrpcStart {
+    Started,
+    /// `frpc verify` refused the config; the tunnel was not spawned.
+    Rejected(String),
+}
+
+/// Runs `frpc verify -c <cfg>`; Err carries frpc's complaint.
+async fn verify_frpc_config(exec: &str, cfg_path: &Path) -> Result<(), String> {
+    let run = Command::new(exec)
+        .arg("verify")
+        .arg("-c")
+        .arg(cfg_path)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let out = match tokio::time::timeout(Duration::from_secs(10), run).await {
+        Err(_) => return Err("frpc verify timed out".to_string()),
+        Ok(Err(e)) => return Err(format!("run frpc verify: {e}")),
+        Ok(Ok(out)) => out,
+    };
+    if out.status.success() {
+        return Ok(());
+    }
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let detail = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ");
+    if detail.is_empty() {
+        Err(format!("frpc verify failed ({})", out.status))
+    } else {
+        Err(detail)
+    }
+}
+
+async fn start_frpc_sidecar(
+    sink: LogSink,
+    instance_dir: PathBuf,
+    owner_pgid: i32,
+    local_port: u16,
+    config_raw: String,
+    frpc: &Frpc,
+) -> anyhow::Result<FrpcStart> {
+    let cfg_dir = instance_dir.join("config");
+    let detected = detect_frp_config_format(&config_raw);
+    let (patched, written) = patch_frp_config(&config_raw, local_port);
+    let cfg_path = cfg_dir.join(format!("frpc.{}", written.extension()));
+
+    tokio::fs::create_dir_all(&cfg_dir)
+        .await
+        .context("create frpc config dir")?;
+
+    let tmp = cfg_path.with_extension(format!("{}.tmp", written.extension()));
+    tokio::fs::write(&tmp, patched.as_bytes())
+        .await
+        .context("write frpc config tmp")?;
+    tokio::fs::rename(&tmp, &cfg_path)
+        .await
+        .context("persist frpc config")?;
+
+    let exec = &frpc.exec;
+    if frpc.verify
+        && let Err(msg) = verify_frpc_config(exec, &cfg_path).await
+    {
+        sink.emit(format!(
+            "[alloy-agent] frpc config rejected, tunnel not started: {msg}"
+        ))
+        .await;
+        return Ok(FrpcStart::Rejected(msg));
+    }
+
+    sink.emit(format!(
+        "[alloy-agent] starting frpc tunnel (local_port={local_port}, source={detected:?}, config={written:?})"
+    ))
+    .await;
+
+    let launch = FrpcLaunch {
+        exec: exec.clone(),
+        cfg_path,
+        instance_dir,
+        owner_pgid,
+    };
+    let child = spawn_frpc(&launch, &sink)?;
+    tokio::spawn(supervise_frpc(child, launch, sink, frpc.restart));
+
+    Ok(FrpcStart::Started)
+}
+
+struct FrpcLaunch {
+    exec: String,
+    cfg_path: PathBuf,
+    instance_dir: PathBuf,
+    owner_pgid: i32,
+}
+
+fn spawn_frpc(launch: &FrpcLaunch, sink: &LogSink) -> anyhow::Result<tokio::process::Child> {
+    let mut cmd = Command::new(&launch.exec);
+    cmd.current_dir(&launch.instance_dir)
+        .arg("-c")
+        .arg(&launch.cfg_path)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+
+    #[cfg(unix)]
+    {
+        let owner_pgid = launch.owner_pgid;
+        unsafe {
+            cmd.pre_exec(move || {
+                set_parent_death_signal()?;
+                if libc::setpgid(0, owner_pgid) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    let mut child = cmd.spawn().with_context(|| {
+        format!(
+            "spawn frpc: exec={} (cfg {})",
+            launch.exec,
+            launch.cfg_path.display()
+        )
+    })?;
+
+    if let Some(out) = child.stdout.take() {
+        tokio::spawn(pump_output(out, sink.clone(), "[frpc stdout]"));
+    }
+    if let Some(err) = child.stderr.take() {
+        tokio::spawn(pump_output(err, sink.clone(), "[frpc stderr]"));
+    }
+    Ok(child)
+}
+
+fn process_group_alive(pgid: i32) -> bool {
+    unsafe { libc::kill(-pgid, 0) == 0 }
+}
+
+/// Restarts frpc with backoff for as long as the game server's process group
+/// is alive. Returns the number of restarts attempted.
+async fn supervise_frpc(
+    child: tokio::process::Child,
+    launch: FrpcLaunch,
+    sink: LogSink,
+    restart: RestartConfig,
+) -> u32 {
+    let mut child = Some(child);
+    let mut attempts = 0u32;
+    loop {
+        if let Some(mut c) = child.take() {
+            match c.wait().await {
+                Ok(st) => sink.emit(format!("[alloy-agent] frpc exited: {st}")).await,
+                Err(e) => {
+                    sink.emit(format!("[alloy-agent] frpc wait failed: {e}"))
+                        .await
+                }
+            }
+        }
+        // frpc shares the server's process group; once it is reaped, an empty
+        // group means the server itself is gone.
+        if !process_group_alive(launch.owner_pgid) {
+            return attempts;
+        }
+        if attempts >= restart.max_retries {
+            sink.emit(format!(
+                "[alloy-agent] frpc kept exiting; giving up after {attempts} restarts"
+            ))
+            .await;
+            return attempts;
+        }
+        attempts += 1;
+        let delay_ms = compute_backoff_ms(restart, attempts);
+        sink.emit(format!(
+            "[alloy-agent] restarting frpc in {delay_ms}ms (attempt {attempts}/{})",
+            restart.max_retries
+        ))
+        .await;
+        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+        if !process_group_alive(launch.owner_pgid) {
+            return attempts;
+        }
+        match spawn_frpc(&launch, &sink) {
+            Ok(c) => child = Some(c),
+            Err(e) => {
+                sink.emit(format!(
+                    "[alloy-agent] frpc restart failed: {}",
+                    format_error_chain(&e)
+                ))
+                .await
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn set_parent_death_signal() -> std::io::Result<()> {
+    // If the agent process dies (crash/kill), ensure the child is terminated.
+    // NOTE: `unsafe fn` bodies are not implicitly unsafe in Rust 2024.
+    let rc = unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM) };
+    if rc == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+unsafe fn set_parent_death_signal() -> std::io::Result<()> {
+    Ok(())
+}
+
+fn prepare_instance_command(
+    process_id: &str,
+    template_id: &str,
+    params: &BTreeMap<String, String>,
+    instance_dir: &Path,
+    cwd: &Path,
+    exec: &str,
+    args: &[String],
+    extra_rw_paths: &[PathBuf],
+) -> anyhow::Result<(Command, sandbox::SandboxLaunch)> {
+    let launch = sandbox::prepare_launch(
+        process_id,
+        template_id,
+        params,
+        instance_dir,
+        cwd,
+        exec,
+        args,
+        extra_rw_paths,
+    )?;
+
+    let mut cmd = Command::new(&launch.exec);
+    cmd.current_dir(&launch.cwd)
+        .args(&launch.args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+
+    #[cfg(unix)]
+    {
+        let limits = launch.limits.clone();
+        let apply_host_limits = launch.should_apply_host_limits();
+        unsafe {
+            cmd.pre_exec(move || {
+                set_parent_death_signal()?;
+                if libc::setpgid(0, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if apply_host_limits {
+                    limits.apply_pre_exec()?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    Ok((cmd, launch))
+}
+
+fn docker_no_such_container(stderr: &str) -> bool {
+    let msg = stderr.to_ascii_lowercase();
+    msg.contains("no such container") || msg.contains("is not running")
+}
+
+fn first_non_empty_line(stdout: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+async fn read_run_container_meta(process_id: &str) -> Option<RunContainerMeta> {
+    let data_root = crate::minecraft::data_root();
+    for dir in ["instances", "processes"] {
+        let path = data_root.join(dir).join(process_id).join("run.json");
+        let raw = match tokio::fs::read(&path).await {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if let Ok(meta) = serde_json::from_slice::<RunContainerMeta>(&raw) {
+            return Some(meta);
+        }
+    }
+    None
+}
+
+/// Whether the process runs in a sandbox container according to its run.json.
+pub async fn is_container_backed(process_id: &str) -> bool {
+    read_run_container_meta(process_id).await.is_some_and(|m| {
+        m.container_id.as_deref().is_some_and(|s| !s.trim().is_empty())
+            || m.container_name.as_deref().is_some_and(|s| !s.trim().is_empty())
+    })
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct PersistedRun {
+    pub template_id: String,
+}
+
+/// Reads run.json for a process that has no live entry in the manager.
+pub async fn read_persisted_run(process_id: &str) -> Option<PersistedRun> {
+    read_run_json(&crate::minecraft::data_root(), process_id).await
+}
+
+fn is_safe_process_id(process_id: &str) -> bool {
+    !process_id.is_empty()
+        && !process_id.starts_with('.')
+        && process_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// What deleting an instance's data directory would remove.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeleteInstancePreview {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub file_count: u64,
+}
+
+/// Finds `<data_root>/{instances,processes}/<process_id>` and resolves it,
+/// refusing anything (e.g. a symlink) that lands outside the data root.
+fn resolve_instance_data_dir(
+    data_root: &Path,
+    process_id: &str,
+) -> anyhow::Result<Option<PathBuf>> {
+    if !is_safe_process_id(process_id) {
+        anyhow::bail!("invalid process_id: {process_id:?}");
+    }
+    let root = std::fs::canonicalize(data_root)
+        .with_context(|| format!("resolve data root {}", data_root.display()))?;
+    for dir in ["instances", "processes"] {
+        let path = data_root.join(dir).join(process_id);
+        if std::fs::symlink_metadata(&path).is_err() {
+            continue;
+        }
+        let resolved =
+            std::fs::canonicalize(&path).with_context(|| format!("resolve {}", path.display()))?;
+        if resolved == root || !resolved.starts_with(&root) {
+            anyhow::bail!(
+                "refusing to delete {}: resolves to {} outside the data root",
+                path.display(),
+                resolved.display()
+            );
+        }
+        if !resolved.is_dir() {
+            anyhow::bail!("{} is not a directory", path.display());
+        }
+        return Ok(Some(resolved));
+    }
+    Ok(None)
+}
+
+/// Sums bytes and regular files below `path` without following symlinks.
+fn dir_usage(path: &Path) -> (u64, u64) {
+    let Ok(meta) = std::fs::symlink_metadata(path) else {
+        return (0, 0);
+    };
+    if !meta.is_dir() {
+        return (meta.len(), u64::from(meta.is_file()));
+    }
+    let Ok(rd) = std::fs::read_dir(path) else {
+        return (0, 0);
+    };
+    rd.flatten().fold((0, 0), |(bytes, files), e| {
+        let (b, f) = dir_usage(&e.path());
+        (bytes.saturating_add(b), files.saturating_add(f))
+    })
+}
+
+fn preview_instance_delete(
+    data_root: &Path,
+    process_id: &str,
+) -> anyhow::Result<Option<DeleteInstancePreview>> {
+    let Some(path) = resolve_instance_data_dir(data_root, process_id)? else {
+        return Ok(None);
+    };
+    let (size_bytes, file_count) = dir_usage(&path);
+    Ok(Some(DeleteInstancePreview {
+        path,
+        size_bytes,
+        file_count,
+    }))
+}
+
+/// Parses `<data_root>/{instances,processes}/<process_id>/run.json`.
+async fn read_run_json<T: serde::de::DeserializeOwned>(
+    data_root: &Path,
+    process_id: &str,
+) -> Option<T> {
+    if !is_safe_process_id(process_id) {
+        return None;
+    }
+    for dir in ["instances", "processes"] {
+        let path = data_root.join(dir).join(process_id).join("run.json");
+        let Ok(raw) = tokio::fs::read(&path).await else {
+            continue;
+        };
+        if let Ok(run) = serde_json::from_slice::<T>(&raw) {
+            return Some(run);
+        }
+    }
+    None
+}
+
+/// Reads the launch params saved by the last start of a process.
+async fn read_persisted_params(process_id: &str) -> Option<BTreeMap<String, String>> {
+    // read_persisted_run validates the id and tells us the process was launched here.
+    read_persisted_run(process_id).await?;
+    let data_root = crate::minecraft::data_root();
+    for dir in ["instances", "processes"] {
+        let path = data_root.join(dir).join(process_id).join("params.json");
+        let Ok(raw) = tokio::fs::read(&path).await else {
+            continue;
+        };
+        if let Ok(params) = serde_json::from_slice(&raw) {
+            return Some(params);
+        }
+    }
+    None
+}
+
+async fn docker_find_container_by_name(container_name: &str) -> Option<String> {
+    let name_filter = format!("name=^/{container_name}$");
+    let output = Command::new("docker")
+        .env_remove("DOCKER_API_VERSION")
+        .arg("ps")
+        .arg("-aq")
+        .arg("--no-trunc")
+        .arg("--filter")
+        .arg(name_filter)
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    first_non_empty_line(&output.stdout)
+}
+
+async fn docker_find_container_by_process(process_id: &str) -> Option<String> {
+    let filter = format!("label=alloy.process_id={process_id}");
+    let output = Command::new("docker")
+        .env_remove("DOCKER_API_VERSION")
+        .arg("ps")
+        .arg("-q")
+        .arg("--no-trunc")
+        .arg("--filter")
+        .arg(filter)
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    first_non_empty_line(&output.stdout)
+}
+
+async fn find_container_for_process(process_id: &str) -> Option<String> {
+    if let Some(meta) = read_run_container_meta(process_id).await {
+        if let Some(container_id) = meta.container_id.filter(|v| !v.trim().is_empty()) {
+            return Some(container_id);
+        }
+        if let Some(container_name) = meta.container_name.filter(|v| !v.trim().is_empty())
+            && let Some(container_id) = docker_find_container_by_name(&container_name).await
+        {
+            return Some(container_id);
+        }
+    }
+
+    docker_find_container_by_process(process_id).await
+}
+
+async fn refresh_docker_container_metadata(process_id: &str, run: &mut RunInfo) {
+    if run.container_name.is_none() {
+        return;
+    }
+    if run.container_id.is_some() {
+        return;
+    }
+
+    if let Some(container_id) = docker_find_container_by_process(process_id).await {
+        run.container_id = Some(container_id);
+    }
+}
+
+async fn docker_stop_container(container_id: &str, stop_timeout_secs: u64) -> anyhow::Result<()> {
+    let timeout_secs = stop_timeout_secs.max(1).to_string();
+    let output = Command::new("docker")
+        .env_remove("DOCKER_API_VERSION")
+        .arg("stop")
+        .arg("--time")
+        .arg(&timeout_secs)
+        .arg(container_id)
+        .output()
+        .await
+        .with_context(|| format!("run `docker stop` for container {container_id}"))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if docker_no_such_container(&stderr) {
+        return Ok(());
+    }
+
+    anyhow::bail!(
+        "docker stop failed for {container_id}: {}",
+        stderr.trim().to_string()
+    );
+}
+
+// Audit detail for a docker stop/kill: the short container id, plus the error if any.
+fn audit_detail(container_id: &str, res: &anyhow::Result<()>) -> String {
+    let short: String = container_id.chars().take(12).collect();
+    match res {
+        Ok(()) => format!("container {short}"),
+        Err(err) => format!("container {short}: {err}"),
+    }
+}
+
+async fn docker_kill_container(container_id: &str) -> anyhow::Result<()> {
+    let output = Command::new("docker")
+        .env_remove("DOCKER_API_VERSION")
+        .arg("kill")
+        .arg(container_id)
+        .output()
+        .await
+        .with_context(|| format!("run `docker kill` for container {container_id}"))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if docker_no_such_container(&stderr) {
+        return Ok(());
+    }
+
+    anyhow::bail!(
+        "docker kill failed for {container_id}: {}",
+        stderr.trim().to_string()
+    );
+}
+
+/// Full id and host pid of `container_ref` when it is running.
+async fn docker_running_container(container_ref: &str) -> Option<(String, u32)> {
+    let output = Command::new("docker")
+        .env_remove("DOCKER_API_VERSION")
+        .arg("inspect")
+        .arg("--format")
+        .arg("{{.Id}} {{.State.Running}} {{.State.Pid}}")
+        .arg(container_ref)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let line = first_non_empty_line(&output.stdout)?;
+    let mut fields = line.split_whitespace();
+    let id = fields.next()?.to_string();
+    if fields.next()? != "true" {
+        return None;
+    }
+    let pid = fields.next()?.parse::<u32>().ok().filter(|pid| *pid > 0)?;
+    Some((id, pid))
+}
+
+/// Blocks until the container stops and returns its exit code.
+async fn docker_wait_container(container_id: &str) -> Option<i32> {
+    let output = Command::new("docker")
+        .env_remove("DOCKER_API_VERSION")
+        .arg("wait")
+        .arg(container_id)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    first_non_empty_line(&output.stdout)?.trim().parse().ok()
+}
+
+async fn wait_for_local_tcp_port(port: u16, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if let Ok(s) = tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
+            drop(s);
+            return true;
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+// Per-attempt budget for a status ping; the whole wait is bounded by the probe timeout.
+const MINECRAFT_PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Waits for a Server List Ping answer. A reply that is not SLP (proxies,
+/// odd modded handshakes) still proves the port is serving, so it counts as
+/// ready without a status.
+async fn wait_for_minecraft_status(
+    port: u16,
+    timeout: Duration,
+) -> Result<Option<ServerStatus>, Duration> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        match minecraft_ping::ping(port, MINECRAFT_PING_TIMEOUT).await {
+            Ok(status) => return Ok(Some(status)),
+            Err(PingError::Protocol(_)) => return Ok(None),
+            Err(PingError::Io(_)) => {}
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            return Err(timeout);
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+/// Re-pings a running Minecraft server so its player count stays current.
+async fn refresh_player_count(
+    inner: Arc<Mutex<HashMap<String, ProcessEntry>>>,
+    process_id: String,
+    pid: Option<u32>,
+    port: u16,
+) {
+    loop {
+        tokio::time::sleep(minecraft_status_poll_interval()).await;
+        // A missed ping keeps the last known count; the server may just be busy.
+        let status = minecraft_ping::ping(port, MINECRAFT_PING_TIMEOUT)
+            .await
+            .ok();
+        let mut map = inner.lock().await;
+        let Some(e) = map.get_mut(&process_id) else {
+            return;
+        };
+        if e.pid != pid || !matches!(e.state, ProcessState::Running) {
+            return;
+        }
+        if let Some(status) = status {
+            e.players = Some(player_count(&status));
+        }
+    }
+}
+
+fn player_count(status: &ServerStatus) -> PlayerCount {
+    PlayerCount {
+        online: status.players_online,
+        max: status.max_players,
+    }
+}
+
+// How long a bound UDP port gets to answer the probe datagram.
+const UDP_REPLY_WAIT: Duration = Duration::from_millis(500);
+
+/// UDP has no handshake, so this waits until something holds the local port
+/// and then sends a probe datagram. A reply proves the server is serving; a
+/// silent server (most game protocols drop unknown packets) still counts once
+/// it holds the bind past [`UDP_REPLY_WAIT`].
+async fn wait_for_local_udp_ready(port: u16, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if udp_port_bound(port) && udp_port_answers_or_holds(port).await {
+            return true;
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+fn udp_port_bound(port: u16) -> bool {
+    matches!(
+        std::net::UdpSocket::bind(("0.0.0.0", port)),
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse
+    )
+}
+
+async fn udp_port_answers_or_holds(port: u16) -> bool {
+    if let Ok(sock) = tokio::net::UdpSocket::bind(("127.0.0.1", 0)).await
+        && sock.connect(("127.0.0.1", port)).await.is_ok()
+        && sock.send(&[0]).await.is_ok()
+    {
+        let mut buf = [0u8; 64];
+        if let Ok(Ok(_)) = tokio::time::timeout(UDP_REPLY_WAIT, sock.recv(&mut buf)).await {
+            return true;
+        }
+    } else {
+        tokio::time::sleep(UDP_REPLY_WAIT).await;
+    }
+    udp_port_bound(port)
+}
+
+/// Waits for any of a template's "server started" markers in the console.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LogReadyMatcher {
+    // Lowercased; matched as substrings of stdout/stderr lines.
+    markers: Vec<String>,
+    timeout: Duration,
+}
+
+impl LogReadyMatcher {
+    fn new(markers: &[String], timeout: Duration) -> Option<Self> {
+        let markers: Vec<String> = markers
+            .iter()
+            .map(|m| m.trim().to_lowercase())
+            .filter(|m| !m.is_empty())
+            .collect();
+        (!markers.is_empty()).then_some(Self { markers, timeout })
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        let line = line.to_lowercase();
+        self.markers.iter().any(|m| line.contains(m))
+    }
+
+    /// Scans lines logged after `cursor` until a marker shows up.
+    async fn wait(&self, logs: &Mutex<LogBuffer>, mut cursor: u64) -> Result<(), Duration> {
+        let filter = LogFilter {
+            streams: vec![LogStream::Stdout, LogStream::Stderr],
+            contains: None,
+        };
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        loop {
+            // Cursor 0 (empty buffer at launch) returns every line, which is what we want.
+            let (lines, next) = logs.lock().await.tail_filtered(cursor, usize::MAX, &filter);
+            if lines.iter().any(|l| self.matches(&l.text)) {
+                return Ok(());
+            }
+            cursor = next;
+
+            if tokio::time::Instant::now() >= deadline {
+                return Err(self.timeout);
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    }
+}
+
+/// How a supervised launch decides the server is ready. Without a probe the
+/// process is marked Running as soon as it spawns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PortProbe {
+    /// Running once the local TCP port accepts connections; failed after `timeout`.
+    Tcp { port: u16, timeout: Duration },
+    /// Running once the server answers a Minecraft Server List Ping; falls
+    /// back to port-open if the reply is not SLP. Failed after `timeout`.
+    Minecraft { port: u16, timeout: Duration },
+    /// Running once the local UDP port is bound and answers or keeps holding
+    /// it; failed after `timeout`.
+    Udp { port: u16, timeout: Duration },
+    /// Running once the console prints a ready marker; failed after the matcher's timeout.
+    Log(LogReadyMatcher),
+}
+
+impl PortProbe {
+    fn minecraft(port: u16) -> Self {
+        PortProbe::Minecraft {
+            port,
+            timeout: port_probe_timeout(),
+        }
+    }
+
+    fn port(&self) -> Option<u16> {
+        match *self {
+            PortProbe::Tcp { port, .. }
+            | PortProbe::Minecraft { port, .. }
+            | PortProbe::Udp { port, .. } => Some(port),
+            PortProbe::Log(_) => None,
+        }
+    }
+
+    fn timeout_message(&self, timeout: Duration) -> String {
+        match self.port() {
+            Some(port) => format!("port {port} did not open within {}ms", timeout.as_millis()),
+            None => format!(
+                "server did not report ready within {}ms",
+                timeout.as_millis()
+            ),
+        }
+    }
+
+    /// Ok carries the server's status when the probe could read one. Log
+    /// probes scan `logs` past `log_cursor`.
+    async fn wait(
+        &self,
+        logs: &Mutex<LogBuffer>,
+        log_cursor: u64,
+    ) -> Result<Option<ServerStatus>, Duration> {
+        match *self {
+            PortProbe::Tcp { port, timeout } => {
+                if wait_for_local_tcp_port(port, timeout).await {
+                    Ok(None)
+                } else {
+                    Err(timeout)
+                }
+            }
+            PortProbe::Minecraft { port, timeout } => {
+                wait_for_minecraft_status(port, timeout).await
+            }
+            PortProbe::Udp { port, timeout } => {
+                if wait_for_local_udp_ready(port, timeout).await {
+                    Ok(None)
+                } else {
+                    Err(timeout)
+                }
+            }
+            PortProbe::Log(ref matcher) => matcher.wait(logs, log_cursor).await.map(|()| None),
+        }
+    }
+}
+
+/// Resolves with the failure message once the console shows the server could
+/// not bind its port; never resolves for games without known bind markers.
+async fn wait_for_port_in_use(
+    logs: &Mutex<LogBuffer>,
+    mut cursor: u64,
+    template_id: &str,
+    port: Option<u16>,
+) -> String {
+    if !detects_port_in_use(template_id) {
+        return std::future::pending().await;
+    }
+    loop {
+        let (lines, next) = logs.lock().await.tail_after(cursor, usize::MAX);
+        if let Some(msg) = port_in_use_message(template_id, port, &lines) {
+            return msg;
+        }
+        cursor = next;
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+/// Everything `ProcessManager::spawn_supervised` needs to launch a server and
+/// watch it until exit.
+struct LaunchContext {
+    process_id: String,
+    template_id: String,
+    params: BTreeMap<String, String>,
+    // Short server name for log lines ("minecraft", "terraria", ...).
+    kind: &'static str,
+    // Appended to the exec log line, e.g. "port=25565 version=1.21.1".
+    exec_detail: String,
+    exec: String,
+    args: Vec<String>,
+    cwd: PathBuf,
+    instance_dir: PathBuf,
+    extra_rw_paths: Vec<PathBuf>,
+    env: Vec<(String, String)>,
+    spawn_hint: &'static str,
+    port_probe: Option<PortProbe>,
+    frp_config: Option<String>,
+    restart: RestartConfig,
+    restart_attempts: u32,
+    graceful_stop: Option<GracefulStopSpec>,
+    sink: LogSink,
+    companions: Vec<Companion>,
+}
+
+/// A second server process belonging to the same instance, such as DST's
+/// Caves shard. It runs `exec` in the main process's group, so stopping the
+/// instance or the main process exiting takes it down too; it exiting on its
+/// own stops the main process.
+struct Companion {
+    name: &'static str,
+    args: Vec<String>,
+    stdout_prefix: &'static str,
+    stderr_prefix: &'static str,
+}
+
+/// The main process a companion joins, and the launch settings it reuses.
+struct CompanionOwner<'a> {
+    process_id: &'a str,
+    template_id: &'a str,
+    params: &'a BTreeMap<String, String>,
+    instance_dir: &'a Path,
+    cwd: &'a Path,
+    exec: &'a str,
+    extra_rw_paths: &'a [PathBuf],
+    pid: Option<u32>,
+    pgid: i32,
+    sink: &'a LogSink,
+}
+
+const JAVA_SPAWN_HINT: &str = "Ensure Java is installed and the instance directory is writable.";
+const DST_SPAWN_HINT: &str = "Ensure the agent image includes required libraries for DST.";
+const TERRARIA_SPAWN_HINT: &str =
+    "Ensure the Terraria server binary is executable and dependencies are installed.";
+const GENERIC_SPAWN_HINT: &str = "Ensure the command exists and is executable.";
+
+fn frp_config_param(params: &BTreeMap<String, String>) -> Option<String> {
+    params
+        .get("frp_config")
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_string())
+}
+
+async fn set_entry_message(
+    inner: &Arc<Mutex<HashMap<String, ProcessEntry>>>,
+    process_id: &str,
+    message: Option<String>,
+) {
+    let mut map = inner.lock().await;
+    let Some(e) = map.get_mut(process_id) else {
+        return;
+    };
+    e.message = message;
+    e.progress = None;
+}
+
+// How long a process group's member list is reused before `/proc` is rescanned.
+const PGID_MEMBERS_TTL: Duration = Duration::from_secs(10);
+
+// Bounds how often download progress takes the process map lock.
+const PROGRESS_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Feeds a download's progress to the entry as `"<label> (NN%)"` plus
+/// [`ProgressInfo`] (throttled to `PROGRESS_UPDATE_INTERVAL`), broadcasts each
+/// new percentage as start progress, and logs every 10% to the console.
+fn download_reporter(
+    inner: &Arc<Mutex<HashMap<String, ProcessEntry>>>,
+    events: &ProcessEvents,
+    process_id: &str,
+    sink: &LogSink,
+    label: &str,
+) -> impl FnMut(u64, u64, u64) + Send + 'static {
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let sink = sink.clone();
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            sink.emit(format!("[alloy-agent] {msg}")).await;
+        }
+    });
+    let (inner, events) = (inner.clone(), events.clone());
+    let (id, label) = (process_id.to_string(), label.to_string());
+    let mut percent = crate::download::percent_messages(label.clone(), 10, move |msg| {
+        let _ = tx.send(msg);
+    });
+    let mut last_update: Option<std::time::Instant> = None;
+    let mut last_percent: Option<u32> = None;
+    move |downloaded, total, speed| {
+        percent(downloaded, total, speed);
+
+        let done = total > 0 && downloaded >= total;
+        if !done && last_update.is_some_and(|t| t.elapsed() < PROGRESS_UPDATE_INTERVAL) {
+            return;
+        }
+        // Runs inside the download loop: skip a contended lock rather than
+        // wait, the next chunk retries.
+        let Ok(mut map) = inner.try_lock() else {
+            return;
+        };
+        let Some(e) = map.get_mut(&id) else {
+            return;
+        };
+        let pct = (downloaded.min(total) * 100).checked_div(total);
+        e.message = Some(match pct {
+            Some(pct) => format!("{label} ({pct}%)"),
+            None => label.clone(),
+        });
+        e.progress = Some(ProgressInfo {
+            bytes_done: downloaded,
+            bytes_total: total,
+            phase: label.clone(),
+        });
+        drop(map);
+        last_update = Some(std::time::Instant::now());
+
+        let pct = pct.map(|p| p as u32);
+        if pct.is_some() && pct != last_percent {
+            last_percent = pct;
+            events.emit(
+                &id,
+                ProcessEventKind::StartProgress {
+                    phase: StartPhase::Downloading,
+                    percent: pct,
+                    message: label.clone(),
+                },
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ProcessEntry {
+    template_id: ProcessTemplateId,
+    state: ProcessState,
+    pid: Option<u32>,
+    resources: Option<alloy_process::ProcessResources>,
+    exit_code: Option<i32>,
+    message: Option<String>,
+    restart: RestartConfig,
+    restart_attempts: u32,
+    stdin: Option<ChildStdin>,
+    graceful_stop: Option<GracefulStopSpec>,
+    pgid: Option<i32>,
+    logs: Arc<Mutex<LogBuffer>>,
+    log_file_tx: Option<mpsc::UnboundedSender<String>>,
+    fail_category: Option<FailCategory>,
+    failure_reason: Option<FailureReason>,
+    failure_hint: Option<String>,
+    // Last player count from a status ping; cleared when the process exits.
+    players: Option<PlayerCount>,
+    // Download/install progress; cleared by the next entry message.
+    progress: Option<ProgressInfo>,
+    // Latest phase of the current start, for late start-progress subscribers.
+    start_phase: Option<StartPhase>,
+}
+
+type ResourceHistory = SampleHistory<(u64, alloy_process::ProcessResources)>;
+
+#[derive(Clone, Debug, Default)]
+pub struct ProcessManager {
+    inner: Arc<Mutex<HashMap<String, ProcessEntry>>>,
+    // Per-process (unix_ms, sample) history, reset on every launch.
+    resource_history: Arc<Mutex<HashMap<String, ResourceHistory>>>,
+    events: ProcessEvents,
+    audit: AgentAudit,
+}
+
+impl ProcessManager {
+    /// The exec/args/cwd/env recorded by the last launch; `None` if it never started.
+    pub async fn get_run_info(&self, process_id: &str) -> Option<RunInfoView> {
+        read_run_json(&crate::minecraft::data_root(), process_id).await
+    }
+
+    /// Live lifecycle events for every supervised process.
+    pub fn subscribe(&self) -> broadcast::Receiver<ProcessEvent> {
+        self.events.subscribe()
+    }
+
+    /// Phase of the current start, while the process is still `Starting`.
+    pub async fn start_phase(&self, process_id: &str) -> Option<StartPhase> {
+        let map = self.inner.lock().await;
+        let e = map.get(process_id)?;
+        (e.state == ProcessState::Starting)
+            .then_some(e.start_phase)
+            .flatten()
+    }
+
+    async fn set_start_phase(
+        &self,
+        process_id: &str,
+        phase: StartPhase,
+        message: impl Into<String>,
+    ) {
+        if let Some(e) = self.inner.lock().await.get_mut(process_id) {
+            e.start_phase = Some(phase);
+        }
+        self.events.emit(
+            process_id,
+            ProcessEventKind::StartProgress {
+                phase,
+                percent: None,
+                message: message.into(),
+            },
+        );
+    }
+
+    /// Spawn/stop/kill actions waiting to be mirrored to the control plane.
+    pub fn audit(&self) -> AgentAudit {
+        self.audit.clone()
+    }
+
+    fn spawn_resource_sampler(&self, process_id: String, pid: u32) {
+        let inner = self.inner.clone();
+        let history = self.resource_history.clone();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let mut last: Option<(u64, tokio::time::Instant)> = None;
+            let interval = resource_sample_interval();
+            let aggregate = aggregate_pgid_resources();
+            // Learned from the entry after the first sample.
+            let mut pgid: Option<i32> = None;
+            let mut members: Option<(tokio::time::Instant, Vec<u32>)> = None;
+            history.lock().await.insert(
+                process_id.clone(),
+                SampleHistory::new(resource_history_samples()),
+            );
+
+            loop {
+                let now = tokio::time::Instant::now();
+                let Some(mut ticks) = read_proc_cpu_ticks(pid).await else {
+                    break;
+                };
+                let mut rss_bytes = read_proc_rss_bytes(pid).await.unwrap_or(0);
+                if let Some(pgid) = pgid.filter(|_| aggregate) {
+                    if members
+                        .as_ref()
+                        .is_none_or(|(at, _)| now.duration_since(*at) >= PGID_MEMBERS_TTL)
+                    {
+                        members = Some((now, pgid_members(Path::new("/proc"), pgid).await));
+                    }
+                    let others = members.iter().flat_map(|(_, m)| m).filter(|&&m| m != pid);
+                    for &m in others {
+                        ticks = ticks.saturating_add(read_proc_cpu_ticks(m).await.unwrap_or(0));
+                        rss_bytes =
+                            rss_bytes.saturating_add(read_proc_rss_bytes(m).await.unwrap_or(0));
+                    }
+                }
+                let (read_bytes, write_bytes) = read_proc_io_bytes(pid).await.unwrap_or((0, 0));
+
+                let cpu_percent_x100 = last
+                    .map(|(prev_ticks, prev_at)| {
+                        cpu_percent_x100(
+                            ticks.saturating_sub(prev_ticks),
+                            ticks_per_sec(),
+                            now.duration_since(prev_at).as_secs_f64(),
+                        )
+                    })
+                    .unwrap_or(0);
+                last = Some((ticks, now));
+
+                let sample = alloy_process::ProcessResources {
+                    cpu_percent_x100,
+                    cpu_percent_normalized_x100: normalize_cpu_x100(
+                        cpu_percent_x100,
+                        online_cpus(),
+                    ),
+                    rss_bytes,
+                    read_bytes,
+                    write_bytes,
+                };
+                {
+                    let mut map = inner.lock().await;
+                    let Some(e) = map.get_mut(&process_id) else {
+                        break;
+                    };
+                    if e.pid != Some(pid) {
+                        break;
+                    }
+                    e.resources = Some(sample.clone());
+                    pgid = e.pgid;
+                }
+                events.emit(
+                    &process_id,
+                    ProcessEventKind::ResourcesSampled(sample.clone()),
+                );
+                let at_ms = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                if let Some(h) = history.lock().await.get_mut(&process_id) {
+                    h.push((at_ms, sample));
+                }
+
+                tokio::time::sleep(interval).await;
+            }
+        });
+    }
+
+    /// Spawns the prepared server command and supervises it: pipes output into
+    /// the log sink, runs the readiness probe, records the exit and schedules
+    /// auto-restarts per the restart policy.
+    /// Starts `companion` in the group of `owner`'s main process and watches
+    /// it: if it exits while that process is still the live entry and not
+    /// stopping, the whole group is stopped.
+    async fn spawn_companion(
+        &self,
+        companion: &Companion,
+        owner: CompanionOwner<'_>,
+    ) -> anyhow::Result<Option<u32>> {
+        let CompanionOwner {
+            process_id,
+            template_id,
+            params,
+            instance_dir,
+            cwd,
+            exec,
+            extra_rw_paths,
+            pid: owner_pid,
+            pgid,
+            sink,
+        } = owner;
+        let (mut cmd, launch) = prepare_instance_command(
+            &format!("{process_id}-{}", companion.name),
+            template_id,
+            params,
+            instance_dir,
+            cwd,
+            exec,
+            &companion.args,
+            extra_rw_paths,
+        )?;
+        cmd.stdin(std::process::Stdio::null());
+        #[cfg(unix)]
+        unsafe {
+            // Runs after prepare_instance_command's own setpgid(0, 0).
+            cmd.pre_exec(move || {
+                if libc::setpgid(0, pgid) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        sink.emit(format!(
+            "[alloy-agent] {} exec: {} {}",
+            companion.name,
+            launch.exec,
+            launch.args.join(" ")
+        ))
+        .await;
+
+        let mut child = cmd.spawn()?;
+        let pid = child.id();
+        if let Some(out) = child.stdout.take() {
+            tokio::spawn(pump_output(out, sink.clone(), companion.stdout_prefix));
+        }
+        if let Some(err) = child.stderr.take() {
+            tokio::spawn(pump_output(err, sink.clone(), companion.stderr_prefix));
+        }
+
+        let inner = self.inner.clone();
+        let audit = self.audit.clone();
+        let name = companion.name;
+        let id = process_id.to_string();
+        let template_id = template_id.to_string();
+        let sink = sink.clone();
+        tokio::spawn(async move {
+            let res = child.wait().await;
+            let owner_live = inner.lock().await.get(&id).is_some_and(|e| {
+                e.pid == owner_pid
+                    && matches!(e.state, ProcessState::Starting | ProcessState::Running)
+            });
+            if !owner_live {
+                return;
+            }
+            let status = match res {
+                Ok(st) => st.to_string(),
+                Err(e) => format!("wait failed: {e}"),
+            };
+            sink.emit(format!(
+                "[alloy-agent] {name} exited ({status}); stopping the instance"
+            ))
+            .await;
+            #[cfg(unix)]
+            unsafe {
+                libc::kill(-pgid, libc::SIGTERM);
+            }
+            audit.record(
+                "sigterm",
+                &id,
+                &template_id,
+                format!("pgid {pgid}: {name} exited"),
+            );
+        });
+        Ok(pid)
+    }
+
+    async fn spawn_supervised(&self, ctx: LaunchContext) -> anyhow::Result<ProcessStatus> {
+        let LaunchContext {
+            process_id,
+            template_id,
+            params,
+            kind,
+            exec_detail,
+            exec,
+            args,
+            cwd,
+            instance_dir,
+            extra_rw_paths,
+            env,
+            spawn_hint,
+            port_probe,
+            frp_config,
+            restart,
+            restart_attempts,
+            graceful_stop,
+            sink,
+            companions,
+        } = ctx;
+        // Oneshot jobs run one command to completion: a quick exit is normal and
+        // the entry is dropped a while after it finishes.
+        let oneshot = template_id == templates::ONESHOT_TEMPLATE_ID;
+
+        let overrides = config_overrides::apply(&instance_dir, &params)?;
+        if overrides > 0 {
+            sink.emit(format!(
+                "[alloy-agent] wrote {overrides} file(s) from {}",
+                config_overrides::PARAM
+            ))
+            .await;
+        }
+
+        let (mut cmd, sandbox_launch) = prepare_instance_command(
+            &process_id,
+            &template_id,
+            &params,
+            &instance_dir,
+            &cwd,
+            &exec,
+            &args,
+            &extra_rw_paths,
+        )?;
+        if !companions.is_empty() && sandbox_launch.is_docker_mode() {
+            return Err(crate::error_payload::anyhow(
+                "invalid_param",
+                format!("{kind} runs several processes, which the docker sandbox can't group"),
+                None,
+                Some("Use sandbox_mode=native or bwrap for this instance.".to_string()),
+            ));
+        }
+        let mut run_env = collect_safe_env();
+        for (key, value) in &env {
+            cmd.env(key, value);
+            run_env.insert(key.clone(), value.clone());
+        }
+
+        let started_at_unix_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let mut run = RunInfo {
+            process_id: process_id.clone(),
+            template_id: template_id.clone(),
+            started_at_unix_ms,
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            pid: None,
+            pgid: None,
+            container_name: sandbox_launch.container_name().map(ToOwned::to_owned),
+            container_id: None,
+            exec: sandbox_launch.exec.clone(),
+            args: sandbox_launch.args.clone(),
+            cwd: sandbox_launch.cwd.display().to_string(),
+            params: redact_params(params.clone()),
+            env: run_env,
+            companions: Vec::new(),
+        };
+        let _ = write_run_json(&instance_dir, &run).await;
+        let _ = write_params_json(&instance_dir, &params).await;
+
+        sink.emit(format!(
+            "[alloy-agent] sandbox: {}",
+            sandbox_launch.summary()
+        ))
+        .await;
+        for warning in sandbox_launch.warnings() {
+            sink.emit(format!("[alloy-agent] sandbox warning: {warning}"))
+                .await;
+        }
+
+        sink.emit(format!(
+            "[alloy-agent] {kind} exec: {} {} (cwd {}) {exec_detail}",
+            sandbox_launch.exec,
+            sandbox_launch.args.join(" "),
+            sandbox_launch.cwd.display(),
+        ))
+        .await;
+
+        self.set_start_phase(&process_id, StartPhase::Launching, "launching server")
+            .await;
+
+        let spawning = match port_probe {
+            Some(PortProbe::Tcp { port, .. } | PortProbe::Minecraft { port, .. }) => {
+                format!("spawning {kind} server (port {port})...")
+            }
+            Some(PortProbe::Udp { port, .. }) => format!("spawning {kind} server (udp {port})..."),
+            _ => format!("spawning {kind} server..."),
+        };
+        set_entry_message(&self.inner, &process_id, Some(spawning)).await;
+
+        // Log probes only look at output from this launch.
+        let ready_cursor = sink.buffer.lock().await.last_seq();
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("spawn {kind} server: exec={exec} (cwd {})", cwd.display()))
+            .map_err(|e| {
+                crate::error_payload::anyhow(
+                    "spawn_failed",
+                    e.to_string(),
+                    None,
+                    Some(spawn_hint.to_string()),
+                )
+            })?;
+        let started = tokio::time::Instant::now();
+        let pid_u32 = child.id();
+        let pgid = pid_u32.map(|p| p as i32);
+
+        if let Some(pid) = pid_u32
+            && let Some(warn) = sandbox_launch.attach_pid(pid)
+        {
+            sink.emit(format!("[alloy-agent] sandbox warning: {warn}"))
+                .await;
+        }
+
+        run.pid = pid_u32;
+        run.pgid = pgid;
+        refresh_docker_container_metadata(&process_id, &mut run).await;
+        if let Some(pgid) = pgid {
+            for companion in &companions {
+                let owner = CompanionOwner {
+                    process_id: &process_id,
+                    template_id: &template_id,
+                    params: &params,
+                    instance_dir: &instance_dir,
+                    cwd: &cwd,
+                    exec: &exec,
+                    extra_rw_paths: &extra_rw_paths,
+                    pid: pid_u32,
+                    pgid,
+                    sink: &sink,
+                };
+                let launched = self.spawn_companion(companion, owner).await;
+                match launched {
+                    Ok(pid) => run.companions.push(CompanionRun {
+                        name: companion.name.to_string(),
+                        pid,
+                        pgid: Some(pgid),
+                    }),
+                    Err(e) => {
+                        #[cfg(unix)]
+                        unsafe {
+                            libc::kill(-pgid, libc::SIGKILL);
+                        }
+                        return Err(crate::error_payload::anyhow(
+                            "spawn_failed",
+                            format!("spawn {kind} {}: {e:#}", companion.name),
+                            None,
+                            Some(spawn_hint.to_string()),
+                        ));
+                    }
+                }
+            }
+        }
+        let _ = write_run_json(&instance_dir, &run).await;
+
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        if let Some(out) = stdout {
+            tokio::spawn(pump_output(out, sink.clone(), "[stdout]"));
+        }
+        if let Some(err) = stderr {
+            tokio::spawn(pump_output(err, sink.clone(), "[stderr]"));
+        }
+
+        let (state, message) = match port_probe.as_ref().and_then(PortProbe::port) {
+            Some(port) => (
+                ProcessState::Starting,
+                Some(format!("waiting for port {port}...")),
+            ),
+            None if matches!(port_probe, Some(PortProbe::Log(_))) => (
+                ProcessState::Starting,
+                Some("waiting for the server to report ready...".to_string()),
+            ),
+            None => (ProcessState::Running, None),
+        };
+        {
+            let mut inner = self.inner.lock().await;
+            let prev = inner.insert(
+                process_id.clone(),
+                ProcessEntry {
+                    template_id: ProcessTemplateId(template_id.clone()),
+                    state,
+                    pid: pid_u32,
+                    resources: None,
+                    exit_code: None,
+                    message: message.clone(),
+                    restart,
+                    restart_attempts,
+                    stdin,
+                    graceful_stop,
+                    pgid,
+                    logs: sink.buffer.clone(),
+                    log_file_tx: sink.file_tx.clone(),
+                    fail_category: None,
+                    failure_reason: None,
+                    failure_hint: None,
+                    players: None,
+                    progress: None,
+                    start_phase: None,
+                },
+            );
+            if prev.map(|e| e.state) != Some(state) {
+                self.events
+                    .emit(&process_id, ProcessEventKind::StateChanged(state));
+            }
+        }
+        if state == ProcessState::Starting
+            && let Some(waiting) = &message
+        {
+            let waiting = waiting.trim_end_matches("...");
+            self.set_start_phase(&process_id, StartPhase::WaitingForReady, waiting)
+                .await;
+        }
 
-async fn wait_for_local_tcp_port(port: u16, timeout: Duration) -> bool {
-    let deadline = tokio::time::Instant::now() + timeout;
-    loop {
-        if let Ok(s) = tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
-            drop(s);
-            return true;
+        self.audit.record(
+            "start",
+            &process_id,
+            &template_id,
+            match pid_u32 {
+                Some(pid) => format!("pid {pid}"),
+                None => String::new(),
+            },
+        );
+
+        if let Some(pid) = pid_u32 {
+            self.spawn_resource_sampler(process_id.clone(), pid);
         }
+        self.spawn_cache_gc();
 
-        if tokio::time::Instant::now() >= deadline {
-            return false;
+        let probe_port = port_probe.as_ref().and_then(PortProbe::port);
+        if port_probe.is_none() {
+            port_alloc::release(&process_id);
         }
-        tokio::time::sleep(Duration::from_millis(250)).await;
-    }
-}
+        if let Some(probe) = port_probe {
+            let inner = self.inner.clone();
+            let events = self.events.clone();
+            let audit = self.audit.clone();
+            let probe_template_id = template_id.clone();
+            let id_str = process_id.clone();
+            let probe_sink = sink.clone();
+            let frp_instance_dir = instance_dir.clone();
+            tokio::spawn(async move {
+                // A bind failure ends the wait early; the error carries the
+                // entry message and whether the port was taken.
+                let outcome = tokio::select! {
+                    res = probe.wait(&probe_sink.buffer, ready_cursor) => {
+                        res.map_err(|timeout| (probe.timeout_message(timeout), false))
+                    }
+                    msg = wait_for_port_in_use(
+                        &probe_sink.buffer,
+                        ready_cursor,
+                        &probe_template_id,
+                        probe_port,
+                    ) => Err((msg, true)),
+                };
+                port_alloc::release(&id_str);
 
-async fn set_entry_message(
-    inner: &Arc<Mutex<HashMap<String, ProcessEntry>>>,
-    process_id: &str,
-    message: Option<String>,
-) {
-    let mut map = inner.lock().await;
-    let Some(e) = map.get_mut(process_id) else {
-        return;
-    };
-    e.message = message;
-}
+                let (pgid, should_kill) = {
+                    let mut map = inner.lock().await;
+                    let Some(e) = map.get_mut(&id_str) else {
+                        return;
+                    };
+                    if e.pid != pid_u32 || !matches!(e.state, ProcessState::Starting) {
+                        return;
+                    }
 
-#[derive(Debug)]
-struct ProcessEntry {
-    template_id: ProcessTemplateId,
-    state: ProcessState,
-    pid: Option<u32>,
-    resources: Option<alloy_process::ProcessResources>,
-    exit_code: Option<i32>,
-    message: Option<String>,
-    restart: RestartConfig,
-    restart_attempts: u32,
-    stdin: Option<ChildStdin>,
-    graceful_stdin: Option<String>,
-    pgid: Option<i32>,
-    logs: Arc<Mutex<LogBuffer>>,
-    log_file_tx: Option<mpsc::UnboundedSender<String>>,
-}
+                    let res = match &outcome {
+                        Ok(status) => {
+                            e.state = ProcessState::Running;
+                            e.message = None;
+                            e.players = status.as_ref().map(player_count);
+                            (e.pgid, false)
+                        }
+                        Err((msg, port_in_use)) => {
+                            e.state = ProcessState::Failed;
+                            e.message = Some(msg.clone());
+                            if *port_in_use {
+                                e.fail_category = Some(FailCategory::Port);
+                            }
+                            (e.pgid, true)
+                        }
+                    };
+                    events.emit(&id_str, ProcessEventKind::StateChanged(e.state));
+                    res
+                };
 
-#[derive(Clone, Debug, Default)]
-pub struct ProcessManager {
-    inner: Arc<Mutex<HashMap<String, ProcessEntry>>>,
-}
+                if let Err((msg, port_in_use)) = &outcome {
+                    probe_sink.emit(format!("[alloy-agent] {kind} {msg}")).await;
+                    if should_kill && let Some(pgid) = pgid {
+                        #[cfg(unix)]
+                        unsafe {
+                            libc::kill(-pgid, libc::SIGTERM);
+                        }
+                        audit.record(
+                            "sigterm",
+                            &id_str,
+                            &probe_template_id,
+                            if *port_in_use {
+                                format!("pgid {pgid}: port in use")
+                            } else {
+                                format!("pgid {pgid}: readiness timeout")
+                            },
+                        );
+                    }
+                    return;
+                }
 
-impl ProcessManager {
-    fn spawn_resource_sampler(&self, process_id: String, pid: u32) {
+                let Some(port) = probe.port() else {
+                    if matches!(probe, PortProbe::Log(_)) {
+                        probe_sink
+                            .emit(format!("[alloy-agent] {kind} reported ready"))
+                            .await;
+                    }
+                    return;
+                };
+                if let (Some(cfg), Some(pgid)) = (frp_config, pgid) {
+                    let started = start_frpc_sidecar(
+                        probe_sink.clone(),
+                        frp_instance_dir,
+                        pgid,
+                        port,
+                        cfg,
+                        &Frpc::from_env(),
+                    )
+                    .await;
+                    let failure = match started {
+                        Ok(FrpcStart::Started) => None,
+                        Ok(FrpcStart::Rejected(msg)) => Some(msg),
+                        Err(e) => {
+                            probe_sink
+                                .emit(format!("[alloy-agent] frpc start failed: {e}"))
+                                .await;
+                            Some(e.to_string())
+                        }
+                    };
+                    if let Some(reason) = failure {
+                        set_entry_message(
+                            &inner,
+                            &id_str,
+                            Some(format!("tunnel_failed: {reason}")),
+                        )
+                        .await;
+                    }
+                }
+                let ready = match &outcome {
+                    Ok(Some(status)) => format!(
+                        "[alloy-agent] {kind} answered server list ping on port {port}: {} ({}/{} players)",
+                        status.version, status.players_online, status.max_players
+                    ),
+                    _ if matches!(probe, PortProbe::Udp { .. }) => {
+                        format!("[alloy-agent] {kind} udp port {port} is up")
+                    }
+                    _ => format!("[alloy-agent] {kind} port {port} is accepting connections"),
+                };
+                probe_sink.emit(ready).await;
+                if matches!(outcome, Ok(Some(_))) {
+                    refresh_player_count(inner, id_str, pid_u32, port).await;
+                }
+            });
+        }
+
+        let manager = self.clone();
         let inner = self.inner.clone();
+        let id_str = process_id.clone();
+        let wait_sink = sink.clone();
+        let restart_template_id = template_id.clone();
         tokio::spawn(async move {
-            let mut last: Option<(u64, tokio::time::Instant)> = None;
-            let interval = resource_sample_interval();
+            let res = child.wait().await;
+            #[cfg(unix)]
+            if let Some(pgid) = pgid {
+                unsafe {
+                    libc::kill(-pgid, libc::SIGTERM);
+                }
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                let alive = unsafe { libc::kill(-pgid, 0) == 0 };
+                if alive {
+                    unsafe {
+                        libc::kill(-pgid, libc::SIGKILL);
+                    }
+                    manager.audit.record(
+                        "sigkill",
+                        &id_str,
+                        &restart_template_id,
+                        format!("pgid {pgid}: leftover process group"),
+                    );
+                }
+            }
+            let runtime = tokio::time::Instant::now().duration_since(started);
+            let recent_logs = wait_sink.recent_lines(200).await;
+            let world_hint = corruption_hint(&restart_template_id, &recent_logs, &instance_dir);
 
-            loop {
-                let now = tokio::time::Instant::now();
-                let Some(ticks) = read_proc_cpu_ticks(pid).await else {
-                    break;
+            let mut restart_after: Option<Duration> = None;
+            let mut restart_attempt: u32 = 0;
+            let mut shown_hint: Option<&'static str> = None;
+
+            let (final_state, exit_code) = {
+                let mut map = inner.lock().await;
+                let Some(e) = map.get_mut(&id_str) else {
+                    return;
                 };
-                let rss_bytes = read_proc_rss_bytes(pid).await.unwrap_or(0);
-                let (read_bytes, write_bytes) = read_proc_io_bytes(pid).await.unwrap_or((0, 0));
 
-                let cpu_percent_x100 = last
-                    .map(|(prev_ticks, prev_at)| cpu_percent_x100(prev_ticks, prev_at, ticks, now))
-                    .unwrap_or(0);
-                last = Some((ticks, now));
+                e.stdin = None;
+                e.players = None;
+                let stopping = matches!(e.state, ProcessState::Stopping);
 
+                match res {
+                    Ok(status) => {
+                        e.exit_code = status.code();
+
+                        if stopping {
+                            e.state = ProcessState::Exited;
+                            e.message = Some("stopped".to_string());
+                        } else if !oneshot && runtime < early_exit_threshold() {
+                            e.state = ProcessState::Failed;
+                            e.message =
+                                Some(format!("exited too quickly ({}ms)", runtime.as_millis()));
+                        } else if status.success() {
+                            e.state = ProcessState::Exited;
+                            e.message = Some("exited".to_string());
+                        } else {
+                            e.state = ProcessState::Failed;
+                            e.message = Some(format!(
+                                "exited with code {}",
+                                status.code().unwrap_or_default()
+                            ));
+                        }
+                    }
+                    Err(err) => {
+                        e.state = ProcessState::Failed;
+                        e.message = Some(format!("wait failed: {err}"));
+                    }
+                }
+
+                // The server exits right after a failed bind, often before
+                // the readiness probe sees the marker.
+                if !stopping
+                    && matches!(e.state, ProcessState::Failed)
+                    && let Some(msg) =
+                        port_in_use_message(&restart_template_id, probe_port, &recent_logs)
                 {
-                    let mut map = inner.lock().await;
-                    let Some(e) = map.get_mut(&process_id) else {
-                        break;
+                    e.message = Some(msg);
+                    e.fail_category = Some(FailCategory::Port);
+                } else if !stopping
+                    && matches!(e.state, ProcessState::Failed)
+                    && let Some(hint) = world_hint
+                {
+                    shown_hint = Some(hint);
+                    e.message = Some(format!(
+                        "{}; {hint}",
+                        e.message.as_deref().unwrap_or("failed")
+                    ));
+                }
+
+                if !stopping
+                    && matches!(e.state, ProcessState::Failed)
+                    && let Some((reason, hint)) = classify_failure(&recent_logs, e.exit_code)
+                {
+                    e.failure_reason = Some(reason);
+                    e.failure_hint = Some(hint.to_string());
+                }
+
+                if !stopping {
+                    let is_failure = matches!(e.state, ProcessState::Failed)
+                        || e.exit_code.is_some_and(|c| c != 0);
+                    let should_restart = match e.restart.policy {
+                        RestartPolicy::Off => false,
+                        RestartPolicy::Always => true,
+                        RestartPolicy::OnFailure => is_failure,
                     };
-                    if e.pid != Some(pid) {
-                        break;
+
+                    if should_restart && e.restart_attempts < e.restart.max_retries {
+                        e.restart_attempts = e.restart_attempts.saturating_add(1);
+                        let delay_ms = compute_backoff_ms(e.restart, e.restart_attempts);
+                        restart_after = Some(Duration::from_millis(delay_ms));
+                        restart_attempt = e.restart_attempts;
+                        e.message = Some(format!(
+                            "restarting in {}ms (attempt {}/{})",
+                            delay_ms, restart_attempt, e.restart.max_retries
+                        ));
                     }
-                    e.resources = Some(alloy_process::ProcessResources {
-                        cpu_percent_x100,
-                        rss_bytes,
-                        read_bytes,
-                        write_bytes,
-                    });
                 }
 
-                tokio::time::sleep(interval).await;
+                (e.state, e.exit_code)
+            };
+
+            manager.events.emit(
+                &id_str,
+                ProcessEventKind::Exited {
+                    state: final_state,
+                    exit_code,
+                },
+            );
+            if let Some(delay) = restart_after {
+                manager.events.emit(
+                    &id_str,
+                    ProcessEventKind::RestartScheduled {
+                        attempt: restart_attempt,
+                        delay_ms: delay.as_millis() as u64,
+                    },
+                );
+            }
+
+            wait_sink
+                .emit(format!(
+                    "[alloy-agent] process exited: state={:?} exit_code={:?} runtime_ms={}",
+                    final_state,
+                    exit_code,
+                    runtime.as_millis()
+                ))
+                .await;
+
+            if let Some(hint) = shown_hint {
+                wait_sink.emit(format!("[alloy-agent] {hint}")).await;
+            }
+
+            if oneshot {
+                let grace = oneshot_forget_grace();
+                wait_sink
+                    .emit(format!(
+                        "[alloy-agent] oneshot finished; entry is forgotten in {}s",
+                        grace.as_secs()
+                    ))
+                    .await;
+                let manager = manager.clone();
+                let id_str = id_str.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(grace).await;
+                    let _ = manager.forget(&id_str).await;
+                });
+            }
+
+            if let Some(delay) = restart_after {
+                wait_sink
+                    .emit(format!(
+                        "[alloy-agent] auto-restart scheduled in {}ms (attempt {})",
+                        delay.as_millis(),
+                        restart_attempt
+                    ))
+                    .await;
+                let handle = tokio::runtime::Handle::current();
+                let wait_sink = wait_sink.clone();
+                tokio::task::spawn_blocking(move || {
+                    std::thread::sleep(delay);
+                    let res = handle.block_on(manager.start_from_template_with_process_id(
+                        &id_str,
+                        &restart_template_id,
+                        params,
+                    ));
+                    match res {
+                        Ok(st) if matches!(st.state, ProcessState::Failed) => {
+                            let msg = st
+                                .message
+                                .filter(|s| !s.trim().is_empty())
+                                .unwrap_or_else(|| "unknown error".to_string());
+                            handle.block_on(
+                                wait_sink.emit(format!("[alloy-agent] auto-restart failed: {msg}")),
+                            );
+                        }
+                        Ok(_) => {
+                            handle.block_on(
+                                wait_sink.emit("[alloy-agent] auto-restart triggered".to_string()),
+                            );
+                        }
+                        Err(err) => {
+                            handle.block_on(
+                                wait_sink.emit(format!("[alloy-agent] auto-restart failed: {err}")),
+                            );
+                        }
+                    }
+                });
             }
         });
+
+        Ok(ProcessStatus {
+            id: ProcessId(process_id),
+            template_id: ProcessTemplateId(template_id),
+            state,
+            pid: pid_u32,
+            exit_code: None,
+            message,
+            resources: None,
+            fail_category: None,
+            failure_reason: None,
+            failure_hint: None,
+            players: None,
+            progress: None,
+        })
     }
 
     pub async fn start_from_template_with_process_id(
@@ -1444,6 +4967,13 @@ impl ProcessManager {
                 reused_logs = Some(old.logs);
             }
         }
+        // Ports left reserved by an earlier attempt of this process.
+        port_alloc::release(process_id);
+        templates::merge_node_defaults(
+            template_id,
+            &mut params,
+            &crate::node_defaults::load().await,
+        );
 
         let base = templates::find_template(template_id)
             .ok_or_else(|| anyhow::anyhow!("unknown template_id: {template_id}"))?;
@@ -1454,35 +4984,27 @@ impl ProcessManager {
             reused_logs.unwrap_or_else(|| Arc::new(Mutex::new(LogBuffer::default())));
 
         let root_dir = if t.template_id == "minecraft:vanilla"
+            || t.template_id == "minecraft:paper"
             || t.template_id == "minecraft:modrinth"
             || t.template_id == "minecraft:import"
             || t.template_id == "minecraft:curseforge"
             || t.template_id == "dst:vanilla"
             || t.template_id == "terraria:vanilla"
+            || t.template_id == "terraria:tmodloader"
+            || custom_templates::is_custom(&t.template_id)
         {
             minecraft::instance_dir(&id.0)
         } else {
             minecraft::data_root().join("processes").join(&id.0)
         };
 
-        let console_log_path = root_dir.join("logs").join("console.log");
-        let (max_bytes, max_files) = log_file_limits();
-        let (log_tx, mut log_rx) = mpsc::unbounded_channel::<String>();
-        tokio::spawn({
-            let path = console_log_path.clone();
-            async move {
-                let Ok(mut writer) = FileLogWriter::open(path, max_bytes, max_files).await else {
-                    return;
-                };
-                while let Some(line) = log_rx.recv().await {
-                    let _ = writer.write_line(&line).await;
-                }
-            }
-        });
+        let log_tx = spawn_console_log_writer(root_dir.join("logs").join("console.log"));
 
         let sink = LogSink {
             buffer: logs.clone(),
             file_tx: Some(log_tx.clone()),
+            limiter: log_rate_limit_lines_per_sec()
+                .map(|limit| Arc::new(std::sync::Mutex::new(LineRateLimiter::new(limit)))),
         };
 
         sink.emit(format!(
@@ -1495,7 +5017,7 @@ impl ProcessManager {
         let initial_restart = parse_restart_config(&params);
         {
             let mut inner = self.inner.lock().await;
-            inner.insert(
+            let prev = inner.insert(
                 id.0.clone(),
                 ProcessEntry {
                     template_id: ProcessTemplateId(t.template_id.clone()),
@@ -1507,13 +5029,27 @@ impl ProcessManager {
                     restart: initial_restart,
                     restart_attempts: reused_restart_attempts,
                     stdin: None,
-                    graceful_stdin: t.graceful_stdin.clone(),
+                    graceful_stop: t.graceful_stop.clone(),
                     pgid: None,
                     logs: logs.clone(),
                     log_file_tx: Some(log_tx.clone()),
+                    fail_category: None,
+                    failure_reason: None,
+                    failure_hint: None,
+                    players: None,
+                    progress: None,
+                    start_phase: None,
                 },
             );
+            if prev.map(|e| e.state) != Some(ProcessState::Starting) {
+                self.events.emit(
+                    &id.0,
+                    ProcessEventKind::StateChanged(ProcessState::Starting),
+                );
+            }
         }
+        self.set_start_phase(&id.0, StartPhase::Preparing, "preparing")
+            .await;
 
         let result: anyhow::Result<ProcessStatus> = async {
             if t.template_id == "minecraft:vanilla" {
@@ -1527,9 +5063,10 @@ impl ProcessManager {
                 })?;
 
                 let mc = minecraft::validate_vanilla_params(&params)?;
+                let jvm_args = minecraft_launch::jvm_args(mc.memory_mb, &params)?;
 
                 // Allow auto port assignment (port=0 means "auto").
-                let mc_port = port_alloc::allocate_tcp_port(mc.port).map_err(|e| {
+                let mc_port = port_alloc::reserve_tcp_port(&id.0, mc.port).map_err(|e| {
                     let mut fields = BTreeMap::new();
                     fields.insert("port".to_string(), e.to_string());
                     crate::error_payload::anyhow(
@@ -1573,22 +5110,19 @@ impl ProcessManager {
                             ),
                         )
                     })?;
-                let have_java = detect_java_major()?;
+                let java = resolve_java_path(&params);
+                let have_java = detect_java_major(&java)?;
                 if have_java != resolved.java_major {
-                    return Err(crate::error_payload::anyhow(
-                        "java_major_mismatch",
-                        format!(
-                            "Need Java {} for Minecraft {}, but runtime has Java {}.",
-                            resolved.java_major, resolved.version_id, have_java
-                        ),
-                        None,
-                        Some(format!(
-                            "Install Java {} (Temurin recommended), or use the Alloy agent Docker image.",
-                            resolved.java_major
-                        )),
+                    return Err(java_major_mismatch(
+                        &java,
+                        have_java,
+                        resolved.java_major,
+                        &resolved.version_id,
                     ));
                 }
 
+                self.set_start_phase(&id.0, StartPhase::Downloading, "downloading minecraft server.jar").await;
+
                 set_entry_message(
                     &self.inner,
                     &id.0,
@@ -1597,8 +5131,17 @@ impl ProcessManager {
                 .await;
                 sink.emit("[alloy-agent] downloading minecraft server.jar".to_string())
                     .await;
-                let cached_jar = minecraft_download::ensure_server_jar(&resolved)
-                    .await
+                let cached_jar = minecraft_download::ensure_server_jar_with_progress(
+                    &resolved,
+                    Some(download_reporter(
+                        &self.inner,
+                        &self.events,
+                        &id.0,
+                        &sink,
+                        "downloading minecraft server.jar",
+                    )),
+                )
+                .await
                     .map_err(|e| {
                         crate::error_payload::anyhow(
                             "download_failed",
@@ -1618,381 +5161,193 @@ impl ProcessManager {
                     )
                 })?;
 
-                let exec = "java".to_string();
-                let raw_args = vec![
-                    format!("-Xmx{}M", mc.memory_mb),
-                    "-jar".to_string(),
-                    "server.jar".to_string(),
-                    "nogui".to_string(),
-                ];
+                let exec = java.display().to_string();
+                let raw_args = minecraft_launch::jar_args(&jvm_args);
+
+                return self
+                    .spawn_supervised(LaunchContext {
+                        process_id: id.0.clone(),
+                        template_id: t.template_id.clone(),
+                        kind: "minecraft",
+                        exec_detail: format!("port={} version={}", mc.port, resolved.version_id),
+                        exec,
+                        args: raw_args,
+                        cwd: dir.clone(),
+                        instance_dir: dir.clone(),
+                        extra_rw_paths: Vec::new(),
+                        env: Vec::new(),
+                        spawn_hint: JAVA_SPAWN_HINT,
+                        port_probe: Some(PortProbe::minecraft(mc.port)),
+                        frp_config: frp_config_param(&params),
+                        restart,
+                        restart_attempts: reused_restart_attempts,
+                        graceful_stop: t.graceful_stop.clone(),
+                        sink: sink.clone(),
+                        params: params.clone(),
+                        companions: Vec::new(),
+                    })
+                    .await;
+            }
 
-                let (mut cmd, sandbox_launch) = prepare_instance_command(
-                    &id.0,
-                    &t.template_id,
-                    &params,
-                    &dir,
-                    &dir,
-                    &exec,
-                    &raw_args,
-                    &[],
-                )?;
+            if t.template_id == "minecraft:paper" {
+                ensure_min_free_space(&minecraft::data_root()).map_err(|e| {
+                    crate::error_payload::anyhow(
+                        "insufficient_disk",
+                        e.to_string(),
+                        None,
+                        Some("Free up disk space under ALLOY_DATA_ROOT and try again.".to_string()),
+                    )
+                })?;
 
-                let started_at_unix_ms = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64;
-                let mut run = RunInfo {
-                    process_id: id.0.clone(),
-                    template_id: t.template_id.clone(),
-                    started_at_unix_ms,
-                    agent_version: env!("CARGO_PKG_VERSION").to_string(),
-                    pid: None,
-                    pgid: None,
-                    container_name: sandbox_launch.container_name().map(ToOwned::to_owned),
-                    container_id: None,
-                    exec: sandbox_launch.exec.clone(),
-                    args: sandbox_launch.args.clone(),
-                    cwd: sandbox_launch.cwd.display().to_string(),
-                    params: redact_params(params.clone()),
-                    env: collect_safe_env(),
-                };
-                let _ = write_run_json(&dir, &run).await;
+                let mc = minecraft_paper::validate_params(&params)?;
+                let jvm_args = minecraft_launch::jvm_args(mc.memory_mb, &params)?;
 
-                sink.emit(format!("[alloy-agent] sandbox: {}", sandbox_launch.summary()))
-                    .await;
-                for warning in sandbox_launch.warnings() {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warning}"))
-                        .await;
-                }
+                let mc_port = port_alloc::reserve_tcp_port(&id.0, mc.port).map_err(|e| {
+                    let mut fields = BTreeMap::new();
+                    fields.insert("port".to_string(), e.to_string());
+                    crate::error_payload::anyhow(
+                        "invalid_param",
+                        "invalid port",
+                        Some(fields),
+                        Some(
+                            "Pick another port, or leave it blank (0) to auto-assign a free port."
+                                .to_string(),
+                        ),
+                    )
+                })?;
+                let mc = minecraft_paper::PaperParams { port: mc_port, ..mc };
+                params.insert("port".to_string(), mc_port.to_string());
+                let restart = parse_restart_config(&params);
 
-                sink.emit(format!(
-                    "[alloy-agent] minecraft exec: {} {} (cwd {}) port={} version={}",
-                    sandbox_launch.exec,
-                    sandbox_launch.args.join(" "),
-                    sandbox_launch.cwd.display(),
-                    mc.port,
-                    resolved.version_id
-                ))
-                .await;
+                let dir = minecraft::instance_dir(&id.0);
+                minecraft::ensure_vanilla_instance_layout(
+                    &dir,
+                    &minecraft::VanillaParams {
+                        accept_eula: mc.accept_eula,
+                        version: mc.mc_version.clone(),
+                        memory_mb: mc.memory_mb,
+                        port: mc.port,
+                    },
+                )?;
 
                 set_entry_message(
                     &self.inner,
                     &id.0,
-                    Some(format!("spawning minecraft server (port {})...", mc.port)),
+                    Some("resolving paper build...".to_string()),
                 )
                 .await;
-
-                let mut child = cmd
-                    .spawn()
-                    .with_context(|| format!("spawn minecraft server (cwd {})", dir.display()))
+                sink.emit("[alloy-agent] resolving paper build".to_string())
+                    .await;
+                let resolved = minecraft_paper::resolve_paper_jar(&mc.mc_version, mc.build)
+                    .await
                     .map_err(|e| {
                         crate::error_payload::anyhow(
-                            "spawn_failed",
-                            e.to_string(),
+                            "download_failed",
+                            format!("failed to resolve paper build: {e:#}"),
                             None,
                             Some(
-                                "Ensure Java is installed and the instance directory is writable."
+                                "Check the Minecraft version and build, and network connectivity to api.papermc.io."
                                     .to_string(),
                             ),
                         )
                     })?;
-                let started = tokio::time::Instant::now();
-                let pid_u32 = child.id();
-                let pgid = pid_u32.map(|p| p as i32);
-
-                if let Some(pid) = pid_u32
-                    && let Some(warn) = sandbox_launch.attach_pid(pid)
-                {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warn}"))
-                        .await;
-                }
-
-                run.pid = pid_u32;
-                run.pgid = pgid;
-                refresh_docker_container_metadata(&id.0, &mut run).await;
-                let _ = write_run_json(&dir, &run).await;
-
-                let stdin = child.stdin.take();
-                let stdout = child.stdout.take();
-                let stderr = child.stderr.take();
-
-                if let Some(out) = stdout {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(out).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stdout] {line}")).await;
-                        }
-                    });
-                }
-                if let Some(err) = stderr {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(err).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stderr] {line}")).await;
-                        }
-                    });
-                }
-
-                {
-                    let mut inner = self.inner.lock().await;
-                    inner.insert(
-                        id.0.clone(),
-                        ProcessEntry {
-                            template_id: ProcessTemplateId(t.template_id.clone()),
-                            state: ProcessState::Starting,
-                            pid: pid_u32,
-                            resources: None,
-                            exit_code: None,
-                            message: Some(format!("waiting for port {}...", mc.port)),
-                            restart,
-                            restart_attempts: reused_restart_attempts,
-                            stdin,
-                            graceful_stdin: t.graceful_stdin.clone(),
-                            pgid,
-                            logs: logs.clone(),
-                            log_file_tx: Some(log_tx.clone()),
-                        },
-                    );
-                }
-
-                if let Some(pid) = pid_u32 {
-                    self.spawn_resource_sampler(id.0.clone(), pid);
+                // Paper publishes no Java requirement; Mojang's metadata for the
+                // same Minecraft version has it.
+                let vanilla = minecraft_download::resolve_server_jar(&resolved.mc_version)
+                    .await
+                    .map_err(|e| {
+                        crate::error_payload::anyhow(
+                            "download_failed",
+                            format!("failed to resolve minecraft version metadata: {e}"),
+                            None,
+                            Some(
+                                "Check network connectivity to Mojang piston-meta endpoints."
+                                    .to_string(),
+                            ),
+                        )
+                    })?;
+                let java = resolve_java_path(&params);
+                let have_java = detect_java_major(&java)?;
+                if have_java != vanilla.java_major {
+                    return Err(java_major_mismatch(
+                        &java,
+                        have_java,
+                        vanilla.java_major,
+                        &resolved.mc_version,
+                    ));
                 }
 
-                let manager = self.clone();
-                let inner = self.inner.clone();
-                let id_str = id.0.clone();
-
-                // Port probe: only mark Running once the server actually listens.
-                let probe_sink = sink.clone();
-                let port = mc.port;
-                let frp_config = params
-                    .get("frp_config")
-                    .map(|v| v.trim())
-                    .filter(|v| !v.is_empty())
-                    .map(|v| v.to_string());
-                let frp_instance_dir = dir.clone();
-                tokio::spawn({
-                    let inner = inner.clone();
-                    let id_str = id_str.clone();
-                    let frp_config = frp_config.clone();
-                    let frp_instance_dir = frp_instance_dir.clone();
-                    async move {
-                        let timeout = port_probe_timeout();
-                        let ok = wait_for_local_tcp_port(port, timeout).await;
-
-                        let (pgid, should_kill) = {
-                            let mut map = inner.lock().await;
-                            let Some(e) = map.get_mut(&id_str) else {
-                                return;
-                            };
-                            if e.pid != pid_u32 || !matches!(e.state, ProcessState::Starting) {
-                                return;
-                            }
-
-                            if ok {
-                                e.state = ProcessState::Running;
-                                e.message = None;
-                                (e.pgid, false)
-                            } else {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!(
-                                    "port {} did not open within {}ms",
-                                    port,
-                                    timeout.as_millis()
-                                ));
-                                (e.pgid, true)
-                            }
-                        };
-
-                        if ok {
-                            if let (Some(cfg), Some(pgid)) = (frp_config.clone(), pgid) {
-                                if let Err(e) = start_frpc_sidecar(
-                                    probe_sink.clone(),
-                                    frp_instance_dir.clone(),
-                                    pgid,
-                                    port,
-                                    cfg,
-                                )
-                                .await
-                                {
-                                    probe_sink
-                                        .emit(format!("[alloy-agent] frpc start failed: {e}"))
-                                        .await;
-                                }
-                            }
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] minecraft port {} is accepting connections",
-                                    port
-                                ))
-                                .await;
-                        } else {
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] minecraft port {} did not open in time",
-                                    port
-                                ))
-                                .await;
-                            if should_kill && let Some(pgid) = pgid {
-                                #[cfg(unix)]
-                                unsafe {
-                                    libc::kill(-pgid, libc::SIGTERM);
-                                }
-                            }
-                        }
-                    }
-                });
-
-                let process_pgid = pgid;
-                let wait_sink = sink.clone();
-                let template_id = t.template_id.clone();
-                let params_for_restart = params.clone();
-                tokio::spawn(async move {
-                    let res = child.wait().await;
-                    #[cfg(unix)]
-                    if let Some(pgid) = process_pgid {
-                        unsafe {
-                            libc::kill(-pgid, libc::SIGTERM);
-                        }
-                        tokio::time::sleep(Duration::from_millis(500)).await;
-                        let alive = unsafe { libc::kill(-pgid, 0) == 0 };
-                        if alive {
-                            unsafe {
-                                libc::kill(-pgid, libc::SIGKILL);
-                            }
-                        }
-                    }
-                    let runtime = tokio::time::Instant::now().duration_since(started);
-
-                    let mut restart_after: Option<Duration> = None;
-                    let mut restart_attempt: u32 = 0;
-
-                    let (final_state, exit_code) = {
-                        let mut map = inner.lock().await;
-                        let Some(e) = map.get_mut(&id_str) else {
-                            return;
-                        };
-
-                        e.stdin = None;
-                        let stopping = matches!(e.state, ProcessState::Stopping);
-
-                        match res {
-                            Ok(status) => {
-                                e.exit_code = status.code();
-
-                                if stopping {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("stopped".to_string());
-                                } else if runtime < early_exit_threshold() {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited too quickly ({}ms)",
-                                        runtime.as_millis()
-                                    ));
-                                } else if status.success() {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("exited".to_string());
-                                } else {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited with code {}",
-                                        status.code().unwrap_or_default()
-                                    ));
-                                }
-                            }
-                            Err(err) => {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!("wait failed: {err}"));
-                            }
-                        }
-
-                        if !stopping {
-                            let is_failure = matches!(e.state, ProcessState::Failed)
-                                || e.exit_code.is_some_and(|c| c != 0);
-                            let should_restart = match e.restart.policy {
-                                RestartPolicy::Off => false,
-                                RestartPolicy::Always => true,
-                                RestartPolicy::OnFailure => is_failure,
-                            };
-
-                            if should_restart && e.restart_attempts < e.restart.max_retries {
-                                e.restart_attempts = e.restart_attempts.saturating_add(1);
-                                let delay_ms = compute_backoff_ms(e.restart, e.restart_attempts);
-                                restart_after = Some(Duration::from_millis(delay_ms));
-                                restart_attempt = e.restart_attempts;
-                                e.message = Some(format!(
-                                    "restarting in {}ms (attempt {}/{})",
-                                    delay_ms, restart_attempt, e.restart.max_retries
-                                ));
-                            }
-                        }
-
-                        (e.state, e.exit_code)
-                    };
-
-                    wait_sink
-                        .emit(format!(
-                            "[alloy-agent] process exited: state={:?} exit_code={:?} runtime_ms={}",
-                            final_state,
-                            exit_code,
-                            runtime.as_millis()
-                        ))
-                        .await;
+                self.set_start_phase(&id.0, StartPhase::Downloading, "downloading paper server jar").await;
+                set_entry_message(
+                    &self.inner,
+                    &id.0,
+                    Some("downloading paper server jar...".to_string()),
+                )
+                .await;
+                sink.emit(format!(
+                    "[alloy-agent] downloading paper {} build {}",
+                    resolved.mc_version, resolved.build
+                ))
+                .await;
+                let cached_jar = minecraft_paper::ensure_paper_jar_with_progress(
+                    &resolved,
+                    Some(download_reporter(
+                        &self.inner,
+                        &self.events,
+                        &id.0,
+                        &sink,
+                        "downloading paper server jar",
+                    )),
+                )
+                .await
+                .map_err(|e| {
+                    crate::error_payload::anyhow(
+                        "download_failed",
+                        format!("failed to download paper server jar: {e:#}"),
+                        None,
+                        Some("Try again; if it persists, clear cache and retry.".to_string()),
+                    )
+                })?;
 
-                    if let Some(delay) = restart_after {
-                        wait_sink
-                            .emit(format!(
-                                "[alloy-agent] auto-restart scheduled in {}ms (attempt {})",
-                                delay.as_millis(),
-                                restart_attempt
-                            ))
-                            .await;
-                        let handle = tokio::runtime::Handle::current();
-                        let wait_sink = wait_sink.clone();
-                        tokio::task::spawn_blocking(move || {
-                            std::thread::sleep(delay);
-                            let res = handle.block_on(manager.start_from_template_with_process_id(
-                                &id_str,
-                                &template_id,
-                                params_for_restart,
-                            ));
-                            match res {
-                                Ok(st) if matches!(st.state, ProcessState::Failed) => {
-                                    let msg = st
-                                        .message
-                                        .filter(|s| !s.trim().is_empty())
-                                        .unwrap_or_else(|| "unknown error".to_string());
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {msg}"
-                                    )));
-                                }
-                                Ok(_) => {
-                                    handle.block_on(wait_sink.emit(
-                                        "[alloy-agent] auto-restart triggered".to_string(),
-                                    ));
-                                }
-                                Err(err) => {
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {err}"
-                                    )));
-                                }
-                            }
-                        });
-                    }
-                });
+                let instance_jar = dir.join("server.jar");
+                materialize_minecraft_server_jar(&instance_jar, &cached_jar).map_err(|e| {
+                    crate::error_payload::anyhow(
+                        "spawn_failed",
+                        format!("failed to prepare server.jar: {e}"),
+                        None,
+                        Some("Ensure the instance directory is writable, then retry.".to_string()),
+                    )
+                })?;
 
-                return Ok(ProcessStatus {
-                    id: id.clone(),
-                    template_id: ProcessTemplateId(t.template_id.clone()),
-                    state: ProcessState::Starting,
-                    pid: pid_u32,
-                    exit_code: None,
-                    message: Some(format!("waiting for port {}...", mc.port)),
-                    resources: None,
-                });
+                let exec = java.display().to_string();
+                let raw_args = minecraft_launch::jar_args(&jvm_args);
+
+                return self
+                    .spawn_supervised(LaunchContext {
+                        process_id: id.0.clone(),
+                        template_id: t.template_id.clone(),
+                        kind: "minecraft",
+                        exec_detail: format!(
+                            "port={} version={} paper_build={}",
+                            mc.port, resolved.mc_version, resolved.build
+                        ),
+                        exec,
+                        args: raw_args,
+                        cwd: dir.clone(),
+                        instance_dir: dir.clone(),
+                        extra_rw_paths: Vec::new(),
+                        env: Vec::new(),
+                        spawn_hint: JAVA_SPAWN_HINT,
+                        port_probe: Some(PortProbe::minecraft(mc.port)),
+                        frp_config: frp_config_param(&params),
+                        restart,
+                        restart_attempts: reused_restart_attempts,
+                        graceful_stop: t.graceful_stop.clone(),
+                        sink: sink.clone(),
+                        params: params.clone(),
+                        companions: Vec::new(),
+                    })
+                    .await;
             }
 
             if t.template_id == "minecraft:modrinth" {
@@ -2006,8 +5361,9 @@ impl ProcessManager {
                 })?;
 
                 let mc = minecraft_modrinth::validate_params(&params)?;
+                let jvm_args = minecraft_launch::jvm_args(mc.memory_mb, &params)?;
 
-                let mc_port = port_alloc::allocate_tcp_port(mc.port).map_err(|e| {
+                let mc_port = port_alloc::reserve_tcp_port(&id.0, mc.port).map_err(|e| {
                     let mut fields = BTreeMap::new();
                     fields.insert("port".to_string(), e.to_string());
                     crate::error_payload::anyhow(
@@ -2028,12 +5384,15 @@ impl ProcessManager {
                 minecraft::ensure_vanilla_instance_layout(
                     &dir,
                     &minecraft::VanillaParams {
+                        accept_eula: mc.accept_eula,
                         version: "latest_release".to_string(),
                         memory_mb: mc.memory_mb,
                         port: mc.port,
                     },
                 )?;
 
+                self.set_start_phase(&id.0, StartPhase::Installing, "installing modpack").await;
+
                 set_entry_message(
                     &self.inner,
                     &id.0,
@@ -2042,9 +5401,21 @@ impl ProcessManager {
                 .await;
                 sink.emit("[alloy-agent] resolving modpack".to_string()).await;
 
-                let installed = minecraft_modrinth::ensure_installed(&dir, &mc.mrpack)
-                    .await
+                let installed = minecraft_modrinth::ensure_installed(
+                    &dir,
+                    &mc.source,
+                    download_reporter(&self.inner, &self.events, &id.0, &sink, "downloading modpack"),
+                    install_output_sender(&sink),
+                )
+                .await
                     .map_err(|e| {
+                        if let Some(err) = crate::archive::as_install_failed(&e) {
+                            return err;
+                        }
+                        // An unknown slug or version is the user's input, not a download problem.
+                        if crate::error_payload::decode(&e).is_some_and(|p| p.code == "invalid_param") {
+                            return e;
+                        }
                         crate::error_payload::anyhow(
                             "download_failed",
                             format!("failed to install modpack: {e}"),
@@ -2076,19 +5447,14 @@ impl ProcessManager {
                         )
                     })?;
 
-                let have_java = detect_java_major()?;
+                let java = resolve_java_path(&params);
+                let have_java = detect_java_major(&java)?;
                 if have_java != resolved.java_major {
-                    return Err(crate::error_payload::anyhow(
-                        "java_major_mismatch",
-                        format!(
-                            "Need Java {} for Minecraft {}, but runtime has Java {}.",
-                            resolved.java_major, resolved.version_id, have_java
-                        ),
-                        None,
-                        Some(format!(
-                            "Install Java {} (Temurin recommended), or use the Alloy agent Docker image.",
-                            resolved.java_major
-                        )),
+                    return Err(java_major_mismatch(
+                        &java,
+                        have_java,
+                        resolved.java_major,
+                        &resolved.version_id,
                     ));
                 }
 
@@ -2102,382 +5468,38 @@ impl ProcessManager {
                     ));
                 }
 
-                let exec = "java".to_string();
-                let raw_args = vec![
-                    format!("-Xmx{}M", mc.memory_mb),
-                    "-jar".to_string(),
-                    "server.jar".to_string(),
-                    "nogui".to_string(),
-                ];
-
-                let (mut cmd, sandbox_launch) = prepare_instance_command(
-                    &id.0,
-                    &t.template_id,
-                    &params,
-                    &dir,
-                    &dir,
-                    &exec,
-                    &raw_args,
-                    &[],
-                )?;
-
-                let started_at_unix_ms = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64;
-                let mut run = RunInfo {
-                    process_id: id.0.clone(),
-                    template_id: t.template_id.clone(),
-                    started_at_unix_ms,
-                    agent_version: env!("CARGO_PKG_VERSION").to_string(),
-                    pid: None,
-                    pgid: None,
-                    container_name: sandbox_launch.container_name().map(ToOwned::to_owned),
-                    container_id: None,
-                    exec: sandbox_launch.exec.clone(),
-                    args: sandbox_launch.args.clone(),
-                    cwd: sandbox_launch.cwd.display().to_string(),
-                    params: redact_params(params.clone()),
-                    env: collect_safe_env(),
-                };
-                let _ = write_run_json(&dir, &run).await;
-
-                sink.emit(format!("[alloy-agent] sandbox: {}", sandbox_launch.summary()))
-                    .await;
-                for warning in sandbox_launch.warnings() {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warning}"))
-                        .await;
-                }
-
-                sink.emit(format!(
-                    "[alloy-agent] minecraft(modrinth) exec: {} {} (cwd {}) port={} minecraft={} loader={}:{}",
-                    sandbox_launch.exec,
-                    sandbox_launch.args.join(" "),
-                    sandbox_launch.cwd.display(),
-                    mc.port,
-                    installed.minecraft,
-                    installed.loader,
-                    installed.loader_version,
-                ))
-                .await;
-
-                set_entry_message(
-                    &self.inner,
-                    &id.0,
-                    Some(format!("spawning minecraft server (port {})...", mc.port)),
-                )
-                .await;
-
-                let mut child = cmd
-                    .spawn()
-                    .with_context(|| format!("spawn minecraft server (cwd {})", dir.display()))
-                    .map_err(|e| {
-                        crate::error_payload::anyhow(
-                            "spawn_failed",
-                            e.to_string(),
-                            None,
-                            Some(
-                                "Ensure Java is installed and the instance directory is writable."
-                                    .to_string(),
-                            ),
-                        )
-                    })?;
-                let started = tokio::time::Instant::now();
-                let pid_u32 = child.id();
-                let pgid = pid_u32.map(|p| p as i32);
-
-                if let Some(pid) = pid_u32
-                    && let Some(warn) = sandbox_launch.attach_pid(pid)
-                {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warn}"))
-                        .await;
-                }
-
-                run.pid = pid_u32;
-                run.pgid = pgid;
-                refresh_docker_container_metadata(&id.0, &mut run).await;
-                let _ = write_run_json(&dir, &run).await;
-
-                let stdin = child.stdin.take();
-                let stdout = child.stdout.take();
-                let stderr = child.stderr.take();
-
-                if let Some(out) = stdout {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(out).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stdout] {line}")).await;
-                        }
-                    });
-                }
-                if let Some(err) = stderr {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(err).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stderr] {line}")).await;
-                        }
-                    });
-                }
-
-                {
-                    let mut inner = self.inner.lock().await;
-                    inner.insert(
-                        id.0.clone(),
-                        ProcessEntry {
-                            template_id: ProcessTemplateId(t.template_id.clone()),
-                            state: ProcessState::Starting,
-                            pid: pid_u32,
-                            resources: None,
-                            exit_code: None,
-                            message: Some(format!("waiting for port {}...", mc.port)),
-                            restart,
-                            restart_attempts: reused_restart_attempts,
-                            stdin,
-                            graceful_stdin: t.graceful_stdin.clone(),
-                            pgid,
-                            logs: logs.clone(),
-                            log_file_tx: Some(log_tx.clone()),
-                        },
-                    );
-                }
-
-                if let Some(pid) = pid_u32 {
-                    self.spawn_resource_sampler(id.0.clone(), pid);
-                }
-
-                let manager = self.clone();
-                let inner = self.inner.clone();
-                let id_str = id.0.clone();
-
-                let probe_sink = sink.clone();
-                let port = mc.port;
-                let frp_config = params
-                    .get("frp_config")
-                    .map(|v| v.trim())
-                    .filter(|v| !v.is_empty())
-                    .map(|v| v.to_string());
-                let frp_instance_dir = dir.clone();
-                tokio::spawn({
-                    let inner = inner.clone();
-                    let id_str = id_str.clone();
-                    let frp_config = frp_config.clone();
-                    let frp_instance_dir = frp_instance_dir.clone();
-                    async move {
-                        let timeout = port_probe_timeout();
-                        let ok = wait_for_local_tcp_port(port, timeout).await;
-
-                        let (pgid, should_kill) = {
-                            let mut map = inner.lock().await;
-                            let Some(e) = map.get_mut(&id_str) else {
-                                return;
-                            };
-                            if e.pid != pid_u32 || !matches!(e.state, ProcessState::Starting) {
-                                return;
-                            }
-
-                            if ok {
-                                e.state = ProcessState::Running;
-                                e.message = None;
-                                (e.pgid, false)
-                            } else {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!(
-                                    "port {} did not open within {}ms",
-                                    port,
-                                    timeout.as_millis()
-                                ));
-                                (e.pgid, true)
-                            }
-                        };
-
-                        if ok {
-                            if let (Some(cfg), Some(pgid)) = (frp_config.clone(), pgid) {
-                                if let Err(e) = start_frpc_sidecar(
-                                    probe_sink.clone(),
-                                    frp_instance_dir.clone(),
-                                    pgid,
-                                    port,
-                                    cfg,
-                                )
-                                .await
-                                {
-                                    probe_sink
-                                        .emit(format!("[alloy-agent] frpc start failed: {e}"))
-                                        .await;
-                                }
-                            }
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] minecraft port {} is accepting connections",
-                                    port
-                                ))
-                                .await;
-                        } else {
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] minecraft port {} did not open in time",
-                                    port
-                                ))
-                                .await;
-                            if should_kill && let Some(pgid) = pgid {
-                                #[cfg(unix)]
-                                unsafe {
-                                    libc::kill(-pgid, libc::SIGTERM);
-                                }
-                            }
-                        }
-                    }
-                });
-
-                let process_pgid = pgid;
-                let wait_sink = sink.clone();
-                let template_id = t.template_id.clone();
-                let params_for_restart = params.clone();
-                tokio::spawn(async move {
-                    let res = child.wait().await;
-                    #[cfg(unix)]
-                    if let Some(pgid) = process_pgid {
-                        unsafe {
-                            libc::kill(-pgid, libc::SIGTERM);
-                        }
-                        tokio::time::sleep(Duration::from_millis(500)).await;
-                        let alive = unsafe { libc::kill(-pgid, 0) == 0 };
-                        if alive {
-                            unsafe {
-                                libc::kill(-pgid, libc::SIGKILL);
-                            }
-                        }
-                    }
-                    let runtime = tokio::time::Instant::now().duration_since(started);
-
-                    let mut restart_after: Option<Duration> = None;
-                    let mut restart_attempt: u32 = 0;
-
-                    let (final_state, exit_code) = {
-                        let mut map = inner.lock().await;
-                        let Some(e) = map.get_mut(&id_str) else {
-                            return;
-                        };
-
-                        e.stdin = None;
-                        let stopping = matches!(e.state, ProcessState::Stopping);
-
-                        match res {
-                            Ok(status) => {
-                                e.exit_code = status.code();
-
-                                if stopping {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("stopped".to_string());
-                                } else if runtime < early_exit_threshold() {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited too quickly ({}ms)",
-                                        runtime.as_millis()
-                                    ));
-                                } else if status.success() {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("exited".to_string());
-                                } else {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited with code {}",
-                                        status.code().unwrap_or_default()
-                                    ));
-                                }
-                            }
-                            Err(err) => {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!("wait failed: {err}"));
-                            }
-                        }
-
-                        if !stopping {
-                            let is_failure = matches!(e.state, ProcessState::Failed)
-                                || e.exit_code.is_some_and(|c| c != 0);
-                            let should_restart = match e.restart.policy {
-                                RestartPolicy::Off => false,
-                                RestartPolicy::Always => true,
-                                RestartPolicy::OnFailure => is_failure,
-                            };
-
-                            if should_restart && e.restart_attempts < e.restart.max_retries {
-                                e.restart_attempts = e.restart_attempts.saturating_add(1);
-                                let delay_ms = compute_backoff_ms(e.restart, e.restart_attempts);
-                                restart_after = Some(Duration::from_millis(delay_ms));
-                                restart_attempt = e.restart_attempts;
-                                e.message = Some(format!(
-                                    "restarting in {}ms (attempt {}/{})",
-                                    delay_ms, restart_attempt, e.restart.max_retries
-                                ));
-                            }
-                        }
-
-                        (e.state, e.exit_code)
-                    };
-
-                    wait_sink
-                        .emit(format!(
-                            "[alloy-agent] process exited: state={:?} exit_code={:?} runtime_ms={}",
-                            final_state,
-                            exit_code,
-                            runtime.as_millis()
-                        ))
-                        .await;
-
-                    if let Some(delay) = restart_after {
-                        wait_sink
-                            .emit(format!(
-                                "[alloy-agent] auto-restart scheduled in {}ms (attempt {})",
-                                delay.as_millis(),
-                                restart_attempt
-                            ))
-                            .await;
-                        let handle = tokio::runtime::Handle::current();
-                        let wait_sink = wait_sink.clone();
-                        tokio::task::spawn_blocking(move || {
-                            std::thread::sleep(delay);
-                            let res = handle.block_on(manager.start_from_template_with_process_id(
-                                &id_str,
-                                &template_id,
-                                params_for_restart,
-                            ));
-                            match res {
-                                Ok(st) if matches!(st.state, ProcessState::Failed) => {
-                                    let msg = st
-                                        .message
-                                        .filter(|s| !s.trim().is_empty())
-                                        .unwrap_or_else(|| "unknown error".to_string());
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {msg}"
-                                    )));
-                                }
-                                Ok(_) => {
-                                    handle.block_on(wait_sink.emit(
-                                        "[alloy-agent] auto-restart triggered".to_string(),
-                                    ));
-                                }
-                                Err(err) => {
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {err}"
-                                    )));
-                                }
-                            }
-                        });
-                    }
-                });
-
-                return Ok(ProcessStatus {
-                    id: id.clone(),
-                    template_id: ProcessTemplateId(t.template_id.clone()),
-                    state: ProcessState::Starting,
-                    pid: pid_u32,
-                    exit_code: None,
-                    message: Some(format!("waiting for port {}...", mc.port)),
-                    resources: None,
-                });
+                let exec = java.display().to_string();
+                let raw_args = minecraft_launch::jar_args(&jvm_args);
+
+                return self
+                    .spawn_supervised(LaunchContext {
+                        process_id: id.0.clone(),
+                        template_id: t.template_id.clone(),
+                        kind: "minecraft",
+                        exec_detail: format!(
+                            "port={} minecraft={} loader={}:{}",
+                            mc.port,
+                            installed.minecraft,
+                            installed.loader,
+                            installed.loader_version
+                        ),
+                        exec,
+                        args: raw_args,
+                        cwd: dir.clone(),
+                        instance_dir: dir.clone(),
+                        extra_rw_paths: Vec::new(),
+                        env: Vec::new(),
+                        spawn_hint: JAVA_SPAWN_HINT,
+                        port_probe: Some(PortProbe::minecraft(mc.port)),
+                        frp_config: frp_config_param(&params),
+                        restart,
+                        restart_attempts: reused_restart_attempts,
+                        graceful_stop: t.graceful_stop.clone(),
+                        sink: sink.clone(),
+                        params: params.clone(),
+                        companions: Vec::new(),
+                    })
+                    .await;
             }
 
             if t.template_id == "minecraft:import" {
@@ -2491,8 +5513,9 @@ impl ProcessManager {
                 })?;
 
                 let mc = minecraft_import::validate_params(&params)?;
+                let jvm_args = minecraft_launch::jvm_args(mc.memory_mb, &params)?;
 
-                let mc_port = port_alloc::allocate_tcp_port(mc.port).map_err(|e| {
+                let mc_port = port_alloc::reserve_tcp_port(&id.0, mc.port).map_err(|e| {
                     let mut fields = BTreeMap::new();
                     fields.insert("port".to_string(), e.to_string());
                     crate::error_payload::anyhow(
@@ -2511,6 +5534,8 @@ impl ProcessManager {
 
                 let dir = minecraft::instance_dir(&id.0);
 
+                self.set_start_phase(&id.0, StartPhase::Installing, "importing server pack").await;
+
                 set_entry_message(
                     &self.inner,
                     &id.0,
@@ -2520,9 +5545,13 @@ impl ProcessManager {
                 sink.emit("[alloy-agent] importing minecraft server pack".to_string())
                     .await;
 
-                minecraft_import::ensure_imported(&dir, &mc.pack)
+                minecraft_import::ensure_imported(&dir, &mc.pack, install_output_sender(&sink))
                     .await
                     .map_err(|e| {
+                        // Download and extraction failures already carry their own payload.
+                        if crate::error_payload::decode(&e).is_some() {
+                            return e;
+                        }
                         crate::error_payload::anyhow(
                             "install_failed",
                             format!("failed to import server pack: {e}"),
@@ -2534,13 +5563,15 @@ impl ProcessManager {
                 minecraft::ensure_vanilla_instance_layout(
                     &dir,
                     &minecraft::VanillaParams {
+                        accept_eula: mc.accept_eula,
                         version: "latest_release".to_string(),
                         memory_mb: mc.memory_mb,
                         port: mc.port,
                     },
                 )?;
 
-                let launch = minecraft_launch::resolve_launch_spec(&dir, mc.memory_mb).map_err(|e| {
+                let java = resolve_java_path(&params);
+                let launch = minecraft_launch::resolve_launch_spec(&dir, &jvm_args, &java).map_err(|e| {
                     crate::error_payload::anyhow(
                         "install_failed",
                         format!("failed to detect launch command: {e}"),
@@ -2555,372 +5586,29 @@ impl ProcessManager {
                 let exec = launch.exec.clone();
                 let raw_args = launch.args.clone();
 
-                let (mut cmd, sandbox_launch) = prepare_instance_command(
-                    &id.0,
-                    &t.template_id,
-                    &params,
-                    &dir,
-                    &dir,
-                    &exec,
-                    &raw_args,
-                    &[],
-                )?;
-
-                let started_at_unix_ms = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64;
-                let mut run = RunInfo {
-                    process_id: id.0.clone(),
-                    template_id: t.template_id.clone(),
-                    started_at_unix_ms,
-                    agent_version: env!("CARGO_PKG_VERSION").to_string(),
-                    pid: None,
-                    pgid: None,
-                    container_name: sandbox_launch.container_name().map(ToOwned::to_owned),
-                    container_id: None,
-                    exec: sandbox_launch.exec.clone(),
-                    args: sandbox_launch.args.clone(),
-                    cwd: sandbox_launch.cwd.display().to_string(),
-                    params: redact_params(params.clone()),
-                    env: collect_safe_env(),
-                };
-                let _ = write_run_json(&dir, &run).await;
-
-                sink.emit(format!("[alloy-agent] sandbox: {}", sandbox_launch.summary()))
+                return self
+                    .spawn_supervised(LaunchContext {
+                        process_id: id.0.clone(),
+                        template_id: t.template_id.clone(),
+                        kind: "minecraft",
+                        exec_detail: format!("port={} launch={}", mc.port, launch.kind),
+                        exec,
+                        args: raw_args,
+                        cwd: dir.clone(),
+                        instance_dir: dir.clone(),
+                        extra_rw_paths: Vec::new(),
+                        env: Vec::new(),
+                        spawn_hint: JAVA_SPAWN_HINT,
+                        port_probe: Some(PortProbe::minecraft(mc.port)),
+                        frp_config: frp_config_param(&params),
+                        restart,
+                        restart_attempts: reused_restart_attempts,
+                        graceful_stop: t.graceful_stop.clone(),
+                        sink: sink.clone(),
+                        params: params.clone(),
+                        companions: Vec::new(),
+                    })
                     .await;
-                for warning in sandbox_launch.warnings() {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warning}"))
-                        .await;
-                }
-
-                sink.emit(format!(
-                    "[alloy-agent] minecraft(import) exec: {} {} (cwd {}) port={} launch={}",
-                    sandbox_launch.exec,
-                    sandbox_launch.args.join(" "),
-                    sandbox_launch.cwd.display(),
-                    mc.port,
-                    launch.kind
-                ))
-                .await;
-
-                set_entry_message(
-                    &self.inner,
-                    &id.0,
-                    Some(format!("spawning minecraft server (port {})...", mc.port)),
-                )
-                .await;
-
-                let mut child = cmd
-                    .spawn()
-                    .with_context(|| format!("spawn minecraft server (cwd {})", dir.display()))
-                    .map_err(|e| {
-                        crate::error_payload::anyhow(
-                            "spawn_failed",
-                            e.to_string(),
-                            None,
-                            Some(
-                                "Ensure Java is installed and the instance directory is writable."
-                                    .to_string(),
-                            ),
-                        )
-                    })?;
-                let started = tokio::time::Instant::now();
-                let pid_u32 = child.id();
-                let pgid = pid_u32.map(|p| p as i32);
-
-                if let Some(pid) = pid_u32
-                    && let Some(warn) = sandbox_launch.attach_pid(pid)
-                {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warn}"))
-                        .await;
-                }
-
-                run.pid = pid_u32;
-                run.pgid = pgid;
-                refresh_docker_container_metadata(&id.0, &mut run).await;
-                let _ = write_run_json(&dir, &run).await;
-
-                let stdin = child.stdin.take();
-                let stdout = child.stdout.take();
-                let stderr = child.stderr.take();
-
-                if let Some(out) = stdout {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(out).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stdout] {line}")).await;
-                        }
-                    });
-                }
-                if let Some(err) = stderr {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(err).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stderr] {line}")).await;
-                        }
-                    });
-                }
-
-                {
-                    let mut inner = self.inner.lock().await;
-                    inner.insert(
-                        id.0.clone(),
-                        ProcessEntry {
-                            template_id: ProcessTemplateId(t.template_id.clone()),
-                            state: ProcessState::Starting,
-                            pid: pid_u32,
-                            resources: None,
-                            exit_code: None,
-                            message: Some(format!("waiting for port {}...", mc.port)),
-                            restart,
-                            restart_attempts: reused_restart_attempts,
-                            stdin,
-                            graceful_stdin: t.graceful_stdin.clone(),
-                            pgid,
-                            logs: logs.clone(),
-                            log_file_tx: Some(log_tx.clone()),
-                        },
-                    );
-                }
-
-                if let Some(pid) = pid_u32 {
-                    self.spawn_resource_sampler(id.0.clone(), pid);
-                }
-
-                let manager = self.clone();
-                let inner = self.inner.clone();
-                let id_str = id.0.clone();
-
-                let probe_sink = sink.clone();
-                let port = mc.port;
-                let frp_config = params
-                    .get("frp_config")
-                    .map(|v| v.trim())
-                    .filter(|v| !v.is_empty())
-                    .map(|v| v.to_string());
-                let frp_instance_dir = dir.clone();
-                tokio::spawn({
-                    let inner = inner.clone();
-                    let id_str = id_str.clone();
-                    let frp_config = frp_config.clone();
-                    let frp_instance_dir = frp_instance_dir.clone();
-                    async move {
-                        let timeout = port_probe_timeout();
-                        let ok = wait_for_local_tcp_port(port, timeout).await;
-
-                        let (pgid, should_kill) = {
-                            let mut map = inner.lock().await;
-                            let Some(e) = map.get_mut(&id_str) else {
-                                return;
-                            };
-                            if e.pid != pid_u32 || !matches!(e.state, ProcessState::Starting) {
-                                return;
-                            }
-
-                            if ok {
-                                e.state = ProcessState::Running;
-                                e.message = None;
-                                (e.pgid, false)
-                            } else {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!(
-                                    "port {} did not open within {}ms",
-                                    port,
-                                    timeout.as_millis()
-                                ));
-                                (e.pgid, true)
-                            }
-                        };
-
-                        if ok {
-                            if let (Some(cfg), Some(pgid)) = (frp_config.clone(), pgid) {
-                                if let Err(e) = start_frpc_sidecar(
-                                    probe_sink.clone(),
-                                    frp_instance_dir.clone(),
-                                    pgid,
-                                    port,
-                                    cfg,
-                                )
-                                .await
-                                {
-                                    probe_sink
-                                        .emit(format!("[alloy-agent] frpc start failed: {e}"))
-                                        .await;
-                                }
-                            }
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] minecraft port {} is accepting connections",
-                                    port
-                                ))
-                                .await;
-                        } else {
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] minecraft port {} did not open in time",
-                                    port
-                                ))
-                                .await;
-                            if should_kill && let Some(pgid) = pgid {
-                                #[cfg(unix)]
-                                unsafe {
-                                    libc::kill(-pgid, libc::SIGTERM);
-                                }
-                            }
-                        }
-                    }
-                });
-
-                let process_pgid = pgid;
-                let wait_sink = sink.clone();
-                let template_id = t.template_id.clone();
-                let params_for_restart = params.clone();
-                tokio::spawn(async move {
-                    let res = child.wait().await;
-                    #[cfg(unix)]
-                    if let Some(pgid) = process_pgid {
-                        unsafe {
-                            libc::kill(-pgid, libc::SIGTERM);
-                        }
-                        tokio::time::sleep(Duration::from_millis(500)).await;
-                        let alive = unsafe { libc::kill(-pgid, 0) == 0 };
-                        if alive {
-                            unsafe {
-                                libc::kill(-pgid, libc::SIGKILL);
-                            }
-                        }
-                    }
-                    let runtime = tokio::time::Instant::now().duration_since(started);
-
-                    let mut restart_after: Option<Duration> = None;
-                    let mut restart_attempt: u32 = 0;
-
-                    let (final_state, exit_code) = {
-                        let mut map = inner.lock().await;
-                        let Some(e) = map.get_mut(&id_str) else {
-                            return;
-                        };
-
-                        e.stdin = None;
-                        let stopping = matches!(e.state, ProcessState::Stopping);
-
-                        match res {
-                            Ok(status) => {
-                                e.exit_code = status.code();
-
-                                if stopping {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("stopped".to_string());
-                                } else if runtime < early_exit_threshold() {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited too quickly ({}ms)",
-                                        runtime.as_millis()
-                                    ));
-                                } else if status.success() {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("exited".to_string());
-                                } else {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited with code {}",
-                                        status.code().unwrap_or_default()
-                                    ));
-                                }
-                            }
-                            Err(err) => {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!("wait failed: {err}"));
-                            }
-                        }
-
-                        if !stopping {
-                            let is_failure = matches!(e.state, ProcessState::Failed)
-                                || e.exit_code.is_some_and(|c| c != 0);
-                            let should_restart = match e.restart.policy {
-                                RestartPolicy::Off => false,
-                                RestartPolicy::Always => true,
-                                RestartPolicy::OnFailure => is_failure,
-                            };
-
-                            if should_restart && e.restart_attempts < e.restart.max_retries {
-                                e.restart_attempts = e.restart_attempts.saturating_add(1);
-                                let delay_ms = compute_backoff_ms(e.restart, e.restart_attempts);
-                                restart_after = Some(Duration::from_millis(delay_ms));
-                                restart_attempt = e.restart_attempts;
-                                e.message = Some(format!(
-                                    "restarting in {}ms (attempt {}/{})",
-                                    delay_ms, restart_attempt, e.restart.max_retries
-                                ));
-                            }
-                        }
-
-                        (e.state, e.exit_code)
-                    };
-
-                    wait_sink
-                        .emit(format!(
-                            "[alloy-agent] process exited: state={:?} exit_code={:?} runtime_ms={}",
-                            final_state,
-                            exit_code,
-                            runtime.as_millis()
-                        ))
-                        .await;
-
-                    if let Some(delay) = restart_after {
-                        wait_sink
-                            .emit(format!(
-                                "[alloy-agent] auto-restart scheduled in {}ms (attempt {})",
-                                delay.as_millis(),
-                                restart_attempt
-                            ))
-                            .await;
-                        let handle = tokio::runtime::Handle::current();
-                        let wait_sink = wait_sink.clone();
-                        tokio::task::spawn_blocking(move || {
-                            std::thread::sleep(delay);
-                            let res = handle.block_on(manager.start_from_template_with_process_id(
-                                &id_str,
-                                &template_id,
-                                params_for_restart,
-                            ));
-                            match res {
-                                Ok(st) if matches!(st.state, ProcessState::Failed) => {
-                                    let msg = st
-                                        .message
-                                        .filter(|s| !s.trim().is_empty())
-                                        .unwrap_or_else(|| "unknown error".to_string());
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {msg}"
-                                    )));
-                                }
-                                Ok(_) => {
-                                    handle.block_on(wait_sink.emit(
-                                        "[alloy-agent] auto-restart triggered".to_string(),
-                                    ));
-                                }
-                                Err(err) => {
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {err}"
-                                    )));
-                                }
-                            }
-                        });
-                    }
-                });
-
-                return Ok(ProcessStatus {
-                    id: id.clone(),
-                    template_id: ProcessTemplateId(t.template_id.clone()),
-                    state: ProcessState::Starting,
-                    pid: pid_u32,
-                    exit_code: None,
-                    message: Some(format!("waiting for port {}...", mc.port)),
-                    resources: None,
-                });
             }
 
             if t.template_id == "minecraft:curseforge" {
@@ -2934,443 +5622,118 @@ impl ProcessManager {
                 })?;
 
                 let mc = minecraft_curseforge::validate_params(&params)?;
+                let jvm_args = minecraft_launch::jvm_args(mc.memory_mb, &params)?;
 
-                let mc_port = port_alloc::allocate_tcp_port(mc.port).map_err(|e| {
+                let mc_port = port_alloc::reserve_tcp_port(&id.0, mc.port).map_err(|e| {
                     let mut fields = BTreeMap::new();
                     fields.insert("port".to_string(), e.to_string());
                     crate::error_payload::anyhow(
                         "invalid_param",
                         "invalid port",
                         Some(fields),
-                        Some(
-                            "Pick another port, or leave it blank (0) to auto-assign a free port."
-                                .to_string(),
-                        ),
-                    )
-                })?;
-                let mc = minecraft_curseforge::CurseforgeParams { port: mc_port, ..mc };
-                params.insert("port".to_string(), mc_port.to_string());
-                let restart = parse_restart_config(&params);
-
-                let dir = minecraft::instance_dir(&id.0);
-
-                set_entry_message(
-                    &self.inner,
-                    &id.0,
-                    Some("resolving curseforge modpack...".to_string()),
-                )
-                .await;
-                sink.emit("[alloy-agent] resolving curseforge modpack".to_string())
-                    .await;
-
-                let installed = minecraft_curseforge::ensure_installed(
-                    &dir,
-                    &mc.source,
-                    &mc.api_key,
-                )
-                .await
-                .map_err(|e| {
-                    crate::error_payload::anyhow(
-                        "download_failed",
-                        format!("failed to install curseforge pack: {e}"),
-                        None,
-                        Some("Check the CurseForge API key and network connectivity.".to_string()),
-                    )
-                })?;
-
-                minecraft::ensure_vanilla_instance_layout(
-                    &dir,
-                    &minecraft::VanillaParams {
-                        version: "latest_release".to_string(),
-                        memory_mb: mc.memory_mb,
-                        port: mc.port,
-                    },
-                )?;
-
-                let launch = minecraft_launch::resolve_launch_spec(&dir, mc.memory_mb).map_err(|e| {
-                    crate::error_payload::anyhow(
-                        "install_failed",
-                        format!("failed to detect launch command: {e}"),
-                        None,
-                        Some(
-                            "Expected server.jar (fabric/vanilla) or libraries/**/unix_args.txt (forge)."
-                                .to_string(),
-                        ),
-                    )
-                })?;
-
-                let exec = launch.exec.clone();
-                let raw_args = launch.args.clone();
-
-                let (mut cmd, sandbox_launch) = prepare_instance_command(
-                    &id.0,
-                    &t.template_id,
-                    &params,
-                    &dir,
-                    &dir,
-                    &exec,
-                    &raw_args,
-                    &[],
-                )?;
-
-                let started_at_unix_ms = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64;
-                let mut run = RunInfo {
-                    process_id: id.0.clone(),
-                    template_id: t.template_id.clone(),
-                    started_at_unix_ms,
-                    agent_version: env!("CARGO_PKG_VERSION").to_string(),
-                    pid: None,
-                    pgid: None,
-                    container_name: sandbox_launch.container_name().map(ToOwned::to_owned),
-                    container_id: None,
-                    exec: sandbox_launch.exec.clone(),
-                    args: sandbox_launch.args.clone(),
-                    cwd: sandbox_launch.cwd.display().to_string(),
-                    params: redact_params(params.clone()),
-                    env: collect_safe_env(),
-                };
-                let _ = write_run_json(&dir, &run).await;
-
-                sink.emit(format!("[alloy-agent] sandbox: {}", sandbox_launch.summary()))
-                    .await;
-                for warning in sandbox_launch.warnings() {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warning}"))
-                        .await;
-                }
-
-                sink.emit(format!(
-                    "[alloy-agent] minecraft(curseforge) exec: {} {} (cwd {}) port={} launch={} cf_mod_id={} cf_file_id={} cf_server_pack_file_id={}",
-                    sandbox_launch.exec,
-                    sandbox_launch.args.join(" "),
-                    sandbox_launch.cwd.display(),
-                    mc.port,
-                    launch.kind,
-                    installed.mod_id,
-                    installed.file_id,
-                    installed.server_pack_file_id,
-                ))
-                .await;
-
-                set_entry_message(
-                    &self.inner,
-                    &id.0,
-                    Some(format!("spawning minecraft server (port {})...", mc.port)),
-                )
-                .await;
-
-                let mut child = cmd
-                    .spawn()
-                    .with_context(|| format!("spawn minecraft server (cwd {})", dir.display()))
-                    .map_err(|e| {
-                        crate::error_payload::anyhow(
-                            "spawn_failed",
-                            e.to_string(),
-                            None,
-                            Some(
-                                "Ensure Java is installed and the instance directory is writable."
-                                    .to_string(),
-                            ),
-                        )
-                    })?;
-                let started = tokio::time::Instant::now();
-                let pid_u32 = child.id();
-                let pgid = pid_u32.map(|p| p as i32);
-
-                if let Some(pid) = pid_u32
-                    && let Some(warn) = sandbox_launch.attach_pid(pid)
-                {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warn}"))
-                        .await;
-                }
-
-                run.pid = pid_u32;
-                run.pgid = pgid;
-                refresh_docker_container_metadata(&id.0, &mut run).await;
-                let _ = write_run_json(&dir, &run).await;
-
-                let stdin = child.stdin.take();
-                let stdout = child.stdout.take();
-                let stderr = child.stderr.take();
-
-                if let Some(out) = stdout {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(out).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stdout] {line}")).await;
-                        }
-                    });
-                }
-                if let Some(err) = stderr {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(err).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stderr] {line}")).await;
-                        }
-                    });
-                }
-
-                {
-                    let mut inner = self.inner.lock().await;
-                    inner.insert(
-                        id.0.clone(),
-                        ProcessEntry {
-                            template_id: ProcessTemplateId(t.template_id.clone()),
-                            state: ProcessState::Starting,
-                            pid: pid_u32,
-                            resources: None,
-                            exit_code: None,
-                            message: Some(format!("waiting for port {}...", mc.port)),
-                            restart,
-                            restart_attempts: reused_restart_attempts,
-                            stdin,
-                            graceful_stdin: t.graceful_stdin.clone(),
-                            pgid,
-                            logs: logs.clone(),
-                            log_file_tx: Some(log_tx.clone()),
-                        },
-                    );
-                }
+                        Some(
+                            "Pick another port, or leave it blank (0) to auto-assign a free port."
+                                .to_string(),
+                        ),
+                    )
+                })?;
+                let mc = minecraft_curseforge::CurseforgeParams { port: mc_port, ..mc };
+                params.insert("port".to_string(), mc_port.to_string());
+                let restart = parse_restart_config(&params);
 
-                if let Some(pid) = pid_u32 {
-                    self.spawn_resource_sampler(id.0.clone(), pid);
-                }
+                let dir = minecraft::instance_dir(&id.0);
 
-                let manager = self.clone();
-                let inner = self.inner.clone();
-                let id_str = id.0.clone();
-
-                let probe_sink = sink.clone();
-                let port = mc.port;
-                let frp_config = params
-                    .get("frp_config")
-                    .map(|v| v.trim())
-                    .filter(|v| !v.is_empty())
-                    .map(|v| v.to_string());
-                let frp_instance_dir = dir.clone();
-                tokio::spawn({
-                    let inner = inner.clone();
-                    let id_str = id_str.clone();
-                    let frp_config = frp_config.clone();
-                    let frp_instance_dir = frp_instance_dir.clone();
-                    async move {
-                        let timeout = port_probe_timeout();
-                        let ok = wait_for_local_tcp_port(port, timeout).await;
-
-                        let (pgid, should_kill) = {
-                            let mut map = inner.lock().await;
-                            let Some(e) = map.get_mut(&id_str) else {
-                                return;
-                            };
-                            if e.pid != pid_u32 || !matches!(e.state, ProcessState::Starting) {
-                                return;
-                            }
+                self.set_start_phase(&id.0, StartPhase::Installing, "installing curseforge modpack").await;
 
-                            if ok {
-                                e.state = ProcessState::Running;
-                                e.message = None;
-                                (e.pgid, false)
-                            } else {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!(
-                                    "port {} did not open within {}ms",
-                                    port,
-                                    timeout.as_millis()
-                                ));
-                                (e.pgid, true)
-                            }
-                        };
+                set_entry_message(
+                    &self.inner,
+                    &id.0,
+                    Some("resolving curseforge modpack...".to_string()),
+                )
+                .await;
+                sink.emit("[alloy-agent] resolving curseforge modpack".to_string())
+                    .await;
 
-                        if ok {
-                            if let (Some(cfg), Some(pgid)) = (frp_config.clone(), pgid) {
-                                if let Err(e) = start_frpc_sidecar(
-                                    probe_sink.clone(),
-                                    frp_instance_dir.clone(),
-                                    pgid,
-                                    port,
-                                    cfg,
-                                )
-                                .await
-                                {
-                                    probe_sink
-                                        .emit(format!("[alloy-agent] frpc start failed: {e}"))
-                                        .await;
-                                }
-                            }
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] minecraft port {} is accepting connections",
-                                    port
-                                ))
-                                .await;
-                        } else {
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] minecraft port {} did not open in time",
-                                    port
-                                ))
-                                .await;
-                            if should_kill && let Some(pgid) = pgid {
-                                #[cfg(unix)]
-                                unsafe {
-                                    libc::kill(-pgid, libc::SIGTERM);
-                                }
-                            }
-                        }
+                let installed = minecraft_curseforge::ensure_installed(
+                    &dir,
+                    &mc.source,
+                    &mc.api_key,
+                    download_reporter(&self.inner, &self.events, &id.0, &sink, "downloading curseforge modpack"),
+                    install_output_sender(&sink),
+                )
+                .await
+                .map_err(|e| {
+                    if let Some(err) = crate::archive::as_install_failed(&e) {
+                        return err;
                     }
-                });
-
-                let process_pgid = pgid;
-                let wait_sink = sink.clone();
-                let template_id = t.template_id.clone();
-                let params_for_restart = params.clone();
-                tokio::spawn(async move {
-                    let res = child.wait().await;
-                    #[cfg(unix)]
-                    if let Some(pgid) = process_pgid {
-                        unsafe {
-                            libc::kill(-pgid, libc::SIGTERM);
-                        }
-                        tokio::time::sleep(Duration::from_millis(500)).await;
-                        let alive = unsafe { libc::kill(-pgid, 0) == 0 };
-                        if alive {
-                            unsafe {
-                                libc::kill(-pgid, libc::SIGKILL);
-                            }
-                        }
+                    // An unknown slug is the user's input, not a download problem.
+                    if crate::error_payload::decode(&e).is_some_and(|p| p.code == "invalid_param") {
+                        return e;
                     }
-                    let runtime = tokio::time::Instant::now().duration_since(started);
-
-                    let mut restart_after: Option<Duration> = None;
-                    let mut restart_attempt: u32 = 0;
-
-                    let (final_state, exit_code) = {
-                        let mut map = inner.lock().await;
-                        let Some(e) = map.get_mut(&id_str) else {
-                            return;
-                        };
-
-                        e.stdin = None;
-                        let stopping = matches!(e.state, ProcessState::Stopping);
-
-                        match res {
-                            Ok(status) => {
-                                e.exit_code = status.code();
-
-                                if stopping {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("stopped".to_string());
-                                } else if runtime < early_exit_threshold() {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited too quickly ({}ms)",
-                                        runtime.as_millis()
-                                    ));
-                                } else if status.success() {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("exited".to_string());
-                                } else {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited with code {}",
-                                        status.code().unwrap_or_default()
-                                    ));
-                                }
-                            }
-                            Err(err) => {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!("wait failed: {err}"));
-                            }
-                        }
-
-                        if !stopping {
-                            let is_failure = matches!(e.state, ProcessState::Failed)
-                                || e.exit_code.is_some_and(|c| c != 0);
-                            let should_restart = match e.restart.policy {
-                                RestartPolicy::Off => false,
-                                RestartPolicy::Always => true,
-                                RestartPolicy::OnFailure => is_failure,
-                            };
-
-                            if should_restart && e.restart_attempts < e.restart.max_retries {
-                                e.restart_attempts = e.restart_attempts.saturating_add(1);
-                                let delay_ms = compute_backoff_ms(e.restart, e.restart_attempts);
-                                restart_after = Some(Duration::from_millis(delay_ms));
-                                restart_attempt = e.restart_attempts;
-                                e.message = Some(format!(
-                                    "restarting in {}ms (attempt {}/{})",
-                                    delay_ms, restart_attempt, e.restart.max_retries
-                                ));
-                            }
-                        }
+                    crate::error_payload::anyhow(
+                        "download_failed",
+                        format!("failed to install curseforge pack: {e}"),
+                        None,
+                        Some("Check the CurseForge API key and network connectivity.".to_string()),
+                    )
+                })?;
 
-                        (e.state, e.exit_code)
-                    };
+                minecraft::ensure_vanilla_instance_layout(
+                    &dir,
+                    &minecraft::VanillaParams {
+                        accept_eula: mc.accept_eula,
+                        version: "latest_release".to_string(),
+                        memory_mb: mc.memory_mb,
+                        port: mc.port,
+                    },
+                )?;
 
-                    wait_sink
-                        .emit(format!(
-                            "[alloy-agent] process exited: state={:?} exit_code={:?} runtime_ms={}",
-                            final_state,
-                            exit_code,
-                            runtime.as_millis()
-                        ))
-                        .await;
+                let java = resolve_java_path(&params);
+                let launch = minecraft_launch::resolve_launch_spec(&dir, &jvm_args, &java).map_err(|e| {
+                    crate::error_payload::anyhow(
+                        "install_failed",
+                        format!("failed to detect launch command: {e}"),
+                        None,
+                        Some(
+                            "Expected server.jar (fabric/vanilla) or libraries/**/unix_args.txt (forge)."
+                                .to_string(),
+                        ),
+                    )
+                })?;
 
-                    if let Some(delay) = restart_after {
-                        wait_sink
-                            .emit(format!(
-                                "[alloy-agent] auto-restart scheduled in {}ms (attempt {})",
-                                delay.as_millis(),
-                                restart_attempt
-                            ))
-                            .await;
-                        let handle = tokio::runtime::Handle::current();
-                        let wait_sink = wait_sink.clone();
-                        tokio::task::spawn_blocking(move || {
-                            std::thread::sleep(delay);
-                            let res = handle.block_on(manager.start_from_template_with_process_id(
-                                &id_str,
-                                &template_id,
-                                params_for_restart,
-                            ));
-                            match res {
-                                Ok(st) if matches!(st.state, ProcessState::Failed) => {
-                                    let msg = st
-                                        .message
-                                        .filter(|s| !s.trim().is_empty())
-                                        .unwrap_or_else(|| "unknown error".to_string());
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {msg}"
-                                    )));
-                                }
-                                Ok(_) => {
-                                    handle.block_on(wait_sink.emit(
-                                        "[alloy-agent] auto-restart triggered".to_string(),
-                                    ));
-                                }
-                                Err(err) => {
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {err}"
-                                    )));
-                                }
-                            }
-                        });
-                    }
-                });
+                let exec = launch.exec.clone();
+                let raw_args = launch.args.clone();
 
-                return Ok(ProcessStatus {
-                    id: id.clone(),
-                    template_id: ProcessTemplateId(t.template_id.clone()),
-                    state: ProcessState::Starting,
-                    pid: pid_u32,
-                    exit_code: None,
-                    message: Some(format!("waiting for port {}...", mc.port)),
-                    resources: None,
-                });
+                return self
+                    .spawn_supervised(LaunchContext {
+                        process_id: id.0.clone(),
+                        template_id: t.template_id.clone(),
+                        kind: "minecraft",
+                        exec_detail: format!(
+                            "port={} launch={} cf_mod_id={} cf_file_id={} cf_server_pack_file_id={}",
+                            mc.port,
+                            launch.kind,
+                            installed.mod_id,
+                            installed.file_id,
+                            installed.server_pack_file_id
+                        ),
+                        exec,
+                        args: raw_args,
+                        cwd: dir.clone(),
+                        instance_dir: dir.clone(),
+                        extra_rw_paths: Vec::new(),
+                        env: Vec::new(),
+                        spawn_hint: JAVA_SPAWN_HINT,
+                        port_probe: Some(PortProbe::minecraft(mc.port)),
+                        frp_config: frp_config_param(&params),
+                        restart,
+                        restart_attempts: reused_restart_attempts,
+                        graceful_stop: t.graceful_stop.clone(),
+                        sink: sink.clone(),
+                        params: params.clone(),
+                        companions: Vec::new(),
+                    })
+                    .await;
             }
 
             if t.template_id == "dst:vanilla" {
@@ -3385,7 +5748,7 @@ impl ProcessManager {
 
                 let tr = dst::validate_vanilla_params(&params)?;
 
-                let game_port = port_alloc::allocate_udp_port(tr.port).map_err(|e| {
+                let game_port = port_alloc::reserve_udp_port(&id.0, tr.port).map_err(|e| {
                     let mut fields = BTreeMap::new();
                     fields.insert("port".to_string(), e.to_string());
                     crate::error_payload::anyhow(
@@ -3395,7 +5758,7 @@ impl ProcessManager {
                         Some("Pick another port (or use 0 to auto-assign).".to_string()),
                     )
                 })?;
-                let master_port = port_alloc::allocate_udp_port(tr.master_port).map_err(|e| {
+                let master_port = port_alloc::reserve_udp_port(&id.0, tr.master_port).map_err(|e| {
                     let mut fields = BTreeMap::new();
                     fields.insert("master_port".to_string(), e.to_string());
                     crate::error_payload::anyhow(
@@ -3405,7 +5768,7 @@ impl ProcessManager {
                         Some("Pick another port (or use 0 to auto-assign).".to_string()),
                     )
                 })?;
-                let auth_port = port_alloc::allocate_udp_port(tr.auth_port).map_err(|e| {
+                let auth_port = port_alloc::reserve_udp_port(&id.0, tr.auth_port).map_err(|e| {
                     let mut fields = BTreeMap::new();
                     fields.insert("auth_port".to_string(), e.to_string());
                     crate::error_payload::anyhow(
@@ -3426,20 +5789,74 @@ impl ProcessManager {
                     ));
                 }
 
+                let caves = match tr.caves.clone() {
+                    None => None,
+                    Some(c) => {
+                        let allocate = |port: u16, field: &str| {
+                            port_alloc::reserve_udp_port(&id.0, port).map_err(|e| {
+                                crate::error_payload::anyhow(
+                                    "invalid_param",
+                                    format!("invalid {field}"),
+                                    Some(BTreeMap::from([(field.to_string(), e.to_string())])),
+                                    Some("Pick another port (or use 0 to auto-assign).".to_string()),
+                                )
+                            })
+                        };
+                        let caves = dst::CavesParams {
+                            port: allocate(c.port, "caves_port")?,
+                            master_port: allocate(c.master_port, "caves_master_port")?,
+                            auth_port: allocate(c.auth_port, "caves_auth_port")?,
+                            shard_port: allocate(0, "enable_caves")?,
+                            cluster_key: ProcessId::new().0,
+                        };
+                        let mut ports = vec![
+                            game_port,
+                            master_port,
+                            auth_port,
+                            caves.port,
+                            caves.master_port,
+                            caves.auth_port,
+                            caves.shard_port,
+                        ];
+                        ports.sort_unstable();
+                        ports.dedup();
+                        if ports.len() < 7 {
+                            return Err(crate::error_payload::anyhow(
+                                "invalid_param",
+                                "ports must be distinct",
+                                None,
+                                Some(
+                                    "Give the Caves shard its own ports, or set them to 0 (auto)."
+                                        .to_string(),
+                                ),
+                            ));
+                        }
+                        Some(caves)
+                    }
+                };
+
                 let tr = dst::VanillaParams {
                     port: game_port,
                     master_port,
                     auth_port,
+                    caves,
                     ..tr
                 };
                 params.insert("port".to_string(), game_port.to_string());
                 params.insert("master_port".to_string(), master_port.to_string());
                 params.insert("auth_port".to_string(), auth_port.to_string());
+                if let Some(caves) = &tr.caves {
+                    params.insert("caves_port".to_string(), caves.port.to_string());
+                    params.insert("caves_master_port".to_string(), caves.master_port.to_string());
+                    params.insert("caves_auth_port".to_string(), caves.auth_port.to_string());
+                }
                 let restart = parse_restart_config(&params);
 
                 let dir = dst::instance_dir(&id.0);
                 dst::ensure_vanilla_instance_layout(&dir, &tr)?;
 
+                self.set_start_phase(&id.0, StartPhase::Installing, "installing dst server files").await;
+
                 set_entry_message(
                     &self.inner,
                     &id.0,
@@ -3449,7 +5866,16 @@ impl ProcessManager {
                 sink.emit("[alloy-agent] installing dst server files".to_string())
                     .await;
 
-                let server = dst_download::ensure_dst_server().await.map_err(|e| {
+                let auth = SteamcmdAuth::from_params(&params)?;
+                let server = dst_download::ensure_dst_server_with_output(
+                    install_output_sender(&sink),
+                    &auth,
+                )
+                .await
+                .map_err(|e| {
+                    if crate::error_payload::decode(&e).is_some() {
+                        return e;
+                    }
                     crate::error_payload::anyhow(
                         "download_failed",
                         format!("failed to install dst server: {e}"),
@@ -3461,325 +5887,98 @@ impl ProcessManager {
                     )
                 })?;
 
+                let ugc_dir = if tr.mods.is_empty() {
+                    None
+                } else {
+                    sink.emit(format!(
+                        "[alloy-agent] installing {} workshop mod(s)",
+                        tr.mods.len()
+                    ))
+                    .await;
+                    let ugc_dir = dst_download::ensure_workshop_mods(
+                        &dst::ugc_install_dir(&dir),
+                        &tr.mods,
+                        &auth,
+                        install_output_sender(&sink),
+                    )
+                    .await?;
+                    Some(ugc_dir)
+                };
+
                 let persistent_root = dir.join("klei");
 
                 let exec = server.bin.display().to_string();
-                let raw_args = vec![
-                    "-console".to_string(),
-                    "-cluster".to_string(),
-                    "Cluster_1".to_string(),
-                    "-shard".to_string(),
-                    "Master".to_string(),
-                    "-persistent_storage_root".to_string(),
-                    persistent_root.display().to_string(),
-                    "-conf_dir".to_string(),
-                    "DoNotStarveTogether".to_string(),
-                ];
+                let shard_args = |shard: &str| {
+                    let mut args = vec![
+                        "-console".to_string(),
+                        "-cluster".to_string(),
+                        "Cluster_1".to_string(),
+                        "-shard".to_string(),
+                        shard.to_string(),
+                        "-persistent_storage_root".to_string(),
+                        persistent_root.display().to_string(),
+                        "-conf_dir".to_string(),
+                        "DoNotStarveTogether".to_string(),
+                    ];
+                    if let Some(ugc_dir) = &ugc_dir {
+                        args.push("-ugc_directory".to_string());
+                        args.push(ugc_dir.display().to_string());
+                    }
+                    args
+                };
+                let raw_args = shard_args("Master");
+                let companions = match &tr.caves {
+                    None => Vec::new(),
+                    Some(_) => vec![Companion {
+                        name: "caves",
+                        args: shard_args("Caves"),
+                        stdout_prefix: "[caves stdout]",
+                        stderr_prefix: "[caves stderr]",
+                    }],
+                };
                 let spawn_cwd = server
                     .bin
                     .parent()
                     .map(std::path::Path::to_path_buf)
                     .unwrap_or_else(|| server.server_root.clone());
 
-                let (mut cmd, sandbox_launch) = prepare_instance_command(
-                    &id.0,
-                    &t.template_id,
-                    &params,
-                    &dir,
-                    &spawn_cwd,
-                    &exec,
-                    &raw_args,
-                    &[server.server_root.clone()],
-                )?;
-
-                let started_at_unix_ms = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64;
-                let mut run = RunInfo {
-                    process_id: id.0.clone(),
-                    template_id: t.template_id.clone(),
-                    started_at_unix_ms,
-                    agent_version: env!("CARGO_PKG_VERSION").to_string(),
-                    pid: None,
-                    pgid: None,
-                    container_name: sandbox_launch.container_name().map(ToOwned::to_owned),
-                    container_id: None,
-                    exec: sandbox_launch.exec.clone(),
-                    args: sandbox_launch.args.clone(),
-                    cwd: sandbox_launch.cwd.display().to_string(),
-                    params: redact_params(params.clone()),
-                    env: collect_safe_env(),
-                };
-                let _ = write_run_json(&dir, &run).await;
-
-                sink.emit(format!("[alloy-agent] sandbox: {}", sandbox_launch.summary()))
-                    .await;
-                for warning in sandbox_launch.warnings() {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warning}"))
-                        .await;
-                }
-
-                sink.emit(format!(
-                    "[alloy-agent] dst exec: {} {} (cwd {}) ports=udp:{} master={} auth={}",
-                    sandbox_launch.exec,
-                    sandbox_launch.args.join(" "),
-                    sandbox_launch.cwd.display(),
-                    tr.port,
-                    tr.master_port,
-                    tr.auth_port,
-                ))
-                .await;
-
-                set_entry_message(
-                    &self.inner,
-                    &id.0,
-                    Some(format!("spawning dst server (udp {})...", tr.port)),
-                )
-                .await;
-
-                let mut child = cmd
-                    .spawn()
-                    .with_context(|| format!("spawn dst server (cwd {})", server.server_root.display()))
-                    .map_err(|e| {
-                        crate::error_payload::anyhow(
-                            "spawn_failed",
-                            e.to_string(),
-                            None,
-                            Some("Ensure the agent image includes required libraries for DST.".to_string()),
-                        )
-                    })?;
-                let started = tokio::time::Instant::now();
-                let pid_u32 = child.id();
-                let pgid = pid_u32.map(|p| p as i32);
-
-                if let Some(pid) = pid_u32
-                    && let Some(warn) = sandbox_launch.attach_pid(pid)
-                {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warn}"))
-                        .await;
-                }
-
-                run.pid = pid_u32;
-                run.pgid = pgid;
-                refresh_docker_container_metadata(&id.0, &mut run).await;
-                let _ = write_run_json(&dir, &run).await;
-
-                let stdin = child.stdin.take();
-                let stdout = child.stdout.take();
-                let stderr = child.stderr.take();
-
-                if let Some(out) = stdout {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(out).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stdout] {line}")).await;
-                        }
-                    });
-                }
-                if let Some(err) = stderr {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(err).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stderr] {line}")).await;
-                        }
-                    });
-                }
-
-                {
-                    let mut inner = self.inner.lock().await;
-                    inner.insert(
-                        id.0.clone(),
-                        ProcessEntry {
-                            template_id: ProcessTemplateId(t.template_id.clone()),
-                            state: ProcessState::Starting,
-                            pid: pid_u32,
-                            resources: None,
-                            exit_code: None,
-                            message: Some("starting...".to_string()),
-                            restart,
-                            restart_attempts: reused_restart_attempts,
-                            stdin,
-                            graceful_stdin: t.graceful_stdin.clone(),
-                            pgid,
-                            logs: logs.clone(),
-                            log_file_tx: Some(log_tx.clone()),
+                return self
+                    .spawn_supervised(LaunchContext {
+                        process_id: id.0.clone(),
+                        template_id: t.template_id.clone(),
+                        kind: "dst",
+                        exec_detail: match &tr.caves {
+                            None => format!(
+                                "ports=udp:{} master={} auth={}",
+                                tr.port, tr.master_port, tr.auth_port
+                            ),
+                            Some(caves) => format!(
+                                "ports=udp:{} master={} auth={} caves=udp:{}",
+                                tr.port, tr.master_port, tr.auth_port, caves.port
+                            ),
                         },
-                    );
-                }
-
-                if let Some(pid) = pid_u32 {
-                    self.spawn_resource_sampler(id.0.clone(), pid);
-                }
-
-                // Best-effort: mark Running after a short delay if the process is still alive.
-                let inner = self.inner.clone();
-                let id_str = id.0.clone();
-                tokio::spawn({
-                    let inner = inner.clone();
-                    async move {
-                        tokio::time::sleep(Duration::from_millis(1500)).await;
-                        let mut map = inner.lock().await;
-                        let Some(e) = map.get_mut(&id_str) else { return };
-                        if e.pid == pid_u32 && matches!(e.state, ProcessState::Starting) {
-                            e.state = ProcessState::Running;
-                            e.message = None;
-                        }
-                    }
-                });
-
-                let manager = self.clone();
-                let inner = self.inner.clone();
-                let id_str = id.0.clone();
-                let process_pgid = pgid;
-                let wait_sink = sink.clone();
-                let template_id = t.template_id.clone();
-                let params_for_restart = params.clone();
-                tokio::spawn(async move {
-                    let res = child.wait().await;
-                    #[cfg(unix)]
-                    if let Some(pgid) = process_pgid {
-                        unsafe {
-                            libc::kill(-pgid, libc::SIGTERM);
-                        }
-                        tokio::time::sleep(Duration::from_millis(500)).await;
-                        let alive = unsafe { libc::kill(-pgid, 0) == 0 };
-                        if alive {
-                            unsafe {
-                                libc::kill(-pgid, libc::SIGKILL);
-                            }
-                        }
-                    }
-                    let runtime = tokio::time::Instant::now().duration_since(started);
-
-                    let mut restart_after: Option<Duration> = None;
-                    let mut restart_attempt: u32 = 0;
-
-                    let (final_state, exit_code) = {
-                        let mut map = inner.lock().await;
-                        let Some(e) = map.get_mut(&id_str) else {
-                            return;
-                        };
-
-                        e.stdin = None;
-                        let stopping = matches!(e.state, ProcessState::Stopping);
-
-                        match res {
-                            Ok(status) => {
-                                e.exit_code = status.code();
-
-                                if stopping {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("stopped".to_string());
-                                } else if runtime < early_exit_threshold() {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited too quickly ({}ms)",
-                                        runtime.as_millis()
-                                    ));
-                                } else if status.success() {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("exited".to_string());
-                                } else {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited with code {}",
-                                        status.code().unwrap_or_default()
-                                    ));
-                                }
-                            }
-                            Err(err) => {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!("wait failed: {err}"));
-                            }
-                        }
-
-                        if !stopping {
-                            let is_failure = matches!(e.state, ProcessState::Failed)
-                                || e.exit_code.is_some_and(|c| c != 0);
-                            let should_restart = match e.restart.policy {
-                                RestartPolicy::Off => false,
-                                RestartPolicy::Always => true,
-                                RestartPolicy::OnFailure => is_failure,
-                            };
-
-                            if should_restart && e.restart_attempts < e.restart.max_retries {
-                                e.restart_attempts = e.restart_attempts.saturating_add(1);
-                                let delay_ms = compute_backoff_ms(e.restart, e.restart_attempts);
-                                restart_after = Some(Duration::from_millis(delay_ms));
-                                restart_attempt = e.restart_attempts;
-                                e.message = Some(format!(
-                                    "restarting in {}ms (attempt {}/{})",
-                                    delay_ms, restart_attempt, e.restart.max_retries
-                                ));
-                            }
-                        }
-
-                        (e.state, e.exit_code)
-                    };
-
-                    wait_sink
-                        .emit(format!(
-                            "[alloy-agent] process exited: state={:?} exit_code={:?} runtime_ms={}",
-                            final_state,
-                            exit_code,
-                            runtime.as_millis()
-                        ))
-                        .await;
-
-                    if let Some(delay) = restart_after {
-                        wait_sink
-                            .emit(format!(
-                                "[alloy-agent] auto-restart scheduled in {}ms (attempt {})",
-                                delay.as_millis(),
-                                restart_attempt
-                            ))
-                            .await;
-                        let handle = tokio::runtime::Handle::current();
-                        let wait_sink = wait_sink.clone();
-                        tokio::task::spawn_blocking(move || {
-                            std::thread::sleep(delay);
-                            let res = handle.block_on(manager.start_from_template_with_process_id(
-                                &id_str,
-                                &template_id,
-                                params_for_restart,
-                            ));
-                            match res {
-                                Ok(st) if matches!(st.state, ProcessState::Failed) => {
-                                    let msg = st
-                                        .message
-                                        .filter(|s| !s.trim().is_empty())
-                                        .unwrap_or_else(|| "unknown error".to_string());
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {msg}"
-                                    )));
-                                }
-                                Ok(_) => {
-                                    handle.block_on(wait_sink.emit(
-                                        "[alloy-agent] auto-restart triggered".to_string(),
-                                    ));
-                                }
-                                Err(err) => {
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {err}"
-                                    )));
-                                }
-                            }
-                        });
-                    }
-                });
-
-                return Ok(ProcessStatus {
-                    id: id.clone(),
-                    template_id: ProcessTemplateId(t.template_id.clone()),
-                    state: ProcessState::Starting,
-                    pid: pid_u32,
-                    exit_code: None,
-                    message: Some("starting...".to_string()),
-                    resources: None,
-                });
+                        exec,
+                        args: raw_args,
+                        cwd: spawn_cwd,
+                        instance_dir: dir.clone(),
+                        extra_rw_paths: vec![server.server_root.clone()],
+                        env: Vec::new(),
+                        spawn_hint: DST_SPAWN_HINT,
+                        port_probe: LogReadyMatcher::new(&t.ready_log_markers, log_ready_timeout())
+                            .map(PortProbe::Log)
+                            .or(Some(PortProbe::Udp {
+                                port: tr.port,
+                                timeout: port_probe_timeout(),
+                            })),
+                        frp_config: None,
+                        restart,
+                        restart_attempts: reused_restart_attempts,
+                        graceful_stop: t.graceful_stop.clone(),
+                        sink: sink.clone(),
+                        params: params.clone(),
+                        companions,
+                    })
+                    .await;
             }
 
             if t.template_id == "terraria:vanilla" {
@@ -3794,7 +5993,7 @@ impl ProcessManager {
 
                 let tr = terraria::validate_vanilla_params(&params)?;
 
-                let tr_port = port_alloc::allocate_tcp_port(tr.port).map_err(|e| {
+                let tr_port = port_alloc::reserve_tcp_port(&id.0, tr.port).map_err(|e| {
                     let mut fields = BTreeMap::new();
                     fields.insert("port".to_string(), e.to_string());
                     crate::error_payload::anyhow(
@@ -3840,6 +6039,7 @@ impl ProcessManager {
                         Some("Check network connectivity, then try again.".to_string()),
                     )
                 })?;
+                self.set_start_phase(&id.0, StartPhase::Downloading, "downloading terraria server zip").await;
                 set_entry_message(
                     &self.inner,
                     &id.0,
@@ -3848,8 +6048,17 @@ impl ProcessManager {
                 .await;
                 sink.emit("[alloy-agent] downloading terraria server zip".to_string())
                     .await;
-                let zip_path = terraria_download::ensure_server_zip(&resolved)
-                    .await
+                let zip_path = terraria_download::ensure_server_zip_with_progress(
+                    &resolved,
+                    Some(download_reporter(
+                        &self.inner,
+                        &self.events,
+                        &id.0,
+                        &sink,
+                        "downloading terraria server zip",
+                    )),
+                )
+                .await
                     .map_err(|e| {
                         crate::error_payload::anyhow(
                             "download_failed",
@@ -3858,6 +6067,7 @@ impl ProcessManager {
                             Some("Try again; if it persists, clear cache and retry.".to_string()),
                         )
                     })?;
+                self.set_start_phase(&id.0, StartPhase::Installing, "extracting terraria server files").await;
                 set_entry_message(
                     &self.inner,
                     &id.0,
@@ -3869,6 +6079,7 @@ impl ProcessManager {
                 let extracted = terraria_download::extract_linux_x64_to_cache(
                     &zip_path,
                     &resolved.version_id,
+                    install_output_sender(&sink),
                 )
                 .map_err(|e| {
                     crate::error_payload::anyhow(
@@ -3904,675 +6115,321 @@ impl ProcessManager {
                 );
                 let exec = exec_path.display().to_string();
                 let raw_args = vec!["-config".to_string(), config_path.display().to_string()];
-                let (mut cmd, sandbox_launch) = prepare_instance_command(
-                    &id.0,
-                    &t.template_id,
-                    &params,
-                    &dir,
-                    &extracted.server_root,
-                    &exec,
-                    &raw_args,
-                    &[extracted.server_root.clone()],
-                )?;
-                cmd.env("TERM", "xterm")
-                    .env("LD_LIBRARY_PATH", &ld_library_path);
+                // Creating a world on first start can take minutes before the port opens.
+                let probe_timeout = if creating_world {
+                    Duration::from_millis(
+                        env_u64("ALLOY_TERRARIA_AUTOCREATE_PORT_PROBE_TIMEOUT_MS")
+                            .map(|v| v.clamp(1000, 10 * 60 * 1000))
+                            .unwrap_or(10 * 60 * 1000),
+                    )
+                } else {
+                    port_probe_timeout()
+                };
+                return self
+                    .spawn_supervised(LaunchContext {
+                        process_id: id.0.clone(),
+                        template_id: t.template_id.clone(),
+                        kind: "terraria",
+                        exec_detail: format!("port={} version={}", tr.port, resolved.version_id),
+                        exec,
+                        args: raw_args,
+                        cwd: extracted.server_root.clone(),
+                        instance_dir: dir.clone(),
+                        extra_rw_paths: vec![extracted.server_root.clone()],
+                        env: vec![
+                            ("TERM".to_string(), "xterm".to_string()),
+                            ("LD_LIBRARY_PATH".to_string(), ld_library_path),
+                        ],
+                        spawn_hint: TERRARIA_SPAWN_HINT,
+                        port_probe: Some(PortProbe::Tcp {
+                            port: tr.port,
+                            timeout: probe_timeout,
+                        }),
+                        frp_config: frp_config_param(&params),
+                        restart,
+                        restart_attempts: reused_restart_attempts,
+                        graceful_stop: t.graceful_stop.clone(),
+                        sink: sink.clone(),
+                        params: params.clone(),
+                        companions: Vec::new(),
+                    })
+                    .await;
+            }
+
+            if t.template_id == "terraria:tmodloader" {
+                ensure_min_free_space(&terraria::data_root()).map_err(|e| {
+                    crate::error_payload::anyhow(
+                        "insufficient_disk",
+                        e.to_string(),
+                        None,
+                        Some("Free up disk space under ALLOY_DATA_ROOT and try again.".to_string()),
+                    )
+                })?;
 
-                let started_at_unix_ms = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_millis() as u64;
-                let mut env = collect_safe_env();
-                env.insert("TERM".to_string(), "xterm".to_string());
-                env.insert("LD_LIBRARY_PATH".to_string(), ld_library_path.clone());
-
-                let mut run = RunInfo {
-                    process_id: id.0.clone(),
-                    template_id: t.template_id.clone(),
-                    started_at_unix_ms,
-                    agent_version: env!("CARGO_PKG_VERSION").to_string(),
-                    pid: None,
-                    pgid: None,
-                    container_name: sandbox_launch.container_name().map(ToOwned::to_owned),
-                    container_id: None,
-                    exec: sandbox_launch.exec.clone(),
-                    args: sandbox_launch.args.clone(),
-                    cwd: sandbox_launch.cwd.display().to_string(),
-                    params: redact_params(params.clone()),
-                    env,
+                let tm = terraria_tmodloader::validate_params(&params)?;
+                let tr_port = port_alloc::reserve_tcp_port(&id.0, tm.server.port).map_err(|e| {
+                    let mut fields = BTreeMap::new();
+                    fields.insert("port".to_string(), e.to_string());
+                    crate::error_payload::anyhow(
+                        "invalid_param",
+                        "invalid port",
+                        Some(fields),
+                        Some(
+                            "Pick another port, or leave it blank (0) to auto-assign a free port."
+                                .to_string(),
+                        ),
+                    )
+                })?;
+                let tr = terraria::VanillaParams {
+                    port: tr_port,
+                    ..tm.server
                 };
-                let _ = write_run_json(&dir, &run).await;
-
-                sink.emit(format!("[alloy-agent] sandbox: {}", sandbox_launch.summary()))
-                    .await;
-                for warning in sandbox_launch.warnings() {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warning}"))
-                        .await;
-                }
+                params.insert("port".to_string(), tr_port.to_string());
+                let restart = parse_restart_config(&params);
 
-                sink.emit(format!(
-                    "[alloy-agent] terraria exec: {} {} (cwd {}) port={} version={}",
-                    sandbox_launch.exec,
-                    sandbox_launch.args.join(" "),
-                    sandbox_launch.cwd.display(),
-                    tr.port,
-                    resolved.version_id
-                ))
-                .await;
+                let dir = terraria::instance_dir(&id.0);
+                terraria::ensure_vanilla_instance_layout(&dir, &tr)?;
+                let creating_world = !dir
+                    .join("worlds")
+                    .join(format!("{}.wld", tr.world_name))
+                    .exists();
+                let config_path = std::fs::canonicalize(dir.join("config").join("serverconfig.txt"))
+                    .unwrap_or_else(|_| dir.join("config").join("serverconfig.txt"));
 
                 set_entry_message(
                     &self.inner,
                     &id.0,
-                    Some(format!("spawning terraria server (port {})...", tr.port)),
+                    Some("resolving tModLoader release...".to_string()),
                 )
                 .await;
-
-                let mut child = cmd
-                    .spawn()
-                    .with_context(|| {
-                        format!(
-                            "spawn terraria server: exec={} (cwd {})",
-                            exec_path.display(),
-                            extracted.server_root.display()
-                        )
-                    })
+                sink.emit(format!("[alloy-agent] resolving tModLoader release {}", tr.version))
+                    .await;
+                let resolved = terraria_tmodloader::resolve_release(&tr.version)
+                    .await
                     .map_err(|e| {
+                        let mut fields = BTreeMap::new();
+                        fields.insert("version".to_string(), e.to_string());
                         crate::error_payload::anyhow(
-                            "spawn_failed",
-                            e.to_string(),
-                            None,
-                            Some(
-                                "Ensure the Terraria server binary is executable and dependencies are installed."
-                                    .to_string(),
-                            ),
+                            "download_failed",
+                            format!("failed to resolve tModLoader release: {e}"),
+                            Some(fields),
+                            Some("Check the version tag and network connectivity.".to_string()),
                         )
                     })?;
-                let started = tokio::time::Instant::now();
-                let pid_u32 = child.id();
-                let pgid = pid_u32.map(|p| p as i32);
-
-                if let Some(pid) = pid_u32
-                    && let Some(warn) = sandbox_launch.attach_pid(pid)
-                {
-                    sink.emit(format!("[alloy-agent] sandbox warning: {warn}"))
-                        .await;
-                }
-
-                run.pid = pid_u32;
-                run.pgid = pgid;
-                refresh_docker_container_metadata(&id.0, &mut run).await;
-                let _ = write_run_json(&dir, &run).await;
-
-                let stdin = child.stdin.take();
-                let stdout = child.stdout.take();
-                let stderr = child.stderr.take();
-
-                if let Some(out) = stdout {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(out).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stdout] {line}")).await;
-                        }
-                    });
-                }
-                if let Some(err) = stderr {
-                    let sink = sink.clone();
-                    tokio::spawn(async move {
-                        let mut lines = BufReader::new(err).lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            sink.emit(format!("[stderr] {line}")).await;
-                        }
-                    });
-                }
-
-                {
-                    let mut inner = self.inner.lock().await;
-                    inner.insert(
-                        id.0.clone(),
-                        ProcessEntry {
-                            template_id: ProcessTemplateId(t.template_id.clone()),
-                            state: ProcessState::Starting,
-                            pid: pid_u32,
-                            resources: None,
-                            exit_code: None,
-                            message: Some(format!("waiting for port {}...", tr.port)),
-                            restart,
-                            restart_attempts: reused_restart_attempts,
-                            stdin,
-                            graceful_stdin: t.graceful_stdin.clone(),
-                            pgid,
-                            logs: logs.clone(),
-                            log_file_tx: Some(log_tx.clone()),
-                        },
-                    );
-                }
-
-                if let Some(pid) = pid_u32 {
-                    self.spawn_resource_sampler(id.0.clone(), pid);
-                }
-
-                let manager = self.clone();
-                let inner = self.inner.clone();
-                let id_str = id.0.clone();
-
-                // Port probe: only mark Running once the server actually listens.
-                let probe_sink = sink.clone();
-                let port = tr.port;
-                let frp_config = params
-                    .get("frp_config")
-                    .map(|v| v.trim())
-                    .filter(|v| !v.is_empty())
-                    .map(|v| v.to_string());
-                let frp_instance_dir = dir.clone();
-                tokio::spawn({
-                    let inner = inner.clone();
-                    let id_str = id_str.clone();
-                    let frp_config = frp_config.clone();
-                    let frp_instance_dir = frp_instance_dir.clone();
-                    async move {
-                        let timeout = if creating_world {
-                            Duration::from_millis(
-                                env_u64("ALLOY_TERRARIA_AUTOCREATE_PORT_PROBE_TIMEOUT_MS")
-                                    .map(|v| v.clamp(1000, 10 * 60 * 1000))
-                                    .unwrap_or(10 * 60 * 1000),
-                            )
-                        } else {
-                            port_probe_timeout()
-                        };
-                        let ok = wait_for_local_tcp_port(port, timeout).await;
-
-                        let (pgid, should_kill) = {
-                            let mut map = inner.lock().await;
-                            let Some(e) = map.get_mut(&id_str) else {
-                                return;
-                            };
-                            if e.pid != pid_u32 || !matches!(e.state, ProcessState::Starting) {
-                                return;
-                            }
-
-                            if ok {
-                                e.state = ProcessState::Running;
-                                e.message = None;
-                                (e.pgid, false)
-                            } else {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!(
-                                    "port {} did not open within {}ms",
-                                    port,
-                                    timeout.as_millis()
-                                ));
-                                (e.pgid, true)
-                            }
-                        };
-
-                        if ok {
-                            if let (Some(cfg), Some(pgid)) = (frp_config.clone(), pgid) {
-                                if let Err(e) = start_frpc_sidecar(
-                                    probe_sink.clone(),
-                                    frp_instance_dir.clone(),
-                                    pgid,
-                                    port,
-                                    cfg,
-                                )
-                                .await
-                                {
-                                    probe_sink
-                                        .emit(format!("[alloy-agent] frpc start failed: {e}"))
-                                        .await;
-                                }
-                            }
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] terraria port {} is accepting connections",
-                                    port
-                                ))
-                                .await;
-                        } else {
-                            probe_sink
-                                .emit(format!(
-                                    "[alloy-agent] terraria port {} did not open in time",
-                                    port
-                                ))
-                                .await;
-                            if should_kill && let Some(pgid) = pgid {
-                                #[cfg(unix)]
-                                unsafe {
-                                    libc::kill(-pgid, libc::SIGTERM);
-                                }
-                            }
-                        }
-                    }
-                });
-
-                let process_pgid = pgid;
-                let wait_sink = sink.clone();
-                let template_id = t.template_id.clone();
-                let params_for_restart = params.clone();
-                tokio::spawn(async move {
-                    let res = child.wait().await;
-                    #[cfg(unix)]
-                    if let Some(pgid) = process_pgid {
-                        unsafe {
-                            libc::kill(-pgid, libc::SIGTERM);
-                        }
-                        tokio::time::sleep(Duration::from_millis(500)).await;
-                        let alive = unsafe { libc::kill(-pgid, 0) == 0 };
-                        if alive {
-                            unsafe {
-                                libc::kill(-pgid, libc::SIGKILL);
-                            }
-                        }
-                    }
-                    let runtime = tokio::time::Instant::now().duration_since(started);
-
-                    let mut restart_after: Option<Duration> = None;
-                    let mut restart_attempt: u32 = 0;
-
-                    let (final_state, exit_code) = {
-                        let mut map = inner.lock().await;
-                        let Some(e) = map.get_mut(&id_str) else {
-                            return;
-                        };
-
-                        e.stdin = None;
-                        let stopping = matches!(e.state, ProcessState::Stopping);
-
-                        match res {
-                            Ok(status) => {
-                                e.exit_code = status.code();
-
-                                if stopping {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("stopped".to_string());
-                                } else if runtime < early_exit_threshold() {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited too quickly ({}ms)",
-                                        runtime.as_millis()
-                                    ));
-                                } else if status.success() {
-                                    e.state = ProcessState::Exited;
-                                    e.message = Some("exited".to_string());
-                                } else {
-                                    e.state = ProcessState::Failed;
-                                    e.message = Some(format!(
-                                        "exited with code {}",
-                                        status.code().unwrap_or_default()
-                                    ));
-                                }
-                            }
-                            Err(err) => {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!("wait failed: {err}"));
-                            }
-                        }
-
-                        if !stopping {
-                            let is_failure = matches!(e.state, ProcessState::Failed)
-                                || e.exit_code.is_some_and(|c| c != 0);
-                            let should_restart = match e.restart.policy {
-                                RestartPolicy::Off => false,
-                                RestartPolicy::Always => true,
-                                RestartPolicy::OnFailure => is_failure,
-                            };
-
-                            if should_restart && e.restart_attempts < e.restart.max_retries {
-                                e.restart_attempts = e.restart_attempts.saturating_add(1);
-                                let delay_ms = compute_backoff_ms(e.restart, e.restart_attempts);
-                                restart_after = Some(Duration::from_millis(delay_ms));
-                                restart_attempt = e.restart_attempts;
-                                e.message = Some(format!(
-                                    "restarting in {}ms (attempt {}/{})",
-                                    delay_ms, restart_attempt, e.restart.max_retries
-                                ));
-                            }
-                        }
-
-                        (e.state, e.exit_code)
-                    };
-
-                    wait_sink
-                        .emit(format!(
-                            "[alloy-agent] process exited: state={:?} exit_code={:?} runtime_ms={}",
-                            final_state,
-                            exit_code,
-                            runtime.as_millis()
-                        ))
-                        .await;
-
-                    if let Some(delay) = restart_after {
-                        wait_sink
-                            .emit(format!(
-                                "[alloy-agent] auto-restart scheduled in {}ms (attempt {})",
-                                delay.as_millis(),
-                                restart_attempt
-                            ))
-                            .await;
-                        let handle = tokio::runtime::Handle::current();
-                        let wait_sink = wait_sink.clone();
-                        tokio::task::spawn_blocking(move || {
-                            std::thread::sleep(delay);
-                            let res = handle.block_on(manager.start_from_template_with_process_id(
-                                &id_str,
-                                &template_id,
-                                params_for_restart,
-                            ));
-                            match res {
-                                Ok(st) if matches!(st.state, ProcessState::Failed) => {
-                                    let msg = st
-                                        .message
-                                        .filter(|s| !s.trim().is_empty())
-                                        .unwrap_or_else(|| "unknown error".to_string());
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {msg}"
-                                    )));
-                                }
-                                Ok(_) => {
-                                    handle.block_on(wait_sink.emit(
-                                        "[alloy-agent] auto-restart triggered".to_string(),
-                                    ));
-                                }
-                                Err(err) => {
-                                    handle.block_on(wait_sink.emit(format!(
-                                        "[alloy-agent] auto-restart failed: {err}"
-                                    )));
-                                }
-                            }
-                        });
-                    }
-                });
-
-                return Ok(ProcessStatus {
-                    id: id.clone(),
-                    template_id: ProcessTemplateId(t.template_id.clone()),
-                    state: ProcessState::Starting,
-                    pid: pid_u32,
-                    exit_code: None,
-                    message: Some(format!("waiting for port {}...", tr.port)),
-                    resources: None,
-                });
-            }
-
-            let exec = t.command.clone();
-            let raw_args = t.args.clone();
-            let restart = parse_restart_config(&params);
-            let cwd_path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-
-            let (mut cmd, sandbox_launch) = prepare_instance_command(
-                &id.0,
-                &t.template_id,
-                &params,
-                &root_dir,
-                &cwd_path,
-                &exec,
-                &raw_args,
-                &[],
-            )?;
-
-            let started_at_unix_ms = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_millis() as u64;
-            let mut run = RunInfo {
-                process_id: id.0.clone(),
-                template_id: t.template_id.clone(),
-                started_at_unix_ms,
-                agent_version: env!("CARGO_PKG_VERSION").to_string(),
-                pid: None,
-                pgid: None,
-                container_name: sandbox_launch.container_name().map(ToOwned::to_owned),
-                container_id: None,
-                exec: sandbox_launch.exec.clone(),
-                args: sandbox_launch.args.clone(),
-                cwd: sandbox_launch.cwd.display().to_string(),
-                params: redact_params(params.clone()),
-                env: collect_safe_env(),
-            };
-            let _ = write_run_json(&root_dir, &run).await;
-
-            sink.emit(format!("[alloy-agent] sandbox: {}", sandbox_launch.summary()))
+                self.set_start_phase(&id.0, StartPhase::Downloading, "downloading tModLoader").await;
+                set_entry_message(
+                    &self.inner,
+                    &id.0,
+                    Some(format!("downloading tModLoader {}...", resolved.tag)),
+                )
                 .await;
-            for warning in sandbox_launch.warnings() {
-                sink.emit(format!("[alloy-agent] sandbox warning: {warning}"))
-                    .await;
-            }
-
-            sink.emit(format!(
-                "[alloy-agent] exec: {} {} (cwd {})",
-                sandbox_launch.exec,
-                sandbox_launch.args.join(" "),
-                sandbox_launch.cwd.display()
-            ))
-            .await;
-
-            let mut child = cmd
-                .spawn()
-                .with_context(|| {
-                    format!(
-                        "spawn process: exec={} (cwd {})",
-                        sandbox_launch.exec,
-                        sandbox_launch.cwd.display()
+                let zip_path = terraria_tmodloader::ensure_release_zip_with_progress(
+                    &resolved,
+                    Some(download_reporter(
+                        &self.inner,
+                        &self.events,
+                        &id.0,
+                        &sink,
+                        "downloading tModLoader",
+                    )),
+                )
+                .await
+                .map_err(|e| {
+                    crate::error_payload::anyhow(
+                        "download_failed",
+                        format!("failed to download tModLoader: {e}"),
+                        None,
+                        Some("Try again; if it persists, clear cache and retry.".to_string()),
                     )
-                })
+                })?;
+                self.set_start_phase(&id.0, StartPhase::Installing, "extracting tModLoader").await;
+                let server_root = terraria_tmodloader::extract_release_to_cache(
+                    &zip_path,
+                    &resolved.tag,
+                    install_output_sender(&sink),
+                )
                 .map_err(|e| {
                     crate::error_payload::anyhow(
-                        "spawn_failed",
-                        e.to_string(),
+                        "download_failed",
+                        format!("failed to extract tModLoader: {e}"),
                         None,
-                        Some("Ensure the command exists and is executable.".to_string()),
+                        Some("Clear cache and retry extraction.".to_string()),
                     )
                 })?;
-            let started = tokio::time::Instant::now();
-            let pid_u32 = child.id();
-            let pgid = pid_u32.map(|p| p as i32);
-
-            if let Some(pid) = pid_u32
-                && let Some(warn) = sandbox_launch.attach_pid(pid)
-            {
-                sink.emit(format!("[alloy-agent] sandbox warning: {warn}"))
-                    .await;
-            }
-
-            run.pid = pid_u32;
-            run.pgid = pgid;
-            refresh_docker_container_metadata(&id.0, &mut run).await;
-            let _ = write_run_json(&root_dir, &run).await;
+                let spec = terraria_tmodloader::detect_launch_spec(&server_root)?;
 
-            let stdin = child.stdin.take();
-            let stdout = child.stdout.take();
-            let stderr = child.stderr.take();
-
-            if let Some(out) = stdout {
-                let sink = sink.clone();
-                tokio::spawn(async move {
-                    let mut lines = BufReader::new(out).lines();
-                    while let Ok(Some(line)) = lines.next_line().await {
-                        sink.emit(format!("[stdout] {line}")).await;
-                    }
-                });
-            }
-            if let Some(err) = stderr {
-                let sink = sink.clone();
-                tokio::spawn(async move {
-                    let mut lines = BufReader::new(err).lines();
-                    while let Ok(Some(line)) = lines.next_line().await {
-                        sink.emit(format!("[stderr] {line}")).await;
+                if !tm.mods.is_empty() {
+                    sink.emit(format!("[alloy-agent] installing {} mod(s)", tm.mods.len()))
+                        .await;
+                }
+                let auth = SteamcmdAuth::from_params(&params)?;
+                terraria_tmodloader::install_mods(
+                    &dir,
+                    &resolved.tag,
+                    &tm.mods,
+                    &auth,
+                    install_output_sender(&sink),
+                )
+                .await?;
+
+                if spec.native {
+                    let missing = check_ldd_missing(Path::new(&spec.exec))?;
+                    if !missing.is_empty() {
+                        return Err(crate::error_payload::anyhow(
+                            "missing_dependency",
+                            format!(
+                                "tModLoader runtime dependencies missing:\n{}",
+                                missing.join("\n")
+                            ),
+                            None,
+                            Some(
+                                "Update the Docker image, or install the listed libraries on the host."
+                                    .to_string(),
+                            ),
+                        ));
                     }
-                });
-            }
+                }
 
-            {
-                let mut inner = self.inner.lock().await;
-                inner.insert(
-                    id.0.clone(),
-                    ProcessEntry {
-                        template_id: ProcessTemplateId(t.template_id.clone()),
-                        state: ProcessState::Running,
-                        pid: pid_u32,
-                        resources: None,
-                        exit_code: None,
-                        message: None,
+                let mut library_path: Vec<String> = spec
+                    .library_dirs
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect();
+                library_path.push(std::env::var("LD_LIBRARY_PATH").unwrap_or_default());
+                let mods_dir = std::fs::canonicalize(dir.join("mods")).unwrap_or(dir.join("mods"));
+                let mut raw_args = spec.args.clone();
+                raw_args.extend([
+                    "-config".to_string(),
+                    config_path.display().to_string(),
+                    "-modpath".to_string(),
+                    mods_dir.display().to_string(),
+                ]);
+                // Creating a world on first start can take minutes before the port opens.
+                let probe_timeout = if creating_world {
+                    Duration::from_millis(
+                        env_u64("ALLOY_TERRARIA_AUTOCREATE_PORT_PROBE_TIMEOUT_MS")
+                            .map(|v| v.clamp(1000, 10 * 60 * 1000))
+                            .unwrap_or(10 * 60 * 1000),
+                    )
+                } else {
+                    port_probe_timeout()
+                };
+                return self
+                    .spawn_supervised(LaunchContext {
+                        process_id: id.0.clone(),
+                        template_id: t.template_id.clone(),
+                        kind: "terraria",
+                        exec_detail: format!("port={} version={}", tr.port, resolved.tag),
+                        exec: spec.exec.clone(),
+                        args: raw_args,
+                        cwd: server_root.clone(),
+                        instance_dir: dir.clone(),
+                        extra_rw_paths: vec![server_root.clone()],
+                        env: vec![
+                            ("TERM".to_string(), "xterm".to_string()),
+                            ("LD_LIBRARY_PATH".to_string(), library_path.join(":")),
+                        ],
+                        spawn_hint: TERRARIA_SPAWN_HINT,
+                        port_probe: Some(PortProbe::Tcp {
+                            port: tr.port,
+                            timeout: probe_timeout,
+                        }),
+                        frp_config: frp_config_param(&params),
                         restart,
                         restart_attempts: reused_restart_attempts,
-                        stdin,
-                        graceful_stdin: t.graceful_stdin.clone(),
-                        pgid,
-                        logs: logs.clone(),
-                        log_file_tx: Some(log_tx.clone()),
-                    },
-                );
-            }
-
-            if let Some(pid) = pid_u32 {
-                self.spawn_resource_sampler(id.0.clone(), pid);
-            }
-
-            let manager = self.clone();
-            let inner = self.inner.clone();
-            let id_str = id.0.clone();
-            let wait_sink = sink.clone();
-            let template_id = t.template_id.clone();
-            let params_for_restart = params.clone();
-            tokio::spawn(async move {
-                let res = child.wait().await;
-                let runtime = tokio::time::Instant::now().duration_since(started);
-
-                let mut restart_after: Option<Duration> = None;
-                let mut restart_attempt: u32 = 0;
-
-                let (final_state, exit_code) = {
-                    let mut map = inner.lock().await;
-                    let Some(e) = map.get_mut(&id_str) else {
-                        return;
-                    };
-
-                    e.stdin = None;
-                    let stopping = matches!(e.state, ProcessState::Stopping);
-
-                    match res {
-                        Ok(status) => {
-                            e.exit_code = status.code();
-
-                            if stopping {
-                                e.state = ProcessState::Exited;
-                                e.message = Some("stopped".to_string());
-                            } else if runtime < early_exit_threshold() {
-                                e.state = ProcessState::Failed;
-                                e.message =
-                                    Some(format!("exited too quickly ({}ms)", runtime.as_millis()));
-                            } else if status.success() {
-                                e.state = ProcessState::Exited;
-                                e.message = Some("exited".to_string());
-                            } else {
-                                e.state = ProcessState::Failed;
-                                e.message = Some(format!(
-                                    "exited with code {}",
-                                    status.code().unwrap_or_default()
-                                ));
-                            }
-                        }
-                        Err(err) => {
-                            e.state = ProcessState::Failed;
-                            e.message = Some(format!("wait failed: {err}"));
-                        }
-                    }
-
-                    if !stopping {
-                        let is_failure = matches!(e.state, ProcessState::Failed)
-                            || e.exit_code.is_some_and(|c| c != 0);
-                        let should_restart = match e.restart.policy {
-                            RestartPolicy::Off => false,
-                            RestartPolicy::Always => true,
-                            RestartPolicy::OnFailure => is_failure,
-                        };
-
-                        if should_restart && e.restart_attempts < e.restart.max_retries {
-                            e.restart_attempts = e.restart_attempts.saturating_add(1);
-                            let delay_ms = compute_backoff_ms(e.restart, e.restart_attempts);
-                            restart_after = Some(Duration::from_millis(delay_ms));
-                            restart_attempt = e.restart_attempts;
-                            e.message = Some(format!(
-                                "restarting in {}ms (attempt {}/{})",
-                                delay_ms, restart_attempt, e.restart.max_retries
-                            ));
-                        }
-                    }
-
-                    (e.state, e.exit_code)
-                };
-
-                wait_sink
-                    .emit(format!(
-                        "[alloy-agent] process exited: state={:?} exit_code={:?} runtime_ms={}",
-                        final_state,
-                        exit_code,
-                        runtime.as_millis()
-                    ))
+                        graceful_stop: t.graceful_stop.clone(),
+                        sink: sink.clone(),
+                        params: params.clone(),
+                        companions: Vec::new(),
+                    })
                     .await;
+            }
 
-                if let Some(delay) = restart_after {
-                    wait_sink
-                        .emit(format!(
-                            "[alloy-agent] auto-restart scheduled in {}ms (attempt {})",
-                            delay.as_millis(),
-                            restart_attempt
-                        ))
-                        .await;
-                    let handle = tokio::runtime::Handle::current();
-                    let wait_sink = wait_sink.clone();
-                    tokio::task::spawn_blocking(move || {
-                        std::thread::sleep(delay);
-                        let res = handle.block_on(manager.start_from_template_with_process_id(
-                            &id_str,
-                            &template_id,
-                            params_for_restart,
-                        ));
-                        match res {
-                            Ok(st) if matches!(st.state, ProcessState::Failed) => {
-                                let msg = st
-                                    .message
-                                    .filter(|s| !s.trim().is_empty())
-                                    .unwrap_or_else(|| "unknown error".to_string());
-                                handle.block_on(
-                                    wait_sink
-                                        .emit(format!("[alloy-agent] auto-restart failed: {msg}")),
-                                );
-                            }
-                            Ok(_) => {
-                                handle.block_on(
-                                    wait_sink
-                                        .emit("[alloy-agent] auto-restart triggered".to_string()),
-                                );
-                            }
-                            Err(err) => {
-                                handle.block_on(
-                                    wait_sink
-                                        .emit(format!("[alloy-agent] auto-restart failed: {err}")),
-                                );
-                            }
-                        }
-                    });
+            let exec = t.command.clone();
+            let custom = custom_templates::is_custom(&t.template_id);
+            let (raw_args, params) = if custom {
+                custom_templates::prepare_launch(&id.0, &t, &params)?
+            } else {
+                (t.args.clone(), params.clone())
+            };
+            let oneshot = t.template_id == templates::ONESHOT_TEMPLATE_ID;
+            let mut restart = parse_restart_config(&params);
+            let mut extra_rw_paths = Vec::<PathBuf>::new();
+            let cwd_path = if oneshot {
+                // Oneshot jobs never restart; they run once in the target instance dir.
+                restart.policy = RestartPolicy::Off;
+                let instance_dir = templates::oneshot_instance_dir(&params)
+                    .ok_or_else(|| anyhow::anyhow!("invalid instance_id"))?;
+                let instance_id = params
+                    .get("instance_id")
+                    .map(|v| v.trim().to_string())
+                    .unwrap_or_default();
+                let busy = self.inner.lock().await.get(&instance_id).is_some_and(|e| {
+                    matches!(
+                        e.state,
+                        ProcessState::Running | ProcessState::Starting | ProcessState::Stopping
+                    )
+                });
+                if busy {
+                    return Err(crate::error_payload::anyhow(
+                        "instance_running",
+                        format!("instance {instance_id} is running"),
+                        None,
+                        Some("Stop the instance before running maintenance commands.".to_string()),
+                    ));
                 }
-            });
-
-            Ok(ProcessStatus {
-                id: id.clone(),
-                template_id: ProcessTemplateId(t.template_id.clone()),
-                state: ProcessState::Running,
-                pid: pid_u32,
-                exit_code: None,
-                message: None,
-                resources: None,
+                extra_rw_paths.push(instance_dir.clone());
+                instance_dir
+            } else if custom {
+                // Custom templates run in their own instance dir, like the game templates.
+                tokio::fs::create_dir_all(&root_dir).await?;
+                root_dir.clone()
+            } else {
+                std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+            };
+
+            let kind = if oneshot {
+                "oneshot"
+            } else if custom {
+                "custom"
+            } else {
+                "process"
+            };
+            self.spawn_supervised(LaunchContext {
+                process_id: id.0.clone(),
+                template_id: t.template_id.clone(),
+                kind,
+                exec_detail: format!("template={}", t.template_id),
+                exec,
+                args: raw_args,
+                cwd: cwd_path,
+                instance_dir: root_dir.clone(),
+                extra_rw_paths,
+                env: Vec::new(),
+                spawn_hint: GENERIC_SPAWN_HINT,
+                port_probe: None,
+                frp_config: None,
+                restart,
+                restart_attempts: reused_restart_attempts,
+                graceful_stop: t.graceful_stop.clone(),
+                sink: sink.clone(),
+                params,
+                companions: Vec::new(),
             })
+            .await
         }
         .await;
 
         match result {
             Ok(st) => Ok(st),
             Err(err) => {
+                port_alloc::release(&id.0);
                 let msg = format_error_chain(&err);
+                let category = fail_category(&err);
                 sink.emit(format!("[alloy-agent] start failed: {msg}"))
                     .await;
+                self.set_start_phase(&id.0, StartPhase::Failed, msg.clone())
+                    .await;
 
                 let restart = parse_restart_config(&params);
 
@@ -4590,13 +6447,21 @@ impl ProcessManager {
                             restart,
                             restart_attempts: reused_restart_attempts,
                             stdin: None,
-                            graceful_stdin: t.graceful_stdin.clone(),
+                            graceful_stop: t.graceful_stop.clone(),
                             pgid: None,
                             logs: logs.clone(),
                             log_file_tx: Some(log_tx.clone()),
+                            fail_category: Some(category),
+                            failure_reason: None,
+                            failure_hint: None,
+                            players: None,
+                            progress: None,
+                            start_phase: None,
                         },
                     );
                 }
+                self.events
+                    .emit(&id.0, ProcessEventKind::StateChanged(ProcessState::Failed));
 
                 Ok(ProcessStatus {
                     id,
@@ -4606,11 +6471,83 @@ impl ProcessManager {
                     exit_code: None,
                     message: Some(msg),
                     resources: None,
+                    fail_category: Some(category),
+                    failure_reason: None,
+                    failure_hint: None,
+                    players: None,
+                    progress: None,
                 })
             }
         }
     }
 
+    /// Pre-downloads `template_id`'s artifacts without starting anything,
+    /// reporting under `progress`'s warm id.
+    pub(crate) async fn warm_cache(
+        &self,
+        template_id: &str,
+        params: &BTreeMap<String, String>,
+        progress: &crate::warm_cache::WarmProgress,
+    ) -> anyhow::Result<String> {
+        let message = crate::warm_cache::warm(template_id, params, progress).await?;
+        self.spawn_cache_gc();
+        Ok(message)
+    }
+
+    /// Cache paths the active servers run from and the files linked into their
+    /// instance dirs; [`cache_gc`] never deletes these.
+    pub(crate) async fn cache_in_use(&self) -> cache_gc::InUse {
+        let data_root = crate::minecraft::data_root();
+        let mut dirs = Vec::new();
+        let mut paths = Vec::new();
+        for status in self.list_processes().await {
+            if !matches!(
+                status.state,
+                ProcessState::Running | ProcessState::Starting | ProcessState::Stopping
+            ) {
+                continue;
+            }
+            let id = status.id.0;
+            dirs.push(data_root.join("instances").join(&id));
+            dirs.push(data_root.join("processes").join(&id));
+            if let Some(run) = read_run_json::<RunInfoView>(&data_root, &id).await {
+                paths.extend(
+                    [run.exec, run.cwd]
+                        .into_iter()
+                        .chain(run.args)
+                        .map(PathBuf::from)
+                        .filter(|p| p.is_absolute()),
+                );
+            }
+        }
+        tokio::task::spawn_blocking(move || cache_gc::InUse::collect(&dirs, paths))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Evicts least recently used cache artifacts while the shared caches
+    /// exceed `ALLOY_CACHE_MAX_BYTES`.
+    pub(crate) async fn enforce_cache_limit(&self) -> Vec<cache_gc::CacheItem> {
+        let Some(max_bytes) = cache_max_bytes() else {
+            return Vec::new();
+        };
+        let in_use = self.cache_in_use().await;
+        let roots = cache_gc::CacheRoots::new(&crate::minecraft::data_root());
+        tokio::task::spawn_blocking(move || cache_gc::evict_to_limit(&roots, &in_use, max_bytes))
+            .await
+            .unwrap_or_default()
+    }
+
+    fn spawn_cache_gc(&self) {
+        if cache_max_bytes().is_none() {
+            return;
+        }
+        let manager = self.clone();
+        tokio::spawn(async move {
+            manager.enforce_cache_limit().await;
+        });
+    }
+
     pub async fn list_templates(&self) -> Vec<templates::ProcessTemplate> {
         templates::list_templates()
     }
@@ -4627,10 +6564,57 @@ impl ProcessManager {
                 exit_code: e.exit_code,
                 message: e.message.clone(),
                 resources: e.resources.clone(),
+                fail_category: e.fail_category,
+                failure_reason: e.failure_reason,
+                failure_hint: e.failure_hint.clone(),
+                players: e.players,
+                progress: e.progress.clone(),
             })
             .collect()
     }
 
+    /// Live entries plus every process known only from its run.json (e.g. after
+    /// an agent restart); those are reported as Exited.
+    pub async fn list_instances(&self) -> Vec<ProcessStatus> {
+        self.list_instances_in(&crate::minecraft::data_root()).await
+    }
+
+    async fn list_instances_in(&self, data_root: &Path) -> Vec<ProcessStatus> {
+        let mut out = self.list_processes().await;
+        let mut seen: BTreeSet<String> = out.iter().map(|s| s.id.0.clone()).collect();
+        for dir in ["instances", "processes"] {
+            let Ok(mut rd) = tokio::fs::read_dir(data_root.join(dir)).await else {
+                continue;
+            };
+            while let Ok(Some(entry)) = rd.next_entry().await {
+                let id = entry.file_name().to_string_lossy().to_string();
+                if seen.contains(&id) {
+                    continue;
+                }
+                let Some(run) = read_run_json::<PersistedRun>(data_root, &id).await else {
+                    continue;
+                };
+                seen.insert(id.clone());
+                out.push(ProcessStatus {
+                    id: ProcessId(id),
+                    template_id: ProcessTemplateId(run.template_id),
+                    state: ProcessState::Exited,
+                    pid: None,
+                    exit_code: None,
+                    message: Some("not running".to_string()),
+                    resources: None,
+                    fail_category: None,
+                    failure_reason: None,
+                    failure_hint: None,
+                    players: None,
+                    progress: None,
+                });
+            }
+        }
+        out.sort_by(|a, b| a.id.0.cmp(&b.id.0));
+        out
+    }
+
     pub async fn get_status(&self, process_id: &str) -> Option<ProcessStatus> {
         let inner = self.inner.lock().await;
         inner.get(process_id).map(|e| ProcessStatus {
@@ -4641,9 +6625,200 @@ impl ProcessManager {
             exit_code: e.exit_code,
             message: e.message.clone(),
             resources: e.resources.clone(),
+            fail_category: e.fail_category,
+            failure_reason: e.failure_reason,
+            failure_hint: e.failure_hint.clone(),
+            players: e.players,
+            progress: e.progress.clone(),
         })
     }
 
+    /// Drops a finished entry (Exited/Failed) from the in-memory map.
+    /// Returns false when the entry is missing or still active.
+    pub async fn forget(&self, process_id: &str) -> bool {
+        let mut inner = self.inner.lock().await;
+        let finished = inner
+            .get(process_id)
+            .is_some_and(|e| matches!(e.state, ProcessState::Exited | ProcessState::Failed));
+        if finished {
+            inner.remove(process_id);
+            self.resource_history.lock().await.remove(process_id);
+        }
+        finished
+    }
+
+    /// Size of the data directory `delete_instance` would remove, if any.
+    pub async fn delete_instance_preview(
+        &self,
+        process_id: &str,
+    ) -> anyhow::Result<Option<DeleteInstancePreview>> {
+        let data_root = minecraft::data_root();
+        let process_id = process_id.to_string();
+        tokio::task::spawn_blocking(move || preview_instance_delete(&data_root, &process_id))
+            .await
+            .context("join delete preview")?
+    }
+
+    /// Forgets a stopped process and, with `remove_data`, deletes its data
+    /// directory. Returns what was removed from disk.
+    pub async fn delete_instance(
+        &self,
+        process_id: &str,
+        remove_data: bool,
+    ) -> anyhow::Result<Option<DeleteInstancePreview>> {
+        {
+            let mut inner = self.inner.lock().await;
+            if let Some(e) = inner.get(process_id)
+                && matches!(
+                    e.state,
+                    ProcessState::Running | ProcessState::Starting | ProcessState::Stopping
+                )
+            {
+                anyhow::bail!("process is still active ({:?}): {process_id}", e.state);
+            }
+            inner.remove(process_id);
+            self.resource_history.lock().await.remove(process_id);
+        }
+        if !remove_data {
+            return Ok(None);
+        }
+
+        let Some(preview) = self.delete_instance_preview(process_id).await? else {
+            return Ok(None);
+        };
+        tokio::fs::remove_dir_all(&preview.path)
+            .await
+            .with_context(|| format!("delete {}", preview.path.display()))?;
+        Ok(Some(preview))
+    }
+
+    /// Retained `(unix_ms, sample)` resource history for a process taken after
+    /// `since_ms`, oldest first.
+    pub async fn resource_history(
+        &self,
+        process_id: &str,
+        since_ms: u64,
+    ) -> Vec<(u64, alloy_process::ProcessResources)> {
+        self.resource_history
+            .lock()
+            .await
+            .get(process_id)
+            .map(|h| h.iter().filter(|(at, _)| *at > since_ms).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Adopts a server left running by a previous agent: registers it as
+    /// `Running` with its recorded pgid, reloads the tail of its console log
+    /// and resumes resource sampling. Its stdout/stderr pipes died with the
+    /// old agent, so only agent messages reach the console until it restarts.
+    pub async fn reattach(
+        &self,
+        process_id: &str,
+        template_id: &str,
+        pid: u32,
+        pgid: i32,
+        root_dir: &Path,
+    ) -> anyhow::Result<()> {
+        if self.inner.lock().await.contains_key(process_id) {
+            anyhow::bail!("process_id already managed: {process_id}");
+        }
+
+        let params = read_persisted_params(process_id).await.unwrap_or_default();
+        let graceful_stop = templates::find_template(template_id)
+            .and_then(|t| templates::apply_params(t, &params).ok())
+            .and_then(|t| t.graceful_stop);
+
+        let console_log = root_dir.join("logs").join("console.log");
+        let mut buffer = LogBuffer::default();
+        for line in read_log_tail(&console_log, buffer.max_lines).await {
+            buffer.push_line(line);
+        }
+        let sink = LogSink {
+            buffer: Arc::new(Mutex::new(buffer)),
+            file_tx: Some(spawn_console_log_writer(console_log)),
+            limiter: None,
+        };
+        sink.emit(format!(
+            "[alloy-agent] reattached after agent restart: pid={pid} pgid={pgid}; console output is not captured until the next start"
+        ))
+        .await;
+
+        {
+            let mut inner = self.inner.lock().await;
+            inner.insert(
+                process_id.to_string(),
+                ProcessEntry {
+                    template_id: ProcessTemplateId(template_id.to_string()),
+                    state: ProcessState::Running,
+                    pid: Some(pid),
+                    resources: None,
+                    exit_code: None,
+                    message: None,
+                    restart: parse_restart_config(&params),
+                    restart_attempts: 0,
+                    stdin: None,
+                    graceful_stop,
+                    pgid: Some(pgid),
+                    logs: sink.buffer.clone(),
+                    log_file_tx: sink.file_tx.clone(),
+                    fail_category: None,
+                    failure_reason: None,
+                    failure_hint: None,
+                    players: None,
+                    progress: None,
+                    start_phase: None,
+                },
+            );
+        }
+        self.events.emit(
+            process_id,
+            ProcessEventKind::StateChanged(ProcessState::Running),
+        );
+        self.spawn_resource_sampler(process_id.to_string(), pid);
+
+        let inner = self.inner.clone();
+        let events = self.events.clone();
+        let id = process_id.to_string();
+        tokio::spawn(async move {
+            while unsafe { libc::kill(pid as i32, 0) == 0 } {
+                tokio::time::sleep(REATTACH_POLL_INTERVAL).await;
+            }
+            // Like the waiter for spawned servers: take down whatever is left in the group.
+            if process_group_alive(pgid) {
+                unsafe {
+                    libc::kill(-pgid, libc::SIGTERM);
+                }
+            }
+
+            let state = {
+                let mut map = inner.lock().await;
+                let Some(e) = map.get_mut(&id) else {
+                    return;
+                };
+                if e.pid != Some(pid) {
+                    return;
+                }
+                e.players = None;
+                e.message = Some(if matches!(e.state, ProcessState::Stopping) {
+                    "stopped".to_string()
+                } else {
+                    "exited (exit code unknown after reattach)".to_string()
+                });
+                e.state = ProcessState::Exited;
+                e.state
+            };
+            events.emit(
+                &id,
+                ProcessEventKind::Exited {
+                    state,
+                    exit_code: None,
+                },
+            );
+            sink.emit("[alloy-agent] reattached process exited").await;
+        });
+        Ok(())
+    }
+
     pub async fn start_from_template(
         &self,
         template_id: &str,
@@ -4654,20 +6829,316 @@ impl ProcessManager {
             .await
     }
 
+    /// Asks a running Minecraft server to re-read its config via the `reload`
+    /// console command. Other templates have no in-place reload.
+    pub async fn reload(&self, process_id: &str) -> anyhow::Result<ProcessStatus> {
+        let (template_id, state, logs, log_tx) = {
+            let inner = self.inner.lock().await;
+            let e = inner.get(process_id).ok_or_else(|| {
+                crate::error_payload::anyhow(
+                    "not_found",
+                    format!("unknown process_id: {process_id}"),
+                    None,
+                    None,
+                )
+            })?;
+            (
+                e.template_id.0.clone(),
+                e.state,
+                e.logs.clone(),
+                e.log_file_tx.clone(),
+            )
+        };
+
+        if !template_id.starts_with("minecraft:") {
+            return Err(crate::error_payload::anyhow(
+                "unsupported",
+                format!("{template_id} does not support reload"),
+                None,
+                Some("Restart the instance to apply config changes.".to_string()),
+            ));
+        }
+        match state {
+            ProcessState::Running => {}
+            ProcessState::Starting => {
+                return Err(crate::error_payload::anyhow(
+                    "instance_starting",
+                    "server is still starting",
+                    None,
+                    Some("Wait until the server is running, then reload.".to_string()),
+                ));
+            }
+            other => {
+                return Err(crate::error_payload::anyhow(
+                    "instance_not_running",
+                    format!("server is not running ({other:?})"),
+                    None,
+                    Some("Start the instance first.".to_string()),
+                ));
+            }
+        }
+
+        let line = "[alloy-agent] reload requested".to_string();
+        logs.lock().await.push_line(line.clone());
+        if let Some(tx) = log_tx {
+            let _ = tx.send(line);
+        }
+        self.send_stdin(process_id, "reload".to_string()).await?;
+
+        self.get_status(process_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("unknown process_id: {process_id}"))
+    }
+
+    /// Writes one line to a running process's console (stdin). Lines longer than
+    /// `MAX_CONSOLE_LINE_BYTES` are truncated.
+    pub async fn send_stdin(&self, process_id: &str, mut line: String) -> anyhow::Result<()> {
+        if line.len() > MAX_CONSOLE_LINE_BYTES {
+            line.truncate(line.floor_char_boundary(MAX_CONSOLE_LINE_BYTES));
+        }
+        let mut stdin = {
+            let mut inner = self.inner.lock().await;
+            let e = inner
+                .get_mut(process_id)
+                .ok_or_else(|| anyhow::anyhow!("unknown process_id: {process_id}"))?;
+            if !matches!(e.state, ProcessState::Running) {
+                anyhow::bail!("process is not running ({:?})", e.state);
+            }
+            e.stdin
+                .take()
+                .ok_or_else(|| anyhow::anyhow!("process console is not available"))?
+        };
+
+        let res = async {
+            stdin.write_all(format!("{line}\n").as_bytes()).await?;
+            stdin.flush().await
+        }
+        .await;
+
+        // Hand stdin back unless a stop began meanwhile (it then falls back to SIGTERM).
+        let mut inner = self.inner.lock().await;
+        if let Some(e) = inner.get_mut(process_id)
+            && matches!(e.state, ProcessState::Running)
+            && e.stdin.is_none()
+        {
+            e.stdin = Some(stdin);
+        }
+        Ok(res?)
+    }
+
+    /// Adopts a sandbox container the previous agent handed off during an
+    /// update: registers it as `Running`, follows its new output with
+    /// `docker logs` and records its exit code from `docker wait`.
+    pub async fn reattach_container(
+        &self,
+        process_id: &str,
+        template_id: &str,
+        container_ref: &str,
+        root_dir: &Path,
+    ) -> anyhow::Result<()> {
+        if self.inner.lock().await.contains_key(process_id) {
+            anyhow::bail!("process_id already managed: {process_id}");
+        }
+        let (container_id, pid) = docker_running_container(container_ref)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("container {container_ref} is not running"))?;
+
+        let params = read_persisted_params(process_id).await.unwrap_or_default();
+        let graceful_stop = templates::find_template(template_id)
+            .and_then(|t| templates::apply_params(t, &params).ok())
+            .and_then(|t| t.graceful_stop);
+
+        let console_log = root_dir.join("logs").join("console.log");
+        let mut buffer = LogBuffer::default();
+        for line in read_log_tail(&console_log, buffer.max_lines).await {
+            buffer.push_line(line);
+        }
+        let sink = LogSink {
+            buffer: Arc::new(Mutex::new(buffer)),
+            file_tx: Some(spawn_console_log_writer(console_log)),
+            limiter: log_rate_limit_lines_per_sec()
+                .map(|limit| Arc::new(std::sync::Mutex::new(LineRateLimiter::new(limit)))),
+        };
+        sink.emit(format!(
+            "[alloy-agent] re-adopted container {} after agent update",
+            container_id.chars().take(12).collect::<String>()
+        ))
+        .await;
+
+        {
+            let mut inner = self.inner.lock().await;
+            inner.insert(
+                process_id.to_string(),
+                ProcessEntry {
+                    template_id: ProcessTemplateId(template_id.to_string()),
+                    state: ProcessState::Running,
+                    pid: Some(pid),
+                    resources: None,
+                    exit_code: None,
+                    message: None,
+                    restart: parse_restart_config(&params),
+                    restart_attempts: 0,
+                    stdin: None,
+                    graceful_stop,
+                    pgid: None,
+                    logs: sink.buffer.clone(),
+                    log_file_tx: sink.file_tx.clone(),
+                    fail_category: None,
+                    failure_reason: None,
+                    failure_hint: None,
+                    players: None,
+                    progress: None,
+                    start_phase: None,
+                },
+            );
+        }
+        self.events.emit(
+            process_id,
+            ProcessEventKind::StateChanged(ProcessState::Running),
+        );
+        self.spawn_resource_sampler(process_id.to_string(), pid);
+
+        // Earlier output is already in console.log; follow only what comes next.
+        match Command::new("docker")
+            .env_remove("DOCKER_API_VERSION")
+            .arg("logs")
+            .arg("--follow")
+            .arg("--tail")
+            .arg("0")
+            .arg(&container_id)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(mut child) => {
+                if let Some(out) = child.stdout.take() {
+                    tokio::spawn(pump_output(out, sink.clone(), "[stdout]"));
+                }
+                if let Some(err) = child.stderr.take() {
+                    tokio::spawn(pump_output(err, sink.clone(), "[stderr]"));
+                }
+                tokio::spawn(async move {
+                    let _ = child.wait().await;
+                });
+            }
+            Err(err) => {
+                sink.emit(format!(
+                    "[alloy-agent] docker logs unavailable ({err}); console output is not captured until the next start"
+                ))
+                .await;
+            }
+        }
+
+        let inner = self.inner.clone();
+        let events = self.events.clone();
+        let id = process_id.to_string();
+        tokio::spawn(async move {
+            let exit_code = docker_wait_container(&container_id).await;
+
+            let state = {
+                let mut map = inner.lock().await;
+                let Some(e) = map.get_mut(&id) else {
+                    return;
+                };
+                if e.pid != Some(pid) {
+                    return;
+                }
+                e.players = None;
+                e.exit_code = exit_code;
+                e.message = Some(if matches!(e.state, ProcessState::Stopping) {
+                    "stopped".to_string()
+                } else {
+                    match exit_code {
+                        Some(code) => format!("exited (code {code})"),
+                        None => "exited (exit code unknown)".to_string(),
+                    }
+                });
+                e.state = ProcessState::Exited;
+                e.state
+            };
+            events.emit(&id, ProcessEventKind::Exited { state, exit_code });
+            sink.emit("[alloy-agent] re-adopted container exited").await;
+        });
+        Ok(())
+    }
+
+    /// Stops a process (if it is active) and starts it again with the template and
+    /// params of its last launch. A manual restart resets the auto-restart budget.
+    pub async fn restart(
+        &self,
+        process_id: &str,
+        timeout: Duration,
+    ) -> anyhow::Result<ProcessStatus> {
+        let template_id = match self.get_status(process_id).await {
+            Some(st) => st.template_id.0,
+            None => {
+                read_persisted_run(process_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("unknown process_id: {process_id}"))?
+                    .template_id
+            }
+        };
+        let params = read_persisted_params(process_id).await.ok_or_else(|| {
+            crate::error_payload::anyhow(
+                "params_unavailable",
+                format!("no stored launch params for {process_id}"),
+                None,
+                Some(
+                    "Start it once with an explicit start; later restarts reuse those params."
+                        .to_string(),
+                ),
+            )
+        })?;
+
+        let active = self.get_status(process_id).await.is_some_and(|st| {
+            matches!(
+                st.state,
+                ProcessState::Running | ProcessState::Starting | ProcessState::Stopping
+            )
+        });
+        if active {
+            self.stop(process_id, timeout).await?;
+            // stop() returns once the kill is sent; the exit is recorded by the wait task.
+            let deadline = tokio::time::Instant::now() + RESTART_EXIT_GRACE;
+            loop {
+                let finished = self.get_status(process_id).await.is_none_or(|st| {
+                    matches!(st.state, ProcessState::Exited | ProcessState::Failed)
+                });
+                if finished {
+                    break;
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    anyhow::bail!("process {process_id} did not exit after stop");
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+
+        if let Some(e) = self.inner.lock().await.get_mut(process_id) {
+            e.restart_attempts = 0;
+        }
+        self.start_from_template_with_process_id(process_id, &template_id, params)
+            .await
+    }
+
     pub async fn stop(&self, process_id: &str, timeout: Duration) -> anyhow::Result<ProcessStatus> {
         // Phase 1 policy:
-        // - If template defines `graceful_stdin`, send it first and give the process time.
+        // - If the template's graceful stop spec has a stdin command, send it first and give
+        //   the process time.
         // - Otherwise, send SIGTERM immediately.
         // - Always escalate: SIGTERM (fallback) -> SIGKILL at the end of timeout.
 
         let mut graceful_sent = false;
         let mut term_sent = false;
-        let template_id: String;
         let pgid: Option<i32>;
         let logs: Arc<Mutex<LogBuffer>>;
         let log_tx: Option<mpsc::UnboundedSender<String>>;
         let mut graceful: Option<(ChildStdin, String)> = None;
+        let spec: GracefulStopSpec;
         let docker_container: Option<String>;
+        let template_id: String;
 
         {
             let mut inner = self.inner.lock().await;
@@ -4684,18 +7155,31 @@ impl ProcessManager {
                     exit_code: e.exit_code,
                     message: e.message.clone(),
                     resources: e.resources.clone(),
+                    fail_category: e.fail_category,
+                    failure_reason: e.failure_reason,
+                    failure_hint: e.failure_hint.clone(),
+                    players: e.players,
+                    progress: e.progress.clone(),
                 });
             }
 
-            template_id = e.template_id.0.clone();
             pgid = e.pgid;
             logs = e.logs.clone();
             log_tx = e.log_file_tx.clone();
+            template_id = e.template_id.0.clone();
             e.state = ProcessState::Stopping;
             e.message = Some("stopping".to_string());
+            self.events.emit(
+                process_id,
+                ProcessEventKind::StateChanged(ProcessState::Stopping),
+            );
 
+            spec = e.graceful_stop.clone().unwrap_or_default();
             if let Some(stdin) = e.stdin.take()
-                && let Some(cmd) = e.graceful_stdin.take()
+                && let Some(cmd) = e
+                    .graceful_stop
+                    .as_mut()
+                    .and_then(|g| g.stdin_command.take())
             {
                 graceful = Some((stdin, cmd));
             }
@@ -4719,6 +7203,12 @@ impl ProcessManager {
             log_tx.clone(),
         )
         .await;
+        self.audit.record(
+            "stop",
+            process_id,
+            &template_id,
+            format!("timeout_ms={}", timeout.as_millis()),
+        );
 
         docker_container = find_container_for_process(process_id).await;
         if let Some(container_id) = docker_container.as_deref() {
@@ -4749,7 +7239,14 @@ impl ProcessManager {
         // If we didn't have a graceful command, send SIGTERM right away.
         if !graceful_sent {
             if let Some(container_id) = docker_container.as_deref() {
-                match docker_stop_container(container_id, timeout.as_secs().max(1)).await {
+                let res = docker_stop_container(container_id, timeout.as_secs().max(1)).await;
+                self.audit.record(
+                    "docker_stop",
+                    process_id,
+                    &template_id,
+                    audit_detail(container_id, &res),
+                );
+                match res {
                     Ok(()) => {
                         term_sent = true;
                         emit(
@@ -4774,6 +7271,8 @@ impl ProcessManager {
                     libc::kill(-pgid, libc::SIGTERM);
                 }
                 term_sent = true;
+                self.audit
+                    .record("sigterm", process_id, &template_id, format!("pgid {pgid}"));
                 emit(
                     "[alloy-agent] stop: sent SIGTERM".to_string(),
                     logs.clone(),
@@ -4787,9 +7286,11 @@ impl ProcessManager {
         let kill_deadline = start + timeout;
         // If we attempted graceful stdin, only send SIGTERM near the end.
         let term_deadline = if graceful_sent {
-            kill_deadline
-                .checked_sub(graceful_term_grace())
-                .unwrap_or(start)
+            let grace = spec
+                .grace_secs
+                .map(|v| Duration::from_secs(v.clamp(1, 60)))
+                .unwrap_or_else(graceful_term_grace);
+            kill_deadline.checked_sub(grace).unwrap_or(start)
         } else {
             start
         };
@@ -4802,16 +7303,11 @@ impl ProcessManager {
         let mut save_confirmed = false;
         let mut save_timeout_warned = false;
 
-        let save_keywords: &[&str] = match template_id.as_str() {
-            "minecraft:vanilla" => &[
-                "saved the game",
-                "saving chunks for level",
-                "all chunks are saved",
-                "saving players",
-            ],
-            "terraria:vanilla" => &["saving world", "world saved"],
-            _ => &[],
-        };
+        let save_keywords: Vec<String> = spec
+            .save_keywords
+            .iter()
+            .map(|k| k.to_ascii_lowercase())
+            .collect();
 
         loop {
             if let Some(status) = self.get_status(process_id).await
@@ -4828,16 +7324,10 @@ impl ProcessManager {
                     save_cursor = next;
                     for line in &lines {
                         let lower = line.to_ascii_lowercase();
-                        if save_keywords.iter().any(|k| lower.contains(k)) {
+                        if let Some(keyword) = save_keywords.iter().find(|k| lower.contains(*k)) {
                             save_confirmed = true;
                             emit(
-                                format!(
-                                    "[alloy-agent] stop: world save confirmed ({})",
-                                    save_keywords
-                                        .iter()
-                                        .find(|k| lower.contains(*k))
-                                        .unwrap_or(&"matched")
-                                ),
+                                format!("[alloy-agent] stop: world save confirmed ({keyword})"),
                                 logs.clone(),
                                 log_tx.clone(),
                             )
@@ -4871,7 +7361,14 @@ impl ProcessManager {
                         .saturating_duration_since(now)
                         .as_secs()
                         .max(1);
-                    match docker_stop_container(container_id, remaining_secs).await {
+                    let res = docker_stop_container(container_id, remaining_secs).await;
+                    self.audit.record(
+                        "docker_stop",
+                        process_id,
+                        &template_id,
+                        audit_detail(container_id, &res),
+                    );
+                    match res {
                         Ok(()) => {
                             term_sent = true;
                             emit(
@@ -4896,6 +7393,12 @@ impl ProcessManager {
                         libc::kill(-pgid, libc::SIGTERM);
                     }
                     term_sent = true;
+                    self.audit.record(
+                        "sigterm",
+                        process_id,
+                        &template_id,
+                        format!("pgid {pgid} (late)"),
+                    );
                     emit(
                         "[alloy-agent] stop: sent SIGTERM (late)".to_string(),
                         logs.clone(),
@@ -4920,7 +7423,14 @@ impl ProcessManager {
                 }
 
                 if let Some(container_id) = docker_container.as_deref() {
-                    match docker_kill_container(container_id).await {
+                    let res = docker_kill_container(container_id).await;
+                    self.audit.record(
+                        "docker_kill",
+                        process_id,
+                        &template_id,
+                        audit_detail(container_id, &res),
+                    );
+                    match res {
                         Ok(()) => {
                             killed = true;
                         }
@@ -4941,6 +7451,12 @@ impl ProcessManager {
                         libc::kill(-pgid, libc::SIGKILL);
                     }
                     killed = true;
+                    self.audit.record(
+                        "sigkill",
+                        process_id,
+                        &template_id,
+                        format!("pgid {pgid} (timeout)"),
+                    );
                 }
 
                 if killed {
@@ -4963,12 +7479,41 @@ impl ProcessManager {
             .ok_or_else(|| anyhow::anyhow!("unknown process_id: {process_id}"))
     }
 
+    /// Stops every live process concurrently, each with `timeout`, and gives up
+    /// waiting after `deadline`. Returns how many processes were asked to stop.
+    pub async fn stop_all(&self, timeout: Duration, deadline: Duration) -> usize {
+        let ids: Vec<String> = self
+            .inner
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, e)| !matches!(e.state, ProcessState::Exited | ProcessState::Failed))
+            .map(|(id, _)| id.clone())
+            .collect();
+        let stops = ids.iter().map(|id| async move {
+            if let Err(err) = self.stop(id, timeout).await {
+                tracing::warn!(process_id = %id, %err, "failed to stop process on shutdown");
+            }
+        });
+        if tokio::time::timeout(deadline, futures_util::future::join_all(stops))
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                deadline_ms = deadline.as_millis() as u64,
+                "shutdown deadline reached before every process stopped"
+            );
+        }
+        ids.len()
+    }
+
     pub async fn tail_logs(
         &self,
         process_id: &str,
         cursor: u64,
         limit: usize,
-    ) -> anyhow::Result<(Vec<String>, u64)> {
+        filter: &LogFilter,
+    ) -> anyhow::Result<(Vec<LogLine>, u64)> {
         let logs = {
             let inner = self.inner.lock().await;
             let e = inner
@@ -4978,6 +7523,6 @@ impl ProcessManager {
         };
 
         let guard = logs.lock().await;
-        Ok(guard.tail_after(cursor, limit))
+        Ok(guard.tail_filtered(cursor, limit, filter))
     }
 }
@@ -1,12 +1,17 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, VecDeque},
+    path::Path,
     sync::OnceLock,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use alloy_process::{FailCategory, FailureReason};
+
 const DEFAULT_LOG_MAX_LINES: usize = 1000;
 const DEFAULT_LOG_FILE_MAX_BYTES: u64 = 10 * 1024 * 1024; // 10 MiB
 const DEFAULT_LOG_FILE_MAX_FILES: usize = 3;
+const DEFAULT_RESOURCE_HISTORY_SAMPLES: usize = 360;
+const DEFAULT_LOG_RATE_LIMIT_LINES_PER_SEC: u32 = 2000;
 
 pub(crate) fn env_usize(name: &str) -> Option<usize> {
     std::env::var(name)
@@ -18,6 +23,93 @@ pub(crate) fn env_u64(name: &str) -> Option<u64> {
     std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok())
 }
 
+pub(crate) fn env_bool(name: &str, default_value: bool) -> bool {
+    match std::env::var(name)
+        .ok()
+        .map(|v| v.trim().to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("1") | Some("true") | Some("yes") | Some("on") => true,
+        Some("0") | Some("false") | Some("no") | Some("off") => false,
+        _ => default_value,
+    }
+}
+
+/// Whether installer/SteamCMD output is mirrored into the instance console.
+pub(crate) fn install_output_enabled() -> bool {
+    env_bool("ALLOY_INSTALL_OUTPUT_TO_CONSOLE", true)
+}
+
+/// Restart budget for a frpc sidecar that dies while its server is still up.
+pub(crate) fn frpc_restart_config() -> RestartConfig {
+    RestartConfig {
+        policy: RestartPolicy::Always,
+        max_retries: env_u64("ALLOY_FRPC_RESTART_MAX")
+            .map(|v| v.clamp(0, 1000) as u32)
+            .unwrap_or(10),
+        backoff_ms: 1000,
+        backoff_max_ms: 60_000,
+    }
+}
+
+/// Whether frpc configs are checked with `frpc verify` before the tunnel starts.
+pub(crate) fn frpc_verify_enabled() -> bool {
+    env_bool("ALLOY_FRPC_VERIFY", true)
+}
+
+/// Whether resource samples sum every process in the server's process group
+/// (wrapper scripts, frpc) instead of the top-level pid. Scans `/proc`, so off
+/// by default.
+pub(crate) fn aggregate_pgid_resources() -> bool {
+    env_bool("ALLOY_AGGREGATE_PGID_RESOURCES", false)
+}
+
+/// Size bound for the shared download caches; past it the least recently used
+/// artifacts are evicted. Unset or 0 keeps everything.
+pub(crate) fn cache_max_bytes() -> Option<u64> {
+    env_u64("ALLOY_CACHE_MAX_BYTES").filter(|v| *v > 0)
+}
+
+/// Download cap for a `minecraft:import` pack given by URL.
+pub(crate) fn import_max_bytes() -> u64 {
+    env_u64("ALLOY_IMPORT_MAX_BYTES")
+        .map(|v| v.clamp(1024 * 1024, 64 * 1024 * 1024 * 1024))
+        .unwrap_or(8 * 1024 * 1024 * 1024)
+}
+
+/// Largest single file the `config_overrides` param may write.
+pub(crate) fn config_override_max_bytes() -> u64 {
+    env_u64("ALLOY_CONFIG_OVERRIDE_MAX_BYTES")
+        .map(|v| v.clamp(1024, 16 * 1024 * 1024))
+        .unwrap_or(256 * 1024)
+}
+
+/// Bare command names a custom template may run; anything else must be an
+/// absolute path.
+pub(crate) fn custom_template_commands() -> Vec<String> {
+    std::env::var("ALLOY_CUSTOM_TEMPLATE_COMMANDS")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| {
+            v.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_else(|| {
+            ["java", "python3", "node", "dotnet"]
+                .into_iter()
+                .map(String::from)
+                .collect()
+        })
+}
+
+/// Whether servers left running by a previous agent are reattached at boot
+/// instead of being killed as orphans.
+pub(crate) fn reattach_enabled() -> bool {
+    env_bool("ALLOY_REATTACH", false)
+}
+
 pub(crate) fn log_max_lines() -> usize {
     env_usize("ALLOY_LOG_MAX_LINES")
         .map(|v| v.clamp(100, 50_000))
@@ -34,6 +126,109 @@ pub(crate) fn log_file_limits() -> (u64, usize) {
     (max_bytes, max_files)
 }
 
+/// Cap on the combined size of a console log's rotations; past it the oldest
+/// are deleted. Unset or 0 leaves only the per-file limits.
+pub(crate) fn log_total_max_bytes() -> Option<u64> {
+    env_u64("ALLOY_LOG_TOTAL_MAX_BYTES").filter(|v| *v > 0)
+}
+
+/// Codec for rotated console log segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LogCompression {
+    Gzip,
+    Zstd,
+}
+
+impl LogCompression {
+    pub(crate) const ALL: [LogCompression; 2] = [Self::Gzip, Self::Zstd];
+
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+        }
+    }
+}
+
+/// `ALLOY_LOG_COMPRESS=gzip|zstd` compresses rotated console logs older than
+/// `.1`; unset or anything else keeps them plain.
+pub(crate) fn log_compression() -> Option<LogCompression> {
+    match std::env::var("ALLOY_LOG_COMPRESS")
+        .ok()?
+        .trim()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "gzip" | "gz" => Some(LogCompression::Gzip),
+        "zstd" | "zst" => Some(LogCompression::Zstd),
+        _ => None,
+    }
+}
+
+/// Max stdout/stderr lines per second kept per process; `None` disables the limit.
+/// `ALLOY_LOG_RATE_LIMIT_LINES_PER_SEC` is still read as a fallback.
+pub(crate) fn log_rate_limit_lines_per_sec() -> Option<u32> {
+    match env_u64("ALLOY_LOG_MAX_LINES_PER_SEC")
+        .or_else(|| env_u64("ALLOY_LOG_RATE_LIMIT_LINES_PER_SEC"))
+    {
+        Some(0) => None,
+        Some(v) => Some(v.clamp(10, 1_000_000) as u32),
+        None => Some(DEFAULT_LOG_RATE_LIMIT_LINES_PER_SEC),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LineAdmit {
+    Pass,
+    /// Pass, after reporting this many lines dropped in earlier windows.
+    PassAfterDrops(u64),
+    Drop,
+}
+
+/// Fixed one-second window limiter shared by a process's output pumps.
+#[derive(Debug)]
+pub(crate) struct LineRateLimiter {
+    limit: u32,
+    window_start: Instant,
+    in_window: u32,
+    dropped: u64,
+}
+
+impl LineRateLimiter {
+    pub(crate) fn new(limit: u32) -> Self {
+        Self {
+            limit: limit.max(1),
+            window_start: Instant::now(),
+            in_window: 0,
+            dropped: 0,
+        }
+    }
+
+    pub(crate) fn admit(&mut self, now: Instant) -> LineAdmit {
+        let mut reported = 0;
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.in_window = 0;
+            reported = std::mem::take(&mut self.dropped);
+        }
+        if self.in_window >= self.limit {
+            self.dropped += 1;
+            return LineAdmit::Drop;
+        }
+        self.in_window += 1;
+        if reported > 0 {
+            LineAdmit::PassAfterDrops(reported)
+        } else {
+            LineAdmit::Pass
+        }
+    }
+
+    /// Drops not yet reported (e.g. when the output stream ends mid-flood).
+    pub(crate) fn take_dropped(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum RestartPolicy {
     Off,
@@ -68,6 +263,28 @@ pub(crate) fn format_error_chain(err: &anyhow::Error) -> String {
     }
 }
 
+/// Classifies a failed start from the error payload code so the UI can offer
+/// the matching remediation.
+pub(crate) fn fail_category(err: &anyhow::Error) -> FailCategory {
+    let Some(payload) = crate::error_payload::decode(err) else {
+        return FailCategory::Unknown;
+    };
+    let port_field = payload
+        .field_errors
+        .as_ref()
+        .is_some_and(|f| f.keys().any(|k| k == "port" || k.ends_with("_port")));
+    match payload.code.as_str() {
+        "download_failed" => FailCategory::Download,
+        "java_major_mismatch" => FailCategory::Java,
+        "insufficient_disk" => FailCategory::Disk,
+        "install_failed" => FailCategory::Install,
+        "spawn_failed" => FailCategory::Spawn,
+        "missing_dependency" => FailCategory::Dependency,
+        "invalid_param" if port_field => FailCategory::Port,
+        _ => FailCategory::Unknown,
+    }
+}
+
 pub(crate) fn parse_restart_config(params: &BTreeMap<String, String>) -> RestartConfig {
     let policy = match params
         .get("restart_policy")
@@ -118,6 +335,152 @@ pub(crate) fn early_exit_threshold() -> Duration {
     )
 }
 
+const MINECRAFT_WORLD_CORRUPTION_PATTERNS: &[&str] = &[
+    "failed to load level",
+    "failed to load world",
+    "exception reading ./world",
+    "failed to read level.dat",
+    "couldn't load chunk",
+    "level.dat is corrupt",
+    "is missing level data",
+];
+
+const TERRARIA_WORLD_CORRUPTION_PATTERNS: &[&str] = &[
+    "load failed!",
+    "world file is corrupt",
+    "error loading world",
+    "endofstreamexception",
+];
+
+const MINECRAFT_PORT_IN_USE_PATTERNS: &[&str] =
+    &["failed to bind to port", "address already in use"];
+
+const TERRARIA_PORT_IN_USE_PATTERNS: &[&str] = &[
+    "tried to run two servers on the same pc",
+    "address already in use",
+];
+
+fn any_line_matches(lines: &[String], patterns: &[&str]) -> bool {
+    lines.iter().any(|line| {
+        let line = line.to_ascii_lowercase();
+        patterns.iter().any(|p| line.contains(p))
+    })
+}
+
+/// Looks at recent console lines of a crashed process and returns a hint when
+/// they match a known world-load failure for the template's game.
+pub(crate) fn world_corruption_hint(template_id: &str, lines: &[String]) -> Option<&'static str> {
+    let patterns = if template_id.starts_with("minecraft:") {
+        MINECRAFT_WORLD_CORRUPTION_PATTERNS
+    } else if template_id.starts_with("terraria:") {
+        TERRARIA_WORLD_CORRUPTION_PATTERNS
+    } else {
+        return None;
+    };
+    any_line_matches(lines, patterns)
+        .then_some("world may be corrupted; restore a backup of the world and start again")
+}
+
+/// [`world_corruption_hint`], offered only when `instance_dir` has a backup
+/// to restore.
+pub(crate) fn corruption_hint(
+    template_id: &str,
+    lines: &[String],
+    instance_dir: &Path,
+) -> Option<&'static str> {
+    let hint = world_corruption_hint(template_id, lines)?;
+    (!crate::backup::list_backups(instance_dir).is_empty()).then_some(hint)
+}
+
+fn port_in_use_patterns(template_id: &str) -> Option<&'static [&'static str]> {
+    if template_id.starts_with("minecraft:") {
+        Some(MINECRAFT_PORT_IN_USE_PATTERNS)
+    } else if template_id.starts_with("terraria:") {
+        Some(TERRARIA_PORT_IN_USE_PATTERNS)
+    } else {
+        None
+    }
+}
+
+/// Whether the template's game reports a failed port bind on its console.
+pub(crate) fn detects_port_in_use(template_id: &str) -> bool {
+    port_in_use_patterns(template_id).is_some()
+}
+
+/// The failure message for a server whose console shows it could not bind its
+/// port, or `None` when `lines` hold no known bind failure.
+pub(crate) fn port_in_use_message(
+    template_id: &str,
+    port: Option<u16>,
+    lines: &[String],
+) -> Option<String> {
+    let patterns = port_in_use_patterns(template_id)?;
+    if !any_line_matches(lines, patterns) {
+        return None;
+    }
+    let port = port
+        .map(|p| format!("port {p}"))
+        .unwrap_or("port".to_string());
+    Some(format!(
+        "{port} already in use; choose another port or 0 for auto"
+    ))
+}
+
+// Checked in order; the first reason with a matching line wins.
+const FAILURE_SIGNATURES: &[(FailureReason, &[&str])] = &[
+    (
+        FailureReason::OutOfMemory,
+        &["java.lang.outofmemoryerror", "system.outofmemoryexception"],
+    ),
+    (
+        FailureReason::MissingDependency,
+        &[
+            "missing or unsupported mandatory dependencies",
+            "incompatible mods found",
+        ],
+    ),
+    (
+        FailureReason::MixinFailure,
+        &["mixin apply failed", "mixinapplyerror"],
+    ),
+    (
+        FailureReason::Crash,
+        &[
+            "---- minecraft crash report ----",
+            "encountered an unexpected exception",
+            "unhandled exception",
+        ],
+    ),
+];
+
+fn failure_hint(reason: FailureReason) -> &'static str {
+    match reason {
+        FailureReason::OutOfMemory => "Server ran out of memory; increase memory_mb",
+        FailureReason::MissingDependency => {
+            "A mod is missing a required dependency; install it or remove the mod"
+        }
+        FailureReason::MixinFailure => {
+            "A mod failed to apply its mixins; update or remove the mod named in the log"
+        }
+        FailureReason::Crash => "Server crashed; see the console log for the crash report",
+    }
+}
+
+/// Maps the console tail of a failed process to a known cause and a hint for
+/// the user. Exit code 137 (SIGKILL, usually the OOM killer) counts as out of
+/// memory when no line matches.
+pub(crate) fn classify_failure(
+    lines: &[String],
+    exit_code: Option<i32>,
+) -> Option<(FailureReason, &'static str)> {
+    let reason = FAILURE_SIGNATURES
+        .iter()
+        .find(|(_, patterns)| any_line_matches(lines, patterns))
+        .map(|(reason, _)| *reason)
+        .or((exit_code == Some(137)).then_some(FailureReason::OutOfMemory))?;
+    Some((reason, failure_hint(reason)))
+}
+
 pub(crate) fn port_probe_timeout() -> Duration {
     Duration::from_millis(
         env_u64("ALLOY_PORT_PROBE_TIMEOUT_MS")
@@ -126,6 +489,51 @@ pub(crate) fn port_probe_timeout() -> Duration {
     )
 }
 
+/// How long a log-marker probe waits; DST generates its world before the marker.
+pub(crate) fn log_ready_timeout() -> Duration {
+    Duration::from_millis(
+        env_u64("ALLOY_LOG_READY_TIMEOUT_MS")
+            .map(|v| v.clamp(1000, 30 * 60 * 1000))
+            .unwrap_or(5 * 60 * 1000),
+    )
+}
+
+/// How often a running Minecraft server is re-pinged for its player count.
+pub(crate) fn minecraft_status_poll_interval() -> Duration {
+    Duration::from_secs(
+        env_u64("ALLOY_MINECRAFT_STATUS_POLL_SEC")
+            .map(|v| v.clamp(5, 60 * 60))
+            .unwrap_or(30),
+    )
+}
+
+/// How long a finished oneshot entry stays visible before it is forgotten.
+pub(crate) fn oneshot_forget_grace() -> Duration {
+    Duration::from_secs(
+        env_u64("ALLOY_ONESHOT_FORGET_GRACE_SEC")
+            .map(|v| v.clamp(10, 24 * 60 * 60))
+            .unwrap_or(300),
+    )
+}
+
+/// Per-process stop timeout used when the agent itself shuts down.
+pub(crate) fn shutdown_stop_timeout() -> Duration {
+    Duration::from_secs(
+        env_u64("ALLOY_SHUTDOWN_STOP_TIMEOUT_SEC")
+            .map(|v| v.clamp(1, 10 * 60))
+            .unwrap_or(30),
+    )
+}
+
+/// Upper bound on stopping every process at shutdown before the agent exits anyway.
+pub(crate) fn shutdown_deadline() -> Duration {
+    Duration::from_secs(
+        env_u64("ALLOY_SHUTDOWN_DEADLINE_SEC")
+            .map(|v| v.clamp(1, 30 * 60))
+            .unwrap_or(60),
+    )
+}
+
 pub(crate) fn resource_sample_interval() -> Duration {
     Duration::from_millis(
         env_u64("ALLOY_RESOURCE_SAMPLE_INTERVAL_MS")
@@ -134,6 +542,42 @@ pub(crate) fn resource_sample_interval() -> Duration {
     )
 }
 
+/// Resource samples kept per process. A sample is ~40 bytes, so the default
+/// (12 minutes at the 2s sampling interval) costs ~14 KiB per process.
+pub(crate) fn resource_history_samples() -> usize {
+    env_usize("ALLOY_RESOURCE_HISTORY_SAMPLES")
+        .map(|v| v.clamp(10, 100_000))
+        .unwrap_or(DEFAULT_RESOURCE_HISTORY_SAMPLES)
+}
+
+/// Fixed-capacity sample buffer; pushing past the cap drops the oldest sample.
+#[derive(Debug)]
+pub(crate) struct SampleHistory<T> {
+    cap: usize,
+    samples: VecDeque<T>,
+}
+
+impl<T> SampleHistory<T> {
+    pub(crate) fn new(cap: usize) -> Self {
+        let cap = cap.max(1);
+        Self {
+            cap,
+            samples: VecDeque::with_capacity(cap.min(1024)),
+        }
+    }
+
+    pub(crate) fn push(&mut self, sample: T) {
+        while self.samples.len() >= self.cap {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        self.samples.iter()
+    }
+}
+
 #[cfg(target_os = "linux")]
 pub(crate) fn ticks_per_sec() -> u64 {
     static TICKS: OnceLock<u64> = OnceLock::new();
@@ -148,6 +592,23 @@ pub(crate) fn ticks_per_sec() -> u64 {
     100
 }
 
+/// Online CPU cores, read once.
+#[cfg(target_os = "linux")]
+pub(crate) fn online_cpus() -> u32 {
+    static CPUS: OnceLock<u32> = OnceLock::new();
+    *CPUS.get_or_init(|| unsafe {
+        let v = libc::sysconf(libc::_SC_NPROCESSORS_ONLN);
+        if v <= 0 { 1 } else { v as u32 }
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn online_cpus() -> u32 {
+    std::thread::available_parallelism()
+        .map(|n| n.get() as u32)
+        .unwrap_or(1)
+}
+
 #[cfg(target_os = "linux")]
 fn page_size() -> u64 {
     static PAGE: OnceLock<u64> = OnceLock::new();
@@ -162,16 +623,59 @@ fn page_size() -> u64 {
     4096
 }
 
+/// utime + stime + cutime + cstime (fields 14-17 of `/proc/<pid>/stat`). Threads
+/// are already part of utime/stime; the c* fields add reaped children, so
+/// forked helpers are counted too.
+pub(crate) fn parse_stat_cpu_ticks(stat: &str) -> Option<u64> {
+    // comm (field 2) may contain spaces and parens; fields resume after the last ')'.
+    let end = stat.rfind(')')?;
+    let rest = stat.get((end + 2)..)?;
+    let parts: Vec<&str> = rest.split_whitespace().collect();
+    let mut total: u64 = 0;
+    for i in 11..=14 {
+        total = total.saturating_add(parts.get(i)?.parse().ok()?);
+    }
+    Some(total)
+}
+
+/// Process group id (field 5 of `/proc/<pid>/stat`).
+pub(crate) fn parse_stat_pgid(stat: &str) -> Option<i32> {
+    let end = stat.rfind(')')?;
+    stat.get((end + 2)..)?
+        .split_whitespace()
+        .nth(2)?
+        .parse()
+        .ok()
+}
+
+/// Pids under `proc_root` (normally `/proc`) whose process group is `pgid`,
+/// sorted. Processes that vanish mid-scan are skipped.
+pub(crate) async fn pgid_members(proc_root: &Path, pgid: i32) -> Vec<u32> {
+    let mut out = Vec::new();
+    let Ok(mut rd) = tokio::fs::read_dir(proc_root).await else {
+        return out;
+    };
+    while let Ok(Some(entry)) = rd.next_entry().await {
+        let name = entry.file_name();
+        let Some(pid) = name.to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(stat) = tokio::fs::read_to_string(entry.path().join("stat")).await else {
+            continue;
+        };
+        if parse_stat_pgid(&stat) == Some(pgid) {
+            out.push(pid);
+        }
+    }
+    out.sort_unstable();
+    out
+}
+
 #[cfg(target_os = "linux")]
 pub(crate) async fn read_proc_cpu_ticks(pid: u32) -> Option<u64> {
     let stat_path = format!("/proc/{pid}/stat");
     let s = tokio::fs::read_to_string(stat_path).await.ok()?;
-    let end = s.rfind(')')?;
-    let rest = s.get((end + 2)..)?;
-    let parts: Vec<&str> = rest.split_whitespace().collect();
-    let utime: u64 = parts.get(11)?.parse().ok()?;
-    let stime: u64 = parts.get(12)?.parse().ok()?;
-    Some(utime.saturating_add(stime))
+    parse_stat_cpu_ticks(&s)
 }
 
 #[cfg(not(target_os = "linux"))]
@@ -1,19 +1,27 @@
-use std::{collections::BTreeMap, time::Duration};
+use std::{collections::BTreeMap, pin::Pin, time::Duration};
 
 use alloy_proto::agent_v1::process_service_server::{ProcessService, ProcessServiceServer};
 use alloy_proto::agent_v1::{
-    CacheEntry, ClearCacheRequest, ClearCacheResponse, GetCacheStatsRequest, GetCacheStatsResponse,
-    GetStatusRequest, GetStatusResponse, GetWarmTemplateProgressRequest,
-    GetWarmTemplateProgressResponse, ListProcessesRequest, ListProcessesResponse,
-    ListTemplatesRequest, ListTemplatesResponse, ProcessResources, ProcessState, ProcessStatus,
-    ProcessTemplate, StartFromTemplateRequest, StartFromTemplateResponse, StopProcessRequest,
-    StopProcessResponse, TailLogsRequest, TailLogsResponse, WarmTemplateCacheRequest,
-    WarmTemplateCacheResponse,
+    CacheCategory, CacheEntry, CancelWarmTemplateRequest, CancelWarmTemplateResponse,
+    ClearCacheRequest, ClearCacheResponse, FailCategory, FailureReason, GetCacheStatsRequest,
+    GetCacheStatsResponse, GetResourceHistoryRequest, GetResourceHistoryResponse, GetStatusRequest,
+    GetStatusResponse, GetVersionRequirementsRequest, GetVersionRequirementsResponse,
+    GetWarmTemplateProgressRequest, GetWarmTemplateProgressResponse, ListProcessesRequest,
+    ListProcessesResponse, ListTemplatesRequest, ListTemplatesResponse, LogLine, LogStream,
+    ProcessEvent, ProcessEventKind, ProcessResources, ProcessState, ProcessStatus, ProcessTemplate,
+    ProgressInfo, ReloadTemplatesRequest, ReloadTemplatesResponse, ResourceSample,
+    SendConsoleRequest, SendConsoleResponse, StartFromTemplateRequest, StartFromTemplateResponse,
+    StartPhase, StartProgressEvent, StopProcessRequest, StopProcessResponse, SubscribeStartRequest,
+    TailLogsRequest, TailLogsResponse, ValidateTemplateRequest, ValidateTemplateResponse,
+    WarmTemplateCacheRequest, WarmTemplateCacheResponse, WatchProcessEventsRequest,
 };
+use futures_util::Stream;
+use tokio::sync::broadcast::error::RecvError;
 use tonic::{Request, Response, Status};
 
-use crate::process_manager::ProcessManager;
-use crate::{minecraft_download, terraria_download};
+use crate::process_manager::{self, LogFilter, ProcessManager};
+use crate::process_manager_support::cache_max_bytes;
+use crate::{cache_gc, minecraft, process_events, start_validation, version_requirements};
 
 #[derive(Debug, Clone)]
 pub struct ProcessApi {
@@ -46,17 +54,167 @@ pub fn map_status(s: alloy_process::ProcessStatus) -> ProcessStatus {
         exit_code: s.exit_code.unwrap_or_default(),
         has_exit_code: s.exit_code.is_some(),
         message: s.message.unwrap_or_default(),
-        resources: s.resources.map(|r| ProcessResources {
-            cpu_percent_x100: r.cpu_percent_x100,
-            rss_bytes: r.rss_bytes,
-            read_bytes: r.read_bytes,
-            write_bytes: r.write_bytes,
+        resources: s.resources.map(map_resources),
+        fail_category: s.fail_category.map(map_fail_category).unwrap_or_default() as i32,
+        failure_reason: s.failure_reason.map(map_failure_reason).unwrap_or_default() as i32,
+        failure_hint: s.failure_hint.unwrap_or_default(),
+        players_online: s.players.map(|p| p.online).unwrap_or_default(),
+        max_players: s.players.map(|p| p.max).unwrap_or_default(),
+        has_players: s.players.is_some(),
+        progress: s.progress.map(|p| ProgressInfo {
+            bytes_done: p.bytes_done,
+            bytes_total: p.bytes_total,
+            phase: p.phase,
         }),
     }
 }
 
+fn map_resources(r: alloy_process::ProcessResources) -> ProcessResources {
+    ProcessResources {
+        cpu_percent_x100: r.cpu_percent_x100,
+        cpu_percent_normalized_x100: r.cpu_percent_normalized_x100,
+        rss_bytes: r.rss_bytes,
+        read_bytes: r.read_bytes,
+        write_bytes: r.write_bytes,
+    }
+}
+
+fn map_log_line(l: process_manager::LogLine) -> LogLine {
+    let stream = match l.stream {
+        process_manager::LogStream::Stdout => LogStream::Stdout,
+        process_manager::LogStream::Stderr => LogStream::Stderr,
+        process_manager::LogStream::Agent => LogStream::Agent,
+    };
+    LogLine {
+        seq: l.seq,
+        stream: stream as i32,
+        text: l.text,
+    }
+}
+
+fn map_fail_category(c: alloy_process::FailCategory) -> FailCategory {
+    match c {
+        alloy_process::FailCategory::Download => FailCategory::Download,
+        alloy_process::FailCategory::Java => FailCategory::Java,
+        alloy_process::FailCategory::Disk => FailCategory::Disk,
+        alloy_process::FailCategory::Port => FailCategory::Port,
+        alloy_process::FailCategory::Install => FailCategory::Install,
+        alloy_process::FailCategory::Spawn => FailCategory::Spawn,
+        alloy_process::FailCategory::Dependency => FailCategory::Dependency,
+        alloy_process::FailCategory::Unknown => FailCategory::Unknown,
+    }
+}
+
+fn map_failure_reason(r: alloy_process::FailureReason) -> FailureReason {
+    match r {
+        alloy_process::FailureReason::OutOfMemory => FailureReason::OutOfMemory,
+        alloy_process::FailureReason::MixinFailure => FailureReason::MixinFailure,
+        alloy_process::FailureReason::MissingDependency => FailureReason::MissingDependency,
+        alloy_process::FailureReason::Crash => FailureReason::Crash,
+    }
+}
+
+fn map_start_phase(phase: process_events::StartPhase) -> StartPhase {
+    match phase {
+        process_events::StartPhase::Preparing => StartPhase::Preparing,
+        process_events::StartPhase::Downloading => StartPhase::Downloading,
+        process_events::StartPhase::Installing => StartPhase::Installing,
+        process_events::StartPhase::Launching => StartPhase::Launching,
+        process_events::StartPhase::WaitingForReady => StartPhase::WaitingForReady,
+        process_events::StartPhase::Running => StartPhase::Running,
+        process_events::StartPhase::Failed => StartPhase::Failed,
+    }
+}
+
+fn start_event(
+    phase: process_events::StartPhase,
+    percent: Option<u32>,
+    message: String,
+    at_unix_ms: u64,
+) -> StartProgressEvent {
+    StartProgressEvent {
+        phase: map_start_phase(phase) as i32,
+        percent: percent.unwrap_or_default(),
+        has_percent: percent.is_some(),
+        message,
+        at_unix_ms,
+    }
+}
+
+/// How a start ended once the process left `Starting`; `None` while it has not.
+fn settled_start(
+    state: alloy_process::ProcessState,
+    message: Option<String>,
+) -> Option<(process_events::StartPhase, String)> {
+    use process_events::StartPhase::{Failed, Running};
+    match state {
+        alloy_process::ProcessState::Starting => None,
+        alloy_process::ProcessState::Running => Some((Running, "running".to_string())),
+        alloy_process::ProcessState::Stopping => Some((Failed, "stopped during start".to_string())),
+        alloy_process::ProcessState::Exited | alloy_process::ProcessState::Failed => Some((
+            Failed,
+            message.unwrap_or_else(|| "process exited during start".to_string()),
+        )),
+    }
+}
+
+type StartEventStream = Pin<Box<dyn Stream<Item = Result<StartProgressEvent, Status>> + Send>>;
+
+fn cache_entry(item: &cache_gc::CacheItem) -> CacheEntry {
+    CacheEntry {
+        key: item.key.clone(),
+        path: item.path.display().to_string(),
+        size_bytes: item.size_bytes,
+        last_used_unix_ms: item.last_used_unix_ms,
+        in_use: item.in_use,
+    }
+}
+
+fn map_process_event(e: process_events::ProcessEvent) -> ProcessEvent {
+    let mut out = ProcessEvent {
+        process_id: e.process_id,
+        at_unix_ms: e.at_unix_ms,
+        ..Default::default()
+    };
+    match e.kind {
+        process_events::ProcessEventKind::StateChanged(state) => {
+            out.kind = ProcessEventKind::StateChanged as i32;
+            out.state = map_state(state) as i32;
+        }
+        process_events::ProcessEventKind::ResourcesSampled(r) => {
+            out.kind = ProcessEventKind::ResourcesSampled as i32;
+            out.resources = Some(map_resources(r));
+        }
+        process_events::ProcessEventKind::RestartScheduled { attempt, delay_ms } => {
+            out.kind = ProcessEventKind::RestartScheduled as i32;
+            out.restart_attempt = attempt;
+            out.restart_delay_ms = delay_ms;
+        }
+        process_events::ProcessEventKind::Exited { state, exit_code } => {
+            out.kind = ProcessEventKind::Exited as i32;
+            out.state = map_state(state) as i32;
+            out.exit_code = exit_code.unwrap_or_default();
+            out.has_exit_code = exit_code.is_some();
+        }
+        process_events::ProcessEventKind::StartProgress {
+            phase,
+            percent,
+            message,
+        } => {
+            out.kind = ProcessEventKind::StartProgress as i32;
+            out.start = Some(start_event(phase, percent, message, e.at_unix_ms));
+        }
+    }
+    out
+}
+
+type ProcessEventStream = Pin<Box<dyn Stream<Item = Result<ProcessEvent, Status>> + Send>>;
+
 #[tonic::async_trait]
 impl ProcessService for ProcessApi {
+    type SubscribeStartStream = StartEventStream;
+    type WatchProcessEventsStream = ProcessEventStream;
+
     async fn list_templates(
         &self,
         _request: Request<ListTemplatesRequest>,
@@ -76,12 +234,33 @@ impl ProcessService for ProcessApi {
         Ok(Response::new(ListTemplatesResponse { templates }))
     }
 
+    async fn reload_templates(
+        &self,
+        _request: Request<ReloadTemplatesRequest>,
+    ) -> Result<Response<ReloadTemplatesResponse>, Status> {
+        let report = tokio::task::spawn_blocking(crate::custom_templates::reload)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        for err in &report.errors {
+            tracing::warn!(error = %err, "custom template rejected");
+        }
+        Ok(Response::new(ReloadTemplatesResponse {
+            loaded: report.loaded,
+            errors: report.errors,
+        }))
+    }
+
     async fn start_from_template(
         &self,
         request: Request<StartFromTemplateRequest>,
     ) -> Result<Response<StartFromTemplateResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        crate::update_drain::ensure_accepting_starts()?;
         let req = request.into_inner();
         let params: BTreeMap<String, String> = req.params.into_iter().collect();
+        crate::node_defaults::save(&req.node_default_params)
+            .await
+            .map_err(|e| Status::internal(format!("failed to store node defaults: {e}")))?;
         let status = self
             .manager
             .start_from_template(&req.template_id, params)
@@ -96,290 +275,36 @@ impl ProcessService for ProcessApi {
         &self,
         request: Request<WarmTemplateCacheRequest>,
     ) -> Result<Response<WarmTemplateCacheResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         let req = request.into_inner();
         let params: BTreeMap<String, String> = req.params.into_iter().collect();
-        let progress_id = req.progress_id.trim().to_string();
-
-        let progress_set = !progress_id.is_empty();
-        let report_progress = |stage: &str,
-                               downloaded_bytes: Option<u64>,
-                               total_bytes: Option<u64>,
-                               speed_bytes_per_sec: Option<u64>,
-                               message: String,
-                               done: Option<bool>| {
-            if !progress_set {
-                return;
-            }
-            crate::download_progress::update(
-                &progress_id,
-                crate::download_progress::UpdateArgs {
-                    stage: Some(stage.to_string()),
-                    downloaded_bytes,
-                    total_bytes,
-                    speed_bytes_per_sec,
-                    message: Some(message),
-                    done,
-                },
-            );
-        };
-
-        let message = match req.template_id.as_str() {
-            "minecraft:vanilla" => {
-                let version = params
-                    .get("version")
-                    .map(|s| s.trim())
-                    .filter(|s| !s.is_empty())
-                    .unwrap_or("latest_release");
-
-                if progress_set {
-                    crate::download_progress::start(
-                        &progress_id,
-                        "resolve",
-                        format!("resolving minecraft version {version}..."),
-                        None,
-                    );
-                }
+        let progress = crate::warm_cache::WarmProgress::new(&req.progress_id);
 
-                let resolved = minecraft_download::resolve_server_jar(version)
-                    .await
-                    .map_err(|e| {
-                        if progress_set {
-                            crate::download_progress::fail(
-                                &progress_id,
-                                format!("failed to resolve minecraft server jar: {e}"),
-                            );
-                        }
-                        Status::invalid_argument(crate::error_payload::encode(
-                            "download_failed",
-                            format!("failed to resolve minecraft server jar: {e}"),
-                            None,
-                            Some(
-                                "Check network connectivity to Mojang piston-meta endpoints."
-                                    .to_string(),
-                            ),
-                        ))
-                    })?;
-
-                report_progress(
-                    "download",
-                    Some(0),
-                    Some(resolved.size),
-                    Some(0),
-                    format!("downloading minecraft {}...", resolved.version_id),
-                    Some(false),
-                );
-
-                let mut last_downloaded = 0u64;
-                let mut last_total = resolved.size;
-                let mut last_speed = 0u64;
-                let jar_path = minecraft_download::ensure_server_jar_with_progress(
-                    &resolved,
-                    Some(|downloaded: u64, total: u64, speed: u64| {
-                        last_downloaded = downloaded;
-                        last_total = total.max(resolved.size);
-                        last_speed = speed;
-                        report_progress(
-                            "download",
-                            Some(downloaded),
-                            Some(total.max(resolved.size)),
-                            Some(speed),
-                            format!(
-                                "downloading minecraft {} ({downloaded}/{})",
-                                resolved.version_id,
-                                total.max(resolved.size)
-                            ),
-                            Some(false),
-                        );
-                    }),
-                )
-                    .await
-                    .map_err(|e| {
-                        if progress_set {
-                            crate::download_progress::fail(
-                                &progress_id,
-                                format!("failed to download minecraft server jar: {e}"),
-                            );
-                        }
-                        Status::internal(crate::error_payload::encode(
-                            "download_failed",
-                            format!("failed to download minecraft server jar: {e}"),
-                            None,
-                            Some("Try again; if it persists, clear cache and retry.".to_string()),
-                        ))
-                    })?;
-
-                report_progress(
-                    "verify",
-                    Some(last_downloaded.max(resolved.size)),
-                    Some(last_total.max(resolved.size)),
-                    Some(last_speed),
-                    format!("verifying minecraft {} integrity...", resolved.version_id),
-                    Some(false),
-                );
-
-                if progress_set {
-                    crate::download_progress::finish(
-                        &progress_id,
-                        format!("minecraft {} ready", resolved.version_id),
-                        last_downloaded.max(resolved.size),
-                        last_total.max(resolved.size),
-                        last_speed,
-                    );
-                }
-
-                format!(
-                    "minecraft cache warmed: version={} sha1={} path={}",
-                    resolved.version_id,
-                    resolved.sha1,
-                    jar_path.display()
-                )
-            }
-            "terraria:vanilla" => {
-                let version = params
-                    .get("version")
-                    .map(|s| s.trim())
-                    .filter(|s| !s.is_empty())
-                    .unwrap_or("1453");
-
-                if progress_set {
-                    crate::download_progress::start(
-                        &progress_id,
-                        "resolve",
-                        format!("resolving terraria version {version}..."),
-                        None,
-                    );
-                }
-
-                let resolved = terraria_download::resolve_server_zip(version).map_err(|e| {
-                    if progress_set {
-                        crate::download_progress::fail(
-                            &progress_id,
-                            format!("failed to resolve terraria server zip: {e}"),
-                        );
-                    }
-                    Status::invalid_argument(crate::error_payload::encode(
-                        "download_failed",
-                        format!("failed to resolve terraria server zip: {e}"),
-                        None,
-                        Some("Check network connectivity, then try again.".to_string()),
-                    ))
-                })?;
-
-                report_progress(
-                    "download",
-                    Some(0),
-                    Some(0),
-                    Some(0),
-                    format!("downloading terraria {}...", resolved.version_id),
-                    Some(false),
-                );
-
-                let mut last_downloaded = 0u64;
-                let mut last_total = 0u64;
-                let mut last_speed = 0u64;
-                let zip_path = terraria_download::ensure_server_zip_with_progress(
-                    &resolved,
-                    Some(|downloaded: u64, total: u64, speed: u64| {
-                        last_downloaded = downloaded;
-                        last_total = total.max(downloaded);
-                        last_speed = speed;
-                        report_progress(
-                            "download",
-                            Some(downloaded),
-                            Some(total.max(downloaded)),
-                            Some(speed),
-                            format!(
-                                "downloading terraria {} ({downloaded}/{})",
-                                resolved.version_id,
-                                total.max(downloaded)
-                            ),
-                            Some(false),
-                        );
-                    }),
-                )
-                    .await
-                    .map_err(|e| {
-                        if progress_set {
-                            crate::download_progress::fail(
-                                &progress_id,
-                                format!("failed to download terraria server zip: {e}"),
-                            );
-                        }
-                        Status::internal(crate::error_payload::encode(
-                            "download_failed",
-                            format!("failed to download terraria server zip: {e}"),
-                            None,
-                            Some("Try again; if it persists, clear cache and retry.".to_string()),
-                        ))
-                    })?;
-
-                report_progress(
-                    "extract",
-                    Some(last_downloaded),
-                    Some(last_total.max(last_downloaded)),
-                    Some(last_speed),
-                    format!("extracting terraria {} files...", resolved.version_id),
-                    Some(false),
-                );
-
-                let extracted =
-                    terraria_download::extract_linux_x64_to_cache(&zip_path, &resolved.version_id)
-                        .map_err(|e| {
-                            if progress_set {
-                                crate::download_progress::fail(
-                                    &progress_id,
-                                    format!("failed to extract terraria server: {e}"),
-                                );
-                            }
-                            Status::internal(crate::error_payload::encode(
-                                "download_failed",
-                                format!("failed to extract terraria server: {e}"),
-                                None,
-                                Some("Clear cache and retry extraction.".to_string()),
-                            ))
-                        })?;
-
-                if progress_set {
-                    crate::download_progress::finish(
-                        &progress_id,
-                        format!("terraria {} ready", resolved.version_id),
-                        last_downloaded,
-                        last_total.max(last_downloaded),
-                        last_speed,
-                    );
-                }
-
-                format!(
-                    "terraria cache warmed: version={} zip_path={} server_root={}",
-                    resolved.version_id,
-                    zip_path.display(),
-                    extracted.server_root.display()
-                )
-            }
-            "demo:sleep" => {
-                if progress_set {
-                    crate::download_progress::start(
-                        &progress_id,
-                        "ready",
-                        "no cache needed for demo:sleep",
-                        Some(0),
-                    );
-                    crate::download_progress::finish(
-                        &progress_id,
-                        "no cache needed for demo:sleep",
-                        0,
-                        0,
-                        0,
-                    );
-                }
-                "no cache needed for demo:sleep".to_string()
-            }
-            _ => return Err(Status::invalid_argument("unknown template_id")),
-        };
+        let warm = self
+            .manager
+            .warm_cache(&req.template_id, &params, &progress);
+        let message = crate::warm_cache::cancellable(&progress, warm)
+            .await
+            .map_err(|e| match crate::error_payload::decode(&e).map(|p| p.code) {
+                Some(code) if code == "invalid_param" => Status::invalid_argument(e.to_string()),
+                Some(code) if code == "canceled" => Status::cancelled(e.to_string()),
+                _ => Status::internal(e.to_string()),
+            })?;
 
         Ok(Response::new(WarmTemplateCacheResponse {
             ok: true,
             message,
+            progress_id: progress.id().to_string(),
+        }))
+    }
+
+    async fn cancel_warm_template(
+        &self,
+        request: Request<CancelWarmTemplateRequest>,
+    ) -> Result<Response<CancelWarmTemplateResponse>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(CancelWarmTemplateResponse {
+            canceled: crate::warm_cache::cancel(&req.progress_id),
         }))
     }
 
@@ -432,336 +357,85 @@ impl ProcessService for ProcessApi {
         &self,
         _request: Request<GetCacheStatsRequest>,
     ) -> Result<Response<GetCacheStatsResponse>, Status> {
-        #[derive(Debug, Clone, serde::Deserialize)]
-        struct MinecraftJarMeta {
-            version_id: Option<String>,
-        }
-
-        fn read_last_used_marker(dir: &std::path::Path) -> u64 {
-            let p = dir.join(".last_used");
-            let raw = std::fs::read_to_string(p).unwrap_or_default();
-            raw.trim().parse::<u64>().unwrap_or(0)
-        }
-
-        fn read_minecraft_version_id(entry_dir: &std::path::Path) -> Option<String> {
-            let p = entry_dir.join("meta.json");
-            let bytes = std::fs::read(p).ok()?;
-            let meta: MinecraftJarMeta = serde_json::from_slice(&bytes).ok()?;
-            meta.version_id
-                .map(|v| v.trim().to_string())
-                .filter(|v| !v.is_empty())
-        }
-
-        fn modified_unix_ms(path: &std::path::Path) -> u64 {
-            let meta = match std::fs::symlink_metadata(path) {
-                Ok(m) => m,
-                Err(_) => return 0,
-            };
-            meta.modified()
-                .ok()
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_millis() as u64)
-                .unwrap_or(0)
-        }
-
-        fn dir_stats(path: &std::path::Path) -> (u64, u64) {
-            fn walk(p: &std::path::Path, size: &mut u64, last_ms: &mut u64) {
-                let meta = match std::fs::symlink_metadata(p) {
-                    Ok(m) => m,
-                    Err(_) => return,
-                };
-
-                let modified_ms = meta
-                    .modified()
-                    .ok()
-                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                    .map(|d| d.as_millis() as u64)
-                    .unwrap_or(0);
-                *last_ms = (*last_ms).max(modified_ms);
-
-                if meta.file_type().is_symlink() {
-                    *size = size.saturating_add(meta.len());
-                    return;
-                }
-                if meta.is_file() {
-                    *size = size.saturating_add(meta.len());
-                    return;
-                }
-                if !meta.is_dir() {
-                    return;
-                }
-
-                let rd = match std::fs::read_dir(p) {
-                    Ok(v) => v,
-                    Err(_) => return,
-                };
-                for e in rd.flatten() {
-                    walk(&e.path(), size, last_ms);
-                }
-            }
-
-            if !path.exists() {
-                return (0, 0);
+        let in_use = self.manager.cache_in_use().await;
+        let roots = cache_gc::CacheRoots::new(&minecraft::data_root());
+        let (entries, categories) = tokio::task::spawn_blocking(move || {
+            let mut entries = Vec::new();
+            let mut categories = Vec::new();
+            for category in cache_gc::CATEGORIES {
+                let items = cache_gc::scan_category(&roots, category, &in_use);
+                let size_bytes = items.iter().map(|i| i.size_bytes).sum::<u64>();
+                entries.push(CacheEntry {
+                    key: category.to_string(),
+                    path: roots.root(category).display().to_string(),
+                    size_bytes,
+                    last_used_unix_ms: items.iter().map(|i| i.last_used_unix_ms).max().unwrap_or(0),
+                    in_use: items.iter().any(|i| i.in_use),
+                });
+                categories.push(CacheCategory {
+                    name: category.to_string(),
+                    size_bytes,
+                    item_count: items.len() as u32,
+                });
+                entries.extend(items.iter().map(cache_entry));
             }
-
-            let mut size = 0u64;
-            let mut last_ms = 0u64;
-            walk(path, &mut size, &mut last_ms);
-            (size, last_ms)
-        }
-
-        let entries = tokio::task::spawn_blocking(|| {
-            let mut out: Vec<(String, std::path::PathBuf, u64, u64)> = Vec::new();
-
-            // Minecraft: per-JAR entries (key includes version + sha1).
-            let mc_root = minecraft_download::cache_dir();
-            let mut mc_entries: Vec<(String, std::path::PathBuf, u64, u64)> = Vec::new();
-            if let Ok(rd) = std::fs::read_dir(&mc_root) {
-                for entry in rd.flatten() {
-                    let path = entry.path();
-                    let Ok(ft) = entry.file_type() else {
-                        continue;
-                    };
-                    if !ft.is_dir() {
-                        continue;
-                    }
-                    let sha1 = entry.file_name().to_string_lossy().to_string();
-                    if sha1.len() != 40 || !sha1.chars().all(|c| c.is_ascii_hexdigit()) {
-                        continue;
-                    }
-                    let jar = path.join("server.jar");
-                    if !jar.is_file() {
-                        continue;
-                    }
-
-                    let version =
-                        read_minecraft_version_id(&path).unwrap_or_else(|| "unknown".to_string());
-                    let (size, _) = dir_stats(&path);
-                    let last_used = read_last_used_marker(&path).max(modified_unix_ms(&jar));
-                    let key = format!("minecraft:vanilla@{version}#{sha1}");
-                    mc_entries.push((key, path, size, last_used));
-                }
-            }
-            mc_entries.sort_by(|a, b| b.3.cmp(&a.3).then_with(|| a.0.cmp(&b.0)));
-            let mc_size = mc_entries.iter().map(|e| e.2).sum::<u64>();
-            let mc_last = mc_entries.iter().map(|e| e.3).max().unwrap_or(0);
-            out.push((
-                "minecraft:vanilla".to_string(),
-                mc_root.clone(),
-                mc_size,
-                mc_last,
-            ));
-            out.extend(mc_entries);
-
-            // Terraria: per-version entries (key includes version).
-            let tr_root = terraria_download::cache_dir();
-            let mut tr_entries: Vec<(String, std::path::PathBuf, u64, u64)> = Vec::new();
-            if let Ok(rd) = std::fs::read_dir(&tr_root) {
-                for entry in rd.flatten() {
-                    let path = entry.path();
-                    let Ok(ft) = entry.file_type() else {
-                        continue;
-                    };
-                    if !ft.is_dir() {
-                        continue;
-                    }
-                    let version = entry.file_name().to_string_lossy().to_string();
-                    if version.is_empty() {
-                        continue;
-                    }
-                    if !version.chars().all(|c| c.is_ascii_digit()) {
-                        continue;
-                    }
-
-                    let (size, last_modified) = dir_stats(&path);
-                    let last_used = read_last_used_marker(&path).max(last_modified);
-                    let key = format!("terraria:vanilla@{version}");
-                    tr_entries.push((key, path, size, last_used));
-                }
-            }
-            tr_entries.sort_by(|a, b| b.3.cmp(&a.3).then_with(|| a.0.cmp(&b.0)));
-            let tr_size = tr_entries.iter().map(|e| e.2).sum::<u64>();
-            let tr_last = tr_entries.iter().map(|e| e.3).max().unwrap_or(0);
-            out.push((
-                "terraria:vanilla".to_string(),
-                tr_root.clone(),
-                tr_size,
-                tr_last,
-            ));
-            out.extend(tr_entries);
-
-            out
+            (entries, categories)
         })
         .await
-        .map_err(|e| Status::internal(format!("cache stats task failed: {e}")))?
-        .into_iter()
-        .map(|(key, path, size_bytes, last_used_unix_ms)| CacheEntry {
-            key,
-            path: path.display().to_string(),
-            size_bytes,
-            last_used_unix_ms,
-        })
-        .collect();
+        .map_err(|e| Status::internal(format!("cache stats task failed: {e}")))?;
 
-        Ok(Response::new(GetCacheStatsResponse { entries }))
+        Ok(Response::new(GetCacheStatsResponse {
+            entries,
+            categories,
+            max_bytes: cache_max_bytes().unwrap_or(0),
+        }))
     }
 
     async fn clear_cache(
         &self,
         request: Request<ClearCacheRequest>,
     ) -> Result<Response<ClearCacheResponse>, Status> {
-        fn template_id_for_cache_key(key: &str) -> Option<&'static str> {
-            if key == "minecraft:vanilla" || key.starts_with("minecraft:vanilla@") {
-                return Some("minecraft:vanilla");
-            }
-            if key == "terraria:vanilla" || key.starts_with("terraria:vanilla@") {
-                return Some("terraria:vanilla");
-            }
-            None
-        }
-
-        fn validate_sha1_hex(s: &str) -> bool {
-            s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit())
-        }
-
+        crate::read_only::ensure_writable()?;
         let req = request.into_inner();
         let keys: Vec<String> = if req.keys.is_empty() {
-            vec![
-                "minecraft:vanilla".to_string(),
-                "terraria:vanilla".to_string(),
-            ]
+            cache_gc::CATEGORIES.iter().map(|c| c.to_string()).collect()
         } else {
             req.keys
         };
-
-        let running = self
-            .manager
-            .list_processes()
-            .await
-            .into_iter()
-            .filter(|p| {
-                matches!(
-                    p.state,
-                    alloy_process::ProcessState::Running
-                        | alloy_process::ProcessState::Starting
-                        | alloy_process::ProcessState::Stopping
-                )
-            })
-            .map(|p| p.template_id.0)
-            .collect::<std::collections::HashSet<_>>();
-
-        for key in &keys {
-            let Some(template_id) = template_id_for_cache_key(key) else {
-                return Err(Status::invalid_argument(format!(
-                    "unknown cache key: {key}"
-                )));
-            };
-            if running.contains(template_id) {
-                return Err(Status::failed_precondition(format!(
-                    "cannot clear cache while process is running: {template_id}"
-                )));
-            }
+        if let Some(key) = keys.iter().find(|k| !cache_gc::is_valid_key(k)) {
+            return Err(Status::invalid_argument(format!(
+                "unknown cache key: {key}"
+            )));
         }
 
-        let mut freed_bytes = 0u64;
-        let mut cleared = Vec::new();
-
-        for key in keys {
-            let dir = if key == "minecraft:vanilla" {
-                minecraft_download::cache_dir()
-            } else if let Some(rest) = key.strip_prefix("minecraft:vanilla@") {
-                let (_, sha1) = rest.split_once('#').ok_or_else(|| {
-                    Status::invalid_argument(format!("invalid minecraft cache key: {key}"))
-                })?;
-                if !validate_sha1_hex(sha1) {
-                    return Err(Status::invalid_argument(format!(
-                        "invalid minecraft cache sha1: {sha1}"
-                    )));
+        let in_use = self.manager.cache_in_use().await;
+        let roots = cache_gc::CacheRoots::new(&minecraft::data_root());
+        let (cleared, skipped) = tokio::task::spawn_blocking(move || {
+            let mut cleared = Vec::new();
+            let mut skipped = Vec::new();
+            for item in cache_gc::scan(&roots, &in_use) {
+                if !keys.iter().any(|k| cache_gc::matches_key(&item, k)) {
+                    continue;
                 }
-                minecraft_download::cache_dir().join(sha1)
-            } else if key == "terraria:vanilla" {
-                terraria_download::cache_dir()
-            } else if let Some(version) = key.strip_prefix("terraria:vanilla@") {
-                if version.is_empty() || !version.chars().all(|c| c.is_ascii_digit()) {
-                    return Err(Status::invalid_argument(format!(
-                        "invalid terraria cache key: {key}"
-                    )));
-                }
-                terraria_download::cache_dir().join(version)
-            } else {
-                return Err(Status::invalid_argument(format!(
-                    "unknown cache key: {key}"
-                )));
-            };
-
-            let (size_bytes, last_used_unix_ms) = tokio::task::spawn_blocking({
-                let dir = dir.clone();
-                move || {
-                    let (size, last) = if dir.exists() {
-                        // Use the same stats logic as get_cache_stats.
-                        let mut size = 0u64;
-                        let mut last = 0u64;
-                        fn walk(p: &std::path::Path, size: &mut u64, last_ms: &mut u64) {
-                            let meta = match std::fs::symlink_metadata(p) {
-                                Ok(m) => m,
-                                Err(_) => return,
-                            };
-                            let modified_ms = meta
-                                .modified()
-                                .ok()
-                                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                                .map(|d| d.as_millis() as u64)
-                                .unwrap_or(0);
-                            *last_ms = (*last_ms).max(modified_ms);
-                            if meta.file_type().is_symlink() {
-                                *size = size.saturating_add(meta.len());
-                                return;
-                            }
-                            if meta.is_file() {
-                                *size = size.saturating_add(meta.len());
-                                return;
-                            }
-                            if !meta.is_dir() {
-                                return;
-                            }
-                            let rd = match std::fs::read_dir(p) {
-                                Ok(v) => v,
-                                Err(_) => return,
-                            };
-                            for e in rd.flatten() {
-                                walk(&e.path(), size, last_ms);
-                            }
-                        }
-                        walk(&dir, &mut size, &mut last);
-                        (size, last)
-                    } else {
-                        (0, 0)
-                    };
-                    (size, last)
+                if item.in_use {
+                    skipped.push(item);
+                    continue;
                 }
-            })
-            .await
-            .unwrap_or((0, 0));
-
-            if dir.exists() {
-                tokio::fs::remove_dir_all(&dir)
-                    .await
-                    .map_err(|e| Status::internal(format!("failed to clear cache: {e}")))?;
+                cache_gc::remove_item(&item)
+                    .map_err(|e| format!("failed to clear cache {}: {e}", item.key))?;
+                cleared.push(item);
             }
-
-            freed_bytes = freed_bytes.saturating_add(size_bytes);
-            cleared.push(CacheEntry {
-                key: key.clone(),
-                path: dir.display().to_string(),
-                size_bytes,
-                last_used_unix_ms,
-            });
-        }
+            Ok::<_, String>((cleared, skipped))
+        })
+        .await
+        .map_err(|e| Status::internal(format!("clear cache task failed: {e}")))?
+        .map_err(Status::internal)?;
 
         Ok(Response::new(ClearCacheResponse {
             ok: true,
-            freed_bytes,
-            cleared,
+            freed_bytes: cleared.iter().map(|i| i.size_bytes).sum(),
+            cleared: cleared.iter().map(cache_entry).collect(),
+            skipped: skipped.iter().map(cache_entry).collect(),
         }))
     }
 
@@ -769,6 +443,7 @@ impl ProcessService for ProcessApi {
         &self,
         request: Request<StopProcessRequest>,
     ) -> Result<Response<StopProcessResponse>, Status> {
+        crate::read_only::ensure_writable()?;
         let req = request.into_inner();
         let timeout = if req.timeout_ms == 0 {
             Duration::from_secs(30)
@@ -786,6 +461,23 @@ impl ProcessService for ProcessApi {
         }))
     }
 
+    async fn send_console(
+        &self,
+        request: Request<SendConsoleRequest>,
+    ) -> Result<Response<SendConsoleResponse>, Status> {
+        crate::read_only::ensure_writable()?;
+        let req = request.into_inner();
+        if req.command.trim().is_empty() {
+            return Err(Status::invalid_argument("command is required"));
+        }
+
+        self.manager
+            .send_stdin(&req.process_id, req.command)
+            .await
+            .map_err(|e| Status::failed_precondition(e.to_string()))?;
+        Ok(Response::new(SendConsoleResponse { ok: true }))
+    }
+
     async fn list_processes(
         &self,
         _request: Request<ListProcessesRequest>,
@@ -805,13 +497,47 @@ impl ProcessService for ProcessApi {
         request: Request<GetStatusRequest>,
     ) -> Result<Response<GetStatusResponse>, Status> {
         let req = request.into_inner();
-        let status = self
-            .manager
-            .get_status(&req.process_id)
+        if let Some(status) = self.manager.get_status(&req.process_id).await {
+            return Ok(Response::new(GetStatusResponse {
+                status: Some(map_status(status)),
+            }));
+        }
+
+        // Created/stopped processes survive agent restarts on disk; report them
+        // as not running instead of unknown.
+        let run = crate::process_manager::read_persisted_run(&req.process_id)
             .await
             .ok_or_else(|| Status::not_found("unknown process_id"))?;
         Ok(Response::new(GetStatusResponse {
-            status: Some(map_status(status)),
+            status: Some(ProcessStatus {
+                process_id: req.process_id,
+                template_id: run.template_id,
+                state: ProcessState::NotRunning as i32,
+                message: "not running".to_string(),
+                ..Default::default()
+            }),
+        }))
+    }
+
+    async fn get_resource_history(
+        &self,
+        request: Request<GetResourceHistoryRequest>,
+    ) -> Result<Response<GetResourceHistoryResponse>, Status> {
+        let req = request.into_inner();
+        let samples = self
+            .manager
+            .resource_history(&req.process_id, req.since_unix_ms)
+            .await
+            .into_iter()
+            .map(|(at, r)| ResourceSample {
+                at_unix_ms: at,
+                resources: Some(map_resources(r)),
+            })
+            .collect();
+        Ok(Response::new(GetResourceHistoryResponse {
+            samples,
+            sample_interval_ms: crate::process_manager_support::resource_sample_interval()
+                .as_millis() as u32,
         }))
     }
 
@@ -826,15 +552,169 @@ impl ProcessService for ProcessApi {
             req.limit as usize
         };
         let cursor: u64 = req.cursor.parse().unwrap_or(0);
-        let (lines, next) = self
+        let filter = LogFilter {
+            streams: req
+                .streams()
+                .filter_map(|s| match s {
+                    LogStream::Stdout => Some(process_manager::LogStream::Stdout),
+                    LogStream::Stderr => Some(process_manager::LogStream::Stderr),
+                    LogStream::Agent => Some(process_manager::LogStream::Agent),
+                    LogStream::Unspecified => None,
+                })
+                .collect(),
+            contains: Some(req.contains).filter(|s| !s.trim().is_empty()),
+        };
+        let (entries, next) = self
             .manager
-            .tail_logs(&req.process_id, cursor, limit)
+            .tail_logs(&req.process_id, cursor, limit, &filter)
             .await
             .map_err(|e| Status::not_found(e.to_string()))?;
 
         Ok(Response::new(TailLogsResponse {
-            lines,
+            lines: entries.iter().map(|l| l.text.clone()).collect(),
             next_cursor: next.to_string(),
+            entries: entries.into_iter().map(map_log_line).collect(),
+        }))
+    }
+
+    async fn subscribe_start(
+        &self,
+        request: Request<SubscribeStartRequest>,
+    ) -> Result<Response<Self::SubscribeStartStream>, Status> {
+        let process_id = request.into_inner().process_id;
+        // Subscribe before taking the snapshot so no transition in between is lost.
+        let rx = self.manager.subscribe();
+        let status = self
+            .manager
+            .get_status(&process_id)
+            .await
+            .ok_or_else(|| Status::not_found("unknown process_id"))?;
+
+        let now = process_events::now_unix_ms();
+        let first = match settled_start(status.state, status.message.clone()) {
+            Some((phase, message)) => Some(start_event(phase, None, message, now)),
+            None => self.manager.start_phase(&process_id).await.map(|phase| {
+                let percent = status.progress.as_ref().and_then(|p| {
+                    (p.bytes_done.min(p.bytes_total) * 100)
+                        .checked_div(p.bytes_total)
+                        .map(|pct| pct as u32)
+                });
+                let message = status.message.clone().unwrap_or_default();
+                start_event(phase, percent, message, now)
+            }),
+        };
+
+        // Follows the shared lifecycle broadcast; ends after Running/Failed.
+        let state = (rx, self.manager.clone(), process_id, first, false);
+        let stream = futures_util::stream::unfold(
+            state,
+            |(mut rx, manager, id, mut pending, mut done)| async move {
+                loop {
+                    if let Some(event) = pending.take() {
+                        done = event.phase == StartPhase::Running as i32
+                            || event.phase == StartPhase::Failed as i32;
+                        return Some((Ok(event), (rx, manager, id, pending, done)));
+                    }
+                    if done {
+                        return None;
+                    }
+                    let e = match rx.recv().await {
+                        Ok(e) if e.process_id == id => e,
+                        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    };
+                    pending = match e.kind {
+                        process_events::ProcessEventKind::StartProgress {
+                            phase,
+                            percent,
+                            message,
+                        } => Some(start_event(phase, percent, message, e.at_unix_ms)),
+                        process_events::ProcessEventKind::StateChanged(state)
+                        | process_events::ProcessEventKind::Exited { state, .. } => {
+                            let message = manager.get_status(&id).await.and_then(|s| s.message);
+                            settled_start(state, message).map(|(phase, message)| {
+                                start_event(phase, None, message, e.at_unix_ms)
+                            })
+                        }
+                        _ => None,
+                    };
+                }
+            },
+        );
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn watch_process_events(
+        &self,
+        request: Request<WatchProcessEventsRequest>,
+    ) -> Result<Response<Self::WatchProcessEventsStream>, Status> {
+        let filter = request.into_inner().process_id;
+        let rx = self.manager.subscribe();
+
+        // A watcher that lags skips what it missed; the next state event resyncs it.
+        let stream = futures_util::stream::unfold((rx, filter), |(mut rx, filter)| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(e) if filter.is_empty() || e.process_id == filter => {
+                        return Some((Ok(map_process_event(e)), (rx, filter)));
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_version_requirements(
+        &self,
+        request: Request<GetVersionRequirementsRequest>,
+    ) -> Result<Response<GetVersionRequirementsResponse>, Status> {
+        let req = request.into_inner();
+        let params: BTreeMap<String, String> = req.params.into_iter().collect();
+        let reqs = version_requirements::resolve(&req.template_id, &params)
+            .await
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let runtime_java = if reqs.java_required {
+            let java = crate::process_manager::resolve_java_path(&params);
+            tokio::task::spawn_blocking(move || crate::process_manager::detect_java_major(&java))
+                .await
+                .ok()
+                .and_then(Result::ok)
+        } else {
+            None
+        };
+        let (java_satisfied, message) = version_requirements::summarize(&reqs, runtime_java);
+
+        Ok(Response::new(GetVersionRequirementsResponse {
+            java_required: reqs.java_required,
+            java_major: reqs.java_major.unwrap_or_default(),
+            minecraft_version: reqs.minecraft_version.unwrap_or_default(),
+            loader: reqs.loader.unwrap_or_default(),
+            runtime_java_major: runtime_java.unwrap_or_default(),
+            java_satisfied,
+            message,
+        }))
+    }
+
+    async fn validate_template(
+        &self,
+        request: Request<ValidateTemplateRequest>,
+    ) -> Result<Response<ValidateTemplateResponse>, Status> {
+        let req = request.into_inner();
+        let mut params: BTreeMap<String, String> = req.params.into_iter().collect();
+        crate::templates::merge_node_defaults(
+            &req.template_id,
+            &mut params,
+            &req.node_default_params,
+        );
+        let report = start_validation::validate(&req.template_id, &params).await;
+        Ok(Response::new(ValidateTemplateResponse {
+            ok: report.ok(),
+            field_errors: report.field_errors.into_iter().collect(),
+            errors: report.errors,
+            warnings: report.warnings,
         }))
     }
 }
@@ -0,0 +1,31 @@
+use tonic::Status;
+
+use crate::process_manager_support::env_bool;
+
+/// Agent-side read-only mode. Kept separate from the control plane's
+/// `ALLOY_READ_ONLY` so it still holds when the agent is reached directly.
+pub(crate) fn is_read_only() -> bool {
+    env_bool("ALLOY_AGENT_READ_ONLY", false)
+}
+
+#[derive(Debug)]
+pub(crate) struct ReadOnlyError;
+
+impl From<ReadOnlyError> for Status {
+    fn from(_: ReadOnlyError) -> Self {
+        Status::permission_denied(crate::error_payload::encode(
+            "read_only",
+            "agent is in read-only mode",
+            None,
+            Some("unset ALLOY_AGENT_READ_ONLY on the agent to allow changes".to_string()),
+        ))
+    }
+}
+
+/// Rejects mutating RPCs while the agent is read-only.
+pub(crate) fn ensure_writable() -> Result<(), ReadOnlyError> {
+    if is_read_only() {
+        return Err(ReadOnlyError);
+    }
+    Ok(())
+}
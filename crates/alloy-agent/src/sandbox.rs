@@ -1,6 +1,7 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     io,
+    ops::RangeInclusive,
     path::{Path, PathBuf},
 };
 
@@ -324,34 +325,125 @@ fn choose_mode(
     }
 }
 
-fn resolve_limits(params: &BTreeMap<String, String>) -> SandboxLimits {
-    let default_memory_mb = env_u64("ALLOY_SANDBOX_MEMORY_MB_DEFAULT")
-        .map(|v| v.clamp(256, 131_072))
-        .unwrap_or(4096);
+pub const PIDS_LIMIT_RANGE: RangeInclusive<u64> = 32..=32_768;
+pub const NOFILE_LIMIT_RANGE: RangeInclusive<u64> = 256..=1_048_576;
+pub const MEMORY_MB_RANGE: RangeInclusive<u64> = 256..=131_072;
+pub const CPU_MILLICORES_RANGE: RangeInclusive<u64> = 100..=64_000;
+
+/// Built-in (nofile, pids) defaults for templates that need something other
+/// than the node-wide defaults.
+fn template_limit_defaults(template_id: &str) -> (Option<u64>, Option<u64>) {
+    match template_id {
+        // Large modpacks open thousands of jars, configs and region files.
+        "minecraft:modrinth" | "minecraft:curseforge" | "minecraft:import" => (Some(65_536), None),
+        // Runs whatever command the caller passes; keep fork bombs contained.
+        crate::templates::ONESHOT_TEMPLATE_ID => (None, Some(64)),
+        _ => (None, None),
+    }
+}
 
-    let default_pids = env_u64("ALLOY_SANDBOX_PIDS_LIMIT_DEFAULT")
-        .map(|v| v.clamp(32, 32_768))
-        .unwrap_or(512);
+/// Default shown for the nofile/pids template params.
+pub fn default_nofile_limit(template_id: &str) -> u64 {
+    env_u64("ALLOY_SANDBOX_NOFILE_LIMIT_DEFAULT")
+        .map(|v| v.clamp(*NOFILE_LIMIT_RANGE.start(), *NOFILE_LIMIT_RANGE.end()))
+        .or(template_limit_defaults(template_id).0)
+        .unwrap_or(8192)
+}
 
-    let default_nofile = env_u64("ALLOY_SANDBOX_NOFILE_LIMIT_DEFAULT")
-        .map(|v| v.clamp(256, 1_048_576))
-        .unwrap_or(8192);
+pub fn default_pids_limit(template_id: &str) -> u64 {
+    env_u64("ALLOY_SANDBOX_PIDS_LIMIT_DEFAULT")
+        .map(|v| v.clamp(*PIDS_LIMIT_RANGE.start(), *PIDS_LIMIT_RANGE.end()))
+        .or(template_limit_defaults(template_id).1)
+        .unwrap_or(512)
+}
+
+/// Parses a sandbox limit param: unset -> None, `0` -> unlimited, otherwise
+/// it must fall inside `range`.
+fn parse_limit_param(
+    params: &BTreeMap<String, String>,
+    key: &str,
+    range: &RangeInclusive<u64>,
+) -> Result<Option<u64>, String> {
+    let Some(raw) = parse_string_param(params, key) else {
+        return Ok(None);
+    };
+    let v = raw.parse::<u64>().map_err(|_| {
+        format!(
+            "Must be an integer ({}..{}, or 0 for unlimited).",
+            range.start(),
+            range.end()
+        )
+    })?;
+    if v != 0 && !range.contains(&v) {
+        return Err(format!(
+            "Must be between {} and {} (or 0 for unlimited).",
+            range.start(),
+            range.end()
+        ));
+    }
+    Ok(Some(v))
+}
+
+/// Rejects out-of-range sandbox limit params, and a memory ceiling below the
+/// Java heap (`memory_mb`) that the JVM would be killed for reaching.
+pub fn validate_limit_params(params: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    let mut fields = BTreeMap::new();
+    for (key, range) in [
+        ("sandbox_memory_mb", &MEMORY_MB_RANGE),
+        ("sandbox_cpu_millicores", &CPU_MILLICORES_RANGE),
+        ("sandbox_nofile_limit", &NOFILE_LIMIT_RANGE),
+        ("sandbox_pids_limit", &PIDS_LIMIT_RANGE),
+    ] {
+        if let Err(msg) = parse_limit_param(params, key, range) {
+            fields.insert(key.to_string(), msg);
+        }
+    }
+    let memory_mb = parse_limit_param(params, "sandbox_memory_mb", &MEMORY_MB_RANGE);
+    if let (Ok(Some(limit_mb)), Some(heap_mb)) = (memory_mb, parse_u64_param(params, "memory_mb"))
+        && limit_mb != 0
+        && limit_mb < heap_mb
+    {
+        fields.insert(
+            "sandbox_memory_mb".to_string(),
+            format!("Must be at least the Java heap size ({heap_mb} MiB from memory_mb)."),
+        );
+    }
+    if fields.is_empty() {
+        return Ok(());
+    }
+    Err(crate::error_payload::anyhow(
+        "invalid_param",
+        "invalid sandbox limits",
+        Some(fields),
+        None,
+    ))
+}
+
+fn resolve_limits(template_id: &str, params: &BTreeMap<String, String>) -> SandboxLimits {
+    let default_memory_mb = env_u64("ALLOY_SANDBOX_MEMORY_MB_DEFAULT")
+        .map(|v| v.clamp(*MEMORY_MB_RANGE.start(), *MEMORY_MB_RANGE.end()))
+        .unwrap_or(4096);
 
     let default_cpu_m = env_u64("ALLOY_SANDBOX_CPU_MILLICORES_DEFAULT")
-        .map(|v| v.clamp(100, 64_000))
+        .map(|v| v.clamp(*CPU_MILLICORES_RANGE.start(), *CPU_MILLICORES_RANGE.end()))
         .unwrap_or(2000);
 
-    let memory_mb = parse_u64_param(params, "sandbox_memory_mb")
-        .map(|v| if v == 0 { 0 } else { v.clamp(256, 131_072) })
+    // Params were range-checked by validate_limit_params; anything else falls back.
+    let memory_mb = parse_limit_param(params, "sandbox_memory_mb", &MEMORY_MB_RANGE)
+        .ok()
+        .flatten()
         .unwrap_or(default_memory_mb);
-    let pids_limit = parse_u64_param(params, "sandbox_pids_limit")
-        .map(|v| if v == 0 { 0 } else { v.clamp(32, 32_768) })
-        .unwrap_or(default_pids);
-    let nofile_limit = parse_u64_param(params, "sandbox_nofile_limit")
-        .map(|v| if v == 0 { 0 } else { v.clamp(256, 1_048_576) })
-        .unwrap_or(default_nofile);
-    let cpu_millicores = parse_u64_param(params, "sandbox_cpu_millicores")
-        .map(|v| if v == 0 { 0 } else { v.clamp(100, 64_000) })
+    let pids_limit = parse_limit_param(params, "sandbox_pids_limit", &PIDS_LIMIT_RANGE)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| default_pids_limit(template_id));
+    let nofile_limit = parse_limit_param(params, "sandbox_nofile_limit", &NOFILE_LIMIT_RANGE)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| default_nofile_limit(template_id));
+    let cpu_millicores = parse_limit_param(params, "sandbox_cpu_millicores", &CPU_MILLICORES_RANGE)
+        .ok()
+        .flatten()
         .unwrap_or(default_cpu_m);
 
     SandboxLimits {
@@ -362,6 +454,55 @@ fn resolve_limits(params: &BTreeMap<String, String>) -> SandboxLimits {
     }
 }
 
+/// Lowers a requested rlimit to the agent's own hard limit when the agent can't
+/// raise it; returns the limit to apply and whether it was lowered.
+fn fit_hard_limit(requested: u64, hard: u64, can_raise: bool) -> (u64, bool) {
+    // 0 leaves the inherited limits alone, so there is nothing to fit.
+    if requested == 0 || requested <= hard || can_raise {
+        return (requested, false);
+    }
+    (hard, true)
+}
+
+/// Host mode applies limits with setrlimit in the child, which fails outright
+/// if a limit exceeds the hard limit and the agent isn't privileged.
+#[cfg(target_os = "linux")]
+fn fit_host_rlimits(limits: &mut SandboxLimits, warnings: &mut Vec<String>) {
+    fn hard_limit(resource: libc::__rlimit_resource_t) -> Option<u64> {
+        let mut lim = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        let rc = unsafe { libc::getrlimit(resource, &mut lim) };
+        if rc == -1 || lim.rlim_max == libc::RLIM_INFINITY {
+            return None;
+        }
+        Some(lim.rlim_max)
+    }
+
+    let can_raise = unsafe { libc::geteuid() } == 0;
+    for (name, resource, limit) in [
+        ("nofile", libc::RLIMIT_NOFILE, &mut limits.nofile_limit),
+        ("nproc", libc::RLIMIT_NPROC, &mut limits.pids_limit),
+    ] {
+        let Some(hard) = hard_limit(resource) else {
+            continue;
+        };
+        let (fitted, lowered) = fit_hard_limit(*limit, hard, can_raise);
+        if lowered {
+            warnings.push(format!(
+                "{name} limit {} exceeds the agent's hard limit ({hard}); using {hard}. \
+                 Raise the agent's hard limit (e.g. LimitNOFILE/LimitNPROC) to allow more.",
+                *limit
+            ));
+            *limit = fitted;
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn fit_host_rlimits(_limits: &mut SandboxLimits, _warnings: &mut Vec<String>) {}
+
 fn normalize_path(path: &Path) -> PathBuf {
     if path.is_absolute() {
         path.to_path_buf()
@@ -862,12 +1003,138 @@ fn sanitize_cgroup_name(raw: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::{
-        detect_docker_data_volume_from_mountinfo, extract_docker_volume_from_mount_root,
-        mount_path_from_mountinfo, mountpoint_prefix_matches,
-        resolve_host_mount_path_from_mountinfo,
+        NOFILE_LIMIT_RANGE, PIDS_LIMIT_RANGE, detect_docker_data_volume_from_mountinfo,
+        extract_docker_volume_from_mount_root, fit_hard_limit, mount_path_from_mountinfo,
+        mountpoint_prefix_matches, parse_limit_param, prepare_launch,
+        resolve_host_mount_path_from_mountinfo, resolve_limits, validate_limit_params,
     };
+    use std::collections::BTreeMap;
     use std::path::Path;
 
+    fn params(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn limit_params_parse_and_validate_ranges() {
+        let p = params(&[
+            ("sandbox_nofile_limit", " 65536 "),
+            ("sandbox_pids_limit", "0"),
+        ]);
+        assert_eq!(
+            parse_limit_param(&p, "sandbox_nofile_limit", &NOFILE_LIMIT_RANGE),
+            Ok(Some(65_536))
+        );
+        assert_eq!(
+            parse_limit_param(&p, "sandbox_pids_limit", &PIDS_LIMIT_RANGE),
+            Ok(Some(0))
+        );
+        assert_eq!(
+            parse_limit_param(&p, "missing", &PIDS_LIMIT_RANGE),
+            Ok(None)
+        );
+        assert!(validate_limit_params(&p).is_ok());
+
+        let bad = params(&[
+            ("sandbox_nofile_limit", "16"),
+            ("sandbox_pids_limit", "lots"),
+        ]);
+        let err = validate_limit_params(&bad).unwrap_err().to_string();
+        assert!(err.contains("sandbox_nofile_limit"));
+        assert!(err.contains("sandbox_pids_limit"));
+    }
+
+    #[test]
+    fn oneshot_refuses_native_launch() {
+        let dir = std::env::temp_dir();
+        let err = prepare_launch(
+            "oneshot-native",
+            crate::templates::ONESHOT_TEMPLATE_ID,
+            &params(&[("sandbox_mode", "native")]),
+            &dir,
+            &dir,
+            "true",
+            &[],
+            &[],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("native launch is not allowed"));
+    }
+
+    #[test]
+    fn limits_prefer_params_then_template_defaults() {
+        let modpack = resolve_limits("minecraft:modrinth", &BTreeMap::new());
+        assert_eq!(modpack.nofile_limit, 65_536);
+        let oneshot = resolve_limits(crate::templates::ONESHOT_TEMPLATE_ID, &BTreeMap::new());
+        assert_eq!(oneshot.pids_limit, 64);
+
+        let explicit = resolve_limits(
+            "minecraft:modrinth",
+            &params(&[
+                ("sandbox_nofile_limit", "4096"),
+                ("sandbox_pids_limit", "0"),
+            ]),
+        );
+        assert_eq!(explicit.nofile_limit, 4096);
+        assert_eq!(explicit.pids_limit, 0);
+
+        let sized = resolve_limits(
+            "minecraft:vanilla",
+            &params(&[
+                ("sandbox_memory_mb", "6144"),
+                ("sandbox_cpu_millicores", "0"),
+            ]),
+        );
+        assert_eq!(sized.memory_bytes, 6144 * 1024 * 1024);
+        assert_eq!(sized.cpu_millicores, 0);
+        // Out-of-range values never reach the sandbox; they fall back to the defaults.
+        let fallback = resolve_limits(
+            "minecraft:vanilla",
+            &params(&[
+                ("sandbox_memory_mb", "64"),
+                ("sandbox_cpu_millicores", "999999"),
+            ]),
+        );
+        assert_eq!(fallback.memory_bytes, 4096 * 1024 * 1024);
+        assert_eq!(fallback.cpu_millicores, 2000);
+    }
+
+    #[test]
+    fn memory_limit_must_cover_the_java_heap() {
+        let err = validate_limit_params(&params(&[
+            ("memory_mb", "4096"),
+            ("sandbox_memory_mb", "2048"),
+        ]))
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("sandbox_memory_mb"));
+        assert!(err.contains("4096 MiB"));
+
+        for limit in ["4096", "8192", "0"] {
+            let p = params(&[("memory_mb", "4096"), ("sandbox_memory_mb", limit)]);
+            assert!(validate_limit_params(&p).is_ok(), "limit {limit}");
+        }
+
+        let bad = params(&[
+            ("sandbox_memory_mb", "128"),
+            ("sandbox_cpu_millicores", "50"),
+        ]);
+        let err = validate_limit_params(&bad).unwrap_err().to_string();
+        assert!(err.contains("sandbox_memory_mb"));
+        assert!(err.contains("sandbox_cpu_millicores"));
+    }
+
+    #[test]
+    fn hard_limit_caps_unprivileged_requests() {
+        assert_eq!(fit_hard_limit(4096, 8192, false), (4096, false));
+        assert_eq!(fit_hard_limit(65_536, 8192, false), (8192, true));
+        assert_eq!(fit_hard_limit(65_536, 8192, true), (65_536, false));
+        assert_eq!(fit_hard_limit(0, 8192, false), (0, false));
+    }
+
     #[test]
     fn mountpoint_prefix_matching_works() {
         assert!(mountpoint_prefix_matches("/data", "/data"));
@@ -1014,7 +1281,16 @@ pub fn prepare_launch(
 
     let mode_override = parse_string_param(params, "sandbox_mode");
     let (mode, mut warnings) = choose_mode(sandbox_enabled, mode_override)?;
-    let limits = resolve_limits(params);
+    if template_id == crate::templates::ONESHOT_TEMPLATE_ID && matches!(mode, Mode::Native) {
+        // Oneshot runs an arbitrary command; without a wrapper it would run as the agent.
+        anyhow::bail!(
+            "oneshot commands require a docker or bwrap sandbox; native launch is not allowed"
+        );
+    }
+    let mut limits = resolve_limits(template_id, params);
+    if !matches!(mode, Mode::Docker) {
+        fit_host_rlimits(&mut limits, &mut warnings);
+    }
 
     let mut cgroup_path = None;
     if sandbox_enabled && !matches!(mode, Mode::Docker) {
@@ -0,0 +1,50 @@
+use crate::process_manager::ProcessManager;
+use crate::process_manager_support::{shutdown_deadline, shutdown_stop_timeout};
+
+/// Resolves on Ctrl-C or SIGTERM.
+async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::warn!(%err, "failed to listen for ctrl-c");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(err) => {
+                tracing::warn!(%err, "failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Waits for a shutdown signal, then stops every managed process gracefully so
+/// worlds are saved instead of the children dying with the agent. The gRPC
+/// server shuts down once this returns.
+pub(crate) async fn stop_processes_on_signal(manager: ProcessManager) {
+    signal().await;
+    let (timeout, deadline) = (shutdown_stop_timeout(), shutdown_deadline());
+    tracing::info!(
+        timeout_ms = timeout.as_millis() as u64,
+        deadline_ms = deadline.as_millis() as u64,
+        "shutdown requested; stopping managed processes"
+    );
+    let stopped = manager.stop_all(timeout, deadline).await;
+    tracing::info!(
+        stopped,
+        "managed processes stopped; shutting down gRPC server"
+    );
+}
@@ -0,0 +1,150 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const STATE_FILE: &str = "staged-upgrade.json";
+// Schedules further out than this are almost certainly a unit mistake.
+pub(crate) const MAX_LEAD_TIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+pub(crate) const MAX_WARNING_SECONDS: u32 = 15 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Phase {
+    Staged,
+    Warning,
+    Stopping,
+    Backup,
+    Swapping,
+    Starting,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl Phase {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Phase::Staged => "staged",
+            Phase::Warning => "warning",
+            Phase::Stopping => "stopping",
+            Phase::Backup => "backup",
+            Phase::Swapping => "swapping",
+            Phase::Starting => "starting",
+            Phase::Done => "done",
+            Phase::Failed => "failed",
+            Phase::Cancelled => "cancelled",
+        }
+    }
+
+    pub(crate) fn is_finished(self) -> bool {
+        matches!(self, Phase::Done | Phase::Failed | Phase::Cancelled)
+    }
+
+    /// Past this point the server has been touched and the upgrade can't be called off.
+    pub(crate) fn is_cancellable(self) -> bool {
+        matches!(self, Phase::Staged | Phase::Warning)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct StagedUpgrade {
+    pub instance_id: String,
+    pub upgrade_id: String,
+    pub from_version: String,
+    pub target_version: String,
+    pub run_at_unix_ms: u64,
+    pub warning_seconds: u32,
+    pub backup: bool,
+    pub stop_timeout_ms: u32,
+    pub phase: Phase,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub backup_id: Option<String>,
+    pub updated_at_unix_ms: u64,
+}
+
+impl StagedUpgrade {
+    pub(crate) fn set_phase(&mut self, phase: Phase, message: impl Into<String>) {
+        self.phase = phase;
+        self.message = message.into();
+        self.updated_at_unix_ms = now_unix_ms();
+    }
+
+    pub(crate) fn delay(&self) -> Duration {
+        Duration::from_millis(self.run_at_unix_ms.saturating_sub(now_unix_ms()))
+    }
+
+    pub(crate) fn to_proto(&self) -> alloy_proto::agent_v1::StagedUpgrade {
+        alloy_proto::agent_v1::StagedUpgrade {
+            instance_id: self.instance_id.clone(),
+            upgrade_id: self.upgrade_id.clone(),
+            from_version: self.from_version.clone(),
+            target_version: self.target_version.clone(),
+            run_at_unix_ms: self.run_at_unix_ms,
+            warning_seconds: self.warning_seconds,
+            backup: self.backup,
+            phase: self.phase.as_str().to_string(),
+            message: self.message.clone(),
+            backup_id: self.backup_id.clone().unwrap_or_default(),
+            updated_at_unix_ms: self.updated_at_unix_ms,
+        }
+    }
+}
+
+pub(crate) fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn state_path(instance_dir: &Path) -> PathBuf {
+    instance_dir.join(STATE_FILE)
+}
+
+pub(crate) async fn load(instance_dir: &Path) -> Option<StagedUpgrade> {
+    let raw = tokio::fs::read(state_path(instance_dir)).await.ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+pub(crate) async fn save(instance_dir: &Path, upgrade: &StagedUpgrade) -> anyhow::Result<()> {
+    let path = state_path(instance_dir);
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(upgrade)?).await?;
+    tokio::fs::rename(tmp, path).await?;
+    Ok(())
+}
+
+/// Message sent to the server console before it goes down.
+pub(crate) fn warning_command(target_version: &str, seconds: u32) -> String {
+    let when = if seconds >= 60 && seconds.is_multiple_of(60) {
+        let minutes = seconds / 60;
+        format!("{minutes} minute{}", if minutes == 1 { "" } else { "s" })
+    } else {
+        format!("{seconds} second{}", if seconds == 1 { "" } else { "s" })
+    };
+    format!("say Server restarting in {when} to upgrade to Minecraft {target_version}.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warning_command_formats_minutes_and_seconds() {
+        assert_eq!(
+            warning_command("1.21.1", 300),
+            "say Server restarting in 5 minutes to upgrade to Minecraft 1.21.1."
+        );
+        assert_eq!(
+            warning_command("1.21.1", 60),
+            "say Server restarting in 1 minute to upgrade to Minecraft 1.21.1."
+        );
+        assert_eq!(
+            warning_command("1.21.1", 90),
+            "say Server restarting in 90 seconds to upgrade to Minecraft 1.21.1."
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+
+use crate::version_requirements::{self, VersionRequirements};
+use crate::{minecraft, port_alloc, process_manager, templates};
+
+/// Everything a start would reject up front, collected instead of stopping
+/// at the first problem.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub field_errors: BTreeMap<String, String>,
+    /// Problems not tied to one param (unknown template, low disk space).
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ValidationReport {
+    pub fn ok(&self) -> bool {
+        self.field_errors.is_empty() && self.errors.is_empty()
+    }
+
+    fn absorb(&mut self, err: &anyhow::Error) {
+        match crate::error_payload::decode(err) {
+            Some(payload) if payload.field_errors.is_some() => {
+                for (k, v) in payload.field_errors.unwrap_or_default() {
+                    self.field_errors.entry(k).or_insert(v);
+                }
+            }
+            Some(payload) => self.errors.push(payload.message),
+            None => self.errors.push(err.to_string()),
+        }
+    }
+}
+
+/// Port params a start binds, and whether they are UDP.
+fn port_params(template_id: &str) -> (&'static [&'static str], bool) {
+    match template_id {
+        "dst:vanilla" => (
+            &[
+                "port",
+                "master_port",
+                "auth_port",
+                "caves_port",
+                "caves_master_port",
+                "caves_auth_port",
+            ],
+            true,
+        ),
+        "demo:sleep" | templates::ONESHOT_TEMPLATE_ID => (&[], false),
+        _ => (&["port"], false),
+    }
+}
+
+/// Runs the checks of a start (params, ports, disk space, Java major)
+/// without downloading or spawning anything.
+pub async fn validate(template_id: &str, params: &BTreeMap<String, String>) -> ValidationReport {
+    let requirements = version_requirements::resolve(template_id, params).await;
+    let template_id = template_id.to_string();
+    let params = params.clone();
+    tokio::task::spawn_blocking(move || check(&template_id, &params, requirements))
+        .await
+        .unwrap_or_else(|e| ValidationReport {
+            errors: vec![format!("validation task failed: {e}")],
+            ..Default::default()
+        })
+}
+
+fn check(
+    template_id: &str,
+    params: &BTreeMap<String, String>,
+    requirements: anyhow::Result<VersionRequirements>,
+) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let Some(base) = templates::find_template(template_id) else {
+        report
+            .errors
+            .push(format!("unknown template_id: {template_id}"));
+        return report;
+    };
+    let dual = base.needs_udp_and_tcp;
+    if let Err(e) = templates::apply_params(base, params) {
+        report.absorb(&e);
+    }
+
+    // Ports are bound and released right away; 0 means auto-assign.
+    let (keys, udp) = port_params(template_id);
+    for key in keys {
+        if report.field_errors.contains_key(*key) {
+            continue;
+        }
+        let Some(port) = params
+            .get(*key)
+            .and_then(|v| v.trim().parse::<u16>().ok())
+            .filter(|p| *p != 0)
+        else {
+            continue;
+        };
+        let bound = if udp {
+            port_alloc::allocate_udp_port(port)
+        } else if dual {
+            port_alloc::allocate_dual_port(port)
+        } else {
+            port_alloc::allocate_tcp_port(port)
+        };
+        if let Err(e) = bound {
+            report.field_errors.insert(key.to_string(), e.to_string());
+        }
+    }
+
+    if let Err(e) = process_manager::ensure_min_free_space(&minecraft::data_root()) {
+        report.errors.push(e.to_string());
+    }
+
+    match requirements {
+        Ok(reqs) if reqs.java_required => {
+            let java = process_manager::resolve_java_path(params);
+            let runtime = process_manager::detect_java_major(&java).ok();
+            let (satisfied, message) = version_requirements::summarize(&reqs, runtime);
+            if !satisfied {
+                report
+                    .field_errors
+                    .entry("java_path".to_string())
+                    .or_insert(message);
+            } else if reqs.java_major.is_none() {
+                report.warnings.push(message);
+            }
+        }
+        Ok(_) => {}
+        // Bad params were already reported by apply_params.
+        Err(e) if crate::error_payload::decode(&e).is_some_and(|p| p.code == "invalid_param") => {}
+        Err(e) => report
+            .warnings
+            .push(format!("Java requirements could not be checked: {e}")),
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::check;
+    use crate::version_requirements::VersionRequirements;
+
+    #[test]
+    fn port_and_java_problems_are_reported_together() {
+        let held = std::net::TcpListener::bind(("0.0.0.0", 0)).unwrap();
+        let port = held.local_addr().unwrap().port();
+
+        let dir = std::env::temp_dir().join(format!(
+            "alloy-agent-start-validation-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let java = dir.join("java");
+        std::fs::write(
+            &java,
+            "#!/bin/sh\necho 'openjdk version \"1.8.0_392\"' >&2\n",
+        )
+        .unwrap();
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&java, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let params = BTreeMap::from([
+            ("accept_eula".to_string(), "true".to_string()),
+            ("port".to_string(), port.to_string()),
+            ("java_path".to_string(), java.display().to_string()),
+        ]);
+        let reqs = VersionRequirements {
+            java_required: true,
+            java_major: Some(21),
+            minecraft_version: Some("1.21".to_string()),
+            loader: None,
+        };
+        let report = check("minecraft:vanilla", &params, Ok(reqs));
+
+        assert!(!report.ok());
+        assert!(report.field_errors["port"].contains("in use"), "{report:?}");
+        assert!(
+            report.field_errors["java_path"].contains("runs Java 8"),
+            "{report:?}"
+        );
+
+        let report = check("nope:nope", &params, Ok(VersionRequirements::default()));
+        assert!(report.errors[0].contains("unknown template_id"));
+
+        drop(held);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::BTreeMap;
+
+/// Steam account credentials from warm/start params. The control plane fills
+/// these in from its settings, including Steam Guard codes generated from the
+/// configured shared_secret.
+#[derive(Clone)]
+pub(crate) struct SteamLogin {
+    pub(crate) username: String,
+    pub(crate) password: String,
+    /// Codes to try in order; empty when the account has no Steam Guard code.
+    pub(crate) guard_codes: Vec<String>,
+}
+
+impl std::fmt::Debug for SteamLogin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SteamLogin")
+            .field("username", &self.username)
+            .field("guard_codes", &self.guard_codes.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SteamLogin {
+    /// Reads `steam_username` and `steam_password`, plus either an explicit
+    /// `steam_guard_code` or comma-separated `steam_guard_codes` candidates.
+    /// `None` means SteamCMD logs in anonymously.
+    pub(crate) fn from_params(params: &BTreeMap<String, String>) -> Option<Self> {
+        let get = |key: &str| params.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+        let username = get("steam_username")?.to_string();
+        let password = params.get("steam_password").filter(|v| !v.is_empty())?;
+
+        let guard_codes = match get("steam_guard_code") {
+            Some(code) => vec![code.to_string()],
+            None => get("steam_guard_codes")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|c| !c.is_empty())
+                .map(str::to_string)
+                .collect(),
+        };
+        Some(Self {
+            username,
+            password: password.clone(),
+            guard_codes,
+        })
+    }
+
+    /// One entry per login attempt: each guard code, or a single attempt
+    /// without one.
+    pub(crate) fn attempts(&self) -> Vec<Option<&str>> {
+        if self.guard_codes.is_empty() {
+            vec![None]
+        } else {
+            self.guard_codes.iter().map(|c| Some(c.as_str())).collect()
+        }
+    }
+}
+
+/// How SteamCMD logs in, from the `steam_login_mode` param.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum LoginMode {
+    /// Anonymous, falling back to the account when Steam refuses anonymous.
+    #[default]
+    Auto,
+    Anonymous,
+    Account,
+}
+
+impl LoginMode {
+    pub(crate) fn from_params(params: &BTreeMap<String, String>) -> anyhow::Result<Self> {
+        match params.get("steam_login_mode").map(|v| v.trim()) {
+            None | Some("" | "auto") => Ok(Self::Auto),
+            Some("anonymous") => Ok(Self::Anonymous),
+            Some("account") => Ok(Self::Account),
+            Some(other) => Err(crate::error_payload::anyhow(
+                "invalid_param",
+                format!("invalid steam_login_mode: {other}"),
+                Some(BTreeMap::from([(
+                    "steam_login_mode".to_string(),
+                    "must be auto, anonymous or account".to_string(),
+                )])),
+                None,
+            )),
+        }
+    }
+}
+
+/// Login mode plus the account, if the params carry one.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SteamcmdAuth {
+    pub(crate) mode: LoginMode,
+    pub(crate) account: Option<SteamLogin>,
+}
+
+impl SteamcmdAuth {
+    pub(crate) fn from_params(params: &BTreeMap<String, String>) -> anyhow::Result<Self> {
+        Ok(Self {
+            mode: LoginMode::from_params(params)?,
+            account: SteamLogin::from_params(params),
+        })
+    }
+
+    /// Logins to try in order, `None` being anonymous. Each one after the
+    /// first is only used when SteamCMD refuses anonymous login.
+    pub(crate) fn logins(&self) -> anyhow::Result<Vec<Option<&SteamLogin>>> {
+        match (self.mode, self.account.as_ref()) {
+            (LoginMode::Anonymous, _) | (LoginMode::Auto, None) => Ok(vec![None]),
+            (LoginMode::Auto, Some(account)) => Ok(vec![None, Some(account)]),
+            (LoginMode::Account, Some(account)) => Ok(vec![Some(account)]),
+            (LoginMode::Account, None) => Err(crate::error_payload::anyhow(
+                "steam_login_required",
+                "steam_login_mode is account, but no SteamCMD account is configured",
+                Some(BTreeMap::from([(
+                    "steam_username".to_string(),
+                    "required for account login".to_string(),
+                )])),
+                Some(
+                    "Save SteamCMD credentials in Settings, or use steam_login_mode=auto."
+                        .to_string(),
+                ),
+            )),
+        }
+    }
+}
+
+/// Whether SteamCMD output says the app can't be installed anonymously.
+pub(crate) fn anonymous_refused(output: &str) -> bool {
+    output.lines().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.contains("no subscription")
+            || (line.contains("anonymous")
+                && (line.contains("not allowed") || line.contains("denied")))
+    })
+}
+
+/// Why SteamCMD refused a login.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LoginFailure {
+    InvalidPassword,
+    GuardCodeRequired,
+    InvalidGuardCode,
+    RateLimited,
+}
+
+/// Maps one line of SteamCMD output to the login failure it reports.
+pub(crate) fn classify_line(line: &str) -> Option<LoginFailure> {
+    let line = line.to_ascii_lowercase();
+    if line.contains("rate limit") || line.contains("throttle") {
+        Some(LoginFailure::RateLimited)
+    } else if line.contains("invalid login auth code") || line.contains("two-factor code mismatch")
+    {
+        Some(LoginFailure::InvalidGuardCode)
+    } else if line.contains("invalid password") {
+        Some(LoginFailure::InvalidPassword)
+    } else if line.contains("account logon denied")
+        || line.contains("steam guard code:")
+        || line.contains("two-factor code:")
+    {
+        Some(LoginFailure::GuardCodeRequired)
+    } else {
+        None
+    }
+}
+
+/// The most specific failure reported anywhere in `output`. A prompt for a
+/// code is followed by the verdict on the code we sent, so later lines win.
+pub(crate) fn classify_output(output: &str) -> Option<LoginFailure> {
+    output.lines().rev().find_map(classify_line)
+}
+
+/// Whether to try the next guard code after a failed attempt. Only a rejected
+/// code is worth retrying: the next candidate covers clock skew. Retrying a
+/// bad password or a rate limit just extends the lockout.
+pub(crate) fn should_retry(failure: Option<LoginFailure>, codes_left: bool) -> bool {
+    codes_left && failure == Some(LoginFailure::InvalidGuardCode)
+}
+
+/// Error payload for a login failure, with a field error the UI can attach
+/// to the input that needs fixing.
+pub(crate) fn login_error(failure: LoginFailure) -> anyhow::Error {
+    let (code, field, message, hint) = match failure {
+        LoginFailure::InvalidPassword => (
+            "steam_login_failed",
+            "steam_password",
+            "Steam rejected the username or password",
+            "Check the SteamCMD username and password in Settings.",
+        ),
+        LoginFailure::GuardCodeRequired => (
+            "steam_guard_required",
+            "steam_guard_code",
+            "Steam requires a Steam Guard code for this account",
+            "Import the account's maFile in Settings so codes are generated automatically.",
+        ),
+        LoginFailure::InvalidGuardCode => (
+            "steam_guard_invalid",
+            "steam_guard_code",
+            "Steam rejected the Steam Guard code",
+            "Re-import the maFile and check that the control host's clock is in sync.",
+        ),
+        LoginFailure::RateLimited => (
+            "steam_rate_limited",
+            "steam_username",
+            "Steam is rate limiting logins from this host",
+            "Wait a while before retrying; repeated attempts extend the limit.",
+        ),
+    };
+    let field_errors = BTreeMap::from([(field.to_string(), message.to_string())]);
+    crate::error_payload::anyhow(code, message, Some(field_errors), Some(hint.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{
+        LoginFailure, LoginMode, SteamLogin, SteamcmdAuth, anonymous_refused, classify_line,
+        classify_output, should_retry,
+    };
+
+    #[test]
+    fn steamcmd_lines_map_to_login_failures() {
+        let cases = [
+            (
+                "Logging in user 'alice' to Steam Public...FAILED (Invalid Password)",
+                Some(LoginFailure::InvalidPassword),
+            ),
+            (
+                "FAILED (Invalid Login Auth Code)",
+                Some(LoginFailure::InvalidGuardCode),
+            ),
+            (
+                "ERROR (Two-factor code mismatch)",
+                Some(LoginFailure::InvalidGuardCode),
+            ),
+            (
+                "FAILED (Rate Limit Exceeded)",
+                Some(LoginFailure::RateLimited),
+            ),
+            (
+                "FAILED (Account Login Denied Throttle)",
+                Some(LoginFailure::RateLimited),
+            ),
+            ("Two-factor code:", Some(LoginFailure::GuardCodeRequired)),
+            (
+                "FAILED (Account Logon Denied)",
+                Some(LoginFailure::GuardCodeRequired),
+            ),
+            ("Waiting for user info...OK", None),
+            (" Update state (0x61) downloading, progress: 12.50", None),
+        ];
+        for (line, want) in cases {
+            assert_eq!(classify_line(line), want, "{line}");
+        }
+    }
+
+    #[test]
+    fn only_rejected_guard_codes_are_retried() {
+        let out = "Two-factor code:\nLogging in user 'alice'...FAILED (Invalid Login Auth Code)\n";
+        let failure = classify_output(out);
+        assert_eq!(failure, Some(LoginFailure::InvalidGuardCode));
+        assert!(should_retry(failure, true));
+        assert!(!should_retry(failure, false));
+
+        for failure in [
+            Some(LoginFailure::InvalidPassword),
+            Some(LoginFailure::RateLimited),
+            Some(LoginFailure::GuardCodeRequired),
+            None,
+        ] {
+            assert!(!should_retry(failure, true), "{failure:?}");
+        }
+    }
+
+    #[test]
+    fn credentials_and_guard_codes_come_from_params() {
+        let mut params = BTreeMap::from([
+            ("steam_username".to_string(), " alice ".to_string()),
+            ("steam_password".to_string(), " pw ".to_string()),
+            (
+                "steam_guard_codes".to_string(),
+                "AAAAA, BBBBB,,".to_string(),
+            ),
+        ]);
+        let login = SteamLogin::from_params(&params).unwrap();
+        assert_eq!(login.username, "alice");
+        assert_eq!(login.password, " pw ");
+        assert_eq!(login.attempts(), [Some("AAAAA"), Some("BBBBB")]);
+
+        params.insert("steam_guard_code".to_string(), "CCCCC".to_string());
+        let login = SteamLogin::from_params(&params).unwrap();
+        assert_eq!(login.attempts(), [Some("CCCCC")]);
+
+        params.remove("steam_password");
+        assert!(SteamLogin::from_params(&params).is_none());
+    }
+
+    #[test]
+    fn login_mode_picks_anonymous_or_account_by_credentials() {
+        let account = BTreeMap::from([
+            ("steam_username".to_string(), "alice".to_string()),
+            ("steam_password".to_string(), "pw".to_string()),
+        ]);
+        let with_mode = |base: &BTreeMap<String, String>, mode: &str| {
+            let mut params = base.clone();
+            params.insert("steam_login_mode".to_string(), mode.to_string());
+            SteamcmdAuth::from_params(&params)
+        };
+        let names = |auth: &SteamcmdAuth| -> Vec<Option<String>> {
+            let logins = auth.logins().unwrap();
+            logins
+                .iter()
+                .map(|l| l.map(|l| l.username.clone()))
+                .collect()
+        };
+        let alice = Some("alice".to_string());
+
+        let auto = SteamcmdAuth::from_params(&account).unwrap();
+        assert_eq!(auto.mode, LoginMode::Auto);
+        assert_eq!(names(&auto), [None, alice.clone()]);
+        let auto_anonymous = SteamcmdAuth::from_params(&BTreeMap::new()).unwrap();
+        assert_eq!(names(&auto_anonymous), [None]);
+
+        assert_eq!(names(&with_mode(&account, "anonymous").unwrap()), [None]);
+        assert_eq!(names(&with_mode(&account, "account").unwrap()), [alice]);
+
+        let no_account = with_mode(&BTreeMap::new(), "account").unwrap();
+        let err = no_account.logins().unwrap_err();
+        let payload = crate::error_payload::decode(&err).unwrap();
+        assert_eq!(payload.code, "steam_login_required");
+        assert!(with_mode(&account, "bogus").is_err());
+    }
+
+    #[test]
+    fn anonymous_refusal_triggers_the_account_fallback() {
+        assert!(anonymous_refused(
+            "Connecting anonymously to Steam Public...OK\nERROR! Failed to install app '343050' (No subscription)"
+        ));
+        assert!(anonymous_refused("FAILED (Anonymous login not allowed)"));
+        assert!(!anonymous_refused(
+            "Connecting anonymously to Steam Public...OK\nSuccess! App '343050' fully installed."
+        ));
+        assert!(!anonymous_refused(
+            "ERROR! Failed to install app '343050' (Disk write failure)"
+        ));
+    }
+}
@@ -2,6 +2,9 @@ use std::collections::BTreeMap;
 
 use alloy_proto::agent_v1::{ParamType, TemplateParam};
 
+/// Short-lived maintenance command run inside an existing instance directory.
+pub const ONESHOT_TEMPLATE_ID: &str = "oneshot:command";
+
 #[derive(Debug, Clone)]
 pub struct ProcessTemplate {
     pub template_id: String,
@@ -10,9 +13,31 @@ pub struct ProcessTemplate {
     pub args: Vec<String>,
     pub params: Vec<TemplateParam>,
 
-    // Optional graceful shutdown string to write to stdin before SIGTERM.
-    #[allow(dead_code)]
-    pub graceful_stdin: Option<String>,
+    // How `stop` shuts the server down before signalling; `None` means SIGTERM right away.
+    pub graceful_stop: Option<GracefulStopSpec>,
+    // Case-insensitive console fragments that mean the server finished loading.
+    pub ready_log_markers: Vec<String>,
+    // The `port` param must be free on UDP as well as TCP.
+    pub needs_udp_and_tcp: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GracefulStopSpec {
+    /// Written to stdin before SIGTERM, e.g. `stop\n`.
+    pub stdin_command: Option<String>,
+    /// Case-insensitive log fragments that confirm the world was saved.
+    pub save_keywords: Vec<String>,
+    /// How long before the stop deadline SIGTERM follows the stdin command;
+    /// `None` uses ALLOY_GRACEFUL_TERM_GRACE_SEC.
+    pub grace_secs: Option<u64>,
+}
+
+fn graceful_stop(stdin_command: &str, save_keywords: &[&str]) -> Option<GracefulStopSpec> {
+    Some(GracefulStopSpec {
+        stdin_command: Some(stdin_command.to_string()),
+        save_keywords: save_keywords.iter().map(|k| k.to_string()).collect(),
+        grace_secs: None,
+    })
 }
 
 fn param_string(
@@ -124,7 +149,9 @@ fn param_bool_advanced(
     p
 }
 
-fn sandbox_params() -> Vec<TemplateParam> {
+fn sandbox_params(template_id: &str) -> Vec<TemplateParam> {
+    let pids_default = crate::sandbox::default_pids_limit(template_id).to_string();
+    let nofile_default = crate::sandbox::default_nofile_limit(template_id).to_string();
     vec![
         param_bool_advanced(
             "sandbox_enabled",
@@ -150,27 +177,27 @@ fn sandbox_params() -> Vec<TemplateParam> {
             256,
             131072,
             "4096",
-            "Hard memory ceiling. 0 means unlimited (not recommended).",
+            "Hard memory ceiling (256..131072, 0 = unlimited). Must cover the Java heap (memory_mb).",
         ),
         param_int_advanced(
             "sandbox_pids_limit",
             "Sandbox PID limit",
             false,
-            "512",
-            32,
+            &pids_default,
+            0,
             32768,
-            "512",
-            "Maximum process count for the instance process tree.",
+            &pids_default,
+            "Maximum process count (32..32768, 0 = unlimited). Caps fork bombs; in host mode this is RLIMIT_NPROC.",
         ),
         param_int_advanced(
             "sandbox_nofile_limit",
             "Sandbox open files",
             false,
-            "8192",
-            256,
+            &nofile_default,
+            0,
             1048576,
-            "8192",
-            "Maximum number of open file descriptors.",
+            &nofile_default,
+            "Maximum open file descriptors (256..1048576, 0 = inherit). Large modpacks need more.",
         ),
         param_int_advanced(
             "sandbox_cpu_millicores",
@@ -180,7 +207,7 @@ fn sandbox_params() -> Vec<TemplateParam> {
             100,
             64000,
             "2000",
-            "CPU quota hint for cgroup (1000 = 1 core).",
+            "CPU quota for cgroup/Docker (100..64000, 1000 = 1 core, 0 = unlimited).",
         ),
         param_string_advanced(
             "restart_policy",
@@ -270,10 +297,10 @@ fn param_secret(
     }
 }
 
-pub fn list_templates() -> Vec<ProcessTemplate> {
+fn builtin_templates() -> Vec<ProcessTemplate> {
     // Phase 1: hardcoded templates to avoid turning the control plane into RCE.
     // These are demos; game adapters will provide real templates later.
-    let mut templates = vec![
+    vec![
         ProcessTemplate {
             template_id: "demo:sleep".to_string(),
             display_name: "Demo: sleep".to_string(),
@@ -289,7 +316,48 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                 "60",
                 "How long the demo process sleeps.",
             )],
-            graceful_stdin: None,
+            graceful_stop: None,
+            ready_log_markers: Vec::new(),
+            needs_udp_and_tcp: false,
+        },
+        ProcessTemplate {
+            template_id: ONESHOT_TEMPLATE_ID.to_string(),
+            display_name: "Maintenance: one-off command".to_string(),
+            // Filled in from params by `apply_params`.
+            command: String::new(),
+            args: vec![],
+            params: vec![
+                param_string(
+                    "instance_id",
+                    "Instance",
+                    true,
+                    "",
+                    vec![],
+                    "my-server",
+                    "The command runs in this instance's directory. Stop the instance first.",
+                ),
+                param_string(
+                    "command",
+                    "Command",
+                    true,
+                    "",
+                    vec![],
+                    "java",
+                    "Executable to run (absolute path or a name on PATH).",
+                ),
+                param_string(
+                    "args",
+                    "Arguments",
+                    false,
+                    "",
+                    vec![],
+                    "-jar tools/repair.jar world",
+                    "Arguments split like a shell command line; quote to keep spaces.",
+                ),
+            ],
+            graceful_stop: None,
+            ready_log_markers: Vec::new(),
+            needs_udp_and_tcp: false,
         },
         ProcessTemplate {
             // Real implementation is added incrementally in Milestone 1.
@@ -336,7 +404,81 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                     "TCP port to bind. Use 0 or leave blank to auto-assign a free port.",
                 ),
             ],
-            graceful_stdin: Some("stop\n".to_string()),
+            graceful_stop: graceful_stop(
+                "stop\n",
+                &[
+                    "saved the game",
+                    "saving chunks for level",
+                    "all chunks are saved",
+                    "saving players",
+                ],
+            ),
+            ready_log_markers: Vec::new(),
+            needs_udp_and_tcp: false,
+        },
+        ProcessTemplate {
+            template_id: "minecraft:paper".to_string(),
+            display_name: "Minecraft: Paper".to_string(),
+            command: "java".to_string(),
+            args: vec![],
+            params: vec![
+                param_bool(
+                    "accept_eula",
+                    "Accept EULA",
+                    true,
+                    false,
+                    "Required to start Minecraft server. You must agree to Mojang's EULA.",
+                ),
+                param_string(
+                    "mc_version",
+                    "Minecraft version",
+                    false,
+                    "latest",
+                    vec!["latest"],
+                    "latest",
+                    "Minecraft version id (e.g. 1.21.1). latest is the newest version Paper supports.",
+                ),
+                param_string(
+                    "build",
+                    "Paper build",
+                    false,
+                    "latest",
+                    vec!["latest"],
+                    "latest",
+                    "Paper build number to pin. latest is the newest stable build.",
+                ),
+                param_int(
+                    "memory_mb",
+                    "Memory (MiB)",
+                    false,
+                    "2048",
+                    512,
+                    65536,
+                    "2048",
+                    "Max heap size passed to Java (Xmx).",
+                ),
+                param_int(
+                    "port",
+                    "Port",
+                    false,
+                    "0",
+                    1024,
+                    65535,
+                    "25565 (leave blank for auto)",
+                    "TCP port to bind. Use 0 or leave blank to auto-assign a free port.",
+                ),
+            ],
+            graceful_stop: graceful_stop(
+                "stop\n",
+                &[
+                    "saved the game",
+                    "saving chunks for level",
+                    "all chunks are saved",
+                    "saving players",
+                ],
+            ),
+            ready_log_markers: Vec::new(),
+            needs_udp_and_tcp: false,
         },
         ProcessTemplate {
             template_id: "minecraft:modrinth".to_string(),
@@ -358,7 +500,25 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                     "",
                     Vec::new(),
                     "https://modrinth.com/modpack/.../version/...",
-                    "Paste a Modrinth version URL or a direct .mrpack download URL.",
+                    "Paste a Modrinth modpack or version URL, a project slug, or a direct .mrpack download URL.",
+                ),
+                param_string_advanced(
+                    "version_id",
+                    "Modpack version",
+                    false,
+                    "latest",
+                    Vec::new(),
+                    "latest",
+                    "Version id or number to install when the modpack is given by slug or project URL. `latest` picks the newest version for the loader.",
+                ),
+                param_string_advanced(
+                    "loader",
+                    "Modpack loader",
+                    false,
+                    "fabric",
+                    vec!["fabric"],
+                    "",
+                    "Loader used to pick the `latest` version. Only fabric packs are supported for now.",
                 ),
                 param_int(
                     "memory_mb",
@@ -381,7 +541,9 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                     "TCP port to bind. Use 0 or leave blank to auto-assign a free port.",
                 ),
             ],
-            graceful_stdin: Some("stop\n".to_string()),
+            graceful_stop: graceful_stop("stop\n", &[]),
+            ready_log_markers: Vec::new(),
+            needs_udp_and_tcp: false,
         },
         ProcessTemplate {
             template_id: "minecraft:import".to_string(),
@@ -426,7 +588,9 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                     "TCP port to bind. Use 0 or leave blank to auto-assign a free port.",
                 ),
             ],
-            graceful_stdin: Some("stop\n".to_string()),
+            graceful_stop: graceful_stop("stop\n", &[]),
+            ready_log_markers: Vec::new(),
+            needs_udp_and_tcp: false,
         },
         ProcessTemplate {
             template_id: "minecraft:curseforge".to_string(),
@@ -443,12 +607,12 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                 ),
                 param_string(
                     "curseforge",
-                    "Modpack (CurseForge file)",
+                    "Modpack (CurseForge)",
                     true,
                     "",
                     Vec::new(),
-                    "https://www.curseforge.com/minecraft/modpacks/.../files/...",
-                    "Paste a CurseForge file URL, or modId:fileId. Server pack is preferred when available.",
+                    "https://www.curseforge.com/minecraft/modpacks/...",
+                    "Paste a CurseForge modpack URL (add /files/<id> to pin a file), slug, or modId:fileId. Server pack is preferred when available.",
                 ),
                 param_int(
                     "memory_mb",
@@ -470,8 +634,20 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                     "25565 (leave blank for auto)",
                     "TCP port to bind. Use 0 or leave blank to auto-assign a free port.",
                 ),
+                TemplateParam {
+                    advanced: true,
+                    ..param_secret(
+                        "curseforge_api_key",
+                        "CurseForge API key",
+                        false,
+                        "",
+                        "Defaults to the key in control-plane settings.",
+                    )
+                },
             ],
-            graceful_stdin: Some("stop\n".to_string()),
+            graceful_stop: graceful_stop("stop\n", &[]),
+            ready_log_markers: Vec::new(),
+            needs_udp_and_tcp: false,
         },
         ProcessTemplate {
             template_id: "terraria:vanilla".to_string(),
@@ -531,6 +707,25 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                     "1",
                     "1=Small, 2=Medium, 3=Large. Only used when auto-creating a new world.",
                 ),
+                param_int(
+                    "difficulty",
+                    "Difficulty",
+                    false,
+                    "0",
+                    0,
+                    3,
+                    "0",
+                    "0=Classic, 1=Expert, 2=Master, 3=Journey. Only used when auto-creating a new world.",
+                ),
+                param_string(
+                    "motd",
+                    "Message of the day",
+                    false,
+                    "Alloy Terraria server",
+                    Vec::new(),
+                    "Alloy Terraria server",
+                    "Shown to players when they join.",
+                ),
                 param_secret(
                     "password",
                     "Password",
@@ -539,7 +734,104 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                     "Optional server password for joining players.",
                 ),
             ],
-            graceful_stdin: Some("exit\n".to_string()),
+            graceful_stop: graceful_stop("exit\n", &["saving world", "world saved"]),
+            ready_log_markers: Vec::new(),
+            needs_udp_and_tcp: false,
+        },
+        ProcessTemplate {
+            template_id: "terraria:tmodloader".to_string(),
+            display_name: "Terraria: tModLoader".to_string(),
+            // Placeholder; spawn spec is prepared by the terraria_tmodloader module.
+            command: "./start-tModLoaderServer.sh".to_string(),
+            args: vec![],
+            params: vec![
+                param_string(
+                    "version",
+                    "tModLoader version",
+                    false,
+                    "latest",
+                    Vec::new(),
+                    "latest",
+                    "latest, or a tModLoader GitHub release tag like v2024.05.3.0.",
+                ),
+                param_string(
+                    "mods",
+                    "Mods",
+                    false,
+                    "",
+                    Vec::new(),
+                    "2824688072, https://example.com/MyMod.tmod",
+                    "Workshop ids or links, or direct .tmod URLs (comma or whitespace separated). Listed mods are installed and enabled.",
+                ),
+                param_int(
+                    "port",
+                    "Port",
+                    false,
+                    "0",
+                    1024,
+                    65535,
+                    "7777 (leave blank for auto)",
+                    "TCP port to bind. Use 0 or leave blank to auto-assign a free port.",
+                ),
+                param_int(
+                    "max_players",
+                    "Max players",
+                    false,
+                    "8",
+                    1,
+                    255,
+                    "8",
+                    "Maximum number of players.",
+                ),
+                param_string(
+                    "world_name",
+                    "World name",
+                    false,
+                    "world",
+                    Vec::new(),
+                    "world",
+                    "Used for world file name under worlds/ (letters, digits, '-', '_' and '.' only).",
+                ),
+                param_int(
+                    "world_size",
+                    "World size",
+                    false,
+                    "1",
+                    1,
+                    3,
+                    "1",
+                    "1=Small, 2=Medium, 3=Large. Only used when auto-creating a new world.",
+                ),
+                param_int(
+                    "difficulty",
+                    "Difficulty",
+                    false,
+                    "0",
+                    0,
+                    3,
+                    "0",
+                    "0=Classic, 1=Expert, 2=Master, 3=Journey. Only used when auto-creating a new world.",
+                ),
+                param_string(
+                    "motd",
+                    "Message of the day",
+                    false,
+                    "Alloy Terraria server",
+                    Vec::new(),
+                    "Alloy Terraria server",
+                    "Shown to players when they join.",
+                ),
+                param_secret(
+                    "password",
+                    "Password",
+                    false,
+                    "",
+                    "Optional server password for joining players.",
+                ),
+            ],
+            graceful_stop: graceful_stop("exit\n", &["saving world", "world saved"]),
+            ready_log_markers: Vec::new(),
+            needs_udp_and_tcp: false,
         },
         ProcessTemplate {
             template_id: "dst:vanilla".to_string(),
@@ -610,14 +902,158 @@ pub fn list_templates() -> Vec<ProcessTemplate> {
                     "8766 (0 = auto)",
                     "Steam authentication port. Use 0 to auto-assign.",
                 ),
+                param_bool(
+                    "enable_caves",
+                    "Caves",
+                    false,
+                    false,
+                    "Also run a Caves shard, linked to the main world and stopped with it.",
+                ),
+                param_int_advanced(
+                    "caves_port",
+                    "Caves port (UDP)",
+                    false,
+                    "11000",
+                    0,
+                    65535,
+                    "11000 (0 = auto)",
+                    "UDP port of the Caves shard. Use 0 to auto-assign.",
+                ),
+                param_int_advanced(
+                    "caves_master_port",
+                    "Caves master port (UDP)",
+                    false,
+                    "27017",
+                    0,
+                    65535,
+                    "27017 (0 = auto)",
+                    "Steam master server port of the Caves shard. Use 0 to auto-assign.",
+                ),
+                param_int_advanced(
+                    "caves_auth_port",
+                    "Caves auth port (UDP)",
+                    false,
+                    "8767",
+                    0,
+                    65535,
+                    "8767 (0 = auto)",
+                    "Steam authentication port of the Caves shard. Use 0 to auto-assign.",
+                ),
+                param_string(
+                    "dst_mods",
+                    "Workshop mods",
+                    false,
+                    "",
+                    Vec::new(),
+                    "378160973, 458940297",
+                    "Steam Workshop ids, comma separated. They are downloaded before start and enabled in modoverrides.lua.",
+                ),
+                param_string_advanced(
+                    "steam_login_mode",
+                    "SteamCMD login",
+                    false,
+                    "auto",
+                    vec!["auto", "anonymous", "account"],
+                    "auto",
+                    "How SteamCMD installs the server. auto tries anonymous first and falls back to the SteamCMD account in Settings.",
+                ),
             ],
-            graceful_stdin: None,
+            graceful_stop: None,
+            // cluster.ini sets pause_when_empty, so an idle server logs "Sim paused" once loaded.
+            ready_log_markers: vec![
+                "Sim paused".to_string(),
+                "Server registered via geo DNS".to_string(),
+            ],
+            needs_udp_and_tcp: false,
         },
-    ];
+    ]
+}
+
+pub fn builtin_template_ids() -> Vec<String> {
+    builtin_templates()
+        .into_iter()
+        .map(|t| t.template_id)
+        .collect()
+}
+
+/// Built-in templates followed by the custom ones from `<data root>/templates`.
+pub fn list_templates() -> Vec<ProcessTemplate> {
+    let mut templates = builtin_templates();
+    templates.extend(crate::custom_templates::list());
 
     for t in &mut templates {
+        if t.command == "java" && !crate::custom_templates::is_custom(&t.template_id) {
+            t.params.push(param_string_advanced(
+                "java_path",
+                "Java binary",
+                false,
+                "",
+                vec![],
+                "/usr/lib/jvm/zulu8/bin/java",
+                "Java to run this instance with. Empty uses $JAVA_HOME/bin/java, then java on PATH.",
+            ));
+            t.params.push(param_int_advanced(
+                "min_memory_mb",
+                "Initial heap (MiB)",
+                false,
+                "0",
+                0,
+                65536,
+                "0 (same as max heap)",
+                "Initial heap size passed to Java (Xms). 0 starts at the max heap.",
+            ));
+            t.params.push(param_int_advanced(
+                "memory_pct",
+                "Heap (% of node RAM)",
+                false,
+                "0",
+                0,
+                90,
+                "0 (use memory_mb)",
+                "Sizes the max heap as a percentage of node RAM instead of memory_mb. 0 disables.",
+            ));
+            t.params.push(param_string_advanced(
+                "jvm_preset",
+                "JVM preset",
+                false,
+                "default",
+                vec!["default", "aikar", "g1gc"],
+                "default",
+                "GC tuning flags added before -jar. aikar = Aikar's flags (mcflags.emc.gs).",
+            ));
+            t.params.push(param_string_advanced(
+                "jvm_flags",
+                "Extra JVM flags",
+                false,
+                "",
+                vec![],
+                "-XX:+UseStringDeduplication",
+                "Space-separated JVM options added after the preset. -jar/-cp and -Xmx/-Xms are not allowed.",
+            ));
+        }
         if t.template_id != "demo:sleep" {
-            t.params.extend(sandbox_params());
+            t.params.push(param_string_advanced(
+                crate::config_overrides::PARAM,
+                "Config overrides",
+                false,
+                "",
+                vec![],
+                r#"{"bukkit.yml": "..."}"#,
+                "JSON object of instance-relative path to file content, written after the generated config on every start.",
+            ));
+            t.params.extend(sandbox_params(&t.template_id));
+        }
+        if t.template_id.starts_with("minecraft:") || t.template_id.starts_with("terraria:") {
+            t.params.push(TemplateParam {
+                advanced: true,
+                ..param_secret(
+                    "frp_config",
+                    "FRP config",
+                    false,
+                    "",
+                    "frpc config (INI or TOML) to expose the server port; its local port is set automatically.",
+                )
+            });
         }
     }
 
@@ -630,6 +1066,27 @@ pub fn find_template(template_id: &str) -> Option<ProcessTemplate> {
         .find(|t| t.template_id == template_id)
 }
 
+/// Fills params left unset (missing or blank) from node-wide defaults.
+/// Only keys the template declares are taken; built-in defaults still apply after this.
+pub fn merge_node_defaults<'a>(
+    template_id: &str,
+    params: &mut BTreeMap<String, String>,
+    defaults: impl IntoIterator<Item = (&'a String, &'a String)>,
+) {
+    let Some(t) = find_template(template_id) else {
+        return;
+    };
+    for (key, value) in defaults {
+        if value.trim().is_empty() || !t.params.iter().any(|p| &p.key == key) {
+            continue;
+        }
+        let unset = params.get(key).is_none_or(|v| v.trim().is_empty());
+        if unset {
+            params.insert(key.clone(), value.clone());
+        }
+    }
+}
+
 pub fn apply_params(
     mut t: ProcessTemplate,
     params: &BTreeMap<String, String>,
@@ -671,11 +1128,30 @@ pub fn apply_params(
         t.args = vec![secs.to_string()];
     }
 
+    if crate::custom_templates::is_custom(&t.template_id) {
+        t = crate::custom_templates::apply_params(t, params)?;
+    }
+
+    if t.template_id != "demo:sleep" {
+        crate::sandbox::validate_limit_params(params)?;
+        crate::config_overrides::parse(params)?;
+    }
+
+    if t.template_id == ONESHOT_TEMPLATE_ID {
+        let (command, args) = validate_oneshot_params(params)?;
+        t.command = command;
+        t.args = args;
+    }
+
     if t.template_id == "minecraft:vanilla" {
         // Contract-only commit: validate params early; runtime wiring is in later commits.
         let _ = crate::minecraft::validate_vanilla_params(params)?;
     }
 
+    if t.template_id == "minecraft:paper" {
+        let _ = crate::minecraft_paper::validate_params(params)?;
+    }
+
     if t.template_id == "minecraft:modrinth" {
         let _ = crate::minecraft_modrinth::validate_params(params)?;
     }
@@ -692,9 +1168,138 @@ pub fn apply_params(
         let _ = crate::terraria::validate_vanilla_params(params)?;
     }
 
+    if t.template_id == "terraria:tmodloader" {
+        let _ = crate::terraria_tmodloader::validate_params(params)?;
+    }
+
     if t.template_id == "dst:vanilla" {
         let _ = crate::dst::validate_vanilla_params(params)?;
+        crate::steamcmd_login::LoginMode::from_params(params)?;
     }
 
     Ok(t)
 }
+
+/// Instance directory a oneshot command runs in (the `instance_id` param).
+pub fn oneshot_instance_dir(params: &BTreeMap<String, String>) -> Option<std::path::PathBuf> {
+    let id = params.get("instance_id").map(|v| v.trim())?;
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        || id.starts_with('.')
+    {
+        return None;
+    }
+    Some(crate::minecraft::instance_dir(id))
+}
+
+fn validate_oneshot_params(
+    params: &BTreeMap<String, String>,
+) -> anyhow::Result<(String, Vec<String>)> {
+    let mut field_errors = BTreeMap::<String, String>::new();
+
+    match oneshot_instance_dir(params) {
+        None => {
+            field_errors.insert(
+                "instance_id".to_string(),
+                "Required. Only letters, digits, '-', '_' and '.' are allowed.".to_string(),
+            );
+        }
+        Some(dir) if !dir.is_dir() => {
+            field_errors.insert(
+                "instance_id".to_string(),
+                "Instance directory does not exist on this node.".to_string(),
+            );
+        }
+        Some(_) => {}
+    }
+
+    let command = params
+        .get("command")
+        .map(|v| v.trim())
+        .unwrap_or_default()
+        .to_string();
+    if command.is_empty() {
+        field_errors.insert("command".to_string(), "Required.".to_string());
+    } else if command.chars().any(|c| c.is_whitespace() || c == '\0') {
+        field_errors.insert(
+            "command".to_string(),
+            "Must be a single executable; put arguments in args.".to_string(),
+        );
+    }
+
+    let args = match shlex::split(params.get("args").map(String::as_str).unwrap_or_default()) {
+        Some(args) => args,
+        None => {
+            field_errors.insert(
+                "args".to_string(),
+                "Unbalanced quotes or trailing backslash.".to_string(),
+            );
+            Vec::new()
+        }
+    };
+
+    if !field_errors.is_empty() {
+        return Err(crate::error_payload::anyhow(
+            "invalid_param",
+            "invalid oneshot params",
+            Some(field_errors),
+            Some("Fix the highlighted fields, then try again.".to_string()),
+        ));
+    }
+
+    Ok((command, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_defaults_fill_only_unset_declared_params() {
+        let defaults: BTreeMap<String, String> = [
+            ("memory_mb", "4096"),
+            ("version", "1.20.1"),
+            ("not_a_param", "x"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let mut params = BTreeMap::from([
+            ("version".to_string(), "1.21.1".to_string()),
+            ("memory_mb".to_string(), " ".to_string()),
+        ]);
+
+        merge_node_defaults("minecraft:vanilla", &mut params, &defaults);
+
+        assert_eq!(params.get("version").map(String::as_str), Some("1.21.1"));
+        assert_eq!(params.get("memory_mb").map(String::as_str), Some("4096"));
+        assert!(!params.contains_key("not_a_param"));
+    }
+
+    #[test]
+    fn vanilla_template_advertises_param_schema() {
+        let t = find_template("minecraft:vanilla").unwrap();
+        let param = |key: &str| t.params.iter().find(|p| p.key == key).unwrap();
+
+        let version = param("version");
+        assert_eq!(version.r#type, ParamType::String as i32);
+        assert!(!version.required);
+        assert_eq!(version.default_value, "latest_release");
+        assert!(version.enum_values.contains(&"latest_snapshot".to_string()));
+
+        let memory = param("memory_mb");
+        assert_eq!(memory.r#type, ParamType::Int as i32);
+        assert_eq!(memory.default_value, "2048");
+        assert_eq!((memory.min_int, memory.max_int), (512, 65536));
+
+        let port = param("port");
+        assert_eq!(port.r#type, ParamType::Int as i32);
+        assert_eq!((port.min_int, port.max_int), (1024, 65535));
+        assert!(!port.secret && !port.advanced);
+
+        let frp = param("frp_config");
+        assert!(frp.secret && frp.advanced);
+    }
+}
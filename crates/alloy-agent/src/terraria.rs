@@ -8,6 +8,8 @@ pub struct VanillaParams {
     pub max_players: u32,
     pub world_name: String,
     pub world_size: u8,
+    pub difficulty: u8,
+    pub motd: String,
     pub password: Option<String>,
 }
 
@@ -28,6 +30,26 @@ pub fn validate_vanilla_params(params: &BTreeMap<String, String>) -> anyhow::Res
         );
     }
 
+    let params = parse_server_params(params, version, &mut field_errors);
+
+    if !field_errors.is_empty() {
+        return Err(crate::error_payload::anyhow(
+            "invalid_param",
+            "invalid terraria params",
+            Some(field_errors),
+            Some("Fix the highlighted fields, then try again.".to_string()),
+        ));
+    }
+    Ok(params)
+}
+
+/// Parses the server and world params shared by the vanilla and tModLoader
+/// templates, recording problems in `field_errors`.
+pub fn parse_server_params(
+    params: &BTreeMap<String, String>,
+    version: String,
+    field_errors: &mut BTreeMap<String, String>,
+) -> VanillaParams {
     let port = match params
         .get("port")
         .map(|v| v.trim())
@@ -123,25 +145,51 @@ pub fn validate_vanilla_params(params: &BTreeMap<String, String>) -> anyhow::Res
         );
     }
 
-    let password = params.get("password").cloned().filter(|s| !s.is_empty());
+    // Terraria uses: 0=Classic, 1=Expert, 2=Master, 3=Journey.
+    let difficulty = match params
+        .get("difficulty")
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+    {
+        None => 0,
+        Some(raw) => raw.parse::<u8>().unwrap_or(u8::MAX),
+    };
+    if difficulty > 3 {
+        field_errors.insert(
+            "difficulty".to_string(),
+            "Must be 0 (Classic), 1 (Expert), 2 (Master), or 3 (Journey).".to_string(),
+        );
+    }
 
-    if !field_errors.is_empty() {
-        return Err(crate::error_payload::anyhow(
-            "invalid_param",
-            "invalid terraria params",
-            Some(field_errors),
-            Some("Fix the highlighted fields, then try again.".to_string()),
-        ));
+    // Each value is one line of serverconfig.txt.
+    let single_line = |key: &str, value: &str, field_errors: &mut BTreeMap<String, String>| {
+        if value.contains(['\n', '\r']) {
+            field_errors.insert(key.to_string(), "Must be a single line.".to_string());
+        }
+    };
+    let motd = params
+        .get("motd")
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+        .unwrap_or("Alloy Terraria server")
+        .to_string();
+    single_line("motd", &motd, field_errors);
+
+    let password = params.get("password").cloned().filter(|s| !s.is_empty());
+    if let Some(pw) = &password {
+        single_line("password", pw, field_errors);
     }
 
-    Ok(VanillaParams {
+    VanillaParams {
         version,
         port,
         max_players,
         world_name,
         world_size,
+        difficulty,
+        motd,
         password,
-    })
+    }
 }
 
 pub fn data_root() -> PathBuf {
@@ -153,6 +201,34 @@ pub fn instance_dir(process_id: &str) -> PathBuf {
     data_root().join("instances").join(process_id)
 }
 
+/// Minimal, deterministic serverconfig.txt. Terraria autocreates the world
+/// when it does not exist yet; size and difficulty only apply then.
+pub fn render_server_config(
+    params: &VanillaParams,
+    world_path: &Path,
+    banlist_path: &Path,
+    world_exists: bool,
+) -> String {
+    let mut cfg = String::new();
+    cfg.push_str("secure=1\n");
+    cfg.push_str("upnp=0\n");
+    cfg.push_str(&format!("port={}\n", params.port));
+    cfg.push_str(&format!("maxplayers={}\n", params.max_players));
+    cfg.push_str("npcstream=60\n");
+    cfg.push_str(&format!("motd={}\n", params.motd));
+    cfg.push_str(&format!("banlist={}\n", banlist_path.display()));
+    if let Some(pw) = &params.password {
+        cfg.push_str(&format!("password={}\n", pw));
+    }
+    cfg.push_str(&format!("world={}\n", world_path.display()));
+    cfg.push_str(&format!("worldname={}\n", params.world_name));
+    if !world_exists {
+        cfg.push_str(&format!("autocreate={}\n", params.world_size));
+        cfg.push_str(&format!("difficulty={}\n", params.difficulty));
+    }
+    cfg
+}
+
 pub fn ensure_vanilla_instance_layout(
     instance_dir: &Path,
     params: &VanillaParams,
@@ -181,26 +257,7 @@ pub fn ensure_vanilla_instance_layout(
         .join("worlds")
         .join(format!("{}.wld", params.world_name));
     let banlist_path = config_dir_abs.join("banlist.txt");
-
-    // Minimal, deterministic serverconfig.
-    // NOTE: Terraria will autocreate if the world file does not exist.
-    let mut cfg = String::new();
-    cfg.push_str("secure=1\n");
-    cfg.push_str("upnp=0\n");
-    cfg.push_str(&format!("port={}\n", params.port));
-    cfg.push_str(&format!("maxplayers={}\n", params.max_players));
-    cfg.push_str("npcstream=60\n");
-    cfg.push_str("motd=Alloy Terraria server\n");
-    cfg.push_str(&format!("banlist={}\n", banlist_path.display()));
-    if let Some(pw) = &params.password {
-        cfg.push_str(&format!("password={}\n", pw));
-    }
-    cfg.push_str(&format!("world={}\n", world_path.display()));
-    cfg.push_str(&format!("worldname={}\n", params.world_name));
-    if !world_path.exists() {
-        cfg.push_str(&format!("autocreate={}\n", params.world_size));
-        cfg.push_str("difficulty=0\n");
-    }
+    let cfg = render_server_config(params, &world_path, &banlist_path, world_path.exists());
 
     // Persist config and banlist under instance config/.
     let config_dir = instance_dir.join("config");
@@ -213,3 +270,66 @@ pub fn ensure_vanilla_instance_layout(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, path::Path};
+
+    use super::{render_server_config, validate_vanilla_params};
+
+    fn params(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn server_config_renders_structured_params() {
+        let p = validate_vanilla_params(&params(&[
+            ("max_players", "16"),
+            ("difficulty", "2"),
+            ("world_size", "3"),
+            ("motd", " Welcome! "),
+            ("password", "hunter2"),
+        ]))
+        .unwrap();
+        let world = Path::new("/srv/worlds/world.wld");
+        let banlist = Path::new("/srv/config/banlist.txt");
+
+        let cfg = render_server_config(&p, world, banlist, false);
+        for line in [
+            "maxplayers=16",
+            "motd=Welcome!",
+            "password=hunter2",
+            "autocreate=3",
+            "difficulty=2",
+        ] {
+            assert!(cfg.lines().any(|l| l == line), "{line} missing:\n{cfg}");
+        }
+
+        // Size and difficulty only matter while creating the world.
+        let cfg = render_server_config(&p, world, banlist, true);
+        assert!(!cfg.contains("autocreate=") && !cfg.contains("difficulty="));
+
+        let redacted = crate::process_manager::redact_params(params(&[("password", "hunter2")]));
+        assert_eq!(redacted["password"], "<redacted>");
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let err = validate_vanilla_params(&params(&[
+            ("max_players", "0"),
+            ("difficulty", "4"),
+            ("world_size", "9"),
+            ("motd", "hi\nport=1"),
+        ]))
+        .unwrap_err();
+        let fields = crate::error_payload::decode(&err)
+            .and_then(|p| p.field_errors)
+            .unwrap();
+        for key in ["max_players", "difficulty", "world_size", "motd"] {
+            assert!(fields.contains_key(key), "{key}: {fields:?}");
+        }
+    }
+}
@@ -3,99 +3,17 @@
 use std::{
     collections::HashMap,
     fs,
-    io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, OnceLock},
-    time::Duration,
 };
 
 use anyhow::Context;
-use futures_util::StreamExt;
-use reqwest::Url;
 use tokio::sync::Mutex;
 
-use crate::minecraft_download::DownloadReport;
+use crate::download::download_with_resume;
+use crate::download_cache::{verify_cached_zip, write_sha256_sidecar};
 
-fn download_chunk_threshold(total_bytes: u64) -> u64 {
-    if total_bytes >= 2 * 1024 * 1024 * 1024 {
-        8 * 1024 * 1024
-    } else if total_bytes >= 512 * 1024 * 1024 {
-        4 * 1024 * 1024
-    } else {
-        1024 * 1024
-    }
-}
-
-async fn download_zip_with_progress<F>(
-    url: Url,
-    expected_size: Option<u64>,
-    mut on_progress: F,
-) -> anyhow::Result<(Vec<u8>, DownloadReport)>
-where
-    F: FnMut(u64, u64, u64) + Send,
-{
-    let resp = http_client()
-        .get(url)
-        .send()
-        .await
-        .context("download terraria server zip")?
-        .error_for_status()
-        .context("download terraria server zip (status)")?;
-
-    let total_bytes = expected_size.or(resp.content_length()).unwrap_or(0);
-    let threshold = download_chunk_threshold(total_bytes.max(1));
-    let mut stream = resp.bytes_stream();
-    let mut out: Vec<u8> = Vec::new();
-    let reserve_cap = total_bytes.min(512 * 1024 * 1024) as usize;
-    if reserve_cap > 0 {
-        out.reserve(reserve_cap);
-    }
-
-    let started_at = std::time::Instant::now();
-    let mut last_emit_bytes = 0u64;
-    let mut last_emit_at = started_at;
-    let mut downloaded_bytes = 0u64;
-
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.context("read terraria server zip body chunk")?;
-        downloaded_bytes = downloaded_bytes.saturating_add(chunk.len() as u64);
-        out.extend_from_slice(&chunk);
-
-        let now = std::time::Instant::now();
-        let should_emit = downloaded_bytes.saturating_sub(last_emit_bytes) >= threshold
-            || now.duration_since(last_emit_at) >= Duration::from_millis(300);
-        if should_emit {
-            let elapsed = now.duration_since(started_at).as_secs_f64();
-            let speed = if elapsed > 0.0 {
-                (downloaded_bytes as f64 / elapsed).round() as u64
-            } else {
-                0
-            };
-            let total = total_bytes.max(downloaded_bytes);
-            on_progress(downloaded_bytes, total, speed);
-            last_emit_bytes = downloaded_bytes;
-            last_emit_at = now;
-        }
-    }
-
-    let elapsed = started_at.elapsed().as_secs_f64();
-    let speed = if elapsed > 0.0 {
-        (downloaded_bytes as f64 / elapsed).round() as u64
-    } else {
-        0
-    };
-    let total = total_bytes.max(downloaded_bytes);
-    on_progress(downloaded_bytes, total, speed);
-
-    Ok((
-        out,
-        DownloadReport {
-            downloaded_bytes,
-            total_bytes: total,
-            speed_bytes_per_sec: speed,
-        },
-    ))
-}
+const MAX_ZIP_BYTES: u64 = 1024 * 1024 * 1024;
 
 pub struct ResolvedServerZip {
     pub version_id: String,
@@ -115,7 +33,7 @@ pub fn cache_dir() -> PathBuf {
         .join("vanilla")
 }
 
-fn mark_last_used(entry_dir: &std::path::Path) {
+pub(crate) fn mark_last_used(entry_dir: &std::path::Path) {
     let path = entry_dir.join(".last_used");
     let now_ms = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
@@ -130,24 +48,13 @@ fn download_locks() -> &'static std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>
     LOCKS.get_or_init(|| std::sync::Mutex::new(HashMap::new()))
 }
 
-fn lock_for(key: &str) -> Arc<Mutex<()>> {
+pub(crate) fn lock_for(key: &str) -> Arc<Mutex<()>> {
     let mut map = download_locks().lock().unwrap_or_else(|e| e.into_inner());
     map.entry(key.to_string())
         .or_insert_with(|| Arc::new(Mutex::new(())))
         .clone()
 }
 
-fn http_client() -> &'static reqwest::Client {
-    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
-    CLIENT.get_or_init(|| {
-        reqwest::Client::builder()
-            .user_agent("alloy-agent")
-            .timeout(Duration::from_secs(15 * 60))
-            .build()
-            .expect("failed to build reqwest client")
-    })
-}
-
 pub fn resolve_server_zip(version: &str) -> anyhow::Result<ResolvedServerZip> {
     // Official Re-Logic endpoint pattern:
     // https://terraria.org/api/download/pc-dedicated-server/terraria-server-<version>.zip
@@ -168,16 +75,27 @@ pub async fn ensure_server_zip(resolved: &ResolvedServerZip) -> anyhow::Result<P
 }
 
 pub async fn ensure_server_zip_with_progress<F>(
+    resolved: &ResolvedServerZip,
+    on_progress: Option<F>,
+) -> anyhow::Result<PathBuf>
+where
+    F: FnMut(u64, u64, u64) + Send,
+{
+    ensure_server_zip_in(&cache_dir(), resolved, on_progress).await
+}
+
+async fn ensure_server_zip_in<F>(
+    cache_root: &Path,
     resolved: &ResolvedServerZip,
     mut on_progress: Option<F>,
 ) -> anyhow::Result<PathBuf>
 where
     F: FnMut(u64, u64, u64) + Send,
 {
-    let zip_path = cache_dir()
+    let zip_path = cache_root
         .join(&resolved.version_id)
         .join(format!("terraria-server-{}.zip", resolved.version_id));
-    if zip_path.exists() {
+    if verify_cached_zip(&zip_path).await {
         if let Some(dir) = zip_path.parent() {
             mark_last_used(dir);
         }
@@ -191,7 +109,7 @@ where
     let lock_key = format!("terraria:vanilla:{}", resolved.version_id);
     let lock = lock_for(&lock_key);
     let _guard = lock.lock().await;
-    if zip_path.exists() {
+    if verify_cached_zip(&zip_path).await {
         if let Some(dir) = zip_path.parent() {
             mark_last_used(dir);
         }
@@ -202,70 +120,21 @@ where
         return Ok(zip_path);
     }
 
-    fs::create_dir_all(zip_path.parent().unwrap())?;
-
-    let url = Url::parse(&resolved.zip_url)?;
-    let mut last_err: Option<anyhow::Error> = None;
-    let mut bytes: Option<Vec<u8>> = None;
-    let mut last_report = DownloadReport {
-        downloaded_bytes: 0,
-        total_bytes: 0,
-        speed_bytes_per_sec: 0,
-    };
-    for attempt in 1..=3_u32 {
-        let res: anyhow::Result<Vec<u8>> = (async {
-            let (bytes, report) = download_zip_with_progress(url.clone(), None, |downloaded, total, speed| {
-                last_report = DownloadReport {
-                    downloaded_bytes: downloaded,
-                    total_bytes: total,
-                    speed_bytes_per_sec: speed,
-                };
-                if let Some(cb) = on_progress.as_mut() {
-                    cb(downloaded, total, speed);
-                }
-            })
-            .await?;
-            last_report = report;
-            Ok(bytes)
-        })
-        .await;
-
-        match res {
-            Ok(b) => {
-                bytes = Some(b);
-                break;
+    download_with_resume(
+        &resolved.zip_url,
+        &zip_path,
+        MAX_ZIP_BYTES,
+        |downloaded, total, speed| {
+            if let Some(cb) = on_progress.as_mut() {
+                cb(downloaded, total, speed);
             }
-            Err(e) => {
-                last_err = Some(e);
-                if attempt < 3 {
-                    tokio::time::sleep(Duration::from_millis(
-                        200_u64.saturating_mul(2_u64.pow(attempt - 1)),
-                    ))
-                    .await;
-                }
-            }
-        }
-    }
-
-    let bytes =
-        bytes.ok_or_else(|| last_err.unwrap_or_else(|| anyhow::anyhow!("download failed")))?;
-
-    // No official first-party checksums are provided by Re-Logic for the ZIP.
-    // We store the bytes as-is and rely on TLS + stable URL pattern.
-
-    let tmp_path = zip_path.with_extension("tmp");
-    let mut f = fs::File::create(&tmp_path)?;
-    f.write_all(&bytes)?;
-    f.sync_all()?;
-    fs::rename(tmp_path, &zip_path)?;
-
-    if let Some(cb) = on_progress.as_mut() {
-        cb(
-            last_report.downloaded_bytes,
-            last_report.total_bytes.max(last_report.downloaded_bytes),
-            last_report.speed_bytes_per_sec,
-        );
-    }
+        },
+    )
+    .await
+    .context("download terraria server zip")?;
+    // Re-Logic publishes no checksums for the ZIP, so later cache hits are
+    // checked against the digest of this download.
+    write_sha256_sidecar(&zip_path).await?;
 
     if let Some(dir) = zip_path.parent() {
         mark_last_used(dir);
@@ -273,9 +142,11 @@ where
     Ok(zip_path)
 }
 
+/// `output`, when set, receives extraction progress lines.
 pub fn extract_linux_x64_to_cache(
     zip_path: &PathBuf,
     version_id: &str,
+    output: Option<tokio::sync::mpsc::UnboundedSender<String>>,
 ) -> anyhow::Result<ExtractedLinuxServer> {
     // IMPORTANT: do NOT extract only the native binary.
     // Terraria expects sidecar files (DLLs / native libs) to exist in its working dir.
@@ -316,9 +187,15 @@ pub fn extract_linux_x64_to_cache(
 
     let f = fs::File::open(zip_path)?;
     let mut archive = zip::ZipArchive::new(f).context("open terraria server zip")?;
+    let mut progress = crate::archive::ExtractProgress::new(
+        output,
+        &format!("terraria {version_id} server"),
+        archive.len(),
+    );
 
     // Extract everything under */Linux/ into server_root.
     for i in 0..archive.len() {
+        progress.entry(i);
         let mut file = archive.by_index(i).context("read zip entry")?;
         let name = file.name();
 
@@ -370,6 +247,7 @@ pub fn extract_linux_x64_to_cache(
             }
         }
     }
+    progress.finish();
 
     if !bin_x86_64.exists() {
         anyhow::bail!(
@@ -389,3 +267,41 @@ pub fn extract_linux_x64_to_cache(
         },
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ResolvedServerZip, ensure_server_zip_in};
+
+    #[tokio::test]
+    async fn corrupt_cached_zip_is_downloaded_again() {
+        let body = b"server zip bytes".to_vec();
+        let (base, requests) = crate::download::serve_bytes(body.clone()).await;
+        let root = std::env::temp_dir().join(format!(
+            "alloy-agent-terraria-zip-cache-{}",
+            std::process::id()
+        ));
+        let resolved = ResolvedServerZip {
+            version_id: "1453".to_string(),
+            zip_url: format!("{base}/terraria-server-1453.zip"),
+        };
+        let progress = None::<fn(u64, u64, u64)>;
+
+        let zip = ensure_server_zip_in(&root, &resolved, progress)
+            .await
+            .unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        ensure_server_zip_in(&root, &resolved, progress)
+            .await
+            .unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        std::fs::write(&zip, &body[..3]).unwrap();
+        ensure_server_zip_in(&root, &resolved, progress)
+            .await
+            .unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        assert_eq!(std::fs::read(&zip).unwrap(), body);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,579 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use anyhow::Context;
+use reqwest::Url;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::download::download_with_resume;
+use crate::download_cache::{verify_cached_zip, write_sha256_sidecar};
+use crate::steamcmd_login::SteamcmdAuth;
+use crate::terraria::{self, VanillaParams};
+use crate::terraria_download::{lock_for, mark_last_used};
+
+const RELEASES_API: &str = "https://api.github.com/repos/tModLoader/tModLoader/releases";
+const RELEASE_ASSET: &str = "tModLoader.zip";
+const MAX_ZIP_BYTES: u64 = 1024 * 1024 * 1024;
+const MAX_MOD_BYTES: u64 = 512 * 1024 * 1024;
+
+/// tModLoader's own Steam app; its workshop mods are published under it.
+const WORKSHOP_APP_ID: &str = "1281930";
+
+const LEGACY_BIN: &str = "tModLoaderServer.bin.x86_64";
+const START_SCRIPT: &str = "start-tModLoaderServer.sh";
+
+#[derive(Debug, Clone)]
+pub struct TmodParams {
+    /// `server.version` is `latest` or a release tag like `v2024.05.3.0`.
+    pub server: VanillaParams,
+    pub mods: Vec<ModSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModSource {
+    Workshop(String),
+    Url(String),
+}
+
+/// Numeric components of a tModLoader version, with or without the `v`.
+fn version_key(raw: &str) -> Option<Vec<u64>> {
+    let raw = raw.trim();
+    let raw = raw.strip_prefix('v').unwrap_or(raw);
+    let parts = raw
+        .split('.')
+        .map(|p| p.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    (!parts.is_empty()).then_some(parts)
+}
+
+fn parse_version(raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    if raw.is_empty() || raw.eq_ignore_ascii_case("latest") {
+        return Ok("latest".to_string());
+    }
+    match version_key(raw) {
+        Some(parts) if (2..=4).contains(&parts.len()) => {
+            Ok(format!("v{}", raw.strip_prefix('v').unwrap_or(raw)))
+        }
+        _ => Err("Use latest, or a tModLoader release tag like v2024.05.3.0.".to_string()),
+    }
+}
+
+fn parse_mods(raw: &str) -> Result<Vec<ModSource>, String> {
+    let mut out = Vec::<ModSource>::new();
+    for entry in raw
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+    {
+        let source = if entry.chars().all(|c| c.is_ascii_digit()) {
+            ModSource::Workshop(entry.to_string())
+        } else if entry.starts_with("http://") || entry.starts_with("https://") {
+            let url = Url::parse(entry).map_err(|e| format!("Invalid URL {entry}: {e}."))?;
+            let workshop_id = url
+                .host_str()
+                .is_some_and(|h| h.ends_with("steamcommunity.com"))
+                .then(|| url.query_pairs().find(|(k, _)| k == "id"))
+                .flatten()
+                .map(|(_, v)| v.into_owned())
+                .filter(|v| !v.is_empty() && v.chars().all(|c| c.is_ascii_digit()));
+            if let Some(id) = workshop_id {
+                ModSource::Workshop(id)
+            } else if url.path().to_ascii_lowercase().ends_with(".tmod") {
+                ModSource::Url(entry.to_string())
+            } else {
+                return Err(format!(
+                    "{entry} is neither a workshop link nor a direct .tmod URL."
+                ));
+            }
+        } else {
+            return Err(format!(
+                "{entry} is not a workshop id, workshop link or .tmod URL."
+            ));
+        };
+        if !out.contains(&source) {
+            out.push(source);
+        }
+    }
+    Ok(out)
+}
+
+pub fn validate_params(params: &BTreeMap<String, String>) -> anyhow::Result<TmodParams> {
+    let mut field_errors = BTreeMap::<String, String>::new();
+
+    let raw_version = params.get("version").map(String::as_str).unwrap_or("");
+    let version = parse_version(raw_version).unwrap_or_else(|msg| {
+        field_errors.insert("version".to_string(), msg);
+        "latest".to_string()
+    });
+    let server = terraria::parse_server_params(params, version, &mut field_errors);
+
+    let mods =
+        parse_mods(params.get("mods").map(String::as_str).unwrap_or("")).unwrap_or_else(|msg| {
+            field_errors.insert("mods".to_string(), msg);
+            Vec::new()
+        });
+
+    if !field_errors.is_empty() {
+        return Err(crate::error_payload::anyhow(
+            "invalid_param",
+            "invalid tmodloader params",
+            Some(field_errors),
+            Some("Fix the highlighted fields, then try again.".to_string()),
+        ));
+    }
+    Ok(TmodParams { server, mods })
+}
+
+fn cache_dir() -> PathBuf {
+    terraria::data_root()
+        .join("cache")
+        .join("terraria")
+        .join("tmodloader")
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent("alloy-agent")
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("failed to build reqwest client")
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    #[serde(default)]
+    assets: Vec<GithubAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedRelease {
+    pub tag: String,
+    pub zip_url: String,
+}
+
+fn parse_release(body: &str) -> anyhow::Result<ResolvedRelease> {
+    let release: GithubRelease = serde_json::from_str(body).context("parse github release")?;
+    if version_key(&release.tag_name).is_none() {
+        anyhow::bail!("unexpected tModLoader release tag {:?}", release.tag_name);
+    }
+    let asset = release
+        .assets
+        .iter()
+        .find(|a| a.name == RELEASE_ASSET)
+        .with_context(|| format!("release {} has no {RELEASE_ASSET}", release.tag_name))?;
+    Ok(ResolvedRelease {
+        tag: release.tag_name.clone(),
+        zip_url: asset.browser_download_url.clone(),
+    })
+}
+
+/// Looks up the GitHub release for `version` (`latest` or a tag).
+pub async fn resolve_release(version: &str) -> anyhow::Result<ResolvedRelease> {
+    let url = if version == "latest" {
+        format!("{RELEASES_API}/latest")
+    } else {
+        format!("{RELEASES_API}/tags/{version}")
+    };
+    let resp = http_client()
+        .get(&url)
+        .header("accept", "application/vnd.github+json")
+        .send()
+        .await
+        .context("query github releases")?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        anyhow::bail!("tModLoader release {version} not found");
+    }
+    let body = resp.error_for_status()?.text().await?;
+    parse_release(&body)
+}
+
+pub async fn ensure_release_zip_with_progress<F>(
+    resolved: &ResolvedRelease,
+    mut on_progress: Option<F>,
+) -> anyhow::Result<PathBuf>
+where
+    F: FnMut(u64, u64, u64) + Send,
+{
+    let dir = cache_dir().join(&resolved.tag);
+    let zip_path = dir.join(RELEASE_ASSET);
+    let lock = lock_for(&format!("terraria:tmodloader:{}", resolved.tag));
+    let _guard = lock.lock().await;
+    if !verify_cached_zip(&zip_path).await {
+        download_with_resume(
+            &resolved.zip_url,
+            &zip_path,
+            MAX_ZIP_BYTES,
+            |downloaded, total, speed| {
+                if let Some(cb) = on_progress.as_mut() {
+                    cb(downloaded, total, speed);
+                }
+            },
+        )
+        .await
+        .context("download tModLoader release")?;
+        write_sha256_sidecar(&zip_path).await?;
+    } else if let Some(cb) = on_progress.as_mut() {
+        let size = fs::metadata(&zip_path).map(|m| m.len()).unwrap_or(0);
+        cb(size, size, 0);
+    }
+    mark_last_used(&dir);
+    Ok(zip_path)
+}
+
+/// Extracts the release next to its zip (once) and returns the server root.
+/// `output`, when set, receives extraction progress lines.
+pub fn extract_release_to_cache(
+    zip_path: &Path,
+    tag: &str,
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<PathBuf> {
+    let dir = cache_dir().join(tag);
+    let server_root = dir.join("server");
+    if detect_launch_spec(&server_root).is_ok() {
+        mark_last_used(&dir);
+        return Ok(server_root);
+    }
+
+    // Extract beside the final dir so an interrupted run never looks complete.
+    let staging = dir.join("server.partial");
+    let _ = fs::remove_dir_all(&staging);
+    let _ = fs::remove_dir_all(&server_root);
+    crate::archive::extract_zip_safely_with_output(zip_path, &staging, output)
+        .context("extract tModLoader release")?;
+    mark_executables(&staging)?;
+    fs::rename(&staging, &server_root)?;
+    detect_launch_spec(&server_root)?;
+    mark_last_used(&dir);
+    Ok(server_root)
+}
+
+/// Zip entries lose their mode bits; restore them on the launchers.
+fn mark_executables(dir: &Path) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            mark_executables(&path)?;
+            continue;
+        }
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        #[cfg(unix)]
+        if name.ends_with(".sh") || name == LEGACY_BIN {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+        }
+    }
+    Ok(())
+}
+
+/// How to start the server found in an extracted release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchSpec {
+    pub exec: String,
+    /// Go before the `-config`/`-modpath` args.
+    pub args: Vec<String>,
+    /// Prepended to LD_LIBRARY_PATH.
+    pub library_dirs: Vec<PathBuf>,
+    /// Native binaries get the ldd check; the start script fetches its own
+    /// .NET runtime on first run.
+    pub native: bool,
+}
+
+/// Prefers the native binary of 1.3-era releases, else the .NET start script
+/// current releases ship.
+pub fn detect_launch_spec(server_root: &Path) -> anyhow::Result<LaunchSpec> {
+    let legacy = server_root.join(LEGACY_BIN);
+    if legacy.is_file() {
+        return Ok(LaunchSpec {
+            exec: legacy.display().to_string(),
+            args: Vec::new(),
+            library_dirs: vec![server_root.join("lib64"), server_root.to_path_buf()],
+            native: true,
+        });
+    }
+    let script = server_root.join(START_SCRIPT);
+    if script.is_file() {
+        return Ok(LaunchSpec {
+            exec: "bash".to_string(),
+            // Without -steam/-nosteam the script prompts on stdin.
+            args: vec![script.display().to_string(), "-nosteam".to_string()],
+            library_dirs: vec![
+                server_root.join("Libraries").join("Native").join("Linux"),
+                server_root.to_path_buf(),
+            ],
+            native: false,
+        });
+    }
+    anyhow::bail!(
+        "no {LEGACY_BIN} or {START_SCRIPT} in {}",
+        server_root.display()
+    )
+}
+
+/// Picks the `.tmod` of a workshop item built for `tml_version`: the newest
+/// version folder not newer than it, else a `.tmod` at the item root.
+fn pick_workshop_tmod(item_dir: &Path, tml_version: &str) -> Option<PathBuf> {
+    let tml = version_key(tml_version).unwrap_or_default();
+    let tmod_in = |dir: &Path| {
+        fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .map(|e| e.path())
+            .find(|p| p.extension().is_some_and(|ext| ext == "tmod") && p.is_file())
+    };
+    let mut folders: Vec<(Vec<u64>, PathBuf)> = fs::read_dir(item_dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .filter_map(|p| {
+            let key = version_key(p.file_name()?.to_str()?)?;
+            let cmp_len = key.len().min(tml.len());
+            (tml.is_empty() || key[..cmp_len] <= tml[..cmp_len]).then_some((key, p))
+        })
+        .collect();
+    folders.sort();
+    folders
+        .iter()
+        .rev()
+        .find_map(|(_, dir)| tmod_in(dir))
+        .or_else(|| tmod_in(item_dir))
+}
+
+fn url_file_name(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let name = url.path_segments()?.next_back()?.to_string();
+    (name.len() > ".tmod".len()
+        && !name.starts_with('.')
+        && name.to_ascii_lowercase().ends_with(".tmod"))
+    .then_some(name)
+}
+
+/// Puts the `mods` into `instance_dir/mods` and lists them in its
+/// `enabled.json`, which is what tModLoader loads. Returns the mod names.
+pub async fn install_mods(
+    instance_dir: &Path,
+    tml_version: &str,
+    mods: &[ModSource],
+    auth: &SteamcmdAuth,
+    output: Option<mpsc::UnboundedSender<String>>,
+) -> anyhow::Result<Vec<String>> {
+    let mods_dir = instance_dir.join("mods");
+    tokio::fs::create_dir_all(&mods_dir).await?;
+    let mod_error = |message: String| {
+        crate::error_payload::anyhow(
+            "download_failed",
+            message.clone(),
+            Some(BTreeMap::from([("mods".to_string(), message)])),
+            Some("Check the mod entry, then try again.".to_string()),
+        )
+    };
+
+    let workshop_ids: Vec<String> = mods
+        .iter()
+        .filter_map(|m| match m {
+            ModSource::Workshop(id) => Some(id.clone()),
+            ModSource::Url(_) => None,
+        })
+        .collect();
+    let workshop_content = if workshop_ids.is_empty() {
+        None
+    } else {
+        let ugc_dir = crate::dst_download::ensure_workshop_items(
+            &instance_dir.join("workshop"),
+            WORKSHOP_APP_ID,
+            &workshop_ids,
+            "mods",
+            auth,
+            output.clone(),
+        )
+        .await?;
+        Some(ugc_dir.join("content").join(WORKSHOP_APP_ID))
+    };
+
+    let mut names = Vec::<String>::new();
+    for source in mods {
+        let file = match source {
+            ModSource::Workshop(id) => {
+                let item_dir = workshop_content
+                    .as_ref()
+                    .map(|c| c.join(id))
+                    .unwrap_or_default();
+                let tmod = pick_workshop_tmod(&item_dir, tml_version).ok_or_else(|| {
+                    mod_error(format!(
+                        "workshop mod {id} has no .tmod for tModLoader {tml_version}"
+                    ))
+                })?;
+                let dest = mods_dir.join(tmod.file_name().unwrap_or_default());
+                tokio::fs::copy(&tmod, &dest).await?;
+                dest
+            }
+            ModSource::Url(url) => {
+                let name = url_file_name(url)
+                    .ok_or_else(|| mod_error(format!("{url} does not name a .tmod file")))?;
+                let dest = mods_dir.join(&name);
+                if !dest.is_file() {
+                    if let Some(output) = &output {
+                        let _ = output.send(format!("downloading {name}"));
+                    }
+                    download_with_resume(url, &dest, MAX_MOD_BYTES, |_, _, _| {})
+                        .await
+                        .map_err(|e| mod_error(format!("failed to download {url}: {e}")))?;
+                }
+                dest
+            }
+        };
+        if let Some(stem) = file.file_stem().and_then(|s| s.to_str()) {
+            names.push(stem.to_string());
+        }
+    }
+
+    tokio::fs::write(
+        mods_dir.join("enabled.json"),
+        serde_json::to_vec_pretty(&names)?,
+    )
+    .await?;
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{
+        ModSource, ResolvedRelease, detect_launch_spec, parse_release, pick_workshop_tmod,
+        validate_params,
+    };
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "alloy-agent-tmodloader-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn release_json_resolves_the_server_zip() {
+        let body = r#"{
+            "tag_name": "v2024.05.3.0",
+            "assets": [
+                {"name": "tModLoader.zip.sha256", "browser_download_url": "https://x/sha"},
+                {"name": "tModLoader.zip", "browser_download_url": "https://x/tModLoader.zip"}
+            ]
+        }"#;
+        assert_eq!(
+            parse_release(body).unwrap(),
+            ResolvedRelease {
+                tag: "v2024.05.3.0".to_string(),
+                zip_url: "https://x/tModLoader.zip".to_string(),
+            }
+        );
+
+        let no_asset = r#"{"tag_name": "v2024.05.3.0", "assets": []}"#;
+        assert!(parse_release(no_asset).is_err());
+        let bad_tag = r#"{"tag_name": "nightly", "assets": []}"#;
+        assert!(parse_release(bad_tag).is_err());
+    }
+
+    #[test]
+    fn version_and_mods_params_are_normalized() {
+        let params = BTreeMap::from([
+            ("version".to_string(), "2024.05.3.0".to_string()),
+            (
+                "mods".to_string(),
+                "2824688072, https://steamcommunity.com/sharedfiles/filedetails/?id=2619954303\n\
+                 https://example.com/files/MyMod.tmod 2824688072"
+                    .to_string(),
+            ),
+        ]);
+        let p = validate_params(&params).unwrap();
+        assert_eq!(p.server.version, "v2024.05.3.0");
+        assert_eq!(
+            p.mods,
+            vec![
+                ModSource::Workshop("2824688072".to_string()),
+                ModSource::Workshop("2619954303".to_string()),
+                ModSource::Url("https://example.com/files/MyMod.tmod".to_string()),
+            ]
+        );
+
+        let bad = BTreeMap::from([
+            ("version".to_string(), "nightly".to_string()),
+            (
+                "mods".to_string(),
+                "https://example.com/mod.zip".to_string(),
+            ),
+        ]);
+        let err = validate_params(&bad).unwrap_err();
+        let fields = crate::error_payload::decode(&err)
+            .and_then(|p| p.field_errors)
+            .unwrap();
+        assert!(fields.contains_key("version") && fields.contains_key("mods"));
+    }
+
+    #[test]
+    fn launch_spec_detects_native_binary_and_start_script() {
+        let legacy = temp_dir("legacy");
+        std::fs::write(legacy.join("tModLoaderServer.bin.x86_64"), b"").unwrap();
+        let spec = detect_launch_spec(&legacy).unwrap();
+        assert!(spec.native);
+        assert!(spec.exec.ends_with("tModLoaderServer.bin.x86_64"));
+        assert_eq!(spec.library_dirs[0], legacy.join("lib64"));
+
+        let current = temp_dir("current");
+        std::fs::write(current.join("start-tModLoaderServer.sh"), b"").unwrap();
+        let spec = detect_launch_spec(&current).unwrap();
+        assert!(!spec.native);
+        assert_eq!(spec.exec, "bash");
+        assert_eq!(spec.args[1], "-nosteam");
+        assert!(spec.library_dirs[0].ends_with("Libraries/Native/Linux"));
+
+        let empty = temp_dir("empty");
+        assert!(detect_launch_spec(&empty).is_err());
+
+        for dir in [legacy, current, empty] {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    #[test]
+    fn workshop_tmod_matches_the_tmodloader_version() {
+        let item = temp_dir("workshop");
+        for folder in ["2023.6", "2024.5", "2025.1"] {
+            std::fs::create_dir_all(item.join(folder)).unwrap();
+            std::fs::write(item.join(folder).join("Mod.tmod"), folder).unwrap();
+        }
+        let picked = pick_workshop_tmod(&item, "v2024.05.3.0").unwrap();
+        assert!(
+            picked.starts_with(item.join("2024.5")),
+            "{}",
+            picked.display()
+        );
+        let picked = pick_workshop_tmod(&item, "v2023.8.1.0").unwrap();
+        assert!(
+            picked.starts_with(item.join("2023.6")),
+            "{}",
+            picked.display()
+        );
+
+        let _ = std::fs::remove_dir_all(item);
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tonic::Status;
+
+const HANDOFF_FILE: &str = "update-handoff.json";
+// A handoff older than this is ignored; the updater is assumed to have failed.
+const HANDOFF_TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Copy)]
+struct DrainState {
+    applied: bool,
+}
+
+fn state() -> &'static Mutex<Option<DrainState>> {
+    static STATE: OnceLock<Mutex<Option<DrainState>>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(None))
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+pub(crate) fn is_draining() -> bool {
+    state().lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+pub(crate) fn begin() {
+    let mut s = state().lock().unwrap_or_else(|e| e.into_inner());
+    if s.is_none() {
+        *s = Some(DrainState { applied: false });
+    }
+}
+
+/// Leaves drain mode. Refused once ApplyUpdate has handed off to the updater.
+pub(crate) fn cancel() -> bool {
+    let mut s = state().lock().unwrap_or_else(|e| e.into_inner());
+    if s.is_some_and(|s| s.applied) {
+        return false;
+    }
+    *s = None;
+    true
+}
+
+pub(crate) fn mark_applied() {
+    let mut s = state().lock().unwrap_or_else(|e| e.into_inner());
+    *s = Some(DrainState { applied: true });
+}
+
+#[derive(Debug)]
+pub(crate) struct DrainingError;
+
+impl From<DrainingError> for Status {
+    fn from(_: DrainingError) -> Self {
+        Status::unavailable(crate::error_payload::encode(
+            "draining",
+            "agent is draining for an update and does not accept new starts",
+            None,
+            Some("Retry after the agent update completes.".to_string()),
+        ))
+    }
+}
+
+/// Rejects new starts while an update drain is in progress.
+pub(crate) fn ensure_accepting_starts() -> Result<(), DrainingError> {
+    if is_draining() {
+        return Err(DrainingError);
+    }
+    Ok(())
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Handoff {
+    created_at_unix_ms: u64,
+    // Container-backed processes left running for the next agent to re-adopt.
+    process_ids: Vec<String>,
+}
+
+fn handoff_path() -> PathBuf {
+    crate::minecraft::data_root().join(HANDOFF_FILE)
+}
+
+pub(crate) fn write_handoff(process_ids: &[String]) -> anyhow::Result<()> {
+    let path = handoff_path();
+    let tmp = path.with_extension("json.tmp");
+    let data = serde_json::to_vec_pretty(&Handoff {
+        created_at_unix_ms: now_unix_ms(),
+        process_ids: process_ids.to_vec(),
+    })?;
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Consumes the handoff left by the previous agent and returns the process
+/// ids whose containers must survive orphan cleanup. Stale handoffs are ignored.
+pub(crate) fn take_handoff() -> Vec<String> {
+    let path = handoff_path();
+    let Ok(raw) = std::fs::read(&path) else {
+        return Vec::new();
+    };
+    let _ = std::fs::remove_file(&path);
+    let Ok(handoff) = serde_json::from_slice::<Handoff>(&raw) else {
+        return Vec::new();
+    };
+    let age = now_unix_ms().saturating_sub(handoff.created_at_unix_ms);
+    if age > HANDOFF_TTL.as_millis() as u64 {
+        tracing::warn!(age_ms = age, "ignoring stale update handoff");
+        return Vec::new();
+    }
+    handoff.process_ids
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use crate::{minecraft_download, minecraft_modrinth, minecraft_paper};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionRequirements {
+    pub java_required: bool,
+    pub java_major: Option<u32>,
+    pub minecraft_version: Option<String>,
+    pub loader: Option<String>,
+}
+
+fn param<'a>(params: &'a BTreeMap<String, String>, key: &str) -> Option<&'a str> {
+    params.get(key).map(|v| v.trim()).filter(|v| !v.is_empty())
+}
+
+/// Resolves runtime requirements for a template without starting anything.
+/// Only the params that determine the runtime are read, so this works before
+/// the rest of the create form (EULA, port, memory) is filled in.
+pub async fn resolve(
+    template_id: &str,
+    params: &BTreeMap<String, String>,
+) -> anyhow::Result<VersionRequirements> {
+    let minecraft_version = match template_id {
+        "minecraft:vanilla" => param(params, "version")
+            .unwrap_or("latest_release")
+            .to_string(),
+        "minecraft:paper" => {
+            let version = param(params, "mc_version").unwrap_or("latest");
+            let version = minecraft_paper::resolve_mc_version(version)
+                .await
+                .map_err(|e| {
+                    crate::error_payload::anyhow(
+                        "download_failed",
+                        format!("failed to resolve paper version: {e}"),
+                        None,
+                        Some("Check network connectivity to api.papermc.io.".to_string()),
+                    )
+                })?;
+            let resolved = resolve_java(&version).await?;
+            return Ok(VersionRequirements {
+                java_required: true,
+                java_major: Some(resolved.java_major),
+                minecraft_version: Some(resolved.version_id),
+                loader: Some("paper".to_string()),
+            });
+        }
+        "minecraft:modrinth" => {
+            let source = minecraft_modrinth::pack_source(params)?;
+            let pack = minecraft_modrinth::inspect_pack(&source)
+                .await
+                .map_err(|e| {
+                    if crate::error_payload::decode(&e).is_some_and(|p| p.code == "invalid_param") {
+                        return e;
+                    }
+                    crate::error_payload::anyhow(
+                        "download_failed",
+                        format!("failed to resolve modpack: {e}"),
+                        None,
+                        Some("Check the modpack link and network connectivity.".to_string()),
+                    )
+                })?;
+            let resolved = resolve_java(&pack.minecraft).await?;
+            return Ok(VersionRequirements {
+                java_required: true,
+                java_major: Some(resolved.java_major),
+                minecraft_version: Some(resolved.version_id),
+                loader: Some(pack.loader),
+            });
+        }
+        // The Minecraft version is only known after the archive is unpacked.
+        "minecraft:import" | "minecraft:curseforge" => {
+            return Ok(VersionRequirements {
+                java_required: true,
+                ..Default::default()
+            });
+        }
+        _ => return Ok(VersionRequirements::default()),
+    };
+
+    let resolved = resolve_java(&minecraft_version).await?;
+    Ok(VersionRequirements {
+        java_required: true,
+        java_major: Some(resolved.java_major),
+        minecraft_version: Some(resolved.version_id),
+        loader: None,
+    })
+}
+
+async fn resolve_java(version: &str) -> anyhow::Result<minecraft_download::ResolvedServerJar> {
+    minecraft_download::resolve_server_jar(version)
+        .await
+        .map_err(|e| {
+            crate::error_payload::anyhow(
+                "download_failed",
+                format!("failed to resolve minecraft version metadata: {e}"),
+                None,
+                Some("Check network connectivity to Mojang piston-meta endpoints.".to_string()),
+            )
+        })
+}
+
+/// Whether `runtime_java` satisfies the requirements, plus a message for the UI.
+pub fn summarize(req: &VersionRequirements, runtime_java: Option<u32>) -> (bool, String) {
+    if !req.java_required {
+        return (true, "No Java runtime required.".to_string());
+    }
+    let Some(need) = req.java_major else {
+        let msg = match runtime_java {
+            Some(have) => format!(
+                "Java version is determined when the server is installed; this node runs Java {have}."
+            ),
+            None => "Requires Java, but no Java runtime was found on this node.".to_string(),
+        };
+        return (runtime_java.is_some(), msg);
+    };
+
+    let subject = match &req.minecraft_version {
+        Some(v) => format!("Minecraft {v} needs Java {need}"),
+        None => format!("Needs Java {need}"),
+    };
+    match runtime_java {
+        Some(have) if have == need => (
+            true,
+            format!("{subject}; will use the installed Java {have}."),
+        ),
+        Some(have) => (false, format!("{subject}, but this node runs Java {have}.")),
+        None => (
+            false,
+            format!("{subject}, but no Java runtime was found on this node."),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize_reports_match_and_gap() {
+        let req = VersionRequirements {
+            java_required: true,
+            java_major: Some(17),
+            minecraft_version: Some("1.20.1".to_string()),
+            loader: None,
+        };
+        let (ok, msg) = summarize(&req, Some(17));
+        assert!(ok);
+        assert_eq!(
+            msg,
+            "Minecraft 1.20.1 needs Java 17; will use the installed Java 17."
+        );
+
+        let (ok, msg) = summarize(&req, Some(21));
+        assert!(!ok);
+        assert!(msg.contains("this node runs Java 21"));
+
+        assert!(!summarize(&req, None).0);
+        assert!(summarize(&VersionRequirements::default(), None).0);
+    }
+}
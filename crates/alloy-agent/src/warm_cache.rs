@@ -0,0 +1,557 @@
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::download_progress::{self, UpdateArgs};
+use crate::minecraft_download::{self, ResolvedServerJar};
+use crate::minecraft_paper::{self, ResolvedPaperJar};
+use crate::steamcmd_login::{SteamLogin, SteamcmdAuth};
+use crate::{dst_download, minecraft_modrinth, terraria_download};
+
+/// Progress of one warm call in [`download_progress`], keyed by its warm id.
+#[derive(Debug, Clone)]
+pub(crate) struct WarmProgress {
+    id: String,
+}
+
+impl WarmProgress {
+    /// Uses `requested` when the caller supplied an id, else a synthetic one.
+    pub(crate) fn new(requested: &str) -> Self {
+        let requested = requested.trim();
+        let id = if requested.is_empty() {
+            format!("warm-{}", alloy_process::ProcessId::new().0)
+        } else {
+            requested.to_string()
+        };
+        Self { id }
+    }
+
+    pub(crate) fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn start(&self, stage: &str, message: impl Into<String>) {
+        download_progress::start(&self.id, stage, message, None);
+    }
+
+    fn report(&self, stage: &str, bytes: Option<(u64, u64, u64)>, message: impl Into<String>) {
+        download_progress::update(
+            &self.id,
+            UpdateArgs {
+                stage: Some(stage.to_string()),
+                downloaded_bytes: bytes.map(|b| b.0),
+                total_bytes: bytes.map(|b| b.1),
+                speed_bytes_per_sec: bytes.map(|b| b.2),
+                message: Some(message.into()),
+                done: Some(false),
+            },
+        );
+    }
+
+    fn finish(&self, message: impl Into<String>, (downloaded, total, speed): (u64, u64, u64)) {
+        download_progress::finish(&self.id, message, downloaded, total, speed);
+    }
+
+    /// Marks the warm failed and returns the matching error payload.
+    fn fail(&self, code: &str, message: String, hint: &str) -> anyhow::Error {
+        download_progress::fail(&self.id, message.clone());
+        crate::error_payload::anyhow(code, message, None, Some(hint.to_string()))
+    }
+
+    /// Like [`Self::fail`], but keeps `err` when it already carries a payload,
+    /// such as a Steam login failure naming the field to fix.
+    fn fail_with(
+        &self,
+        err: anyhow::Error,
+        code: &str,
+        context: &str,
+        hint: &str,
+    ) -> anyhow::Error {
+        match crate::error_payload::decode(&err) {
+            Some(payload) => {
+                download_progress::fail(&self.id, payload.message);
+                err
+            }
+            None => self.fail(code, format!("{context}: {err}"), hint),
+        }
+    }
+
+    /// Channel whose lines are reported under `stage`, for SteamCMD output.
+    fn lines(
+        &self,
+        stage: &'static str,
+    ) -> (mpsc::UnboundedSender<String>, tokio::task::JoinHandle<()>) {
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let progress = self.clone();
+        let forward = tokio::spawn(async move {
+            while let Some(line) = rx.recv().await {
+                progress.report(stage, None, line);
+            }
+        });
+        (tx, forward)
+    }
+}
+
+/// Cancel handles of warms in flight, keyed by warm id.
+fn in_flight() -> &'static Mutex<HashMap<String, oneshot::Sender<()>>> {
+    static IN_FLIGHT: OnceLock<Mutex<HashMap<String, oneshot::Sender<()>>>> = OnceLock::new();
+    IN_FLIGHT.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Runs `fut` until it finishes or [`cancel`] is called with the warm's id.
+/// Cancelling drops `fut`, which aborts the transfer and removes its partial
+/// file, then marks the warm failed with code `canceled`.
+pub(crate) async fn cancellable<T>(
+    progress: &WarmProgress,
+    fut: impl Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    let (tx, rx) = oneshot::channel();
+    {
+        let mut map = in_flight().lock().unwrap_or_else(|e| e.into_inner());
+        // Entries whose warm already ended have a closed sender.
+        map.retain(|_, tx| !tx.is_closed());
+        map.insert(progress.id.clone(), tx);
+    }
+    tokio::select! {
+        res = fut => res,
+        _ = rx => Err(progress.fail(
+            "canceled",
+            "download canceled".to_string(),
+            "Retry the download to start it again.",
+        )),
+    }
+}
+
+/// Cancels the warm with id `progress_id`; `false` if none is running.
+pub(crate) fn cancel(progress_id: &str) -> bool {
+    let tx = in_flight()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(progress_id.trim());
+    tx.is_some_and(|tx| tx.send(()).is_ok())
+}
+
+fn param<'a>(params: &'a BTreeMap<String, String>, key: &str, default: &'a str) -> &'a str {
+    params
+        .get(key)
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .unwrap_or(default)
+}
+
+/// Runs only the resolve and download steps of `template_id`, so the first
+/// real start finds its artifacts cached. Repeated or concurrent calls for the
+/// same artifact share the downloaders' per-artifact locks and download once.
+pub(crate) async fn warm(
+    template_id: &str,
+    params: &BTreeMap<String, String>,
+    progress: &WarmProgress,
+) -> anyhow::Result<String> {
+    match template_id {
+        "minecraft:vanilla" => {
+            let version = param(params, "version", "latest_release");
+            progress.start(
+                "resolve",
+                format!("resolving minecraft version {version}..."),
+            );
+            let resolved = minecraft_download::resolve_server_jar(version)
+                .await
+                .map_err(|e| {
+                    progress.fail(
+                        "download_failed",
+                        format!("failed to resolve minecraft server jar: {e}"),
+                        "Check network connectivity to Mojang piston-meta endpoints.",
+                    )
+                })?;
+            warm_minecraft_jar(&minecraft_download::cache_dir(), &resolved, progress).await
+        }
+        "minecraft:paper" => {
+            let version = param(params, "mc_version", "latest");
+            let build = match param(params, "build", "latest") {
+                "latest" => None,
+                raw => Some(raw.parse::<u32>().map_err(|_| {
+                    progress.fail(
+                        "invalid_param",
+                        format!("invalid paper build: {raw}"),
+                        "Use a Paper build number or latest.",
+                    )
+                })?),
+            };
+            progress.start("resolve", format!("resolving paper {version}..."));
+            let resolved = minecraft_paper::resolve_paper_jar(version, build)
+                .await
+                .map_err(|e| {
+                    progress.fail(
+                        "download_failed",
+                        format!("failed to resolve paper build: {e:#}"),
+                        "Check the version and build, and network connectivity to api.papermc.io.",
+                    )
+                })?;
+            warm_paper_jar(&resolved, progress).await
+        }
+        "minecraft:modrinth" => {
+            progress.start("resolve", "resolving modpack...");
+            let source = minecraft_modrinth::pack_source(params).map_err(|e| {
+                progress.fail(
+                    "invalid_param",
+                    format!("invalid modpack source: {e}"),
+                    "Provide a Modrinth project, version or .mrpack URL.",
+                )
+            })?;
+            progress.report("download", None, "downloading modpack...");
+            let pack = minecraft_modrinth::inspect_pack(&source)
+                .await
+                .map_err(|e| {
+                    progress.fail(
+                        "download_failed",
+                        format!("failed to download modpack: {e}"),
+                        "Try again; if it persists, clear cache and retry.",
+                    )
+                })?;
+            let message = format!(
+                "modpack cache warmed: minecraft={} loader={} {}",
+                pack.minecraft, pack.loader, pack.loader_version
+            );
+            progress.finish(message.clone(), (0, 0, 0));
+            Ok(message)
+        }
+        "terraria:vanilla" => warm_terraria(param(params, "version", "1453"), progress).await,
+        "dst:vanilla" => {
+            progress.start("install", "installing dst dedicated server via steamcmd...");
+            let auth = SteamcmdAuth::from_params(params).map_err(|e| {
+                progress.fail_with(
+                    e,
+                    "invalid_param",
+                    "invalid steam_login_mode",
+                    "Use auto, anonymous or account.",
+                )
+            })?;
+            let (tx, forward) = progress.lines("install");
+            let installed = dst_download::ensure_dst_server_with_output(Some(tx), &auth).await;
+            let _ = forward.await;
+            let installed = installed.map_err(|e| {
+                progress.fail_with(
+                    e,
+                    "download_failed",
+                    "failed to install dst dedicated server",
+                    "Check network connectivity to Steam, then try again.",
+                )
+            })?;
+            let message = format!(
+                "dst cache warmed: server_root={}",
+                installed.server_root.display()
+            );
+            progress.finish(message.clone(), (0, 0, 0));
+            Ok(message)
+        }
+        "steamcmd:auth" => {
+            let login = SteamLogin::from_params(params).ok_or_else(|| {
+                progress.fail(
+                    "invalid_param",
+                    "steam_username and steam_password are required".to_string(),
+                    "Enter the SteamCMD username and password.",
+                )
+            })?;
+            progress.start("login", "logging in to steam via steamcmd...");
+            let (tx, forward) = progress.lines("login");
+            let checked = dst_download::check_steam_login(&login, Some(tx)).await;
+            let _ = forward.await;
+            checked.map_err(|e| {
+                progress.fail_with(
+                    e,
+                    "steam_login_failed",
+                    "steamcmd login failed",
+                    "Check network connectivity to Steam, then try again.",
+                )
+            })?;
+            let message = "steam login ok";
+            progress.finish(message, (0, 0, 0));
+            Ok(message.to_string())
+        }
+        "demo:sleep" => {
+            let message = "no cache needed for demo:sleep";
+            progress.start("ready", message);
+            progress.finish(message, (0, 0, 0));
+            Ok(message.to_string())
+        }
+        _ => Err(crate::error_payload::anyhow(
+            "invalid_param",
+            format!("unknown template_id: {template_id}"),
+            None,
+            None,
+        )),
+    }
+}
+
+async fn warm_minecraft_jar(
+    cache_root: &Path,
+    resolved: &ResolvedServerJar,
+    progress: &WarmProgress,
+) -> anyhow::Result<String> {
+    let version = &resolved.version_id;
+    let mut last = (0, resolved.size, 0);
+    progress.report(
+        "download",
+        Some(last),
+        format!("downloading minecraft {version}..."),
+    );
+    let jar_path = minecraft_download::ensure_server_jar_in(
+        cache_root,
+        resolved,
+        Some(|downloaded: u64, total: u64, speed: u64| {
+            let total = total.max(resolved.size);
+            last = (downloaded, total, speed);
+            progress.report(
+                "download",
+                Some(last),
+                format!("downloading minecraft {version} ({downloaded}/{total})"),
+            );
+        }),
+    )
+    .await
+    .map_err(|e| {
+        progress.fail(
+            "download_failed",
+            format!("failed to download minecraft server jar: {e}"),
+            "Try again; if it persists, clear cache and retry.",
+        )
+    })?;
+
+    progress.finish(
+        format!("minecraft {version} ready"),
+        (resolved.size, resolved.size, last.2),
+    );
+    Ok(format!(
+        "minecraft cache warmed: version={version} sha1={} path={}",
+        resolved.sha1,
+        jar_path.display()
+    ))
+}
+
+async fn warm_paper_jar(
+    resolved: &ResolvedPaperJar,
+    progress: &WarmProgress,
+) -> anyhow::Result<String> {
+    let label = format!("paper {} build {}", resolved.mc_version, resolved.build);
+    let mut last = (0, 0, 0);
+    progress.report("download", Some(last), format!("downloading {label}..."));
+    let jar_path = minecraft_paper::ensure_paper_jar_with_progress(
+        resolved,
+        Some(|downloaded: u64, total: u64, speed: u64| {
+            last = (downloaded, total.max(downloaded), speed);
+            progress.report(
+                "download",
+                Some(last),
+                format!("downloading {label} ({downloaded}/{total})"),
+            );
+        }),
+    )
+    .await
+    .map_err(|e| {
+        progress.fail(
+            "download_failed",
+            format!("failed to download paper server jar: {e:#}"),
+            "Try again; if it persists, clear cache and retry.",
+        )
+    })?;
+
+    progress.finish(format!("{label} ready"), last);
+    Ok(format!(
+        "paper cache warmed: version={} build={} path={}",
+        resolved.mc_version,
+        resolved.build,
+        jar_path.display()
+    ))
+}
+
+async fn warm_terraria(version: &str, progress: &WarmProgress) -> anyhow::Result<String> {
+    progress.start(
+        "resolve",
+        format!("resolving terraria version {version}..."),
+    );
+    let resolved = terraria_download::resolve_server_zip(version).map_err(|e| {
+        progress.fail(
+            "download_failed",
+            format!("failed to resolve terraria server zip: {e}"),
+            "Check network connectivity, then try again.",
+        )
+    })?;
+    let version = &resolved.version_id;
+
+    let mut last = (0, 0, 0);
+    progress.report(
+        "download",
+        Some(last),
+        format!("downloading terraria {version}..."),
+    );
+    let zip_path = terraria_download::ensure_server_zip_with_progress(
+        &resolved,
+        Some(|downloaded: u64, total: u64, speed: u64| {
+            let total = total.max(downloaded);
+            last = (downloaded, total, speed);
+            progress.report(
+                "download",
+                Some(last),
+                format!("downloading terraria {version} ({downloaded}/{total})"),
+            );
+        }),
+    )
+    .await
+    .map_err(|e| {
+        progress.fail(
+            "download_failed",
+            format!("failed to download terraria server zip: {e}"),
+            "Try again; if it persists, clear cache and retry.",
+        )
+    })?;
+
+    progress.report(
+        "extract",
+        Some(last),
+        format!("extracting terraria {version} files..."),
+    );
+    let extracted = terraria_download::extract_linux_x64_to_cache(&zip_path, version, None)
+        .map_err(|e| {
+            progress.fail(
+                "download_failed",
+                format!("failed to extract terraria server: {e}"),
+                "Clear cache and retry extraction.",
+            )
+        })?;
+
+    progress.finish(format!("terraria {version} ready"), last);
+    Ok(format!(
+        "terraria cache warmed: version={version} zip_path={} server_root={}",
+        zip_path.display(),
+        extracted.server_root.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WarmProgress, cancel, cancellable, warm, warm_minecraft_jar};
+    use crate::minecraft_download::ResolvedServerJar;
+    use sha1::Digest;
+    use std::collections::BTreeMap;
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn concurrent_warms_of_one_jar_download_it_once() {
+        let body = vec![7u8; 256 * 1024];
+        let sha1 = hex::encode(sha1::Sha1::digest(&body));
+        let (base, requests) = crate::download::serve_bytes(body.clone()).await;
+        let root =
+            std::env::temp_dir().join(format!("alloy-agent-warm-cache-{}", std::process::id()));
+        let resolved = ResolvedServerJar {
+            version_id: "1.21".to_string(),
+            jar_url: format!("{base}/server.jar"),
+            sha1: sha1.clone(),
+            size: body.len() as u64,
+            java_major: 21,
+        };
+
+        let (a, b) = (WarmProgress::new(""), WarmProgress::new(""));
+        assert_ne!(a.id(), b.id());
+        let (ra, rb) = tokio::join!(
+            warm_minecraft_jar(&root, &resolved, &a),
+            warm_minecraft_jar(&root, &resolved, &b),
+        );
+        ra.unwrap();
+        rb.unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert_eq!(
+            std::fs::read(root.join(&sha1).join("server.jar")).unwrap(),
+            body
+        );
+        for progress in [&a, &b] {
+            let snap = crate::download_progress::get(progress.id()).unwrap();
+            assert!(snap.done);
+            assert_eq!(snap.stage, "ready");
+            assert_eq!(snap.downloaded_bytes, body.len() as u64);
+        }
+
+        // Warming again is a cache hit.
+        warm_minecraft_jar(&root, &resolved, &WarmProgress::new("again"))
+            .await
+            .unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_warm_aborts_the_transfer_and_removes_the_partial() {
+        // Advertises a huge body, sends the first 64 KiB and then stalls.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut sock, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 8192];
+                    let _ = sock.read(&mut buf).await;
+                    let mut resp = crate::download::http_response(
+                        "200 OK",
+                        &[("Content-Length", (1u64 << 30).to_string())],
+                        &[],
+                    );
+                    resp.extend(vec![1u8; 64 * 1024]);
+                    let _ = sock.write_all(&resp).await;
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                });
+            }
+        });
+        let root =
+            std::env::temp_dir().join(format!("alloy-agent-warm-cancel-{}", std::process::id()));
+        let resolved = ResolvedServerJar {
+            version_id: "1.21".to_string(),
+            jar_url: format!("{base}/server.jar"),
+            sha1: "0".repeat(40),
+            size: 1 << 30,
+            java_major: 21,
+        };
+        let partial = root.join(&resolved.sha1).join("server.jar.partial");
+
+        let progress = WarmProgress::new("warm-cancel-test");
+        let warm = tokio::spawn({
+            let (root, progress) = (root.clone(), progress.clone());
+            async move {
+                let warm = warm_minecraft_jar(&root, &resolved, &progress);
+                cancellable(&progress, warm).await
+            }
+        });
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !partial.exists() {
+            assert!(Instant::now() < deadline, "download never started");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let cancelled_at = Instant::now();
+        assert!(cancel("warm-cancel-test"));
+        let err = tokio::time::timeout(Duration::from_secs(2), warm)
+            .await
+            .expect("cancel did not stop the download")
+            .unwrap()
+            .unwrap_err();
+        assert!(cancelled_at.elapsed() < Duration::from_secs(2));
+        assert_eq!(crate::error_payload::decode(&err).unwrap().code, "canceled");
+        assert!(!partial.exists());
+        assert!(!cancel("warm-cancel-test"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn unknown_templates_are_rejected_as_invalid_params() {
+        let err = warm("nope:nothing", &BTreeMap::new(), &WarmProgress::new(""))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            crate::error_payload::decode(&err).unwrap().code,
+            "invalid_param"
+        );
+    }
+}
@@ -87,6 +87,7 @@ fn safe_to_retry_over_direct(method: &str) -> bool {
     matches!(
         method,
         "/alloy.agent.v1.AgentHealthService/Check"
+            | "/alloy.agent.v1.AgentHealthService/GetNodeResources"
             | "/alloy.agent.v1.FilesystemService/GetCapabilities"
             | "/alloy.agent.v1.FilesystemService/ListDir"
             | "/alloy.agent.v1.FilesystemService/ReadFile"
@@ -95,9 +96,11 @@ fn safe_to_retry_over_direct(method: &str) -> bool {
             | "/alloy.agent.v1.ProcessService/GetCacheStats"
             | "/alloy.agent.v1.ProcessService/ListProcesses"
             | "/alloy.agent.v1.ProcessService/GetStatus"
+            | "/alloy.agent.v1.ProcessService/GetResourceHistory"
             | "/alloy.agent.v1.ProcessService/TailLogs"
             | "/alloy.agent.v1.InstanceService/List"
             | "/alloy.agent.v1.InstanceService/Get"
+            | "/alloy.agent.v1.InstanceService/GetStagedUpgrade"
     )
 }
 
@@ -107,18 +110,40 @@ fn is_long_running_method(method: &str) -> bool {
         "/alloy.agent.v1.ProcessService/WarmTemplateCache"
             | "/alloy.agent.v1.ProcessService/StartFromTemplate"
             | "/alloy.agent.v1.InstanceService/Start"
+            | "/alloy.agent.v1.InstanceService/Restart"
             | "/alloy.agent.v1.InstanceService/ImportSaveFromUrl"
+            | "/alloy.agent.v1.InstanceService/CreateBackup"
+            | "/alloy.agent.v1.InstanceService/RestoreBackup"
+            | "/alloy.agent.v1.InstanceService/StageUpgrade"
+            | "/alloy.agent.v1.InstanceService/ExportInstance"
+            | "/alloy.agent.v1.InstanceService/ImportInstance"
+            | "/alloy.agent.v1.ProcessService/GetVersionRequirements"
+            | "/alloy.agent.v1.ProcessService/ValidateTemplate"
+            | "/alloy.agent.v1.AgentHealthService/PrepareUpdate"
     )
 }
 
+/// Versions reported by the agent that served a call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AgentVersionInfo {
+    pub agent_version: String,
+    pub protocol_version: Option<u32>,
+}
+
+/// Receives the versions of the agent that served the latest call.
+pub type AgentVersionSlot = Arc<std::sync::Mutex<Option<AgentVersionInfo>>>;
+
 #[derive(Clone)]
 pub struct AgentTransport {
     hub: AgentHub,
     node: String,
+    // Pinned transports only ever talk to `node` (no fallback to another tunnel).
+    pinned: bool,
     mode: TransportMode,
     timeout: Duration,
     next_id: Arc<AtomicU64>,
     b64: base64::engine::general_purpose::GeneralPurpose,
+    version_slot: Option<AgentVersionSlot>,
 }
 
 impl AgentTransport {
@@ -126,21 +151,65 @@ impl AgentTransport {
         Self {
             hub,
             node: default_node_name(),
+            pinned: false,
             mode: parse_mode(std::env::var("ALLOY_AGENT_TRANSPORT").ok()),
             timeout: parse_timeout_ms(std::env::var("ALLOY_AGENT_TIMEOUT_MS").ok()),
             next_id: Arc::new(AtomicU64::new(1)),
             b64: base64::engine::general_purpose::STANDARD,
+            version_slot: None,
         }
     }
 
+    /// Reports the serving agent's versions into `slot` after every call.
+    pub fn with_version_slot(mut self, slot: AgentVersionSlot) -> Self {
+        self.version_slot = Some(slot);
+        self
+    }
+
+    fn record_version(&self, agent_version: String, protocol_version: Option<u32>) {
+        let Some(slot) = &self.version_slot else {
+            return;
+        };
+        if agent_version.is_empty() {
+            return;
+        }
+        *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(AgentVersionInfo {
+            agent_version,
+            protocol_version,
+        });
+    }
+
+    /// Transport that routes every call over `node`'s tunnel. Unlike the
+    /// default transport it never falls back to another node or the direct
+    /// endpoint, so node-to-node operations can't hit the wrong agent.
+    pub fn for_node(&self, node: &str) -> Self {
+        let mut t = self.clone();
+        t.node = node.to_string();
+        t.pinned = true;
+        t.mode = TransportMode::TunnelOnly;
+        t
+    }
+
     pub async fn connected_nodes(&self) -> Vec<String> {
         self.hub.nodes().await
     }
 
+    /// Name of the node calls are routed to (the single connected node wins
+    /// when the configured default is not tunnel-connected).
+    pub async fn target_node(&self) -> String {
+        match self.pick_tunnel_conn().await {
+            Some(conn) => conn.node.clone(),
+            None => self.node.clone(),
+        }
+    }
+
     async fn pick_tunnel_conn(&self) -> Option<Arc<AgentConnection>> {
         if let Some(c) = self.hub.get(&self.node).await {
             return Some(c);
         }
+        if self.pinned {
+            return None;
+        }
         let nodes = self.hub.nodes().await;
         if nodes.len() == 1 {
             return self.hub.get(&nodes[0]).await;
@@ -199,6 +268,42 @@ impl AgentTransport {
         }
     }
 
+    /// Opens a server-streaming call. The tunnel only carries unary calls, so
+    /// streams always use the direct gRPC endpoint.
+    pub async fn server_stream<Req, Res>(
+        &self,
+        method: &'static str,
+        req: Req,
+    ) -> Result<tonic::Streaming<Res>, tonic::Status>
+    where
+        Req: prost::Message + Default + 'static,
+        Res: prost::Message + Default + 'static,
+    {
+        if self.mode == TransportMode::TunnelOnly {
+            return Err(tonic::Status::unavailable(
+                "streaming calls need a direct agent connection",
+            ));
+        }
+
+        let endpoint = agent_endpoint();
+        let channel = tonic::transport::Channel::from_shared(endpoint.clone())
+            .map_err(|e| tonic::Status::internal(format!("invalid agent endpoint: {e}")))?
+            .connect()
+            .await
+            .map_err(|e| tonic::Status::unavailable(format!("connect failed ({endpoint}): {e}")))?;
+
+        let mut grpc = tonic::client::Grpc::new(channel);
+        grpc.ready().await.map_err(|e| {
+            tonic::Status::unavailable(format!("agent is not ready ({endpoint}): {e}"))
+        })?;
+        let path = tonic::codegen::http::uri::PathAndQuery::from_static(method);
+        let codec = tonic::codec::ProstCodec::default();
+        let resp = grpc
+            .server_streaming(tonic::Request::new(req), path, codec)
+            .await?;
+        Ok(resp.into_inner())
+    }
+
     async fn call_tunnel_bytes<Res>(
         &self,
         method: &'static str,
@@ -252,6 +357,13 @@ impl AgentTransport {
             }
         };
 
+        self.record_version(
+            resp.agent_version
+                .clone()
+                .unwrap_or_else(|| conn.agent_version.clone()),
+            resp.protocol_version,
+        );
+
         if !resp.ok {
             let code = code_from_i32(resp.status_code.unwrap_or(2));
             return Err(tonic::Status::new(
@@ -303,6 +415,16 @@ impl AgentTransport {
         let path = tonic::codegen::http::uri::PathAndQuery::from_static(method);
         let codec = tonic::codec::ProstCodec::default();
         let resp = grpc.unary(request, path, codec).await.map_err(|s| s)?;
+        let meta = resp.metadata();
+        let header = |key: &str| {
+            meta.get(key)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        self.record_version(
+            header(alloy_proto::AGENT_VERSION_HEADER).unwrap_or_default(),
+            header(alloy_proto::PROTOCOL_VERSION_HEADER).and_then(|v| v.parse().ok()),
+        );
         Ok(resp.into_inner())
     }
 }
@@ -2,7 +2,7 @@ use std::{collections::HashMap, sync::Arc};
 
 use axum::{
     extract::{
-        State,
+        Query, State,
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
     http::{HeaderMap, StatusCode},
@@ -44,6 +44,15 @@ pub enum AgentToControlFrame {
         payload_b64: Option<String>,
         status_code: Option<i32>,
         status_message: Option<String>,
+        // Absent from agents that predate per-response versions.
+        #[serde(default)]
+        agent_version: Option<String>,
+        #[serde(default)]
+        protocol_version: Option<u32>,
+    },
+    #[serde(rename = "audit")]
+    Audit {
+        event: crate::audit::AgentAuditEvent,
     },
     #[serde(other)]
     Unknown,
@@ -55,6 +64,8 @@ pub struct TunnelResponse {
     pub payload_b64: Option<String>,
     pub status_code: Option<i32>,
     pub status_message: Option<String>,
+    pub agent_version: Option<String>,
+    pub protocol_version: Option<u32>,
 }
 
 #[derive(Debug)]
@@ -90,6 +101,22 @@ impl AgentHub {
     pub async fn remove(&self, node: &str) {
         self.inner.write().await.remove(node);
     }
+
+    /// Sends a close frame to every tunnel, e.g. on shutdown.
+    pub async fn close_all(&self) {
+        let conns: Vec<_> = self.inner.write().await.drain().map(|(_, c)| c).collect();
+        for conn in conns {
+            let _ = conn.tx.send(Message::Close(None)).await;
+        }
+    }
+
+    /// Closes `node`'s tunnel, if any; the agent has to authenticate again.
+    pub async fn disconnect(&self, node: &str) {
+        let conn = self.inner.write().await.remove(node);
+        if let Some(conn) = conn {
+            let _ = conn.tx.send(Message::Close(None)).await;
+        }
+    }
 }
 
 fn configured_agent_token() -> Option<String> {
@@ -106,6 +133,17 @@ fn hash_token(raw: &str) -> String {
     hex::encode(hasher.finalize())
 }
 
+/// Whether `token` hashes to `stored_hash`, compared in constant time.
+fn token_matches(token: &str, stored_hash: &str) -> bool {
+    let got = hash_token(token);
+    got.len() == stored_hash.len()
+        && got
+            .bytes()
+            .zip(stored_hash.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
 fn bearer_token(headers: &HeaderMap) -> Option<String> {
     let raw = headers
         .get(axum::http::header::AUTHORIZATION)?
@@ -120,29 +158,39 @@ fn bearer_token(headers: &HeaderMap) -> Option<String> {
     Some(token.to_string())
 }
 
+/// Token from `Authorization: Bearer`, else the `token` query parameter (for
+/// clients that cannot set headers on a websocket upgrade).
+fn request_token(headers: &HeaderMap, query: &HashMap<String, String>) -> Option<String> {
+    bearer_token(headers).or_else(|| {
+        query
+            .get("token")
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    })
+}
+
 #[derive(Debug, Clone)]
 enum WsAuth {
     /// Authorized by a global shared token (ALLOY_AGENT_CONNECT_TOKEN).
     AnyToken,
     /// Authorized by a per-node token; node name must match hello.node.
     NodeToken { node: String },
-    /// No token provided; only nodes without a connect token may connect.
-    NoToken,
 }
 
 async fn authorize(
     db: &alloy_db::sea_orm::DatabaseConnection,
-    headers: &HeaderMap,
+    token: Option<String>,
 ) -> Result<WsAuth, StatusCode> {
     if let Some(expected) = configured_agent_token() {
-        if bearer_token(headers).is_some_and(|got| got == expected) {
+        if token.is_some_and(|got| token_matches(&got, &hash_token(&expected))) {
             return Ok(WsAuth::AnyToken);
         }
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    let Some(token) = bearer_token(headers) else {
-        return Ok(WsAuth::NoToken);
+    // Anonymous tunnels would receive every RPC routed to the node they claim.
+    let Some(token) = token else {
+        return Err(StatusCode::UNAUTHORIZED);
     };
 
     let token_hash = hash_token(&token);
@@ -161,8 +209,9 @@ pub async fn agent_ws(
     State(state): State<AppState>,
     ws: WebSocketUpgrade,
     headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
-    let auth = match authorize(&state.db, &headers).await {
+    let auth = match authorize(&state.db, request_token(&headers, &query)).await {
         Ok(v) => v,
         Err(code) => return (code, "unauthorized").into_response(),
     };
@@ -212,30 +261,10 @@ async fn handle_agent_socket(state: AppState, socket: WebSocket, auth: WsAuth) {
                     return;
                 }
             }
-            WsAuth::NoToken => {
-                // No token provided: only allow nodes that do NOT have a connect token configured.
-                // This prevents accidentally leaving token-protected nodes open.
-                let existing = alloy_db::entities::nodes::Entity::find()
-                    .filter(alloy_db::entities::nodes::Column::Name.eq(node.clone()))
-                    .one(&*state.db)
-                    .await
-                    .ok()
-                    .flatten();
-
-                if let Some(row) = existing {
-                    if !row.enabled {
-                        let _ = sender.send(Message::Close(None)).await;
-                        return;
-                    }
-                    if row.connect_token_hash.is_some() {
-                        let _ = sender.send(Message::Close(None)).await;
-                        return;
-                    }
-                }
-            }
         }
 
-        // Ensure the node exists in the DB (supports "agent discovers panel" bootstrapping).
+        // Ensure the node exists in the DB (supports "agent discovers panel" bootstrapping
+        // for agents holding the shared token).
         let now: chrono::DateTime<chrono::Utc> = chrono::Utc::now();
         let existing = alloy_db::entities::nodes::Entity::find()
             .filter(alloy_db::entities::nodes::Column::Name.eq(node.clone()))
@@ -260,6 +289,7 @@ async fn handle_agent_socket(state: AppState, socket: WebSocket, auth: WsAuth) {
                 last_seen_at: Set(Some(now.into())),
                 agent_version: Set(Some(hello.agent_version.clone())),
                 last_error: Set(None),
+                tags: Set(String::new()),
                 created_at: Set(now.into()),
                 updated_at: Set(now.into()),
             };
@@ -300,6 +330,8 @@ async fn handle_agent_socket(state: AppState, socket: WebSocket, auth: WsAuth) {
                             payload_b64,
                             status_code,
                             status_message,
+                            agent_version,
+                            protocol_version,
                         } => {
                             let tx = conn.pending.lock().await.remove(&id);
                             if let Some(tx) = tx {
@@ -308,9 +340,14 @@ async fn handle_agent_socket(state: AppState, socket: WebSocket, auth: WsAuth) {
                                     payload_b64,
                                     status_code,
                                     status_message,
+                                    agent_version,
+                                    protocol_version,
                                 });
                             }
                         }
+                        AgentToControlFrame::Audit { event } => {
+                            crate::audit::record_agent(&state.db, &node, event).await;
+                        }
                         AgentToControlFrame::Hello { .. } | AgentToControlFrame::Unknown => {}
                     }
                 }
@@ -327,3 +364,47 @@ async fn handle_agent_socket(state: AppState, socket: WebSocket, auth: WsAuth) {
     .instrument(span)
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{authorize, hash_token, request_token, token_matches};
+    use axum::http::{HeaderMap, StatusCode};
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn tokenless_connections_are_refused() {
+        // Rejected before the database is consulted.
+        let db = sea_orm::DatabaseConnection::Disconnected;
+        assert_eq!(
+            authorize(&db, None).await.unwrap_err(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn only_the_matching_token_passes_the_hash_check() {
+        let stored = hash_token("node-secret");
+        assert!(token_matches("node-secret", &stored));
+        assert!(!token_matches("node-secret2", &stored));
+        assert!(!token_matches("", &stored));
+        assert!(!token_matches("node-secret", ""));
+    }
+
+    #[test]
+    fn token_comes_from_the_bearer_header_or_the_query() {
+        let mut query = HashMap::new();
+        query.insert("token".to_string(), "from-query".to_string());
+        assert_eq!(
+            request_token(&HeaderMap::new(), &query).as_deref(),
+            Some("from-query")
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer from-header".parse().unwrap());
+        assert_eq!(
+            request_token(&headers, &query).as_deref(),
+            Some("from-header")
+        );
+        assert_eq!(request_token(&HeaderMap::new(), &HashMap::new()), None);
+    }
+}
@@ -0,0 +1,217 @@
+use alloy_db::entities::{api_tokens, users};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+
+use crate::roles::{self, Role};
+use crate::rpc::AuthUser;
+
+/// Makes tokens recognizable in config files and secret scanners.
+pub const TOKEN_PREFIX: &str = "alloy_pat_";
+
+/// What a token may call, by the role level of the procedure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "read" => Some(Self::Read),
+            "write" => Some(Self::Write),
+            "admin" => Some(Self::Admin),
+            _ => None,
+        }
+    }
+
+    /// Highest procedure level this scope reaches.
+    pub fn role(self) -> Role {
+        match self {
+            Self::Read => Role::Viewer,
+            Self::Write => Role::Operator,
+            Self::Admin => Role::Admin,
+        }
+    }
+}
+
+/// Parses the stored `scopes` column, skipping unknown entries.
+pub fn parse_scopes(raw: &str) -> Vec<Scope> {
+    let mut out: Vec<Scope> = raw.split([' ', ',']).filter_map(Scope::parse).collect();
+    out.sort();
+    out.dedup();
+    out
+}
+
+pub fn format_scopes(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether a token with `scopes` may call `procedure`. Tokens can never manage
+/// tokens, so a leaked one can't be used to mint a longer-lived replacement.
+pub fn scopes_allow(scopes: &[Scope], procedure: &str) -> bool {
+    if procedure.starts_with("apiToken.") {
+        return false;
+    }
+    let required = roles::required_role(procedure);
+    scopes.iter().any(|s| s.role() >= required)
+}
+
+/// Returns `(plaintext, hash)`; only the hash is stored.
+pub fn generate() -> (String, String) {
+    use base64::Engine;
+    use rand::RngCore;
+
+    let mut buf = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut buf);
+    let raw = format!(
+        "{TOKEN_PREFIX}{}",
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(buf)
+    );
+    let hash = hash(&raw);
+    (raw, hash)
+}
+
+pub fn hash(raw: &str) -> String {
+    use sha2::Digest;
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(raw.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Invalid,
+    Expired,
+    Unavailable,
+}
+
+impl Rejection {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Invalid => "invalid api token",
+            Self::Expired => "api token expired",
+            Self::Unavailable => "api token lookup failed",
+        }
+    }
+}
+
+/// Checks a stored token at `now` and returns its scopes.
+pub fn check(row: &api_tokens::Model, now: DateTimeWithTimeZone) -> Result<Vec<Scope>, Rejection> {
+    if row.expires_at.is_some_and(|at| at <= now) {
+        return Err(Rejection::Expired);
+    }
+    Ok(parse_scopes(&row.scopes))
+}
+
+/// Resolves a bearer token to its user and scopes, bumping `last_used_at`.
+pub async fn authenticate(
+    db: &DatabaseConnection,
+    raw: &str,
+) -> Result<(AuthUser, Vec<Scope>), Rejection> {
+    if !raw.starts_with(TOKEN_PREFIX) {
+        return Err(Rejection::Invalid);
+    }
+    let row = api_tokens::Entity::find()
+        .filter(api_tokens::Column::TokenHash.eq(hash(raw)))
+        .one(db)
+        .await
+        .map_err(|_| Rejection::Unavailable)?
+        .ok_or(Rejection::Invalid)?;
+    let now: DateTimeWithTimeZone = chrono::Utc::now().into();
+    let scopes = check(&row, now)?;
+
+    let user = users::Entity::find_by_id(row.user_id)
+        .one(db)
+        .await
+        .map_err(|_| Rejection::Unavailable)?
+        .ok_or(Rejection::Invalid)?;
+
+    let mut active: api_tokens::ActiveModel = row.into();
+    active.last_used_at = Set(Some(now));
+    if let Err(err) = active.update(db).await {
+        tracing::warn!(%err, "failed to update api token last_used_at");
+    }
+
+    let role = Role::of_user(&user.role, user.is_admin);
+    Ok((
+        AuthUser {
+            user_id: user.id.to_string(),
+            username: user.username,
+            is_admin: role == Role::Admin,
+            role: role.as_str().to_string(),
+        },
+        scopes,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::prelude::Uuid;
+
+    use super::{Rejection, Scope, check, generate, hash, parse_scopes, scopes_allow};
+    use alloy_db::entities::api_tokens;
+
+    fn row(token_hash: String, scopes: &str, expires_in_secs: Option<i64>) -> api_tokens::Model {
+        let now = chrono::Utc::now();
+        api_tokens::Model {
+            id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: "ci".to_string(),
+            token_hash,
+            scopes: scopes.to_string(),
+            expires_at: expires_in_secs.map(|s| (now + chrono::Duration::seconds(s)).into()),
+            last_used_at: None,
+            created_at: now.into(),
+        }
+    }
+
+    #[test]
+    fn generated_token_checks_out_with_its_scopes() {
+        let (raw, stored) = generate();
+        assert!(raw.starts_with(super::TOKEN_PREFIX));
+        assert_eq!(hash(&raw), stored);
+
+        let r = row(stored, "write read", Some(3600));
+        assert_eq!(
+            check(&r, chrono::Utc::now().into()),
+            Ok(vec![Scope::Read, Scope::Write])
+        );
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let r = row(hash("alloy_pat_x"), "admin", Some(-1));
+        assert_eq!(
+            check(&r, chrono::Utc::now().into()),
+            Err(Rejection::Expired)
+        );
+    }
+
+    #[test]
+    fn scopes_cap_procedures() {
+        let read = parse_scopes("read");
+        assert!(scopes_allow(&read, "instance.list"));
+        assert!(!scopes_allow(&read, "instance.stop"));
+
+        let write = parse_scopes("write,bogus");
+        assert_eq!(write, [Scope::Write]);
+        assert!(scopes_allow(&write, "instance.stop"));
+        assert!(!scopes_allow(&write, "node.create"));
+
+        assert!(!scopes_allow(&parse_scopes("admin"), "apiToken.create"));
+    }
+}
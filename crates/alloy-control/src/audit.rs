@@ -1,5 +1,9 @@
 use alloy_db::entities::audit_events;
-use sea_orm::{ActiveModelTrait, Set};
+use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Select, Set,
+};
 
 use crate::rpc::Ctx;
 
@@ -16,6 +20,7 @@ pub async fn record(ctx: &Ctx, action: &str, target: &str, meta: Option<serde_js
         action: Set(action.to_string()),
         target: Set(target.to_string()),
         meta: Set(meta),
+        source: Set("control".to_string()),
         created_at: Set(chrono::Utc::now().into()),
     };
 
@@ -23,3 +28,245 @@ pub async fn record(ctx: &Ctx, action: &str, target: &str, meta: Option<serde_js
         tracing::warn!(%err, action, target, "failed to write audit event");
     }
 }
+
+/// Records an action with no signed-in user (e.g. a failed login).
+pub async fn record_anonymous(
+    db: &DatabaseConnection,
+    request_id: &str,
+    action: &str,
+    target: &str,
+    meta: Option<serde_json::Value>,
+) {
+    let model = audit_events::ActiveModel {
+        id: Set(sea_orm::prelude::Uuid::new_v4()),
+        request_id: Set(request_id.to_string()),
+        user_id: Set(None),
+        action: Set(action.to_string()),
+        target: Set(target.to_string()),
+        meta: Set(meta),
+        source: Set("control".to_string()),
+        created_at: Set(chrono::Utc::now().into()),
+    };
+
+    if let Err(err) = model.insert(db).await {
+        tracing::warn!(%err, action, target, "failed to write audit event");
+    }
+}
+
+/// An action an agent took on its own (stop escalation, orphan cleanup, ...).
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct AgentAuditEvent {
+    pub action: String,
+    pub process_id: String,
+    #[serde(default)]
+    pub template_id: String,
+    #[serde(default)]
+    pub detail: String,
+    pub at_unix_ms: u64,
+}
+
+/// Persists an event mirrored from `node` with `source = "agent"`.
+pub async fn record_agent(db: &DatabaseConnection, node: &str, event: AgentAuditEvent) {
+    let action = format!("agent.{}", event.action);
+    let created_at = chrono::DateTime::from_timestamp_millis(event.at_unix_ms as i64)
+        .unwrap_or_else(chrono::Utc::now);
+
+    let model = audit_events::ActiveModel {
+        id: Set(sea_orm::prelude::Uuid::new_v4()),
+        request_id: Set(String::new()),
+        user_id: Set(None),
+        action: Set(action.clone()),
+        target: Set(event.process_id.clone()),
+        meta: Set(Some(serde_json::json!({
+            "node": node,
+            "template_id": event.template_id,
+            "detail": event.detail,
+        }))),
+        source: Set("agent".to_string()),
+        created_at: Set(created_at.into()),
+    };
+
+    if let Err(err) = model.insert(db).await {
+        tracing::warn!(
+            %err,
+            action,
+            node,
+            target = %event.process_id,
+            "failed to write agent audit event"
+        );
+    }
+}
+
+pub const LIST_PAGE_DEFAULT: u64 = 50;
+pub const LIST_PAGE_MAX: u64 = 200;
+
+#[derive(Debug, Clone, Default)]
+pub struct ListFilter {
+    pub user_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub from: Option<DateTimeWithTimeZone>,
+    pub to: Option<DateTimeWithTimeZone>,
+}
+
+/// Last event of a page. Events are listed newest first with ties on
+/// `created_at` broken by id, so the next page starts strictly after this.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListCursor {
+    pub created_at: DateTimeWithTimeZone,
+    pub id: Uuid,
+}
+
+impl ListCursor {
+    /// `<unix micros>_<id>`; micros match the column's precision.
+    pub fn encode(&self) -> String {
+        format!("{}_{}", self.created_at.timestamp_micros(), self.id)
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        let (micros, id) = raw.trim().split_once('_')?;
+        let created_at = chrono::DateTime::from_timestamp_micros(micros.parse().ok()?)?;
+        Some(Self {
+            created_at: created_at.into(),
+            id: Uuid::parse_str(id).ok()?,
+        })
+    }
+}
+
+/// One page of events; fetches `limit + 1` rows so [`split_page`] can tell
+/// whether another page follows.
+pub fn list_query(
+    filter: &ListFilter,
+    cursor: Option<&ListCursor>,
+    limit: u64,
+) -> Select<audit_events::Entity> {
+    let mut cond = Condition::all();
+    if let Some(user_id) = filter.user_id {
+        cond = cond.add(audit_events::Column::UserId.eq(user_id));
+    }
+    if let Some(action) = &filter.action {
+        cond = cond.add(audit_events::Column::Action.eq(action.as_str()));
+    }
+    if let Some(target) = &filter.target {
+        cond = cond.add(audit_events::Column::Target.eq(target.as_str()));
+    }
+    if let Some(from) = filter.from {
+        cond = cond.add(audit_events::Column::CreatedAt.gte(from));
+    }
+    if let Some(to) = filter.to {
+        cond = cond.add(audit_events::Column::CreatedAt.lt(to));
+    }
+    if let Some(c) = cursor {
+        cond = cond.add(
+            Condition::any()
+                .add(audit_events::Column::CreatedAt.lt(c.created_at))
+                .add(
+                    Condition::all()
+                        .add(audit_events::Column::CreatedAt.eq(c.created_at))
+                        .add(audit_events::Column::Id.lt(c.id)),
+                ),
+        );
+    }
+    audit_events::Entity::find()
+        .filter(cond)
+        .order_by_desc(audit_events::Column::CreatedAt)
+        .order_by_desc(audit_events::Column::Id)
+        .limit(limit.clamp(1, LIST_PAGE_MAX) + 1)
+}
+
+/// Trims the lookahead row from a [`list_query`] result and returns the
+/// cursor for the next page, if any.
+pub fn split_page(
+    mut rows: Vec<audit_events::Model>,
+    limit: u64,
+) -> (Vec<audit_events::Model>, Option<ListCursor>) {
+    let limit = limit.clamp(1, LIST_PAGE_MAX) as usize;
+    if rows.len() <= limit {
+        return (rows, None);
+    }
+    rows.truncate(limit);
+    let next = rows.last().map(|r| ListCursor {
+        created_at: r.created_at,
+        id: r.id,
+    });
+    (rows, next)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_db::entities::audit_events;
+    use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
+    use sea_orm::{DbBackend, QueryTrait};
+
+    use super::{ListCursor, ListFilter, list_query, split_page};
+
+    fn event(created_at: DateTimeWithTimeZone, id: Uuid) -> audit_events::Model {
+        audit_events::Model {
+            id,
+            request_id: "rid".to_string(),
+            user_id: None,
+            action: "instance.stop".to_string(),
+            target: "inst-1".to_string(),
+            meta: None,
+            source: "control".to_string(),
+            created_at,
+        }
+    }
+
+    #[test]
+    fn keyset_page_boundary_breaks_timestamp_ties_by_id() {
+        let at: DateTimeWithTimeZone =
+            chrono::DateTime::from_timestamp_micros(1_700_000_000_123_456)
+                .unwrap()
+                .into();
+        let (hi, lo) = (Uuid::from_u128(2), Uuid::from_u128(1));
+
+        // Exactly a full page: no next cursor.
+        let (page, next) = split_page(vec![event(at, hi), event(at, lo)], 2);
+        assert_eq!(page.len(), 2);
+        assert_eq!(next, None);
+
+        // One row past the page: the cursor points at the last row kept.
+        let (page, next) = split_page(vec![event(at, hi), event(at, lo)], 1);
+        assert_eq!(page.len(), 1);
+        let next = next.expect("next page");
+        assert_eq!(
+            next,
+            ListCursor {
+                created_at: at,
+                id: hi
+            }
+        );
+        assert_eq!(ListCursor::parse(&next.encode()), Some(next.clone()));
+
+        // The follow-up query keeps same-timestamp rows with a smaller id.
+        let sql = list_query(&ListFilter::default(), Some(&next), 1)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            sql.contains(r#""created_at" < '2023-11-14 22:13:20.123456 +00:00'"#),
+            "{sql}"
+        );
+        assert!(sql.contains(&format!(r#""id" < '{hi}'"#)), "{sql}");
+        assert!(
+            sql.contains(r#"ORDER BY "audit_events"."created_at" DESC, "audit_events"."id" DESC"#)
+        );
+        assert!(sql.contains("LIMIT 2"), "{sql}");
+    }
+
+    #[test]
+    fn filters_by_action() {
+        let filter = ListFilter {
+            action: Some("instance.stop".to_string()),
+            ..ListFilter::default()
+        };
+        let sql = list_query(&filter, None, 500)
+            .build(DbBackend::Postgres)
+            .to_string();
+        assert!(
+            sql.contains(r#""audit_events"."action" = 'instance.stop'"#),
+            "{sql}"
+        );
+        assert!(sql.contains("LIMIT 201"), "{sql}");
+    }
+}
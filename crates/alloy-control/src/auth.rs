@@ -1,4 +1,14 @@
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
 use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
 use serde::{Deserialize, Serialize};
 
@@ -10,6 +20,8 @@ use alloy_db::sea_orm::{
 use sea_orm::prelude::Expr;
 use sea_orm::prelude::Uuid;
 
+use crate::request_meta::RequestMeta;
+use crate::roles::Role;
 use crate::state::AppState;
 
 pub const CSRF_COOKIE_NAME: &str = "csrf";
@@ -85,7 +97,9 @@ pub struct LoginRequest {
 pub struct WhoamiResponse {
     pub user_id: String,
     pub username: String,
+    /// Derived from `role`; kept for older clients.
     pub is_admin: bool,
+    pub role: String,
 }
 
 fn hash_refresh_token(raw: &str) -> String {
@@ -136,6 +150,7 @@ async fn ensure_admin_user(db: &DatabaseConnection) -> Result<(), String> {
         username: Set(username),
         password_hash: Set(ph),
         is_admin: Set(true),
+        role: Set(Role::Admin.as_str().to_string()),
         created_at: Set(chrono::Utc::now().into()),
     };
 
@@ -146,6 +161,133 @@ async fn ensure_admin_user(db: &DatabaseConnection) -> Result<(), String> {
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy)]
+struct FailureState {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Failed-login counts per username and per client IP. Past `max_failures`
+/// every further failure locks the key out, doubling from `base_lockout` up
+/// to `max_lockout`; counts are forgotten after `max_lockout` of quiet.
+struct LoginThrottle {
+    max_failures: u32,
+    base_lockout: Duration,
+    max_lockout: Duration,
+    entries: std::sync::Mutex<HashMap<String, FailureState>>,
+}
+
+impl LoginThrottle {
+    fn new(max_failures: u32, base_lockout: Duration, max_lockout: Duration) -> Self {
+        Self {
+            max_failures,
+            base_lockout,
+            max_lockout: max_lockout.max(base_lockout),
+            entries: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn global() -> &'static LoginThrottle {
+        static LT: OnceLock<LoginThrottle> = OnceLock::new();
+        LT.get_or_init(|| {
+            let env_u64 = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+            let max_failures = env_u64("ALLOY_LOGIN_MAX_FAILURES")
+                .unwrap_or(5)
+                .clamp(1, 1000) as u32;
+            let base_ms = env_u64("ALLOY_LOGIN_LOCKOUT_MS")
+                .unwrap_or(30_000)
+                .clamp(1000, 3_600_000);
+            let max_ms = env_u64("ALLOY_LOGIN_LOCKOUT_MAX_MS")
+                .unwrap_or(15 * 60_000)
+                .clamp(1000, 86_400_000);
+            LoginThrottle::new(
+                max_failures,
+                Duration::from_millis(base_ms),
+                Duration::from_millis(max_ms),
+            )
+        })
+    }
+
+    fn is_locked(&self, key: &str, now: Instant) -> bool {
+        let map = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        map.get(key)
+            .and_then(|s| s.locked_until)
+            .is_some_and(|until| now < until)
+    }
+
+    fn record_failure(&self, key: &str, now: Instant) {
+        let mut map = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if map.len() > 10_000 {
+            let forget = self.max_lockout;
+            map.retain(|_, s| now.duration_since(s.last_failure) < forget);
+        }
+        let state = map.entry(key.to_string()).or_insert(FailureState {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        if now.duration_since(state.last_failure) >= self.max_lockout {
+            state.failures = 0;
+        }
+        state.failures += 1;
+        state.last_failure = now;
+        if state.failures >= self.max_failures {
+            let doublings = (state.failures - self.max_failures).min(20);
+            let lockout = self
+                .base_lockout
+                .saturating_mul(1 << doublings)
+                .min(self.max_lockout);
+            state.locked_until = Some(now + lockout);
+        }
+    }
+
+    fn clear(&self, key: &str) {
+        let mut map = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        map.remove(key);
+    }
+}
+
+/// Proxies whose forwarding headers are believed, from `ALLOY_TRUSTED_PROXIES`
+/// (comma-separated IPs). Unset means none: the peer address is used as is.
+fn trusted_proxies() -> &'static [IpAddr] {
+    static TP: OnceLock<Vec<IpAddr>> = OnceLock::new();
+    TP.get_or_init(|| {
+        std::env::var("ALLOY_TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|s| s.trim().parse().ok())
+            .collect()
+    })
+}
+
+/// Address the login lockout keys on: the TCP peer, or the client a trusted
+/// proxy reports. Forwarding headers from anyone else are ignored since the
+/// client could pick a fresh address per attempt.
+fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpAddr]) -> IpAddr {
+    if !trusted.contains(&peer) {
+        return peer;
+    }
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    if let Some(ip) = header("x-real-ip").and_then(|v| v.trim().parse().ok()) {
+        return ip;
+    }
+    // Proxies append, so the nearest untrusted hop is the real client.
+    header("x-forwarded-for")
+        .into_iter()
+        .flat_map(|v| v.rsplit(','))
+        .filter_map(|v| v.trim().parse::<IpAddr>().ok())
+        .find(|ip| !trusted.contains(ip))
+        .unwrap_or(peer)
+}
+
+fn login_throttle_keys(username: &str, ip: &str) -> [String; 2] {
+    [
+        format!("user:{}", username.trim().to_ascii_lowercase()),
+        format!("ip:{ip}"),
+    ]
+}
+
 fn build_access_cookie(jwt: String) -> Cookie<'static> {
     // Access token is used by both the API layer and `/auth/whoami`, so it must
     // be available on all paths.
@@ -171,12 +313,19 @@ struct Claims {
     sub: String,
     username: String,
     is_admin: bool,
+    // Tokens minted before roles existed only carry `is_admin`.
+    #[serde(default = "legacy_claims_role")]
+    role: String,
     exp: usize,
     iat: usize,
     iss: String,
     aud: String,
 }
 
+fn legacy_claims_role() -> String {
+    Role::Operator.as_str().to_string()
+}
+
 pub fn validate_access_jwt(token: &str) -> anyhow::Result<WhoamiResponse> {
     let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256);
     validation.set_audience(&["alloy-web"]);
@@ -188,10 +337,12 @@ pub fn validate_access_jwt(token: &str) -> anyhow::Result<WhoamiResponse> {
         &validation,
     )?;
 
+    let role = Role::of_user(&data.claims.role, data.claims.is_admin);
     Ok(WhoamiResponse {
         user_id: data.claims.sub,
         username: data.claims.username,
-        is_admin: data.claims.is_admin,
+        is_admin: role == Role::Admin,
+        role: role.as_str().to_string(),
     })
 }
 
@@ -200,10 +351,12 @@ fn make_access_jwt(user: &alloy_db::entities::users::Model) -> anyhow::Result<St
     let exp = (now + time::Duration::minutes(5)).unix_timestamp() as usize;
     let iat = now.unix_timestamp() as usize;
 
+    let role = Role::of_user(&user.role, user.is_admin);
     let claims = Claims {
         sub: user.id.to_string(),
         username: user.username.clone(),
-        is_admin: user.is_admin,
+        is_admin: role == Role::Admin,
+        role: role.as_str().to_string(),
         exp,
         iat,
         iss: "alloy".to_string(),
@@ -219,6 +372,9 @@ fn make_access_jwt(user: &alloy_db::entities::users::Model) -> anyhow::Result<St
 
 pub async fn login(
     State(state): State<AppState>,
+    Extension(meta): Extension<RequestMeta>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     jar: CookieJar,
     Json(input): Json<LoginRequest>,
 ) -> impl IntoResponse {
@@ -231,23 +387,46 @@ pub async fn login(
         .into_response();
     }
 
+    // Same answer whether the username or the address is locked out.
+    let throttle = LoginThrottle::global();
+    let ip = client_ip(peer.ip(), &headers, trusted_proxies()).to_string();
+    let keys = login_throttle_keys(&input.username, &ip);
+    if keys.iter().any(|k| throttle.is_locked(k, Instant::now())) {
+        return json_error(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too many failed login attempts; try again later",
+        )
+        .into_response();
+    }
+
     let user = match alloy_db::entities::users::Entity::find()
         .filter(alloy_db::entities::users::Column::Username.eq(input.username.clone()))
         .one(db)
         .await
     {
-        Ok(Some(u)) => u,
-        Ok(None) => {
-            return json_error(StatusCode::UNAUTHORIZED, "invalid credentials").into_response();
-        }
+        Ok(v) => v,
         Err(e) => {
             return json_error(StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {e}"))
                 .into_response();
         }
     };
 
-    if !verify_password(&user.password_hash, &input.password) {
+    let Some(user) = user.filter(|u| verify_password(&u.password_hash, &input.password)) else {
+        for key in &keys {
+            throttle.record_failure(key, Instant::now());
+        }
+        crate::audit::record_anonymous(
+            db,
+            &meta.request_id,
+            "auth.loginFailed",
+            &input.username,
+            Some(serde_json::json!({ "ip": ip })),
+        )
+        .await;
         return json_error(StatusCode::UNAUTHORIZED, "invalid credentials").into_response();
+    };
+    for key in &keys {
+        throttle.clear(key);
     }
 
     let access = match make_access_jwt(&user) {
@@ -285,10 +464,14 @@ pub async fn login(
 
     (
         jar,
-        Json(WhoamiResponse {
-            user_id: user.id.to_string(),
-            username: user.username,
-            is_admin: user.is_admin,
+        Json({
+            let role = Role::of_user(&user.role, user.is_admin);
+            WhoamiResponse {
+                user_id: user.id.to_string(),
+                username: user.username,
+                is_admin: role == Role::Admin,
+                role: role.as_str().to_string(),
+            }
         }),
     )
         .into_response()
@@ -416,3 +599,65 @@ pub async fn refresh(State(state): State<AppState>, jar: CookieJar) -> impl Into
 
     (jar, StatusCode::NO_CONTENT).into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{LoginThrottle, client_ip, login_throttle_keys};
+    use axum::http::{HeaderMap, HeaderValue};
+    use std::net::IpAddr;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn repeated_failures_lock_out_until_a_success_clears_them() {
+        let throttle = LoginThrottle::new(3, Duration::from_secs(10), Duration::from_secs(60));
+        let [user, _] = login_throttle_keys("Admin", "10.0.0.1");
+        let t0 = Instant::now();
+
+        for _ in 0..2 {
+            throttle.record_failure(&user, t0);
+        }
+        assert!(!throttle.is_locked(&user, t0));
+        throttle.record_failure(&user, t0);
+        assert!(throttle.is_locked(&user, t0 + Duration::from_secs(9)));
+        assert!(!throttle.is_locked(&user, t0 + Duration::from_secs(10)));
+
+        // The next failure doubles the lockout.
+        let t1 = t0 + Duration::from_secs(11);
+        throttle.record_failure(&user, t1);
+        assert!(throttle.is_locked(&user, t1 + Duration::from_secs(19)));
+
+        throttle.clear(&user);
+        assert!(!throttle.is_locked(&user, t1));
+        throttle.record_failure(&user, t1);
+        assert!(!throttle.is_locked(&user, t1));
+    }
+
+    #[test]
+    fn usernames_are_counted_case_insensitively() {
+        let [a, _] = login_throttle_keys("Admin ", "x");
+        let [b, ip] = login_throttle_keys("admin", "x");
+        assert_eq!(a, b);
+        assert_eq!(ip, "ip:x");
+    }
+
+    #[test]
+    fn forwarding_headers_only_count_from_trusted_proxies() {
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        let proxy = ip("10.0.0.2");
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("1.1.1.1, 203.0.113.7, 10.0.0.2"),
+        );
+
+        // Spoofed headers from a direct client are ignored.
+        let direct = ip("198.51.100.1");
+        assert_eq!(client_ip(direct, &headers, &[proxy]), direct);
+        // The client-supplied left end is skipped for the nearest untrusted hop.
+        assert_eq!(client_ip(proxy, &headers, &[proxy]), ip("203.0.113.7"));
+
+        headers.insert("x-real-ip", HeaderValue::from_static("203.0.113.9"));
+        assert_eq!(client_ip(proxy, &headers, &[proxy]), ip("203.0.113.9"));
+        assert_eq!(client_ip(proxy, &headers, &[]), proxy);
+    }
+}
@@ -0,0 +1,97 @@
+use std::{sync::OnceLock, time::Duration};
+
+use specta::Type;
+
+const API_BASE: &str = "https://api.curseforge.com/v1";
+
+/// Outcome of [`check_key`]. `detail` never contains the key.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, Type)]
+pub struct CurseforgeKeyCheck {
+    pub ok: bool,
+    pub detail: String,
+}
+
+fn http_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .user_agent("alloy-control")
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("failed to build reqwest client")
+    })
+}
+
+/// Makes one cheap authenticated call (the first page of the game list) to
+/// see whether CurseForge accepts `key`.
+pub async fn check_key(key: &str) -> CurseforgeKeyCheck {
+    check_key_at(API_BASE, key).await
+}
+
+async fn check_key_at(base: &str, key: &str) -> CurseforgeKeyCheck {
+    let resp = http_client()
+        .get(format!("{base}/games"))
+        .query(&[("pageSize", "1")])
+        .header("x-api-key", key)
+        .send()
+        .await;
+    let status = match resp {
+        Ok(resp) => resp.status(),
+        Err(e) => {
+            return CurseforgeKeyCheck {
+                ok: false,
+                detail: format!("could not reach CurseForge: {e}"),
+            };
+        }
+    };
+
+    let (ok, detail) = if status.is_success() {
+        (true, "CurseForge accepted the key".to_string())
+    } else if status == reqwest::StatusCode::UNAUTHORIZED
+        || status == reqwest::StatusCode::FORBIDDEN
+    {
+        (
+            false,
+            format!("CurseForge rejected the key (HTTP {status})"),
+        )
+    } else {
+        (false, format!("CurseForge returned HTTP {status}"))
+    };
+    CurseforgeKeyCheck { ok, detail }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, http::HeaderMap, http::StatusCode, routing::get};
+
+    use super::check_key_at;
+
+    /// Mock of `/games` that accepts only `good-key`.
+    async fn mock_api() -> String {
+        async fn games(headers: HeaderMap) -> (StatusCode, &'static str) {
+            match headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+                Some("good-key") => (StatusCode::OK, r#"{"data":[]}"#),
+                _ => (StatusCode::FORBIDDEN, ""),
+            }
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/games", get(games));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn accepted_and_rejected_keys() {
+        let base = mock_api().await;
+
+        let ok = check_key_at(&base, "good-key").await;
+        assert!(ok.ok, "{}", ok.detail);
+
+        let rejected = check_key_at(&base, "bad-key").await;
+        assert!(!rejected.ok);
+        assert!(rejected.detail.contains("403"), "{}", rejected.detail);
+        assert!(!rejected.detail.contains("bad-key"));
+    }
+}
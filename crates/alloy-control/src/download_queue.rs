@@ -0,0 +1,464 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use alloy_db::entities::download_jobs;
+use sea_orm::prelude::{DateTimeWithTimeZone, Uuid};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+
+pub const PRIORITY_MIN: i32 = -100;
+pub const PRIORITY_MAX: i32 = 100;
+
+/// Attempts a new job gets, and a manual retry adds back.
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 3;
+const RETRY_BASE: Duration = Duration::from_secs(30);
+const RETRY_MAX: Duration = Duration::from_secs(30 * 60);
+
+const DEFAULT_CONCURRENCY: usize = 2;
+const MAX_CONCURRENCY: usize = 16;
+
+fn parse_concurrency(raw: Option<String>) -> usize {
+    raw.as_deref()
+        .unwrap_or_default()
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .unwrap_or(DEFAULT_CONCURRENCY)
+        .clamp(1, MAX_CONCURRENCY)
+}
+
+/// How many download jobs may run at once (`ALLOY_DOWNLOAD_CONCURRENCY`).
+pub fn concurrency() -> usize {
+    parse_concurrency(std::env::var("ALLOY_DOWNLOAD_CONCURRENCY").ok())
+}
+
+pub fn clamp_priority(priority: i32) -> i32 {
+    priority.clamp(PRIORITY_MIN, PRIORITY_MAX)
+}
+
+/// Order waiting jobs run in: higher priority first, FIFO within a priority.
+pub fn queue_order(a: &download_jobs::Model, b: &download_jobs::Model) -> Ordering {
+    b.priority
+        .cmp(&a.priority)
+        .then(a.queue_position.cmp(&b.queue_position))
+        .then(a.created_at.cmp(&b.created_at))
+}
+
+/// Whether a queued job may start at `now`; retries wait for `next_attempt_at`.
+pub fn is_due(job: &download_jobs::Model, now: DateTimeWithTimeZone) -> bool {
+    job.next_attempt_at.is_none_or(|at| at <= now)
+}
+
+/// Earliest pending retry among `queued`, if any is still waiting.
+pub fn next_retry_at(
+    queued: &[download_jobs::Model],
+    now: DateTimeWithTimeZone,
+) -> Option<DateTimeWithTimeZone> {
+    queued
+        .iter()
+        .filter_map(|j| j.next_attempt_at)
+        .filter(|at| *at > now)
+        .min()
+}
+
+/// Delay before retrying after failed attempt number `attempt` (1-based):
+/// 30s doubled per attempt, capped at 30 minutes.
+pub fn retry_delay(attempt: i32) -> Duration {
+    let doublings = attempt.saturating_sub(1).clamp(0, 16) as u32;
+    RETRY_BASE.saturating_mul(1 << doublings).min(RETRY_MAX)
+}
+
+/// Whether a failed download may succeed if tried again. Bad input, missing
+/// artifacts (HTTP 4xx other than 408/429) and corrupt downloads won't.
+pub fn is_retryable(code: Option<&str>, message: &str) -> bool {
+    if matches!(code, Some("invalid_param" | "not_found" | "canceled")) {
+        return false;
+    }
+    let message = message.to_ascii_lowercase();
+    if message.contains("mismatch") || message.contains("download too large") {
+        return false;
+    }
+    match message.split("http status ").nth(1) {
+        Some(status) => {
+            !status.starts_with('4') || status.starts_with("408") || status.starts_with("429")
+        }
+        None => true,
+    }
+}
+
+/// What to do with a job whose attempt just failed: `Some(delay)` to requeue
+/// it, `None` to leave it in `error`.
+pub fn retry_after(
+    job: &download_jobs::Model,
+    code: Option<&str>,
+    message: &str,
+) -> Option<Duration> {
+    (job.attempt_count < job.max_attempts && is_retryable(code, message))
+        .then(|| retry_delay(job.attempt_count))
+}
+
+/// Cache artifact a job writes; jobs sharing one must not run together.
+pub fn artifact_key(job: &download_jobs::Model) -> String {
+    format!("{}@{}", job.template_id, job.version)
+}
+
+/// The job the worker should start next out of `queued` rows, skipping jobs
+/// whose artifact is `busy`. Only consulted when a slot frees up, so a running
+/// job is never preempted.
+pub fn next_job<'a>(
+    queued: &'a [download_jobs::Model],
+    busy: &HashSet<String>,
+) -> Option<&'a download_jobs::Model> {
+    queued
+        .iter()
+        .filter(|job| !busy.contains(&artifact_key(job)))
+        .min_by(|a, b| queue_order(a, b))
+}
+
+/// Running-job slots: at most `concurrency` jobs, one per artifact.
+#[derive(Clone)]
+pub struct JobSlots {
+    permits: Arc<Semaphore>,
+    size: u32,
+    busy: Arc<Mutex<HashSet<String>>>,
+}
+
+/// A claimed slot; frees the permit and the artifact lock when dropped.
+pub struct JobSlot {
+    _permit: OwnedSemaphorePermit,
+    artifact: String,
+    busy: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Drop for JobSlot {
+    fn drop(&mut self) {
+        self.busy
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.artifact);
+    }
+}
+
+impl JobSlots {
+    pub fn new(concurrency: usize) -> Self {
+        let size = concurrency.clamp(1, MAX_CONCURRENCY);
+        Self {
+            permits: Arc::new(Semaphore::new(size)),
+            size: size as u32,
+            busy: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Picks the next runnable job out of `queued` and claims a slot for it.
+    /// `None` when every slot is taken or every queued artifact is busy.
+    pub fn claim<'a>(
+        &self,
+        queued: &'a [download_jobs::Model],
+    ) -> Option<(&'a download_jobs::Model, JobSlot)> {
+        let permit = self.permits.clone().try_acquire_owned().ok()?;
+        let mut busy = self.busy.lock().unwrap_or_else(|e| e.into_inner());
+        let job = next_job(queued, &busy)?;
+        let artifact = artifact_key(job);
+        busy.insert(artifact.clone());
+        let slot = JobSlot {
+            _permit: permit,
+            artifact,
+            busy: self.busy.clone(),
+        };
+        Some((job, slot))
+    }
+
+    /// Resolves once every claimed slot has been released.
+    pub async fn idle(&self) {
+        let _ = self.permits.acquire_many(self.size).await;
+    }
+}
+
+/// Cancel tokens of the jobs running on this control plane.
+#[derive(Clone, Default)]
+pub struct RunningJobs {
+    tokens: Arc<Mutex<HashMap<Uuid, CancellationToken>>>,
+}
+
+/// A job registered in [`RunningJobs`]; unregisters it when dropped.
+pub struct RunningJob {
+    id: Uuid,
+    token: CancellationToken,
+    tokens: Arc<Mutex<HashMap<Uuid, CancellationToken>>>,
+}
+
+impl RunningJob {
+    /// Resolves once the job is cancelled.
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+}
+
+impl Drop for RunningJob {
+    fn drop(&mut self) {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
+}
+
+impl RunningJobs {
+    pub fn start(&self, id: Uuid) -> RunningJob {
+        let token = CancellationToken::new();
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, token.clone());
+        RunningJob {
+            id,
+            token,
+            tokens: self.tokens.clone(),
+        }
+    }
+
+    /// Cancels a running job; `false` if it isn't running.
+    pub fn cancel(&self, id: Uuid) -> bool {
+        let tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(token) = tokens.get(&id) else {
+            return false;
+        };
+        token.cancel();
+        true
+    }
+}
+
+/// `(priority, queue_position)` that puts `job_id` ahead of every other
+/// waiting job, or `None` if it isn't waiting.
+pub fn front_slot(waiting: &[download_jobs::Model], job_id: Uuid) -> Option<(i32, i64)> {
+    let job = waiting.iter().find(|r| r.id == job_id)?;
+    let priority = waiting
+        .iter()
+        .map(|r| r.priority)
+        .max()
+        .unwrap_or(job.priority);
+    let position = waiting
+        .iter()
+        .map(|r| r.queue_position)
+        .min()
+        .unwrap_or(job.queue_position)
+        .saturating_sub(1);
+    Some((priority, position))
+}
+
+/// Emits a structured event for a job changing state.
+pub fn transition(job_id: Uuid, from: &str, to: &str) {
+    tracing::info!(
+        target: "alloy_control::download_queue",
+        %job_id,
+        from,
+        to,
+        "download job state changed"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_db::entities::download_jobs;
+    use sea_orm::prelude::Uuid;
+
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    use super::{
+        JobSlots, RunningJobs, front_slot, is_due, is_retryable, next_job, next_retry_at,
+        parse_concurrency, retry_after,
+    };
+
+    fn job(priority: i32, queue_position: i64, age_secs: i64) -> download_jobs::Model {
+        let created = chrono::Utc::now() - chrono::Duration::seconds(age_secs);
+        download_jobs::Model {
+            id: Uuid::new_v4(),
+            target: "minecraft".to_string(),
+            template_id: "minecraft:vanilla".to_string(),
+            version: "latest".to_string(),
+            params_json: "{}".to_string(),
+            state: "queued".to_string(),
+            message: String::new(),
+            request_id: None,
+            queue_position,
+            priority,
+            attempt_count: 0,
+            max_attempts: 3,
+            next_attempt_at: None,
+            created_at: created.into(),
+            updated_at: created.into(),
+            started_at: None,
+            finished_at: None,
+        }
+    }
+
+    fn next_id(queued: &[download_jobs::Model]) -> Option<Uuid> {
+        next_job(queued, &HashSet::new()).map(|j| j.id)
+    }
+
+    #[test]
+    fn higher_priority_runs_first_then_fifo() {
+        let low_old = job(0, 1, 30);
+        let high_new = job(5, 4, 10);
+        let high_old = job(5, 2, 20);
+        let negative = job(-3, 0, 40);
+        let mut rows = vec![
+            low_old.clone(),
+            high_new.clone(),
+            negative,
+            high_old.clone(),
+        ];
+        assert_eq!(next_id(&rows), Some(high_old.id));
+
+        rows.retain(|j| j.id != high_old.id);
+        assert_eq!(next_id(&rows), Some(high_new.id));
+        rows.retain(|j| j.id != high_new.id);
+        assert_eq!(next_id(&rows), Some(low_old.id));
+        assert!(next_id(&[]).is_none());
+    }
+
+    #[test]
+    fn move_to_front_makes_the_job_next() {
+        let mut rows = vec![job(5, 1, 30), job(0, 2, 20), job(0, 3, 10)];
+        let last = rows[2].id;
+        assert_ne!(next_id(&rows), Some(last));
+
+        let (priority, position) = front_slot(&rows, last).unwrap();
+        assert_eq!((priority, position), (5, 0));
+        rows[2].priority = priority;
+        rows[2].queue_position = position;
+        assert_eq!(next_id(&rows), Some(last));
+
+        assert!(front_slot(&rows, Uuid::new_v4()).is_none());
+    }
+
+    fn for_version(version: &str, queue_position: i64) -> download_jobs::Model {
+        let mut j = job(0, queue_position, 0);
+        j.version = version.to_string();
+        j
+    }
+
+    #[test]
+    fn different_artifacts_run_together_but_same_artifact_serializes() {
+        let slots = JobSlots::new(2);
+        let a1 = for_version("1.21.1", 1);
+        let a2 = for_version("1.21.1", 2);
+        let b = for_version("1.20.4", 3);
+        let queued = vec![a1.clone(), a2.clone(), b.clone()];
+
+        let (first, first_slot) = slots.claim(&queued).unwrap();
+        assert_eq!(first.id, a1.id);
+        // a2 writes the same cache file as a1, so the second slot goes to b.
+        let (second, second_slot) = slots.claim(&queued[1..]).unwrap();
+        assert_eq!(second.id, b.id);
+        assert!(slots.claim(&queued[1..2]).is_none());
+
+        // A free slot alone isn't enough while a1 holds the artifact.
+        drop(second_slot);
+        assert!(slots.claim(&queued[1..2]).is_none());
+        drop(first_slot);
+        let (third, _slot) = slots.claim(&queued[1..2]).unwrap();
+        assert_eq!(third.id, a2.id);
+    }
+
+    #[tokio::test]
+    async fn idle_waits_for_claimed_slots() {
+        let slots = JobSlots::new(2);
+        let queued = vec![for_version("1.21.1", 1)];
+        let (_, slot) = slots.claim(&queued).unwrap();
+
+        let idle = tokio::spawn({
+            let slots = slots.clone();
+            async move { slots.idle().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!idle.is_finished());
+
+        drop(slot);
+        tokio::time::timeout(Duration::from_secs(1), idle)
+            .await
+            .expect("idle did not resolve")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancel_reaches_only_the_running_job() {
+        let running = RunningJobs::default();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let job = running.start(a);
+
+        assert!(!running.cancel(b));
+        assert!(running.cancel(a));
+        tokio::time::timeout(Duration::from_secs(1), job.cancelled())
+            .await
+            .expect("token was not cancelled");
+
+        drop(job);
+        assert!(!running.cancel(a));
+    }
+
+    #[test]
+    fn failed_attempts_back_off_until_max_attempts() {
+        let mut j = job(0, 1, 0);
+        let mut delays = Vec::new();
+        for attempt in 1..=j.max_attempts {
+            j.attempt_count = attempt;
+            delays.push(retry_after(&j, Some("download_failed"), "connection reset"));
+        }
+        let secs = |s| Some(Duration::from_secs(s));
+        assert_eq!(delays, [secs(30), secs(60), None]);
+
+        j.max_attempts = 20;
+        j.attempt_count = 12;
+        assert_eq!(retry_after(&j, None, "timed out"), secs(30 * 60));
+    }
+
+    #[test]
+    fn permanent_failures_are_not_retried() {
+        let mut j = job(0, 1, 0);
+        j.attempt_count = 1;
+        for (code, message) in [
+            (Some("invalid_param"), "unknown template_id"),
+            (None, "download x: http status 404 Not Found"),
+            (None, "minecraft server.jar sha1 mismatch: expected abc"),
+            (None, "download too large"),
+        ] {
+            assert!(!is_retryable(code, message), "{message}");
+            assert_eq!(retry_after(&j, code, message), None);
+        }
+        for message in [
+            "download x: http status 503 Service Unavailable",
+            "download x: http status 429 Too Many Requests",
+            "download x: error sending request",
+        ] {
+            assert!(is_retryable(Some("download_failed"), message), "{message}");
+        }
+    }
+
+    #[test]
+    fn delayed_retries_wait_for_their_time() {
+        let now = chrono::Utc::now().fixed_offset();
+        let ready = job(0, 1, 0);
+        let mut waiting = job(0, 2, 0);
+        waiting.next_attempt_at = Some(now + chrono::Duration::seconds(60));
+        assert!(is_due(&ready, now));
+        assert!(!is_due(&waiting, now));
+        assert!(is_due(&waiting, now + chrono::Duration::seconds(60)));
+
+        let rows = vec![ready, waiting.clone()];
+        assert_eq!(next_retry_at(&rows, now), waiting.next_attempt_at);
+        assert_eq!(next_retry_at(&rows[..1], now), None);
+    }
+
+    #[test]
+    fn concurrency_defaults_and_clamps() {
+        assert_eq!(parse_concurrency(None), 2);
+        assert_eq!(parse_concurrency(Some(" 4 ".to_string())), 4);
+        assert_eq!(parse_concurrency(Some("0".to_string())), 1);
+        assert_eq!(parse_concurrency(Some("500".to_string())), 16);
+        assert_eq!(parse_concurrency(Some("many".to_string())), 2);
+    }
+}
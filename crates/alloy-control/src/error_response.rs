@@ -0,0 +1,129 @@
+use axum::{
+    body::{Body, to_bytes},
+    http::{Request, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::rpc::{API_ERROR_JSON_PREFIX, ApiError};
+
+/// HTTP status for an `ApiError` code. 401 is left to the session guard: the
+/// web client treats it as an expired session and signs the user out.
+pub fn status_for_code(code: &str) -> StatusCode {
+    match code {
+        "invalid_param" | "missing_setting" | "failed_precondition" => StatusCode::BAD_REQUEST,
+        "unauthorized" | "forbidden" | "read_only" | "permission_denied" => StatusCode::FORBIDDEN,
+        "not_found" => StatusCode::NOT_FOUND,
+        "already_exists" => StatusCode::CONFLICT,
+        "rate_limited" => StatusCode::TOO_MANY_REQUESTS,
+        "not_supported" => StatusCode::NOT_IMPLEMENTED,
+        "agent_unreachable" => StatusCode::BAD_GATEWAY,
+        "timeout" => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub fn api_error_response(err: ApiError) -> Response {
+    (status_for_code(&err.code), axum::Json(err)).into_response()
+}
+
+/// Pulls the `ApiError` back out of a legacy rspc error envelope, where it
+/// travels as a prefixed string in `result.data.message`.
+fn from_legacy_body(body: &[u8]) -> Option<ApiError> {
+    let value: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let result = value.get("result")?;
+    if result.get("type")?.as_str()? != "error" {
+        return None;
+    }
+    let message = result.get("data")?.get("message")?.as_str()?;
+    let json = message.trim().strip_prefix(API_ERROR_JSON_PREFIX)?;
+    serde_json::from_str(json).ok()
+}
+
+// Middleware: answer rspc resolver errors with the `ApiError` as the JSON body
+// and a status derived from its code, instead of a 200 with the error smuggled
+// in a string.
+pub async fn structured_errors(req: Request<Body>, next: Next) -> Response {
+    let resp = next.run(req).await;
+    let is_json = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if resp.status() != StatusCode::OK || !is_json {
+        return resp;
+    }
+
+    let (parts, body) = resp.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(b) => b,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    // Cheap pre-check so successful results are never parsed here.
+    let smuggled = bytes
+        .windows(API_ERROR_JSON_PREFIX.len())
+        .any(|w| w == API_ERROR_JSON_PREFIX.as_bytes());
+    if let Some(err) = smuggled.then(|| from_legacy_body(&bytes)).flatten() {
+        return api_error_response(err);
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+
+    use super::{api_error_response, from_legacy_body};
+    use crate::rpc::{API_ERROR_JSON_PREFIX, ApiError};
+
+    fn legacy_body(code: &str) -> Vec<u8> {
+        let err = ApiError {
+            code: code.to_string(),
+            message: "boom".to_string(),
+            request_id: "rid-1".to_string(),
+            field_errors: [("name".to_string(), "required".to_string())].into(),
+            hint: None,
+        };
+        let message = format!(
+            "{API_ERROR_JSON_PREFIX}{}",
+            serde_json::to_string(&err).unwrap()
+        );
+        serde_json::to_vec(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "result": { "type": "error", "data": { "code": 500, "message": message, "data": null } },
+        }))
+        .unwrap()
+    }
+
+    async fn rewrite(code: &str) -> (StatusCode, serde_json::Value) {
+        let err = from_legacy_body(&legacy_body(code)).expect("legacy error");
+        let resp = api_error_response(err);
+        let status = resp.status();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn resolver_errors_become_json_bodies_with_mapped_status() {
+        let (status, body) = rewrite("invalid_param").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_param");
+        assert_eq!(body["message"], "boom");
+        assert_eq!(body["request_id"], "rid-1");
+        assert_eq!(body["field_errors"]["name"], "required");
+
+        let (status, body) = rewrite("not_found").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+
+        let (status, _) = rewrite("db_error").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn successful_results_are_left_alone() {
+        let ok = br#"{"jsonrpc":"2.0","id":null,"result":{"type":"response","data":{"ok":true}}}"#;
+        assert!(from_legacy_body(ok).is_none());
+    }
+}
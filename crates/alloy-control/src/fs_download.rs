@@ -0,0 +1,134 @@
+use axum::{
+    Extension,
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+
+use crate::agent_transport::AgentTransport;
+use crate::instance_owners;
+use crate::rpc::AuthUser;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadTreeQuery {
+    #[serde(default)]
+    pub path: String,
+}
+
+pub(crate) fn http_status(code: tonic::Code) -> StatusCode {
+    match code {
+        tonic::Code::InvalidArgument => StatusCode::BAD_REQUEST,
+        tonic::Code::NotFound => StatusCode::NOT_FOUND,
+        tonic::Code::PermissionDenied => StatusCode::FORBIDDEN,
+        tonic::Code::ResourceExhausted => StatusCode::PAYLOAD_TOO_LARGE,
+        tonic::Code::Unavailable => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Name of the downloaded zip: the last path segment, or `data` for the root.
+fn archive_name(path: &str) -> String {
+    let name = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect::<String>();
+    if name.is_empty() || name.chars().all(|c| c == '.') {
+        "data.zip".to_string()
+    } else {
+        format!("{name}.zip")
+    }
+}
+
+/// Non-admins may only download inside instances they own; anything else
+/// (the data root, caches, other users' instances) reads as missing.
+async fn may_download(
+    state: &AppState,
+    user: &AuthUser,
+    path: &str,
+) -> Result<bool, sea_orm::DbErr> {
+    if user.is_admin {
+        return Ok(true);
+    }
+    let Some(instance_id) = instance_owners::instance_of_path(path) else {
+        return Ok(false);
+    };
+    let owner = instance_owners::owner_of(&state.db, &instance_id).await?;
+    Ok(instance_owners::can_access(user, owner))
+}
+
+/// `GET /fs/download?path=`: proxies the agent's DownloadTree stream as a zip.
+pub async fn download_tree(
+    State(state): State<AppState>,
+    user: Option<Extension<AuthUser>>,
+    Query(query): Query<DownloadTreeQuery>,
+) -> Response {
+    let Some(Extension(user)) = user else {
+        return (StatusCode::UNAUTHORIZED, "unauthorized").into_response();
+    };
+    match may_download(&state, &user, &query.path).await {
+        Ok(true) => {}
+        Ok(false) => return (StatusCode::NOT_FOUND, "not found").into_response(),
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {e}")).into_response();
+        }
+    }
+
+    // Instance trees live on the node the instance was placed on.
+    let placed = match instance_owners::instance_of_path(&query.path) {
+        Some(instance_id) => {
+            match crate::node_placement::placed_node(&state.db, &instance_id).await {
+                Ok(v) => v,
+                Err(e) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {e}"))
+                        .into_response();
+                }
+            }
+        }
+        None => None,
+    };
+    let transport = AgentTransport::new(state.agent_hub.clone());
+    let default_node = transport.target_node().await;
+    let transport = match crate::node_placement::pinned_node(placed.as_deref(), &default_node) {
+        Some(node) => transport.for_node(node),
+        None => transport,
+    };
+    let stream = match transport
+        .server_stream::<_, alloy_proto::agent_v1::DownloadTreeChunk>(
+            "/alloy.agent.v1.FilesystemService/DownloadTree",
+            alloy_proto::agent_v1::DownloadTreeRequest {
+                path: query.path.clone(),
+            },
+        )
+        .await
+    {
+        Ok(v) => v,
+        Err(status) => {
+            return (http_status(status.code()), status.message().to_string()).into_response();
+        }
+    };
+
+    let body = Body::from_stream(stream.map(|chunk| {
+        chunk
+            .map(|c| Bytes::from(c.data))
+            .map_err(|status| std::io::Error::other(status.message().to_string()))
+    }));
+    let disposition = format!("attachment; filename=\"{}\"", archive_name(&query.path));
+    let mut resp = body.into_response();
+    let headers = resp.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    if let Ok(v) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, v);
+    }
+    resp
+}
@@ -0,0 +1,107 @@
+use alloy_db::entities::nodes;
+use sea_orm::{ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use sea_orm_migration::MigratorTrait;
+use serde::Serialize;
+
+use crate::agent_tunnel::AgentHub;
+
+/// What `/readyz` reports; the control plane only takes traffic when all hold.
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub db_ok: bool,
+    pub migrations_current: bool,
+    pub healthy_nodes: usize,
+    pub error: Option<String>,
+}
+
+impl Readiness {
+    fn new(
+        db_ok: bool,
+        migrations_current: bool,
+        healthy_nodes: usize,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            ready: db_ok && migrations_current && healthy_nodes > 0,
+            db_ok,
+            migrations_current,
+            healthy_nodes,
+            error,
+        }
+    }
+}
+
+pub async fn readiness(db: &DatabaseConnection, hub: &AgentHub) -> Readiness {
+    if let Err(e) = db.execute_unprepared("SELECT 1").await {
+        return Readiness::new(false, false, 0, Some(format!("db: {e}")));
+    }
+
+    let mut errors = Vec::new();
+    let migrations_current = match applied_migrations(db).await {
+        Ok(applied) => {
+            let expected = alloy_migration::Migrator::migrations().len();
+            if applied < expected {
+                errors.push(format!("migrations: {applied}/{expected} applied"));
+            }
+            applied >= expected
+        }
+        Err(e) => {
+            errors.push(format!("migrations: {e}"));
+            false
+        }
+    };
+
+    let connected = hub.nodes().await;
+    let healthy_nodes = match nodes::Entity::find()
+        .filter(nodes::Column::Enabled.eq(true))
+        .all(db)
+        .await
+    {
+        Ok(rows) => rows
+            .iter()
+            .filter(|n| crate::node_placement::is_healthy(n, &connected))
+            .count(),
+        Err(e) => {
+            errors.push(format!("nodes: {e}"));
+            0
+        }
+    };
+    if healthy_nodes == 0 && errors.is_empty() {
+        errors.push("no healthy node".to_string());
+    }
+
+    let error = (!errors.is_empty()).then(|| errors.join("; "));
+    Readiness::new(true, migrations_current, healthy_nodes, error)
+}
+
+async fn applied_migrations(db: &DatabaseConnection) -> Result<usize, DbErr> {
+    Ok(alloy_migration::Migrator::get_applied_migrations(db)
+        .await?
+        .len())
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::DatabaseConnection;
+
+    use super::{Readiness, readiness};
+    use crate::agent_tunnel::AgentHub;
+
+    #[tokio::test]
+    async fn not_ready_when_the_db_query_fails() {
+        let r = readiness(&DatabaseConnection::Disconnected, &AgentHub::new()).await;
+        assert!(!r.ready);
+        assert!(!r.db_ok);
+        assert!(!r.migrations_current);
+        assert_eq!(r.healthy_nodes, 0);
+        assert!(r.error.is_some_and(|e| e.starts_with("db: ")));
+    }
+
+    #[test]
+    fn ready_needs_a_healthy_node() {
+        assert!(!Readiness::new(true, true, 0, None).ready);
+        assert!(!Readiness::new(true, false, 2, None).ready);
+        assert!(Readiness::new(true, true, 1, None).ready);
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::BTreeMap;
+
+use alloy_db::entities::{instance_placements, nodes};
+use alloy_proto::agent_v1::{
+    DeleteExportRequest, DeleteExportResponse, DeleteInstanceRequest, DeleteInstanceResponse,
+    ExportInstanceRequest, ExportInstanceResponse, GetInstanceRequest, GetInstanceResponse,
+    ImportInstanceRequest, ImportInstanceResponse, ProcessState, ProcessStatus,
+    ReadExportChunkRequest, ReadExportChunkResponse, StartInstanceRequest, StartInstanceResponse,
+    StopInstanceRequest, StopInstanceResponse, WriteImportChunkRequest, WriteImportChunkResponse,
+};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
+
+use crate::agent_transport::AgentTransport;
+
+const CHUNK_BYTES: u32 = 1024 * 1024;
+const MAX_ERROR_CHARS: usize = 512;
+
+/// Migration phases, run in order. Progress is persisted after each phase so
+/// a failed migration resumes where it stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Stop,
+    Export,
+    Transfer,
+    Import,
+    Start,
+    Cleanup,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Stop => "stop",
+            Phase::Export => "export",
+            Phase::Transfer => "transfer",
+            Phase::Import => "import",
+            Phase::Start => "start",
+            Phase::Cleanup => "cleanup",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "stop" => Phase::Stop,
+            "export" => Phase::Export,
+            "transfer" => Phase::Transfer,
+            "import" => Phase::Import,
+            "start" => Phase::Start,
+            "cleanup" => Phase::Cleanup,
+            _ => return None,
+        })
+    }
+
+    fn next(self) -> Option<Self> {
+        match self {
+            Phase::Stop => Some(Phase::Export),
+            Phase::Export => Some(Phase::Transfer),
+            Phase::Transfer => Some(Phase::Import),
+            Phase::Import => Some(Phase::Start),
+            Phase::Start => Some(Phase::Cleanup),
+            Phase::Cleanup => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Invalid(String),
+    Db(DbErr),
+    Agent { phase: Phase, status: tonic::Status },
+}
+
+impl From<DbErr> for MigrationError {
+    fn from(value: DbErr) -> Self {
+        MigrationError::Db(value)
+    }
+}
+
+#[derive(Debug)]
+pub struct MigrationOutcome {
+    pub source_node: String,
+    pub target_node: String,
+    pub status: Option<ProcessStatus>,
+}
+
+struct Progress<'a> {
+    instance_id: &'a str,
+    source: &'a str,
+    target: &'a str,
+    phase: Phase,
+    export: Option<(String, u64)>,
+}
+
+async fn save_progress(
+    db: &DatabaseConnection,
+    p: &Progress<'_>,
+    error: Option<&str>,
+) -> Result<(), DbErr> {
+    let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
+    instance_placements::Entity::insert(instance_placements::ActiveModel {
+        instance_id: Set(p.instance_id.to_string()),
+        node: Set(p.source.to_string()),
+        migration_target: Set(Some(p.target.to_string())),
+        migration_phase: Set(Some(p.phase.as_str().to_string())),
+        migration_export_id: Set(p.export.as_ref().map(|(id, _)| id.clone())),
+        migration_export_size: Set(p.export.as_ref().map(|(_, size)| *size as i64)),
+        migration_error: Set(error.map(|e| e.chars().take(MAX_ERROR_CHARS).collect())),
+        updated_at: Set(now),
+    })
+    .on_conflict(
+        OnConflict::column(instance_placements::Column::InstanceId)
+            .update_columns([
+                instance_placements::Column::Node,
+                instance_placements::Column::MigrationTarget,
+                instance_placements::Column::MigrationPhase,
+                instance_placements::Column::MigrationExportId,
+                instance_placements::Column::MigrationExportSize,
+                instance_placements::Column::MigrationError,
+                instance_placements::Column::UpdatedAt,
+            ])
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn set_placement(
+    db: &DatabaseConnection,
+    instance_id: &str,
+    node: &str,
+) -> Result<(), DbErr> {
+    let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
+    instance_placements::Entity::insert(instance_placements::ActiveModel {
+        instance_id: Set(instance_id.to_string()),
+        node: Set(node.to_string()),
+        migration_target: Set(None),
+        migration_phase: Set(None),
+        migration_export_id: Set(None),
+        migration_export_size: Set(None),
+        migration_error: Set(None),
+        updated_at: Set(now),
+    })
+    .on_conflict(
+        OnConflict::column(instance_placements::Column::InstanceId)
+            .update_columns([
+                instance_placements::Column::Node,
+                instance_placements::Column::MigrationTarget,
+                instance_placements::Column::MigrationPhase,
+                instance_placements::Column::MigrationExportId,
+                instance_placements::Column::MigrationExportSize,
+                instance_placements::Column::MigrationError,
+                instance_placements::Column::UpdatedAt,
+            ])
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    Ok(())
+}
+
+async fn ensure_target_node(
+    db: &DatabaseConnection,
+    transport: &AgentTransport,
+    target: &str,
+) -> Result<(), MigrationError> {
+    let node = nodes::Entity::find()
+        .filter(nodes::Column::Name.eq(target))
+        .one(db)
+        .await?
+        .ok_or_else(|| MigrationError::Invalid(format!("unknown node {target:?}")))?;
+    if !node.enabled {
+        return Err(MigrationError::Invalid(format!(
+            "node {target:?} is disabled"
+        )));
+    }
+    if !transport
+        .connected_nodes()
+        .await
+        .iter()
+        .any(|n| n == target)
+    {
+        return Err(MigrationError::Invalid(format!(
+            "node {target:?} is not connected"
+        )));
+    }
+    Ok(())
+}
+
+/// Move `instance_id` to `target_node`: stop on the source, export, copy the
+/// archive across in chunks, import and start on the target, then remove the
+/// source copy. On failure the source instance is left intact (stopped) and
+/// calling this again resumes from the failed phase.
+pub async fn migrate_instance(
+    db: &DatabaseConnection,
+    transport: &AgentTransport,
+    instance_id: &str,
+    target_node: &str,
+) -> Result<MigrationOutcome, MigrationError> {
+    let target = target_node.trim();
+    if target.is_empty() {
+        return Err(MigrationError::Invalid(
+            "target_node is required".to_string(),
+        ));
+    }
+
+    let row = instance_placements::Entity::find_by_id(instance_id.to_string())
+        .one(db)
+        .await?;
+    let (source, phase, export) = match &row {
+        Some(r) if r.migration_target.is_some() => {
+            let pending = r.migration_target.as_deref().unwrap_or_default();
+            if pending != target {
+                return Err(MigrationError::Invalid(format!(
+                    "a migration to {pending:?} is already in progress; resume or abort it first"
+                )));
+            }
+            let phase = r
+                .migration_phase
+                .as_deref()
+                .and_then(Phase::parse)
+                .unwrap_or(Phase::Stop);
+            let export = r
+                .migration_export_id
+                .clone()
+                .zip(r.migration_export_size.map(|v| v.max(0) as u64));
+            (r.node.clone(), phase, export)
+        }
+        Some(r) => (r.node.clone(), Phase::Stop, None),
+        None => (transport.target_node().await, Phase::Stop, None),
+    };
+    if source == target {
+        return Err(MigrationError::Invalid(format!(
+            "instance is already on node {target:?}"
+        )));
+    }
+    ensure_target_node(db, transport, target).await?;
+
+    let src = transport.for_node(&source);
+    let dst = transport.for_node(target);
+    let target_defaults = crate::node_defaults::load(db, target).await?;
+    let mut progress = Progress {
+        instance_id,
+        source: &source,
+        target,
+        phase,
+        export,
+    };
+    save_progress(db, &progress, None).await?;
+
+    let mut status = None;
+    loop {
+        if let Err(err) = run_phase(&src, &dst, &target_defaults, &mut progress, &mut status).await
+        {
+            save_progress(db, &progress, Some(err.message())).await?;
+            return Err(MigrationError::Agent {
+                phase: progress.phase,
+                status: err,
+            });
+        }
+        match progress.phase.next() {
+            Some(next) => {
+                progress.phase = next;
+                save_progress(db, &progress, None).await?;
+            }
+            None => break,
+        }
+    }
+
+    set_placement(db, instance_id, target).await?;
+    Ok(MigrationOutcome {
+        source_node: source.clone(),
+        target_node: target.to_string(),
+        status,
+    })
+}
+
+async fn run_phase(
+    src: &AgentTransport,
+    dst: &AgentTransport,
+    target_defaults: &BTreeMap<String, String>,
+    p: &mut Progress<'_>,
+    status: &mut Option<ProcessStatus>,
+) -> Result<(), tonic::Status> {
+    let instance_id = p.instance_id.to_string();
+    match p.phase {
+        Phase::Stop => {
+            let res: Result<StopInstanceResponse, _> = src
+                .call(
+                    "/alloy.agent.v1.InstanceService/Stop",
+                    StopInstanceRequest {
+                        instance_id,
+                        timeout_ms: 0,
+                    },
+                )
+                .await;
+            match res {
+                // Not tracked by the process manager means it is not running.
+                Err(e) if e.code() == tonic::Code::NotFound => Ok(()),
+                Err(e) => Err(e),
+                Ok(_) => Ok(()),
+            }
+        }
+        Phase::Export => {
+            let resp: ExportInstanceResponse = src
+                .call(
+                    "/alloy.agent.v1.InstanceService/ExportInstance",
+                    ExportInstanceRequest { instance_id },
+                )
+                .await?;
+            p.export = Some((resp.export_id, resp.size_bytes));
+            Ok(())
+        }
+        Phase::Transfer => {
+            let Some((export_id, size)) = p.export.clone() else {
+                return Err(tonic::Status::failed_precondition(
+                    "no export recorded for this migration; abort and retry",
+                ));
+            };
+            // Always restart from zero: uploads are cheap to redo and this
+            // keeps resume logic trivial.
+            let mut offset = 0u64;
+            loop {
+                let chunk: ReadExportChunkResponse = src
+                    .call(
+                        "/alloy.agent.v1.InstanceService/ReadExportChunk",
+                        ReadExportChunkRequest {
+                            export_id: export_id.clone(),
+                            offset,
+                            limit: CHUNK_BYTES,
+                        },
+                    )
+                    .await?;
+                let n = chunk.data.len() as u64;
+                if n == 0 && !chunk.eof {
+                    return Err(tonic::Status::data_loss("export returned an empty chunk"));
+                }
+                let _: WriteImportChunkResponse = dst
+                    .call(
+                        "/alloy.agent.v1.InstanceService/WriteImportChunk",
+                        WriteImportChunkRequest {
+                            import_id: export_id.clone(),
+                            offset,
+                            data: chunk.data,
+                        },
+                    )
+                    .await?;
+                offset += n;
+                if chunk.eof {
+                    break;
+                }
+            }
+            if offset != size {
+                return Err(tonic::Status::data_loss(format!(
+                    "transferred {offset} bytes, expected {size}"
+                )));
+            }
+            Ok(())
+        }
+        Phase::Import => {
+            let Some((export_id, size)) = p.export.clone() else {
+                return Err(tonic::Status::failed_precondition(
+                    "no export recorded for this migration; abort and retry",
+                ));
+            };
+            let _: ImportInstanceResponse = dst
+                .call(
+                    "/alloy.agent.v1.InstanceService/ImportInstance",
+                    ImportInstanceRequest {
+                        instance_id,
+                        import_id: export_id,
+                        size_bytes: size,
+                    },
+                )
+                .await?;
+            Ok(())
+        }
+        Phase::Start => {
+            let res: Result<StartInstanceResponse, _> = dst
+                .call(
+                    "/alloy.agent.v1.InstanceService/Start",
+                    StartInstanceRequest {
+                        instance_id: instance_id.clone(),
+                        node_default_params: target_defaults.clone().into_iter().collect(),
+                    },
+                )
+                .await;
+            match res {
+                Ok(resp) => {
+                    *status = resp.status;
+                    Ok(())
+                }
+                Err(e) => {
+                    // A resumed migration may find the instance already up.
+                    let running = dst
+                        .call::<_, GetInstanceResponse>(
+                            "/alloy.agent.v1.InstanceService/Get",
+                            GetInstanceRequest { instance_id },
+                        )
+                        .await
+                        .ok()
+                        .and_then(|r| r.info)
+                        .and_then(|i| i.status)
+                        .filter(|s| {
+                            matches!(s.state(), ProcessState::Running | ProcessState::Starting)
+                        });
+                    match running {
+                        Some(s) => {
+                            *status = Some(s);
+                            Ok(())
+                        }
+                        None => Err(e),
+                    }
+                }
+            }
+        }
+        Phase::Cleanup => {
+            // Best-effort: the instance already runs on the target.
+            if let Some((export_id, _)) = p.export.clone() {
+                let res: Result<DeleteExportResponse, _> = src
+                    .call(
+                        "/alloy.agent.v1.InstanceService/DeleteExport",
+                        DeleteExportRequest { export_id },
+                    )
+                    .await;
+                if let Err(err) = res {
+                    tracing::warn!(%err, instance_id, "failed to delete migration export");
+                }
+            }
+            let res: Result<DeleteInstanceResponse, _> = src
+                .call(
+                    "/alloy.agent.v1.InstanceService/Delete",
+                    DeleteInstanceRequest {
+                        instance_id: instance_id.clone(),
+                    },
+                )
+                .await;
+            if let Err(err) = res {
+                tracing::warn!(%err, instance_id, "failed to delete migrated source instance");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Drop a pending migration. Any partial copy on the target is removed; the
+/// source instance is never touched.
+pub async fn abort_migration(
+    db: &DatabaseConnection,
+    transport: &AgentTransport,
+    instance_id: &str,
+) -> Result<bool, MigrationError> {
+    let Some(row) = instance_placements::Entity::find_by_id(instance_id.to_string())
+        .one(db)
+        .await?
+    else {
+        return Ok(false);
+    };
+    let Some(target) = row.migration_target.clone() else {
+        return Ok(false);
+    };
+    let phase = row
+        .migration_phase
+        .as_deref()
+        .and_then(Phase::parse)
+        .unwrap_or(Phase::Stop);
+    if phase >= Phase::Cleanup {
+        return Err(MigrationError::Invalid(
+            "migration already started the instance on the target; resume it instead".to_string(),
+        ));
+    }
+
+    // Only a completed import leaves a copy on the target; earlier phases never
+    // touch an existing instance there.
+    if phase >= Phase::Start {
+        let dst = transport.for_node(&target);
+        let res: Result<DeleteInstanceResponse, _> = dst
+            .call(
+                "/alloy.agent.v1.InstanceService/Delete",
+                DeleteInstanceRequest {
+                    instance_id: instance_id.to_string(),
+                },
+            )
+            .await;
+        match res {
+            Err(e) if e.code() != tonic::Code::NotFound => {
+                return Err(MigrationError::Agent { phase, status: e });
+            }
+            _ => {}
+        }
+    }
+    if let Some(export_id) = row.migration_export_id.clone() {
+        let src = transport.for_node(&row.node);
+        let res: Result<DeleteExportResponse, _> = src
+            .call(
+                "/alloy.agent.v1.InstanceService/DeleteExport",
+                DeleteExportRequest { export_id },
+            )
+            .await;
+        if let Err(err) = res {
+            tracing::warn!(%err, instance_id, "failed to delete migration export");
+        }
+    }
+
+    set_placement(db, instance_id, &row.node).await?;
+    Ok(true)
+}
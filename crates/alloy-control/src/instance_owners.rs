@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+
+use alloy_db::entities::instance_owners;
+use sea_orm::prelude::Uuid;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
+
+use crate::rpc::AuthUser;
+
+/// Marks `user_id` as the owner of `instance_id`, replacing any previous owner.
+pub async fn set_owner(
+    db: &DatabaseConnection,
+    instance_id: &str,
+    user_id: Uuid,
+) -> Result<(), DbErr> {
+    instance_owners::Entity::insert(instance_owners::ActiveModel {
+        instance_id: Set(instance_id.to_string()),
+        user_id: Set(user_id),
+        created_at: Set(chrono::Utc::now().into()),
+    })
+    .on_conflict(
+        OnConflict::column(instance_owners::Column::InstanceId)
+            .update_column(instance_owners::Column::UserId)
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn forget(db: &DatabaseConnection, instance_id: &str) -> Result<(), DbErr> {
+    instance_owners::Entity::delete_by_id(instance_id.to_string())
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn owner_of(db: &DatabaseConnection, instance_id: &str) -> Result<Option<Uuid>, DbErr> {
+    Ok(instance_owners::Entity::find_by_id(instance_id.to_string())
+        .one(db)
+        .await?
+        .map(|row| row.user_id))
+}
+
+pub async fn owned_by(db: &DatabaseConnection, user_id: Uuid) -> Result<HashSet<String>, DbErr> {
+    Ok(instance_owners::Entity::find()
+        .filter(instance_owners::Column::UserId.eq(user_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|row| row.instance_id)
+        .collect())
+}
+
+/// Admins see every instance; everyone else only the ones they own.
+/// Instances with no owner row (created before ownership existed) are admin-only.
+pub fn can_access(user: &AuthUser, owner: Option<Uuid>) -> bool {
+    user.is_admin
+        || owner.is_some_and(|owner| Uuid::parse_str(&user.user_id).is_ok_and(|id| id == owner))
+}
+
+/// Drops the items `user` may not see from an instance listing.
+pub fn retain_visible<T>(
+    user: &AuthUser,
+    owned: &HashSet<String>,
+    items: &mut Vec<T>,
+    instance_id: impl Fn(&T) -> &str,
+) {
+    if !user.is_admin {
+        items.retain(|item| owned.contains(instance_id(item)));
+    }
+}
+
+// Resolves `.` and `..` the way the agent does.
+fn path_parts(path: &str) -> Vec<&str> {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+    parts
+}
+
+/// Instance a data-root relative path falls under (`instances/<id>/...`).
+pub fn instance_of_path(path: &str) -> Option<String> {
+    match path_parts(path).as_slice() {
+        ["instances", id, ..] => Some((*id).to_string()),
+        _ => None,
+    }
+}
+
+/// Whether a data-root relative path is the `instances/` directory itself.
+pub fn is_instances_dir(path: &str) -> bool {
+    path_parts(path) == ["instances"]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use sea_orm::prelude::Uuid;
+
+    use super::{can_access, instance_of_path, is_instances_dir, retain_visible};
+    use crate::rpc::AuthUser;
+
+    fn user(id: Uuid, is_admin: bool) -> AuthUser {
+        AuthUser {
+            user_id: id.to_string(),
+            username: "u".to_string(),
+            is_admin,
+            role: if is_admin { "admin" } else { "operator" }.to_string(),
+        }
+    }
+
+    #[test]
+    fn non_admins_only_see_instances_they_own() {
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        let u = user(alice, false);
+
+        assert!(can_access(&u, Some(alice)));
+        assert!(!can_access(&u, Some(bob)));
+        assert!(!can_access(&u, None));
+
+        let owned = HashSet::from(["a1".to_string()]);
+        let mut ids = vec!["a1", "b1", "legacy"];
+        retain_visible(&u, &owned, &mut ids, |id| id);
+        assert_eq!(ids, ["a1"]);
+
+        assert_eq!(
+            instance_of_path("x/../instances/b1/logs/latest.log").as_deref(),
+            Some("b1")
+        );
+        assert_eq!(instance_of_path("logs/agent.log"), None);
+        assert!(is_instances_dir("./instances/"));
+        assert!(!is_instances_dir(""));
+        assert!(!is_instances_dir("instances/b1"));
+    }
+
+    #[test]
+    fn admins_bypass_ownership() {
+        let admin = user(Uuid::new_v4(), true);
+        assert!(can_access(&admin, Some(Uuid::new_v4())));
+        assert!(can_access(&admin, None));
+
+        let mut ids = vec!["a1", "b1"];
+        retain_visible(&admin, &HashSet::new(), &mut ids, |id| id);
+        assert_eq!(ids, ["a1", "b1"]);
+    }
+}
@@ -1,11 +1,28 @@
 pub mod agent_transport;
 pub mod agent_tunnel;
+pub mod api_tokens;
 pub mod audit;
 pub mod auth;
+pub mod curseforge;
+pub mod download_queue;
+pub mod error_response;
+pub mod fs_download;
+pub mod health;
+pub mod instance_migration;
+pub mod instance_owners;
 pub mod minecraft_versions;
+pub mod node_defaults;
 pub mod node_health;
+pub mod node_placement;
+pub mod node_stats;
 pub mod request_meta;
+pub mod roles;
 pub mod rpc;
+pub mod scheduler;
 pub mod security;
+pub mod settings;
+pub mod shutdown;
+pub mod start_progress;
 pub mod state;
+pub mod steam_guard;
 pub mod update;
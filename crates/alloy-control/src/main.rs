@@ -2,13 +2,22 @@ use std::net::SocketAddr;
 
 use alloy_control::agent_tunnel;
 use alloy_control::auth;
+use alloy_control::error_response;
+use alloy_control::fs_download;
+use alloy_control::health::{self, Readiness};
 use alloy_control::node_health::NodeHealthPoller;
+use alloy_control::node_stats;
 use alloy_control::request_meta::RequestMeta;
 use alloy_control::rpc;
+use alloy_control::scheduler::Scheduler;
 use alloy_control::security;
+use alloy_control::shutdown;
+use alloy_control::start_progress;
 use alloy_control::state::AppState;
 use axum::extract::State;
+use axum::http::StatusCode;
 use axum::middleware;
+use axum::response::IntoResponse;
 use axum::{
     Json, Router,
     routing::{get, post},
@@ -16,6 +25,7 @@ use axum::{
 use sea_orm::EntityTrait;
 use sea_orm_migration::MigratorTrait;
 use serde::Serialize;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Serialize)]
 struct HealthzPort {
@@ -42,14 +52,30 @@ struct HealthzResponse {
     status: &'static str,
     version: &'static str,
     read_only: bool,
+    ready: Readiness,
     agent: HealthzAgent,
 }
 
-async fn healthz(State(_state): State<AppState>) -> Json<HealthzResponse> {
+// Liveness: answers while the process serves HTTP, without touching the DB or agents.
+async fn livez() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let ready = health::readiness(&state.db, &state.agent_hub).await;
+    let status = if ready.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(ready))
+}
+
+async fn healthz(State(state): State<AppState>) -> Json<HealthzResponse> {
     let agent_endpoint = std::env::var("ALLOY_AGENT_ENDPOINT")
         .unwrap_or_else(|_| "http://127.0.0.1:50051".to_string());
 
-    let transport = alloy_control::agent_transport::AgentTransport::new(_state.agent_hub.clone());
+    let transport = alloy_control::agent_transport::AgentTransport::new(state.agent_hub.clone());
     let agent = match transport
         .call::<_, alloy_proto::agent_v1::HealthCheckResponse>(
             "/alloy.agent.v1.AgentHealthService/Check",
@@ -94,8 +120,13 @@ async fn healthz(State(_state): State<AppState>) -> Json<HealthzResponse> {
         },
     };
 
+    let ready = health::readiness(&state.db, &state.agent_hub).await;
     Json(HealthzResponse {
-        status: "ok",
+        status: if ready.ready && agent.ok {
+            "ok"
+        } else {
+            "degraded"
+        },
         version: env!("CARGO_PKG_VERSION"),
         read_only: std::env::var("ALLOY_READ_ONLY").is_ok_and(|v| {
             matches!(
@@ -103,10 +134,44 @@ async fn healthz(State(_state): State<AppState>) -> Json<HealthzResponse> {
                 "1" | "true" | "yes" | "on"
             )
         }),
+        ready,
         agent,
     })
 }
 
+// Off unless ALLOY_METRICS_TOKEN is set; scrapers send it as a bearer token.
+async fn metrics(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> axum::response::Response {
+    let Some(token) = node_stats::metrics_token() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let authorization = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    if !node_stats::metrics_authorized(&token, authorization) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    match alloy_db::entities::node_stats::Entity::find()
+        .all(&*state.db)
+        .await
+    {
+        Ok(rows) => (
+            [(
+                axum::http::header::CONTENT_TYPE,
+                "text/plain; version=0.0.4",
+            )],
+            node_stats::render_prometheus(&rows),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::warn!(error = %e, "metrics query failed");
+            (StatusCode::SERVICE_UNAVAILABLE, "metrics unavailable").into_response()
+        }
+    }
+}
+
 async fn init_db_and_migrate() -> anyhow::Result<AppState> {
     let database_url =
         std::env::var("DATABASE_URL").map_err(|_| anyhow::anyhow!("DATABASE_URL is required"))?;
@@ -118,6 +183,12 @@ async fn init_db_and_migrate() -> anyhow::Result<AppState> {
     // Ensure the default node exists so the UI has something to show.
     // This is idempotent and safe to run on every boot.
     if let Ok(endpoint) = std::env::var("ALLOY_AGENT_ENDPOINT") {
+        // The seeded node has no token of its own; its tunnel needs the shared one.
+        if !std::env::var("ALLOY_AGENT_CONNECT_TOKEN").is_ok_and(|v| !v.trim().is_empty()) {
+            tracing::warn!(
+                "ALLOY_AGENT_CONNECT_TOKEN is not set; the default node can only be reached over direct gRPC"
+            );
+        }
         let _ = alloy_db::entities::nodes::Entity::insert(alloy_db::entities::nodes::ActiveModel {
             id: sea_orm::Set(sea_orm::prelude::Uuid::new_v4()),
             name: sea_orm::Set("default".to_string()),
@@ -127,6 +198,7 @@ async fn init_db_and_migrate() -> anyhow::Result<AppState> {
             last_seen_at: sea_orm::Set(None),
             agent_version: sea_orm::Set(None),
             last_error: sea_orm::Set(None),
+            tags: sea_orm::Set(String::new()),
             created_at: sea_orm::Set(chrono::Utc::now().into()),
             updated_at: sea_orm::Set(chrono::Utc::now().into()),
         })
@@ -157,8 +229,17 @@ async fn main() -> anyhow::Result<()> {
 
     let state = init_db_and_migrate().await?;
 
-    NodeHealthPoller::new(state.db.clone(), state.agent_hub.clone()).spawn();
-    rpc::init_download_queue_runtime(state.db.clone(), state.agent_hub.clone());
+    let shutdown_token = CancellationToken::new();
+    let mut workers = vec![
+        NodeHealthPoller::new(state.db.clone(), state.agent_hub.clone())
+            .spawn(shutdown_token.clone()),
+        Scheduler::new(state.db.clone(), state.agent_hub.clone()).spawn(shutdown_token.clone()),
+    ];
+    workers.extend(rpc::init_download_queue_runtime(
+        state.db.clone(),
+        state.agent_hub.clone(),
+        shutdown_token.clone(),
+    ));
 
     let router = rpc::router();
     let (procedures, _types) = router
@@ -174,7 +255,7 @@ async fn main() -> anyhow::Result<()> {
         .layer(middleware::from_fn(security::csrf_and_origin))
         .with_state(state.clone());
 
-    // Protect /rspc procedures with JWT cookie; allowlist health procedures.
+    // Protect /rspc procedures with JWT cookie or API token; allowlist health procedures.
     let rspc_router = rspc_axum::endpoint(
         procedures,
         |axum::extract::State(state): axum::extract::State<AppState>,
@@ -185,24 +266,74 @@ async fn main() -> anyhow::Result<()> {
                 agent_hub: state.agent_hub.clone(),
                 user: user.map(|axum::Extension(u)| u),
                 request_id: meta.request_id,
+                agent_version: meta.agent_version,
             }
         },
     )
-    .layer(middleware::from_fn(security::rspc_auth_guard));
+    .layer(middleware::from_fn(error_response::structured_errors))
+    .layer(middleware::from_fn_with_state(
+        state.clone(),
+        security::rspc_auth_guard,
+    ));
+
+    // Streamed downloads sit outside rspc but use the same auth guard.
+    let fs_router = Router::new()
+        .route("/download", get(fs_download::download_tree))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            security::rspc_auth_guard,
+        ));
+
+    // Start progress is relayed as server-sent events, also outside rspc.
+    let stream_router = Router::new()
+        .route(
+            "/process.startProgress",
+            get(start_progress::start_progress),
+        )
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            security::rspc_auth_guard,
+        ));
 
     let app = Router::new()
         .route("/healthz", get(healthz))
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
         .route("/auth/whoami", get(auth::whoami))
         .route("/agent/ws", get(agent_tunnel::agent_ws))
         .nest("/auth", auth_router)
         .nest("/rspc", rspc_router)
+        .nest("/fs", fs_router)
+        .nest("/stream", stream_router)
         .layer(middleware::from_fn(security::request_id))
-        .with_state(state);
+        .with_state(state.clone());
     let addr: SocketAddr = ([0, 0, 0, 0], 8080).into();
     tracing::info!(%addr, "alloy-control HTTP listening");
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // The login lockout keys on the peer address.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown({
+        let shutdown_token = shutdown_token.clone();
+        async move {
+            shutdown::signal().await;
+            tracing::info!("shutting down: draining in-flight requests");
+            shutdown_token.cancel();
+        }
+    })
+    .await?;
+
+    // Upgraded websockets aren't tracked by the HTTP drain; close them explicitly
+    // once no request can still be waiting on a tunnel response.
+    state.agent_hub.close_all().await;
+    let drain = futures_util::future::join_all(workers);
+    if tokio::time::timeout(shutdown::GRACE, drain).await.is_err() {
+        tracing::warn!("background workers did not stop in time");
+    }
 
     Ok(())
 }
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use alloy_db::entities::settings;
+use alloy_proto::is_secret_param_key;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait, Set};
+
+const SETTING_PREFIX: &str = "node.default_params.";
+const MAX_KEYS: usize = 64;
+const MAX_KEY_CHARS: usize = 64;
+const MAX_VALUE_BYTES: usize = 16 * 1024;
+
+/// Placeholder returned instead of secret values.
+pub const REDACTED: &str = "<redacted>";
+
+fn setting_key(node: &str) -> String {
+    format!("{SETTING_PREFIX}{node}")
+}
+
+pub fn redact(params: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+    params
+        .iter()
+        .map(|(k, v)| {
+            let v = if is_secret_param_key(k) {
+                REDACTED.to_string()
+            } else {
+                v.clone()
+            };
+            (k.clone(), v)
+        })
+        .collect()
+}
+
+pub fn validate(params: &BTreeMap<String, String>) -> Result<(), String> {
+    if params.len() > MAX_KEYS {
+        return Err(format!("at most {MAX_KEYS} default params are allowed"));
+    }
+    for (key, value) in params {
+        let valid_key = !key.is_empty()
+            && key.len() <= MAX_KEY_CHARS
+            && key
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid_key {
+            return Err(format!("invalid param key: {key:?}"));
+        }
+        if value.len() > MAX_VALUE_BYTES {
+            return Err(format!("value for {key} is too large"));
+        }
+    }
+    Ok(())
+}
+
+/// Stored defaults for `node` (unredacted; only pass these to the agent).
+pub async fn load(db: &DatabaseConnection, node: &str) -> Result<BTreeMap<String, String>, DbErr> {
+    let Some(model) = settings::Entity::find_by_id(setting_key(node))
+        .one(db)
+        .await?
+    else {
+        return Ok(BTreeMap::new());
+    };
+    Ok(serde_json::from_str(&model.value).unwrap_or_else(|err| {
+        tracing::warn!(%err, node, "ignoring malformed node default params");
+        BTreeMap::new()
+    }))
+}
+
+/// Replaces the defaults for `node` and returns the stored map.
+/// `REDACTED` values keep the stored secret so a redacted map can be sent back
+/// unchanged; blank values drop the key.
+pub async fn save(
+    db: &DatabaseConnection,
+    node: &str,
+    params: BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, DbErr> {
+    let existing = load(db, node).await?;
+    let mut next = BTreeMap::new();
+    for (key, value) in params {
+        if value == REDACTED {
+            if let Some(old) = existing.get(&key) {
+                next.insert(key, old.clone());
+            }
+            continue;
+        }
+        if !value.trim().is_empty() {
+            next.insert(key, value);
+        }
+    }
+
+    if next.is_empty() {
+        settings::Entity::delete_by_id(setting_key(node))
+            .exec(db)
+            .await?;
+        return Ok(next);
+    }
+
+    let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
+    let value = serde_json::to_string(&next).unwrap_or_default();
+    settings::Entity::insert(settings::ActiveModel {
+        key: Set(setting_key(node)),
+        value: Set(value),
+        is_secret: Set(next.keys().any(|k| is_secret_param_key(k))),
+        created_at: Set(now),
+        updated_at: Set(now),
+    })
+    .on_conflict(
+        OnConflict::column(settings::Column::Key)
+            .update_columns([
+                settings::Column::Value,
+                settings::Column::IsSecret,
+                settings::Column::UpdatedAt,
+            ])
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{REDACTED, redact};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn redact_matches_the_agent_secret_keys() {
+        let params = BTreeMap::from([
+            ("frp_config".to_string(), "token = abc".to_string()),
+            ("rcon_password".to_string(), "hunter2".to_string()),
+            ("memory_mb".to_string(), "2048".to_string()),
+        ]);
+        let redacted = redact(&params);
+        assert_eq!(redacted["frp_config"], REDACTED);
+        assert_eq!(redacted["rcon_password"], REDACTED);
+        assert_eq!(redacted["memory_mb"], "2048");
+    }
+}
@@ -1,12 +1,66 @@
-use std::time::Duration;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use alloy_db::entities::nodes;
 use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
 
 use alloy_proto::agent_v1::HealthCheckRequest;
 use alloy_proto::agent_v1::agent_health_service_client::AgentHealthServiceClient;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use tonic::Request;
 
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// Dialing a down node backs off exponentially up to this.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+// Consecutive outcomes needed before the reported status flips.
+const FAILURES_TO_MARK_DOWN: u32 = 3;
+const SUCCESSES_TO_MARK_UP: u32 = 2;
+
+/// In-memory poll history for one node.
+#[derive(Debug, Default)]
+struct ProbeState {
+    consecutive_failures: u32,
+    consecutive_successes: u32,
+    // Last status written to `last_error`; `None` until the first poll.
+    healthy: Option<bool>,
+    next_dial_at: Option<Instant>,
+}
+
+impl ProbeState {
+    /// Records a poll outcome and returns the status to report, which only
+    /// changes once enough consecutive outcomes agree.
+    fn observe(&mut self, ok: bool) -> bool {
+        if ok {
+            self.consecutive_failures = 0;
+            self.consecutive_successes = self.consecutive_successes.saturating_add(1);
+        } else {
+            self.consecutive_successes = 0;
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        }
+        let healthy = match self.healthy {
+            None => ok,
+            Some(true) => self.consecutive_failures < FAILURES_TO_MARK_DOWN,
+            Some(false) => self.consecutive_successes >= SUCCESSES_TO_MARK_UP,
+        };
+        self.healthy = Some(healthy);
+        healthy
+    }
+
+    /// Delay before dialing again: the poll interval, doubled per consecutive failure.
+    fn backoff(&self) -> Duration {
+        let doublings = self.consecutive_failures.min(16);
+        POLL_INTERVAL
+            .saturating_mul(1 << doublings)
+            .min(MAX_BACKOFF)
+    }
+}
+
+enum Outcome {
+    Up { agent_version: String },
+    Down { error: String },
+}
+
 #[derive(Clone)]
 pub struct NodeHealthPoller {
     db: std::sync::Arc<DatabaseConnection>,
@@ -18,16 +72,19 @@ impl NodeHealthPoller {
         Self { db, hub }
     }
 
-    pub fn spawn(self) {
+    pub fn spawn(self, shutdown: CancellationToken) -> JoinHandle<()> {
         tokio::spawn(async move {
+            let mut probes = HashMap::new();
             loop {
-                self.tick().await;
-                tokio::time::sleep(Duration::from_secs(5)).await;
+                self.tick(&mut probes).await;
+                if !crate::shutdown::sleep(&shutdown, POLL_INTERVAL).await {
+                    break;
+                }
             }
-        });
+        })
     }
 
-    async fn tick(&self) {
+    async fn tick(&self, probes: &mut HashMap<String, ProbeState>) {
         let db = &*self.db;
 
         let rows = match nodes::Entity::find()
@@ -38,47 +95,110 @@ impl NodeHealthPoller {
             Ok(v) => v,
             Err(_) => return,
         };
+        probes.retain(|name, _| rows.iter().any(|n| &n.name == name));
 
         for n in rows {
             let name = n.name.clone();
             let endpoint = n.endpoint.clone();
-            let mut update: nodes::ActiveModel = n.into();
-            update.updated_at = Set(chrono::Utc::now().into());
+            let probe = probes.entry(name.clone()).or_default();
 
-            if let Some(conn) = self.hub.get(&name).await {
-                update.last_seen_at = Set(Some(chrono::Utc::now().into()));
-                update.agent_version = Set(Some(conn.agent_version.clone()));
-                update.last_error = Set(None);
-                let _ = update.update(db).await;
+            let mut dialed = false;
+            let outcome = if let Some(conn) = self.hub.get(&name).await {
+                Outcome::Up {
+                    agent_version: conn.agent_version.clone(),
+                }
+            } else if !endpoint.trim().starts_with("http://")
+                && !endpoint.trim().starts_with("https://")
+            {
+                // "tunnel://" is a logical endpoint used for reverse-connected nodes.
+                // If the node isn't currently tunnel-connected, there's nothing to dial.
+                Outcome::Down {
+                    error: "agent is not connected".to_string(),
+                }
+            } else if probe.next_dial_at.is_some_and(|at| Instant::now() < at) {
                 continue;
-            }
+            } else {
+                dialed = true;
+                match AgentHealthServiceClient::connect(endpoint.clone()).await {
+                    Ok(mut client) => match client.check(Request::new(HealthCheckRequest {})).await
+                    {
+                        Ok(resp) => Outcome::Up {
+                            agent_version: resp.into_inner().agent_version,
+                        },
+                        Err(e) => Outcome::Down {
+                            error: format!("health check failed: {e}"),
+                        },
+                    },
+                    Err(e) => Outcome::Down {
+                        error: format!("connect failed ({endpoint}): {e}"),
+                    },
+                }
+            };
 
-            // "tunnel://" is a logical endpoint used for reverse-connected nodes.
-            // If the node isn't currently tunnel-connected, there's nothing to dial.
-            if !endpoint.trim().starts_with("http://") && !endpoint.trim().starts_with("https://") {
-                update.last_error = Set(Some("agent is not connected".to_string()));
-                let _ = update.update(db).await;
-                continue;
+            let healthy = probe.observe(matches!(outcome, Outcome::Up { .. }));
+            if dialed {
+                probe.next_dial_at = Some(Instant::now() + probe.backoff());
             }
 
-            match AgentHealthServiceClient::connect(endpoint.clone()).await {
-                Ok(mut client) => match client.check(Request::new(HealthCheckRequest {})).await {
-                    Ok(resp) => {
-                        let resp = resp.into_inner();
-                        update.last_seen_at = Set(Some(chrono::Utc::now().into()));
-                        update.agent_version = Set(Some(resp.agent_version));
+            let mut update: nodes::ActiveModel = n.into();
+            match outcome {
+                Outcome::Up { agent_version } => {
+                    update.last_seen_at = Set(Some(chrono::Utc::now().into()));
+                    update.agent_version = Set(Some(agent_version));
+                    if healthy {
                         update.last_error = Set(None);
                     }
-                    Err(e) => {
-                        update.last_error = Set(Some(format!("health check failed: {e}")));
-                    }
-                },
-                Err(e) => {
-                    update.last_error = Set(Some(format!("connect failed ({endpoint}): {e}")));
+                }
+                // A blip on a node still reported healthy isn't worth a write.
+                Outcome::Down { .. } if healthy => continue,
+                Outcome::Down { error } => {
+                    update.last_error = Set(Some(error));
                 }
             }
-
+            update.updated_at = Set(chrono::Utc::now().into());
             let _ = update.update(db).await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FAILURES_TO_MARK_DOWN, ProbeState};
+
+    #[test]
+    fn backoff_doubles_per_failure_up_to_the_cap() {
+        let mut p = ProbeState::default();
+        let mut schedule = Vec::new();
+        for _ in 0..10 {
+            p.observe(false);
+            schedule.push(p.backoff().as_secs());
+        }
+        assert_eq!(schedule, [10, 20, 40, 80, 160, 300, 300, 300, 300, 300]);
+
+        p.observe(true);
+        assert_eq!(p.backoff(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn status_only_flips_after_consecutive_outcomes_agree() {
+        let mut p = ProbeState::default();
+        assert!(p.observe(true));
+
+        // Isolated failures on a healthy node are absorbed.
+        let outcomes = [false, false, true, false, false, true];
+        assert!(outcomes.iter().all(|&ok| p.observe(ok)));
+
+        for _ in 1..FAILURES_TO_MARK_DOWN {
+            assert!(p.observe(false));
+        }
+        assert!(!p.observe(false));
+
+        // One success isn't enough to come back up.
+        assert!(!p.observe(true));
+        assert!(!p.observe(false));
+        assert!(!p.observe(true));
+        assert!(p.observe(true));
+    }
+}
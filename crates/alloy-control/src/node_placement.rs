@@ -0,0 +1,166 @@
+use alloy_db::entities::{instance_placements, nodes};
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait, QueryOrder};
+
+const MAX_TAGS: usize = 16;
+const MAX_TAG_LEN: usize = 32;
+
+/// Tags stored in `nodes.tags`, in their canonical (sorted, deduped) order.
+pub fn parse_tags(raw: &str) -> Vec<String> {
+    let mut out: Vec<String> = raw
+        .split(',')
+        .map(|t| t.trim().to_ascii_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    out.sort();
+    out.dedup();
+    out
+}
+
+pub fn format_tags(tags: &[String]) -> String {
+    tags.join(",")
+}
+
+/// Validates tags from user input: lowercase letters, digits, `-` and `_`.
+pub fn normalize_tags(input: &[String]) -> Result<Vec<String>, String> {
+    let tags = parse_tags(&input.join(","));
+    if tags.len() > MAX_TAGS {
+        return Err(format!("at most {MAX_TAGS} tags are allowed"));
+    }
+    for t in &tags {
+        let valid_chars = t
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if t.len() > MAX_TAG_LEN || !valid_chars {
+            return Err(format!("invalid tag {t:?}"));
+        }
+    }
+    Ok(tags)
+}
+
+/// A node is healthy when its tunnel is up, or the health poller last reached it.
+pub fn is_healthy(node: &nodes::Model, connected: &[String]) -> bool {
+    connected.contains(&node.name) || (node.last_seen_at.is_some() && node.last_error.is_none())
+}
+
+/// Node for a new instance when the caller didn't pick one: the first enabled,
+/// healthy node in creation order.
+pub fn pick_default<'a>(
+    rows: &'a [nodes::Model],
+    connected: &[String],
+) -> Option<&'a nodes::Model> {
+    rows.iter()
+        .filter(|n| n.enabled && is_healthy(n, connected))
+        .min_by(|a, b| (a.created_at, &a.name).cmp(&(b.created_at, &b.name)))
+}
+
+/// Node to pin calls for an instance to, if it doesn't live on `default_node`.
+/// The default node keeps the default transport so direct (non-tunnel)
+/// endpoints still work.
+pub fn pinned_node<'a>(placed: Option<&'a str>, default_node: &str) -> Option<&'a str> {
+    placed.filter(|node| *node != default_node)
+}
+
+pub async fn all_nodes(db: &DatabaseConnection) -> Result<Vec<nodes::Model>, DbErr> {
+    nodes::Entity::find()
+        .order_by_asc(nodes::Column::CreatedAt)
+        .all(db)
+        .await
+}
+
+pub async fn placed_node(
+    db: &DatabaseConnection,
+    instance_id: &str,
+) -> Result<Option<String>, DbErr> {
+    let row = instance_placements::Entity::find_by_id(instance_id.to_string())
+        .one(db)
+        .await?;
+    Ok(row.map(|row| row.node))
+}
+
+pub async fn forget(db: &DatabaseConnection, instance_id: &str) -> Result<(), DbErr> {
+    instance_placements::Entity::delete_by_id(instance_id.to_string())
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Distinct nodes that have at least one instance placed on them.
+pub async fn placed_nodes(db: &DatabaseConnection) -> Result<Vec<String>, DbErr> {
+    let mut out: Vec<String> = instance_placements::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|row| row.node)
+        .collect();
+    out.sort();
+    out.dedup();
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_db::entities::nodes;
+    use sea_orm::prelude::Uuid;
+
+    use super::{normalize_tags, parse_tags, pick_default, pinned_node};
+    use crate::agent_transport::AgentTransport;
+    use crate::agent_tunnel::AgentHub;
+
+    fn node(name: &str, age_secs: i64, enabled: bool, last_error: Option<&str>) -> nodes::Model {
+        let created = chrono::Utc::now() - chrono::Duration::seconds(age_secs);
+        nodes::Model {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            endpoint: format!("tunnel://{name}"),
+            connect_token_hash: None,
+            enabled,
+            last_seen_at: Some(created.into()),
+            agent_version: None,
+            last_error: last_error.map(str::to_string),
+            tags: String::new(),
+            created_at: created.into(),
+            updated_at: created.into(),
+        }
+    }
+
+    #[test]
+    fn default_node_is_the_first_enabled_healthy_one() {
+        let rows = vec![
+            node("disabled", 40, false, None),
+            node("down", 30, true, Some("connect failed")),
+            node("b", 10, true, None),
+            node("a", 20, true, None),
+        ];
+        assert_eq!(pick_default(&rows, &[]).map(|n| n.name.as_str()), Some("a"));
+
+        // A live tunnel counts as healthy even if the last poll failed.
+        let connected = vec!["down".to_string()];
+        assert_eq!(
+            pick_default(&rows, &connected).map(|n| n.name.as_str()),
+            Some("down")
+        );
+
+        let none_usable = vec![node("x", 5, false, None), node("y", 5, true, Some("boom"))];
+        assert!(pick_default(&none_usable, &[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn placed_instances_route_to_their_node() {
+        assert_eq!(pinned_node(Some("edge-2"), "default"), Some("edge-2"));
+        assert_eq!(pinned_node(Some("default"), "default"), None);
+        assert_eq!(pinned_node(None, "default"), None);
+
+        let transport = AgentTransport::new(AgentHub::new()).for_node("edge-2");
+        assert_eq!(transport.target_node().await, "edge-2");
+    }
+
+    #[test]
+    fn tags_are_normalized() {
+        assert_eq!(parse_tags(" SSD,eu,,ssd "), ["eu", "ssd"]);
+        assert_eq!(
+            normalize_tags(&["eu-west".to_string(), "gpu_1".to_string()]).unwrap(),
+            ["eu-west", "gpu_1"]
+        );
+        assert!(normalize_tags(&["no spaces".to_string()]).is_err());
+    }
+}
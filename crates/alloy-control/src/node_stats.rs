@@ -0,0 +1,219 @@
+use alloy_db::entities::node_stats;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
+
+use crate::rpc::Ctx;
+
+const MAX_REASON_CHARS: usize = 512;
+
+/// Records the outcome of a start request proxied to `node`.
+pub async fn record_start(ctx: &Ctx, node: &str, result: Result<(), &str>) {
+    if let Err(err) = record_start_db(&ctx.db, node, result).await {
+        tracing::warn!(%err, node, "failed to update node start stats");
+    }
+}
+
+pub async fn record_start_db(
+    db: &DatabaseConnection,
+    node: &str,
+    result: Result<(), &str>,
+) -> Result<(), DbErr> {
+    let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
+
+    // Ensure the row exists, then bump counters in a single UPDATE so
+    // concurrent starts don't lose increments.
+    node_stats::Entity::insert(node_stats::ActiveModel {
+        node: Set(node.to_string()),
+        starts_attempted: Set(0),
+        starts_succeeded: Set(0),
+        starts_failed: Set(0),
+        last_success_at: Set(None),
+        last_failure_at: Set(None),
+        last_failure_reason: Set(None),
+        updated_at: Set(now),
+    })
+    .on_conflict(
+        OnConflict::column(node_stats::Column::Node)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+
+    let mut update = node_stats::Entity::update_many()
+        .col_expr(
+            node_stats::Column::StartsAttempted,
+            Expr::col(node_stats::Column::StartsAttempted).add(1),
+        )
+        .col_expr(node_stats::Column::UpdatedAt, Expr::value(now));
+    update = match result {
+        Ok(()) => update
+            .col_expr(
+                node_stats::Column::StartsSucceeded,
+                Expr::col(node_stats::Column::StartsSucceeded).add(1),
+            )
+            .col_expr(node_stats::Column::LastSuccessAt, Expr::value(now)),
+        Err(reason) => update
+            .col_expr(
+                node_stats::Column::StartsFailed,
+                Expr::col(node_stats::Column::StartsFailed).add(1),
+            )
+            .col_expr(node_stats::Column::LastFailureAt, Expr::value(now))
+            .col_expr(
+                node_stats::Column::LastFailureReason,
+                Expr::value(reason.chars().take(MAX_REASON_CHARS).collect::<String>()),
+            ),
+    };
+    update
+        .filter(node_stats::Column::Node.eq(node))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Failure rate in basis points (0..=10000), or `None` before the first start.
+pub fn failure_rate_x100(stats: &node_stats::Model) -> Option<u32> {
+    if stats.starts_attempted <= 0 {
+        return None;
+    }
+    let failed = stats.starts_failed.max(0) as u64;
+    let attempted = stats.starts_attempted as u64;
+    Some((failed.saturating_mul(10_000) / attempted).min(10_000) as u32)
+}
+
+/// Bearer token `/metrics` requires; the endpoint is off while unset.
+pub fn metrics_token() -> Option<String> {
+    std::env::var("ALLOY_METRICS_TOKEN")
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Whether an `Authorization` header carries `token`, compared in constant time.
+pub fn metrics_authorized(token: &str, authorization: Option<&str>) -> bool {
+    let Some(got) = authorization
+        .and_then(|v| v.trim().strip_prefix("Bearer "))
+        .map(str::trim)
+    else {
+        return false;
+    };
+    got.len() == token.len()
+        && got
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn escape_label(v: &str) -> String {
+    v.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn push_metric(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    rows: &[node_stats::Model],
+    value: impl Fn(&node_stats::Model) -> Option<i64>,
+) {
+    out.push_str(&format!("# HELP {name} {help}\n# TYPE {name} {kind}\n"));
+    for s in rows {
+        if let Some(v) = value(s) {
+            out.push_str(&format!(
+                "{name}{{node=\"{}\"}} {v}\n",
+                escape_label(&s.node)
+            ));
+        }
+    }
+}
+
+/// Renders per-node start counters in the Prometheus text exposition format.
+pub fn render_prometheus(rows: &[node_stats::Model]) -> String {
+    let mut out = String::new();
+    push_metric(
+        &mut out,
+        "alloy_node_starts_attempted_total",
+        "counter",
+        "Start requests proxied to the node.",
+        rows,
+        |s| Some(s.starts_attempted.into()),
+    );
+    push_metric(
+        &mut out,
+        "alloy_node_starts_succeeded_total",
+        "counter",
+        "Start requests the node accepted.",
+        rows,
+        |s| Some(s.starts_succeeded.into()),
+    );
+    push_metric(
+        &mut out,
+        "alloy_node_starts_failed_total",
+        "counter",
+        "Start requests the node rejected.",
+        rows,
+        |s| Some(s.starts_failed.into()),
+    );
+    push_metric(
+        &mut out,
+        "alloy_node_last_start_success_timestamp_seconds",
+        "gauge",
+        "Unix time of the last successful start.",
+        rows,
+        |s| s.last_success_at.map(|t| t.timestamp()),
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy_db::entities::node_stats;
+
+    use super::{failure_rate_x100, metrics_authorized, render_prometheus};
+
+    fn stats(node: &str, attempted: i32, failed: i32) -> node_stats::Model {
+        let at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        node_stats::Model {
+            node: node.to_string(),
+            starts_attempted: attempted,
+            starts_succeeded: attempted - failed,
+            starts_failed: failed,
+            last_success_at: (attempted > failed).then(|| at.into()),
+            last_failure_at: None,
+            last_failure_reason: None,
+            updated_at: at.into(),
+        }
+    }
+
+    #[test]
+    fn failure_rate_is_basis_points() {
+        assert_eq!(failure_rate_x100(&stats("a", 0, 0)), None);
+        assert_eq!(failure_rate_x100(&stats("a", 3, 1)), Some(3333));
+        assert_eq!(failure_rate_x100(&stats("a", 4, 4)), Some(10_000));
+        // Counters drifting past each other never exceed 100%.
+        assert_eq!(failure_rate_x100(&stats("a", 2, 5)), Some(10_000));
+    }
+
+    #[test]
+    fn prometheus_output_escapes_labels_and_skips_missing_values() {
+        let out = render_prometheus(&[stats("default", 3, 1), stats("a\"b", 1, 1)]);
+        assert!(out.contains("# TYPE alloy_node_starts_attempted_total counter\n"));
+        assert!(out.contains("alloy_node_starts_attempted_total{node=\"default\"} 3\n"));
+        assert!(out.contains("alloy_node_starts_failed_total{node=\"a\\\"b\"} 1\n"));
+        assert!(out.contains(
+            "alloy_node_last_start_success_timestamp_seconds{node=\"default\"} 1700000000\n"
+        ));
+        assert!(!out.contains("alloy_node_last_start_success_timestamp_seconds{node=\"a"));
+    }
+
+    #[test]
+    fn metrics_token_must_match_exactly() {
+        assert!(metrics_authorized("s3cret", Some("Bearer s3cret")));
+        assert!(!metrics_authorized("s3cret", Some("Bearer s3cre")));
+        assert!(!metrics_authorized("s3cret", Some("s3cret")));
+        assert!(!metrics_authorized("s3cret", None));
+    }
+}
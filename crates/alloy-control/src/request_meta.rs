@@ -1,4 +1,8 @@
+use crate::agent_transport::AgentVersionSlot;
+
 #[derive(Clone, Debug)]
 pub struct RequestMeta {
     pub request_id: String,
+    // Filled by agent calls made while serving the request; echoed as a response header.
+    pub agent_version: AgentVersionSlot,
 }
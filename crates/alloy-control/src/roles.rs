@@ -0,0 +1,123 @@
+/// Access tiers, lowest first; each role can do everything the ones below it can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Read-only: instance state, logs and stats.
+    Viewer,
+    /// Runs instances: start/stop, files, backups, schedules.
+    Operator,
+    /// Manages nodes, settings, FRP and updates.
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Admin => "admin",
+        }
+    }
+
+    /// Parses the `users.role` column; unknown values fall back to `viewer`.
+    pub fn parse(raw: &str) -> Self {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "admin" => Self::Admin,
+            "operator" => Self::Operator,
+            _ => Self::Viewer,
+        }
+    }
+
+    /// Role of a user row, honoring `is_admin` for rows written before roles.
+    pub fn of_user(role: &str, is_admin: bool) -> Self {
+        if is_admin {
+            Self::Admin
+        } else {
+            Self::parse(role)
+        }
+    }
+}
+
+// Reads that are safe to show anyone signed in, plus managing one's own API
+// tokens (scopes are capped at the caller's role). Instance env/config dumps,
+// files and diagnostics can carry secrets, so they stay at operator.
+const VIEWER_PROCEDURES: &[&str] = &[
+    "agent.health",
+    "apiToken.create",
+    "apiToken.list",
+    "apiToken.revoke",
+    "audit.list",
+    "control.ping",
+    "instance.diskUsage",
+    "instance.get",
+    "instance.list",
+    "instance.listBackups",
+    "instance.stagedUpgrade",
+    "log.tailFile",
+    "minecraft.versions",
+    "node.list",
+    "node.resources",
+    "process.cacheStats",
+    "process.downloadQueue",
+    "process.list",
+    "process.logsTail",
+    "process.resourceHistory",
+    "process.startProgress",
+    "process.status",
+    "process.templates",
+    "process.versionRequirements",
+    "schedule.list",
+    "settings.status",
+    "update.check",
+];
+
+const ADMIN_PROCEDURES: &[&str] = &[
+    "instance.assignNode",
+    "node.applyUpdate",
+    "node.create",
+    "node.prepareUpdate",
+    "node.rotateToken",
+    "node.setEnabled",
+    "node.setTags",
+    "settings.previewSteamGuard",
+    "settings.setCurseforgeApiKey",
+    "settings.setDstDefaultKleiKey",
+    "settings.setNodeDefaultParams",
+    "settings.setSteamcmdCredentials",
+    "settings.testCurseforgeKey",
+    "update.trigger",
+];
+
+/// Minimum role for an rspc procedure (`instance.stop`). Anything not listed
+/// needs `operator`, so new procedures are never open to viewers by default.
+pub fn required_role(procedure: &str) -> Role {
+    if procedure.starts_with("frp.") || ADMIN_PROCEDURES.contains(&procedure) {
+        Role::Admin
+    } else if VIEWER_PROCEDURES.contains(&procedure) {
+        Role::Viewer
+    } else {
+        Role::Operator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Role, required_role};
+
+    #[test]
+    fn viewers_can_tail_logs_but_not_stop_instances() {
+        let viewer = Role::Viewer;
+        assert!(viewer >= required_role("log.tailFile"));
+        assert!(viewer >= required_role("process.logsTail"));
+        assert!(viewer < required_role("instance.stop"));
+        assert!(Role::Operator >= required_role("instance.stop"));
+        assert!(Role::Operator < required_role("node.rotateToken"));
+        assert_eq!(required_role("frp.list"), Role::Admin);
+    }
+
+    #[test]
+    fn legacy_admins_and_unknown_roles() {
+        assert_eq!(Role::of_user("operator", true), Role::Admin);
+        assert_eq!(Role::of_user("operator", false), Role::Operator);
+        assert_eq!(Role::of_user("superuser", false), Role::Viewer);
+    }
+}
@@ -1,30 +1,27 @@
 use alloy_proto::agent_v1::{
-    ClearCacheRequest, CreateInstanceRequest, DeleteInstancePreviewRequest, DeleteInstanceRequest,
-    GetCacheStatsRequest, GetCapabilitiesRequest, GetInstanceRequest, GetStatusRequest,
-    GetWarmTemplateProgressRequest, HealthCheckRequest, ListDirRequest, ListInstancesRequest,
-    ListProcessesRequest, ListTemplatesRequest, ReadFileRequest, StartFromTemplateRequest,
-    StartInstanceRequest, StopInstanceRequest, StopProcessRequest, TailFileRequest,
-    TailLogsRequest, UpdateInstanceRequest, WarmTemplateCacheRequest,
+    ApplyUpdateRequest, BulkApplyParamsRequest, CancelWarmTemplateRequest, ClearCacheRequest,
+    CreateInstanceRequest, DeleteInstancePreviewRequest, DeleteInstanceRequest,
+    GetCacheStatsRequest, GetCapabilitiesRequest, GetInstanceRequest, GetNodeResourcesRequest,
+    GetResourceHistoryRequest, GetStatusRequest, GetVersionRequirementsRequest,
+    GetWarmTemplateProgressRequest, HealthCheckRequest, InspectInstanceRequest, ListDirRequest,
+    ListInstancesRequest, ListProcessesRequest, ListTemplatesRequest, LogStream,
+    PrepareUpdateRequest, ReadFileRequest, ReloadInstanceRequest, ReloadTemplatesRequest,
+    RestartInstanceRequest, SendConsoleRequest, StartFromTemplateRequest, StartInstanceRequest,
+    StopInstanceRequest, StopProcessRequest, TailFileRequest, TailLogsRequest,
+    UpdateInstanceRequest, ValidateTemplateRequest, WarmTemplateCacheRequest,
 };
 use rspc::{Procedure, ProcedureError, ResolverError, Router};
 
 use specta::Type;
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     sync::{Arc, OnceLock},
     time::{Duration, Instant},
 };
 
 use crate::agent_transport::AgentTransport;
 use crate::audit;
-
-const SETTING_DST_DEFAULT_KLEI_KEY: &str = "dst.default_klei_key";
-const SETTING_CURSEFORGE_API_KEY: &str = "minecraft.curseforge_api_key";
-const SETTING_STEAMCMD_USERNAME: &str = "steamcmd.username";
-const SETTING_STEAMCMD_PASSWORD: &str = "steamcmd.password";
-const SETTING_STEAMCMD_SHARED_SECRET: &str = "steamcmd.shared_secret";
-const SETTING_STEAMCMD_ACCOUNT_NAME: &str = "steamcmd.account_name";
-const SETTING_DOWNLOAD_QUEUE_PAUSED: &str = "downloads.queue.paused";
+use crate::settings;
 
 const DOWNLOAD_STATE_QUEUED: &str = "queued";
 const DOWNLOAD_STATE_RUNNING: &str = "running";
@@ -49,12 +46,6 @@ fn hash_token(raw: &str) -> String {
     hex::encode(hasher.finalize())
 }
 
-#[derive(Debug, Clone, serde::Deserialize)]
-struct SteamMaFileRaw {
-    account_name: Option<String>,
-    shared_secret: Option<String>,
-}
-
 fn normalize_steam_guard_code(value: Option<&str>) -> Option<String> {
     let raw = value?.trim();
     if raw.is_empty() {
@@ -68,83 +59,6 @@ fn normalize_steam_guard_code(value: Option<&str>) -> Option<String> {
     }
 }
 
-fn parse_steam_mafile_or_secret(raw: &str) -> Result<(Option<String>, String), String> {
-    let trimmed = raw.trim();
-    if trimmed.is_empty() {
-        return Err("empty input".to_string());
-    }
-
-    let (account_name, shared_secret) = if trimmed.starts_with('{') {
-        let parsed = serde_json::from_str::<SteamMaFileRaw>(trimmed)
-            .map_err(|e| format!("invalid maFile json: {e}"))?;
-        let account_name = parsed
-            .account_name
-            .map(|v| v.trim().to_string())
-            .filter(|v| !v.is_empty());
-        let shared_secret = parsed
-            .shared_secret
-            .map(|v| v.trim().to_string())
-            .filter(|v| !v.is_empty())
-            .ok_or_else(|| "maFile missing shared_secret".to_string())?;
-        (account_name, shared_secret)
-    } else {
-        (None, trimmed.to_string())
-    };
-
-    use base64::Engine;
-    base64::engine::general_purpose::STANDARD
-        .decode(shared_secret.as_bytes())
-        .map_err(|_| "shared_secret is not valid base64".to_string())?;
-
-    Ok((account_name, shared_secret))
-}
-
-fn generate_steam_guard_code(shared_secret_b64: &str, unix_seconds: i64) -> Result<String, String> {
-    use base64::Engine;
-    use hmac::{Hmac, Mac};
-    use sha1::Sha1;
-
-    type HmacSha1 = Hmac<Sha1>;
-
-    let secret = base64::engine::general_purpose::STANDARD
-        .decode(shared_secret_b64.as_bytes())
-        .map_err(|_| "shared_secret is not valid base64".to_string())?;
-
-    let timestep = (unix_seconds.div_euclid(30)).max(0) as u64;
-    let msg = timestep.to_be_bytes();
-
-    let mut mac = HmacSha1::new_from_slice(&secret)
-        .map_err(|_| "failed to initialize HMAC with shared_secret".to_string())?;
-    mac.update(&msg);
-    let digest = mac.finalize().into_bytes();
-
-    let offset = (digest[19] & 0x0f) as usize;
-    let mut value = ((u32::from(digest[offset]) & 0x7f) << 24)
-        | (u32::from(digest[offset + 1]) << 16)
-        | (u32::from(digest[offset + 2]) << 8)
-        | u32::from(digest[offset + 3]);
-
-    const STEAM_GUARD_CHARS: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
-    let mut out = String::with_capacity(5);
-    for _ in 0..5 {
-        out.push(STEAM_GUARD_CHARS[(value % 26) as usize] as char);
-        value /= 26;
-    }
-    Ok(out)
-}
-
-fn generate_steam_guard_candidates(shared_secret_b64: &str) -> Result<Vec<String>, String> {
-    let now = chrono::Utc::now().timestamp();
-    let mut out = Vec::<String>::new();
-    for delta in [0_i64, -30, 30] {
-        let code = generate_steam_guard_code(shared_secret_b64, now + delta)?;
-        if !out.contains(&code) {
-            out.push(code);
-        }
-    }
-    Ok(out)
-}
-
 fn normalize_node_name(name: &str) -> Result<String, ()> {
     let n = name.trim();
     if n.is_empty() {
@@ -300,6 +214,19 @@ fn compact_allocatable_ports(ports: impl IntoIterator<Item = u16>) -> String {
     out.join(",")
 }
 
+// frp v0.52+ keeps serverAddr/serverPort at the top level; serverPort defaults to 7000.
+fn frp_v2_endpoint(addr: &str, port: Option<u64>) -> Option<(String, u16)> {
+    let addr = addr.trim();
+    if addr.is_empty() {
+        return None;
+    }
+    let port = match port {
+        Some(p) => u16::try_from(p).ok().filter(|p| *p > 0)?,
+        None => 7000,
+    };
+    Some((addr.to_string(), port))
+}
+
 fn parse_frp_endpoint_from_text(config: &str) -> Option<(String, u16)> {
     let raw = config.trim();
     if raw.is_empty() {
@@ -319,6 +246,10 @@ fn parse_frp_endpoint_from_text(config: &str) -> Option<(String, u16)> {
                 .and_then(|x| u16::try_from(x).ok())?;
             return Some((addr.to_string(), port));
         }
+        if let Some(addr) = v.get("serverAddr").and_then(|x| x.as_str()) {
+            let port = v.get("serverPort").and_then(|x| x.as_u64());
+            return frp_v2_endpoint(addr, port);
+        }
     }
 
     if let Ok(v) = raw.parse::<toml::Value>() {
@@ -334,6 +265,13 @@ fn parse_frp_endpoint_from_text(config: &str) -> Option<(String, u16)> {
                 .and_then(|x| u16::try_from(x).ok())?;
             return Some((addr.to_string(), port));
         }
+        if let Some(addr) = v.get("serverAddr").and_then(|x| x.as_str()) {
+            let port = v
+                .get("serverPort")
+                .and_then(|x| x.as_integer())
+                .and_then(|x| u64::try_from(x).ok());
+            return frp_v2_endpoint(addr, port);
+        }
     }
 
     if let Ok(v) = serde_yaml::from_str::<serde_yaml::Value>(raw) {
@@ -349,6 +287,10 @@ fn parse_frp_endpoint_from_text(config: &str) -> Option<(String, u16)> {
                 .and_then(|x| u16::try_from(x).ok())?;
             return Some((addr.to_string(), port));
         }
+        if let Some(addr) = v.get("serverAddr").and_then(|x| x.as_str()) {
+            let port = v.get("serverPort").and_then(|x| x.as_u64());
+            return frp_v2_endpoint(addr, port);
+        }
     }
 
     let mut in_common = false;
@@ -609,7 +551,9 @@ fn api_error_with_field(
 pub struct AuthUser {
     pub user_id: String,
     pub username: String,
+    /// Derived from `role`.
     pub is_admin: bool,
+    pub role: String,
 }
 
 #[derive(Clone)]
@@ -617,23 +561,29 @@ struct DownloadQueueRuntime {
     db: Arc<alloy_db::sea_orm::DatabaseConnection>,
     agent_hub: crate::agent_tunnel::AgentHub,
     notify: Arc<tokio::sync::Notify>,
+    running: crate::download_queue::RunningJobs,
 }
 
 static DOWNLOAD_QUEUE_RUNTIME: OnceLock<DownloadQueueRuntime> = OnceLock::new();
 
+/// Starts the download queue worker; it stops taking jobs once `shutdown` is
+/// cancelled. Returns `None` if the worker was already running.
 pub fn init_download_queue_runtime(
     db: Arc<alloy_db::sea_orm::DatabaseConnection>,
     agent_hub: crate::agent_tunnel::AgentHub,
-) {
+    shutdown: tokio_util::sync::CancellationToken,
+) -> Option<tokio::task::JoinHandle<()>> {
     let runtime = DownloadQueueRuntime {
         db,
         agent_hub,
         notify: Arc::new(tokio::sync::Notify::new()),
+        running: crate::download_queue::RunningJobs::default(),
     };
 
-    if DOWNLOAD_QUEUE_RUNTIME.set(runtime.clone()).is_ok() {
-        tokio::spawn(download_queue_worker_loop(runtime));
-    }
+    DOWNLOAD_QUEUE_RUNTIME
+        .set(runtime.clone())
+        .is_ok()
+        .then(|| tokio::spawn(download_queue_worker_loop(runtime, shutdown)))
 }
 
 fn download_queue_runtime() -> Option<&'static DownloadQueueRuntime> {
@@ -646,6 +596,21 @@ fn wake_download_queue_worker() {
     }
 }
 
+/// Wakes the worker once a delayed retry becomes due at `at`.
+fn wake_download_queue_worker_at(
+    runtime: &DownloadQueueRuntime,
+    at: sea_orm::prelude::DateTimeWithTimeZone,
+) {
+    let delay = (at.to_utc() - chrono::Utc::now())
+        .to_std()
+        .unwrap_or_default();
+    let notify = runtime.notify.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        notify.notify_one();
+    });
+}
+
 // Request context for rspc procedures.
 #[derive(Clone)]
 pub struct Ctx {
@@ -653,9 +618,13 @@ pub struct Ctx {
     pub agent_hub: crate::agent_tunnel::AgentHub,
     pub user: Option<AuthUser>,
     pub request_id: String,
+    pub agent_version: crate::agent_transport::AgentVersionSlot,
 }
 
-#[derive(Debug, Clone, serde::Serialize, Type)]
+/// Marks an `ApiError` serialized into a legacy rspc error message.
+pub const API_ERROR_JSON_PREFIX: &str = "ALLOY_API_ERROR_JSON:";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Type)]
 pub struct ApiError {
     pub code: String,
     pub message: String,
@@ -669,10 +638,10 @@ impl rspc::Error for ApiError {
         // Keep error payload intentionally minimal/safe for frontend.
         //
         // NOTE: rspc-axum's legacy JSON-RPC executor currently discards the resolver value and only
-        // forwards a string message. Use `LegacyErrorInterop` to preserve a structured error for the
-        // frontend while still remaining compatible with future non-legacy executors.
+        // forwards a string message. Use `LegacyErrorInterop` to carry the structured error through
+        // it; `error_response::structured_errors` unpacks it into a proper JSON body on HTTP.
         let msg = serde_json::to_string(&self)
-            .map(|json| format!("ALLOY_API_ERROR_JSON:{json}"))
+            .map(|json| format!("{API_ERROR_JSON_PREFIX}{json}"))
             .unwrap_or_else(|_| format!("ALLOY_API_ERROR:{}", self.message));
         ResolverError::new(self, Some(rspc_procedure::LegacyErrorInterop(msg))).into()
     }
@@ -734,6 +703,16 @@ impl RateLimiter {
         })
     }
 
+    /// Tighter limit for checks that call third-party APIs on the user's behalf.
+    fn external_checks() -> &'static RateLimiter {
+        static RL: OnceLock<RateLimiter> = OnceLock::new();
+        RL.get_or_init(|| RateLimiter {
+            window: Duration::from_secs(60),
+            max_hits: 5,
+            hits: std::sync::Mutex::new(HashMap::new()),
+        })
+    }
+
     fn allow(&self, key: &str) -> bool {
         let now = Instant::now();
         let mut map = self.hits.lock().unwrap_or_else(|e| e.into_inner());
@@ -809,6 +788,33 @@ fn api_error_from_agent_status(ctx: &Ctx, action: &str, status: tonic::Status) -
     api_error(ctx, code, format!("{action}: {}", status.message()))
 }
 
+fn api_error_from_migration(ctx: &Ctx, err: crate::instance_migration::MigrationError) -> ApiError {
+    use crate::instance_migration::MigrationError;
+
+    match err {
+        MigrationError::Invalid(msg) => api_error(ctx, "invalid_param", msg),
+        MigrationError::Db(e) => api_error(ctx, "db_error", format!("db error: {e}")),
+        MigrationError::Agent { phase, status } => {
+            let mut err = api_error_from_agent_status(ctx, "instance.migrate", status);
+            err.message = format!(
+                "migration failed during {}: {}",
+                phase.as_str(),
+                err.message
+            );
+            err.hint = Some(
+                "The source instance was left intact. Run the migration again to resume, or abort it."
+                    .to_string(),
+            );
+            err
+        }
+    }
+}
+
+async fn record_start_failure(ctx: &Ctx, node: &str, err: &ApiError) {
+    let reason = format!("{}: {}", err.code, err.message);
+    crate::node_stats::record_start(ctx, node, Err(&reason)).await;
+}
+
 #[derive(Debug, Clone, serde::Serialize, Type)]
 pub struct PingResponse {
     pub status: String,
@@ -873,7 +879,10 @@ pub struct ProcessTemplateDto {
 
 #[derive(Debug, Clone, serde::Serialize, Type)]
 pub struct ProcessResourcesDto {
+    // Per core (400% for four busy cores).
     pub cpu_percent_x100: u32,
+    // Share of the whole machine, 0-10000.
+    pub cpu_percent_normalized_x100: u32,
     pub rss_bytes: String,
     pub read_bytes: String,
     pub write_bytes: String,
@@ -888,6 +897,25 @@ pub struct ProcessStatusDto {
     pub exit_code: Option<i32>,
     pub message: Option<String>,
     pub resources: Option<ProcessResourcesDto>,
+    // FAIL_CATEGORY_* when a start failed (download, java, disk, port, ...).
+    pub fail_category: Option<String>,
+    // FAILURE_REASON_* when a failed server's console matched a known cause.
+    pub failure_reason: Option<String>,
+    // What to do about it, e.g. "Server ran out of memory; increase memory_mb".
+    pub failure_hint: Option<String>,
+    // Reported by the server when it supports a status ping (Minecraft).
+    pub players_online: Option<u32>,
+    pub max_players: Option<u32>,
+    // Byte progress while a start downloads or installs.
+    pub progress: Option<ProgressInfoDto>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ProgressInfoDto {
+    pub bytes_done: String,
+    // "0" when the size is unknown.
+    pub bytes_total: String,
+    pub phase: String,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, Type)]
@@ -902,6 +930,17 @@ pub struct StopProcessInput {
     pub timeout_ms: Option<u32>,
 }
 
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct SendConsoleInput {
+    pub process_id: String,
+    pub command: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct SendConsoleOutput {
+    pub ok: bool,
+}
+
 #[derive(Debug, Clone, serde::Deserialize, Type)]
 pub struct GetStatusInput {
     pub process_id: String,
@@ -912,6 +951,10 @@ pub struct TailLogsInput {
     pub process_id: String,
     pub cursor: Option<String>,
     pub limit: Option<u32>,
+    // "stdout" | "stderr" | "agent"; omit for every stream.
+    pub streams: Option<Vec<String>>,
+    // Case-insensitive substring filter.
+    pub contains: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, Type)]
@@ -920,6 +963,58 @@ pub struct TailLogsOutput {
     pub next_cursor: String,
 }
 
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct ResourceHistoryInput {
+    pub process_id: String,
+    // Newest at_unix_ms the caller already has; omit for the whole history.
+    pub since_unix_ms: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ResourceSampleDto {
+    pub at_unix_ms: String,
+    pub resources: ProcessResourcesDto,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ResourceHistoryOutput {
+    // Oldest first.
+    pub samples: Vec<ResourceSampleDto>,
+    pub sample_interval_ms: u32,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct VersionRequirementsInput {
+    pub template_id: String,
+    pub params: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct VersionRequirementsOutput {
+    pub java_required: bool,
+    pub java_major: Option<u32>,
+    pub minecraft_version: Option<String>,
+    pub loader: Option<String>,
+    // Java the node would launch with; null when none was found.
+    pub runtime_java_major: Option<u32>,
+    pub java_satisfied: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct ValidateTemplateInput {
+    pub template_id: String,
+    pub params: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ValidateTemplateOutput {
+    pub ok: bool,
+    pub field_errors: std::collections::BTreeMap<String, String>,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
 #[derive(Debug, Clone, serde::Deserialize, Type)]
 pub struct WarmTemplateCacheInput {
     pub template_id: String,
@@ -938,11 +1033,28 @@ pub struct CacheEntryDto {
     pub path: String,
     pub size_bytes: String,
     pub last_used_unix_ms: String,
+    pub in_use: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct CacheCategoryDto {
+    pub name: String,
+    pub size_bytes: String,
+    pub item_count: u32,
 }
 
 #[derive(Debug, Clone, serde::Serialize, Type)]
 pub struct CacheStatsOutput {
     pub entries: Vec<CacheEntryDto>,
+    pub categories: Vec<CacheCategoryDto>,
+    // None when the agent does not evict (ALLOY_CACHE_MAX_BYTES unset).
+    pub max_bytes: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ReloadTemplatesOutput {
+    pub loaded: Vec<String>,
+    pub errors: Vec<String>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, Type)]
@@ -955,6 +1067,33 @@ pub struct ClearCacheOutput {
     pub ok: bool,
     pub freed_bytes: String,
     pub cleared: Vec<CacheEntryDto>,
+    pub skipped: Vec<CacheEntryDto>,
+}
+
+fn map_cache_entry(e: alloy_proto::agent_v1::CacheEntry) -> CacheEntryDto {
+    CacheEntryDto {
+        key: e.key,
+        path: e.path,
+        size_bytes: e.size_bytes.to_string(),
+        last_used_unix_ms: e.last_used_unix_ms.to_string(),
+        in_use: e.in_use,
+    }
+}
+
+fn map_cache_stats(resp: alloy_proto::agent_v1::GetCacheStatsResponse) -> CacheStatsOutput {
+    CacheStatsOutput {
+        entries: resp.entries.into_iter().map(map_cache_entry).collect(),
+        categories: resp
+            .categories
+            .into_iter()
+            .map(|c| CacheCategoryDto {
+                name: c.name,
+                size_bytes: c.size_bytes.to_string(),
+                item_count: c.item_count,
+            })
+            .collect(),
+        max_bytes: (resp.max_bytes > 0).then(|| resp.max_bytes.to_string()),
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, Type)]
@@ -968,7 +1107,11 @@ pub struct DownloadQueueJobDto {
     pub message: String,
     pub request_id: Option<String>,
     pub queue_position: String,
+    pub priority: i32,
     pub attempt_count: i32,
+    pub max_attempts: i32,
+    /// Set while the job waits to be retried after a failure.
+    pub next_attempt_at_unix_ms: Option<String>,
     pub created_at_unix_ms: String,
     pub started_at_unix_ms: Option<String>,
     pub updated_at_unix_ms: String,
@@ -993,6 +1136,8 @@ pub struct DownloadQueueEnqueueInput {
     pub template_id: String,
     pub version: String,
     pub params: std::collections::BTreeMap<String, String>,
+    // Higher runs sooner; defaults to 0.
+    pub priority: Option<i32>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, Type)]
@@ -1006,6 +1151,12 @@ pub struct DownloadQueueMoveInput {
     pub direction: i32,
 }
 
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct DownloadQueueSetPriorityInput {
+    pub job_id: String,
+    pub priority: i32,
+}
+
 #[derive(Debug, Clone, serde::Deserialize, Type)]
 pub struct DownloadQueueJobActionInput {
     pub job_id: String,
@@ -1019,11 +1170,17 @@ pub struct DownloadQueueMutationOutput {
 #[derive(Debug, Clone, serde::Deserialize, Type)]
 pub struct ListDirInput {
     pub path: Option<String>,
+    // Walk subdirectories too; pages of `max_entries` continue from `cursor`.
+    pub recursive: Option<bool>,
+    pub max_entries: Option<u32>,
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, Type)]
 pub struct DirEntryDto {
     pub name: String,
+    // Relative to the listed directory.
+    pub path: String,
     pub is_dir: bool,
     pub size_bytes: u32,
     pub modified_unix_ms: String,
@@ -1032,6 +1189,7 @@ pub struct DirEntryDto {
 #[derive(Debug, Clone, serde::Serialize, Type)]
 pub struct ListDirOutput {
     pub entries: Vec<DirEntryDto>,
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, Type)]
@@ -1046,6 +1204,37 @@ pub struct DeleteInstancePreviewOutput {
     pub size_bytes: String,
 }
 
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct InstanceDiskUsageOutput {
+    pub instance_id: String,
+    pub total_bytes: String,
+    pub world_bytes: String,
+    pub logs_bytes: String,
+    pub backups_bytes: String,
+    pub cache_shared_bytes: String,
+    pub truncated: bool,
+    pub free_bytes: Option<String>,
+    pub min_free_bytes: String,
+    // Starts on this node would currently fail the free-space check.
+    pub below_min_free: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct InspectInstanceOutput {
+    pub process_id: String,
+    pub template_id: String,
+    pub started_at_unix_ms: String,
+    pub agent_version: String,
+    pub pid: Option<u32>,
+    pub container_name: Option<String>,
+    pub container_id: Option<String>,
+    pub exec: String,
+    pub args: Vec<String>,
+    pub cwd: String,
+    pub params: std::collections::BTreeMap<String, String>,
+    pub env: std::collections::BTreeMap<String, String>,
+}
+
 #[derive(Debug, Clone, serde::Serialize, Type)]
 pub struct ControlDiagnosticsOutput {
     pub fetched_at_unix_ms: String,
@@ -1097,6 +1286,13 @@ pub struct NodeDto {
     pub last_seen_at: Option<String>,
     pub agent_version: Option<String>,
     pub last_error: Option<String>,
+    pub tags: Vec<String>,
+    pub starts_attempted: u32,
+    pub starts_succeeded: u32,
+    pub starts_failed: u32,
+    pub start_failure_rate_x100: Option<u32>,
+    pub last_start_success_at: Option<String>,
+    pub last_start_failure_reason: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, Type)]
@@ -1144,6 +1340,121 @@ pub struct FrpNodeDeleteOutput {
     pub ok: bool,
 }
 
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ScheduleDto {
+    pub id: String,
+    // Node the instance was on when the schedule was created; runs follow the
+    // instance's current placement.
+    pub node_id: String,
+    pub instance_id: String,
+    pub kind: String,
+    pub cron: String,
+    pub enabled: bool,
+    pub last_run_at: Option<String>,
+    pub next_run_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct ScheduleCreateInput {
+    pub instance_id: String,
+    // restart or backup.
+    pub kind: String,
+    // Five-field crontab expression, evaluated in UTC.
+    pub cron: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct ScheduleListInput {
+    pub instance_id: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct ScheduleDeleteInput {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ScheduleDeleteOutput {
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct ScheduleToggleInput {
+    pub id: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct AuditEventDto {
+    pub id: String,
+    // Matches the `x-request-id` logged for the call.
+    pub request_id: String,
+    pub user_id: Option<String>,
+    pub actor: Option<String>,
+    pub action: String,
+    pub target: String,
+    // JSON-encoded details, when the action recorded any.
+    pub meta: Option<String>,
+    pub source: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct AuditListInput {
+    // Username; ignored for non-admins, who only see their own actions.
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub instance_id: Option<String>,
+    pub from_unix_ms: Option<String>,
+    pub to_unix_ms: Option<String>,
+    // `next_cursor` from the previous page.
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct AuditListOutput {
+    pub events: Vec<AuditEventDto>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ApiTokenDto {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct ApiTokenCreateInput {
+    pub name: String,
+    // read, write and/or admin; none may exceed the caller's role.
+    pub scopes: Vec<String>,
+    // Omit for a token that never expires.
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ApiTokenCreateOutput {
+    // Shown only in this response; only its hash is stored.
+    pub token: String,
+    pub info: ApiTokenDto,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct ApiTokenRevokeInput {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ApiTokenRevokeOutput {
+    pub ok: bool,
+}
+
 #[derive(Debug, Clone, serde::Deserialize, Type)]
 pub struct NodeCreateInput {
     pub name: String,
@@ -1160,6 +1471,8 @@ pub struct CreateInstanceInput {
     pub template_id: String,
     pub params: std::collections::BTreeMap<String, String>,
     pub display_name: Option<String>,
+    /// Node to create on; defaults to the first enabled, healthy node.
+    pub node_id: Option<String>,
 }
 
 #[derive(Debug, Clone, serde::Serialize, Type)]
@@ -1217,6 +1530,32 @@ pub struct UpdateInstanceInput {
     pub display_name: Option<String>,
 }
 
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct BulkApplyParamsInput {
+    pub instance_ids: Vec<String>,
+    // Merged over each instance's params; a blank value clears the key.
+    pub params: std::collections::BTreeMap<String, String>,
+    // Roll through running instances with graceful restarts so the params apply.
+    pub restart_running: bool,
+    pub stop_timeout_ms: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct BulkApplyParamsResultDto {
+    pub instance_id: String,
+    pub ok: bool,
+    pub error: Option<String>,
+    // Secret values are redacted.
+    pub params: std::collections::BTreeMap<String, String>,
+    pub restart_pending: bool,
+    pub restart_scheduled: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct BulkApplyParamsOutput {
+    pub results: Vec<BulkApplyParamsResultDto>,
+}
+
 #[derive(Debug, Clone, serde::Serialize, Type)]
 pub struct SettingsStatusOutput {
     pub dst_default_klei_key_set: bool,
@@ -1227,6 +1566,25 @@ pub struct SettingsStatusOutput {
     pub steamcmd_account_name: Option<String>,
 }
 
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct NodeDefaultParamsInput {
+    // Defaults to the node requests are currently routed to.
+    pub node: Option<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct SetNodeDefaultParamsInput {
+    pub node: Option<String>,
+    pub params: std::collections::BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct NodeDefaultParamsOutput {
+    pub node: String,
+    // Secret values are returned as "<redacted>"; sending them back keeps the stored value.
+    pub params: std::collections::BTreeMap<String, String>,
+}
+
 #[derive(Debug, Clone, serde::Deserialize, Type)]
 pub struct SetDstDefaultKleiKeyInput {
     pub key: String,
@@ -1237,6 +1595,18 @@ pub struct SetCurseforgeApiKeyInput {
     pub key: String,
 }
 
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct PreviewSteamGuardInput {
+    /// maFile JSON or a bare base64 shared_secret.
+    pub mafile_or_secret: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct TestCurseforgeKeyInput {
+    /// Blank tests the saved key.
+    pub key: String,
+}
+
 #[derive(Debug, Clone, serde::Deserialize, Type)]
 pub struct SetSteamcmdCredentialsInput {
     pub username: String,
@@ -1284,36 +1654,239 @@ pub struct ImportSaveFromUrlOutput {
 }
 
 #[derive(Debug, Clone, serde::Serialize, Type)]
-pub struct DeleteInstanceOutput {
-    pub ok: bool,
+pub struct BackupRecordDto {
+    pub backup_id: String,
+    pub format: String,
+    pub size_bytes: String,
+    pub sha256: String,
+    pub created_at_unix_ms: String,
 }
 
 #[derive(Debug, Clone, serde::Deserialize, Type)]
-pub struct NodeSetEnabledInput {
-    pub node_id: String,
-    pub enabled: bool,
+pub struct CreateBackupInput {
+    pub instance_id: String,
+    // tar.zst (default), tar.gz or zip.
+    pub format: Option<String>,
 }
 
-fn map_instance_config(cfg: alloy_proto::agent_v1::InstanceConfig) -> InstanceConfigDto {
-    InstanceConfigDto {
-        instance_id: cfg.instance_id,
-        template_id: cfg.template_id,
-        params: cfg.params.into_iter().collect(),
-        display_name: if cfg.display_name.trim().is_empty() {
-            None
-        } else {
-            Some(cfg.display_name)
-        },
-    }
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct ListBackupsInput {
+    pub instance_id: String,
 }
 
-fn map_param_type(t: i32) -> ParamTypeDto {
-    match t {
-        x if x == alloy_proto::agent_v1::ParamType::String as i32 => ParamTypeDto::String,
-        x if x == alloy_proto::agent_v1::ParamType::Int as i32 => ParamTypeDto::Int,
-        x if x == alloy_proto::agent_v1::ParamType::Bool as i32 => ParamTypeDto::Bool,
-        _ => ParamTypeDto::String,
-    }
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct ListBackupsOutput {
+    pub backups: Vec<BackupRecordDto>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct RestoreBackupInput {
+    pub instance_id: String,
+    pub backup_id: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct RestoreBackupOutput {
+    pub ok: bool,
+    pub pre_restore_backup_id: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct StageUpgradeInput {
+    pub instance_id: String,
+    pub target_version: String,
+    // Unset or empty runs the upgrade as soon as the jar is staged.
+    pub run_at_unix_ms: Option<String>,
+    pub warning_seconds: Option<u32>,
+    pub backup: bool,
+    pub stop_timeout_ms: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct StagedUpgradeDto {
+    pub instance_id: String,
+    pub upgrade_id: String,
+    pub from_version: String,
+    pub target_version: String,
+    pub run_at_unix_ms: String,
+    pub warning_seconds: u32,
+    pub backup: bool,
+    // staged | warning | stopping | backup | swapping | starting | done | failed | cancelled
+    pub phase: String,
+    pub message: String,
+    pub backup_id: Option<String>,
+    pub updated_at_unix_ms: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct StagedUpgradeInput {
+    pub instance_id: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct StagedUpgradeOutput {
+    pub upgrade: Option<StagedUpgradeDto>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct AssignNodeInput {
+    pub instance_id: String,
+    pub node_id: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct AssignNodeOutput {
+    pub instance_id: String,
+    pub node: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct MigrateInstanceInput {
+    pub instance_id: String,
+    pub target_node: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct MigrateInstanceOutput {
+    pub source_node: String,
+    pub target_node: String,
+    pub status: Option<ProcessStatusDto>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct AbortMigrationOutput {
+    pub aborted: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct DeleteInstanceOutput {
+    pub ok: bool,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct NodeSetEnabledInput {
+    pub node_id: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct NodeSetTagsInput {
+    pub node_id: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct NodeRotateTokenInput {
+    pub node_id: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct NodePrepareUpdateInput {
+    pub node_id: String,
+    // Gracefully stop running instances as part of the drain.
+    pub stop_instances: bool,
+    pub timeout_ms: Option<u32>,
+    // Leave drain mode instead (refused once the update was applied).
+    pub cancel: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct NodePrepareUpdateOutput {
+    pub draining: bool,
+    pub ready: bool,
+    pub running_process_ids: Vec<String>,
+    pub busy_process_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, Type)]
+pub struct NodeApplyUpdateInput {
+    pub node_id: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct NodeApplyUpdateOutput {
+    pub adoptable_process_ids: Vec<String>,
+    pub native_process_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct NodeResourcesDto {
+    pub node: String,
+    pub cpu_count: u32,
+    // Null where the agent host does not report them (non-Linux).
+    pub total_mem_bytes: Option<String>,
+    pub available_mem_bytes: Option<String>,
+    pub load_avg_1m: Option<f64>,
+    pub load_avg_5m: Option<f64>,
+    pub load_avg_15m: Option<f64>,
+    pub data_root_free_bytes: Option<String>,
+    pub running_process_count: u32,
+}
+
+#[derive(Debug, Clone, serde::Serialize, Type)]
+pub struct NodeResourcesOutput {
+    // Connected nodes, least loaded first.
+    pub nodes: Vec<NodeResourcesDto>,
+    // Connected nodes that did not answer.
+    pub unavailable: Vec<String>,
+}
+
+/// Lower is less loaded: load average (or, without one, process count) per
+/// CPU plus the fraction of memory in use.
+fn node_load_score(r: &alloy_proto::agent_v1::GetNodeResourcesResponse) -> f64 {
+    let cpus = f64::from(r.cpu_count.max(1));
+    let load = if r.has_load_avg {
+        r.load_avg_1m
+    } else {
+        f64::from(r.running_process_count)
+    };
+    let mem_used = if r.has_memory && r.total_mem_bytes > 0 {
+        1.0 - r.available_mem_bytes as f64 / r.total_mem_bytes as f64
+    } else {
+        0.0
+    };
+    load / cpus + mem_used
+}
+
+fn map_node_resources(
+    node: String,
+    r: alloy_proto::agent_v1::GetNodeResourcesResponse,
+) -> NodeResourcesDto {
+    NodeResourcesDto {
+        node,
+        cpu_count: r.cpu_count,
+        total_mem_bytes: r.has_memory.then(|| r.total_mem_bytes.to_string()),
+        available_mem_bytes: r.has_memory.then(|| r.available_mem_bytes.to_string()),
+        load_avg_1m: r.has_load_avg.then_some(r.load_avg_1m),
+        load_avg_5m: r.has_load_avg.then_some(r.load_avg_5m),
+        load_avg_15m: r.has_load_avg.then_some(r.load_avg_15m),
+        data_root_free_bytes: r
+            .has_data_root_free_bytes
+            .then(|| r.data_root_free_bytes.to_string()),
+        running_process_count: r.running_process_count,
+    }
+}
+
+fn map_instance_config(cfg: alloy_proto::agent_v1::InstanceConfig) -> InstanceConfigDto {
+    InstanceConfigDto {
+        instance_id: cfg.instance_id,
+        template_id: cfg.template_id,
+        params: cfg.params.into_iter().collect(),
+        display_name: if cfg.display_name.trim().is_empty() {
+            None
+        } else {
+            Some(cfg.display_name)
+        },
+    }
+}
+
+fn map_param_type(t: i32) -> ParamTypeDto {
+    match t {
+        x if x == alloy_proto::agent_v1::ParamType::String as i32 => ParamTypeDto::String,
+        x if x == alloy_proto::agent_v1::ParamType::Int as i32 => ParamTypeDto::Int,
+        x if x == alloy_proto::agent_v1::ParamType::Bool as i32 => ParamTypeDto::Bool,
+        _ => ParamTypeDto::String,
+    }
 }
 
 fn map_template_param(p: alloy_proto::agent_v1::TemplateParam) -> TemplateParamDto {
@@ -1350,7 +1923,25 @@ fn map_template_param(p: alloy_proto::agent_v1::TemplateParam) -> TemplateParamD
     }
 }
 
+fn map_process_resources(r: alloy_proto::agent_v1::ProcessResources) -> ProcessResourcesDto {
+    ProcessResourcesDto {
+        cpu_percent_x100: r.cpu_percent_x100,
+        cpu_percent_normalized_x100: r.cpu_percent_normalized_x100,
+        rss_bytes: r.rss_bytes.to_string(),
+        read_bytes: r.read_bytes.to_string(),
+        write_bytes: r.write_bytes.to_string(),
+    }
+}
+
 fn map_process_status(p: alloy_proto::agent_v1::ProcessStatus) -> ProcessStatusDto {
+    let fail_category = match p.fail_category() {
+        alloy_proto::agent_v1::FailCategory::Unspecified => None,
+        c => Some(c.as_str_name().to_string()),
+    };
+    let failure_reason = match p.failure_reason() {
+        alloy_proto::agent_v1::FailureReason::Unspecified => None,
+        r => Some(r.as_str_name().to_string()),
+    };
     ProcessStatusDto {
         process_id: p.process_id.clone(),
         template_id: p.template_id.clone(),
@@ -1366,11 +1957,20 @@ fn map_process_status(p: alloy_proto::agent_v1::ProcessStatus) -> ProcessStatusD
         } else {
             Some(p.message)
         },
-        resources: p.resources.map(|r| ProcessResourcesDto {
-            cpu_percent_x100: r.cpu_percent_x100,
-            rss_bytes: r.rss_bytes.to_string(),
-            read_bytes: r.read_bytes.to_string(),
-            write_bytes: r.write_bytes.to_string(),
+        resources: p.resources.map(map_process_resources),
+        fail_category,
+        failure_reason,
+        failure_hint: if p.failure_hint.is_empty() {
+            None
+        } else {
+            Some(p.failure_hint)
+        },
+        players_online: p.has_players.then_some(p.players_online),
+        max_players: p.has_players.then_some(p.max_players),
+        progress: p.progress.map(|p| ProgressInfoDto {
+            bytes_done: p.bytes_done.to_string(),
+            bytes_total: p.bytes_total.to_string(),
+            phase: p.phase,
         }),
     }
 }
@@ -1389,6 +1989,29 @@ fn map_instance_info(
     })
 }
 
+fn map_node(
+    n: alloy_db::entities::nodes::Model,
+    stats: Option<&alloy_db::entities::node_stats::Model>,
+) -> NodeDto {
+    NodeDto {
+        id: n.id.to_string(),
+        name: n.name,
+        endpoint: n.endpoint,
+        has_connect_token: n.connect_token_hash.is_some(),
+        enabled: n.enabled,
+        last_seen_at: n.last_seen_at.map(|t| t.to_rfc3339()),
+        agent_version: n.agent_version,
+        last_error: n.last_error,
+        tags: crate::node_placement::parse_tags(&n.tags),
+        starts_attempted: stats.map(|s| s.starts_attempted.max(0) as u32).unwrap_or(0),
+        starts_succeeded: stats.map(|s| s.starts_succeeded.max(0) as u32).unwrap_or(0),
+        starts_failed: stats.map(|s| s.starts_failed.max(0) as u32).unwrap_or(0),
+        start_failure_rate_x100: stats.and_then(crate::node_stats::failure_rate_x100),
+        last_start_success_at: stats.and_then(|s| s.last_success_at.map(|t| t.to_rfc3339())),
+        last_start_failure_reason: stats.and_then(|s| s.last_failure_reason.clone()),
+    }
+}
+
 fn clamp_u64_to_u32(v: u64) -> u32 {
     if v > u32::MAX as u64 {
         u32::MAX
@@ -1397,8 +2020,338 @@ fn clamp_u64_to_u32(v: u64) -> u32 {
     }
 }
 
+fn map_staged_upgrade(up: alloy_proto::agent_v1::StagedUpgrade) -> StagedUpgradeDto {
+    StagedUpgradeDto {
+        instance_id: up.instance_id,
+        upgrade_id: up.upgrade_id,
+        from_version: up.from_version,
+        target_version: up.target_version,
+        run_at_unix_ms: up.run_at_unix_ms.to_string(),
+        warning_seconds: up.warning_seconds,
+        backup: up.backup,
+        phase: up.phase,
+        message: up.message,
+        backup_id: (!up.backup_id.is_empty()).then_some(up.backup_id),
+        updated_at_unix_ms: up.updated_at_unix_ms.to_string(),
+    }
+}
+
+fn backup_record_dto(b: alloy_proto::agent_v1::BackupRecord) -> BackupRecordDto {
+    BackupRecordDto {
+        backup_id: b.backup_id,
+        format: b.format,
+        size_bytes: b.size_bytes.to_string(),
+        sha256: b.sha256,
+        created_at_unix_ms: b.created_at_unix_ms.to_string(),
+    }
+}
+
+/// Keeps a control-side record of a backup the agent just wrote.
+pub(crate) async fn record_backup(
+    db: &sea_orm::DatabaseConnection,
+    instance_id: &str,
+    b: &alloy_proto::agent_v1::BackupRecord,
+) {
+    use alloy_db::entities::backups;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    let created_at = chrono::DateTime::<chrono::Utc>::from_timestamp_millis(
+        i64::try_from(b.created_at_unix_ms).unwrap_or(i64::MAX),
+    )
+    .unwrap_or_else(chrono::Utc::now);
+    let model = backups::ActiveModel {
+        id: Set(sea_orm::prelude::Uuid::new_v4()),
+        instance_id: Set(instance_id.to_string()),
+        backup_id: Set(b.backup_id.clone()),
+        format: Set(b.format.clone()),
+        size_bytes: Set(i64::try_from(b.size_bytes).unwrap_or(i64::MAX)),
+        sha256: Set(b.sha256.clone()),
+        created_at: Set(created_at.into()),
+    };
+    if let Err(err) = model.insert(db).await {
+        tracing::warn!(%err, instance_id, backup_id = %b.backup_id, "failed to record backup");
+    }
+}
+
+fn parse_unix_ms_param(
+    ctx: &Ctx,
+    name: &str,
+    raw: Option<&str>,
+) -> Result<Option<sea_orm::prelude::DateTimeWithTimeZone>, ApiError> {
+    match raw.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(v) => v
+            .parse::<i64>()
+            .ok()
+            .and_then(chrono::DateTime::from_timestamp_millis)
+            .map(|t| Some(t.into()))
+            .ok_or_else(|| api_error(ctx, "invalid_param", format!("invalid {name}"))),
+    }
+}
+
+fn api_token_dto(t: alloy_db::entities::api_tokens::Model) -> ApiTokenDto {
+    ApiTokenDto {
+        id: t.id.to_string(),
+        name: t.name,
+        scopes: crate::api_tokens::parse_scopes(&t.scopes)
+            .into_iter()
+            .map(|s| s.as_str().to_string())
+            .collect(),
+        expires_at: t.expires_at.map(|t| t.to_rfc3339()),
+        last_used_at: t.last_used_at.map(|t| t.to_rfc3339()),
+        created_at: t.created_at.to_rfc3339(),
+    }
+}
+
+fn ctx_user_id(ctx: &Ctx) -> Result<sea_orm::prelude::Uuid, ApiError> {
+    ctx.user
+        .as_ref()
+        .and_then(|u| sea_orm::prelude::Uuid::parse_str(&u.user_id).ok())
+        .ok_or_else(|| api_error(ctx, "unauthorized", "unauthorized"))
+}
+
+fn schedule_dto(s: alloy_db::entities::schedules::Model) -> ScheduleDto {
+    ScheduleDto {
+        id: s.id.to_string(),
+        node_id: s.node_id,
+        instance_id: s.instance_id,
+        kind: s.kind,
+        cron: s.cron,
+        enabled: s.enabled,
+        last_run_at: s.last_run_at.map(|t| t.to_rfc3339()),
+        next_run_at: s.next_run_at.map(|t| t.to_rfc3339()),
+        created_at: s.created_at.to_rfc3339(),
+    }
+}
+
+fn schedule_next_run(
+    ctx: &Ctx,
+    cron: &str,
+) -> Result<Option<chrono::DateTime<chrono::FixedOffset>>, ApiError> {
+    crate::scheduler::next_fire_after(cron, chrono::Utc::now())
+        .map(|next| next.map(Into::into))
+        .map_err(|msg| api_error_with_field(ctx, "invalid_param", msg.clone(), "cron", msg))
+}
+
 fn agent_transport(ctx: &Ctx) -> AgentTransport {
-    AgentTransport::new(ctx.agent_hub.clone())
+    AgentTransport::new(ctx.agent_hub.clone()).with_version_slot(ctx.agent_version.clone())
+}
+
+/// Transport for calls about `instance_id`, routed to the node it was placed on.
+async fn instance_transport(ctx: &Ctx, instance_id: &str) -> Result<AgentTransport, ApiError> {
+    let placed = crate::node_placement::placed_node(&ctx.db, instance_id)
+        .await
+        .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
+    Ok(pin_transport(agent_transport(ctx), placed.as_deref()).await)
+}
+
+async fn pin_transport(transport: AgentTransport, node: Option<&str>) -> AgentTransport {
+    let default_node = transport.target_node().await;
+    match crate::node_placement::pinned_node(node, &default_node) {
+        Some(node) => transport.for_node(node),
+        None => transport,
+    }
+}
+
+/// One transport per node instances may live on: the default node first, then
+/// every other node with placed instances.
+async fn instance_transports(ctx: &Ctx) -> Result<Vec<AgentTransport>, ApiError> {
+    let transport = agent_transport(ctx);
+    let default_node = transport.target_node().await;
+    let placed = crate::node_placement::placed_nodes(&ctx.db)
+        .await
+        .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
+    let mut out = vec![transport.clone()];
+    out.extend(
+        placed
+            .iter()
+            .filter(|node| **node != default_node)
+            .map(|node| transport.for_node(node)),
+    );
+    Ok(out)
+}
+
+/// Calls `method` on every node from `instance_transports`. Only a failure on
+/// the default node is an error; other nodes being down just drops their part.
+async fn call_instance_nodes<Req, Res>(
+    ctx: &Ctx,
+    method: &'static str,
+    op: &str,
+    req: Req,
+) -> Result<Vec<Res>, ApiError>
+where
+    Req: prost::Message + Default + Clone + 'static,
+    Res: prost::Message + Default + 'static,
+{
+    let mut out = Vec::new();
+    for (i, transport) in instance_transports(ctx).await?.into_iter().enumerate() {
+        match transport.call(method, req.clone()).await {
+            Ok(resp) => out.push(resp),
+            Err(status) if i > 0 => {
+                let node = transport.target_node().await;
+                tracing::warn!(%node, error = %status.message(), "{op} failed");
+            }
+            Err(status) => return Err(api_error_from_agent_status(ctx, op, status)),
+        }
+    }
+    Ok(out)
+}
+
+/// Node a new instance goes to: the requested one, else the first enabled,
+/// healthy node. `None` (no usable node registered) keeps the default transport.
+async fn placement_node(ctx: &Ctx, node_id: Option<&str>) -> Result<Option<String>, ApiError> {
+    let rows = crate::node_placement::all_nodes(&ctx.db)
+        .await
+        .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
+    let Some(node_id) = node_id.map(str::trim).filter(|id| !id.is_empty()) else {
+        let connected = agent_transport(ctx).connected_nodes().await;
+        return Ok(crate::node_placement::pick_default(&rows, &connected).map(|n| n.name.clone()));
+    };
+
+    let id = sea_orm::prelude::Uuid::parse_str(node_id).map_err(|_| {
+        api_error_with_field(
+            ctx,
+            "invalid_param",
+            "invalid node_id",
+            "node_id",
+            "invalid node",
+        )
+    })?;
+    let node = rows
+        .into_iter()
+        .find(|n| n.id == id)
+        .ok_or_else(|| api_error(ctx, "not_found", "node not found"))?;
+    if !node.enabled {
+        return Err(api_error_with_field(
+            ctx,
+            "failed_precondition",
+            "node is disabled",
+            "node_id",
+            "node is disabled",
+        ));
+    }
+    Ok(Some(node.name))
+}
+
+/// Node-wide default params forwarded with start requests to `node`.
+async fn node_default_params(
+    ctx: &Ctx,
+    node: &str,
+) -> Result<std::collections::HashMap<String, String>, ApiError> {
+    let params = crate::node_defaults::load(&ctx.db, node)
+        .await
+        .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
+    Ok(params.into_iter().collect())
+}
+
+async fn settings_node_name(ctx: &Ctx, node: Option<String>) -> String {
+    match node.as_deref().map(str::trim) {
+        Some(n) if !n.is_empty() => n.to_string(),
+        _ => agent_transport(ctx).target_node().await,
+    }
+}
+
+/// Fails with `not_found` unless the caller owns the instance (admins own
+/// everything), so other users' instance ids can't be probed.
+async fn ensure_instance_access(ctx: &Ctx, instance_id: &str) -> Result<(), ApiError> {
+    let user = ctx
+        .user
+        .as_ref()
+        .ok_or_else(|| api_error(ctx, "unauthorized", "unauthorized"))?;
+    if user.is_admin {
+        return Ok(());
+    }
+    let owner = crate::instance_owners::owner_of(&ctx.db, instance_id)
+        .await
+        .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
+    if crate::instance_owners::can_access(user, owner) {
+        Ok(())
+    } else {
+        Err(api_error(ctx, "not_found", "instance not found"))
+    }
+}
+
+/// Access check for a data-root path. Instance paths need access to that
+/// instance; outside them non-admins may only list `instances/` itself, which
+/// callers filter with [`owned_instances`]. Returns the path's instance.
+async fn ensure_path_access(
+    ctx: &Ctx,
+    path: &str,
+    allow_instances_dir: bool,
+) -> Result<Option<String>, ApiError> {
+    if let Some(id) = crate::instance_owners::instance_of_path(path) {
+        ensure_instance_access(ctx, &id).await?;
+        return Ok(Some(id));
+    }
+    let user = ctx
+        .user
+        .as_ref()
+        .ok_or_else(|| api_error(ctx, "unauthorized", "unauthorized"))?;
+    if user.is_admin || (allow_instances_dir && crate::instance_owners::is_instances_dir(path)) {
+        Ok(None)
+    } else {
+        Err(api_error(ctx, "not_found", "path not found"))
+    }
+}
+
+/// Instance ids the caller owns; `None` for admins, who see everything.
+async fn owned_instances(ctx: &Ctx) -> Result<Option<HashSet<String>>, ApiError> {
+    let user = ctx
+        .user
+        .as_ref()
+        .ok_or_else(|| api_error(ctx, "unauthorized", "unauthorized"))?;
+    if user.is_admin {
+        return Ok(None);
+    }
+    let user_id = sea_orm::prelude::Uuid::parse_str(&user.user_id)
+        .map_err(|_| api_error(ctx, "unauthorized", "unauthorized"))?;
+    crate::instance_owners::owned_by(&ctx.db, user_id)
+        .await
+        .map(Some)
+        .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))
+}
+
+/// Records the caller as owner of something they just created.
+async fn record_instance_owner(ctx: &Ctx, instance_id: &str) {
+    let Some(user_id) = ctx
+        .user
+        .as_ref()
+        .and_then(|u| sea_orm::prelude::Uuid::parse_str(&u.user_id).ok())
+    else {
+        return;
+    };
+    if let Err(err) = crate::instance_owners::set_owner(&ctx.db, instance_id, user_id).await {
+        tracing::warn!(%err, instance_id, "failed to record instance owner");
+    }
+}
+
+async fn record_instance_placement(ctx: &Ctx, instance_id: &str, node: &str) {
+    if let Err(err) = crate::instance_migration::set_placement(&ctx.db, instance_id, node).await {
+        tracing::warn!(%err, instance_id, node, "failed to record instance placement");
+    }
+}
+
+/// Admin-only lookup of a node name by id, for node-targeted agent calls.
+async fn admin_node_name(ctx: &Ctx, node_id: &str) -> Result<String, ApiError> {
+    use alloy_db::entities::nodes;
+    use sea_orm::EntityTrait;
+
+    let user = ctx
+        .user
+        .clone()
+        .ok_or_else(|| api_error(ctx, "unauthorized", "unauthorized"))?;
+    if !user.is_admin {
+        return Err(api_error(ctx, "forbidden", "forbidden"));
+    }
+
+    let id = sea_orm::prelude::Uuid::parse_str(node_id)
+        .map_err(|_| api_error(ctx, "invalid_param", "invalid node_id"))?;
+    let model = nodes::Entity::find_by_id(id)
+        .one(&*ctx.db)
+        .await
+        .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?
+        .ok_or_else(|| api_error(ctx, "not_found", "node not found"))?;
+    Ok(model.name)
 }
 
 async fn verify_steamcmd_login_via_agent(
@@ -1489,12 +2442,17 @@ async fn setting_set(
     Ok(())
 }
 
-async fn setting_set_secret(
-    db: &alloy_db::sea_orm::DatabaseConnection,
-    key: &str,
-    value: &str,
-) -> Result<(), sea_orm::DbErr> {
-    setting_set(db, key, value, true).await
+/// Validates `value` against the [`settings`] schema and stores it.
+async fn setting_write(ctx: &Ctx, key: &str, value: &str) -> Result<(), ApiError> {
+    let value = settings::validate(key, value).map_err(|e| invalid_setting(ctx, e))?;
+    let secret = settings::lookup(key).is_some_and(|s| s.secret);
+    setting_set(&ctx.db, key, &value, secret)
+        .await
+        .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))
+}
+
+fn invalid_setting(ctx: &Ctx, err: settings::InvalidSetting) -> ApiError {
+    api_error_with_field(ctx, "invalid_param", err.message, err.field, err.hint)
 }
 
 async fn setting_clear(
@@ -1667,7 +2625,10 @@ fn map_download_job_model(
         message: model.message,
         request_id: model.request_id,
         queue_position: model.queue_position.to_string(),
+        priority: model.priority,
         attempt_count: model.attempt_count,
+        max_attempts: model.max_attempts,
+        next_attempt_at_unix_ms: model.next_attempt_at.map(dt_to_unix_ms),
         created_at_unix_ms: dt_to_unix_ms(model.created_at),
         started_at_unix_ms: model.started_at.map(dt_to_unix_ms),
         updated_at_unix_ms: dt_to_unix_ms(model.updated_at),
@@ -1684,7 +2645,7 @@ fn map_download_job_model(
 async fn download_queue_is_paused(
     db: &alloy_db::sea_orm::DatabaseConnection,
 ) -> Result<bool, sea_orm::DbErr> {
-    Ok(setting_get(db, SETTING_DOWNLOAD_QUEUE_PAUSED)
+    Ok(setting_get(db, settings::DOWNLOAD_QUEUE_PAUSED)
         .await?
         .is_some_and(|v| matches!(v.trim(), "1" | "true" | "yes" | "on")))
 }
@@ -1694,9 +2655,9 @@ async fn download_queue_set_paused(
     paused: bool,
 ) -> Result<(), sea_orm::DbErr> {
     if paused {
-        setting_set(db, SETTING_DOWNLOAD_QUEUE_PAUSED, "1", false).await
+        setting_set(db, settings::DOWNLOAD_QUEUE_PAUSED, "1", false).await
     } else {
-        setting_clear(db, SETTING_DOWNLOAD_QUEUE_PAUSED).await
+        setting_clear(db, settings::DOWNLOAD_QUEUE_PAUSED).await
     }
 }
 
@@ -1720,10 +2681,7 @@ async fn download_queue_snapshot(
             return ar.cmp(&br);
         }
         if ar <= 2 {
-            if a.queue_position != b.queue_position {
-                return a.queue_position.cmp(&b.queue_position);
-            }
-            return a.created_at.cmp(&b.created_at);
+            return crate::download_queue::queue_order(a, b);
         }
         b.updated_at.cmp(&a.updated_at)
     });
@@ -1794,6 +2752,24 @@ async fn download_queue_next_position(
     Ok(top.map(|m| m.queue_position.saturating_add(1)).unwrap_or(1))
 }
 
+/// Queued and paused jobs, in the order they will run.
+async fn download_queue_waiting(
+    db: &alloy_db::sea_orm::DatabaseConnection,
+) -> Result<Vec<alloy_db::entities::download_jobs::Model>, sea_orm::DbErr> {
+    use alloy_db::entities::download_jobs;
+    use sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter};
+
+    let waiting = Condition::any()
+        .add(download_jobs::Column::State.eq(DOWNLOAD_STATE_QUEUED))
+        .add(download_jobs::Column::State.eq(DOWNLOAD_STATE_PAUSED));
+    let mut rows = download_jobs::Entity::find()
+        .filter(waiting)
+        .all(db)
+        .await?;
+    rows.sort_by(crate::download_queue::queue_order);
+    Ok(rows)
+}
+
 async fn trim_download_history(
     db: &alloy_db::sea_orm::DatabaseConnection,
     keep_terminal: usize,
@@ -1852,6 +2828,7 @@ async fn recover_download_queue_after_restart(
     let next_base = download_queue_next_position(db).await?;
 
     for (idx, row) in rows.into_iter().enumerate() {
+        let job_id = row.id;
         let mut active: download_jobs::ActiveModel = row.into();
         active.state = Set(DOWNLOAD_STATE_QUEUED.to_string());
         active.message = Set("queued after control restart".to_string());
@@ -1860,6 +2837,7 @@ async fn recover_download_queue_after_restart(
         active.updated_at = Set(now);
         active.queue_position = Set(next_base.saturating_add(idx as i64));
         let _ = active.update(db).await?;
+        crate::download_queue::transition(job_id, DOWNLOAD_STATE_RUNNING, DOWNLOAD_STATE_QUEUED);
     }
 
     Ok(())
@@ -1886,39 +2864,79 @@ fn compact_download_error_message(raw: &str) -> String {
     text
 }
 
+/// For `dst:vanilla`, passes the saved SteamCMD account (and Steam Guard
+/// candidates when a shared_secret is saved) so the agent can log in with it
+/// as `steam_login_mode` selects. Callers must not persist the result: it
+/// carries the password.
 async fn prepare_warm_params(
-    _db: &alloy_db::sea_orm::DatabaseConnection,
-    _template_id: &str,
-    params: std::collections::BTreeMap<String, String>,
+    db: &alloy_db::sea_orm::DatabaseConnection,
+    template_id: &str,
+    mut params: std::collections::BTreeMap<String, String>,
 ) -> Result<std::collections::BTreeMap<String, String>, String> {
-    Ok(params)
+    let anonymous = params
+        .get("steam_login_mode")
+        .is_some_and(|m| m.trim() == "anonymous");
+    if template_id != "dst:vanilla" || anonymous || params.contains_key("steam_username") {
+        return Ok(params);
+    }
+    let db_err = |e: sea_orm::DbErr| format!("db error: {e}");
+    let saved = |v: Option<String>| v.filter(|v| !v.trim().is_empty());
+    let username = setting_get(db, settings::STEAMCMD_USERNAME)
+        .await
+        .map_err(db_err)?;
+    let password = setting_get(db, settings::STEAMCMD_PASSWORD)
+        .await
+        .map_err(db_err)?;
+    let (Some(username), Some(password)) = (saved(username), saved(password)) else {
+        return Ok(params);
+    };
+    params.insert("steam_username".to_string(), username.trim().to_string());
+    params.insert("steam_password".to_string(), password);
+
+    let secret = setting_get(db, settings::STEAMCMD_SHARED_SECRET)
+        .await
+        .map_err(db_err)?;
+    if let Some(secret) = saved(secret) {
+        let codes = crate::steam_guard::candidates(secret.trim())
+            .map_err(|e| format!("invalid steamcmd shared_secret: {e}"))?;
+        params.insert("steam_guard_codes".to_string(), codes.join(","));
+    }
+    Ok(params)
 }
 
-async fn download_queue_worker_loop(runtime: DownloadQueueRuntime) {
+// Runs up to `ALLOY_DOWNLOAD_CONCURRENCY` jobs at once. On shutdown no new job
+// starts and running ones get to finish; a job interrupted anyway is left
+// `running` and requeued by `recover_download_queue_after_restart` on the next boot.
+async fn download_queue_worker_loop(
+    runtime: DownloadQueueRuntime,
+    shutdown: tokio_util::sync::CancellationToken,
+) {
     if let Err(e) = recover_download_queue_after_restart(&runtime.db).await {
         tracing::error!(error = %e, "download queue recovery failed");
     }
 
-    loop {
-        let did_work = match run_next_download_queue_job(&runtime).await {
-            Ok(v) => v,
+    let slots = crate::download_queue::JobSlots::new(crate::download_queue::concurrency());
+    crate::shutdown::run_worker(&shutdown, &runtime.notify, || async {
+        match start_next_download_queue_job(&runtime, &slots).await {
+            Ok(did_work) => did_work,
             Err(e) => {
                 tracing::error!(error = %e, "download queue worker tick failed");
                 false
             }
-        };
-
-        if did_work {
-            continue;
         }
-
-        runtime.notify.notified().await;
-    }
+    })
+    .await;
+    slots.idle().await;
 }
 
-async fn run_next_download_queue_job(runtime: &DownloadQueueRuntime) -> Result<bool, String> {
+/// Claims the next runnable job and runs it in the background. Returns `false`
+/// when paused, when every slot is busy, or when nothing queued can start.
+async fn start_next_download_queue_job(
+    runtime: &DownloadQueueRuntime,
+    slots: &crate::download_queue::JobSlots,
+) -> Result<bool, String> {
     use alloy_db::entities::download_jobs;
-    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+    use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
 
     if download_queue_is_paused(&runtime.db)
         .await
@@ -1927,31 +2945,62 @@ async fn run_next_download_queue_job(runtime: &DownloadQueueRuntime) -> Result<b
         return Ok(false);
     }
 
-    let Some(row) = download_jobs::Entity::find()
+    let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
+    let mut queued = download_jobs::Entity::find()
         .filter(download_jobs::Column::State.eq(DOWNLOAD_STATE_QUEUED))
-        .order_by_asc(download_jobs::Column::QueuePosition)
-        .order_by_asc(download_jobs::Column::CreatedAt)
-        .one(&*runtime.db)
+        .all(&*runtime.db)
         .await
-        .map_err(|e| format!("db error: {e}"))?
-    else {
+        .map_err(|e| format!("db error: {e}"))?;
+    let next_retry_at = crate::download_queue::next_retry_at(&queued, now);
+    queued.retain(|j| crate::download_queue::is_due(j, now));
+    let Some((row, slot)) = slots.claim(&queued) else {
+        if let Some(at) = next_retry_at {
+            wake_download_queue_worker_at(runtime, at);
+        }
         return Ok(false);
     };
-
-    let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
-                let mut running: download_jobs::ActiveModel = row.clone().into();
-                running.state = Set(DOWNLOAD_STATE_RUNNING.to_string());
-                running.message = Set("resolving download target…".to_string());
-                running.request_id = Set(None);
-                running.started_at = Set(Some(now));
-                running.finished_at = Set(None);
-                running.updated_at = Set(now);
-                running.attempt_count = Set(row.attempt_count.saturating_add(1));
+    let row = row.clone();
+
+    let mut running: download_jobs::ActiveModel = row.clone().into();
+    running.state = Set(DOWNLOAD_STATE_RUNNING.to_string());
+    running.message = Set("resolving download target…".to_string());
+    running.request_id = Set(None);
+    running.started_at = Set(Some(now));
+    running.finished_at = Set(None);
+    running.next_attempt_at = Set(None);
+    running.updated_at = Set(now);
+    running.attempt_count = Set(row.attempt_count.saturating_add(1));
     let running = running
         .update(&*runtime.db)
         .await
         .map_err(|e| format!("db error: {e}"))?;
+    crate::download_queue::transition(running.id, DOWNLOAD_STATE_QUEUED, DOWNLOAD_STATE_RUNNING);
+
+    let runtime = runtime.clone();
+    let job = runtime.running.start(running.id);
+    tokio::spawn(async move {
+        let result = tokio::select! {
+            res = run_download_queue_job(&runtime, running.clone()) => res,
+            _ = job.cancelled() => finish_canceled_download_queue_job(&runtime, running).await,
+        };
+        if let Err(e) = result {
+            tracing::error!(error = %e, "download queue job failed");
+        }
+        drop(job);
+        drop(slot);
+        runtime.notify.notify_one();
+    });
+    Ok(true)
+}
 
+async fn run_download_queue_job(
+    runtime: &DownloadQueueRuntime,
+    running: alloy_db::entities::download_jobs::Model,
+) -> Result<(), String> {
+    use alloy_db::entities::download_jobs;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    let job_id = running.id;
     let mut params = parse_download_job_params(&running.params_json);
     params = prepare_warm_params(&runtime.db, &running.template_id, params).await?;
 
@@ -1979,14 +3028,21 @@ async fn run_next_download_queue_job(runtime: &DownloadQueueRuntime) -> Result<b
                 .update(&*runtime.db)
                 .await
                 .map_err(|e| format!("db error: {e}"))?;
+            crate::download_queue::transition(
+                job_id,
+                DOWNLOAD_STATE_RUNNING,
+                DOWNLOAD_STATE_SUCCESS,
+            );
             let _ = trim_download_history(&runtime.db, 50).await;
-            Ok(true)
+            Ok(())
         }
         Err(status) => {
-            let msg = if let Some(payload) = parse_agent_error_payload(status.message()) {
-                payload.message
-            } else {
-                format!("process.warm_template_cache: {}", status.message())
+            let (code, msg) = match parse_agent_error_payload(status.message()) {
+                Some(payload) => (Some(payload.code), payload.message),
+                None => (
+                    None,
+                    format!("process.warm_template_cache: {}", status.message()),
+                ),
             };
 
             if let Ok(progress) = transport
@@ -2011,6 +3067,35 @@ async fn run_next_download_queue_job(runtime: &DownloadQueueRuntime) -> Result<b
             }
 
             let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
+            if let Some(delay) = crate::download_queue::retry_after(&running, code.as_deref(), &msg)
+            {
+                let at = now + delay;
+                let message = format!(
+                    "attempt {}/{} failed, retrying in {}s: {}",
+                    running.attempt_count,
+                    running.max_attempts,
+                    delay.as_secs(),
+                    compact_download_error_message(&msg)
+                );
+                let mut retry: download_jobs::ActiveModel = running.into();
+                retry.state = Set(DOWNLOAD_STATE_QUEUED.to_string());
+                retry.message = Set(message);
+                retry.request_id = Set(None);
+                retry.next_attempt_at = Set(Some(at));
+                retry.updated_at = Set(now);
+                retry
+                    .update(&*runtime.db)
+                    .await
+                    .map_err(|e| format!("db error: {e}"))?;
+                crate::download_queue::transition(
+                    job_id,
+                    DOWNLOAD_STATE_RUNNING,
+                    DOWNLOAD_STATE_QUEUED,
+                );
+                wake_download_queue_worker_at(runtime, at);
+                return Ok(());
+            }
+
             let mut failed: download_jobs::ActiveModel = running.into();
             failed.state = Set(DOWNLOAD_STATE_ERROR.to_string());
             failed.message = Set(compact_download_error_message(&msg));
@@ -2021,29 +3106,69 @@ async fn run_next_download_queue_job(runtime: &DownloadQueueRuntime) -> Result<b
                 .update(&*runtime.db)
                 .await
                 .map_err(|e| format!("db error: {e}"))?;
+            crate::download_queue::transition(job_id, DOWNLOAD_STATE_RUNNING, DOWNLOAD_STATE_ERROR);
             let _ = trim_download_history(&runtime.db, 50).await;
-            Ok(true)
+            Ok(())
         }
     }
 }
 
+/// Finishes a running job the user cancelled: asks the agent to abort the
+/// transfer, which also removes its partial file, and marks the job canceled.
+async fn finish_canceled_download_queue_job(
+    runtime: &DownloadQueueRuntime,
+    running: alloy_db::entities::download_jobs::Model,
+) -> Result<(), String> {
+    use alloy_db::entities::download_jobs;
+    use sea_orm::{ActiveModelTrait, Set};
+
+    let job_id = running.id;
+    let transport = AgentTransport::new(runtime.agent_hub.clone());
+    if let Err(status) = transport
+        .call::<_, alloy_proto::agent_v1::CancelWarmTemplateResponse>(
+            "/alloy.agent.v1.ProcessService/CancelWarmTemplate",
+            CancelWarmTemplateRequest {
+                progress_id: download_progress_id(&running.id, running.attempt_count),
+            },
+        )
+        .await
+    {
+        tracing::warn!(%job_id, error = %status.message(), "failed to cancel download on agent");
+    }
+
+    let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
+    let mut canceled: download_jobs::ActiveModel = running.into();
+    canceled.state = Set(DOWNLOAD_STATE_CANCELED.to_string());
+    canceled.message = Set("canceled by user".to_string());
+    canceled.request_id = Set(None);
+    canceled.updated_at = Set(now);
+    canceled.finished_at = Set(Some(now));
+    canceled
+        .update(&*runtime.db)
+        .await
+        .map_err(|e| format!("db error: {e}"))?;
+    crate::download_queue::transition(job_id, DOWNLOAD_STATE_RUNNING, DOWNLOAD_STATE_CANCELED);
+    let _ = trim_download_history(&runtime.db, 50).await;
+    Ok(())
+}
+
 async fn settings_status_output(ctx: &Ctx) -> Result<SettingsStatusOutput, ApiError> {
-    let dst_set = setting_is_set(&*ctx.db, SETTING_DST_DEFAULT_KLEI_KEY)
+    let dst_set = setting_is_set(&*ctx.db, settings::DST_DEFAULT_KLEI_KEY)
         .await
         .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
-    let cf_set = setting_is_set(&*ctx.db, SETTING_CURSEFORGE_API_KEY)
+    let cf_set = setting_is_set(&*ctx.db, settings::CURSEFORGE_API_KEY)
         .await
         .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
-    let steam_user_set = setting_is_set(&*ctx.db, SETTING_STEAMCMD_USERNAME)
+    let steam_user_set = setting_is_set(&*ctx.db, settings::STEAMCMD_USERNAME)
         .await
         .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
-    let steam_pass_set = setting_is_set(&*ctx.db, SETTING_STEAMCMD_PASSWORD)
+    let steam_pass_set = setting_is_set(&*ctx.db, settings::STEAMCMD_PASSWORD)
         .await
         .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
-    let steam_shared_secret_set = setting_is_set(&*ctx.db, SETTING_STEAMCMD_SHARED_SECRET)
+    let steam_shared_secret_set = setting_is_set(&*ctx.db, settings::STEAMCMD_SHARED_SECRET)
         .await
         .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?;
-    let steam_account_name = setting_get(&*ctx.db, SETTING_STEAMCMD_ACCOUNT_NAME)
+    let steam_account_name = setting_get(&*ctx.db, settings::STEAMCMD_ACCOUNT_NAME)
         .await
         .map_err(|e| api_error(ctx, "db_error", format!("db error: {e}")))?
         .map(|v| v.trim().to_string())
@@ -2161,18 +3286,7 @@ pub fn router() -> Router<Ctx> {
                         api_error_from_agent_status(&ctx, "process.get_cache_stats", status)
                     })?;
 
-                let cache = CacheStatsOutput {
-                    entries: cache_resp
-                        .entries
-                        .into_iter()
-                        .map(|e| CacheEntryDto {
-                            key: e.key,
-                            path: e.path,
-                            size_bytes: e.size_bytes.to_string(),
-                            last_used_unix_ms: e.last_used_unix_ms.to_string(),
-                        })
-                        .collect(),
-                };
+                let cache = map_cache_stats(cache_resp);
 
                 let mut agent_log_path: Option<String> = None;
                 let mut agent_log_lines: Vec<String> = Vec::new();
@@ -2181,6 +3295,7 @@ pub fn router() -> Router<Ctx> {
                         "/alloy.agent.v1.FilesystemService/ListDir",
                         ListDirRequest {
                             path: "logs".to_string(),
+                            ..Default::default()
                         },
                     )
                     .await
@@ -2272,24 +3387,70 @@ pub fn router() -> Router<Ctx> {
             }),
         )
         .procedure(
-            "list",
-            Procedure::builder::<ApiError>().query(|ctx, _: ()| async move {
+            "reloadTemplates",
+            Procedure::builder::<ApiError>().mutation(|ctx: Ctx, _: ()| async move {
+                ensure_writable(&ctx)?;
+                enforce_rate_limit(&ctx)?;
+
+                let user = ctx
+                    .user
+                    .clone()
+                    .ok_or_else(|| api_error(&ctx, "unauthorized", "unauthorized"))?;
+                if !user.is_admin {
+                    return Err(api_error(&ctx, "forbidden", "forbidden"));
+                }
+
                 let transport = agent_transport(&ctx);
-                let resp: alloy_proto::agent_v1::ListProcessesResponse = transport
+                let resp: alloy_proto::agent_v1::ReloadTemplatesResponse = transport
                     .call(
-                        "/alloy.agent.v1.ProcessService/ListProcesses",
-                        ListProcessesRequest {},
+                        "/alloy.agent.v1.ProcessService/ReloadTemplates",
+                        ReloadTemplatesRequest {},
                     )
                     .await
                     .map_err(|status| {
-                        api_error_from_agent_status(&ctx, "process.list_processes", status)
+                        api_error_from_agent_status(&ctx, "process.reload_templates", status)
                     })?;
 
-                Ok(resp
-                    .processes
+                audit::record(
+                    &ctx,
+                    "process.reloadTemplates",
+                    "templates",
+                    Some(serde_json::json!({
+                        "loaded": resp.loaded,
+                        "errors": resp.errors.len(),
+                    })),
+                )
+                .await;
+
+                Ok(ReloadTemplatesOutput {
+                    loaded: resp.loaded,
+                    errors: resp.errors,
+                })
+            }),
+        )
+        .procedure(
+            "list",
+            Procedure::builder::<ApiError>().query(|ctx, _: ()| async move {
+                let resps: Vec<alloy_proto::agent_v1::ListProcessesResponse> = call_instance_nodes(
+                    &ctx,
+                    "/alloy.agent.v1.ProcessService/ListProcesses",
+                    "process.list_processes",
+                    ListProcessesRequest {},
+                )
+                .await?;
+
+                let mut out = resps
                     .into_iter()
+                    .flat_map(|resp| resp.processes)
                     .map(map_process_status)
-                    .collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                if let (Some(user), Some(owned)) = (ctx.user.as_ref(), owned_instances(&ctx).await?)
+                {
+                    crate::instance_owners::retain_visible(user, &owned, &mut out, |p| {
+                        &p.process_id
+                    });
+                }
+                Ok(out)
             }),
         )
         .procedure(
@@ -2298,19 +3459,58 @@ pub fn router() -> Router<Ctx> {
                 ensure_writable(&ctx)?;
                 enforce_rate_limit(&ctx)?;
 
+                // Oneshot commands run inside an existing instance directory.
+                if input.template_id.starts_with("oneshot:") {
+                    // Arbitrary commands are an admin capability, like custom templates.
+                    let user = ctx
+                        .user
+                        .clone()
+                        .ok_or_else(|| api_error(&ctx, "unauthorized", "unauthorized"))?;
+                    if !user.is_admin {
+                        return Err(api_error(&ctx, "forbidden", "forbidden"));
+                    }
+                    let instance_id = input
+                        .params
+                        .get("instance_id")
+                        .map(|v| v.trim())
+                        .unwrap_or_default();
+                    if instance_id.is_empty() {
+                        return Err(api_error_with_field(
+                            &ctx,
+                            "invalid_param",
+                            "instance_id is required",
+                            "instance_id",
+                            "Required.",
+                        ));
+                    }
+                    ensure_instance_access(&ctx, instance_id).await?;
+                }
+
                 let transport = agent_transport(&ctx);
+                let node = transport.target_node().await;
 
                 let req = StartFromTemplateRequest {
                     template_id: input.template_id,
                     params: input.params.into_iter().collect(),
+                    node_default_params: node_default_params(&ctx, &node).await?,
                 };
 
-                let resp: alloy_proto::agent_v1::StartFromTemplateResponse = transport
+                let resp: alloy_proto::agent_v1::StartFromTemplateResponse = match transport
                     .call("/alloy.agent.v1.ProcessService/StartFromTemplate", req)
                     .await
-                    .map_err(|status| {
-                        api_error_from_agent_status(&ctx, "process.start_from_template", status)
-                    })?;
+                {
+                    Ok(resp) => resp,
+                    Err(status) => {
+                        let err = api_error_from_agent_status(
+                            &ctx,
+                            "process.start_from_template",
+                            status,
+                        );
+                        record_start_failure(&ctx, &node, &err).await;
+                        return Err(err);
+                    }
+                };
+                crate::node_stats::record_start(&ctx, &node, Ok(())).await;
 
                 let status = resp
                     .status
@@ -2318,6 +3518,7 @@ pub fn router() -> Router<Ctx> {
 
                 let process_id = status.process_id.clone();
                 let template_id = status.template_id.clone();
+                record_instance_owner(&ctx, &process_id).await;
                 audit::record(
                     &ctx,
                     "process.start",
@@ -2332,10 +3533,11 @@ pub fn router() -> Router<Ctx> {
         .procedure(
             "stop",
             Procedure::builder::<ApiError>().mutation(|ctx, input: StopProcessInput| async move {
+                ensure_instance_access(&ctx, &input.process_id).await?;
                 ensure_writable(&ctx)?;
                 enforce_rate_limit(&ctx)?;
 
-                let transport = agent_transport(&ctx);
+                let transport = instance_transport(&ctx, &input.process_id).await?;
 
                 let req = StopProcessRequest {
                     process_id: input.process_id,
@@ -2364,10 +3566,43 @@ pub fn router() -> Router<Ctx> {
                 Ok(map_process_status(status))
             }),
         )
+        .procedure(
+            "sendConsole",
+            Procedure::builder::<ApiError>().mutation(|ctx, input: SendConsoleInput| async move {
+                ensure_instance_access(&ctx, &input.process_id).await?;
+                ensure_writable(&ctx)?;
+                enforce_rate_limit(&ctx)?;
+
+                let transport = instance_transport(&ctx, &input.process_id).await?;
+                let resp: alloy_proto::agent_v1::SendConsoleResponse = transport
+                    .call(
+                        "/alloy.agent.v1.ProcessService/SendConsole",
+                        SendConsoleRequest {
+                            process_id: input.process_id.clone(),
+                            command: input.command.clone(),
+                        },
+                    )
+                    .await
+                    .map_err(|status| {
+                        api_error_from_agent_status(&ctx, "process.send_console", status)
+                    })?;
+
+                audit::record(
+                    &ctx,
+                    "process.sendConsole",
+                    &input.process_id,
+                    Some(serde_json::json!({ "command": input.command })),
+                )
+                .await;
+
+                Ok(SendConsoleOutput { ok: resp.ok })
+            }),
+        )
         .procedure(
             "status",
             Procedure::builder::<ApiError>().query(|ctx, input: GetStatusInput| async move {
-                let transport = agent_transport(&ctx);
+                ensure_instance_access(&ctx, &input.process_id).await?;
+                let transport = instance_transport(&ctx, &input.process_id).await?;
 
                 let resp: alloy_proto::agent_v1::GetStatusResponse = transport
                     .call(
@@ -2391,7 +3626,23 @@ pub fn router() -> Router<Ctx> {
         .procedure(
             "logsTail",
             Procedure::builder::<ApiError>().query(|ctx, input: TailLogsInput| async move {
-                let transport = agent_transport(&ctx);
+                ensure_instance_access(&ctx, &input.process_id).await?;
+                let streams = input
+                    .streams
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|s| match s.trim() {
+                        "stdout" => Ok(LogStream::Stdout as i32),
+                        "stderr" => Ok(LogStream::Stderr as i32),
+                        "agent" => Ok(LogStream::Agent as i32),
+                        other => Err(api_error(
+                            &ctx,
+                            "invalid_param",
+                            format!("unknown log stream: {other}"),
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let transport = instance_transport(&ctx, &input.process_id).await?;
                 let resp: alloy_proto::agent_v1::TailLogsResponse = transport
                     .call(
                         "/alloy.agent.v1.ProcessService/TailLogs",
@@ -2399,6 +3650,8 @@ pub fn router() -> Router<Ctx> {
                             process_id: input.process_id,
                             limit: input.limit.unwrap_or(200),
                             cursor: input.cursor.unwrap_or_default(),
+                            streams,
+                            contains: input.contains.unwrap_or_default(),
                         },
                     )
                     .await
@@ -2412,6 +3665,110 @@ pub fn router() -> Router<Ctx> {
                 })
             }),
         )
+        .procedure(
+            "resourceHistory",
+            Procedure::builder::<ApiError>().query(|ctx, input: ResourceHistoryInput| async move {
+                ensure_instance_access(&ctx, &input.process_id).await?;
+                let since_unix_ms = match input.since_unix_ms.as_deref().map(str::trim) {
+                    None | Some("") => 0,
+                    Some(v) => v
+                        .parse::<u64>()
+                        .map_err(|_| api_error(&ctx, "invalid_param", "invalid since_unix_ms"))?,
+                };
+                let transport = instance_transport(&ctx, &input.process_id).await?;
+                let resp: alloy_proto::agent_v1::GetResourceHistoryResponse = transport
+                    .call(
+                        "/alloy.agent.v1.ProcessService/GetResourceHistory",
+                        GetResourceHistoryRequest {
+                            process_id: input.process_id,
+                            since_unix_ms,
+                        },
+                    )
+                    .await
+                    .map_err(|status| {
+                        api_error_from_agent_status(&ctx, "process.resource_history", status)
+                    })?;
+
+                Ok(ResourceHistoryOutput {
+                    samples: resp
+                        .samples
+                        .into_iter()
+                        .filter_map(|s| {
+                            Some(ResourceSampleDto {
+                                at_unix_ms: s.at_unix_ms.to_string(),
+                                resources: map_process_resources(s.resources?),
+                            })
+                        })
+                        .collect(),
+                    sample_interval_ms: resp.sample_interval_ms,
+                })
+            }),
+        )
+        .procedure(
+            "versionRequirements",
+            Procedure::builder::<ApiError>().query(
+                |ctx, input: VersionRequirementsInput| async move {
+                    let transport = agent_transport(&ctx);
+                    let resp: alloy_proto::agent_v1::GetVersionRequirementsResponse = transport
+                        .call(
+                            "/alloy.agent.v1.ProcessService/GetVersionRequirements",
+                            GetVersionRequirementsRequest {
+                                template_id: input.template_id,
+                                params: input.params.into_iter().collect(),
+                            },
+                        )
+                        .await
+                        .map_err(|status| {
+                            api_error_from_agent_status(
+                                &ctx,
+                                "process.version_requirements",
+                                status,
+                            )
+                        })?;
+
+                    let non_empty = |v: String| (!v.is_empty()).then_some(v);
+                    Ok(VersionRequirementsOutput {
+                        java_required: resp.java_required,
+                        java_major: (resp.java_major != 0).then_some(resp.java_major),
+                        minecraft_version: non_empty(resp.minecraft_version),
+                        loader: non_empty(resp.loader),
+                        runtime_java_major: (resp.runtime_java_major != 0)
+                            .then_some(resp.runtime_java_major),
+                        java_satisfied: resp.java_satisfied,
+                        message: resp.message,
+                    })
+                },
+            ),
+        )
+        .procedure(
+            "validateTemplate",
+            Procedure::builder::<ApiError>().query(
+                |ctx, input: ValidateTemplateInput| async move {
+                    let transport = agent_transport(&ctx);
+                    let node = transport.target_node().await;
+                    let resp: alloy_proto::agent_v1::ValidateTemplateResponse = transport
+                        .call(
+                            "/alloy.agent.v1.ProcessService/ValidateTemplate",
+                            ValidateTemplateRequest {
+                                template_id: input.template_id,
+                                params: input.params.into_iter().collect(),
+                                node_default_params: node_default_params(&ctx, &node).await?,
+                            },
+                        )
+                        .await
+                        .map_err(|status| {
+                            api_error_from_agent_status(&ctx, "process.validate_template", status)
+                        })?;
+
+                    Ok(ValidateTemplateOutput {
+                        ok: resp.ok,
+                        field_errors: resp.field_errors.into_iter().collect(),
+                        errors: resp.errors,
+                        warnings: resp.warnings,
+                    })
+                },
+            ),
+        )
         .procedure(
             "warmCache",
             Procedure::builder::<ApiError>().mutation(
@@ -2455,23 +3812,7 @@ pub fn router() -> Router<Ctx> {
                             api_error_from_agent_status(&ctx, "process.warm_template_cache", status)
                         })?;
 
-                    let mut audit_params = serde_json::Map::new();
-                    for (key, value) in &params {
-                        let key_lower = key.to_ascii_lowercase();
-                        let is_secret = key_lower.contains("password")
-                            || key_lower.contains("token")
-                            || key_lower.contains("secret")
-                            || key_lower.contains("api_key")
-                            || key_lower.contains("apikey");
-                        audit_params.insert(
-                            key.clone(),
-                            serde_json::Value::String(if is_secret {
-                                "<redacted>".to_string()
-                            } else {
-                                value.clone()
-                            }),
-                        );
-                    }
+                    let audit_params = crate::node_defaults::redact(&input.params);
 
                     audit::record(
                         &ctx,
@@ -2502,18 +3843,7 @@ pub fn router() -> Router<Ctx> {
                         api_error_from_agent_status(&ctx, "process.get_cache_stats", status)
                     })?;
 
-                Ok(CacheStatsOutput {
-                    entries: resp
-                        .entries
-                        .into_iter()
-                        .map(|e| CacheEntryDto {
-                            key: e.key,
-                            path: e.path,
-                            size_bytes: e.size_bytes.to_string(),
-                            last_used_unix_ms: e.last_used_unix_ms.to_string(),
-                        })
-                        .collect(),
-                })
+                Ok(map_cache_stats(resp))
             }),
         )
         .procedure(
@@ -2546,16 +3876,8 @@ pub fn router() -> Router<Ctx> {
                 Ok(ClearCacheOutput {
                     ok: resp.ok,
                     freed_bytes: resp.freed_bytes.to_string(),
-                    cleared: resp
-                        .cleared
-                        .into_iter()
-                        .map(|e| CacheEntryDto {
-                            key: e.key,
-                            path: e.path,
-                            size_bytes: e.size_bytes.to_string(),
-                            last_used_unix_ms: e.last_used_unix_ms.to_string(),
-                        })
-                        .collect(),
+                    cleared: resp.cleared.into_iter().map(map_cache_entry).collect(),
+                    skipped: resp.skipped.into_iter().map(map_cache_entry).collect(),
                 })
             }),
         )
@@ -2642,7 +3964,12 @@ pub fn router() -> Router<Ctx> {
                         message: Set("queued for download".to_string()),
                         request_id: Set(None),
                         queue_position: Set(queue_position),
+                        priority: Set(crate::download_queue::clamp_priority(
+                            input.priority.unwrap_or(0),
+                        )),
                         attempt_count: Set(0),
+                        max_attempts: Set(crate::download_queue::DEFAULT_MAX_ATTEMPTS),
+                        next_attempt_at: Set(None),
                         created_at: Set(now),
                         updated_at: Set(now),
                         started_at: Set(None),
@@ -2654,6 +3981,7 @@ pub fn router() -> Router<Ctx> {
                         .await
                         .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
 
+                    crate::download_queue::transition(inserted.id, "new", DOWNLOAD_STATE_QUEUED);
                     let _ = trim_download_history(&*ctx.db, 50).await;
                     wake_download_queue_worker();
 
@@ -2702,10 +4030,7 @@ pub fn router() -> Router<Ctx> {
             Procedure::builder::<ApiError>().mutation(
                 |ctx, input: DownloadQueueMoveInput| async move {
                     use alloy_db::entities::download_jobs;
-                    use sea_orm::{
-                        ActiveModelTrait, ColumnTrait, Condition, EntityTrait, QueryFilter,
-                        QueryOrder, Set,
-                    };
+                    use sea_orm::{ActiveModelTrait, Set};
 
                     ensure_writable(&ctx)?;
                     enforce_rate_limit(&ctx)?;
@@ -2731,15 +4056,7 @@ pub fn router() -> Router<Ctx> {
                         return Ok(DownloadQueueMutationOutput { ok: true });
                     }
 
-                    let queue_condition = Condition::any()
-                        .add(download_jobs::Column::State.eq(DOWNLOAD_STATE_QUEUED))
-                        .add(download_jobs::Column::State.eq(DOWNLOAD_STATE_PAUSED));
-
-                    let rows = download_jobs::Entity::find()
-                        .filter(queue_condition)
-                        .order_by_asc(download_jobs::Column::QueuePosition)
-                        .order_by_asc(download_jobs::Column::CreatedAt)
-                        .all(&*ctx.db)
+                    let rows = download_queue_waiting(&*ctx.db)
                         .await
                         .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
 
@@ -2753,18 +4070,21 @@ pub fn router() -> Router<Ctx> {
                     }
                     let next_idx = next_idx as usize;
 
-                    let left_pos = rows[idx].queue_position;
-                    let right_pos = rows[next_idx].queue_position;
+                    // Swapping priorities too lets a job move across priority tiers.
+                    let left_slot = (rows[idx].priority, rows[idx].queue_position);
+                    let right_slot = (rows[next_idx].priority, rows[next_idx].queue_position);
 
                     let mut left: download_jobs::ActiveModel = rows[idx].clone().into();
-                    left.queue_position = Set(right_pos);
+                    left.priority = Set(right_slot.0);
+                    left.queue_position = Set(right_slot.1);
                     left.updated_at = Set(chrono::Utc::now().into());
                     left.update(&*ctx.db)
                         .await
                         .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
 
                     let mut right: download_jobs::ActiveModel = rows[next_idx].clone().into();
-                    right.queue_position = Set(left_pos);
+                    right.priority = Set(left_slot.0);
+                    right.queue_position = Set(left_slot.1);
                     right.updated_at = Set(chrono::Utc::now().into());
                     right
                         .update(&*ctx.db)
@@ -2777,9 +4097,9 @@ pub fn router() -> Router<Ctx> {
             ),
         )
         .procedure(
-            "downloadQueuePauseJob",
+            "downloadQueueSetPriority",
             Procedure::builder::<ApiError>().mutation(
-                |ctx, input: DownloadQueueJobActionInput| async move {
+                |ctx, input: DownloadQueueSetPriorityInput| async move {
                     use alloy_db::entities::download_jobs;
                     use sea_orm::{ActiveModelTrait, EntityTrait, Set};
 
@@ -2804,29 +4124,40 @@ pub fn router() -> Router<Ctx> {
                     else {
                         return Ok(DownloadQueueMutationOutput { ok: true });
                     };
-                    if model.state != DOWNLOAD_STATE_QUEUED {
+                    // A running job keeps running; priority only affects what starts next.
+                    if model.state != DOWNLOAD_STATE_QUEUED && model.state != DOWNLOAD_STATE_PAUSED
+                    {
                         return Ok(DownloadQueueMutationOutput { ok: true });
                     }
 
+                    let priority = crate::download_queue::clamp_priority(input.priority);
                     let mut active: download_jobs::ActiveModel = model.into();
-                    active.state = Set(DOWNLOAD_STATE_PAUSED.to_string());
-                    active.message = Set("paused in queue".to_string());
+                    active.priority = Set(priority);
                     active.updated_at = Set(chrono::Utc::now().into());
                     active
                         .update(&*ctx.db)
                         .await
                         .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
 
+                    audit::record(
+                        &ctx,
+                        "process.downloadQueueSetPriority",
+                        &job_id.to_string(),
+                        Some(serde_json::json!({ "priority": priority })),
+                    )
+                    .await;
+
+                    wake_download_queue_worker();
                     Ok(DownloadQueueMutationOutput { ok: true })
                 },
             ),
         )
         .procedure(
-            "downloadQueueResumeJob",
+            "downloadQueueMoveToFront",
             Procedure::builder::<ApiError>().mutation(
                 |ctx, input: DownloadQueueJobActionInput| async move {
                     use alloy_db::entities::download_jobs;
-                    use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+                    use sea_orm::{ActiveModelTrait, Set};
 
                     ensure_writable(&ctx)?;
                     enforce_rate_limit(&ctx)?;
@@ -2842,20 +4173,21 @@ pub fn router() -> Router<Ctx> {
                             )
                         })?;
 
-                    let Some(model) = download_jobs::Entity::find_by_id(job_id)
-                        .one(&*ctx.db)
+                    let rows = download_queue_waiting(&*ctx.db)
                         .await
-                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    let Some((priority, position)) =
+                        crate::download_queue::front_slot(&rows, job_id)
                     else {
                         return Ok(DownloadQueueMutationOutput { ok: true });
                     };
-                    if model.state != DOWNLOAD_STATE_PAUSED {
+                    let Some(model) = rows.into_iter().find(|r| r.id == job_id) else {
                         return Ok(DownloadQueueMutationOutput { ok: true });
-                    }
+                    };
 
                     let mut active: download_jobs::ActiveModel = model.into();
-                    active.state = Set(DOWNLOAD_STATE_QUEUED.to_string());
-                    active.message = Set("queued for download".to_string());
+                    active.priority = Set(priority);
+                    active.queue_position = Set(position);
                     active.updated_at = Set(chrono::Utc::now().into());
                     active
                         .update(&*ctx.db)
@@ -2868,7 +4200,7 @@ pub fn router() -> Router<Ctx> {
             ),
         )
         .procedure(
-            "downloadQueueCancelJob",
+            "downloadQueuePauseJob",
             Procedure::builder::<ApiError>().mutation(
                 |ctx, input: DownloadQueueJobActionInput| async move {
                     use alloy_db::entities::download_jobs;
@@ -2895,30 +4227,27 @@ pub fn router() -> Router<Ctx> {
                     else {
                         return Ok(DownloadQueueMutationOutput { ok: true });
                     };
-                    if model.state != DOWNLOAD_STATE_QUEUED && model.state != DOWNLOAD_STATE_PAUSED
-                    {
+                    if model.state != DOWNLOAD_STATE_QUEUED {
                         return Ok(DownloadQueueMutationOutput { ok: true });
                     }
 
-                    let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
+                    let (job_id, from) = (model.id, model.state.clone());
                     let mut active: download_jobs::ActiveModel = model.into();
-                    active.state = Set(DOWNLOAD_STATE_CANCELED.to_string());
-                    active.message = Set("canceled by user".to_string());
-                    active.updated_at = Set(now);
-                    active.finished_at = Set(Some(now));
+                    active.state = Set(DOWNLOAD_STATE_PAUSED.to_string());
+                    active.message = Set("paused in queue".to_string());
+                    active.updated_at = Set(chrono::Utc::now().into());
                     active
                         .update(&*ctx.db)
                         .await
                         .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    crate::download_queue::transition(job_id, &from, DOWNLOAD_STATE_PAUSED);
 
-                    let _ = trim_download_history(&*ctx.db, 50).await;
-                    wake_download_queue_worker();
                     Ok(DownloadQueueMutationOutput { ok: true })
                 },
             ),
         )
         .procedure(
-            "downloadQueueRetryJob",
+            "downloadQueueResumeJob",
             Procedure::builder::<ApiError>().mutation(
                 |ctx, input: DownloadQueueJobActionInput| async move {
                     use alloy_db::entities::download_jobs;
@@ -2945,10 +4274,117 @@ pub fn router() -> Router<Ctx> {
                     else {
                         return Ok(DownloadQueueMutationOutput { ok: true });
                     };
+                    if model.state != DOWNLOAD_STATE_PAUSED {
+                        return Ok(DownloadQueueMutationOutput { ok: true });
+                    }
 
-                    if model.state != DOWNLOAD_STATE_SUCCESS
-                        && model.state != DOWNLOAD_STATE_ERROR
-                        && model.state != DOWNLOAD_STATE_CANCELED
+                    let (job_id, from) = (model.id, model.state.clone());
+                    let mut active: download_jobs::ActiveModel = model.into();
+                    active.state = Set(DOWNLOAD_STATE_QUEUED.to_string());
+                    active.message = Set("queued for download".to_string());
+                    active.updated_at = Set(chrono::Utc::now().into());
+                    active
+                        .update(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    crate::download_queue::transition(job_id, &from, DOWNLOAD_STATE_QUEUED);
+
+                    wake_download_queue_worker();
+                    Ok(DownloadQueueMutationOutput { ok: true })
+                },
+            ),
+        )
+        .procedure(
+            "downloadQueueCancelJob",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx, input: DownloadQueueJobActionInput| async move {
+                    use alloy_db::entities::download_jobs;
+                    use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let job_id =
+                        sea_orm::prelude::Uuid::parse_str(input.job_id.trim()).map_err(|_| {
+                            api_error_with_field(
+                                &ctx,
+                                "invalid_param",
+                                "invalid job id",
+                                "job_id",
+                                "invalid uuid",
+                            )
+                        })?;
+
+                    let Some(model) = download_jobs::Entity::find_by_id(job_id)
+                        .one(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                    else {
+                        return Ok(DownloadQueueMutationOutput { ok: true });
+                    };
+                    // The worker aborts the transfer and records the cancel itself.
+                    if model.state == DOWNLOAD_STATE_RUNNING {
+                        if let Some(runtime) = download_queue_runtime() {
+                            runtime.running.cancel(job_id);
+                        }
+                        return Ok(DownloadQueueMutationOutput { ok: true });
+                    }
+                    if model.state != DOWNLOAD_STATE_QUEUED && model.state != DOWNLOAD_STATE_PAUSED
+                    {
+                        return Ok(DownloadQueueMutationOutput { ok: true });
+                    }
+
+                    let now: sea_orm::prelude::DateTimeWithTimeZone = chrono::Utc::now().into();
+                    let (job_id, from) = (model.id, model.state.clone());
+                    let mut active: download_jobs::ActiveModel = model.into();
+                    active.state = Set(DOWNLOAD_STATE_CANCELED.to_string());
+                    active.message = Set("canceled by user".to_string());
+                    active.updated_at = Set(now);
+                    active.finished_at = Set(Some(now));
+                    active
+                        .update(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    crate::download_queue::transition(job_id, &from, DOWNLOAD_STATE_CANCELED);
+
+                    let _ = trim_download_history(&*ctx.db, 50).await;
+                    wake_download_queue_worker();
+                    Ok(DownloadQueueMutationOutput { ok: true })
+                },
+            ),
+        )
+        .procedure(
+            "downloadQueueRetryJob",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx, input: DownloadQueueJobActionInput| async move {
+                    use alloy_db::entities::download_jobs;
+                    use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let job_id =
+                        sea_orm::prelude::Uuid::parse_str(input.job_id.trim()).map_err(|_| {
+                            api_error_with_field(
+                                &ctx,
+                                "invalid_param",
+                                "invalid job id",
+                                "job_id",
+                                "invalid uuid",
+                            )
+                        })?;
+
+                    let Some(model) = download_jobs::Entity::find_by_id(job_id)
+                        .one(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                    else {
+                        return Ok(DownloadQueueMutationOutput { ok: true });
+                    };
+
+                    if model.state != DOWNLOAD_STATE_SUCCESS
+                        && model.state != DOWNLOAD_STATE_ERROR
+                        && model.state != DOWNLOAD_STATE_CANCELED
                         && model.state != DOWNLOAD_STATE_PAUSED
                     {
                         return Ok(DownloadQueueMutationOutput { ok: true });
@@ -2959,10 +4395,17 @@ pub fn router() -> Router<Ctx> {
                         .await
                         .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
 
+                    let (job_id, from) = (model.id, model.state.clone());
+                    // A manual retry grants a fresh set of automatic attempts.
+                    let max_attempts = model
+                        .attempt_count
+                        .saturating_add(crate::download_queue::DEFAULT_MAX_ATTEMPTS);
                     let mut active: download_jobs::ActiveModel = model.into();
                     active.state = Set(DOWNLOAD_STATE_QUEUED.to_string());
                     active.message = Set("queued for retry".to_string());
                     active.request_id = Set(None);
+                    active.max_attempts = Set(max_attempts);
+                    active.next_attempt_at = Set(None);
                     active.queue_position = Set(next_pos);
                     active.started_at = Set(None);
                     active.finished_at = Set(None);
@@ -2971,6 +4414,7 @@ pub fn router() -> Router<Ctx> {
                         .update(&*ctx.db)
                         .await
                         .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    crate::download_queue::transition(job_id, &from, DOWNLOAD_STATE_QUEUED);
 
                     wake_download_queue_worker();
                     Ok(DownloadQueueMutationOutput { ok: true })
@@ -3023,16 +4467,30 @@ pub fn router() -> Router<Ctx> {
         .procedure(
             "listDir",
             Procedure::builder::<ApiError>().query(|ctx, input: ListDirInput| async move {
-                let transport = agent_transport(&ctx);
-                let resp: alloy_proto::agent_v1::ListDirResponse = transport
+                let path = input.path.unwrap_or_default();
+                let (transport, owned) = match ensure_path_access(&ctx, &path, true).await? {
+                    Some(id) => (instance_transport(&ctx, &id).await?, None),
+                    None => (agent_transport(&ctx), owned_instances(&ctx).await?),
+                };
+
+                let mut resp: alloy_proto::agent_v1::ListDirResponse = transport
                     .call(
                         "/alloy.agent.v1.FilesystemService/ListDir",
                         ListDirRequest {
-                            path: input.path.unwrap_or_default(),
+                            path,
+                            recursive: input.recursive.unwrap_or(false),
+                            max_entries: input.max_entries.unwrap_or(0),
+                            cursor: input.cursor.unwrap_or_default(),
                         },
                     )
                     .await
                     .map_err(|status| api_error_from_agent_status(&ctx, "fs.list_dir", status))?;
+                // Entry paths are relative to `instances/`, so they start with the id.
+                if let (Some(user), Some(owned)) = (ctx.user.as_ref(), owned) {
+                    crate::instance_owners::retain_visible(user, &owned, &mut resp.entries, |e| {
+                        e.path.split('/').next().unwrap_or_default()
+                    });
+                }
 
                 Ok(ListDirOutput {
                     entries: resp
@@ -3040,18 +4498,23 @@ pub fn router() -> Router<Ctx> {
                         .into_iter()
                         .map(|e| DirEntryDto {
                             name: e.name,
+                            path: e.path,
                             is_dir: e.is_dir,
                             size_bytes: clamp_u64_to_u32(e.size_bytes),
                             modified_unix_ms: e.modified_unix_ms.to_string(),
                         })
                         .collect(),
+                    next_cursor: (!resp.next_cursor.is_empty()).then_some(resp.next_cursor),
                 })
             }),
         )
         .procedure(
             "readFile",
             Procedure::builder::<ApiError>().query(|ctx, input: ReadFileInput| async move {
-                let transport = agent_transport(&ctx);
+                let transport = match ensure_path_access(&ctx, &input.path, false).await? {
+                    Some(id) => instance_transport(&ctx, &id).await?,
+                    None => agent_transport(&ctx),
+                };
                 let resp: alloy_proto::agent_v1::ReadFileResponse = transport
                     .call(
                         "/alloy.agent.v1.FilesystemService/ReadFile",
@@ -3077,7 +4540,10 @@ pub fn router() -> Router<Ctx> {
     let log = Router::new().procedure(
         "tailFile",
         Procedure::builder::<ApiError>().query(|ctx, input: TailFileInput| async move {
-            let transport = agent_transport(&ctx);
+            let transport = match ensure_path_access(&ctx, &input.path, false).await? {
+                Some(id) => instance_transport(&ctx, &id).await?,
+                None => agent_transport(&ctx),
+            };
             let resp: alloy_proto::agent_v1::TailFileResponse = transport
                 .call(
                     "/alloy.agent.v1.LogsService/TailFile",
@@ -3112,7 +4578,7 @@ pub fn router() -> Router<Ctx> {
                     if input.template_id == "dst:vanilla" {
                         let current = params.get("cluster_token").map(|s| s.trim()).unwrap_or("");
                         if current.is_empty() {
-                            if let Some(v) = setting_get(&*ctx.db, SETTING_DST_DEFAULT_KLEI_KEY)
+                            if let Some(v) = setting_get(&*ctx.db, settings::DST_DEFAULT_KLEI_KEY)
                                 .await
                                 .map_err(|e| {
                                     api_error(&ctx, "db_error", format!("db error: {e}"))
@@ -3132,7 +4598,7 @@ pub fn router() -> Router<Ctx> {
                             .map(|s| s.trim())
                             .unwrap_or("");
                         if current.is_empty() {
-                            let v = setting_get(&*ctx.db, SETTING_CURSEFORGE_API_KEY)
+                            let v = setting_get(&*ctx.db, settings::CURSEFORGE_API_KEY)
                                 .await
                                 .map_err(|e| {
                                     api_error(&ctx, "db_error", format!("db error: {e}"))
@@ -3156,7 +4622,8 @@ pub fn router() -> Router<Ctx> {
                         }
                     }
 
-                    let transport = agent_transport(&ctx);
+                    let node = placement_node(&ctx, input.node_id.as_deref()).await?;
+                    let transport = pin_transport(agent_transport(&ctx), node.as_deref()).await;
                     let resp: alloy_proto::agent_v1::CreateInstanceResponse = transport
                         .call(
                             "/alloy.agent.v1.InstanceService/Create",
@@ -3175,11 +4642,15 @@ pub fn router() -> Router<Ctx> {
                         .config
                         .ok_or_else(|| api_error(&ctx, "internal", "missing instance config"))?;
 
+                    record_instance_owner(&ctx, &cfg.instance_id).await;
+                    if let Some(node) = &node {
+                        record_instance_placement(&ctx, &cfg.instance_id, node).await;
+                    }
                     audit::record(
                         &ctx,
                         "instance.create",
                         &cfg.instance_id,
-                        Some(serde_json::json!({ "template_id": cfg.template_id })),
+                        Some(serde_json::json!({ "template_id": cfg.template_id, "node": node })),
                     )
                     .await;
 
@@ -3190,7 +4661,8 @@ pub fn router() -> Router<Ctx> {
         .procedure(
             "get",
             Procedure::builder::<ApiError>().query(|ctx, input: InstanceIdInput| async move {
-                let transport = agent_transport(&ctx);
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
                 let resp: alloy_proto::agent_v1::GetInstanceResponse = transport
                     .call(
                         "/alloy.agent.v1.InstanceService/Get",
@@ -3211,19 +4683,24 @@ pub fn router() -> Router<Ctx> {
         .procedure(
             "list",
             Procedure::builder::<ApiError>().query(|ctx, _: ()| async move {
-                let transport = agent_transport(&ctx);
-                let resp: alloy_proto::agent_v1::ListInstancesResponse = transport
-                    .call(
-                        "/alloy.agent.v1.InstanceService/List",
-                        ListInstancesRequest {},
-                    )
-                    .await
-                    .map_err(|status| api_error_from_agent_status(&ctx, "instance.list", status))?;
+                let resps: Vec<alloy_proto::agent_v1::ListInstancesResponse> = call_instance_nodes(
+                    &ctx,
+                    "/alloy.agent.v1.InstanceService/List",
+                    "instance.list",
+                    ListInstancesRequest {},
+                )
+                .await?;
 
                 let mut out = Vec::new();
-                for info in resp.instances {
+                for info in resps.into_iter().flat_map(|resp| resp.instances) {
                     out.push(map_instance_info(&ctx, info)?);
                 }
+                if let (Some(user), Some(owned)) = (ctx.user.as_ref(), owned_instances(&ctx).await?)
+                {
+                    crate::instance_owners::retain_visible(user, &owned, &mut out, |i| {
+                        &i.config.instance_id
+                    });
+                }
                 Ok(out)
             }),
         )
@@ -3231,9 +4708,10 @@ pub fn router() -> Router<Ctx> {
             "diagnostics",
             Procedure::builder::<ApiError>().mutation(
                 |ctx, input: InstanceDiagnosticsInput| async move {
+                    ensure_instance_access(&ctx, &input.instance_id).await?;
                     enforce_rate_limit(&ctx)?;
 
-                    let transport = agent_transport(&ctx);
+                    let transport = instance_transport(&ctx, &input.instance_id).await?;
 
                     let instance_id = input.instance_id;
                     let max_lines = input.max_lines.unwrap_or(400).clamp(1, 2000);
@@ -3400,20 +4878,64 @@ pub fn router() -> Router<Ctx> {
         .procedure(
             "start",
             Procedure::builder::<ApiError>().mutation(|ctx, input: InstanceIdInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
                 ensure_writable(&ctx)?;
                 enforce_rate_limit(&ctx)?;
 
-                let transport = agent_transport(&ctx);
-                let resp: alloy_proto::agent_v1::StartInstanceResponse = transport
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let node = transport.target_node().await;
+                let resp: alloy_proto::agent_v1::StartInstanceResponse = match transport
                     .call(
                         "/alloy.agent.v1.InstanceService/Start",
                         StartInstanceRequest {
                             instance_id: input.instance_id,
+                            node_default_params: node_default_params(&ctx, &node).await?,
+                        },
+                    )
+                    .await
+                {
+                    Ok(resp) => resp,
+                    Err(status) => {
+                        let err = api_error_from_agent_status(&ctx, "instance.start", status);
+                        record_start_failure(&ctx, &node, &err).await;
+                        return Err(err);
+                    }
+                };
+                crate::node_stats::record_start(&ctx, &node, Ok(())).await;
+
+                let status = resp
+                    .status
+                    .ok_or_else(|| api_error(&ctx, "internal", "missing status"))?;
+
+                audit::record(
+                    &ctx,
+                    "instance.start",
+                    &status.process_id,
+                    Some(serde_json::json!({ "template_id": status.template_id })),
+                )
+                .await;
+
+                Ok(map_process_status(status))
+            }),
+        )
+        .procedure(
+            "reload",
+            Procedure::builder::<ApiError>().mutation(|ctx, input: InstanceIdInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                ensure_writable(&ctx)?;
+                enforce_rate_limit(&ctx)?;
+
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let resp: alloy_proto::agent_v1::ReloadInstanceResponse = transport
+                    .call(
+                        "/alloy.agent.v1.InstanceService/Reload",
+                        ReloadInstanceRequest {
+                            instance_id: input.instance_id,
                         },
                     )
                     .await
                     .map_err(|status| {
-                        api_error_from_agent_status(&ctx, "instance.start", status)
+                        api_error_from_agent_status(&ctx, "instance.reload", status)
                     })?;
 
                 let status = resp
@@ -3422,7 +4944,7 @@ pub fn router() -> Router<Ctx> {
 
                 audit::record(
                     &ctx,
-                    "instance.start",
+                    "instance.reload",
                     &status.process_id,
                     Some(serde_json::json!({ "template_id": status.template_id })),
                 )
@@ -3435,10 +4957,51 @@ pub fn router() -> Router<Ctx> {
             "restart",
             Procedure::builder::<ApiError>().mutation(
                 |ctx, input: RestartInstanceInput| async move {
+                    ensure_instance_access(&ctx, &input.instance_id).await?;
                     ensure_writable(&ctx)?;
                     enforce_rate_limit(&ctx)?;
 
-                    let transport = agent_transport(&ctx);
+                    let transport = instance_transport(&ctx, &input.instance_id).await?;
+                    let timeout_ms = input.timeout_ms.unwrap_or(30_000);
+
+                    // The agent restarts with the params of the last start. Older agents, or
+                    // instances that never started on this agent, fall back to stop + start.
+                    match transport
+                        .call::<_, alloy_proto::agent_v1::RestartInstanceResponse>(
+                            "/alloy.agent.v1.InstanceService/Restart",
+                            RestartInstanceRequest {
+                                instance_id: input.instance_id.clone(),
+                                timeout_ms,
+                            },
+                        )
+                        .await
+                    {
+                        Ok(resp) => {
+                            let status = resp
+                                .status
+                                .ok_or_else(|| api_error(&ctx, "internal", "missing status"))?;
+                            audit::record(
+                                &ctx,
+                                "instance.restart",
+                                &status.process_id,
+                                Some(serde_json::json!({ "template_id": status.template_id })),
+                            )
+                            .await;
+                            return Ok(map_process_status(status));
+                        }
+                        Err(status)
+                            if matches!(
+                                status.code(),
+                                tonic::Code::Unimplemented | tonic::Code::FailedPrecondition
+                            ) => {}
+                        Err(status) => {
+                            return Err(api_error_from_agent_status(
+                                &ctx,
+                                "instance.restart",
+                                status,
+                            ));
+                        }
+                    }
 
                     // Best-effort: if the instance isn't running, the stop call may return NOT_FOUND.
                     // Treat that as "already stopped" and continue to start.
@@ -3447,7 +5010,7 @@ pub fn router() -> Router<Ctx> {
                             "/alloy.agent.v1.InstanceService/Stop",
                             StopInstanceRequest {
                                 instance_id: input.instance_id.clone(),
-                                timeout_ms: input.timeout_ms.unwrap_or(30_000),
+                                timeout_ms,
                             },
                         )
                         .await
@@ -3464,17 +5027,25 @@ pub fn router() -> Router<Ctx> {
                         }
                     }
 
-                    let resp: alloy_proto::agent_v1::StartInstanceResponse = transport
+                    let node = transport.target_node().await;
+                    let resp: alloy_proto::agent_v1::StartInstanceResponse = match transport
                         .call(
                             "/alloy.agent.v1.InstanceService/Start",
                             StartInstanceRequest {
                                 instance_id: input.instance_id,
+                                node_default_params: node_default_params(&ctx, &node).await?,
                             },
                         )
                         .await
-                        .map_err(|status| {
-                            api_error_from_agent_status(&ctx, "instance.start", status)
-                        })?;
+                    {
+                        Ok(resp) => resp,
+                        Err(status) => {
+                            let err = api_error_from_agent_status(&ctx, "instance.start", status);
+                            record_start_failure(&ctx, &node, &err).await;
+                            return Err(err);
+                        }
+                    };
+                    crate::node_stats::record_start(&ctx, &node, Ok(())).await;
 
                     let status = resp
                         .status
@@ -3495,10 +5066,11 @@ pub fn router() -> Router<Ctx> {
         .procedure(
             "stop",
             Procedure::builder::<ApiError>().mutation(|ctx, input: StopInstanceInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
                 ensure_writable(&ctx)?;
                 enforce_rate_limit(&ctx)?;
 
-                let transport = agent_transport(&ctx);
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
                 let resp: alloy_proto::agent_v1::StopInstanceResponse = transport
                     .call(
                         "/alloy.agent.v1.InstanceService/Stop",
@@ -3529,10 +5101,11 @@ pub fn router() -> Router<Ctx> {
             "update",
             Procedure::builder::<ApiError>().mutation(
                 |ctx, input: UpdateInstanceInput| async move {
+                    ensure_instance_access(&ctx, &input.instance_id).await?;
                     ensure_writable(&ctx)?;
                     enforce_rate_limit(&ctx)?;
 
-                    let transport = agent_transport(&ctx);
+                    let transport = instance_transport(&ctx, &input.instance_id).await?;
                     let resp: alloy_proto::agent_v1::UpdateInstanceResponse = transport
                         .call(
                             "/alloy.agent.v1.InstanceService/Update",
@@ -3563,14 +5136,100 @@ pub fn router() -> Router<Ctx> {
                 },
             ),
         )
+        .procedure(
+            "bulkApplyParams",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx, input: BulkApplyParamsInput| async move {
+                    for id in &input.instance_ids {
+                        ensure_instance_access(&ctx, id).await?;
+                    }
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    // The agent applies the batch locally, so every instance
+                    // must live on the same node.
+                    let mut transport = agent_transport(&ctx);
+                    let mut batch_node: Option<String> = None;
+                    for id in &input.instance_ids {
+                        let t = instance_transport(&ctx, id).await?;
+                        let node = t.target_node().await;
+                        if batch_node.as_ref().is_some_and(|n| *n != node) {
+                            return Err(api_error(
+                                &ctx,
+                                "failed_precondition",
+                                "instances are on different nodes; apply params per node",
+                            ));
+                        }
+                        batch_node = Some(node);
+                        transport = t;
+                    }
+                    let node_defaults = if input.restart_running {
+                        let node = transport.target_node().await;
+                        node_default_params(&ctx, &node).await?
+                    } else {
+                        HashMap::new()
+                    };
+                    let audit_params = crate::node_defaults::redact(&input.params);
+                    let resp: alloy_proto::agent_v1::BulkApplyParamsResponse = transport
+                        .call(
+                            "/alloy.agent.v1.InstanceService/BulkApplyParams",
+                            BulkApplyParamsRequest {
+                                instance_ids: input.instance_ids,
+                                params: input.params.into_iter().collect(),
+                                restart_running: input.restart_running,
+                                stop_timeout_ms: input.stop_timeout_ms.unwrap_or(30_000),
+                                node_default_params: node_defaults,
+                            },
+                        )
+                        .await
+                        .map_err(|status| {
+                            api_error_from_agent_status(&ctx, "instance.bulk_apply_params", status)
+                        })?;
+
+                    let applied: Vec<&str> = resp
+                        .results
+                        .iter()
+                        .filter(|r| r.ok)
+                        .map(|r| r.instance_id.as_str())
+                        .collect();
+                    audit::record(
+                        &ctx,
+                        "instance.bulkApplyParams",
+                        &applied.join(","),
+                        Some(serde_json::json!({
+                            "params": audit_params,
+                            "restart_running": input.restart_running,
+                            "failed": resp.results.len() - applied.len(),
+                        })),
+                    )
+                    .await;
+
+                    Ok(BulkApplyParamsOutput {
+                        results: resp
+                            .results
+                            .into_iter()
+                            .map(|r| BulkApplyParamsResultDto {
+                                instance_id: r.instance_id,
+                                ok: r.ok,
+                                error: (!r.error.is_empty()).then_some(r.error),
+                                params: r.params.into_iter().collect(),
+                                restart_pending: r.restart_pending,
+                                restart_scheduled: r.restart_scheduled,
+                            })
+                            .collect(),
+                    })
+                },
+            ),
+        )
         .procedure(
             "importSaveFromUrl",
             Procedure::builder::<ApiError>().mutation(
                 |ctx, input: ImportSaveFromUrlInput| async move {
+                    ensure_instance_access(&ctx, &input.instance_id).await?;
                     ensure_writable(&ctx)?;
                     enforce_rate_limit(&ctx)?;
 
-                    let transport = agent_transport(&ctx);
+                    let transport = instance_transport(&ctx, &input.instance_id).await?;
                     let resp: alloy_proto::agent_v1::ImportSaveFromUrlResponse = transport
                         .call(
                             "/alloy.agent.v1.InstanceService/ImportSaveFromUrl",
@@ -3604,55 +5263,470 @@ pub fn router() -> Router<Ctx> {
             ),
         )
         .procedure(
-            "deletePreview",
-            Procedure::builder::<ApiError>().query(|ctx, input: InstanceIdInput| async move {
-                let transport = agent_transport(&ctx);
-                let resp: alloy_proto::agent_v1::DeleteInstancePreviewResponse = transport
+            "createBackup",
+            Procedure::builder::<ApiError>().mutation(|ctx, input: CreateBackupInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                ensure_writable(&ctx)?;
+                enforce_rate_limit(&ctx)?;
+
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let resp: alloy_proto::agent_v1::CreateBackupResponse = transport
                     .call(
-                        "/alloy.agent.v1.InstanceService/DeletePreview",
-                        DeleteInstancePreviewRequest {
-                            instance_id: input.instance_id,
+                        "/alloy.agent.v1.InstanceService/CreateBackup",
+                        alloy_proto::agent_v1::CreateBackupRequest {
+                            instance_id: input.instance_id.clone(),
+                            format: input.format.unwrap_or_default(),
                         },
                     )
                     .await
                     .map_err(|status| {
-                        api_error_from_agent_status(&ctx, "instance.delete_preview", status)
+                        api_error_from_agent_status(&ctx, "instance.create_backup", status)
                     })?;
+                let backup = resp.backup.ok_or_else(|| {
+                    api_error(&ctx, "internal", "agent returned no backup record")
+                })?;
 
-                Ok(DeleteInstancePreviewOutput {
-                    instance_id: resp.instance_id,
-                    path: resp.path,
-                    size_bytes: resp.size_bytes.to_string(),
-                })
+                record_backup(&ctx.db, &input.instance_id, &backup).await;
+                audit::record(
+                    &ctx,
+                    "instance.create_backup",
+                    &input.instance_id,
+                    Some(serde_json::json!({
+                        "backup_id": backup.backup_id,
+                        "format": backup.format,
+                        "size_bytes": backup.size_bytes,
+                    })),
+                )
+                .await;
+
+                Ok(backup_record_dto(backup))
             }),
         )
         .procedure(
-            "delete",
-            Procedure::builder::<ApiError>().mutation(|ctx, input: InstanceIdInput| async move {
-                ensure_writable(&ctx)?;
-                enforce_rate_limit(&ctx)?;
-
-                let instance_id = input.instance_id;
-                let transport = agent_transport(&ctx);
-                let resp: alloy_proto::agent_v1::DeleteInstanceResponse = transport
+            "listBackups",
+            Procedure::builder::<ApiError>().query(|ctx, input: ListBackupsInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let resp: alloy_proto::agent_v1::ListBackupsResponse = transport
                     .call(
-                        "/alloy.agent.v1.InstanceService/Delete",
-                        DeleteInstanceRequest {
-                            instance_id: instance_id.clone(),
+                        "/alloy.agent.v1.InstanceService/ListBackups",
+                        alloy_proto::agent_v1::ListBackupsRequest {
+                            instance_id: input.instance_id,
                         },
                     )
                     .await
                     .map_err(|status| {
-                        api_error_from_agent_status(&ctx, "instance.delete", status)
+                        api_error_from_agent_status(&ctx, "instance.list_backups", status)
                     })?;
 
-                if resp.ok {
-                    audit::record(&ctx, "instance.delete", &instance_id, None).await;
-                }
-
-                Ok(DeleteInstanceOutput { ok: resp.ok })
+                Ok(ListBackupsOutput {
+                    backups: resp.backups.into_iter().map(backup_record_dto).collect(),
+                })
             }),
-        );
+        )
+        .procedure(
+            "restoreBackup",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx, input: RestoreBackupInput| async move {
+                    ensure_instance_access(&ctx, &input.instance_id).await?;
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let transport = instance_transport(&ctx, &input.instance_id).await?;
+                    let resp: alloy_proto::agent_v1::RestoreBackupResponse = transport
+                        .call(
+                            "/alloy.agent.v1.InstanceService/RestoreBackup",
+                            alloy_proto::agent_v1::RestoreBackupRequest {
+                                process_id: input.instance_id.clone(),
+                                backup_id: input.backup_id.clone(),
+                            },
+                        )
+                        .await
+                        .map_err(|status| {
+                            api_error_from_agent_status(&ctx, "instance.restore_backup", status)
+                        })?;
+
+                    audit::record(
+                        &ctx,
+                        "instance.restore_backup",
+                        &input.instance_id,
+                        Some(serde_json::json!({
+                            "backup_id": input.backup_id,
+                            "pre_restore_backup_id": resp.pre_restore_backup_id,
+                        })),
+                    )
+                    .await;
+
+                    Ok(RestoreBackupOutput {
+                        ok: resp.ok,
+                        pre_restore_backup_id: resp.pre_restore_backup_id,
+                    })
+                },
+            ),
+        )
+        .procedure(
+            "stageUpgrade",
+            Procedure::builder::<ApiError>().mutation(|ctx, input: StageUpgradeInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                ensure_writable(&ctx)?;
+                enforce_rate_limit(&ctx)?;
+
+                let run_at_unix_ms = match input.run_at_unix_ms.as_deref().map(str::trim) {
+                    None | Some("") => 0,
+                    Some(v) => v.parse::<u64>().map_err(|_| {
+                        api_error_with_field(
+                            &ctx,
+                            "invalid_param",
+                            "invalid run_at_unix_ms",
+                            "run_at_unix_ms",
+                            "Must be a unix timestamp in milliseconds.",
+                        )
+                    })?,
+                };
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let node = transport.target_node().await;
+                let node_defaults = node_default_params(&ctx, &node).await?;
+                let resp: alloy_proto::agent_v1::StageUpgradeResponse = transport
+                    .call(
+                        "/alloy.agent.v1.InstanceService/StageUpgrade",
+                        alloy_proto::agent_v1::StageUpgradeRequest {
+                            instance_id: input.instance_id.clone(),
+                            target_version: input.target_version,
+                            run_at_unix_ms,
+                            warning_seconds: input.warning_seconds.unwrap_or_default(),
+                            backup: input.backup,
+                            stop_timeout_ms: input.stop_timeout_ms.unwrap_or(30_000),
+                            node_default_params: node_defaults,
+                        },
+                    )
+                    .await
+                    .map_err(|status| {
+                        api_error_from_agent_status(&ctx, "instance.stage_upgrade", status)
+                    })?;
+                let upgrade = resp
+                    .upgrade
+                    .ok_or_else(|| api_error(&ctx, "internal", "missing staged upgrade"))?;
+
+                audit::record(
+                    &ctx,
+                    "instance.stage_upgrade",
+                    &input.instance_id,
+                    Some(serde_json::json!({
+                        "upgrade_id": upgrade.upgrade_id,
+                        "target_version": upgrade.target_version,
+                        "run_at_unix_ms": upgrade.run_at_unix_ms,
+                        "backup": upgrade.backup,
+                    })),
+                )
+                .await;
+
+                Ok(map_staged_upgrade(upgrade))
+            }),
+        )
+        .procedure(
+            "stagedUpgrade",
+            Procedure::builder::<ApiError>().query(|ctx, input: StagedUpgradeInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let resp: alloy_proto::agent_v1::GetStagedUpgradeResponse = transport
+                    .call(
+                        "/alloy.agent.v1.InstanceService/GetStagedUpgrade",
+                        alloy_proto::agent_v1::GetStagedUpgradeRequest {
+                            instance_id: input.instance_id,
+                        },
+                    )
+                    .await
+                    .map_err(|status| {
+                        api_error_from_agent_status(&ctx, "instance.staged_upgrade", status)
+                    })?;
+                Ok(StagedUpgradeOutput {
+                    upgrade: resp.upgrade.map(map_staged_upgrade),
+                })
+            }),
+        )
+        .procedure(
+            "cancelStagedUpgrade",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx, input: StagedUpgradeInput| async move {
+                    ensure_instance_access(&ctx, &input.instance_id).await?;
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let transport = instance_transport(&ctx, &input.instance_id).await?;
+                    let resp: alloy_proto::agent_v1::CancelStagedUpgradeResponse = transport
+                        .call(
+                            "/alloy.agent.v1.InstanceService/CancelStagedUpgrade",
+                            alloy_proto::agent_v1::CancelStagedUpgradeRequest {
+                                instance_id: input.instance_id.clone(),
+                            },
+                        )
+                        .await
+                        .map_err(|status| {
+                            api_error_from_agent_status(
+                                &ctx,
+                                "instance.cancel_staged_upgrade",
+                                status,
+                            )
+                        })?;
+                    let upgrade = resp
+                        .upgrade
+                        .ok_or_else(|| api_error(&ctx, "internal", "missing staged upgrade"))?;
+
+                    audit::record(
+                        &ctx,
+                        "instance.cancel_staged_upgrade",
+                        &input.instance_id,
+                        Some(serde_json::json!({ "upgrade_id": upgrade.upgrade_id })),
+                    )
+                    .await;
+
+                    Ok(map_staged_upgrade(upgrade))
+                },
+            ),
+        )
+        .procedure(
+            "assignNode",
+            Procedure::builder::<ApiError>().mutation(|ctx, input: AssignNodeInput| async move {
+                use alloy_db::entities::instance_placements;
+                use sea_orm::EntityTrait;
+
+                ensure_writable(&ctx)?;
+                enforce_rate_limit(&ctx)?;
+                let node = admin_node_name(&ctx, &input.node_id).await?;
+
+                let pending = instance_placements::Entity::find_by_id(input.instance_id.clone())
+                    .one(&*ctx.db)
+                    .await
+                    .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                    .is_some_and(|row| row.migration_target.is_some());
+                if pending {
+                    return Err(api_error(
+                        &ctx,
+                        "failed_precondition",
+                        "a migration is in progress; resume or abort it first",
+                    ));
+                }
+
+                // Only record a placement the node can back up.
+                let transport = pin_transport(agent_transport(&ctx), Some(&node)).await;
+                let _: alloy_proto::agent_v1::GetInstanceResponse = transport
+                    .call(
+                        "/alloy.agent.v1.InstanceService/Get",
+                        GetInstanceRequest {
+                            instance_id: input.instance_id.clone(),
+                        },
+                    )
+                    .await
+                    .map_err(|status| {
+                        api_error_from_agent_status(&ctx, "instance.assign_node", status)
+                    })?;
+
+                crate::instance_migration::set_placement(&ctx.db, &input.instance_id, &node)
+                    .await
+                    .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+
+                audit::record(
+                    &ctx,
+                    "instance.assignNode",
+                    &input.instance_id,
+                    Some(serde_json::json!({ "node": node })),
+                )
+                .await;
+
+                Ok(AssignNodeOutput {
+                    instance_id: input.instance_id,
+                    node,
+                })
+            }),
+        )
+        .procedure(
+            "migrate",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx, input: MigrateInstanceInput| async move {
+                    ensure_instance_access(&ctx, &input.instance_id).await?;
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let transport = instance_transport(&ctx, &input.instance_id).await?;
+                    let out = crate::instance_migration::migrate_instance(
+                        &ctx.db,
+                        &transport,
+                        &input.instance_id,
+                        &input.target_node,
+                    )
+                    .await
+                    .map_err(|e| api_error_from_migration(&ctx, e))?;
+
+                    audit::record(
+                        &ctx,
+                        "instance.migrate",
+                        &input.instance_id,
+                        Some(serde_json::json!({
+                            "source_node": out.source_node,
+                            "target_node": out.target_node,
+                        })),
+                    )
+                    .await;
+
+                    Ok(MigrateInstanceOutput {
+                        source_node: out.source_node,
+                        target_node: out.target_node,
+                        status: out.status.map(map_process_status),
+                    })
+                },
+            ),
+        )
+        .procedure(
+            "abortMigration",
+            Procedure::builder::<ApiError>().mutation(|ctx, input: InstanceIdInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                ensure_writable(&ctx)?;
+                enforce_rate_limit(&ctx)?;
+
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let aborted = crate::instance_migration::abort_migration(
+                    &ctx.db,
+                    &transport,
+                    &input.instance_id,
+                )
+                .await
+                .map_err(|e| api_error_from_migration(&ctx, e))?;
+
+                if aborted {
+                    audit::record(&ctx, "instance.migrate_abort", &input.instance_id, None).await;
+                }
+
+                Ok(AbortMigrationOutput { aborted })
+            }),
+        )
+        .procedure(
+            "inspect",
+            Procedure::builder::<ApiError>().query(|ctx, input: InstanceIdInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let resp: alloy_proto::agent_v1::InspectInstanceResponse = transport
+                    .call(
+                        "/alloy.agent.v1.InstanceService/Inspect",
+                        InspectInstanceRequest {
+                            instance_id: input.instance_id,
+                        },
+                    )
+                    .await
+                    .map_err(|status| {
+                        api_error_from_agent_status(&ctx, "instance.inspect", status)
+                    })?;
+                let run = resp
+                    .run
+                    .ok_or_else(|| api_error(&ctx, "internal", "agent returned no run info"))?;
+
+                Ok(InspectInstanceOutput {
+                    process_id: run.process_id,
+                    template_id: run.template_id,
+                    started_at_unix_ms: run.started_at_unix_ms.to_string(),
+                    agent_version: run.agent_version,
+                    pid: run.has_pid.then_some(run.pid),
+                    container_name: Some(run.container_name).filter(|s| !s.is_empty()),
+                    container_id: Some(run.container_id).filter(|s| !s.is_empty()),
+                    exec: run.exec,
+                    args: run.args,
+                    cwd: run.cwd,
+                    params: run.params.into_iter().collect(),
+                    env: run.env.into_iter().collect(),
+                })
+            }),
+        )
+        .procedure(
+            "diskUsage",
+            Procedure::builder::<ApiError>().query(|ctx, input: InstanceIdInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let resp: alloy_proto::agent_v1::GetInstanceDiskUsageResponse = transport
+                    .call(
+                        "/alloy.agent.v1.InstanceService/GetDiskUsage",
+                        alloy_proto::agent_v1::GetInstanceDiskUsageRequest {
+                            instance_id: input.instance_id,
+                        },
+                    )
+                    .await
+                    .map_err(|status| {
+                        api_error_from_agent_status(&ctx, "instance.disk_usage", status)
+                    })?;
+
+                let free_known = resp.free_bytes > 0;
+                Ok(InstanceDiskUsageOutput {
+                    instance_id: resp.instance_id,
+                    total_bytes: resp.total_bytes.to_string(),
+                    world_bytes: resp.world_bytes.to_string(),
+                    logs_bytes: resp.logs_bytes.to_string(),
+                    backups_bytes: resp.backups_bytes.to_string(),
+                    cache_shared_bytes: resp.cache_shared_bytes.to_string(),
+                    truncated: resp.truncated,
+                    free_bytes: free_known.then(|| resp.free_bytes.to_string()),
+                    min_free_bytes: resp.min_free_bytes.to_string(),
+                    below_min_free: free_known
+                        && resp.min_free_bytes > 0
+                        && resp.free_bytes < resp.min_free_bytes,
+                })
+            }),
+        )
+        .procedure(
+            "deletePreview",
+            Procedure::builder::<ApiError>().query(|ctx, input: InstanceIdInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                let transport = instance_transport(&ctx, &input.instance_id).await?;
+                let resp: alloy_proto::agent_v1::DeleteInstancePreviewResponse = transport
+                    .call(
+                        "/alloy.agent.v1.InstanceService/DeletePreview",
+                        DeleteInstancePreviewRequest {
+                            instance_id: input.instance_id,
+                        },
+                    )
+                    .await
+                    .map_err(|status| {
+                        api_error_from_agent_status(&ctx, "instance.delete_preview", status)
+                    })?;
+
+                Ok(DeleteInstancePreviewOutput {
+                    instance_id: resp.instance_id,
+                    path: resp.path,
+                    size_bytes: resp.size_bytes.to_string(),
+                })
+            }),
+        )
+        .procedure(
+            "delete",
+            Procedure::builder::<ApiError>().mutation(|ctx, input: InstanceIdInput| async move {
+                ensure_instance_access(&ctx, &input.instance_id).await?;
+                ensure_writable(&ctx)?;
+                enforce_rate_limit(&ctx)?;
+
+                let instance_id = input.instance_id;
+                let transport = instance_transport(&ctx, &instance_id).await?;
+                let resp: alloy_proto::agent_v1::DeleteInstanceResponse = transport
+                    .call(
+                        "/alloy.agent.v1.InstanceService/Delete",
+                        DeleteInstanceRequest {
+                            instance_id: instance_id.clone(),
+                        },
+                    )
+                    .await
+                    .map_err(|status| {
+                        api_error_from_agent_status(&ctx, "instance.delete", status)
+                    })?;
+
+                if resp.ok {
+                    if let Err(err) = crate::instance_owners::forget(&ctx.db, &instance_id).await {
+                        tracing::warn!(%err, instance_id, "failed to forget instance owner");
+                    }
+                    if let Err(err) = crate::node_placement::forget(&ctx.db, &instance_id).await {
+                        tracing::warn!(%err, instance_id, "failed to forget instance placement");
+                    }
+                    audit::record(&ctx, "instance.delete", &instance_id, None).await;
+                }
+
+                Ok(DeleteInstanceOutput { ok: resp.ok })
+            }),
+        );
 
     let node = Router::new()
         .procedure(
@@ -3666,17 +5740,20 @@ pub fn router() -> Router<Ctx> {
                     .await
                     .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
 
+                let stats = alloy_db::entities::node_stats::Entity::find()
+                    .all(&*ctx.db)
+                    .await
+                    .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                let stats = stats
+                    .into_iter()
+                    .map(|s| (s.node.clone(), s))
+                    .collect::<HashMap<_, _>>();
+
                 Ok(rows
                     .into_iter()
-                    .map(|n| NodeDto {
-                        id: n.id.to_string(),
-                        name: n.name,
-                        endpoint: n.endpoint,
-                        has_connect_token: n.connect_token_hash.is_some(),
-                        enabled: n.enabled,
-                        last_seen_at: n.last_seen_at.map(|t| t.to_rfc3339()),
-                        agent_version: n.agent_version,
-                        last_error: n.last_error,
+                    .map(|n| {
+                        let st = stats.get(&n.name);
+                        map_node(n, st)
                     })
                     .collect::<Vec<_>>())
             }),
@@ -3737,6 +5814,7 @@ pub fn router() -> Router<Ctx> {
                         last_seen_at: Set(None),
                         agent_version: Set(None),
                         last_error: Set(None),
+                        tags: Set(String::new()),
                         created_at: Set(chrono::Utc::now().into()),
                         updated_at: Set(chrono::Utc::now().into()),
                     };
@@ -3749,16 +5827,62 @@ pub fn router() -> Router<Ctx> {
                     audit::record(&ctx, "node.create", &inserted.id.to_string(), None).await;
 
                     Ok(NodeCreateOutput {
-                        node: NodeDto {
-                            id: inserted.id.to_string(),
-                            name: inserted.name,
-                            endpoint: inserted.endpoint,
-                            has_connect_token: inserted.connect_token_hash.is_some(),
-                            enabled: inserted.enabled,
-                            last_seen_at: inserted.last_seen_at.map(|t| t.to_rfc3339()),
-                            agent_version: inserted.agent_version,
-                            last_error: inserted.last_error,
-                        },
+                        node: map_node(inserted, None),
+                        connect_token: token,
+                    })
+                },
+            ),
+        )
+        .procedure(
+            "rotateToken",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx: Ctx, input: NodeRotateTokenInput| async move {
+                    use alloy_db::entities::nodes;
+                    use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let user = ctx
+                        .user
+                        .clone()
+                        .ok_or_else(|| api_error(&ctx, "unauthorized", "unauthorized"))?;
+                    if !user.is_admin {
+                        return Err(api_error(&ctx, "forbidden", "forbidden"));
+                    }
+
+                    let id = sea_orm::prelude::Uuid::parse_str(&input.node_id)
+                        .map_err(|_| api_error(&ctx, "invalid_param", "invalid node_id"))?;
+
+                    let model = nodes::Entity::find_by_id(id)
+                        .one(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                        .ok_or_else(|| api_error(&ctx, "not_found", "node not found"))?;
+
+                    // The plaintext is only ever returned here; the DB keeps the hash.
+                    let token = random_token(32);
+                    let mut active: nodes::ActiveModel = model.into();
+                    active.connect_token_hash = Set(Some(hash_token(&token)));
+                    active.updated_at = Set(chrono::Utc::now().into());
+                    let updated = active
+                        .update(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+
+                    // Drop the live tunnel so the agent must reconnect with the new token.
+                    ctx.agent_hub.disconnect(&updated.name).await;
+
+                    audit::record(&ctx, "node.rotateToken", &updated.id.to_string(), None).await;
+
+                    let stats =
+                        alloy_db::entities::node_stats::Entity::find_by_id(updated.name.clone())
+                            .one(&*ctx.db)
+                            .await
+                            .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+
+                    Ok(NodeCreateOutput {
+                        node: map_node(updated, stats.as_ref()),
                         connect_token: token,
                     })
                 },
@@ -3806,18 +5930,196 @@ pub fn router() -> Router<Ctx> {
                     )
                     .await;
 
-                    Ok(NodeDto {
-                        id: updated.id.to_string(),
-                        name: updated.name,
-                        endpoint: updated.endpoint,
-                        has_connect_token: updated.connect_token_hash.is_some(),
-                        enabled: updated.enabled,
-                        last_seen_at: updated.last_seen_at.map(|t| t.to_rfc3339()),
-                        agent_version: updated.agent_version,
-                        last_error: updated.last_error,
+                    let stats =
+                        alloy_db::entities::node_stats::Entity::find_by_id(updated.name.clone())
+                            .one(&*ctx.db)
+                            .await
+                            .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+
+                    Ok(map_node(updated, stats.as_ref()))
+                },
+            ),
+        )
+        .procedure(
+            "setTags",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx: Ctx, input: NodeSetTagsInput| async move {
+                    use alloy_db::entities::nodes;
+                    use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let user = ctx
+                        .user
+                        .clone()
+                        .ok_or_else(|| api_error(&ctx, "unauthorized", "unauthorized"))?;
+                    if !user.is_admin {
+                        return Err(api_error(&ctx, "forbidden", "forbidden"));
+                    }
+
+                    let id = sea_orm::prelude::Uuid::parse_str(&input.node_id)
+                        .map_err(|_| api_error(&ctx, "invalid_param", "invalid node_id"))?;
+                    let tags = crate::node_placement::normalize_tags(&input.tags)
+                        .map_err(|msg| {
+                            api_error_with_field(&ctx, "invalid_param", msg.clone(), "tags", msg)
+                        })?;
+
+                    let model = nodes::Entity::find_by_id(id)
+                        .one(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                        .ok_or_else(|| api_error(&ctx, "not_found", "node not found"))?;
+
+                    let mut active: nodes::ActiveModel = model.into();
+                    active.tags = Set(crate::node_placement::format_tags(&tags));
+                    active.updated_at = Set(chrono::Utc::now().into());
+                    let updated = active
+                        .update(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+
+                    audit::record(
+                        &ctx,
+                        "node.setTags",
+                        &updated.id.to_string(),
+                        Some(serde_json::json!({ "tags": tags })),
+                    )
+                    .await;
+
+                    let stats =
+                        alloy_db::entities::node_stats::Entity::find_by_id(updated.name.clone())
+                            .one(&*ctx.db)
+                            .await
+                            .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+
+                    Ok(map_node(updated, stats.as_ref()))
+                },
+            ),
+        )
+        .procedure(
+            "prepareUpdate",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx: Ctx, input: NodePrepareUpdateInput| async move {
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+                    let node = admin_node_name(&ctx, &input.node_id).await?;
+
+                    let resp: alloy_proto::agent_v1::PrepareUpdateResponse = agent_transport(&ctx)
+                        .for_node(&node)
+                        .call(
+                            "/alloy.agent.v1.AgentHealthService/PrepareUpdate",
+                            PrepareUpdateRequest {
+                                stop_instances: input.stop_instances,
+                                timeout_ms: input.timeout_ms.unwrap_or(0),
+                                cancel: input.cancel,
+                            },
+                        )
+                        .await
+                        .map_err(|status| {
+                            api_error_from_agent_status(&ctx, "node.prepare_update", status)
+                        })?;
+
+                    audit::record(
+                        &ctx,
+                        "node.prepareUpdate",
+                        &input.node_id,
+                        Some(serde_json::json!({
+                            "node": node,
+                            "stop_instances": input.stop_instances,
+                            "cancel": input.cancel,
+                            "ready": resp.ready,
+                        })),
+                    )
+                    .await;
+
+                    Ok(NodePrepareUpdateOutput {
+                        draining: resp.draining,
+                        ready: resp.ready,
+                        running_process_ids: resp.running_process_ids,
+                        busy_process_ids: resp.busy_process_ids,
                     })
                 },
             ),
+        )
+        .procedure(
+            "applyUpdate",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx: Ctx, input: NodeApplyUpdateInput| async move {
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+                    let node = admin_node_name(&ctx, &input.node_id).await?;
+
+                    let resp: alloy_proto::agent_v1::ApplyUpdateResponse = agent_transport(&ctx)
+                        .for_node(&node)
+                        .call(
+                            "/alloy.agent.v1.AgentHealthService/ApplyUpdate",
+                            ApplyUpdateRequest {},
+                        )
+                        .await
+                        .map_err(|status| {
+                            api_error_from_agent_status(&ctx, "node.apply_update", status)
+                        })?;
+
+                    audit::record(
+                        &ctx,
+                        "node.applyUpdate",
+                        &input.node_id,
+                        Some(serde_json::json!({
+                            "node": node,
+                            "adoptable": resp.adoptable_process_ids.len(),
+                            "native": resp.native_process_ids.len(),
+                        })),
+                    )
+                    .await;
+
+                    Ok(NodeApplyUpdateOutput {
+                        adoptable_process_ids: resp.adoptable_process_ids,
+                        native_process_ids: resp.native_process_ids,
+                    })
+                },
+            ),
+        )
+        .procedure(
+            "resources",
+            Procedure::builder::<ApiError>().query(|ctx: Ctx, _: ()| async move {
+                let transport = agent_transport(&ctx);
+                let mut nodes = transport.connected_nodes().await;
+                nodes.sort_unstable();
+                let calls = nodes.iter().map(|node| {
+                    let t = transport.for_node(node);
+                    async move {
+                        t.call(
+                            "/alloy.agent.v1.AgentHealthService/GetNodeResources",
+                            GetNodeResourcesRequest {},
+                        )
+                        .await
+                    }
+                });
+                let results: Vec<Result<alloy_proto::agent_v1::GetNodeResourcesResponse, _>> =
+                    futures_util::future::join_all(calls).await;
+
+                let mut reports = Vec::new();
+                let mut unavailable = Vec::new();
+                for (node, res) in nodes.into_iter().zip(results) {
+                    match res {
+                        Ok(r) => reports.push((node_load_score(&r), node, r)),
+                        Err(status) => {
+                            tracing::warn!(%node, error = %status.message(), "node.resources failed");
+                            unavailable.push(node);
+                        }
+                    }
+                }
+                reports.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                Ok(NodeResourcesOutput {
+                    nodes: reports
+                        .into_iter()
+                        .map(|(_, node, r)| map_node_resources(node, r))
+                        .collect(),
+                    unavailable,
+                })
+            }),
         );
 
     let minecraft = Router::new().procedure(
@@ -3843,6 +6145,57 @@ pub fn router() -> Router<Ctx> {
                 settings_status_output(&ctx).await
             }),
         )
+        .procedure(
+            "nodeDefaultParams",
+            Procedure::builder::<ApiError>().query(
+                |ctx, input: NodeDefaultParamsInput| async move {
+                    let node = settings_node_name(&ctx, input.node).await;
+                    let params = crate::node_defaults::load(&ctx.db, &node)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    Ok(NodeDefaultParamsOutput {
+                        node,
+                        params: crate::node_defaults::redact(&params),
+                    })
+                },
+            ),
+        )
+        .procedure(
+            "setNodeDefaultParams",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx, input: SetNodeDefaultParamsInput| async move {
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let user = ctx
+                        .user
+                        .clone()
+                        .ok_or_else(|| api_error(&ctx, "unauthorized", "unauthorized"))?;
+                    if !user.is_admin {
+                        return Err(api_error(&ctx, "forbidden", "forbidden"));
+                    }
+
+                    crate::node_defaults::validate(&input.params)
+                        .map_err(|e| api_error(&ctx, "invalid_param", e))?;
+
+                    let node = settings_node_name(&ctx, input.node).await;
+                    let params = crate::node_defaults::save(&ctx.db, &node, input.params)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    let params = crate::node_defaults::redact(&params);
+
+                    audit::record(
+                        &ctx,
+                        "settings.setNodeDefaultParams",
+                        &node,
+                        Some(serde_json::json!({ "params": params })),
+                    )
+                    .await;
+
+                    Ok(NodeDefaultParamsOutput { node, params })
+                },
+            ),
+        )
         .procedure(
             "setDstDefaultKleiKey",
             Procedure::builder::<ApiError>().mutation(
@@ -3860,19 +6213,17 @@ pub fn router() -> Router<Ctx> {
 
                     let v = input.key.trim().to_string();
                     if v.is_empty() {
-                        setting_clear(&*ctx.db, SETTING_DST_DEFAULT_KLEI_KEY)
+                        setting_clear(&*ctx.db, settings::DST_DEFAULT_KLEI_KEY)
                             .await
                             .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
                     } else {
-                        setting_set_secret(&*ctx.db, SETTING_DST_DEFAULT_KLEI_KEY, &v)
-                            .await
-                            .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                        setting_write(&ctx, settings::DST_DEFAULT_KLEI_KEY, &v).await?;
                     }
 
                     audit::record(
                         &ctx,
                         "settings.setDstDefaultKleiKey",
-                        SETTING_DST_DEFAULT_KLEI_KEY,
+                        settings::DST_DEFAULT_KLEI_KEY,
                         None,
                     )
                     .await;
@@ -3898,24 +6249,92 @@ pub fn router() -> Router<Ctx> {
 
                     let v = input.key.trim().to_string();
                     if v.is_empty() {
-                        setting_clear(&*ctx.db, SETTING_CURSEFORGE_API_KEY)
+                        setting_clear(&*ctx.db, settings::CURSEFORGE_API_KEY)
                             .await
                             .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
                     } else {
-                        setting_set_secret(&*ctx.db, SETTING_CURSEFORGE_API_KEY, &v)
+                        setting_write(&ctx, settings::CURSEFORGE_API_KEY, &v).await?;
+                    }
+
+                    audit::record(
+                        &ctx,
+                        "settings.setCurseforgeApiKey",
+                        settings::CURSEFORGE_API_KEY,
+                        None,
+                    )
+                    .await;
+
+                    settings_status_output(&ctx).await
+                },
+            ),
+        )
+        .procedure(
+            "testCurseforgeKey",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx, input: TestCurseforgeKeyInput| async move {
+                    enforce_rate_limit(&ctx)?;
+
+                    let user = ctx
+                        .user
+                        .clone()
+                        .ok_or_else(|| api_error(&ctx, "unauthorized", "unauthorized"))?;
+                    if !user.is_admin {
+                        return Err(api_error(&ctx, "forbidden", "forbidden"));
+                    }
+                    if !RateLimiter::external_checks().allow(&rate_limit_key(&ctx)) {
+                        return Err(api_error(
+                            &ctx,
+                            "rate_limited",
+                            "too many key checks; try again in a minute",
+                        ));
+                    }
+
+                    let mut key = input.key.trim().to_string();
+                    if key.is_empty() {
+                        key = setting_get(&*ctx.db, settings::CURSEFORGE_API_KEY)
                             .await
-                            .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                            .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                            .map(|v| v.trim().to_string())
+                            .filter(|v| !v.is_empty())
+                            .ok_or_else(|| {
+                                api_error_with_field(
+                                    &ctx,
+                                    "invalid_param",
+                                    "no CurseForge API key to test",
+                                    "key",
+                                    "Paste a key, or save one first.",
+                                )
+                            })?;
                     }
 
-                    audit::record(
-                        &ctx,
-                        "settings.setCurseforgeApiKey",
-                        SETTING_CURSEFORGE_API_KEY,
-                        None,
-                    )
-                    .await;
+                    Ok(crate::curseforge::check_key(&key).await)
+                },
+            ),
+        )
+        .procedure(
+            "previewSteamGuard",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx, input: PreviewSteamGuardInput| async move {
+                    enforce_rate_limit(&ctx)?;
+
+                    let user = ctx
+                        .user
+                        .clone()
+                        .ok_or_else(|| api_error(&ctx, "unauthorized", "unauthorized"))?;
+                    if !user.is_admin {
+                        return Err(api_error(&ctx, "forbidden", "forbidden"));
+                    }
 
-                    settings_status_output(&ctx).await
+                    let now = chrono::Utc::now().timestamp();
+                    crate::steam_guard::preview(&input.mafile_or_secret, now).map_err(|e| {
+                        api_error_with_field(
+                            &ctx,
+                            "invalid_param",
+                            format!("invalid maFile: {e}"),
+                            "mafile_or_secret",
+                            "Paste a maFile JSON or a base64 shared_secret.",
+                        )
+                    })
                 },
             ),
         )
@@ -3951,8 +6370,8 @@ pub fn router() -> Router<Ctx> {
                         .map(str::trim)
                         .filter(|v| !v.is_empty())
                     {
-                        let (ma_account_name, ma_shared_secret) = parse_steam_mafile_or_secret(raw)
-                            .map_err(|e| {
+                        let (ma_account_name, ma_shared_secret) =
+                            crate::steam_guard::parse_mafile_or_secret(raw).map_err(|e| {
                                 api_error_with_field(
                                     &ctx,
                                     "invalid_param",
@@ -3967,19 +6386,10 @@ pub fn router() -> Router<Ctx> {
                         account_name = ma_account_name;
                     }
 
+                    // Checked before the agent login round-trip, not just at write time.
                     if let Some(secret) = shared_secret.as_deref() {
-                        use base64::Engine;
-                        base64::engine::general_purpose::STANDARD
-                            .decode(secret.as_bytes())
-                            .map_err(|_| {
-                                api_error_with_field(
-                                    &ctx,
-                                    "invalid_param",
-                                    "invalid shared_secret",
-                                    "shared_secret",
-                                    "shared_secret must be valid base64",
-                                )
-                            })?;
+                        settings::validate(settings::STEAMCMD_SHARED_SECRET, secret)
+                            .map_err(|e| invalid_setting(&ctx, e))?;
                     }
 
                     if username.is_empty() {
@@ -3997,16 +6407,16 @@ pub fn router() -> Router<Ctx> {
                         && account_name.is_none();
 
                     if clear_requested {
-                        setting_clear(&*ctx.db, SETTING_STEAMCMD_USERNAME)
+                        setting_clear(&*ctx.db, settings::STEAMCMD_USERNAME)
                             .await
                             .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
-                        setting_clear(&*ctx.db, SETTING_STEAMCMD_PASSWORD)
+                        setting_clear(&*ctx.db, settings::STEAMCMD_PASSWORD)
                             .await
                             .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
-                        setting_clear(&*ctx.db, SETTING_STEAMCMD_SHARED_SECRET)
+                        setting_clear(&*ctx.db, settings::STEAMCMD_SHARED_SECRET)
                             .await
                             .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
-                        setting_clear(&*ctx.db, SETTING_STEAMCMD_ACCOUNT_NAME)
+                        setting_clear(&*ctx.db, settings::STEAMCMD_ACCOUNT_NAME)
                             .await
                             .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
                     } else if username.is_empty() || password.is_empty() {
@@ -4019,7 +6429,7 @@ pub fn router() -> Router<Ctx> {
                         let guard_attempts: Vec<Option<String>> = if let Some(code) = steam_guard_code.clone() {
                             vec![Some(code)]
                         } else if let Some(secret) = shared_secret.as_deref() {
-                            generate_steam_guard_candidates(secret)
+                            crate::steam_guard::candidates(secret)
                                 .map_err(|e| {
                                     api_error_with_field(
                                         &ctx,
@@ -4091,21 +6501,13 @@ pub fn router() -> Router<Ctx> {
                             ));
                         }
 
-                        setting_set_secret(&*ctx.db, SETTING_STEAMCMD_USERNAME, &username)
-                            .await
-                            .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
-                        setting_set_secret(&*ctx.db, SETTING_STEAMCMD_PASSWORD, &password)
-                            .await
-                            .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                        setting_write(&ctx, settings::STEAMCMD_USERNAME, &username).await?;
+                        setting_write(&ctx, settings::STEAMCMD_PASSWORD, &password).await?;
 
                         if let Some(secret) = shared_secret.as_deref() {
-                            setting_set_secret(&*ctx.db, SETTING_STEAMCMD_SHARED_SECRET, secret)
-                                .await
-                                .map_err(|e| {
-                                    api_error(&ctx, "db_error", format!("db error: {e}"))
-                                })?;
+                            setting_write(&ctx, settings::STEAMCMD_SHARED_SECRET, secret).await?;
                         } else {
-                            setting_clear(&*ctx.db, SETTING_STEAMCMD_SHARED_SECRET)
+                            setting_clear(&*ctx.db, settings::STEAMCMD_SHARED_SECRET)
                                 .await
                                 .map_err(|e| {
                                     api_error(&ctx, "db_error", format!("db error: {e}"))
@@ -4116,17 +6518,9 @@ pub fn router() -> Router<Ctx> {
                             .map(|v| v.trim().to_string())
                             .filter(|v| !v.is_empty())
                         {
-                            setting_set_secret(&*ctx.db, SETTING_STEAMCMD_ACCOUNT_NAME, &name)
-                                .await
-                                .map_err(|e| {
-                                    api_error(&ctx, "db_error", format!("db error: {e}"))
-                                })?;
+                            setting_write(&ctx, settings::STEAMCMD_ACCOUNT_NAME, &name).await?;
                         } else {
-                            setting_set_secret(&*ctx.db, SETTING_STEAMCMD_ACCOUNT_NAME, &username)
-                                .await
-                                .map_err(|e| {
-                                    api_error(&ctx, "db_error", format!("db error: {e}"))
-                                })?;
+                            setting_write(&ctx, settings::STEAMCMD_ACCOUNT_NAME, &username).await?;
                         }
                     }
 
@@ -4554,6 +6948,424 @@ pub fn router() -> Router<Ctx> {
             ),
         );
 
+    let schedule = Router::new()
+        .procedure(
+            "list",
+            Procedure::builder::<ApiError>().query(
+                |ctx: Ctx, input: ScheduleListInput| async move {
+                    use alloy_db::entities::schedules;
+                    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+                    let mut query =
+                        schedules::Entity::find().order_by_asc(schedules::Column::CreatedAt);
+                    if let Some(instance_id) = input.instance_id {
+                        query = query.filter(schedules::Column::InstanceId.eq(instance_id));
+                    }
+                    let mut rows = query
+                        .all(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    if let (Some(user), Some(owned)) =
+                        (ctx.user.as_ref(), owned_instances(&ctx).await?)
+                    {
+                        crate::instance_owners::retain_visible(user, &owned, &mut rows, |s| {
+                            &s.instance_id
+                        });
+                    }
+                    Ok(rows.into_iter().map(schedule_dto).collect::<Vec<_>>())
+                },
+            ),
+        )
+        .procedure(
+            "create",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx: Ctx, input: ScheduleCreateInput| async move {
+                    use alloy_db::entities::schedules;
+                    use sea_orm::{ActiveModelTrait, Set};
+
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let instance_id = input.instance_id.trim().to_string();
+                    if instance_id.is_empty() {
+                        return Err(api_error_with_field(
+                            &ctx,
+                            "invalid_param",
+                            "instance_id is required",
+                            "instance_id",
+                            "required",
+                        ));
+                    }
+                    ensure_instance_access(&ctx, &instance_id).await?;
+                    let kind =
+                        crate::scheduler::ScheduleKind::parse(&input.kind).ok_or_else(|| {
+                            api_error_with_field(
+                                &ctx,
+                                "invalid_param",
+                                "kind must be restart or backup",
+                                "kind",
+                                "must be restart or backup",
+                            )
+                        })?;
+                    let cron = input.cron.trim().to_string();
+                    let next_run_at = schedule_next_run(&ctx, &cron)?;
+                    let node_id = instance_transport(&ctx, &instance_id)
+                        .await?
+                        .target_node()
+                        .await;
+
+                    let model = schedules::ActiveModel {
+                        id: Set(sea_orm::prelude::Uuid::new_v4()),
+                        node_id: Set(node_id),
+                        instance_id: Set(instance_id),
+                        kind: Set(kind.as_str().to_string()),
+                        cron: Set(cron),
+                        enabled: Set(true),
+                        last_run_at: Set(None),
+                        next_run_at: Set(next_run_at),
+                        created_at: Set(chrono::Utc::now().into()),
+                    };
+                    let inserted = model
+                        .insert(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+
+                    audit::record(
+                        &ctx,
+                        "schedule.create",
+                        &inserted.id.to_string(),
+                        Some(serde_json::json!({
+                            "instance_id": inserted.instance_id,
+                            "kind": inserted.kind,
+                            "cron": inserted.cron,
+                        })),
+                    )
+                    .await;
+
+                    Ok(schedule_dto(inserted))
+                },
+            ),
+        )
+        .procedure(
+            "delete",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx: Ctx, input: ScheduleDeleteInput| async move {
+                    use alloy_db::entities::schedules;
+                    use sea_orm::EntityTrait;
+
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let id = sea_orm::prelude::Uuid::parse_str(&input.id)
+                        .map_err(|_| api_error(&ctx, "invalid_param", "invalid id"))?;
+                    let model = schedules::Entity::find_by_id(id)
+                        .one(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                        .ok_or_else(|| api_error(&ctx, "not_found", "schedule not found"))?;
+                    ensure_instance_access(&ctx, &model.instance_id).await?;
+
+                    let rows = schedules::Entity::delete_by_id(id)
+                        .exec(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    if rows.rows_affected == 0 {
+                        return Err(api_error(&ctx, "not_found", "schedule not found"));
+                    }
+
+                    audit::record(&ctx, "schedule.delete", &id.to_string(), None).await;
+
+                    Ok(ScheduleDeleteOutput { ok: true })
+                },
+            ),
+        )
+        .procedure(
+            "toggle",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx: Ctx, input: ScheduleToggleInput| async move {
+                    use alloy_db::entities::schedules;
+                    use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let id = sea_orm::prelude::Uuid::parse_str(&input.id)
+                        .map_err(|_| api_error(&ctx, "invalid_param", "invalid id"))?;
+                    let model = schedules::Entity::find_by_id(id)
+                        .one(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                        .ok_or_else(|| api_error(&ctx, "not_found", "schedule not found"))?;
+                    ensure_instance_access(&ctx, &model.instance_id).await?;
+
+                    // Re-enabling starts from now, so runs missed while the
+                    // schedule was off are skipped rather than fired at once.
+                    let next_run_at = if input.enabled {
+                        schedule_next_run(&ctx, &model.cron)?
+                    } else {
+                        model.next_run_at
+                    };
+                    let mut active: schedules::ActiveModel = model.into();
+                    active.enabled = Set(input.enabled);
+                    active.next_run_at = Set(next_run_at);
+                    let updated = active
+                        .update(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+
+                    audit::record(
+                        &ctx,
+                        "schedule.toggle",
+                        &id.to_string(),
+                        Some(serde_json::json!({ "enabled": updated.enabled })),
+                    )
+                    .await;
+
+                    Ok(schedule_dto(updated))
+                },
+            ),
+        );
+
+    let api_token = Router::new()
+        .procedure(
+            "list",
+            Procedure::builder::<ApiError>().query(|ctx: Ctx, _: ()| async move {
+                use alloy_db::entities::api_tokens;
+                use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+                let user_id = ctx_user_id(&ctx)?;
+                let rows = api_tokens::Entity::find()
+                    .filter(api_tokens::Column::UserId.eq(user_id))
+                    .order_by_desc(api_tokens::Column::CreatedAt)
+                    .all(&*ctx.db)
+                    .await
+                    .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                Ok(rows.into_iter().map(api_token_dto).collect::<Vec<_>>())
+            }),
+        )
+        .procedure(
+            "create",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx: Ctx, input: ApiTokenCreateInput| async move {
+                    use alloy_db::entities::api_tokens;
+                    use sea_orm::{ActiveModelTrait, Set};
+
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let user_id = ctx_user_id(&ctx)?;
+                    let name = input.name.trim().to_string();
+                    if name.is_empty() || name.chars().count() > 64 {
+                        return Err(api_error_with_field(
+                            &ctx,
+                            "invalid_param",
+                            "name must be 1-64 characters",
+                            "name",
+                            "must be 1-64 characters",
+                        ));
+                    }
+
+                    let mut scopes = Vec::new();
+                    for raw in &input.scopes {
+                        let scope = crate::api_tokens::Scope::parse(raw).ok_or_else(|| {
+                            api_error_with_field(
+                                &ctx,
+                                "invalid_param",
+                                format!("unknown scope: {raw}"),
+                                "scopes",
+                                "must be read, write or admin",
+                            )
+                        })?;
+                        scopes.push(scope);
+                    }
+                    scopes.sort();
+                    scopes.dedup();
+                    if scopes.is_empty() {
+                        return Err(api_error_with_field(
+                            &ctx,
+                            "invalid_param",
+                            "at least one scope is required",
+                            "scopes",
+                            "required",
+                        ));
+                    }
+                    let role = ctx
+                        .user
+                        .as_ref()
+                        .map(|u| crate::roles::Role::parse(&u.role))
+                        .unwrap_or(crate::roles::Role::Viewer);
+                    if scopes.iter().any(|s| s.role() > role) {
+                        return Err(api_error_with_field(
+                            &ctx,
+                            "invalid_param",
+                            "scopes cannot exceed your role",
+                            "scopes",
+                            format!("your role is {}", role.as_str()),
+                        ));
+                    }
+
+                    let now = chrono::Utc::now();
+                    let expires_at: Option<sea_orm::prelude::DateTimeWithTimeZone> = input
+                        .expires_in_days
+                        .map(|d| (now + chrono::Duration::days(i64::from(d.max(1)))).into());
+                    let (token, token_hash) = crate::api_tokens::generate();
+                    let model = api_tokens::ActiveModel {
+                        id: Set(sea_orm::prelude::Uuid::new_v4()),
+                        user_id: Set(user_id),
+                        name: Set(name),
+                        token_hash: Set(token_hash),
+                        scopes: Set(crate::api_tokens::format_scopes(&scopes)),
+                        expires_at: Set(expires_at),
+                        last_used_at: Set(None),
+                        created_at: Set(now.into()),
+                    };
+                    let inserted = model
+                        .insert(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+
+                    audit::record(
+                        &ctx,
+                        "apiToken.create",
+                        &inserted.id.to_string(),
+                        Some(serde_json::json!({
+                            "name": inserted.name,
+                            "scopes": inserted.scopes,
+                        })),
+                    )
+                    .await;
+
+                    Ok(ApiTokenCreateOutput {
+                        token,
+                        info: api_token_dto(inserted),
+                    })
+                },
+            ),
+        )
+        .procedure(
+            "revoke",
+            Procedure::builder::<ApiError>().mutation(
+                |ctx: Ctx, input: ApiTokenRevokeInput| async move {
+                    use alloy_db::entities::api_tokens;
+                    use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+                    ensure_writable(&ctx)?;
+                    enforce_rate_limit(&ctx)?;
+
+                    let user_id = ctx_user_id(&ctx)?;
+                    let id = sea_orm::prelude::Uuid::parse_str(&input.id)
+                        .map_err(|_| api_error(&ctx, "invalid_param", "invalid id"))?;
+                    // Admins may revoke anyone's token; others only their own.
+                    let mut query =
+                        api_tokens::Entity::delete_many().filter(api_tokens::Column::Id.eq(id));
+                    if !ctx.user.as_ref().is_some_and(|u| u.is_admin) {
+                        query = query.filter(api_tokens::Column::UserId.eq(user_id));
+                    }
+                    let rows = query
+                        .exec(&*ctx.db)
+                        .await
+                        .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                    if rows.rows_affected == 0 {
+                        return Err(api_error(&ctx, "not_found", "api token not found"));
+                    }
+
+                    audit::record(&ctx, "apiToken.revoke", &id.to_string(), None).await;
+
+                    Ok(ApiTokenRevokeOutput { ok: true })
+                },
+            ),
+        );
+
+    let audit_log = Router::new().procedure(
+        "list",
+        Procedure::builder::<ApiError>().query(|ctx: Ctx, input: AuditListInput| async move {
+            use alloy_db::entities::users;
+            use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+            let is_admin = ctx.user.as_ref().is_some_and(|u| u.is_admin);
+            let user_id = if is_admin {
+                match input.actor.as_deref().map(str::trim) {
+                    None | Some("") => None,
+                    Some(name) => {
+                        let user = users::Entity::find()
+                            .filter(users::Column::Username.eq(name))
+                            .one(&*ctx.db)
+                            .await
+                            .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+                        let Some(user) = user else {
+                            return Ok(AuditListOutput {
+                                events: Vec::new(),
+                                next_cursor: None,
+                            });
+                        };
+                        Some(user.id)
+                    }
+                }
+            } else {
+                Some(ctx_user_id(&ctx)?)
+            };
+
+            let non_empty =
+                |v: Option<String>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+            let filter = audit::ListFilter {
+                user_id,
+                action: non_empty(input.action),
+                target: non_empty(input.instance_id),
+                from: parse_unix_ms_param(&ctx, "from_unix_ms", input.from_unix_ms.as_deref())?,
+                to: parse_unix_ms_param(&ctx, "to_unix_ms", input.to_unix_ms.as_deref())?,
+            };
+            let cursor = match input.cursor.as_deref().map(str::trim) {
+                None | Some("") => None,
+                Some(raw) => Some(
+                    audit::ListCursor::parse(raw)
+                        .ok_or_else(|| api_error(&ctx, "invalid_param", "invalid cursor"))?,
+                ),
+            };
+            let limit = input
+                .limit
+                .map(u64::from)
+                .unwrap_or(audit::LIST_PAGE_DEFAULT);
+
+            let rows = audit::list_query(&filter, cursor.as_ref(), limit)
+                .all(&*ctx.db)
+                .await
+                .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?;
+            let (rows, next) = audit::split_page(rows, limit);
+
+            let user_ids: Vec<_> = rows.iter().filter_map(|r| r.user_id).collect();
+            let names: HashMap<_, _> = if user_ids.is_empty() {
+                HashMap::new()
+            } else {
+                users::Entity::find()
+                    .filter(users::Column::Id.is_in(user_ids))
+                    .all(&*ctx.db)
+                    .await
+                    .map_err(|e| api_error(&ctx, "db_error", format!("db error: {e}")))?
+                    .into_iter()
+                    .map(|u| (u.id, u.username))
+                    .collect()
+            };
+
+            Ok(AuditListOutput {
+                events: rows
+                    .into_iter()
+                    .map(|r| AuditEventDto {
+                        id: r.id.to_string(),
+                        request_id: r.request_id,
+                        user_id: r.user_id.map(|id| id.to_string()),
+                        actor: r.user_id.and_then(|id| names.get(&id).cloned()),
+                        action: r.action,
+                        target: r.target,
+                        meta: r.meta.map(|m| m.to_string()),
+                        source: r.source,
+                        created_at: r.created_at.to_rfc3339(),
+                    })
+                    .collect(),
+                next_cursor: next.map(|c| c.encode()),
+            })
+        }),
+    );
+
     Router::new()
         .nest("control", control)
         .nest("agent", agent)
@@ -4562,8 +7374,11 @@ pub fn router() -> Router<Ctx> {
         .nest("settings", settings)
         .nest("update", update)
         .nest("frp", frp)
+        .nest("schedule", schedule)
         .nest("fs", fs)
         .nest("log", log)
         .nest("instance", instance)
         .nest("node", node)
+        .nest("apiToken", api_token)
+        .nest("audit", audit_log)
 }
@@ -0,0 +1,248 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use alloy_db::entities::schedules;
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use alloy_proto::agent_v1::{
+    CreateBackupRequest, CreateBackupResponse, RestartInstanceRequest, RestartInstanceResponse,
+};
+
+use crate::agent_transport::AgentTransport;
+
+const TICK: Duration = Duration::from_secs(15);
+const RESTART_TIMEOUT_MS: u32 = 30_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleKind {
+    Restart,
+    Backup,
+}
+
+impl ScheduleKind {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "restart" => Some(Self::Restart),
+            "backup" => Some(Self::Backup),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Restart => "restart",
+            Self::Backup => "backup",
+        }
+    }
+}
+
+/// Parses a crontab expression. The usual five fields (`0 4 * * *`) are
+/// accepted as well as the six/seven-field form with seconds (and years).
+pub fn parse_cron(expr: &str) -> Result<cron::Schedule, String> {
+    let fields = expr.split_whitespace().collect::<Vec<_>>();
+    let normalized = match fields.len() {
+        5 => format!("0 {}", fields.join(" ")),
+        6 | 7 => fields.join(" "),
+        n => return Err(format!("expected 5 cron fields, got {n}")),
+    };
+    cron::Schedule::from_str(&normalized).map_err(|e| format!("invalid cron expression: {e}"))
+}
+
+/// First fire time strictly after `after`, in UTC. None if the expression
+/// never fires again (e.g. a past year).
+pub fn next_fire_after(expr: &str, after: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, String> {
+    Ok(parse_cron(expr)?.after(&after).next())
+}
+
+/// Enabled schedules whose stored next-fire time has been reached.
+pub fn due_schedules(rows: &[schedules::Model], now: DateTime<Utc>) -> Vec<&schedules::Model> {
+    rows.iter()
+        .filter(|s| s.enabled && s.next_run_at.is_some_and(|at| at <= now))
+        .collect()
+}
+
+/// Fires due restart/backup schedules. Runs alongside `NodeHealthPoller`.
+#[derive(Clone)]
+pub struct Scheduler {
+    db: Arc<DatabaseConnection>,
+    hub: crate::agent_tunnel::AgentHub,
+}
+
+impl Scheduler {
+    pub fn new(db: Arc<DatabaseConnection>, hub: crate::agent_tunnel::AgentHub) -> Self {
+        Self { db, hub }
+    }
+
+    pub fn spawn(self, shutdown: CancellationToken) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                self.tick().await;
+                if !crate::shutdown::sleep(&shutdown, TICK).await {
+                    break;
+                }
+            }
+        })
+    }
+
+    async fn tick(&self) {
+        let rows = match schedules::Entity::find()
+            .filter(schedules::Column::Enabled.eq(true))
+            .all(&*self.db)
+            .await
+        {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::warn!(%err, "scheduler: failed to load schedules");
+                return;
+            }
+        };
+
+        let now = Utc::now();
+        for s in due_schedules(&rows, now) {
+            self.fire(s.clone(), now).await;
+        }
+    }
+
+    async fn fire(&self, s: schedules::Model, now: DateTime<Utc>) {
+        // Advance the stored next-fire time before dispatching, so a control
+        // restart mid-call cannot fire the same run twice. Runs missed while
+        // control was down collapse into this one.
+        let next = match next_fire_after(&s.cron, now) {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::warn!(schedule_id = %s.id, cron = %s.cron, %err, "scheduler: bad cron");
+                None
+            }
+        };
+        let mut active: schedules::ActiveModel = s.clone().into();
+        active.last_run_at = Set(Some(now.into()));
+        active.next_run_at = Set(next.map(Into::into));
+        if let Err(err) = active.update(&*self.db).await {
+            tracing::warn!(schedule_id = %s.id, %err, "scheduler: failed to advance schedule");
+            return;
+        }
+
+        let Some(kind) = ScheduleKind::parse(&s.kind) else {
+            tracing::warn!(schedule_id = %s.id, kind = %s.kind, "scheduler: unknown kind");
+            return;
+        };
+        // Resolved per run, so a schedule follows its instance across migrations.
+        let placed = match crate::node_placement::placed_node(&self.db, &s.instance_id).await {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::warn!(schedule_id = %s.id, %err, "scheduler: failed to load placement");
+                return;
+            }
+        };
+        let transport = AgentTransport::new(self.hub.clone());
+        let default_node = transport.target_node().await;
+        let transport = match crate::node_placement::pinned_node(placed.as_deref(), &default_node) {
+            Some(node) => transport.for_node(node),
+            None => transport,
+        };
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            let res = match kind {
+                ScheduleKind::Restart => transport
+                    .call::<_, RestartInstanceResponse>(
+                        "/alloy.agent.v1.InstanceService/Restart",
+                        RestartInstanceRequest {
+                            instance_id: s.instance_id.clone(),
+                            timeout_ms: RESTART_TIMEOUT_MS,
+                        },
+                    )
+                    .await
+                    .map(|_| None),
+                ScheduleKind::Backup => transport
+                    .call::<_, CreateBackupResponse>(
+                        "/alloy.agent.v1.InstanceService/CreateBackup",
+                        CreateBackupRequest {
+                            instance_id: s.instance_id.clone(),
+                            format: String::new(),
+                        },
+                    )
+                    .await
+                    .map(|resp| resp.backup),
+            };
+            let res = match res {
+                Ok(Some(backup)) => {
+                    crate::rpc::record_backup(&db, &s.instance_id, &backup).await;
+                    Ok(())
+                }
+                Ok(None) => Ok(()),
+                Err(status) => Err(status),
+            };
+            match res {
+                Ok(()) => tracing::info!(
+                    schedule_id = %s.id,
+                    instance_id = %s.instance_id,
+                    kind = kind.as_str(),
+                    "scheduler: fired"
+                ),
+                Err(status) => tracing::warn!(
+                    schedule_id = %s.id,
+                    instance_id = %s.instance_id,
+                    kind = kind.as_str(),
+                    error = %status.message(),
+                    "scheduler: dispatch failed"
+                ),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{due_schedules, next_fire_after};
+    use alloy_db::entities::schedules;
+    use chrono::{DateTime, TimeZone, Utc};
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    fn schedule(enabled: bool, next_run_at: Option<DateTime<Utc>>) -> schedules::Model {
+        schedules::Model {
+            id: sea_orm::prelude::Uuid::new_v4(),
+            node_id: "node".to_string(),
+            instance_id: "inst".to_string(),
+            kind: "restart".to_string(),
+            cron: "0 4 * * *".to_string(),
+            enabled,
+            last_run_at: None,
+            next_run_at: next_run_at.map(Into::into),
+            created_at: at(2026, 1, 1, 0, 0).into(),
+        }
+    }
+
+    #[test]
+    fn next_fire_is_computed_in_utc() {
+        let next = next_fire_after("0 4 * * *", at(2026, 3, 10, 4, 0)).unwrap();
+        assert_eq!(next, Some(at(2026, 3, 11, 4, 0)));
+        let next = next_fire_after("30 */6 * * *", at(2026, 3, 10, 7, 0)).unwrap();
+        assert_eq!(next, Some(at(2026, 3, 10, 12, 30)));
+        // Six-field form with seconds.
+        let next = next_fire_after("0 0 4 * * *", at(2026, 3, 10, 3, 0)).unwrap();
+        assert_eq!(next, Some(at(2026, 3, 10, 4, 0)));
+
+        assert!(next_fire_after("* * *", Utc::now()).is_err());
+        assert!(next_fire_after("0 25 * * *", Utc::now()).is_err());
+    }
+
+    #[test]
+    fn only_enabled_schedules_that_are_due_are_selected() {
+        let now = at(2026, 3, 10, 4, 0);
+        let rows = vec![
+            schedule(true, Some(now)),
+            schedule(true, Some(at(2026, 3, 10, 3, 0))),
+            schedule(true, Some(at(2026, 3, 10, 4, 1))),
+            schedule(false, Some(at(2026, 3, 10, 3, 0))),
+            schedule(true, None),
+        ];
+        let due = due_schedules(&rows, now);
+        assert_eq!(due.len(), 2);
+        assert!(due.iter().all(|s| s.id == rows[0].id || s.id == rows[1].id));
+    }
+}
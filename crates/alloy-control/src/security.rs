@@ -1,5 +1,6 @@
 use axum::{
     body::Body,
+    extract::State,
     http::{HeaderMap, HeaderValue, Method, Request, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
@@ -9,9 +10,14 @@ use rand::RngCore;
 use serde::Serialize;
 use tracing::Instrument;
 
+use crate::agent_transport::AgentVersionSlot;
+use crate::api_tokens;
 use crate::auth::{ACCESS_COOKIE_NAME, CSRF_COOKIE_NAME, validate_access_jwt};
+use crate::error_response;
 use crate::request_meta::RequestMeta;
-use crate::rpc::AuthUser;
+use crate::roles::{self, Role};
+use crate::rpc::{ApiError, AuthUser};
+use crate::state::AppState;
 
 const CSRF_HEADER_NAME: &str = "x-csrf-token";
 
@@ -113,34 +119,97 @@ pub async fn csrf_and_origin(req: Request<Body>, next: Next) -> Response {
     next.run(req).await
 }
 
-// Middleware: require a valid access JWT cookie for `/rspc` requests.
+fn forbidden(req: &Request<Body>, message: String, hint: Option<String>) -> Response {
+    let request_id = req
+        .extensions()
+        .get::<RequestMeta>()
+        .map(|m| m.request_id.clone())
+        .unwrap_or_default();
+    error_response::api_error_response(ApiError {
+        code: "forbidden".to_string(),
+        message,
+        request_id,
+        field_errors: std::collections::BTreeMap::new(),
+        hint,
+    })
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
+
+// Middleware: require a valid access JWT cookie or API token for `/rspc` requests.
 //
 // Allowlist a few public procedures so the UI can show health/version before login.
-pub async fn rspc_auth_guard(req: Request<Body>, next: Next) -> Response {
+// API tokens arrive in `Authorization: Bearer`, which browsers never attach on
+// their own, so token requests need no CSRF check.
+pub async fn rspc_auth_guard(
+    State(state): State<AppState>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
     // `/rspc/<procedure>` (v2 endpoint uses `/:id`).
     let path = req.uri().path();
-    let proc = path.strip_prefix('/').unwrap_or(path);
+    let proc = path.strip_prefix('/').unwrap_or(path).to_string();
 
     // Health endpoints should remain public.
-    if matches!(proc, "control.ping" | "agent.health") {
+    if matches!(proc.as_str(), "control.ping" | "agent.health") {
         return next.run(req).await;
     }
 
     let headers = req.headers();
-    let jar = CookieJar::from_headers(headers);
-    let token = match jar.get(ACCESS_COOKIE_NAME) {
-        Some(c) => c.value(),
-        None => return json_error(StatusCode::UNAUTHORIZED, "missing access token"),
+    let (user, scopes) = if let Some(raw) = bearer_token(headers) {
+        match api_tokens::authenticate(&state.db, raw).await {
+            Ok((user, scopes)) => (user, Some(scopes)),
+            Err(rejection) => return json_error(StatusCode::UNAUTHORIZED, rejection.message()),
+        }
+    } else {
+        let jar = CookieJar::from_headers(headers);
+        let token = match jar.get(ACCESS_COOKIE_NAME) {
+            Some(c) => c.value(),
+            None => return json_error(StatusCode::UNAUTHORIZED, "missing access token"),
+        };
+        match validate_access_jwt(token) {
+            Ok(u) => (
+                AuthUser {
+                    user_id: u.user_id,
+                    username: u.username,
+                    is_admin: u.is_admin,
+                    role: u.role,
+                },
+                None,
+            ),
+            Err(_) => return json_error(StatusCode::UNAUTHORIZED, "invalid access token"),
+        }
     };
 
-    let user = match validate_access_jwt(token) {
-        Ok(u) => AuthUser {
-            user_id: u.user_id,
-            username: u.username,
-            is_admin: u.is_admin,
-        },
-        Err(_) => return json_error(StatusCode::UNAUTHORIZED, "invalid access token"),
-    };
+    let role = Role::parse(&user.role);
+    let required = roles::required_role(&proc);
+    if role < required {
+        return forbidden(
+            &req,
+            format!("requires the {} role", required.as_str()),
+            Some(format!("signed in as {}", role.as_str())),
+        );
+    }
+    if let Some(scopes) = scopes
+        && !api_tokens::scopes_allow(&scopes, &proc)
+    {
+        return forbidden(
+            &req,
+            format!("api token scope does not allow {proc}"),
+            Some(format!(
+                "token scopes: {}",
+                api_tokens::format_scopes(&scopes)
+            )),
+        );
+    }
 
     let mut req = req;
     req.extensions_mut().insert(user);
@@ -148,6 +217,9 @@ pub async fn rspc_auth_guard(req: Request<Body>, next: Next) -> Response {
 }
 
 const REQUEST_ID_HEADER_NAME: &str = "x-request-id";
+const CONTROL_VERSION_HEADER_NAME: &str = "x-alloy-control-version";
+// The served agent's protocol, next to the control's own `x-alloy-protocol-version`.
+const AGENT_PROTOCOL_VERSION_HEADER_NAME: &str = "x-alloy-agent-protocol-version";
 
 fn generate_request_id() -> String {
     let mut bytes = [0u8; 16];
@@ -160,6 +232,8 @@ fn generate_request_id() -> String {
 // - If the client supplies `x-request-id`, keep it (best-effort).
 // - Otherwise generate one.
 // - Always echo `x-request-id` back in the response and expose it to handlers via extensions.
+// - Stamp control/protocol versions, plus the agent and its protocol version if an agent
+//   served the request.
 pub async fn request_id(mut req: Request<Body>, next: Next) -> Response {
     let rid = req
         .headers()
@@ -169,8 +243,10 @@ pub async fn request_id(mut req: Request<Body>, next: Next) -> Response {
         .filter(|s| !s.is_empty())
         .unwrap_or_else(generate_request_id);
 
+    let agent_version = AgentVersionSlot::default();
     req.extensions_mut().insert(RequestMeta {
         request_id: rid.clone(),
+        agent_version: agent_version.clone(),
     });
 
     let method = req.method().clone();
@@ -180,5 +256,29 @@ pub async fn request_id(mut req: Request<Body>, next: Next) -> Response {
     if let Ok(v) = HeaderValue::from_str(&rid) {
         resp.headers_mut().insert(REQUEST_ID_HEADER_NAME, v);
     }
+    let headers = resp.headers_mut();
+    headers.insert(
+        CONTROL_VERSION_HEADER_NAME,
+        HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+    );
+    headers.insert(
+        alloy_proto::PROTOCOL_VERSION_HEADER,
+        HeaderValue::from(alloy_proto::PROTOCOL_VERSION),
+    );
+    let served_by = agent_version
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .take();
+    if let Some(info) = served_by {
+        if let Ok(v) = HeaderValue::from_str(&info.agent_version) {
+            headers.insert(alloy_proto::AGENT_VERSION_HEADER, v);
+        }
+        if let Some(protocol) = info.protocol_version {
+            headers.insert(
+                AGENT_PROTOCOL_VERSION_HEADER_NAME,
+                HeaderValue::from(protocol),
+            );
+        }
+    }
     resp
 }
@@ -0,0 +1,176 @@
+use base64::Engine;
+
+pub const DST_DEFAULT_KLEI_KEY: &str = "dst.default_klei_key";
+pub const CURSEFORGE_API_KEY: &str = "minecraft.curseforge_api_key";
+pub const STEAMCMD_USERNAME: &str = "steamcmd.username";
+pub const STEAMCMD_PASSWORD: &str = "steamcmd.password";
+pub const STEAMCMD_SHARED_SECRET: &str = "steamcmd.shared_secret";
+pub const STEAMCMD_ACCOUNT_NAME: &str = "steamcmd.account_name";
+pub const DOWNLOAD_QUEUE_PAUSED: &str = "downloads.queue.paused";
+
+/// What a setting's stored string must parse as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKind {
+    /// Stored as `1` or `0`.
+    Bool,
+    /// Non-blank; stored trimmed.
+    NonEmpty,
+    /// Non-blank; stored verbatim, since whitespace may be part of it.
+    Password,
+    Base64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Setting {
+    pub key: &'static str,
+    pub kind: SettingKind,
+    pub secret: bool,
+    /// Input field the value comes from, for `field_errors`.
+    pub field: &'static str,
+}
+
+const SETTINGS: &[Setting] = &[
+    Setting {
+        key: DST_DEFAULT_KLEI_KEY,
+        kind: SettingKind::NonEmpty,
+        secret: true,
+        field: "key",
+    },
+    Setting {
+        key: CURSEFORGE_API_KEY,
+        kind: SettingKind::NonEmpty,
+        secret: true,
+        field: "key",
+    },
+    Setting {
+        key: STEAMCMD_USERNAME,
+        kind: SettingKind::NonEmpty,
+        secret: true,
+        field: "username",
+    },
+    Setting {
+        key: STEAMCMD_PASSWORD,
+        kind: SettingKind::Password,
+        secret: true,
+        field: "password",
+    },
+    Setting {
+        key: STEAMCMD_SHARED_SECRET,
+        kind: SettingKind::Base64,
+        secret: true,
+        field: "shared_secret",
+    },
+    Setting {
+        key: STEAMCMD_ACCOUNT_NAME,
+        kind: SettingKind::NonEmpty,
+        secret: true,
+        field: "account_name",
+    },
+    Setting {
+        key: DOWNLOAD_QUEUE_PAUSED,
+        kind: SettingKind::Bool,
+        secret: false,
+        field: "paused",
+    },
+];
+
+pub fn lookup(key: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|s| s.key == key)
+}
+
+/// Why a value was rejected; maps onto an `invalid_param` field error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSetting {
+    pub field: &'static str,
+    pub message: String,
+    pub hint: &'static str,
+}
+
+impl std::fmt::Display for InvalidSetting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for InvalidSetting {}
+
+/// Checks `value` against the schema for `key` and returns the string to store.
+pub fn validate(key: &str, value: &str) -> Result<String, InvalidSetting> {
+    let setting = lookup(key).ok_or_else(|| InvalidSetting {
+        field: "key",
+        message: format!("unknown setting: {key}"),
+        hint: "Use one of the documented setting keys.",
+    })?;
+    let invalid = |message: String, hint| InvalidSetting {
+        field: setting.field,
+        message,
+        hint,
+    };
+
+    match setting.kind {
+        SettingKind::Bool => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok("1".to_string()),
+            "0" | "false" | "no" | "off" => Ok("0".to_string()),
+            _ => Err(invalid(
+                format!("invalid {key}: expected a boolean"),
+                "Use true or false.",
+            )),
+        },
+        SettingKind::NonEmpty | SettingKind::Password if value.trim().is_empty() => Err(invalid(
+            format!("invalid {key}: must not be empty"),
+            "Enter a value, or clear the setting instead.",
+        )),
+        SettingKind::NonEmpty => Ok(value.trim().to_string()),
+        SettingKind::Password => Ok(value.to_string()),
+        SettingKind::Base64 => {
+            let value = value.trim();
+            match base64::engine::general_purpose::STANDARD.decode(value.as_bytes()) {
+                Ok(bytes) if !bytes.is_empty() => Ok(value.to_string()),
+                _ => Err(invalid(
+                    format!("invalid {key}: expected base64"),
+                    "shared_secret must be valid base64",
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DOWNLOAD_QUEUE_PAUSED, STEAMCMD_PASSWORD, STEAMCMD_SHARED_SECRET, STEAMCMD_USERNAME,
+        validate,
+    };
+
+    #[test]
+    fn booleans_are_normalized_and_junk_is_rejected() {
+        assert_eq!(validate(DOWNLOAD_QUEUE_PAUSED, " TRUE ").unwrap(), "1");
+        assert_eq!(validate(DOWNLOAD_QUEUE_PAUSED, "off").unwrap(), "0");
+
+        let err = validate(DOWNLOAD_QUEUE_PAUSED, "maybe").unwrap_err();
+        assert_eq!(err.field, "paused");
+        assert!(err.message.contains("boolean"));
+    }
+
+    #[test]
+    fn shared_secret_must_be_base64() {
+        let ok = validate(STEAMCMD_SHARED_SECRET, " c2VjcmV0MTIzNDU2Nzg5MA== ").unwrap();
+        assert_eq!(ok, "c2VjcmV0MTIzNDU2Nzg5MA==");
+
+        for bad in ["not base64!", "abc", ""] {
+            let err = validate(STEAMCMD_SHARED_SECRET, bad).unwrap_err();
+            assert_eq!(err.field, "shared_secret", "{bad}");
+        }
+    }
+
+    #[test]
+    fn strings_must_not_be_empty_and_unknown_keys_are_rejected() {
+        assert_eq!(validate(STEAMCMD_USERNAME, " alice ").unwrap(), "alice");
+        assert_eq!(validate(STEAMCMD_PASSWORD, " pw ").unwrap(), " pw ");
+        assert_eq!(
+            validate(STEAMCMD_USERNAME, "  ").unwrap_err().field,
+            "username"
+        );
+        assert_eq!(validate("nope", "1").unwrap_err().field, "key");
+    }
+}
@@ -0,0 +1,108 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+/// How long background workers get to finish their current unit of work
+/// once HTTP has drained. Anything still running is recovered on next boot.
+pub const GRACE: Duration = Duration::from_secs(30);
+
+/// Resolves on Ctrl-C or SIGTERM.
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::warn!(%err, "failed to listen for ctrl-c");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(err) => {
+                tracing::warn!(%err, "failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Runs `tick` back to back while it reports work, otherwise parks until
+/// `notify` fires. Returns once `shutdown` is cancelled; a tick that is
+/// already running is allowed to finish.
+pub async fn run_worker<F, Fut>(shutdown: &CancellationToken, notify: &Notify, mut tick: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    while !shutdown.is_cancelled() {
+        if tick().await {
+            continue;
+        }
+        tokio::select! {
+            _ = notify.notified() => {}
+            _ = shutdown.cancelled() => break,
+        }
+    }
+}
+
+/// Sleeps for `period`, waking early on shutdown. Returns `false` once
+/// shutdown was requested.
+pub async fn sleep(shutdown: &CancellationToken, period: Duration) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(period) => !shutdown.is_cancelled(),
+        _ = shutdown.cancelled() => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    use tokio::sync::Notify;
+    use tokio_util::sync::CancellationToken;
+
+    use super::run_worker;
+
+    #[tokio::test]
+    async fn worker_loop_exits_promptly_when_cancelled() {
+        let shutdown = CancellationToken::new();
+        let notify = Arc::new(Notify::new());
+        let ticks = Arc::new(AtomicU32::new(0));
+
+        let worker = tokio::spawn({
+            let (shutdown, notify, ticks) = (shutdown.clone(), notify.clone(), ticks.clone());
+            async move {
+                run_worker(&shutdown, &notify, || {
+                    let n = ticks.fetch_add(1, Ordering::SeqCst);
+                    // Two jobs queued, then idle.
+                    async move { n < 2 }
+                })
+                .await
+            }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), 3);
+
+        shutdown.cancel();
+        tokio::time::timeout(Duration::from_secs(1), worker)
+            .await
+            .expect("worker did not stop")
+            .unwrap();
+        assert_eq!(ticks.load(Ordering::SeqCst), 3);
+    }
+}
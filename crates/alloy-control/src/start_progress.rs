@@ -0,0 +1,96 @@
+use axum::{
+    Extension,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+
+use crate::agent_transport::AgentTransport;
+use crate::fs_download::http_status;
+use crate::instance_owners;
+use crate::rpc::AuthUser;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct StartProgressQuery {
+    pub process_id: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct StartProgressEventDto {
+    phase: String,
+    percent: Option<u32>,
+    message: String,
+    at_unix_ms: String,
+}
+
+fn event_dto(e: alloy_proto::agent_v1::StartProgressEvent) -> StartProgressEventDto {
+    StartProgressEventDto {
+        phase: e.phase().as_str_name().to_string(),
+        percent: e.has_percent.then_some(e.percent),
+        message: e.message,
+        at_unix_ms: e.at_unix_ms.to_string(),
+    }
+}
+
+/// `GET /stream/process.startProgress?process_id=`: relays the agent's
+/// SubscribeStart stream as server-sent `progress` events. The agent ends the
+/// stream once the process is Running or Failed.
+pub async fn start_progress(
+    State(state): State<AppState>,
+    user: Option<Extension<AuthUser>>,
+    Query(query): Query<StartProgressQuery>,
+) -> Response {
+    let Some(Extension(user)) = user else {
+        return (StatusCode::UNAUTHORIZED, "unauthorized").into_response();
+    };
+    let owner = match instance_owners::owner_of(&state.db, &query.process_id).await {
+        Ok(v) => v,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {e}")).into_response();
+        }
+    };
+    if !instance_owners::can_access(&user, owner) {
+        return (StatusCode::NOT_FOUND, "not found").into_response();
+    }
+
+    let placed = match crate::node_placement::placed_node(&state.db, &query.process_id).await {
+        Ok(v) => v,
+        Err(e) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("db error: {e}")).into_response();
+        }
+    };
+    let transport = AgentTransport::new(state.agent_hub.clone());
+    let default_node = transport.target_node().await;
+    let transport = match crate::node_placement::pinned_node(placed.as_deref(), &default_node) {
+        Some(node) => transport.for_node(node),
+        None => transport,
+    };
+    let stream = match transport
+        .server_stream::<_, alloy_proto::agent_v1::StartProgressEvent>(
+            "/alloy.agent.v1.ProcessService/SubscribeStart",
+            alloy_proto::agent_v1::SubscribeStartRequest {
+                process_id: query.process_id,
+            },
+        )
+        .await
+    {
+        Ok(v) => v,
+        Err(status) => {
+            return (http_status(status.code()), status.message().to_string()).into_response();
+        }
+    };
+
+    let events = stream.map(|item| match item {
+        Ok(e) => Event::default().event("progress").json_data(event_dto(e)),
+        Err(status) => Ok(Event::default().event("error").data(status.message())),
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
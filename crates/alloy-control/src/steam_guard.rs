@@ -0,0 +1,155 @@
+use specta::Type;
+
+const PERIOD_SECS: i64 = 30;
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct SteamMaFileRaw {
+    account_name: Option<String>,
+    shared_secret: Option<String>,
+}
+
+/// Accepts a Steam Desktop Authenticator maFile or a bare base64 shared secret
+/// and returns `(account_name, shared_secret)`.
+pub fn parse_mafile_or_secret(raw: &str) -> Result<(Option<String>, String), String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Err("empty input".to_string());
+    }
+
+    let (account_name, shared_secret) = if trimmed.starts_with('{') {
+        let parsed = serde_json::from_str::<SteamMaFileRaw>(trimmed)
+            .map_err(|e| format!("invalid maFile json: {e}"))?;
+        let account_name = parsed
+            .account_name
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+        let shared_secret = parsed
+            .shared_secret
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| "maFile missing shared_secret".to_string())?;
+        (account_name, shared_secret)
+    } else {
+        (None, trimmed.to_string())
+    };
+
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD
+        .decode(shared_secret.as_bytes())
+        .map_err(|_| "shared_secret is not valid base64".to_string())?;
+
+    Ok((account_name, shared_secret))
+}
+
+/// The 5-character Steam Guard code for the 30s window containing `unix_seconds`.
+pub fn code_at(shared_secret_b64: &str, unix_seconds: i64) -> Result<String, String> {
+    use base64::Engine;
+    use hmac::{Hmac, Mac};
+    use sha1::Sha1;
+
+    type HmacSha1 = Hmac<Sha1>;
+
+    let secret = base64::engine::general_purpose::STANDARD
+        .decode(shared_secret_b64.as_bytes())
+        .map_err(|_| "shared_secret is not valid base64".to_string())?;
+
+    let timestep = (unix_seconds.div_euclid(PERIOD_SECS)).max(0) as u64;
+    let msg = timestep.to_be_bytes();
+
+    let mut mac = HmacSha1::new_from_slice(&secret)
+        .map_err(|_| "failed to initialize HMAC with shared_secret".to_string())?;
+    mac.update(&msg);
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[19] & 0x0f) as usize;
+    let mut value = ((u32::from(digest[offset]) & 0x7f) << 24)
+        | (u32::from(digest[offset + 1]) << 16)
+        | (u32::from(digest[offset + 2]) << 8)
+        | u32::from(digest[offset + 3]);
+
+    const STEAM_GUARD_CHARS: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+    let mut out = String::with_capacity(5);
+    for _ in 0..5 {
+        out.push(STEAM_GUARD_CHARS[(value % 26) as usize] as char);
+        value /= 26;
+    }
+    Ok(out)
+}
+
+/// Codes for the current window and its neighbours, to absorb clock skew.
+pub fn candidates(shared_secret_b64: &str) -> Result<Vec<String>, String> {
+    let now = chrono::Utc::now().timestamp();
+    let mut out = Vec::<String>::new();
+    for delta in [0, -PERIOD_SECS, PERIOD_SECS] {
+        let code = code_at(shared_secret_b64, now + delta)?;
+        if !out.contains(&code) {
+            out.push(code);
+        }
+    }
+    Ok(out)
+}
+
+/// What `settings.previewSteamGuard` shows; nothing but the code is unmasked.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, Type)]
+pub struct SteamGuardPreview {
+    pub code: String,
+    pub seconds_remaining: u32,
+    pub account_name: Option<String>,
+}
+
+/// Validates `raw` like [`parse_mafile_or_secret`] and returns the code valid
+/// at `unix_seconds`.
+pub fn preview(raw: &str, unix_seconds: i64) -> Result<SteamGuardPreview, String> {
+    let (account_name, shared_secret) = parse_mafile_or_secret(raw)?;
+    Ok(SteamGuardPreview {
+        code: code_at(&shared_secret, unix_seconds)?,
+        seconds_remaining: (PERIOD_SECS - unix_seconds.rem_euclid(PERIOD_SECS)) as u32,
+        account_name: account_name.as_deref().map(mask),
+    })
+}
+
+/// Keeps the first and last character: `alice` -> `a***e`.
+fn mask(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    match chars.as_slice() {
+        [] => String::new(),
+        [_] | [_, _] => "***".to_string(),
+        [first, .., last] => format!("{first}***{last}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{code_at, mask, parse_mafile_or_secret, preview};
+
+    // base64("alloy-steam-guard-test")
+    const SECRET: &str = "YWxsb3ktc3RlYW0tZ3VhcmQtdGVzdA==";
+
+    #[test]
+    fn known_secret_and_time_produce_the_expected_code() {
+        assert_eq!(code_at(SECRET, 1_700_000_000).unwrap(), "DW6BP");
+        // 1_700_000_010 starts the next 30s window.
+        assert_eq!(code_at(SECRET, 1_700_000_029).unwrap(), "QMGV2");
+
+        let p = preview(SECRET, 1_700_000_000).unwrap();
+        assert_eq!(p.code, "DW6BP");
+        assert_eq!(p.seconds_remaining, 10);
+        assert_eq!(p.account_name, None);
+    }
+
+    #[test]
+    fn mafile_account_names_are_masked() {
+        let mafile = format!(r#"{{"account_name":"alice","shared_secret":"{SECRET}"}}"#);
+        let p = preview(&mafile, 1_700_000_000).unwrap();
+        assert_eq!(p.code, "DW6BP");
+        assert_eq!(p.account_name.as_deref(), Some("a***e"));
+        assert_eq!(mask("ab"), "***");
+    }
+
+    #[test]
+    fn invalid_secrets_are_rejected() {
+        assert!(parse_mafile_or_secret("not base64!").is_err());
+        assert!(preview(r#"{"account_name":"alice"}"#, 0).is_err());
+        assert!(preview("  ", 0).is_err());
+    }
+}
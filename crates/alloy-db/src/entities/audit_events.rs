@@ -10,6 +10,8 @@ pub struct Model {
     pub action: String,
     pub target: String,
     pub meta: Option<Json>,
+    // "control" for RPC-driven actions, "agent" for ones mirrored from a node.
+    pub source: String,
     pub created_at: DateTimeWithTimeZone,
 }
 
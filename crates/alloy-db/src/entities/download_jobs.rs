@@ -13,7 +13,13 @@ pub struct Model {
     pub message: String,
     pub request_id: Option<String>,
     pub queue_position: i64,
+    /// Higher runs sooner; `queue_position` orders jobs within a priority.
+    pub priority: i32,
     pub attempt_count: i32,
+    /// Attempts allowed before a retryable failure becomes terminal.
+    pub max_attempts: i32,
+    /// When a job waiting to be retried may start again.
+    pub next_attempt_at: Option<DateTimeWithTimeZone>,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
     pub started_at: Option<DateTimeWithTimeZone>,
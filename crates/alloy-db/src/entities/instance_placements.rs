@@ -0,0 +1,20 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "instance_placements")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub instance_id: String,
+    pub node: String,
+    pub migration_target: Option<String>,
+    pub migration_phase: Option<String>,
+    pub migration_export_id: Option<String>,
+    pub migration_export_size: Option<i64>,
+    pub migration_error: Option<String>,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
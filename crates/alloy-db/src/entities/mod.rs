@@ -1,7 +1,13 @@
+pub mod api_tokens;
 pub mod audit_events;
+pub mod backups;
 pub mod download_jobs;
 pub mod frp_nodes;
+pub mod instance_owners;
+pub mod instance_placements;
+pub mod node_stats;
 pub mod nodes;
 pub mod refresh_tokens;
+pub mod schedules;
 pub mod settings;
 pub mod users;
@@ -0,0 +1,20 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "node_stats")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub node: String,
+    pub starts_attempted: i32,
+    pub starts_succeeded: i32,
+    pub starts_failed: i32,
+    pub last_success_at: Option<DateTimeWithTimeZone>,
+    pub last_failure_at: Option<DateTimeWithTimeZone>,
+    pub last_failure_reason: Option<String>,
+    pub updated_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
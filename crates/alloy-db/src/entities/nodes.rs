@@ -12,6 +12,8 @@ pub struct Model {
     pub last_seen_at: Option<DateTimeWithTimeZone>,
     pub agent_version: Option<String>,
     pub last_error: Option<String>,
+    /// Comma-separated placement tags (`eu,ssd`).
+    pub tags: String,
     pub created_at: DateTimeWithTimeZone,
     pub updated_at: DateTimeWithTimeZone,
 }
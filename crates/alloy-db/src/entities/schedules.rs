@@ -0,0 +1,23 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "schedules")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: Uuid,
+    pub node_id: String,
+    pub instance_id: String,
+    // restart | backup
+    pub kind: String,
+    pub cron: String,
+    pub enabled: bool,
+    pub last_run_at: Option<DateTimeWithTimeZone>,
+    // Persisted so a control restart neither re-fires nor skips a due run.
+    pub next_run_at: Option<DateTimeWithTimeZone>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -8,6 +8,8 @@ pub struct Model {
     pub username: String,
     pub password_hash: String,
     pub is_admin: bool,
+    /// `viewer`, `operator` or `admin`; `is_admin` mirrors `admin`.
+    pub role: String,
     pub created_at: DateTimeWithTimeZone,
 }
 
@@ -9,6 +9,17 @@ mod m0006_create_settings;
 mod m0007_create_frp_nodes;
 mod m0008_add_frp_node_metadata;
 mod m0009_create_download_jobs;
+mod m0010_create_node_stats;
+mod m0011_create_instance_placements;
+mod m0012_create_backups;
+mod m0013_create_schedules;
+mod m0014_add_audit_event_source;
+mod m0015_add_user_role;
+mod m0016_create_instance_owners;
+mod m0017_create_api_tokens;
+mod m0018_add_node_tags;
+mod m0019_add_download_job_priority;
+mod m0020_add_download_job_retry;
 
 pub struct Migrator;
 
@@ -25,6 +36,17 @@ impl MigratorTrait for Migrator {
             Box::new(m0007_create_frp_nodes::Migration),
             Box::new(m0008_add_frp_node_metadata::Migration),
             Box::new(m0009_create_download_jobs::Migration),
+            Box::new(m0010_create_node_stats::Migration),
+            Box::new(m0011_create_instance_placements::Migration),
+            Box::new(m0012_create_backups::Migration),
+            Box::new(m0013_create_schedules::Migration),
+            Box::new(m0014_add_audit_event_source::Migration),
+            Box::new(m0015_add_user_role::Migration),
+            Box::new(m0016_create_instance_owners::Migration),
+            Box::new(m0017_create_api_tokens::Migration),
+            Box::new(m0018_add_node_tags::Migration),
+            Box::new(m0019_add_download_job_priority::Migration),
+            Box::new(m0020_add_download_job_retry::Migration),
         ]
     }
 }
@@ -0,0 +1,78 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NodeStats::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NodeStats::Node)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeStats::StartsAttempted)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(NodeStats::StartsSucceeded)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(NodeStats::StartsFailed)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(NodeStats::LastSuccessAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(NodeStats::LastFailureAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(ColumnDef::new(NodeStats::LastFailureReason).text().null())
+                    .col(
+                        ColumnDef::new(NodeStats::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NodeStats::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum NodeStats {
+    Table,
+    Node,
+    StartsAttempted,
+    StartsSucceeded,
+    StartsFailed,
+    LastSuccessAt,
+    LastFailureAt,
+    LastFailureReason,
+    UpdatedAt,
+}
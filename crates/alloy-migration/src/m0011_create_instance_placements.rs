@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(InstancePlacements::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(InstancePlacements::InstanceId)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(InstancePlacements::Node).string().not_null())
+                    .col(
+                        ColumnDef::new(InstancePlacements::MigrationTarget)
+                            .string()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(InstancePlacements::MigrationPhase)
+                            .string()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(InstancePlacements::MigrationExportId)
+                            .string()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(InstancePlacements::MigrationExportSize)
+                            .big_integer()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(InstancePlacements::MigrationError)
+                            .text()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(InstancePlacements::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(InstancePlacements::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum InstancePlacements {
+    Table,
+    InstanceId,
+    Node,
+    MigrationTarget,
+    MigrationPhase,
+    MigrationExportId,
+    MigrationExportSize,
+    MigrationError,
+    UpdatedAt,
+}
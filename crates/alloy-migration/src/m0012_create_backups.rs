@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Backups::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Backups::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Backups::InstanceId).string().not_null())
+                    .col(ColumnDef::new(Backups::BackupId).string().not_null())
+                    .col(ColumnDef::new(Backups::Format).string().not_null())
+                    .col(ColumnDef::new(Backups::SizeBytes).big_integer().not_null())
+                    .col(ColumnDef::new(Backups::Sha256).string().not_null())
+                    .col(
+                        ColumnDef::new(Backups::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_backups_instance_backup")
+                    .table(Backups::Table)
+                    .col(Backups::InstanceId)
+                    .col(Backups::BackupId)
+                    .unique()
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Backups::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Backups {
+    Table,
+    Id,
+    InstanceId,
+    BackupId,
+    Format,
+    SizeBytes,
+    Sha256,
+    CreatedAt,
+}
@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Schedules::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Schedules::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Schedules::NodeId).string().not_null())
+                    .col(ColumnDef::new(Schedules::InstanceId).string().not_null())
+                    .col(ColumnDef::new(Schedules::Kind).string().not_null())
+                    .col(ColumnDef::new(Schedules::Cron).string().not_null())
+                    .col(
+                        ColumnDef::new(Schedules::Enabled)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        ColumnDef::new(Schedules::LastRunAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(Schedules::NextRunAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(Schedules::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_schedules_instance_id")
+                    .table(Schedules::Table)
+                    .col(Schedules::InstanceId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Schedules::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Schedules {
+    Table,
+    Id,
+    NodeId,
+    InstanceId,
+    Kind,
+    Cron,
+    Enabled,
+    LastRunAt,
+    NextRunAt,
+    CreatedAt,
+}
@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(InstanceOwners::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(InstanceOwners::InstanceId)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(InstanceOwners::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(InstanceOwners::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_instance_owners_user_id")
+                    .table(InstanceOwners::Table)
+                    .col(InstanceOwners::UserId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(InstanceOwners::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum InstanceOwners {
+    Table,
+    InstanceId,
+    UserId,
+    CreatedAt,
+}
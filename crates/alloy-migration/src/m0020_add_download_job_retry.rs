@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DownloadJobs::Table)
+                    .add_column(
+                        ColumnDef::new(DownloadJobs::MaxAttempts)
+                            .integer()
+                            .not_null()
+                            .default(3),
+                    )
+                    .add_column(
+                        ColumnDef::new(DownloadJobs::NextAttemptAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DownloadJobs::Table)
+                    .drop_column(DownloadJobs::NextAttemptAt)
+                    .drop_column(DownloadJobs::MaxAttempts)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum DownloadJobs {
+    Table,
+    MaxAttempts,
+    NextAttemptAt,
+}
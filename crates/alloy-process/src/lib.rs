@@ -33,7 +33,11 @@ pub enum ProcessState {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Type)]
 pub struct ProcessResources {
     // CPU usage over the last sampling interval, in basis points (1/100 of a percent).
+    // Per core: a process using 4 cores reports 40000.
     pub cpu_percent_x100: u32,
+    // The same divided by the online core count, so 10000 is the whole machine.
+    #[serde(default)]
+    pub cpu_percent_normalized_x100: u32,
     // Resident set size in bytes (best-effort).
     pub rss_bytes: u64,
     // Best-effort IO totals.
@@ -41,6 +45,44 @@ pub struct ProcessResources {
     pub write_bytes: u64,
 }
 
+/// Player counts reported by the server itself (Minecraft Server List Ping).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Type)]
+pub struct PlayerCount {
+    pub online: u32,
+    pub max: u32,
+}
+
+/// Byte progress of the current install step (e.g. a download).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, Type)]
+pub struct ProgressInfo {
+    pub bytes_done: u64,
+    // 0 when the size is unknown.
+    pub bytes_total: u64,
+    pub phase: String,
+}
+
+/// Why a start failed, so callers can offer the matching remediation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Type)]
+pub enum FailCategory {
+    Download,
+    Java,
+    Disk,
+    Port,
+    Install,
+    Spawn,
+    Dependency,
+    Unknown,
+}
+
+/// What a failed server's console points to as the cause of the exit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, Type)]
+pub enum FailureReason {
+    OutOfMemory,
+    MixinFailure,
+    MissingDependency,
+    Crash,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Type)]
 pub struct ProcessStatus {
     pub id: ProcessId,
@@ -50,6 +92,19 @@ pub struct ProcessStatus {
     pub exit_code: Option<i32>,
     pub message: Option<String>,
     pub resources: Option<ProcessResources>,
+    // Set on Failed statuses produced by a failed start.
+    #[serde(default)]
+    pub fail_category: Option<FailCategory>,
+    // Set on Failed statuses whose console matched a known failure signature.
+    #[serde(default)]
+    pub failure_reason: Option<FailureReason>,
+    #[serde(default)]
+    pub failure_hint: Option<String>,
+    #[serde(default)]
+    pub players: Option<PlayerCount>,
+    // Set while a start is downloading or installing.
+    #[serde(default)]
+    pub progress: Option<ProgressInfo>,
 }
 
 #[cfg(test)]
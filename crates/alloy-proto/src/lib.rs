@@ -12,3 +12,23 @@ pub mod alloy {
 }
 
 pub use alloy::agent::v1 as agent_v1;
+
+/// Agent/control protocol version. Bump on incompatible RPC or tunnel changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Response metadata naming the agent build and protocol that served a call.
+pub const AGENT_VERSION_HEADER: &str = "x-alloy-agent-version";
+pub const PROTOCOL_VERSION_HEADER: &str = "x-alloy-protocol-version";
+
+/// Params whose key names a credential (password, token, API key, frp config, ...).
+///
+/// Shared so the agent and control redact the same keys.
+pub fn is_secret_param_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    key.contains("password")
+        || key.contains("token")
+        || key.contains("secret")
+        || key.contains("api_key")
+        || key.contains("apikey")
+        || (key.contains("frp") && key.contains("config"))
+}
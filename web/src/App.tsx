@@ -636,6 +636,17 @@ function App() {
   }
 
   const createInstance = rspc.createMutation(() => 'instance.create')
+  const [createNodeId, setCreateNodeId] = createSignal<string>('')
+  const placementNodes = rspc.createQuery(
+    () => ['node.list', null],
+    () => ({ enabled: isAuthed(), refetchOnWindowFocus: false }),
+  )
+  const createNodeOptions = createMemo(() => [
+    { value: '', label: 'Automatic', meta: 'first healthy node' },
+    ...(placementNodes.data ?? [])
+      .filter((n) => n.enabled)
+      .map((n) => ({ value: n.id, label: n.name, meta: n.tags.length > 0 ? n.tags.join(', ') : undefined })),
+  ])
   const updateInstance = rspc.createMutation(() => 'instance.update')
   const importSaveFromUrl = rspc.createMutation(() => 'instance.importSaveFromUrl')
   const startInstance = rspc.createMutation(() => 'instance.start')
@@ -753,6 +764,7 @@ function App() {
   const downloadQueueEnqueue = rspc.createMutation(() => 'process.downloadQueueEnqueue')
   const downloadQueueSetPaused = rspc.createMutation(() => 'process.downloadQueueSetPaused')
   const downloadQueueMove = rspc.createMutation(() => 'process.downloadQueueMove')
+  const downloadQueueMoveToFront = rspc.createMutation(() => 'process.downloadQueueMoveToFront')
   const downloadQueuePauseJob = rspc.createMutation(() => 'process.downloadQueuePauseJob')
   const downloadQueueResumeJob = rspc.createMutation(() => 'process.downloadQueueResumeJob')
   const downloadQueueCancelJob = rspc.createMutation(() => 'process.downloadQueueCancelJob')
@@ -761,7 +773,9 @@ function App() {
 
   const setDstDefaultKleiKey = rspc.createMutation(() => 'settings.setDstDefaultKleiKey')
   const setCurseforgeApiKey = rspc.createMutation(() => 'settings.setCurseforgeApiKey')
+  const testCurseforgeKey = rspc.createMutation(() => 'settings.testCurseforgeKey')
   const setSteamcmdCredentials = rspc.createMutation(() => 'settings.setSteamcmdCredentials')
+  const previewSteamGuard = rspc.createMutation(() => 'settings.previewSteamGuard')
 
   const [settingsDstKey, setSettingsDstKey] = createSignal('')
   const [settingsDstKeyVisible, setSettingsDstKeyVisible] = createSignal(false)
@@ -1247,6 +1261,15 @@ function App() {
     }
   }
 
+  async function moveDownloadJobToFront(jobId: string) {
+    try {
+      await downloadQueueMoveToFront.mutateAsync({ job_id: jobId })
+      await invalidateDownloadQueue()
+    } catch (e) {
+      toastError('Reorder failed', e)
+    }
+  }
+
   async function pauseDownloadJob(jobId: string) {
     try {
       await downloadQueuePauseJob.mutateAsync({ job_id: jobId })
@@ -1356,6 +1379,7 @@ function App() {
         template_id: req.templateId,
         version: req.version,
         params: req.params,
+        priority: null,
       })
       pushToast('info', 'Added to queue', `${downloadTargetLabel(target)} · ${req.version}`)
       await invalidateDownloadQueue()
@@ -1495,6 +1519,8 @@ function App() {
         process_id: canTailProcessLogs() ? (selectedInstanceId() ?? '') : '',
         cursor: processLogCursor(),
         limit: 400,
+        streams: null,
+        contains: null,
       },
     ],
     () => ({
@@ -1757,6 +1783,8 @@ function App() {
     createFieldErrors,
     createFormError,
     createInstance,
+    createNodeId,
+    createNodeOptions,
     createPreview,
     createTemplateId,
     dstAuthPort,
@@ -1837,6 +1865,7 @@ function App() {
     setCreateMcMemoryEl: (el: HTMLInputElement) => (createMcMemoryEl = el),
     setCreateMcMrpackEl: (el: HTMLInputElement) => (createMcMrpackEl = el),
     setCreateMcPortEl: (el: HTMLInputElement) => (createMcPortEl = el),
+    setCreateNodeId,
     setCreateSleepSecondsEl: (el: HTMLInputElement) => (createSleepSecondsEl = el),
     setCreateTrFrpConfigEl: (el: HTMLTextAreaElement) => (createTrFrpConfigEl = el),
     setCreateTrFrpNodeEl: (el: HTMLDivElement) => (createTrFrpNodeEl = el),
@@ -1926,6 +1955,7 @@ function App() {
     toggleDownloadQueuePaused,
     clearDownloadHistory,
     moveDownloadJob,
+    moveDownloadJobToFront,
     pauseDownloadJob,
     resumeDownloadJob,
     cancelDownloadJob,
@@ -1981,6 +2011,7 @@ function App() {
     setSettingsCurseforgeKey,
     setSettingsCurseforgeKeyVisible,
     setCurseforgeApiKey,
+    testCurseforgeKey,
     settingsSteamcmdUsername,
     setSettingsSteamcmdUsername,
     settingsSteamcmdPasswordVisible,
@@ -1992,6 +2023,7 @@ function App() {
     settingsSteamcmdMaFile,
     setSettingsSteamcmdMaFile,
     setSteamcmdCredentials,
+    previewSteamGuard,
     updateCheck,
     triggerUpdate,
     controlDiagnostics,
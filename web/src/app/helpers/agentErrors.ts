@@ -33,5 +33,5 @@ export function statusMessageParts(status: ProcessStatusDto | null): { text: str
   const raw = status?.message ?? null
   const payload = parseAgentErrorPayload(raw)
   if (payload) return { text: payload.message, code: payload.code, hint: payload.hint ?? null }
-  return { text: raw, code: null, hint: null }
+  return { text: raw, code: null, hint: status?.failure_hint ?? null }
 }
@@ -149,7 +149,7 @@ export function buildCreatePreview(input: BuildCreatePreviewInput): CreatePrevie
     }
 
     if (!input.mcEula) warnings.push('Accept the Minecraft EULA to start.')
-    if (!src) warnings.push('Paste a Modrinth version link or a direct .mrpack URL.')
+    if (!src) warnings.push('Paste a Modrinth modpack link, slug, or a direct .mrpack URL.')
   }
 
   if (template_id === 'minecraft:import') {
@@ -187,7 +187,7 @@ export function buildCreatePreview(input: BuildCreatePreviewInput): CreatePrevie
     }
 
     if (!input.mcEula) warnings.push('Accept the Minecraft EULA to start.')
-    if (!src) warnings.push('Paste a CurseForge file URL, or modId:fileId.')
+    if (!src) warnings.push('Paste a CurseForge modpack URL, slug, or modId:fileId.')
     if (!input.curseforgeApiKeySet) warnings.push('CurseForge API key is not configured (Settings).')
   }
 
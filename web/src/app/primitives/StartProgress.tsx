@@ -1,9 +1,15 @@
 import { For, Show } from 'solid-js'
+import type { ProgressInfoDto } from '../../bindings'
 import { startProgressIndex, startProgressSteps } from '../helpers/instances'
 
-export function StartProgress(props: { templateId: string; message: string }) {
+export function StartProgress(props: { templateId: string; message: string; progress?: ProgressInfoDto | null }) {
   const steps = () => startProgressSteps(props.templateId)
   const active = () => startProgressIndex(props.templateId, props.message)
+  const percent = () => {
+    const total = Number(props.progress?.bytes_total ?? 0)
+    if (!total) return null
+    return Math.min(100, (Number(props.progress?.bytes_done ?? 0) / total) * 100)
+  }
   return (
     <div class="mt-2 rounded-xl border border-slate-200 bg-white/60 px-3 py-2 text-[11px] text-slate-600 dark:border-slate-800 dark:bg-slate-950/40 dark:text-slate-300">
       <div class="flex flex-wrap items-center gap-2">
@@ -38,6 +44,14 @@ export function StartProgress(props: { templateId: string; message: string }) {
           {props.message}
         </span>
       </div>
+
+      <Show when={percent()}>
+        {(pct) => (
+          <div class="mt-1.5 h-1 overflow-hidden rounded-full bg-slate-200 dark:bg-slate-800">
+            <div class="h-full rounded-full bg-amber-400 transition-all duration-300" style={{ width: `${pct()}%` }} aria-hidden="true" />
+          </div>
+        )}
+      </Show>
     </div>
   )
 }
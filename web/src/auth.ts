@@ -39,6 +39,7 @@ export type WhoamiResponse = {
   user_id: string
   username: string
   is_admin: boolean
+  role: string
 }
 
 export type LoginRequest = {
@@ -4,13 +4,25 @@
 
 export type AgentHealthFullDto = { endpoint: string; ok: boolean; status: string | null; agent_version: string | null; data_root: string | null; data_root_writable: boolean | null; data_root_free_bytes: string | null; ports: PortAvailabilityDto[] | null; error: string | null }
 
-export type CacheEntryDto = { key: string; path: string; size_bytes: string; last_used_unix_ms: string }
+export type ApiTokenDto = { id: string; name: string; scopes: string[]; expires_at: string | null; last_used_at: string | null; created_at: string }
 
-export type CacheStatsOutput = { entries: CacheEntryDto[] }
+export type AuditEventDto = { id: string; request_id: string; user_id: string | null; actor: string | null; action: string; target: string; meta: string | null; source: string; created_at: string }
 
-export type DirEntryDto = { name: string; is_dir: boolean; size_bytes: number; modified_unix_ms: string }
+export type BackupRecordDto = { backup_id: string; format: string; size_bytes: string; sha256: string; created_at_unix_ms: string }
 
-export type DownloadQueueJobDto = { id: string; target: string; template_id: string; version: string; params: Partial<{ [key in string]: string }>; state: string; message: string; request_id: string | null; queue_position: string; attempt_count: number; created_at_unix_ms: string; started_at_unix_ms: string | null; updated_at_unix_ms: string; finished_at_unix_ms: string | null; progress_stage: string | null; progress_downloaded_bytes: string | null; progress_total_bytes: string | null; progress_speed_bytes_per_sec: string | null; progress_percent_x100: number | null; progress_eta_sec: number | null }
+export type BulkApplyParamsResultDto = { instance_id: string; ok: boolean; error: string | null; params: Partial<{ [key in string]: string }>; restart_pending: boolean; restart_scheduled: boolean }
+
+export type CacheCategoryDto = { name: string; size_bytes: string; item_count: number }
+
+export type CacheEntryDto = { key: string; path: string; size_bytes: string; last_used_unix_ms: string; in_use: boolean }
+
+export type CacheStatsOutput = { entries: CacheEntryDto[]; categories: CacheCategoryDto[]; max_bytes: string | null }
+
+export type CurseforgeKeyCheck = { ok: boolean; detail: string }
+
+export type DirEntryDto = { name: string; path: string; is_dir: boolean; size_bytes: number; modified_unix_ms: string }
+
+export type DownloadQueueJobDto = { id: string; target: string; template_id: string; version: string; params: Partial<{ [key in string]: string }>; state: string; message: string; request_id: string | null; queue_position: string; priority: number; attempt_count: number; max_attempts: number; next_attempt_at_unix_ms: string | null; created_at_unix_ms: string; started_at_unix_ms: string | null; updated_at_unix_ms: string; finished_at_unix_ms: string | null; progress_stage: string | null; progress_downloaded_bytes: string | null; progress_total_bytes: string | null; progress_speed_bytes_per_sec: string | null; progress_percent_x100: number | null; progress_eta_sec: number | null }
 
 export type FsCapabilitiesOutput = { write_enabled: boolean }
 
@@ -18,17 +30,28 @@ export type InstanceConfigDto = { instance_id: string; template_id: string; para
 
 export type MinecraftVersionRef = { id: string; kind: string; release_time: string }
 
-export type NodeDto = { id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null }
+export type NodeDto = { id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null; tags: string[]; starts_attempted: number; starts_succeeded: number; starts_failed: number; start_failure_rate_x100: number | null; last_start_success_at: string | null; last_start_failure_reason: string | null }
+
+export type NodeResourcesDto = { node: string; cpu_count: number; total_mem_bytes: string | null; available_mem_bytes: string | null; load_avg_1m: number | null; load_avg_5m: number | null; load_avg_15m: number | null; data_root_free_bytes: string | null; running_process_count: number }
 
 export type ParamTypeDto = "String" | "Int" | "Bool"
 
 export type PortAvailabilityDto = { port: number; available: boolean; error: string | null }
 
-export type ProceduresLegacy = { queries: { key: "agent.health"; input: null; result: { status: string; agent_version: string } } | { key: "control.diagnostics"; input: null; result: { fetched_at_unix_ms: string; request_id: string; control_version: string; read_only: boolean; agent: AgentHealthFullDto; fs: FsCapabilitiesOutput; cache: CacheStatsOutput; agent_log_path: string | null; agent_log_lines: string[] } } | { key: "control.ping"; input: null; result: { status: string; version: string } } | { key: "frp.list"; input: null; result: ({ id: string; name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string; latency_ms: number | null; created_at: string; updated_at: string })[] } | { key: "fs.capabilities"; input: null; result: { write_enabled: boolean } } | { key: "fs.listDir"; input: { path: string | null }; result: { entries: DirEntryDto[] } } | { key: "fs.readFile"; input: { path: string; offset: number | null; limit: number | null }; result: { text: string; size_bytes: number } } | { key: "instance.deletePreview"; input: { instance_id: string }; result: { instance_id: string; path: string; size_bytes: string } } | { key: "instance.get"; input: { instance_id: string }; result: { config: InstanceConfigDto; status: ProcessStatusDto | null } } | { key: "instance.list"; input: null; result: ({ config: InstanceConfigDto; status: ProcessStatusDto | null })[] } | { key: "log.tailFile"; input: { path: string; cursor: string | null; limit_bytes: number | null; max_lines: number | null }; result: { lines: string[]; next_cursor: string } } | { key: "minecraft.versions"; input: null; result: { latest_release: string; latest_snapshot: string; versions: MinecraftVersionRef[] } } | { key: "node.list"; input: null; result: ({ id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null })[] } | { key: "process.cacheStats"; input: null; result: { entries: CacheEntryDto[] } } | { key: "process.downloadQueue"; input: null; result: { queue_paused: boolean; jobs: DownloadQueueJobDto[] } } | { key: "process.list"; input: null; result: ({ process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null })[] } | { key: "process.logsTail"; input: { process_id: string; cursor: string | null; limit: number | null }; result: { lines: string[]; next_cursor: string } } | { key: "process.status"; input: { process_id: string }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null } } | { key: "process.templates"; input: null; result: { template_id: string; display_name: string; params: TemplateParamDto[] }[] } | { key: "settings.status"; input: null; result: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null } } | { key: "update.check"; input: null; result: { current_version: string; latest: UpdateLatestReleaseDto | null; update_available: boolean; can_trigger_update: boolean } }; mutations: { key: "frp.create"; input: { name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string }; result: { id: string; name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string; latency_ms: number | null; created_at: string; updated_at: string } } | { key: "frp.delete"; input: { id: string }; result: { ok: boolean } } | { key: "frp.update"; input: { id: string; name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string }; result: { id: string; name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string; latency_ms: number | null; created_at: string; updated_at: string } } | { key: "instance.create"; input: { template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null }; result: { instance_id: string; template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null } } | { key: "instance.delete"; input: { instance_id: string }; result: { ok: boolean } } | { key: "instance.diagnostics"; input: { instance_id: string; max_lines: number | null; limit_bytes: number | null }; result: { instance_id: string; fetched_at_unix_ms: string; request_id: string; instance_json: string | null; run_json: string | null; console_log_lines: string[] } } | { key: "instance.importSaveFromUrl"; input: { instance_id: string; url: string }; result: { ok: boolean; message: string; installed_path: string; backup_path: string } } | { key: "instance.restart"; input: { instance_id: string; timeout_ms: number | null }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null } } | { key: "instance.start"; input: { instance_id: string }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null } } | { key: "instance.stop"; input: { instance_id: string; timeout_ms: number | null }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null } } | { key: "instance.update"; input: { instance_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null }; result: { instance_id: string; template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null } } | { key: "node.create"; input: { name: string }; result: { node: NodeDto; connect_token: string } } | { key: "node.setEnabled"; input: { node_id: string; enabled: boolean }; result: { id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null } } | { key: "process.clearCache"; input: { keys: string[] }; result: { ok: boolean; freed_bytes: string; cleared: CacheEntryDto[] } } | { key: "process.downloadQueueCancelJob"; input: { job_id: string }; result: { ok: boolean } } | { key: "process.downloadQueueClearHistory"; input: null; result: { ok: boolean } } | { key: "process.downloadQueueEnqueue"; input: { target: string; template_id: string; version: string; params: Partial<{ [key in string]: string }> }; result: { ok: boolean } } | { key: "process.downloadQueueMove"; input: { job_id: string; direction: number }; result: { ok: boolean } } | { key: "process.downloadQueuePauseJob"; input: { job_id: string }; result: { ok: boolean } } | { key: "process.downloadQueueResumeJob"; input: { job_id: string }; result: { ok: boolean } } | { key: "process.downloadQueueRetryJob"; input: { job_id: string }; result: { ok: boolean } } | { key: "process.downloadQueueSetPaused"; input: { paused: boolean }; result: { ok: boolean } } | { key: "process.start"; input: { template_id: string; params: Partial<{ [key in string]: string }> }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null } } | { key: "process.stop"; input: { process_id: string; timeout_ms: number | null }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null } } | { key: "process.warmCache"; input: { template_id: string; params: Partial<{ [key in string]: string }> }; result: { ok: boolean; message: string } } | { key: "settings.setCurseforgeApiKey"; input: { key: string }; result: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null } } | { key: "settings.setDstDefaultKleiKey"; input: { key: string }; result: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null } } | { key: "settings.setSteamcmdCredentials"; input: { username: string; password: string; steam_guard_code: string | null; shared_secret: string | null; mafile_json: string | null }; result: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null } } | { key: "update.trigger"; input: null; result: { ok: boolean; message: string } }; subscriptions: never }
+export type ProceduresLegacy = { queries: { key: "agent.health"; input: null; result: { status: string; agent_version: string } } | { key: "apiToken.list"; input: null; result: ({ id: string; name: string; scopes: string[]; expires_at: string | null; last_used_at: string | null; created_at: string })[] } | { key: "audit.list"; input: { actor: string | null; action: string | null; instance_id: string | null; from_unix_ms: string | null; to_unix_ms: string | null; cursor: string | null; limit: number | null }; result: { events: AuditEventDto[]; next_cursor: string | null } } | { key: "control.diagnostics"; input: null; result: { fetched_at_unix_ms: string; request_id: string; control_version: string; read_only: boolean; agent: AgentHealthFullDto; fs: FsCapabilitiesOutput; cache: CacheStatsOutput; agent_log_path: string | null; agent_log_lines: string[] } } | { key: "control.ping"; input: null; result: { status: string; version: string } } | { key: "frp.list"; input: null; result: ({ id: string; name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string; latency_ms: number | null; created_at: string; updated_at: string })[] } | { key: "fs.capabilities"; input: null; result: { write_enabled: boolean } } | { key: "fs.listDir"; input: { path: string | null; recursive: boolean | null; max_entries: number | null; cursor: string | null }; result: { entries: DirEntryDto[]; next_cursor: string | null } } | { key: "fs.readFile"; input: { path: string; offset: number | null; limit: number | null }; result: { text: string; size_bytes: number } } | { key: "instance.deletePreview"; input: { instance_id: string }; result: { instance_id: string; path: string; size_bytes: string } } | { key: "instance.diskUsage"; input: { instance_id: string }; result: { instance_id: string; total_bytes: string; world_bytes: string; logs_bytes: string; backups_bytes: string; cache_shared_bytes: string; truncated: boolean; free_bytes: string | null; min_free_bytes: string; below_min_free: boolean } } | { key: "instance.get"; input: { instance_id: string }; result: { config: InstanceConfigDto; status: ProcessStatusDto | null } } | { key: "instance.inspect"; input: { instance_id: string }; result: { process_id: string; template_id: string; started_at_unix_ms: string; agent_version: string; pid: number | null; container_name: string | null; container_id: string | null; exec: string; args: string[]; cwd: string; params: Partial<{ [key in string]: string }>; env: Partial<{ [key in string]: string }> } } | { key: "instance.list"; input: null; result: ({ config: InstanceConfigDto; status: ProcessStatusDto | null })[] } | { key: "instance.listBackups"; input: { instance_id: string }; result: { backups: BackupRecordDto[] } } | { key: "instance.stagedUpgrade"; input: { instance_id: string }; result: { upgrade: StagedUpgradeDto | null } } | { key: "log.tailFile"; input: { path: string; cursor: string | null; limit_bytes: number | null; max_lines: number | null }; result: { lines: string[]; next_cursor: string } } | { key: "minecraft.versions"; input: null; result: { latest_release: string; latest_snapshot: string; versions: MinecraftVersionRef[] } } | { key: "node.list"; input: null; result: ({ id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null; tags: string[]; starts_attempted: number; starts_succeeded: number; starts_failed: number; start_failure_rate_x100: number | null; last_start_success_at: string | null; last_start_failure_reason: string | null })[] } | { key: "node.resources"; input: null; result: { nodes: NodeResourcesDto[]; unavailable: string[] } } | { key: "process.cacheStats"; input: null; result: { entries: CacheEntryDto[]; categories: CacheCategoryDto[]; max_bytes: string | null } } | { key: "process.downloadQueue"; input: null; result: { queue_paused: boolean; jobs: DownloadQueueJobDto[] } } | { key: "process.list"; input: null; result: ({ process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null })[] } | { key: "process.logsTail"; input: { process_id: string; cursor: string | null; limit: number | null; streams: string[] | null; contains: string | null }; result: { lines: string[]; next_cursor: string } } | { key: "process.resourceHistory"; input: { process_id: string; since_unix_ms: string | null }; result: { samples: ResourceSampleDto[]; sample_interval_ms: number } } | { key: "process.status"; input: { process_id: string }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null } } | { key: "process.templates"; input: null; result: { template_id: string; display_name: string; params: TemplateParamDto[] }[] } | { key: "process.validateTemplate"; input: { template_id: string; params: Partial<{ [key in string]: string }> }; result: { ok: boolean; field_errors: Partial<{ [key in string]: string }>; errors: string[]; warnings: string[] } } | { key: "process.versionRequirements"; input: { template_id: string; params: Partial<{ [key in string]: string }> }; result: { java_required: boolean; java_major: number | null; minecraft_version: string | null; loader: string | null; runtime_java_major: number | null; java_satisfied: boolean; message: string } } | { key: "schedule.list"; input: { instance_id: string | null }; result: ({ id: string; node_id: string; instance_id: string; kind: string; cron: string; enabled: boolean; last_run_at: string | null; next_run_at: string | null; created_at: string })[] } | { key: "settings.nodeDefaultParams"; input: { node: string | null }; result: { node: string; params: Partial<{ [key in string]: string }> } } | { key: "settings.status"; input: null; result: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null } } | { key: "update.check"; input: null; result: { current_version: string; latest: UpdateLatestReleaseDto | null; update_available: boolean; can_trigger_update: boolean } }; mutations: { key: "apiToken.create"; input: { name: string; scopes: string[]; expires_in_days: number | null }; result: { token: string; info: ApiTokenDto } } | { key: "apiToken.revoke"; input: { id: string }; result: { ok: boolean } } | { key: "frp.create"; input: { name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string }; result: { id: string; name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string; latency_ms: number | null; created_at: string; updated_at: string } } | { key: "frp.delete"; input: { id: string }; result: { ok: boolean } } | { key: "frp.update"; input: { id: string; name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string }; result: { id: string; name: string; server_addr: string | null; server_port: number | null; allocatable_ports: string | null; token: string | null; config: string; latency_ms: number | null; created_at: string; updated_at: string } } | { key: "instance.abortMigration"; input: { instance_id: string }; result: { aborted: boolean } } | { key: "instance.assignNode"; input: { instance_id: string; node_id: string }; result: { instance_id: string; node: string } } | { key: "instance.bulkApplyParams"; input: { instance_ids: string[]; params: Partial<{ [key in string]: string }>; restart_running: boolean; stop_timeout_ms: number | null }; result: { results: BulkApplyParamsResultDto[] } } | { key: "instance.cancelStagedUpgrade"; input: { instance_id: string }; result: { instance_id: string; upgrade_id: string; from_version: string; target_version: string; run_at_unix_ms: string; warning_seconds: number; backup: boolean; phase: string; message: string; backup_id: string | null; updated_at_unix_ms: string } } | { key: "instance.create"; input: { template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null; node_id: string | null }; result: { instance_id: string; template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null } } | { key: "instance.createBackup"; input: { instance_id: string; format: string | null }; result: { backup_id: string; format: string; size_bytes: string; sha256: string; created_at_unix_ms: string } } | { key: "instance.delete"; input: { instance_id: string }; result: { ok: boolean } } | { key: "instance.diagnostics"; input: { instance_id: string; max_lines: number | null; limit_bytes: number | null }; result: { instance_id: string; fetched_at_unix_ms: string; request_id: string; instance_json: string | null; run_json: string | null; console_log_lines: string[] } } | { key: "instance.importSaveFromUrl"; input: { instance_id: string; url: string }; result: { ok: boolean; message: string; installed_path: string; backup_path: string } } | { key: "instance.migrate"; input: { instance_id: string; target_node: string }; result: { source_node: string; target_node: string; status: ProcessStatusDto | null } } | { key: "instance.reload"; input: { instance_id: string }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null } } | { key: "instance.restart"; input: { instance_id: string; timeout_ms: number | null }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null } } | { key: "instance.restoreBackup"; input: { instance_id: string; backup_id: string }; result: { ok: boolean; pre_restore_backup_id: string } } | { key: "instance.stageUpgrade"; input: { instance_id: string; target_version: string; run_at_unix_ms: string | null; warning_seconds: number | null; backup: boolean; stop_timeout_ms: number | null }; result: { instance_id: string; upgrade_id: string; from_version: string; target_version: string; run_at_unix_ms: string; warning_seconds: number; backup: boolean; phase: string; message: string; backup_id: string | null; updated_at_unix_ms: string } } | { key: "instance.start"; input: { instance_id: string }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null } } | { key: "instance.stop"; input: { instance_id: string; timeout_ms: number | null }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null } } | { key: "instance.update"; input: { instance_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null }; result: { instance_id: string; template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null } } | { key: "node.applyUpdate"; input: { node_id: string }; result: { adoptable_process_ids: string[]; native_process_ids: string[] } } | { key: "node.create"; input: { name: string }; result: { node: NodeDto; connect_token: string } } | { key: "node.prepareUpdate"; input: { node_id: string; stop_instances: boolean; timeout_ms: number | null; cancel: boolean }; result: { draining: boolean; ready: boolean; running_process_ids: string[]; busy_process_ids: string[] } } | { key: "node.rotateToken"; input: { node_id: string }; result: { node: NodeDto; connect_token: string } } | { key: "node.setEnabled"; input: { node_id: string; enabled: boolean }; result: { id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null; tags: string[]; starts_attempted: number; starts_succeeded: number; starts_failed: number; start_failure_rate_x100: number | null; last_start_success_at: string | null; last_start_failure_reason: string | null } } | { key: "node.setTags"; input: { node_id: string; tags: string[] }; result: { id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null; tags: string[]; starts_attempted: number; starts_succeeded: number; starts_failed: number; start_failure_rate_x100: number | null; last_start_success_at: string | null; last_start_failure_reason: string | null } } | { key: "process.clearCache"; input: { keys: string[] }; result: { ok: boolean; freed_bytes: string; cleared: CacheEntryDto[]; skipped: CacheEntryDto[] } } | { key: "process.downloadQueueCancelJob"; input: { job_id: string }; result: { ok: boolean } } | { key: "process.downloadQueueClearHistory"; input: null; result: { ok: boolean } } | { key: "process.downloadQueueEnqueue"; input: { target: string; template_id: string; version: string; params: Partial<{ [key in string]: string }>; priority: number | null }; result: { ok: boolean } } | { key: "process.downloadQueueMove"; input: { job_id: string; direction: number }; result: { ok: boolean } } | { key: "process.downloadQueueMoveToFront"; input: { job_id: string }; result: { ok: boolean } } | { key: "process.downloadQueuePauseJob"; input: { job_id: string }; result: { ok: boolean } } | { key: "process.downloadQueueResumeJob"; input: { job_id: string }; result: { ok: boolean } } | { key: "process.downloadQueueRetryJob"; input: { job_id: string }; result: { ok: boolean } } | { key: "process.downloadQueueSetPaused"; input: { paused: boolean }; result: { ok: boolean } } | { key: "process.downloadQueueSetPriority"; input: { job_id: string; priority: number }; result: { ok: boolean } } | { key: "process.reloadTemplates"; input: null; result: { loaded: string[]; errors: string[] } } | { key: "process.sendConsole"; input: { process_id: string; command: string }; result: { ok: boolean } } | { key: "process.start"; input: { template_id: string; params: Partial<{ [key in string]: string }> }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null } } | { key: "process.stop"; input: { process_id: string; timeout_ms: number | null }; result: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null } } | { key: "process.warmCache"; input: { template_id: string; params: Partial<{ [key in string]: string }> }; result: { ok: boolean; message: string } } | { key: "schedule.create"; input: { instance_id: string; kind: string; cron: string }; result: { id: string; node_id: string; instance_id: string; kind: string; cron: string; enabled: boolean; last_run_at: string | null; next_run_at: string | null; created_at: string } } | { key: "schedule.delete"; input: { id: string }; result: { ok: boolean } } | { key: "schedule.toggle"; input: { id: string; enabled: boolean }; result: { id: string; node_id: string; instance_id: string; kind: string; cron: string; enabled: boolean; last_run_at: string | null; next_run_at: string | null; created_at: string } } | { key: "settings.previewSteamGuard"; input: { mafile_or_secret: string }; result: SteamGuardPreview } | { key: "settings.setCurseforgeApiKey"; input: { key: string }; result: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null } } | { key: "settings.setDstDefaultKleiKey"; input: { key: string }; result: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null } } | { key: "settings.setNodeDefaultParams"; input: { node: string | null; params: Partial<{ [key in string]: string }> }; result: { node: string; params: Partial<{ [key in string]: string }> } } | { key: "settings.setSteamcmdCredentials"; input: { username: string; password: string; steam_guard_code: string | null; shared_secret: string | null; mafile_json: string | null }; result: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null } } | { key: "settings.testCurseforgeKey"; input: { key: string }; result: CurseforgeKeyCheck } | { key: "update.trigger"; input: null; result: { ok: boolean; message: string } }; subscriptions: never }
+
+export type ProcessResourcesDto = { cpu_percent_x100: number; cpu_percent_normalized_x100: number; rss_bytes: string; read_bytes: string; write_bytes: string }
 
-export type ProcessResourcesDto = { cpu_percent_x100: number; rss_bytes: string; read_bytes: string; write_bytes: string }
+export type ProcessStatusDto = { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null }
 
-export type ProcessStatusDto = { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null }
+export type ProgressInfoDto = { bytes_done: string; bytes_total: string; phase: string }
+
+export type ResourceSampleDto = { at_unix_ms: string; resources: ProcessResourcesDto }
+
+export type StagedUpgradeDto = { instance_id: string; upgrade_id: string; from_version: string; target_version: string; run_at_unix_ms: string; warning_seconds: number; backup: boolean; phase: string; message: string; backup_id: string | null; updated_at_unix_ms: string }
+
+
+export type SteamGuardPreview = { code: string; seconds_remaining: number; account_name: string | null }
 
 export type TemplateParamDto = { key: string; label: string; kind: ParamTypeDto; required: boolean; default_value: string; min_int: number | null; max_int: number | null; enum_values: string[]; secret: boolean; placeholder: string | null; help: string | null; advanced: boolean }
 
@@ -37,6 +60,14 @@ export type UpdateLatestReleaseDto = { tag: string; version: string | null; url:
 export type Procedures = {
 	agent: {
 	health: { kind: "query", input: null, output: { status: string; agent_version: string }, error: unknown },
+},
+	apiToken: {
+	create: { kind: "mutation", input: { name: string; scopes: string[]; expires_in_days: number | null }, output: { token: string; info: ApiTokenDto }, error: unknown },
+	list: { kind: "query", input: null, output: ({ id: string; name: string; scopes: string[]; expires_at: string | null; last_used_at: string | null; created_at: string })[], error: unknown },
+	revoke: { kind: "mutation", input: { id: string }, output: { ok: boolean }, error: unknown },
+},
+	audit: {
+	list: { kind: "query", input: { actor: string | null; action: string | null; instance_id: string | null; from_unix_ms: string | null; to_unix_ms: string | null; cursor: string | null; limit: number | null }, output: { events: AuditEventDto[]; next_cursor: string | null }, error: unknown },
 },
 	control: {
 	diagnostics: { kind: "query", input: null, output: { fetched_at_unix_ms: string; request_id: string; control_version: string; read_only: boolean; agent: AgentHealthFullDto; fs: FsCapabilitiesOutput; cache: CacheStatsOutput; agent_log_path: string | null; agent_log_lines: string[] }, error: unknown },
@@ -50,20 +81,33 @@ export type Procedures = {
 },
 	fs: {
 	capabilities: { kind: "query", input: null, output: { write_enabled: boolean }, error: unknown },
-	listDir: { kind: "query", input: { path: string | null }, output: { entries: DirEntryDto[] }, error: unknown },
+	listDir: { kind: "query", input: { path: string | null; recursive: boolean | null; max_entries: number | null; cursor: string | null }, output: { entries: DirEntryDto[]; next_cursor: string | null }, error: unknown },
 	readFile: { kind: "query", input: { path: string; offset: number | null; limit: number | null }, output: { text: string; size_bytes: number }, error: unknown },
 },
 	instance: {
-	create: { kind: "mutation", input: { template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null }, output: { instance_id: string; template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null }, error: unknown },
+	abortMigration: { kind: "mutation", input: { instance_id: string }, output: { aborted: boolean }, error: unknown },
+	assignNode: { kind: "mutation", input: { instance_id: string; node_id: string }, output: { instance_id: string; node: string }, error: unknown },
+	bulkApplyParams: { kind: "mutation", input: { instance_ids: string[]; params: Partial<{ [key in string]: string }>; restart_running: boolean; stop_timeout_ms: number | null }, output: { results: BulkApplyParamsResultDto[] }, error: unknown },
+	cancelStagedUpgrade: { kind: "mutation", input: { instance_id: string }, output: { instance_id: string; upgrade_id: string; from_version: string; target_version: string; run_at_unix_ms: string; warning_seconds: number; backup: boolean; phase: string; message: string; backup_id: string | null; updated_at_unix_ms: string }, error: unknown },
+	create: { kind: "mutation", input: { template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null; node_id: string | null }, output: { instance_id: string; template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null }, error: unknown },
+	createBackup: { kind: "mutation", input: { instance_id: string; format: string | null }, output: { backup_id: string; format: string; size_bytes: string; sha256: string; created_at_unix_ms: string }, error: unknown },
 	delete: { kind: "mutation", input: { instance_id: string }, output: { ok: boolean }, error: unknown },
 	deletePreview: { kind: "query", input: { instance_id: string }, output: { instance_id: string; path: string; size_bytes: string }, error: unknown },
 	diagnostics: { kind: "mutation", input: { instance_id: string; max_lines: number | null; limit_bytes: number | null }, output: { instance_id: string; fetched_at_unix_ms: string; request_id: string; instance_json: string | null; run_json: string | null; console_log_lines: string[] }, error: unknown },
+	diskUsage: { kind: "query", input: { instance_id: string }, output: { instance_id: string; total_bytes: string; world_bytes: string; logs_bytes: string; backups_bytes: string; cache_shared_bytes: string; truncated: boolean; free_bytes: string | null; min_free_bytes: string; below_min_free: boolean }, error: unknown },
 	get: { kind: "query", input: { instance_id: string }, output: { config: InstanceConfigDto; status: ProcessStatusDto | null }, error: unknown },
 	importSaveFromUrl: { kind: "mutation", input: { instance_id: string; url: string }, output: { ok: boolean; message: string; installed_path: string; backup_path: string }, error: unknown },
+	inspect: { kind: "query", input: { instance_id: string }, output: { process_id: string; template_id: string; started_at_unix_ms: string; agent_version: string; pid: number | null; container_name: string | null; container_id: string | null; exec: string; args: string[]; cwd: string; params: Partial<{ [key in string]: string }>; env: Partial<{ [key in string]: string }> }, error: unknown },
 	list: { kind: "query", input: null, output: ({ config: InstanceConfigDto; status: ProcessStatusDto | null })[], error: unknown },
-	restart: { kind: "mutation", input: { instance_id: string; timeout_ms: number | null }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null }, error: unknown },
-	start: { kind: "mutation", input: { instance_id: string }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null }, error: unknown },
-	stop: { kind: "mutation", input: { instance_id: string; timeout_ms: number | null }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null }, error: unknown },
+	listBackups: { kind: "query", input: { instance_id: string }, output: { backups: BackupRecordDto[] }, error: unknown },
+	migrate: { kind: "mutation", input: { instance_id: string; target_node: string }, output: { source_node: string; target_node: string; status: ProcessStatusDto | null }, error: unknown },
+	reload: { kind: "mutation", input: { instance_id: string }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null }, error: unknown },
+	restart: { kind: "mutation", input: { instance_id: string; timeout_ms: number | null }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null }, error: unknown },
+	restoreBackup: { kind: "mutation", input: { instance_id: string; backup_id: string }, output: { ok: boolean; pre_restore_backup_id: string }, error: unknown },
+	stageUpgrade: { kind: "mutation", input: { instance_id: string; target_version: string; run_at_unix_ms: string | null; warning_seconds: number | null; backup: boolean; stop_timeout_ms: number | null }, output: { instance_id: string; upgrade_id: string; from_version: string; target_version: string; run_at_unix_ms: string; warning_seconds: number; backup: boolean; phase: string; message: string; backup_id: string | null; updated_at_unix_ms: string }, error: unknown },
+	stagedUpgrade: { kind: "query", input: { instance_id: string }, output: { upgrade: StagedUpgradeDto | null }, error: unknown },
+	start: { kind: "mutation", input: { instance_id: string }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null }, error: unknown },
+	stop: { kind: "mutation", input: { instance_id: string; timeout_ms: number | null }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null }, error: unknown },
 	update: { kind: "mutation", input: { instance_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null }, output: { instance_id: string; template_id: string; params: Partial<{ [key in string]: string }>; display_name: string | null }, error: unknown },
 },
 	log: {
@@ -73,34 +117,56 @@ export type Procedures = {
 	versions: { kind: "query", input: null, output: { latest_release: string; latest_snapshot: string; versions: MinecraftVersionRef[] }, error: unknown },
 },
 	node: {
+	applyUpdate: { kind: "mutation", input: { node_id: string }, output: { adoptable_process_ids: string[]; native_process_ids: string[] }, error: unknown },
 	create: { kind: "mutation", input: { name: string }, output: { node: NodeDto; connect_token: string }, error: unknown },
-	list: { kind: "query", input: null, output: ({ id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null })[], error: unknown },
-	setEnabled: { kind: "mutation", input: { node_id: string; enabled: boolean }, output: { id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null }, error: unknown },
+	list: { kind: "query", input: null, output: ({ id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null; tags: string[]; starts_attempted: number; starts_succeeded: number; starts_failed: number; start_failure_rate_x100: number | null; last_start_success_at: string | null; last_start_failure_reason: string | null })[], error: unknown },
+	prepareUpdate: { kind: "mutation", input: { node_id: string; stop_instances: boolean; timeout_ms: number | null; cancel: boolean }, output: { draining: boolean; ready: boolean; running_process_ids: string[]; busy_process_ids: string[] }, error: unknown },
+	resources: { kind: "query", input: null, output: { nodes: NodeResourcesDto[]; unavailable: string[] }, error: unknown },
+	rotateToken: { kind: "mutation", input: { node_id: string }, output: { node: NodeDto; connect_token: string }, error: unknown },
+	setEnabled: { kind: "mutation", input: { node_id: string; enabled: boolean }, output: { id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null; tags: string[]; starts_attempted: number; starts_succeeded: number; starts_failed: number; start_failure_rate_x100: number | null; last_start_success_at: string | null; last_start_failure_reason: string | null }, error: unknown },
+	setTags: { kind: "mutation", input: { node_id: string; tags: string[] }, output: { id: string; name: string; endpoint: string; has_connect_token: boolean; enabled: boolean; last_seen_at: string | null; agent_version: string | null; last_error: string | null; tags: string[]; starts_attempted: number; starts_succeeded: number; starts_failed: number; start_failure_rate_x100: number | null; last_start_success_at: string | null; last_start_failure_reason: string | null }, error: unknown },
 },
 	process: {
-	cacheStats: { kind: "query", input: null, output: { entries: CacheEntryDto[] }, error: unknown },
-	clearCache: { kind: "mutation", input: { keys: string[] }, output: { ok: boolean; freed_bytes: string; cleared: CacheEntryDto[] }, error: unknown },
+	cacheStats: { kind: "query", input: null, output: { entries: CacheEntryDto[]; categories: CacheCategoryDto[]; max_bytes: string | null }, error: unknown },
+	clearCache: { kind: "mutation", input: { keys: string[] }, output: { ok: boolean; freed_bytes: string; cleared: CacheEntryDto[]; skipped: CacheEntryDto[] }, error: unknown },
 	downloadQueue: { kind: "query", input: null, output: { queue_paused: boolean; jobs: DownloadQueueJobDto[] }, error: unknown },
 	downloadQueueCancelJob: { kind: "mutation", input: { job_id: string }, output: { ok: boolean }, error: unknown },
 	downloadQueueClearHistory: { kind: "mutation", input: null, output: { ok: boolean }, error: unknown },
-	downloadQueueEnqueue: { kind: "mutation", input: { target: string; template_id: string; version: string; params: Partial<{ [key in string]: string }> }, output: { ok: boolean }, error: unknown },
+	downloadQueueEnqueue: { kind: "mutation", input: { target: string; template_id: string; version: string; params: Partial<{ [key in string]: string }>; priority: number | null }, output: { ok: boolean }, error: unknown },
 	downloadQueueMove: { kind: "mutation", input: { job_id: string; direction: number }, output: { ok: boolean }, error: unknown },
+	downloadQueueMoveToFront: { kind: "mutation", input: { job_id: string }, output: { ok: boolean }, error: unknown },
 	downloadQueuePauseJob: { kind: "mutation", input: { job_id: string }, output: { ok: boolean }, error: unknown },
 	downloadQueueResumeJob: { kind: "mutation", input: { job_id: string }, output: { ok: boolean }, error: unknown },
 	downloadQueueRetryJob: { kind: "mutation", input: { job_id: string }, output: { ok: boolean }, error: unknown },
 	downloadQueueSetPaused: { kind: "mutation", input: { paused: boolean }, output: { ok: boolean }, error: unknown },
-	list: { kind: "query", input: null, output: ({ process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null })[], error: unknown },
-	logsTail: { kind: "query", input: { process_id: string; cursor: string | null; limit: number | null }, output: { lines: string[]; next_cursor: string }, error: unknown },
-	start: { kind: "mutation", input: { template_id: string; params: Partial<{ [key in string]: string }> }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null }, error: unknown },
-	status: { kind: "query", input: { process_id: string }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null }, error: unknown },
-	stop: { kind: "mutation", input: { process_id: string; timeout_ms: number | null }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null }, error: unknown },
+	downloadQueueSetPriority: { kind: "mutation", input: { job_id: string; priority: number }, output: { ok: boolean }, error: unknown },
+	list: { kind: "query", input: null, output: ({ process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null })[], error: unknown },
+	logsTail: { kind: "query", input: { process_id: string; cursor: string | null; limit: number | null; streams: string[] | null; contains: string | null }, output: { lines: string[]; next_cursor: string }, error: unknown },
+	reloadTemplates: { kind: "mutation", input: null, output: { loaded: string[]; errors: string[] }, error: unknown },
+	resourceHistory: { kind: "query", input: { process_id: string; since_unix_ms: string | null }, output: { samples: ResourceSampleDto[]; sample_interval_ms: number }, error: unknown },
+	sendConsole: { kind: "mutation", input: { process_id: string; command: string }, output: { ok: boolean }, error: unknown },
+	start: { kind: "mutation", input: { template_id: string; params: Partial<{ [key in string]: string }> }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null }, error: unknown },
+	status: { kind: "query", input: { process_id: string }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null }, error: unknown },
+	stop: { kind: "mutation", input: { process_id: string; timeout_ms: number | null }, output: { process_id: string; template_id: string; state: string; pid: number | null; exit_code: number | null; message: string | null; resources: ProcessResourcesDto | null; fail_category: string | null; failure_reason: string | null; failure_hint: string | null; players_online: number | null; max_players: number | null; progress: ProgressInfoDto | null }, error: unknown },
 	templates: { kind: "query", input: null, output: { template_id: string; display_name: string; params: TemplateParamDto[] }[], error: unknown },
+	validateTemplate: { kind: "query", input: { template_id: string; params: Partial<{ [key in string]: string }> }, output: { ok: boolean; field_errors: Partial<{ [key in string]: string }>; errors: string[]; warnings: string[] }, error: unknown },
+	versionRequirements: { kind: "query", input: { template_id: string; params: Partial<{ [key in string]: string }> }, output: { java_required: boolean; java_major: number | null; minecraft_version: string | null; loader: string | null; runtime_java_major: number | null; java_satisfied: boolean; message: string }, error: unknown },
 	warmCache: { kind: "mutation", input: { template_id: string; params: Partial<{ [key in string]: string }> }, output: { ok: boolean; message: string }, error: unknown },
+},
+	schedule: {
+	create: { kind: "mutation", input: { instance_id: string; kind: string; cron: string }, output: { id: string; node_id: string; instance_id: string; kind: string; cron: string; enabled: boolean; last_run_at: string | null; next_run_at: string | null; created_at: string }, error: unknown },
+	delete: { kind: "mutation", input: { id: string }, output: { ok: boolean }, error: unknown },
+	list: { kind: "query", input: { instance_id: string | null }, output: ({ id: string; node_id: string; instance_id: string; kind: string; cron: string; enabled: boolean; last_run_at: string | null; next_run_at: string | null; created_at: string })[], error: unknown },
+	toggle: { kind: "mutation", input: { id: string; enabled: boolean }, output: { id: string; node_id: string; instance_id: string; kind: string; cron: string; enabled: boolean; last_run_at: string | null; next_run_at: string | null; created_at: string }, error: unknown },
 },
 	settings: {
+	nodeDefaultParams: { kind: "query", input: { node: string | null }, output: { node: string; params: Partial<{ [key in string]: string }> }, error: unknown },
+	previewSteamGuard: { kind: "mutation", input: { mafile_or_secret: string }, output: SteamGuardPreview, error: unknown },
 	setCurseforgeApiKey: { kind: "mutation", input: { key: string }, output: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null }, error: unknown },
 	setDstDefaultKleiKey: { kind: "mutation", input: { key: string }, output: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null }, error: unknown },
+	setNodeDefaultParams: { kind: "mutation", input: { node: string | null; params: Partial<{ [key in string]: string }> }, output: { node: string; params: Partial<{ [key in string]: string }> }, error: unknown },
 	setSteamcmdCredentials: { kind: "mutation", input: { username: string; password: string; steam_guard_code: string | null; shared_secret: string | null; mafile_json: string | null }, output: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null }, error: unknown },
+	testCurseforgeKey: { kind: "mutation", input: { key: string }, output: CurseforgeKeyCheck, error: unknown },
 	status: { kind: "query", input: null, output: { dst_default_klei_key_set: boolean; curseforge_api_key_set: boolean; steamcmd_username_set: boolean; steamcmd_password_set: boolean; steamcmd_shared_secret_set: boolean; steamcmd_account_name: string | null }, error: unknown },
 },
 	update: {
@@ -200,7 +200,7 @@ export default function ControlDiagnosticsModal(props: ControlDiagnosticsModalPr
                                 }
                                 try {
                                   const out = await clearCache.mutateAsync({ keys })
-                                  pushToast('success', 'Cache cleared', `Freed ${formatBytes(Number(out.freed_bytes))}`)
+                                  pushToast('success', 'Cache cleared', `Freed ${formatBytes(Number(out.freed_bytes))}${out.skipped.length ? ` (${out.skipped.length} in use, kept)` : ''}`)
                                   await queryClient.invalidateQueries({ queryKey: ['control.diagnostics', null] })
                                   await queryClient.invalidateQueries({ queryKey: ['process.cacheStats', null] })
                                 } catch (e) {
@@ -240,7 +240,7 @@ export function FileBrowser(props: FileBrowserProps) {
   }
 
   const fsList = rspc.createQuery(
-    () => ['fs.listDir', { path: path() ? path() : null }],
+    () => ['fs.listDir', { path: path() ? path() : null, recursive: null, max_entries: null, cursor: null }],
     () => ({ enabled: props.enabled, refetchOnWindowFocus: false, staleTime: 0 }),
   )
 
@@ -470,6 +470,17 @@ export function FileBrowser(props: FileBrowserProps) {
                   />
                 </svg>
               </IconButton>
+              <IconButton
+                type="button"
+                label="Download folder as zip"
+                variant="ghost"
+                onClick={() => window.open(`/fs/download?path=${encodeURIComponent(path())}`, '_blank')}
+              >
+                <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20" fill="currentColor" class="h-4 w-4">
+                  <path d="M10.75 2.75a.75.75 0 00-1.5 0v8.614L6.295 8.235a.75.75 0 10-1.09 1.03l4.25 4.5a.75.75 0 001.09 0l4.25-4.5a.75.75 0 00-1.09-1.03l-2.955 3.129V2.75z" />
+                  <path d="M3.5 12.75a.75.75 0 00-1.5 0v2.5A2.75 2.75 0 004.75 18h10.5A2.75 2.75 0 0018 15.25v-2.5a.75.75 0 00-1.5 0v2.5c0 .69-.56 1.25-1.25 1.25H4.75c-.69 0-1.25-.56-1.25-1.25v-2.5z" />
+                </svg>
+              </IconButton>
               <IconButton type="button" label="Refresh" variant="ghost" onClick={() => fsList.refetch()}>
                 <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20" fill="currentColor" class="h-4 w-4">
                   <path
@@ -248,7 +248,7 @@ export default function InstanceDetailsModal(props: InstanceDetailsModalProps) {
                         </div>
 
                         <Show when={status()?.state === 'PROCESS_STATE_STARTING' ? status()?.message?.trim() : null}>
-                          {(msg) => <StartProgress templateId={inst().config.template_id} message={msg()} />}
+                          {(msg) => <StartProgress templateId={inst().config.template_id} message={msg()} progress={status()?.progress} />}
                         </Show>
                       </div>
 
@@ -508,8 +508,8 @@ export default function InstanceDetailsModal(props: InstanceDetailsModalProps) {
                           >
                             {(r) => (
                               <div class="mt-3 flex flex-wrap items-center gap-2 text-[11px] text-slate-500">
-                                <span class="rounded-full border border-slate-200 bg-white/60 px-2 py-0.5 font-mono dark:border-slate-800 dark:bg-slate-950/40">
-                                  cpu {formatCpuPercent(r().cpu_percent_x100)}
+                                <span class="rounded-full border border-slate-200 bg-white/60 px-2 py-0.5 font-mono dark:border-slate-800 dark:bg-slate-950/40" title={`${formatCpuPercent(r().cpu_percent_x100)} of one core`}>
+                                  cpu {formatCpuPercent(r().cpu_percent_normalized_x100)}
                                 </span>
                                 <span class="rounded-full border border-slate-200 bg-white/60 px-2 py-0.5 font-mono dark:border-slate-800 dark:bg-slate-950/40">
                                   rss {formatBytes(parseU64(r().rss_bytes))}
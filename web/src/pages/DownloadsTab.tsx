@@ -63,6 +63,7 @@ export default function DownloadsTab(props: DownloadsTabProps) {
     toggleDownloadQueuePaused,
     clearDownloadHistory,
     moveDownloadJob,
+    moveDownloadJobToFront,
     pauseDownloadJob,
     resumeDownloadJob,
     cancelDownloadJob,
@@ -170,7 +171,7 @@ export default function DownloadsTab(props: DownloadsTabProps) {
     try {
       setDeletingKey(key)
       const out = await clearCache.mutateAsync({ keys: [key] })
-      pushToast('success', 'Deleted', `Freed ${formatBytes(Number(out.freed_bytes))}`)
+      pushToast('success', 'Deleted', `Freed ${formatBytes(Number(out.freed_bytes))}${out.skipped.length ? ` (${out.skipped.length} in use, kept)` : ''}`)
       await queryClient.invalidateQueries({ queryKey: ['control.diagnostics', null] })
       await queryClient.invalidateQueries({ queryKey: ['process.cacheStats', null] })
     } catch (e) {
@@ -427,6 +428,7 @@ export default function DownloadsTab(props: DownloadsTabProps) {
                               nowUnixMs={downloadNowUnixMs}
                               canReorder
                               onMoveJob={moveDownloadJob}
+                              onMoveJobToFront={moveDownloadJobToFront}
                               onPauseJob={pauseDownloadJob}
                               onResumeJob={resumeDownloadJob}
                               onCancelJob={cancelDownloadJob}
@@ -452,6 +454,7 @@ export default function DownloadsTab(props: DownloadsTabProps) {
                               nowUnixMs={downloadNowUnixMs}
                               compact
                               onMoveJob={moveDownloadJob}
+                              onMoveJobToFront={moveDownloadJobToFront}
                               onPauseJob={pauseDownloadJob}
                               onResumeJob={resumeDownloadJob}
                               onCancelJob={cancelDownloadJob}
@@ -23,6 +23,8 @@ export default function InstancesCreatePanel(props: InstancesCreatePanelProps) {
     createFieldErrors,
     createFormError,
     createInstance,
+    createNodeId,
+    createNodeOptions,
     createPreview,
     createTemplateId,
     dstAuthPort,
@@ -54,6 +56,7 @@ export default function InstancesCreatePanel(props: InstancesCreatePanelProps) {
     setCreateFormError,
     setCreateInstanceNameEl,
     setCreateInstanceNameRef,
+    setCreateNodeId,
     setCreateSleepSecondsEl,
     setInstanceName,
     setSelectedInstanceId,
@@ -105,6 +108,17 @@ export default function InstancesCreatePanel(props: InstancesCreatePanelProps) {
                         />
                       </Field>
 
+                      <Show when={createNodeOptions().length > 2}>
+                        <Field label="Node">
+                          <Dropdown
+                            label=""
+                            value={createNodeId()}
+                            options={createNodeOptions()}
+                            onChange={setCreateNodeId}
+                          />
+                        </Field>
+                      </Show>
+
 	                      <Show when={selectedTemplate() === 'demo:sleep'}>
 	                        <Field
 	                          label="Seconds"
@@ -230,7 +244,7 @@ export default function InstancesCreatePanel(props: InstancesCreatePanelProps) {
                               if (mcFrpEnabled() && mcFrpCfg) params.frp_config = mcFrpCfg
                             } else if (template_id === 'minecraft:modrinth') {
                               if (!mcEula()) localErrors.accept_eula = 'You must accept the EULA to start a Minecraft server.'
-                              if (!mcMrpack().trim()) localErrors.mrpack = 'Paste a Modrinth version link or a direct .mrpack URL.'
+                              if (!mcMrpack().trim()) localErrors.mrpack = 'Paste a Modrinth modpack link, slug, or a direct .mrpack URL.'
                               if (mcFrpEnabled() && !mcFrpCfg)
                                 localErrors.frp_config = mcFrpMode() === 'node' ? 'Select an FRP node.' : 'Paste FRP config.'
                               params.accept_eula = 'true'
@@ -250,7 +264,7 @@ export default function InstancesCreatePanel(props: InstancesCreatePanelProps) {
                               if (mcFrpEnabled() && mcFrpCfg) params.frp_config = mcFrpCfg
                             } else if (template_id === 'minecraft:curseforge') {
                               if (!mcEula()) localErrors.accept_eula = 'You must accept the EULA to start a Minecraft server.'
-                              if (!mcCurseforge().trim()) localErrors.curseforge = 'Paste a CurseForge file URL, or modId:fileId.'
+                              if (!mcCurseforge().trim()) localErrors.curseforge = 'Paste a CurseForge modpack URL, slug, or modId:fileId.'
                               if (mcFrpEnabled() && !mcFrpCfg)
                                 localErrors.frp_config = mcFrpMode() === 'node' ? 'Select an FRP node.' : 'Paste FRP config.'
                               params.accept_eula = 'true'
@@ -289,7 +303,12 @@ export default function InstancesCreatePanel(props: InstancesCreatePanelProps) {
                             }
 
                             try {
-                              const out = await createInstance.mutateAsync({ template_id, params, display_name })
+                              const out = await createInstance.mutateAsync({
+                                template_id,
+                                params,
+                                display_name,
+                                node_id: createNodeId() || null,
+                              })
                               pushToast('success', 'Instance created', display_name ?? undefined)
                               await invalidateInstances()
                               revealInstance(out.instance_id)
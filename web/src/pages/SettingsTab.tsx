@@ -32,6 +32,7 @@ export default function SettingsTab(props: SettingsTabProps) {
     setSettingsCurseforgeKey,
     setSettingsCurseforgeKeyVisible,
     setCurseforgeApiKey,
+    testCurseforgeKey,
     settingsSteamcmdUsername,
     setSettingsSteamcmdUsername,
     settingsSteamcmdPasswordVisible,
@@ -43,6 +44,7 @@ export default function SettingsTab(props: SettingsTabProps) {
     settingsSteamcmdMaFile,
     setSettingsSteamcmdMaFile,
     setSteamcmdCredentials,
+    previewSteamGuard,
     updateCheck,
     triggerUpdate,
     controlDiagnostics,
@@ -188,6 +190,22 @@ export default function SettingsTab(props: SettingsTabProps) {
                           >
                             Save
                           </Button>
+                          <Button
+                            size="sm"
+                            variant="secondary"
+                            loading={testCurseforgeKey.isPending}
+                            disabled={!settingsCurseforgeKey().trim() && !settingsStatus.data?.curseforge_api_key_set}
+                            onClick={async () => {
+                              try {
+                                const res = await testCurseforgeKey.mutateAsync({ key: settingsCurseforgeKey() })
+                                pushToast(res.ok ? 'success' : 'error', res.ok ? 'Key works' : 'Key check failed', res.detail)
+                              } catch (e) {
+                                toastError('Key check failed', e)
+                              }
+                            }}
+                          >
+                            Test
+                          </Button>
                           <Button
                             size="sm"
                             variant="secondary"
@@ -319,6 +337,21 @@ export default function SettingsTab(props: SettingsTabProps) {
                               >
                                 Clear maFile
                               </Button>
+                              <Button
+                                size="xs"
+                                variant="secondary"
+                                loading={previewSteamGuard.isPending}
+                                onClick={async () => {
+                                  try {
+                                    const res = await previewSteamGuard.mutateAsync({ mafile_or_secret: settingsSteamcmdMaFile() })
+                                    pushToast('success', `Steam Guard code ${res.code}`, `Valid for ${res.seconds_remaining}s`)
+                                  } catch (e) {
+                                    toastError('maFile check failed', e)
+                                  }
+                                }}
+                              >
+                                Preview code
+                              </Button>
                             </Show>
                           </div>
                         </div>
@@ -1,5 +1,5 @@
 import { For, Show, createMemo } from 'solid-js'
-import { ArrowDown, ArrowUp, Download, Pause, Play, RotateCw, Search, Trash2, X } from 'lucide-solid'
+import { ArrowDown, ArrowUp, ArrowUpToLine, Download, Pause, Play, RotateCw, Search, Trash2, X } from 'lucide-solid'
 import type { DownloadJob } from '../../app/types'
 import {
   downloadJobPercent,
@@ -41,6 +41,7 @@ export function JobRow(props: {
   compact?: boolean
   canReorder?: boolean
   onMoveJob: (id: string, delta: number) => void
+  onMoveJobToFront: (id: string) => void
   onPauseJob: (id: string) => void
   onResumeJob: (id: string) => void
   onCancelJob: (id: string) => void
@@ -169,6 +170,9 @@ export function JobRow(props: {
 
         <Show when={showReorder()}>
           <>
+            <IconButton label="Move to front" variant="ghost" onClick={() => void props.onMoveJobToFront(props.job.id)}>
+              <ArrowUpToLine class="h-4 w-4" aria-hidden="true" />
+            </IconButton>
             <IconButton label="Move up" variant="ghost" onClick={() => void props.onMoveJob(props.job.id, -1)}>
               <ArrowUp class="h-4 w-4" aria-hidden="true" />
             </IconButton>
@@ -188,7 +192,7 @@ export function JobRow(props: {
             <Play class="h-4 w-4" aria-hidden="true" />
           </IconButton>
         </Show>
-        <Show when={props.job.state === 'queued' || props.job.state === 'paused'}>
+        <Show when={props.job.state === 'queued' || props.job.state === 'paused' || props.job.state === 'running'}>
           <IconButton label="Cancel" variant="danger" onClick={() => void props.onCancelJob(props.job.id)}>
             <X class="h-4 w-4" aria-hidden="true" />
           </IconButton>
@@ -154,7 +154,7 @@ export default function MinecraftCreateSection(props: MinecraftCreateSectionProp
                               label={
                                 <LabelTip
                                   label="Modpack (mrpack)"
-                                  content="Paste a Modrinth version link (recommended) or a direct .mrpack URL."
+                                  content="Paste a Modrinth version link (recommended), a modpack link or slug for its latest version, or a direct .mrpack URL."
                                 />
                               }
                               required
@@ -202,7 +202,7 @@ export default function MinecraftCreateSection(props: MinecraftCreateSectionProp
                               label={
                                 <LabelTip
                                   label="Modpack file"
-                                  content="Paste a CurseForge file URL (recommended), or modId:fileId. Alloy will prefer the author's server pack when available."
+                                  content="Paste a CurseForge modpack URL (add /files/<id> to pin a file), a slug, or modId:fileId. Alloy will prefer the author's server pack when available."
                                 />
                               }
                               required
@@ -214,7 +214,7 @@ export default function MinecraftCreateSection(props: MinecraftCreateSectionProp
                                 }}
                                 value={mcCurseforge()}
                                 onInput={(e) => setMcCurseforge(e.currentTarget.value)}
-                                placeholder="https://www.curseforge.com/minecraft/modpacks/.../files/..."
+                                placeholder="https://www.curseforge.com/minecraft/modpacks/..."
                                 spellcheck={false}
                                 invalid={Boolean(createFieldErrors().curseforge)}
                               />
@@ -126,7 +126,7 @@ export default function InstanceCard(props: InstanceCardProps) {
         {(r) => (
           <div class="relative z-10 mt-3 flex flex-wrap items-center gap-2 text-[11px] text-slate-500">
             <span class="rounded-full border border-slate-200 bg-white/60 px-2 py-0.5 font-mono dark:border-slate-800 dark:bg-slate-950/40">
-              cpu {formatCpuPercent(r().cpu_percent_x100)}
+              cpu {formatCpuPercent(r().cpu_percent_normalized_x100)}
             </span>
             <span class="rounded-full border border-slate-200 bg-white/60 px-2 py-0.5 font-mono dark:border-slate-800 dark:bg-slate-950/40">
               rss {formatBytes(parseU64(r().rss_bytes))}
@@ -116,7 +116,7 @@ export default function InstanceCardHeader(props: InstanceCardHeaderProps) {
                                       </Show>
                                     </div>
                                     <Show when={i.status?.state === 'PROCESS_STATE_STARTING' ? i.status?.message?.trim() : null}>
-                                      {(msg) => <StartProgress templateId={i.config.template_id} message={msg()} />}
+                                      {(msg) => <StartProgress templateId={i.config.template_id} message={msg()} progress={i.status?.progress} />}
                                     </Show>
                                   </div>
                                   <div class="mt-0.5 flex flex-none items-center gap-2">
@@ -218,6 +218,10 @@ class AlloyFetchTransport {
         throw new AlloyApiError({ code: 'http_error', message: msg, request_id: requestId, hint: null })
       }
     })()) as unknown
+    // Resolver and auth errors arrive as a bare ApiError body with a 4xx/5xx status.
+    if (!resp.ok && isPlainObject(respBody) && !('result' in respBody) && typeof respBody.message === 'string') {
+      throw this.normalizeRspcError(respBody, { key, operation, httpStatus: resp.status, requestId })
+    }
     if (!isPlainObject(respBody) || !isPlainObject(respBody.result)) {
       throw new AlloyApiError({
         code: 'invalid_rspc_response',
@@ -16,6 +16,11 @@ export default defineConfig({
         target: 'http://localhost:8080',
         changeOrigin: true,
       },
+      // Streamed zip downloads.
+      '/fs': {
+        target: 'http://localhost:8080',
+        changeOrigin: true,
+      },
     },
   },
 })